@@ -1,27 +1,211 @@
 //! Benchmarking command implementation for µDCN CLI
 
 use anyhow::{Context, Result};
+use bytes::Bytes;
+use clap::ValueEnum;
 use futures::future::join_all;
-use log::{debug, info, warn};
-use rust_udcn_common::ndn::{Interest, Name};
-use rust_udcn_quic::{ClientOptions, NdnQuicTransport, TransportConfig, TransportMode};
-use std::{sync::Arc, time::{Duration, Instant}};
-use tokio::{sync::Mutex, time::timeout};
+use log::{debug, info};
+use rust_udcn_common::{
+    metrics::{Counter, Histogram, HistogramSnapshot, Meter},
+    ndn::{Data, Interest, NackReason, Name},
+};
+use rust_udcn_quic::{
+    ClientOptions, Face, FaceError, FaceEvent, FetchOptions, NdnFace, NdnQuicClient, NdnQuicServer, NdnQuicTransport,
+    SegmentFetcher, ServerOptions, TransportConfig, TransportMode,
+};
+use serde::Serialize;
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    signal,
+    sync::{broadcast::error::RecvError, Mutex},
+    time::{interval, sleep, timeout, Interval, MissedTickBehavior},
+};
+
+use crate::{
+    output::{print_fields, print_json, print_row, OutputFormat},
+    utils::{format_bytes, write_self_signed_certificate},
+};
+
+/// Time to wait for each Data
+const BENCHMARK_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// Component after the prefix naming the synthetic objects of the
+/// throughput benchmark, `<prefix>/bulk/<object size>/<segment size>/<run>`
+const BULK_COMPONENT: &[u8] = b"bulk";
+
+/// Format of the exported results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One row per Interest, for plotting the latency over time
+    Csv,
+
+    /// The summary and the latency histogram
+    Json,
+}
+
+/// Parameters of a benchmark run
+#[derive(Debug, Clone)]
+pub struct BenchmarkOptions {
+    /// Interests measured
+    pub count: usize,
+
+    /// Name prefix of the Interests
+    pub prefix: String,
+
+    /// Interests outstanding at once
+    pub concurrent: usize,
+
+    /// Interests sent per second at most, as fast as possible if not given
+    pub rate: Option<f64>,
+
+    /// Interests sent before measuring, to fill caches and the window
+    pub warmup: usize,
+
+    /// Where to export the results
+    pub export: Option<PathBuf>,
+
+    /// Format of the exported results
+    pub export_format: ExportFormat,
+
+    /// How to print the results
+    pub output: OutputFormat,
+}
+
+/// Parameters of a bulk transfer benchmark
+#[derive(Debug, Clone)]
+pub struct ThroughputOptions {
+    /// Name prefix the responder answers
+    pub prefix: String,
+
+    /// Size of the object fetched in each run
+    pub object_size: usize,
+
+    /// Segment sizes measured
+    pub segment_sizes: Vec<usize>,
+
+    /// Pipeline windows measured, in Interests
+    pub windows: Vec<usize>,
+
+    /// Where to export the results
+    pub export: Option<PathBuf>,
+
+    /// Format of the exported results
+    pub export_format: ExportFormat,
+
+    /// How to print the results
+    pub output: OutputFormat,
+}
+
+/// One fetch of the throughput benchmark
+#[derive(Debug, Clone, Serialize)]
+struct ThroughputRun {
+    segment_size: usize,
+    window: usize,
+    segments: u64,
+    bytes: u64,
+    elapsed_ms: f64,
+    /// Content bits per second
+    goodput_bps: f64,
+    retransmissions: u64,
+    srtt_ms: f64,
+    /// Why the fetch failed, if it did
+    error: Option<String>,
+}
+
+/// Parameters of the benchmark responder
+#[derive(Debug, Clone)]
+pub struct ResponderOptions {
+    /// Name prefix answered
+    pub prefix: String,
+
+    /// Size of the content of each Data
+    pub payload_size: usize,
+
+    /// Time to wait before answering each Interest
+    pub delay: Duration,
+
+    /// Address to listen on
+    pub listen_addr: String,
+}
+
+/// What became of one measured Interest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Data,
+    Failed,
+    Timeout,
+}
+
+/// One measured Interest
+#[derive(Debug, Clone)]
+struct Sample {
+    /// Sequence number in the name, counting the warmup
+    seq: usize,
+
+    /// When it was sent, since the measurement started
+    sent_at_us: u64,
+
+    /// Time to the Data, if it came
+    rtt_us: Option<u64>,
+
+    outcome: Outcome,
+}
+
+/// Summary of a run, as exported
+#[derive(Debug, Clone, Serialize)]
+struct BenchmarkReport {
+    prefix: String,
+    count: usize,
+    concurrent: usize,
+    target_rate: Option<f64>,
+    warmup: usize,
+    successful: usize,
+    failed: usize,
+    timeouts: usize,
+    elapsed_ms: f64,
+    /// Interests answered per second
+    throughput: f64,
+    /// Interests sent per second
+    send_rate: f64,
+    rtt_min_us: Option<u64>,
+    rtt_max_us: Option<u64>,
+    rtt_avg_us: f64,
+    rtt_p50_us: f64,
+    rtt_p90_us: f64,
+    rtt_p99_us: f64,
+    rtt_p999_us: f64,
+    /// Latency distribution in microseconds
+    rtt_histogram: HistogramSnapshot,
+}
 
 /// Run the benchmark with the specified parameters
-pub async fn run_benchmark(count: usize, prefix: String, concurrent: usize) -> Result<()> {
-    info!(
-        "Running benchmark: count={}, prefix={}, concurrent={}",
-        count, prefix, concurrent
-    );
+pub async fn run_benchmark(options: BenchmarkOptions) -> Result<()> {
+    info!("Running benchmark: {:?}", options);
+    let verbose = options.output.is_table();
 
-    println!("Starting µDCN benchmark");
-    println!("======================");
-    println!("Parameters:");
-    println!("  Interest count: {}", count);
-    println!("  Name prefix: {}", prefix);
-    println!("  Concurrent requests: {}", concurrent);
-    println!();
+    if verbose {
+        println!("Starting µDCN benchmark");
+        println!("======================");
+        println!("Parameters:");
+        println!("  Interest count: {}", options.count);
+        println!("  Name prefix: {}", options.prefix);
+        println!("  Concurrent requests: {}", options.concurrent);
+        match options.rate {
+            Some(rate) => println!("  Target rate: {} Interests/s", rate),
+            None => println!("  Target rate: unlimited"),
+        }
+        println!("  Warmup Interests: {}", options.warmup);
+        println!();
+    }
 
     // Configure the QUIC transport
     let config = TransportConfig {
@@ -30,7 +214,7 @@ pub async fn run_benchmark(count: usize, prefix: String, concurrent: usize) -> R
             verify_certificate: false, // For testing only
             ..Default::default()
         },
-        interest_timeout_ms: 4000, // 4 seconds timeout
+        interest_timeout_ms: BENCHMARK_TIMEOUT.as_millis() as u64,
         ..Default::default()
     };
 
@@ -39,116 +223,144 @@ pub async fn run_benchmark(count: usize, prefix: String, concurrent: usize) -> R
         .context("Failed to create QUIC transport")?);
 
     // Connect to the NDN router (localhost in this case)
-    println!("Connecting to localhost:6367...");
+    if verbose {
+        println!("Connecting to localhost:6367...");
+    }
     let face = transport.connect(("localhost", 6367)).await
         .context("Failed to connect to NDN router")?;
-    println!("Connected to NDN router.");
+    if verbose {
+        println!("Connected to NDN router.");
+    }
+    let face_id = face.id().to_string();
 
-    // Prepare for the benchmark
-    let start_time = Instant::now();
-    let results = Arc::new(Mutex::new(BenchmarkResults {
-        total_interests: count,
-        successful_requests: 0,
-        failed_requests: 0,
-        timeouts: 0,
-        total_time_ms: 0,
-        min_rtt_ms: u64::MAX,
-        max_rtt_ms: 0,
-        total_rtt_ms: 0,
-    }));
-
-    println!("\nRunning benchmark...");
-
-    // Split the interests into batches
-    let batch_size = std::cmp::max(1, count / concurrent);
-    let mut tasks = Vec::new();
-
-    for batch_index in 0..concurrent {
-        let start_index = batch_index * batch_size;
-        let end_index = if batch_index == concurrent - 1 {
-            count
-        } else {
-            start_index + batch_size
-        };
+    let prefix = Name::from_string(&options.prefix)?;
+    let concurrent = options.concurrent.max(1);
+    let pacer = options.rate.map(|rate| Arc::new(Mutex::new(pacer(rate))));
 
-        if start_index >= count {
-            break;
+    if options.warmup > 0 {
+        if verbose {
+            println!("\nWarming up...");
         }
-
-        // Clone references for the task
-        let transport_clone = Arc::clone(&transport);
-        let face_id = face.id().to_string();
-        let prefix_clone = prefix.clone();
-        let results_clone = Arc::clone(&results);
-
-        // Create a task to run this batch of interests
-        let task = tokio::spawn(async move {
-            for i in start_index..end_index {
-                // Create a unique name for this interest
-                let name_str = format!("{}/{}", prefix_clone, i);
-                let name = Name::from_string(&name_str)
-                    .expect("failed to parse benchmark name");
-
-                // Create an interest
-                let mut interest = Interest::new(name);
-                interest.set_can_be_prefix(false);
-                interest.set_must_be_fresh(true);
-
-                // Start measuring time
-                let request_start = Instant::now();
-
-                // Send the interest and wait for data with a timeout
-                let result = timeout(
-                    Duration::from_millis(4000), // 4 seconds timeout
-                    transport_clone.express_interest(interest, Some(&face_id), None)
-                ).await;
-
-                // Calculate RTT
-                let rtt_ms = request_start.elapsed().as_millis() as u64;
-
-                // Update results
-                let mut results = results_clone.lock().await;
-                match result {
-                    Ok(Ok(_)) => {
-                        results.successful_requests += 1;
-                        results.min_rtt_ms = results.min_rtt_ms.min(rtt_ms);
-                        results.max_rtt_ms = results.max_rtt_ms.max(rtt_ms);
-                        results.total_rtt_ms += rtt_ms;
-                    }
-                    Ok(Err(_)) => {
-                        results.failed_requests += 1;
+        let next = Arc::new(AtomicUsize::new(0));
+        let warmup = options.warmup;
+        let workers = (0..concurrent).map(|_| {
+            let (transport, face_id, prefix, next, pacer) =
+                (Arc::clone(&transport), face_id.clone(), prefix.clone(), Arc::clone(&next), pacer.clone());
+            tokio::spawn(async move {
+                loop {
+                    let seq = next.fetch_add(1, Ordering::Relaxed);
+                    if seq >= warmup {
+                        break;
                     }
-                    Err(_) => {
-                        results.timeouts += 1;
+                    if let Some(pacer) = &pacer {
+                        pacer.lock().await.tick().await;
                     }
+                    let _ = send(&transport, &face_id, &prefix, seq).await;
                 }
-
-                // Update progress if this is the first batch
-                if batch_index == 0 && (i - start_index) % 10 == 0 {
-                    let progress = (i - start_index + 1) as f64 / (end_index - start_index) as f64;
-                    print!("\rProgress: {:.1}%", progress * 100.0);
-                    std::io::Write::flush(&mut std::io::stdout()).unwrap();
-                }
-            }
+            })
         });
+        join_all(workers).await;
+    }
 
-        tasks.push(task);
+    if verbose {
+        println!("\nRunning benchmark...");
     }
 
-    // Wait for all tasks to complete
-    join_all(tasks).await;
+    // Every worker takes the next sequence number until all are sent
+    let histogram = Arc::new(Histogram::exponential(10, 10_000_000, 64));
+    let satisfied = Arc::new(Meter::new());
+    let received_bytes = Arc::new(Meter::new());
+    let samples = Arc::new(Mutex::new(Vec::with_capacity(options.count)));
+    let next = Arc::new(AtomicUsize::new(0));
+    let start_time = Instant::now();
+
+    let mut workers = Vec::new();
+    for _ in 0..concurrent {
+        let (transport, face_id, prefix, next, pacer) =
+            (Arc::clone(&transport), face_id.clone(), prefix.clone(), Arc::clone(&next), pacer.clone());
+        let (histogram, satisfied, received_bytes, samples) =
+            (Arc::clone(&histogram), Arc::clone(&satisfied), Arc::clone(&received_bytes), Arc::clone(&samples));
+        let (count, warmup) = (options.count, options.warmup);
+
+        workers.push(tokio::spawn(async move {
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= count {
+                    break;
+                }
+                if let Some(pacer) = &pacer {
+                    pacer.lock().await.tick().await;
+                }
 
-    // Calculate total time
-    let total_time_ms = start_time.elapsed().as_millis() as u64;
+                let seq = warmup + index;
+                let sent_at = start_time.elapsed();
+                let request_start = Instant::now();
+                let result = send(&transport, &face_id, &prefix, seq).await;
+                let rtt = request_start.elapsed();
 
-    // Update final results
-    {
-        let mut results = results.lock().await;
-        results.total_time_ms = total_time_ms;
+                let (outcome, rtt_us) = match result {
+                    Ok(Ok(bytes)) => {
+                        let rtt_us = rtt.as_micros() as u64;
+                        histogram.observe(rtt_us);
+                        satisfied.mark(1);
+                        received_bytes.mark(bytes as u64);
+                        (Outcome::Data, Some(rtt_us))
+                    }
+                    Ok(Err(FaceError::Timeout)) | Err(_) => (Outcome::Timeout, None),
+                    Ok(Err(_)) => (Outcome::Failed, None),
+                };
+                samples.lock().await.push(Sample {
+                    seq,
+                    sent_at_us: sent_at.as_micros() as u64,
+                    rtt_us,
+                    outcome,
+                });
+            }
+        }));
     }
 
-    // Print results
-    print_benchmark_results(results.lock().await.clone()).await;
+    // Report progress until the workers are done
+    let progress = verbose.then(|| {
+        let (satisfied, received_bytes, next) = (Arc::clone(&satisfied), Arc::clone(&received_bytes), Arc::clone(&next));
+        let count = options.count;
+        tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_millis(500));
+            loop {
+                ticker.tick().await;
+                let sent = next.load(Ordering::Relaxed).min(count);
+                print!("\rProgress: {:.1}% ({:.0} Interests/s, {}/s)   ",
+                    sent as f64 / count.max(1) as f64 * 100.0,
+                    satisfied.rate(Duration::from_secs(1)),
+                    format_bytes(received_bytes.rate(Duration::from_secs(1)) as usize));
+                let _ = std::io::stdout().flush();
+            }
+        })
+    });
+    join_all(workers).await;
+    if let Some(progress) = progress {
+        progress.abort();
+    }
+    let elapsed = start_time.elapsed();
+
+    let mut samples = std::mem::take(&mut *samples.lock().await);
+    samples.sort_by_key(|sample| sample.seq);
+    let report = report(&options, &samples, &histogram.snapshot(), elapsed);
+    match options.output {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Plain => print_fields(&report)?,
+        OutputFormat::Table => print_benchmark_results(&report),
+    }
+
+    if let Some(path) = &options.export {
+        let contents = match options.export_format {
+            ExportFormat::Csv => to_csv(&samples),
+            ExportFormat::Json => serde_json::to_string_pretty(&report)?,
+        };
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+        if verbose {
+            println!("\nResults written to {}", path.display());
+        }
+    }
 
     // Close the transport
     transport.close().await?;
@@ -156,63 +368,391 @@ pub async fn run_benchmark(count: usize, prefix: String, concurrent: usize) -> R
     Ok(())
 }
 
-/// Benchmark results structure
-#[derive(Debug, Clone)]
-struct BenchmarkResults {
-    /// Total number of interests sent
-    total_interests: usize,
-    
-    /// Successful requests (got Data)
-    successful_requests: usize,
-    
-    /// Failed requests (error)
-    failed_requests: usize,
-    
-    /// Timed out requests
-    timeouts: usize,
-    
-    /// Total time for the benchmark (ms)
-    total_time_ms: u64,
-    
-    /// Minimum RTT (ms)
-    min_rtt_ms: u64,
-    
-    /// Maximum RTT (ms)
-    max_rtt_ms: u64,
-    
-    /// Total RTT (ms) for calculating average
-    total_rtt_ms: u64,
+/// Ticks `rate` times per second, catching up on ticks missed while the
+/// workers were busy so the average rate holds
+fn pacer(rate: f64) -> Interval {
+    let mut ticker = interval(Duration::from_secs_f64(1.0 / rate.max(0.001)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    ticker
+}
+
+/// Express the Interest with sequence number `seq` under `prefix`.
+/// Returns the size of the content on success.
+async fn send(
+    transport: &NdnQuicTransport,
+    face_id: &str,
+    prefix: &Name,
+    seq: usize,
+) -> std::result::Result<std::result::Result<usize, FaceError>, tokio::time::error::Elapsed> {
+    let name = Name::from_string(&format!("{}/{}", prefix, seq)).expect("failed to parse benchmark name");
+    let interest = Interest::new(name)
+        .with_can_be_prefix(false)
+        .with_must_be_fresh(true)
+        .with_lifetime(BENCHMARK_TIMEOUT.as_millis() as u32);
+
+    timeout(BENCHMARK_TIMEOUT, transport.express_interest(interest, Some(face_id), None))
+        .await
+        .map(|result| result.map(|data| data.content().len()))
+}
+
+/// Sum a run up
+fn report(options: &BenchmarkOptions, samples: &[Sample], rtt: &HistogramSnapshot, elapsed: Duration) -> BenchmarkReport {
+    let count_of = |outcome| samples.iter().filter(|sample| sample.outcome == outcome).count();
+    let successful = count_of(Outcome::Data);
+    let rtts = || samples.iter().filter_map(|sample| sample.rtt_us);
+    let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+
+    BenchmarkReport {
+        prefix: options.prefix.clone(),
+        count: options.count,
+        concurrent: options.concurrent,
+        target_rate: options.rate,
+        warmup: options.warmup,
+        successful,
+        failed: count_of(Outcome::Failed),
+        timeouts: count_of(Outcome::Timeout),
+        elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+        throughput: successful as f64 / secs,
+        send_rate: samples.len() as f64 / secs,
+        rtt_min_us: rtts().min(),
+        rtt_max_us: rtts().max(),
+        rtt_avg_us: rtt.average(),
+        rtt_p50_us: rtt.quantile(0.5),
+        rtt_p90_us: rtt.quantile(0.9),
+        rtt_p99_us: rtt.quantile(0.99),
+        rtt_p999_us: rtt.quantile(0.999),
+        rtt_histogram: rtt.clone(),
+    }
+}
+
+/// One row per measured Interest
+fn to_csv(samples: &[Sample]) -> String {
+    let mut csv = String::from("seq,sent_at_us,rtt_us,outcome\n");
+    for sample in samples {
+        let rtt = sample.rtt_us.map(|rtt| rtt.to_string()).unwrap_or_default();
+        let outcome = match sample.outcome {
+            Outcome::Data => "data",
+            Outcome::Failed => "failed",
+            Outcome::Timeout => "timeout",
+        };
+        csv.push_str(&format!("{},{},{},{}\n", sample.seq, sample.sent_at_us, rtt, outcome));
+    }
+    csv
 }
 
 /// Print the benchmark results
-async fn print_benchmark_results(results: BenchmarkResults) {
+fn print_benchmark_results(report: &BenchmarkReport) {
+    let share = |n: usize| n as f64 / report.count.max(1) as f64 * 100.0;
+    let ms = |us: f64| us / 1000.0;
+
     println!("\n\nBenchmark Results");
     println!("=================");
-    println!("Total Interests Sent: {}", results.total_interests);
-    println!("Successful Requests: {} ({:.2}%)", 
-        results.successful_requests,
-        (results.successful_requests as f64 / results.total_interests as f64) * 100.0
-    );
-    println!("Failed Requests: {} ({:.2}%)", 
-        results.failed_requests,
-        (results.failed_requests as f64 / results.total_interests as f64) * 100.0
-    );
-    println!("Timeouts: {} ({:.2}%)", 
-        results.timeouts,
-        (results.timeouts as f64 / results.total_interests as f64) * 100.0
-    );
-    
+    println!("Total Interests Sent: {}", report.count);
+    println!("Successful Requests: {} ({:.2}%)", report.successful, share(report.successful));
+    println!("Failed Requests: {} ({:.2}%)", report.failed, share(report.failed));
+    println!("Timeouts: {} ({:.2}%)", report.timeouts, share(report.timeouts));
+
     println!("\nTiming:");
-    println!("Total Time: {:.2} seconds", results.total_time_ms as f64 / 1000.0);
-    
-    if results.successful_requests > 0 {
-        println!("Throughput: {:.2} interests/second", 
-            results.total_interests as f64 / (results.total_time_ms as f64 / 1000.0)
-        );
-        println!("Min RTT: {} ms", results.min_rtt_ms);
-        println!("Max RTT: {} ms", results.max_rtt_ms);
-        println!("Avg RTT: {:.2} ms", 
-            results.total_rtt_ms as f64 / results.successful_requests as f64
-        );
+    println!("Total Time: {:.2} seconds", report.elapsed_ms / 1000.0);
+    println!("Send Rate: {:.2} interests/second", report.send_rate);
+
+    if let (Some(min), Some(max)) = (report.rtt_min_us, report.rtt_max_us) {
+        println!("Throughput: {:.2} interests/second", report.throughput);
+        println!("Min RTT: {:.2} ms", ms(min as f64));
+        println!("Max RTT: {:.2} ms", ms(max as f64));
+        println!("Avg RTT: {:.2} ms", ms(report.rtt_avg_us));
+        println!("RTT percentiles: p50 {:.2} ms, p90 {:.2} ms, p99 {:.2} ms, p99.9 {:.2} ms",
+            ms(report.rtt_p50_us), ms(report.rtt_p90_us), ms(report.rtt_p99_us), ms(report.rtt_p999_us));
+    }
+}
+
+/// Fetch a synthetic object from the responder for every combination of
+/// segment size and pipeline window, and report the goodput of each
+pub async fn run_throughput(options: ThroughputOptions) -> Result<()> {
+    info!("Running throughput benchmark: {:?}", options);
+    let verbose = options.output.is_table();
+
+    if verbose {
+        println!("Starting µDCN throughput benchmark");
+        println!("==================================");
+        println!("Parameters:");
+        println!("  Object size: {}", format_bytes(options.object_size));
+        println!("  Name prefix: {}", options.prefix);
+        println!("  Segment sizes: {:?}", options.segment_sizes);
+        println!("  Windows: {:?}", options.windows);
+        println!();
+    }
+
+    let client = NdnQuicClient::new(ClientOptions {
+        verify_certificate: false, // For testing only
+        ..Default::default()
+    })
+    .await
+    .context("Failed to create QUIC client")?;
+    if verbose {
+        println!("Connecting to localhost:6367...");
+    }
+    let face: Arc<dyn NdnFace> = client.connect(("localhost", 6367)).await
+        .context("Failed to connect to NDN router")?;
+    if verbose {
+        println!("Connected to NDN router.\n");
+        println!("{:>12} {:>8} {:>14} {:>10} {:>10} {:>10}", "Segment", "Window", "Goodput", "Time", "Retrans", "SRTT");
+    }
+
+    let prefix = Name::from_string(&options.prefix)?;
+
+    let mut runs = Vec::new();
+    for &segment_size in &options.segment_sizes {
+        for &window in &options.windows {
+            // A fresh name per run, so no cache on the way answers it
+            let name = Name::from_string(&format!(
+                "{}/bulk/{}/{}/{}",
+                prefix,
+                options.object_size,
+                segment_size,
+                runs.len()
+            ))?;
+            let window = window.max(1);
+            let fetcher = SegmentFetcher::new(
+                Arc::clone(&face),
+                FetchOptions {
+                    initial_window: window as f64,
+                    min_window: window as f64,
+                    max_window: window as f64,
+                    ..Default::default()
+                },
+            );
+
+            let run = match fetcher.fetch(&name).await {
+                Ok(object) => {
+                    let stats = object.stats;
+                    ThroughputRun {
+                        segment_size,
+                        window,
+                        segments: stats.segments,
+                        bytes: stats.bytes,
+                        elapsed_ms: stats.elapsed.as_secs_f64() * 1000.0,
+                        goodput_bps: stats.bytes as f64 * 8.0 / stats.elapsed.as_secs_f64().max(f64::EPSILON),
+                        retransmissions: stats.retransmissions,
+                        srtt_ms: stats.srtt.as_secs_f64() * 1000.0,
+                        error: (object.content.len() != options.object_size).then(|| {
+                            format!("Fetched {} bytes instead of {}", object.content.len(), options.object_size)
+                        }),
+                    }
+                }
+                Err(e) => ThroughputRun {
+                    segment_size,
+                    window,
+                    segments: 0,
+                    bytes: 0,
+                    elapsed_ms: 0.0,
+                    goodput_bps: 0.0,
+                    retransmissions: 0,
+                    srtt_ms: 0.0,
+                    error: Some(e.to_string()),
+                },
+            };
+            match options.output {
+                OutputFormat::Table => print_throughput_run(&run),
+                OutputFormat::Plain => print_row(&[
+                    &run.segment_size,
+                    &run.window,
+                    &run.segments,
+                    &run.bytes,
+                    &run.elapsed_ms,
+                    &run.goodput_bps,
+                    &run.retransmissions,
+                    &run.srtt_ms,
+                    &run.error.as_deref().unwrap_or_default(),
+                ]),
+                OutputFormat::Json => {}
+            }
+            runs.push(run);
+        }
+    }
+
+    if options.output == OutputFormat::Json {
+        print_json(&runs)?;
+    }
+
+    if let Some(path) = &options.export {
+        let contents = match options.export_format {
+            ExportFormat::Csv => throughput_to_csv(&runs),
+            ExportFormat::Json => serde_json::to_string_pretty(&runs)?,
+        };
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+        if verbose {
+            println!("\nResults written to {}", path.display());
+        }
+    }
+
+    client.close().await?;
+
+    Ok(())
+}
+
+/// One line of the throughput table
+fn print_throughput_run(run: &ThroughputRun) {
+    if let Some(error) = &run.error {
+        println!("{:>12} {:>8}  failed: {}", format_bytes(run.segment_size), run.window, error);
+        return;
+    }
+    println!(
+        "{:>12} {:>8} {:>9.2} Mb/s {:>8.2} s {:>10} {:>7.2} ms",
+        format_bytes(run.segment_size),
+        run.window,
+        run.goodput_bps / 1_000_000.0,
+        run.elapsed_ms / 1000.0,
+        run.retransmissions,
+        run.srtt_ms
+    );
+}
+
+/// One row per throughput run
+fn throughput_to_csv(runs: &[ThroughputRun]) -> String {
+    let mut csv = String::from("segment_size,window,segments,bytes,elapsed_ms,goodput_bps,retransmissions,srtt_ms,error\n");
+    for run in runs {
+        csv.push_str(&format!(
+            "{},{},{},{},{:.3},{:.0},{},{:.3},{}\n",
+            run.segment_size,
+            run.window,
+            run.segments,
+            run.bytes,
+            run.elapsed_ms,
+            run.goodput_bps,
+            run.retransmissions,
+            run.srtt_ms,
+            run.error.as_deref().unwrap_or_default().replace(',', ";")
+        ));
+    }
+    csv
+}
+
+/// Answer benchmark Interests over QUIC until Ctrl+C, so the benchmark can
+/// run against this crate alone
+pub async fn run_responder(options: ResponderOptions) -> Result<()> {
+    info!("Running benchmark responder: {:?}", options);
+
+    let prefix = Name::from_string(&options.prefix)?;
+    let (cert_path, key_path) = write_self_signed_certificate(&std::env::temp_dir().join("udcn-benchmark"))
+        .context("Failed to generate a self-signed certificate")?;
+    let server = NdnQuicServer::new(ServerOptions {
+        listen_addr: options.listen_addr.clone(),
+        cert_path,
+        key_path,
+        ..Default::default()
+    })
+    .await
+    .context("Failed to start QUIC server")?;
+    let mut faces = server.subscribe_faces();
+    server.run().await?;
+
+    println!("µDCN benchmark responder");
+    println!("========================");
+    println!("  Listening on: {}", server.address());
+    println!("  Name prefix: {}", prefix);
+    println!("  Payload size: {}", format_bytes(options.payload_size));
+    println!("  Delay: {} ms", options.delay.as_millis());
+    println!("Press Ctrl+C to stop the responder");
+
+    let payload = Bytes::from(vec![0u8; options.payload_size]);
+    let answered = Arc::new(Counter::new());
+    let rate = Arc::new(Meter::new());
+    let mut ticker = interval(Duration::from_secs(1));
+    let ctrl_c = signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        tokio::select! {
+            face = faces.recv() => match face {
+                Ok(face) => {
+                    println!("\rConsumer connected from {}", face.remote_address());
+                    let (prefix, payload) = (prefix.clone(), payload.clone());
+                    let (answered, rate) = (Arc::clone(&answered), Arc::clone(&rate));
+                    tokio::spawn(answer_face(face, prefix, payload, options.delay, answered, rate));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            _ = ticker.tick() => {
+                print!("\rAnswering {:.0} Interests/s ({} total)   ", rate.rate(Duration::from_secs(1)), answered.value());
+                let _ = std::io::stdout().flush();
+            }
+            _ = &mut ctrl_c => break,
+        }
+    }
+
+    println!("\nShutting down, {} Interests answered", answered.value());
+    server.stop().await?;
+
+    Ok(())
+}
+
+/// Answer the Interests of one consumer: the segments of the synthetic
+/// objects under `<prefix>/bulk`, Data with `payload` elsewhere under
+/// `prefix`, and a NoRoute Nack for anything else
+async fn answer_face(
+    face: Arc<Face>,
+    prefix: Name,
+    payload: Bytes,
+    delay: Duration,
+    answered: Arc<Counter>,
+    rate: Arc<Meter>,
+) {
+    while let Some(event) = face.next_event().await {
+        match event {
+            FaceEvent::InterestReceived(interest) => {
+                let name = interest.name();
+                let data = if !(prefix.len() <= name.len() && prefix.is_prefix_of(name)) {
+                    None
+                } else if name.get(prefix.len()).is_some_and(|component| component.as_bytes() == BULK_COMPONENT) {
+                    bulk_segment(&prefix, name)
+                } else {
+                    Some(Data::new(name.clone(), payload.clone()))
+                };
+                let Some(data) = data else {
+                    let _ = face.send_nack(interest, NackReason::NoRoute).await;
+                    continue;
+                };
+
+                // Answer concurrently, so the delay adds latency but does
+                // not limit the rate
+                let face = Arc::clone(&face);
+                let (answered, rate) = (Arc::clone(&answered), Arc::clone(&rate));
+                tokio::spawn(async move {
+                    if !delay.is_zero() {
+                        sleep(delay).await;
+                    }
+                    match face.send_data(data).await {
+                        Ok(()) => {
+                            answered.increment();
+                            rate.mark(1);
+                        }
+                        Err(e) => debug!("Failed to answer {}: {}", interest.name(), e),
+                    }
+                });
+            }
+            FaceEvent::Closed => break,
+            _ => {}
+        }
+    }
+}
+
+/// Segment named `name` of a synthetic object
+/// `<prefix>/bulk/<object size>/<segment size>/<run>`, unless it lies past
+/// the end of the object
+fn bulk_segment(prefix: &Name, name: &Name) -> Option<Data> {
+    if name.len() != prefix.len() + 5 {
+        return None;
+    }
+    let number = |index: usize| -> Option<u64> { std::str::from_utf8(name.get(index)?.as_bytes()).ok()?.parse().ok() };
+    let object_size = number(prefix.len() + 1)?;
+    let segment_size = number(prefix.len() + 2)?.max(1);
+    let segment = name.segment()?;
+
+    let last = object_size.saturating_sub(1) / segment_size;
+    if segment > last {
+        return None;
     }
+    let size = segment_size.min(object_size - segment * segment_size);
+    Some(Data::new(name.clone(), vec![0u8; size as usize]).with_final_block_id(last))
 }
@@ -0,0 +1,97 @@
+//! Certificate command implementation for µDCN CLI
+
+use anyhow::{Context, Result};
+use rust_udcn_common::ndn::Name;
+use rust_udcn_quic::{format_timestamp, parse_timestamp, KeyChain, NdnCertificate, ValidityPeriod};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::CertCommands;
+
+/// Handle certificate commands
+pub fn handle_command(cmd: CertCommands, keychain: &Path) -> Result<()> {
+    let keychain = KeyChain::open(keychain)
+        .with_context(|| format!("Failed to open the key chain in {}", keychain.display()))?;
+
+    match cmd {
+        CertCommands::Issue { identity, issuer, valid_from, days, out } => {
+            let identity = Name::from_string(&identity)?;
+            let issuer = issuer.as_deref().map(Name::from_string).transpose()?;
+            let validity = validity_period(valid_from.as_deref(), days)?;
+
+            let certificate = keychain.issue(&identity, issuer.as_ref(), validity)?;
+            print_certificate(&certificate);
+            write_certificate(&certificate, out.as_deref())?;
+        }
+        CertCommands::Sign { request, issuer, valid_from, days, out } => {
+            let bytes = fs::read(&request).with_context(|| format!("Failed to read {}", request.display()))?;
+            let request = NdnCertificate::decode(&bytes)
+                .with_context(|| format!("{} does not hold a certificate", request.display()))?;
+            let issuer = Name::from_string(&issuer)?;
+            let validity = validity_period(valid_from.as_deref(), days)?;
+
+            let certificate = keychain.sign(&request, &issuer, validity)?;
+            print_certificate(&certificate);
+            write_certificate(&certificate, Some(&out))?;
+        }
+        CertCommands::Install { file } => {
+            let bytes = fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+            let certificate = NdnCertificate::decode(&bytes)
+                .with_context(|| format!("{} does not hold a certificate", file.display()))?;
+            let current = keychain.certificate(&certificate.identity())?;
+            if current.public_key() != certificate.public_key() {
+                anyhow::bail!("{} is not for the key of {}", certificate.name(), certificate.identity());
+            }
+
+            keychain.add_certificate(&certificate)?;
+            println!("Installed {}", certificate.name());
+        }
+        CertCommands::Show { identity } => {
+            let certificate = keychain.certificate(&Name::from_string(&identity)?)?;
+            print_certificate(&certificate);
+        }
+    }
+
+    Ok(())
+}
+
+/// Validity starting at `valid_from`, or now, for `days`
+fn validity_period(valid_from: Option<&str>, days: u64) -> Result<ValidityPeriod> {
+    let start = match valid_from {
+        Some(time) => parse_timestamp(time).with_context(|| format!("Invalid time {}, expected YYYYMMDDThhmmss", time))?,
+        None => SystemTime::now(),
+    };
+    Ok(ValidityPeriod::new(start, Duration::from_secs(days * 24 * 3600)))
+}
+
+fn print_certificate(certificate: &NdnCertificate) {
+    println!("Certificate: {}", certificate.name());
+    println!("Identity:    {}", certificate.identity());
+    println!("Signed by:   {}", certificate.signer());
+    println!(
+        "Valid:       {} to {}",
+        format_timestamp(certificate.validity().not_before),
+        format_timestamp(certificate.validity().not_after)
+    );
+}
+
+fn write_certificate(certificate: &NdnCertificate, out: Option<&Path>) -> Result<()> {
+    let Some(out) = out else {
+        return Ok(());
+    };
+    fs::write(out, certificate.encode()).with_context(|| format!("Failed to write {}", out.display()))?;
+    println!("Written to {}", out.display());
+    Ok(())
+}
+
+/// Key chain of the current user: `~/.udcn/keychain`
+pub fn default_keychain_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".udcn")
+        .join("keychain")
+}
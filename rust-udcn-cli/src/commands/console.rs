@@ -0,0 +1,226 @@
+//! Interactive console for µDCN CLI
+//!
+//! The console keeps one control connection to udcnd and one QUIC
+//! connection to the router open across commands, opening each on first
+//! use and again after it fails.
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use rust_udcn_daemon::mgmt::MgmtClient;
+use rustyline::{error::ReadlineError, DefaultEditor};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    commands::{cs, face, fib, interest::{InterestOptions, QuicSession}, pit, stats},
+    output::OutputFormat,
+    CsCommands, FaceCommands, FibCommands, PitCommands,
+};
+
+/// File the console history is kept in, under the home directory
+const HISTORY_FILE: &str = ".udcn_history";
+
+/// Most lines kept in the history
+const HISTORY_SIZE: usize = 1000;
+
+/// Commands of the µDCN console
+#[derive(Parser)]
+#[clap(no_binary_name = true, disable_version_flag = true, override_usage = "<COMMAND>")]
+struct ConsoleLine {
+    #[clap(subcommand)]
+    command: ConsoleCommands,
+}
+
+#[derive(Subcommand)]
+enum ConsoleCommands {
+    /// Manage the forwarding table (FIB)
+    Fib {
+        #[clap(subcommand)]
+        cmd: FibCommands,
+    },
+
+    /// Manage the faces of the daemon
+    Face {
+        #[clap(subcommand)]
+        cmd: FaceCommands,
+    },
+
+    /// Inspect and manage the content store
+    Cs {
+        #[clap(subcommand)]
+        cmd: CsCommands,
+    },
+
+    /// Inspect and flush the Pending Interest Tables
+    Pit {
+        #[clap(subcommand)]
+        cmd: PitCommands,
+    },
+
+    /// Show forwarding statistics
+    Stats,
+
+    /// Send an Interest and print the Data
+    Interest {
+        /// Name to request (NDN URI format)
+        name: String,
+
+        /// Timeout in milliseconds
+        #[clap(short, long, default_value = "4000")]
+        timeout: u64,
+
+        /// Set CanBePrefix, accepting Data under the name
+        #[clap(long)]
+        prefix: bool,
+
+        /// Set MustBeFresh, refusing stale cached Data
+        #[clap(long)]
+        fresh: bool,
+    },
+
+    /// Change how command results are printed
+    Output {
+        #[clap(value_enum)]
+        format: OutputFormat,
+    },
+
+    /// Leave the console
+    #[clap(alias = "quit")]
+    Exit,
+}
+
+/// Connections kept open across console commands
+struct Session {
+    /// Control socket of udcnd
+    socket: PathBuf,
+
+    /// How command results are printed
+    output: OutputFormat,
+
+    /// Control connection to udcnd
+    mgmt: Option<MgmtClient>,
+
+    /// QUIC connection to the router, for Interests
+    quic: Option<QuicSession>,
+}
+
+impl Session {
+    /// Control connection to udcnd, opened if needed
+    async fn mgmt(&mut self) -> Result<&mut MgmtClient> {
+        if self.mgmt.is_none() {
+            self.mgmt = Some(MgmtClient::connect(&self.socket).await?);
+        }
+        Ok(self.mgmt.as_mut().expect("connected above"))
+    }
+
+    /// QUIC connection to the router, opened again if it was closed
+    async fn quic(&mut self, timeout_ms: u64) -> Result<&QuicSession> {
+        if let Some(quic) = &self.quic {
+            if quic.is_closed().await {
+                self.quic.take().expect("checked above").close().await?;
+            }
+        }
+        if self.quic.is_none() {
+            self.quic = Some(QuicSession::connect(timeout_ms, self.output).await?);
+        }
+        Ok(self.quic.as_ref().expect("connected above"))
+    }
+
+    /// Run one console command
+    async fn run(&mut self, command: ConsoleCommands) -> Result<()> {
+        let output = self.output;
+        let result = match command {
+            ConsoleCommands::Fib { cmd } => fib::run_command(self.mgmt().await?, cmd, output).await,
+            ConsoleCommands::Face { cmd } => face::run_command(self.mgmt().await?, cmd, output).await,
+            ConsoleCommands::Cs { cmd } => cs::run_command(self.mgmt().await?, cmd).await,
+            ConsoleCommands::Pit { cmd } => pit::run_command(self.mgmt().await?, cmd).await,
+            ConsoleCommands::Stats => {
+                stats::request_stats(self.mgmt().await?).await.map(|stats| stats::print_stats(&stats))
+            }
+            ConsoleCommands::Interest { name, timeout, prefix, fresh } => {
+                let options = InterestOptions {
+                    can_be_prefix: prefix,
+                    must_be_fresh: fresh,
+                    ..InterestOptions::once(timeout)
+                };
+                return self.quic(timeout).await?.send_interest(&name, &options, output).await;
+            }
+            ConsoleCommands::Output { format } => {
+                self.output = format;
+                return Ok(());
+            }
+            ConsoleCommands::Exit => return Ok(()),
+        };
+
+        // The daemon may have gone away, so connect again for the next command
+        if result.is_err() {
+            self.mgmt = None;
+        }
+        result
+    }
+
+    /// Close the connections
+    async fn close(self) -> Result<()> {
+        if let Some(quic) = self.quic {
+            quic.close().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Read commands from the terminal and run them until `exit` or Ctrl+D
+pub async fn run_console(socket: &Path, output: OutputFormat) -> Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    editor.set_max_history_size(HISTORY_SIZE)?;
+    let history = std::env::var_os("HOME").map(|home| Path::new(&home).join(HISTORY_FILE));
+    if let Some(history) = &history {
+        // There is no history on first use
+        let _ = editor.load_history(history);
+    }
+
+    let mut session = Session {
+        socket: socket.to_path_buf(),
+        output,
+        mgmt: None,
+        quic: None,
+    };
+
+    println!("µDCN console, type help for the commands and exit or Ctrl+D to leave");
+    loop {
+        // Reading blocks, so let the runtime move other tasks off this thread
+        let line = match tokio::task::block_in_place(|| editor.readline("udcn> ")) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        let Some(args) = shlex::split(line) else {
+            eprintln!("Error: unbalanced quotes");
+            continue;
+        };
+        let command = match ConsoleLine::try_parse_from(args) {
+            Ok(parsed) => parsed.command,
+            Err(e) => {
+                // Covers help as well as mistakes
+                let _ = e.print();
+                continue;
+            }
+        };
+        if matches!(command, ConsoleCommands::Exit) {
+            break;
+        }
+        if let Err(e) = session.run(command).await {
+            eprintln!("Error: {:#}", e);
+        }
+    }
+
+    if let Some(history) = &history {
+        editor.save_history(history)?;
+    }
+    session.close().await
+}
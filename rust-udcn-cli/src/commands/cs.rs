@@ -0,0 +1,126 @@
+//! Content store command implementation for µDCN CLI
+
+use anyhow::{Context, Result};
+use bytes::BytesMut;
+use rust_udcn_common::ndn::{Data, Name};
+use rust_udcn_daemon::mgmt::{MgmtBody, MgmtClient, MgmtRequest};
+use std::{fs, path::Path, time::Duration};
+
+use crate::{
+    utils::{format_bytes, format_duration},
+    CsCommands,
+};
+
+/// Handle content store commands
+pub async fn handle_command(cmd: CsCommands, socket: &Path) -> Result<()> {
+    let mut client = MgmtClient::connect(socket).await?;
+    run_command(&mut client, cmd).await
+}
+
+/// Run a content store command over an open control connection
+pub async fn run_command(client: &mut MgmtClient, cmd: CsCommands) -> Result<()> {
+    match cmd {
+        CsCommands::Info => {
+            let info = match client.request(&MgmtRequest::CsInfo).await? {
+                MgmtBody::ContentStore(info) => info,
+                other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+            };
+            
+            println!("Entries: {}", info.entries);
+            println!("Size: {}", format_bytes(info.bytes));
+            println!("Persistent: {}", if info.persistent { "yes" } else { "no" });
+            if let Some(kernel_entries) = info.kernel_entries {
+                println!("Mirrored in kernel: {}", kernel_entries);
+            }
+        }
+        CsCommands::Show { prefix } => {
+            let entries = match client.request(&MgmtRequest::CsList { prefix: prefix.clone() }).await? {
+                MgmtBody::CsEntries(entries) => entries,
+                other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+            };
+            
+            if entries.is_empty() {
+                println!("No Data cached under {}.", prefix);
+                return Ok(());
+            }
+            
+            println!("{:<50} {:>10} {:>10} {:>10}  {}", "NAME", "SIZE", "AGE", "FRESHNESS", "KERNEL");
+            for entry in entries {
+                println!(
+                    "{:<50} {:>10} {:>10} {:>10}  {}",
+                    entry.name,
+                    format_bytes(entry.bytes),
+                    format_duration(Duration::from_millis(entry.age_ms)),
+                    format_duration(Duration::from_millis(entry.freshness_ms as u64)),
+                    if entry.kernel { "yes" } else { "no" }
+                );
+            }
+        }
+        CsCommands::Insert { file, name, freshness } => {
+            let bytes = fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+            let data = match name {
+                Some(name) => {
+                    let mut data = Data::new(Name::from_string(&name)?, bytes);
+                    data.freshness_period_ms = freshness;
+                    data
+                }
+                None => Data::decode(&bytes)
+                    .with_context(|| format!("{} does not hold a Data packet, use --name for raw content", file.display()))?,
+            };
+            
+            let mut wire = BytesMut::new();
+            data.encode(&mut wire)?;
+            client.request(&MgmtRequest::CsInsert { wire: wire.to_vec() }).await?;
+            println!("Cached {} ({})", data.name(), format_bytes(data.content().len()));
+        }
+        CsCommands::Stats => {
+            let stats = match client.request(&MgmtRequest::CsStats).await? {
+                MgmtBody::CsStats(stats) => stats,
+                other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+            };
+            
+            println!("Entries: {} of {} ({:.1}% full)",
+                stats.entries, stats.max_entries, occupancy(stats.entries, stats.max_entries));
+            if stats.max_bytes > 0 {
+                println!("Size: {} of {} ({:.1}% full)",
+                    format_bytes(stats.bytes), format_bytes(stats.max_bytes), occupancy(stats.bytes, stats.max_bytes));
+            } else {
+                println!("Size: {}", format_bytes(stats.bytes));
+            }
+            println!("Inserts: {}", stats.inserts);
+            println!("Lookups: {} hits, {} misses", stats.hits, stats.misses);
+            if let (Some(kernel_hits), Some(kernel_entries)) = (stats.kernel_hits, stats.kernel_entries) {
+                println!("Kernel CS: {} hits, {} entries mirrored", kernel_hits, kernel_entries);
+            }
+            println!("Hit ratio: {:.2}%", stats.hit_ratio * 100.0);
+        }
+        CsCommands::Erase { prefix } => {
+            match client.request(&MgmtRequest::CsErase { prefix: prefix.clone() }).await? {
+                MgmtBody::Erased(count) => println!("Erased {} Data packet(s) under {}", count, prefix),
+                other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+            }
+        }
+        CsCommands::Purge { prefix } => {
+            let purged = match client.request(&MgmtRequest::CsPurge { prefix: prefix.clone() }).await? {
+                MgmtBody::Purged(purged) => purged,
+                other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+            };
+            
+            println!("Erased {} Data packet(s) under {}", purged.entries, prefix);
+            if let Some(kernel_entries) = purged.kernel_entries {
+                println!("Purged from kernel: {}", kernel_entries);
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Percentage of a limit in use
+fn occupancy(used: usize, limit: usize) -> f64 {
+    if limit == 0 {
+        0.0
+    } else {
+        used as f64 / limit as f64 * 100.0
+    }
+}
@@ -0,0 +1,119 @@
+//! Packet capture command for µDCN CLI, after tcpdump
+
+use anyhow::Result;
+use clap::ValueEnum;
+use rust_udcn_common::ndn::{ChildSelector, Name};
+use rust_udcn_quic::{Capture, CapturedPacket, NdnPacket};
+use std::time::Instant;
+use tokio::signal;
+
+/// Kind of NDN packet to show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PacketKind {
+    Interest,
+    Data,
+    Nack,
+}
+
+impl PacketKind {
+    fn of(packet: &NdnPacket) -> Self {
+        match packet {
+            NdnPacket::Interest(_) => PacketKind::Interest,
+            NdnPacket::Data(_) => PacketKind::Data,
+            NdnPacket::Nack(_) => PacketKind::Nack,
+        }
+    }
+}
+
+/// Print the NDN packets seen on `interface` until Ctrl+C, or until
+/// `count` were shown
+pub async fn run_dump(
+    interface: String,
+    prefix: Option<String>,
+    kind: Option<PacketKind>,
+    count: Option<u64>,
+) -> Result<()> {
+    let prefix = prefix.map(|prefix| Name::from_string(&prefix)).transpose()?;
+    let capture = Capture::open(&interface)?;
+    let start = Instant::now();
+
+    println!("Capturing NDN packets on {}, Ctrl+C to stop", capture.interface());
+
+    let mut shown = 0;
+    let mut skipped = 0;
+    let ctrl_c = signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    while count.is_none_or(|count| shown < count) {
+        let captured = tokio::select! {
+            captured = capture.next() => captured?,
+            _ = &mut ctrl_c => break,
+        };
+
+        let name = name_of(&captured.packet);
+        let matches_prefix = prefix
+            .as_ref()
+            .is_none_or(|prefix| prefix.len() <= name.len() && prefix.is_prefix_of(name));
+        if !matches_prefix || kind.is_some_and(|kind| kind != PacketKind::of(&captured.packet)) {
+            skipped += 1;
+            continue;
+        }
+
+        println!("{:>12.6} {} {}", start.elapsed().as_secs_f64(), capture.interface(), describe(&captured));
+        shown += 1;
+    }
+
+    println!("\n{} packets shown, {} filtered out", shown, skipped);
+    Ok(())
+}
+
+/// Name an NDN packet is about
+fn name_of(packet: &NdnPacket) -> &Name {
+    match packet {
+        NdnPacket::Interest(interest) => interest.name(),
+        NdnPacket::Data(data) => data.name(),
+        NdnPacket::Nack(nack) => nack.name(),
+    }
+}
+
+/// One line about a captured packet
+fn describe(captured: &CapturedPacket) -> String {
+    let direction = if captured.outgoing { "out" } else { "in " };
+    let packet = match &captured.packet {
+        NdnPacket::Interest(interest) => {
+            let mut line = format!(
+                "Interest {} nonce={:08x} lifetime={}ms",
+                interest.name(),
+                interest.nonce,
+                interest.lifetime_ms
+            );
+            if let Some(hop_limit) = interest.hop_limit {
+                line.push_str(&format!(" hop-limit={}", hop_limit));
+            }
+            if interest.can_be_prefix {
+                line.push_str(" can-be-prefix");
+            }
+            if interest.must_be_fresh {
+                line.push_str(" must-be-fresh");
+            }
+            if interest.child_selector == ChildSelector::Rightmost {
+                line.push_str(" rightmost");
+            }
+            line
+        }
+        NdnPacket::Data(data) => {
+            let mut line = format!("Data {} content={}B", data.name(), data.content().len());
+            if let Some(last) = data.final_block_id {
+                line.push_str(&format!(" final-block={}", last));
+            }
+            line
+        }
+        NdnPacket::Nack(nack) => {
+            let mut line = format!("Nack {} reason={} nonce={:08x}", nack.name(), nack.reason, nack.interest.nonce);
+            if let Some(reporter) = &nack.reporter {
+                line.push_str(&format!(" from={}", reporter));
+            }
+            line
+        }
+    };
+    format!("{} {} {} ({} bytes)", direction, captured.endpoints, packet, captured.size)
+}
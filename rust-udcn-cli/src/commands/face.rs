@@ -0,0 +1,91 @@
+//! Face command implementation for µDCN CLI
+
+use anyhow::Result;
+use rust_udcn_daemon::mgmt::{MgmtBody, MgmtClient, MgmtRequest};
+use std::{path::Path, time::Duration};
+
+use crate::{
+    output::{print_json, print_row, OutputFormat},
+    utils::{format_bytes, format_duration},
+    FaceCommands,
+};
+
+/// Handle face-related commands
+pub async fn handle_command(cmd: FaceCommands, socket: &Path, output: OutputFormat) -> Result<()> {
+    let mut client = MgmtClient::connect(socket).await?;
+    run_command(&mut client, cmd, output).await
+}
+
+/// Run a face command over an open control connection
+pub async fn run_command(client: &mut MgmtClient, cmd: FaceCommands, output: OutputFormat) -> Result<()> {
+    match cmd {
+        FaceCommands::List => {
+            list_faces(client, output).await
+        }
+        FaceCommands::Destroy { face } => {
+            client.request(&MgmtRequest::FaceDestroy { face: face.clone() }).await?;
+            if output.is_table() {
+                println!("Closed face {}", face);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// List the faces of the daemon with their counters
+async fn list_faces(client: &mut MgmtClient, output: OutputFormat) -> Result<()> {
+    let faces = match client.request(&MgmtRequest::FaceList).await? {
+        MgmtBody::Faces(faces) => faces,
+        other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+    };
+    
+    match output {
+        OutputFormat::Json => return print_json(&faces),
+        OutputFormat::Plain => {
+            for face in &faces {
+                print_row(&[
+                    &face.id,
+                    &face.interests_received,
+                    &face.interests_sent,
+                    &face.data_received,
+                    &face.data_sent,
+                    &face.nacks_received,
+                    &face.nacks_sent,
+                    &face.bytes_received,
+                    &face.bytes_sent,
+                    &face.rtt_avg_us,
+                    &face.idle_ms,
+                ]);
+            }
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
+    
+    if faces.is_empty() {
+        println!("No faces are open.");
+        return Ok(());
+    }
+    
+    for face in faces {
+        println!("{}", face.id);
+        println!(
+            "  Interests: {} in, {} out   Data: {} in, {} out   Nacks: {} in, {} out",
+            face.interests_received,
+            face.interests_sent,
+            face.data_received,
+            face.data_sent,
+            face.nacks_received,
+            face.nacks_sent
+        );
+        println!(
+            "  Traffic: {} in, {} out   RTT: {:.2} ms   Idle: {}",
+            format_bytes(face.bytes_received as usize),
+            format_bytes(face.bytes_sent as usize),
+            face.rtt_avg_us / 1000.0,
+            format_duration(Duration::from_millis(face.idle_ms))
+        );
+    }
+    
+    Ok(())
+}
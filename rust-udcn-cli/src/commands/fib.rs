@@ -1,84 +1,106 @@
 //! FIB command implementation for µDCN CLI
 
-use anyhow::{Context, Result};
-use log::{debug, info, warn};
-use rust_udcn_common::ndn::Name;
-use rust_udcn_xdp::XdpManager;
+use anyhow::Result;
+use log::info;
+use rust_udcn_daemon::mgmt::{MgmtBody, MgmtClient, MgmtRequest};
+use std::path::Path;
 
-use crate::FibCommands;
+use crate::{
+    output::{print_json, print_row, OutputFormat},
+    FibCommands,
+};
 
 /// Handle FIB-related commands
-pub async fn handle_command(cmd: FibCommands) -> Result<()> {
+pub async fn handle_command(cmd: FibCommands, socket: &Path, output: OutputFormat) -> Result<()> {
+    let mut client = MgmtClient::connect(socket).await?;
+    run_command(&mut client, cmd, output).await
+}
+
+/// Run a FIB command over an open control connection
+pub async fn run_command(client: &mut MgmtClient, cmd: FibCommands, output: OutputFormat) -> Result<()> {
     match cmd {
         FibCommands::Add { prefix, face, cost } => {
-            add_route(prefix, face, cost).await
+            add_route(client, prefix, face, cost, output).await
         }
-        FibCommands::Remove { prefix } => {
-            remove_route(prefix).await
+        FibCommands::Remove { prefix, face } => {
+            remove_route(client, prefix, face, output).await
         }
         FibCommands::Show => {
-            show_routes().await
+            show_routes(client, output).await
         }
     }
 }
 
 /// Add a route to the FIB
-async fn add_route(prefix_str: String, face: u16, cost: u8) -> Result<()> {
-    info!("Adding route: prefix={}, face={}, cost={}", prefix_str, face, cost);
-    
-    // Parse the prefix string into a Name
-    let prefix = Name::from_string(&prefix_str)?;
-    
-    // Load the XDP manager
-    let xdp = XdpManager::load_from_embedded().await?;
+async fn add_route(client: &mut MgmtClient, prefix: String, face: String, cost: u32, output: OutputFormat) -> Result<()> {
+    info!("Adding route: prefix={}, face={}, cost={}", prefix, face, cost);
     
-    // Add the route
-    xdp.add_route(&prefix, face.into(), cost).await?;
+    client
+        .request(&MgmtRequest::FibAdd {
+            prefix: prefix.clone(),
+            face: face.clone(),
+            cost,
+        })
+        .await?;
     
-    info!("Route added successfully");
-    println!("Added route: {} -> face {} (cost {})", prefix_str, face, cost);
+    if output.is_table() {
+        println!("Added route: {} -> face {} (cost {})", prefix, face, cost);
+    }
     
     Ok(())
 }
 
 /// Remove a route from the FIB
-async fn remove_route(prefix_str: String) -> Result<()> {
-    info!("Removing route: prefix={}", prefix_str);
-    
-    // Parse the prefix string into a Name
-    let prefix = Name::from_string(&prefix_str)?;
-    
-    // Load the XDP manager
-    let xdp = XdpManager::load_from_embedded().await?;
+async fn remove_route(client: &mut MgmtClient, prefix: String, face: String, output: OutputFormat) -> Result<()> {
+    info!("Removing route: prefix={}, face={}", prefix, face);
     
-    // Remove the route
-    xdp.remove_route(&prefix).await?;
+    client
+        .request(&MgmtRequest::FibRemove {
+            prefix: prefix.clone(),
+            face: face.clone(),
+        })
+        .await?;
     
-    info!("Route removed successfully");
-    println!("Removed route: {}", prefix_str);
+    if output.is_table() {
+        println!("Removed route: {} -> face {}", prefix, face);
+    }
     
     Ok(())
 }
 
 /// Show all routes in the FIB
-async fn show_routes() -> Result<()> {
-    info!("Showing all routes in FIB");
-    
-    // Load the XDP manager
-    let xdp = XdpManager::load_from_embedded().await?;
+async fn show_routes(client: &mut MgmtClient, output: OutputFormat) -> Result<()> {
+    let routes = match client.request(&MgmtRequest::FibList).await? {
+        MgmtBody::Routes(routes) => routes,
+        other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+    };
     
-    // In a real implementation, we would implement a method to list all FIB entries
-    // For now, display a message about the limitation
-    println!("FIB route listing is not fully implemented in this version.");
-    println!("This would show all routes in the FIB along with their face IDs and costs.");
+    match output {
+        OutputFormat::Json => return print_json(&routes),
+        OutputFormat::Plain => {
+            for route in &routes {
+                print_row(&[&route.prefix, &route.face, &route.cost, &route.kernel]);
+            }
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
     
-    // Get the FIB component
-    let fib = xdp.fib();
+    if routes.is_empty() {
+        println!("The FIB is empty.");
+        return Ok(());
+    }
     
-    // Here we would iterate through the FIB entries and display them
-    println!("\nFIB management functions available:");
-    println!("  - Add route:    rust-udcn-cli fib add <prefix> <face> --cost <cost>");
-    println!("  - Remove route: rust-udcn-cli fib remove <prefix>");
+    println!("{:<40} {:<40} {:>6}  {}", "PREFIX", "FACE", "COST", "KERNEL");
+    for route in routes {
+        println!(
+            "{:<40} {:<40} {:>6}  {}",
+            route.prefix,
+            route.face,
+            route.cost,
+            if route.kernel { "yes" } else { "no" }
+        );
+    }
     
     Ok(())
 }
@@ -0,0 +1,134 @@
+//! File transfer commands for µDCN CLI
+
+use anyhow::{Context, Result};
+use bytes::{Bytes, BytesMut};
+use rust_udcn_common::ndn::Name;
+use rust_udcn_daemon::mgmt::{MgmtClient, MgmtRequest};
+use rust_udcn_quic::{
+    connect_unix, segment_content, FetchOptions, FetchProgress, FileServer, NdnFace, SegmentFetcher,
+};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::signal;
+
+use crate::utils::{format_bytes, format_duration};
+
+/// Connect to the local forwarder
+async fn connect(unix_socket: &Path) -> Result<Arc<dyn NdnFace>> {
+    let face = connect_unix(unix_socket)
+        .await
+        .context("Failed to connect to udcnd (is it running?)")?;
+    Ok(Arc::new(face))
+}
+
+/// Bits per second moved in `bytes` over `elapsed`, as a readable rate
+fn format_rate(bytes: u64, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    if secs == 0.0 {
+        return "-".to_string();
+    }
+    let bits = bytes as f64 * 8.0 / secs;
+    if bits >= 1e9 {
+        format!("{:.2} Gbit/s", bits / 1e9)
+    } else if bits >= 1e6 {
+        format!("{:.2} Mbit/s", bits / 1e6)
+    } else {
+        format!("{:.2} kbit/s", bits / 1e3)
+    }
+}
+
+/// Serve a file under `name` until Ctrl+C, or insert its segments in the
+/// content store of udcnd with `repo`
+pub async fn put_file(
+    name: String,
+    path: &Path,
+    segment_size: usize,
+    repo: bool,
+    unix_socket: &Path,
+    socket: &Path,
+) -> Result<()> {
+    let name = Name::from_string(&name)?;
+    let content = Bytes::from(fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?);
+    let size = content.len();
+
+    if repo {
+        let segments = segment_content(&name, &content, segment_size);
+        let mut client = MgmtClient::connect(socket).await?;
+        for data in &segments {
+            let mut wire = BytesMut::new();
+            data.encode(&mut wire)?;
+            client.request(&MgmtRequest::CsInsert { wire: wire.to_vec() }).await?;
+        }
+        println!("Inserted {} ({}, {} segments) into the content store of udcnd",
+            name, format_bytes(size), segments.len());
+        return Ok(());
+    }
+
+    let face = connect(unix_socket).await?;
+    let server = FileServer::new(Arc::clone(&face), &name, content, segment_size);
+    server.register().await?;
+
+    println!("Serving {} as {} ({}, {} segments), Ctrl+C to stop",
+        path.display(), name, format_bytes(size), server.segments().len());
+
+    let mut served = 0;
+    tokio::select! {
+        result = server.run(|_| served += 1) => {
+            result?;
+            println!("udcnd closed the connection");
+        }
+        _ = signal::ctrl_c() => {}
+    }
+
+    println!("{} segments served", served);
+    face.close().await;
+    Ok(())
+}
+
+/// Fetch the file named `name` and write it to `path`
+pub async fn get_file(name: String, path: &Path, timeout_ms: u64, unix_socket: &Path) -> Result<()> {
+    let name = Name::from_string(&name)?;
+    let face = connect(unix_socket).await?;
+    let fetcher = SegmentFetcher::new(
+        Arc::clone(&face),
+        FetchOptions {
+            interest_lifetime_ms: timeout_ms,
+            ..Default::default()
+        },
+    );
+
+    let fetched = fetcher.fetch_with_progress(&name, print_progress).await;
+    // Leave the progress line
+    println!();
+    let fetched = fetched?;
+    face.close().await;
+
+    fs::write(path, &fetched.content).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    let stats = &fetched.stats;
+    println!("Fetched {} into {}", name, path.display());
+    println!("Size: {} in {} segments", format_bytes(stats.bytes as usize), stats.segments);
+    println!("Time: {}", format_duration(stats.elapsed));
+    println!("Throughput: {}", format_rate(stats.bytes, stats.elapsed));
+    println!("Retransmissions: {}, congestion events: {}", stats.retransmissions, stats.congestion_events);
+    println!("Final RTT: {:.2} ms, window: {:.1} Interests",
+        stats.srtt.as_secs_f64() * 1000.0, stats.final_window);
+    Ok(())
+}
+
+/// Redraw the progress line of a fetch
+fn print_progress(progress: &FetchProgress) {
+    let done = match progress.total_segments {
+        Some(total) => format!("{}/{} segments ({:.0}%)",
+            progress.segments, total, progress.segments as f64 / total as f64 * 100.0),
+        None => format!("{} segments", progress.segments),
+    };
+    print!("\r{}, {}, {}    ", done, format_bytes(progress.bytes as usize),
+        format_rate(progress.bytes, progress.elapsed));
+    let _ = io::stdout().flush();
+}
@@ -1,33 +1,165 @@
 //! Interest sending command implementation for µDCN CLI
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use bytes::{Bytes, BytesMut};
 use log::{debug, info, warn};
-use rust_udcn_common::{ndn::{Interest, Name}, types::FaceId};
-use rust_udcn_quic::{ClientOptions, NdnQuicClient, NdnQuicTransport, TransportConfig, TransportMode};
-use tokio::time::timeout;
+use rust_udcn_common::{ndn::{ChildSelector, Data, Interest, Name}, types::FaceId};
+use rust_udcn_quic::{ClientOptions, Face, FaceError, NdnQuicClient, NdnQuicTransport, TransportConfig, TransportMode};
+use serde::Serialize;
+use std::{fs, path::Path, sync::Arc, time::{Duration, Instant}};
+use tokio::time::{sleep, timeout};
 
-/// Send an Interest and print the Data response
-pub async fn send_interest(name_str: String, timeout_ms: u64, interface: Option<String>) -> Result<()> {
-    info!("Sending Interest: name={}, timeout={}ms", name_str, timeout_ms);
+use crate::{
+    output::{print_fields, print_json, OutputFormat},
+    utils::hexdump,
+};
+
+/// How the interest command builds and sends its Interests
+#[derive(Debug, Clone)]
+pub struct InterestOptions {
+    /// Time to wait for each Data in milliseconds, also the InterestLifetime
+    pub timeout_ms: u64,
+    
+    /// Interests to send
+    pub count: u64,
+    
+    /// Time between Interests in milliseconds
+    pub interval_ms: u64,
+    
+    /// Accept Data under the name rather than only with it
+    pub can_be_prefix: bool,
+    
+    /// Refuse stale cached Data
+    pub must_be_fresh: bool,
+    
+    /// Prefer the rightmost child among the Data under the name
+    pub rightmost: bool,
+    
+    /// ApplicationParameters of each Interest
+    pub app_params: Option<Bytes>,
+    
+    /// Print the wire format of each packet
+    pub hexdump: bool,
+}
+
+impl InterestOptions {
+    /// A single Interest, waiting `timeout_ms` for Data
+    pub fn once(timeout_ms: u64) -> Self {
+        Self {
+            timeout_ms,
+            count: 1,
+            interval_ms: 0,
+            can_be_prefix: false,
+            must_be_fresh: false,
+            rightmost: false,
+            app_params: None,
+            hexdump: false,
+        }
+    }
+    
+    /// The `seq`th Interest for `name`
+    fn build(&self, name: &Name, seq: u64) -> Interest {
+        let interest = Interest::new(name.clone())
+            .with_can_be_prefix(self.can_be_prefix)
+            .with_must_be_fresh(self.must_be_fresh)
+            .with_child_selector(if self.rightmost { ChildSelector::Rightmost } else { ChildSelector::Leftmost })
+            .with_lifetime(self.timeout_ms.min(u32::MAX as u64) as u32);
+        // Forwarders drop an Interest whose nonce they saw, so each repeat gets its own
+        let nonce = interest.nonce.wrapping_add(seq as u32);
+        let interest = interest.with_nonce(nonce);
+        match &self.app_params {
+            Some(params) => interest.with_application_parameters(params.clone()),
+            None => interest,
+        }
+    }
+}
+
+/// Read ApplicationParameters from a file, or from hex digits
+pub fn parse_app_params(value: &str) -> Result<Bytes> {
+    let path = Path::new(value);
+    if path.is_file() {
+        return Ok(fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?.into());
+    }
+    
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    if digits.len() % 2 != 0 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(anyhow!("{} is neither a file nor hex digits", value));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(Into::into))
+        .collect::<Result<Vec<u8>>>()
+        .map(Bytes::from)
+}
+
+/// How an Interest was answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ReplyStatus {
+    Data,
+    Error,
+    Timeout,
+}
+
+/// Result of the interest command
+#[derive(Debug, Clone, Serialize)]
+struct InterestReply {
+    /// Name the Interest asked for
+    name: String,
+    status: ReplyStatus,
+    /// Round-trip time, when Data came back
+    rtt_ms: Option<f64>,
+    /// Name of the Data
+    data_name: Option<String>,
+    /// Freshness period of the Data
+    freshness_ms: Option<u32>,
+    /// Size of the content
+    content_size: Option<usize>,
+    /// Content, when it is UTF-8 text
+    content: Option<String>,
+    /// Why no Data came back
+    error: Option<String>,
+}
+
+impl InterestReply {
+    fn new(name: &Name, status: ReplyStatus) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            rtt_ms: None,
+            data_name: None,
+            freshness_ms: None,
+            content_size: None,
+            content: None,
+            error: None,
+        }
+    }
+}
+
+/// Send Interests and print the Data responses
+pub async fn send_interest(
+    name_str: String,
+    options: InterestOptions,
+    interface: Option<String>,
+    output: OutputFormat,
+) -> Result<()> {
+    info!("Sending Interest: name={}, timeout={}ms", name_str, options.timeout_ms);
     
     // Parse the name string into a Name
     let name = Name::from_string(&name_str)?;
     
-    // Create an Interest packet
-    let mut interest = Interest::new(name);
-    interest.set_can_be_prefix(false);
-    interest.set_must_be_fresh(true);
-    
-    println!("Sending Interest: {}", name_str);
-    println!("Timeout: {}ms", timeout_ms);
+    if output.is_table() {
+        println!("Sending Interest: {}", name_str);
+        println!("Timeout: {}ms", options.timeout_ms);
+    }
     
     // Determine how to send the Interest:
     // 1. If an interface is specified, use XDP to send via that interface
     // 2. Otherwise, use QUIC transport to send via default route
     if let Some(iface) = interface {
-        send_via_xdp(interest, &iface, timeout_ms).await?;
+        send_via_xdp(options.build(&name, 0), &iface, options.timeout_ms).await?;
     } else {
-        send_via_quic(interest, timeout_ms).await?;
+        send_via_quic(&name, &options, output).await?;
     }
     
     Ok(())
@@ -47,73 +179,198 @@ async fn send_via_xdp(interest: Interest, interface: &str, timeout_ms: u64) -> R
     // For demonstration, show what we're trying to do
     println!("\nInterest details:");
     println!("  Name: {}", interest.name());
-    println!("  CanBePrefix: {}", interest.can_be_prefix());
-    println!("  MustBeFresh: {}", interest.must_be_fresh());
+    println!("  CanBePrefix: {}", interest.can_be_prefix);
+    println!("  MustBeFresh: {}", interest.must_be_fresh);
+    println!("  ChildSelector: {:?}", interest.child_selector);
     
     Ok(())
 }
 
-/// Send an Interest via QUIC transport
-async fn send_via_quic(interest: Interest, timeout_ms: u64) -> Result<()> {
+/// Send Interests via QUIC transport
+async fn send_via_quic(name: &Name, options: &InterestOptions, output: OutputFormat) -> Result<()> {
     info!("Sending Interest via QUIC");
     
-    // Configure the QUIC transport
-    let config = TransportConfig {
-        mode: TransportMode::Client,
-        client_options: ClientOptions {
-            verify_certificate: false, // For testing only
+    let session = QuicSession::connect(options.timeout_ms, output).await?;
+    if output.is_table() {
+        println!("Connected. Sending Interest...");
+    }
+    
+    let result = session.run(name, options, output).await;
+    
+    // Close the transport
+    session.close().await?;
+    
+    result
+}
+
+/// QUIC connection to the local NDN router, which can carry many Interests
+pub struct QuicSession {
+    transport: NdnQuicTransport,
+    face: Arc<Face>,
+}
+
+impl QuicSession {
+    /// Connect to the NDN router on localhost
+    pub async fn connect(timeout_ms: u64, output: OutputFormat) -> Result<Self> {
+        // Configure the QUIC transport
+        let config = TransportConfig {
+            mode: TransportMode::Client,
+            client_options: ClientOptions {
+                verify_certificate: false, // For testing only
+                ..Default::default()
+            },
+            interest_timeout_ms: timeout_ms,
             ..Default::default()
-        },
-        interest_timeout_ms: timeout_ms,
-        ..Default::default()
-    };
+        };
+        
+        // Create the transport
+        let transport = NdnQuicTransport::new(config).await
+            .context("Failed to create QUIC transport")?;
+        
+        // Connect to a default NDN router (localhost in this case)
+        if output.is_table() {
+            println!("Connecting to localhost:6367...");
+        }
+        let face = transport.connect(("localhost", 6367)).await
+            .context("Failed to connect to NDN router")?;
+        
+        Ok(Self { transport, face })
+    }
+    
+    /// Whether the router closed the connection
+    pub async fn is_closed(&self) -> bool {
+        self.face.is_closed().await
+    }
+    
+    /// Send Interests for `name` and print the Data responses
+    pub async fn send_interest(&self, name: &str, options: &InterestOptions, output: OutputFormat) -> Result<()> {
+        self.run(&Name::from_string(name)?, options, output).await
+    }
+    
+    /// Close the connection
+    pub async fn close(self) -> Result<()> {
+        Ok(self.transport.close().await?)
+    }
     
-    // Create the transport
-    let transport = NdnQuicTransport::new(config).await
-        .context("Failed to create QUIC transport")?;
-    
-    // Connect to a default NDN router (localhost in this case)
-    println!("Connecting to localhost:6367...");
-    let face = transport.connect(("localhost", 6367)).await
-        .context("Failed to connect to NDN router")?;
-    
-    println!("Connected. Sending Interest...");
-    
-    // Send the Interest and wait for Data
-    match timeout(
-        std::time::Duration::from_millis(timeout_ms),
-        transport.express_interest(interest.clone(), Some(face.id()), None)
-    ).await {
-        Ok(Ok(data)) => {
-            println!("\nReceived Data:");
-            println!("  Name: {}", data.name());
-            println!("  Content-Type: {}", data.content_type());
-            println!("  Freshness Period: {} ms", data.freshness_period_ms());
+    /// Express `options.count` Interests for `name` and print the replies
+    async fn run(&self, name: &Name, options: &InterestOptions, output: OutputFormat) -> Result<()> {
+        let mut replies = Vec::new();
+        for seq in 0..options.count {
+            if seq > 0 {
+                sleep(Duration::from_millis(options.interval_ms)).await;
+            }
             
-            // Print content (truncate if too long)
-            let content = data.content();
-            if content.len() <= 100 {
-                println!("  Content: {:?}", content);
-                
-                // If content looks like UTF-8 text, print it as string
-                if let Ok(text) = String::from_utf8(content.to_vec()) {
-                    println!("  Content (as text): {}", text);
-                }
-            } else {
-                println!("  Content: {} bytes", content.len());
-                println!("  Content (first 100 bytes): {:?}", &content[..100]);
+            let reply = self.express(options.build(name, seq), options, output).await?;
+            match output {
+                OutputFormat::Json => {}
+                OutputFormat::Plain => print_fields(&reply)?,
+                OutputFormat::Table => print_reply(&reply),
             }
+            replies.push(reply);
         }
-        Ok(Err(e)) => {
-            println!("Error retrieving data: {}", e);
+        
+        match output {
+            OutputFormat::Json if replies.len() == 1 => print_json(&replies[0]),
+            OutputFormat::Json => print_json(&replies),
+            OutputFormat::Table if replies.len() > 1 => {
+                print_summary(name, &replies);
+                Ok(())
+            }
+            _ => Ok(()),
         }
-        Err(_) => {
-            println!("Timeout after {}ms", timeout_ms);
+    }
+    
+    /// Express `interest` and wait for the reply
+    async fn express(&self, interest: Interest, options: &InterestOptions, output: OutputFormat) -> Result<InterestReply> {
+        let timeout_ms = options.timeout_ms;
+        let dump = options.hexdump && output.is_table();
+        if dump {
+            let mut wire = BytesMut::new();
+            interest.encode(&mut wire)?;
+            println!("\nInterest wire format ({} bytes):\n{}", wire.len(), hexdump(&wire));
         }
+        
+        // Send the Interest and wait for Data
+        let start = Instant::now();
+        let reply = match timeout(
+            std::time::Duration::from_millis(timeout_ms),
+            self.transport.express_interest(interest.clone(), Some(self.face.id()), Some(timeout_ms))
+        ).await {
+            Ok(Ok(data)) => {
+                let reply = data_reply(interest.name(), &data, start.elapsed().as_secs_f64() * 1000.0);
+                if dump {
+                    let mut wire = BytesMut::new();
+                    data.encode(&mut wire)?;
+                    println!("\nData wire format ({} bytes):\n{}", wire.len(), hexdump(&wire));
+                }
+                reply
+            }
+            Ok(Err(FaceError::Timeout)) | Err(_) => InterestReply {
+                error: Some(format!("Timeout after {}ms", timeout_ms)),
+                ..InterestReply::new(interest.name(), ReplyStatus::Timeout)
+            },
+            Ok(Err(e)) => InterestReply {
+                error: Some(e.to_string()),
+                ..InterestReply::new(interest.name(), ReplyStatus::Error)
+            },
+        };
+        
+        Ok(reply)
     }
+}
+
+/// Reply to the Interest for `name` answered by `data`
+fn data_reply(name: &Name, data: &Data, rtt_ms: f64) -> InterestReply {
+    InterestReply {
+        rtt_ms: Some(rtt_ms),
+        data_name: Some(data.name().to_string()),
+        freshness_ms: Some(data.freshness_period_ms),
+        content_size: Some(data.content().len()),
+        content: std::str::from_utf8(data.content()).ok().map(str::to_string),
+        ..InterestReply::new(name, ReplyStatus::Data)
+    }
+}
+
+/// Print how many Interests were answered and how fast, after ping
+fn print_summary(name: &Name, replies: &[InterestReply]) {
+    let rtts: Vec<f64> = replies.iter().filter_map(|reply| reply.rtt_ms).collect();
+    let lost = replies.len() - rtts.len();
     
-    // Close the transport
-    transport.close().await?;
+    println!("\n--- {} statistics ---", name);
+    println!(
+        "{} Interests sent, {} Data received, {:.1}% lost",
+        replies.len(),
+        rtts.len(),
+        lost as f64 * 100.0 / replies.len() as f64
+    );
+    if !rtts.is_empty() {
+        let min = rtts.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = rtts.iter().cloned().fold(0.0, f64::max);
+        let avg = rtts.iter().sum::<f64>() / rtts.len() as f64;
+        println!("rtt min/avg/max = {:.3}/{:.3}/{:.3} ms", min, avg, max);
+    }
+}
+
+/// Print the reply for people
+fn print_reply(reply: &InterestReply) {
+    let Some(data_name) = &reply.data_name else {
+        match reply.status {
+            ReplyStatus::Timeout => println!("{}", reply.error.as_deref().unwrap_or("Timeout")),
+            _ => println!("Error retrieving data: {}", reply.error.as_deref().unwrap_or_default()),
+        }
+        return;
+    };
     
-    Ok(())
+    println!("\nReceived Data:");
+    println!("  Name: {}", data_name);
+    println!("  Freshness Period: {} ms", reply.freshness_ms.unwrap_or_default());
+    println!("  RTT: {:.2} ms", reply.rtt_ms.unwrap_or_default());
+    
+    // Print content (truncate if too long)
+    let size = reply.content_size.unwrap_or_default();
+    match &reply.content {
+        Some(text) if size <= 100 => println!("  Content (as text): {}", text),
+        Some(text) => println!("  Content: {} bytes, starting {:?}", size, text.chars().take(100).collect::<String>()),
+        None => println!("  Content: {} bytes of binary data", size),
+    }
 }
@@ -2,6 +2,17 @@
 
 pub mod xdp;
 pub mod fib;
+pub mod face;
+pub mod cs;
+pub mod pit;
+pub mod stats;
 pub mod interest;
 pub mod publish;
+pub mod ping;
+pub mod traceroute;
+pub mod dump;
+pub mod file;
 pub mod benchmark;
+pub mod console;
+pub mod monitor;
+pub mod cert;
@@ -0,0 +1,290 @@
+//! Live dashboard of the daemon's counters for µDCN CLI
+
+use anyhow::{anyhow, Result};
+use rust_udcn_daemon::mgmt::{FaceInfo, MgmtBody, MgmtClient, MgmtEvent, MgmtRequest, StatsInfo, XdpStats};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{signal, time::MissedTickBehavior};
+
+use crate::{
+    commands::stats::request_stats,
+    output::{print_row, OutputFormat},
+    utils::{format_bytes, format_duration},
+};
+
+/// Face events kept on the dashboard
+const RECENT_EVENTS: usize = 5;
+
+/// Rates over the last refresh interval
+#[derive(Debug, Clone, Serialize)]
+struct Snapshot {
+    /// Seconds since the Unix epoch
+    timestamp: f64,
+    interests_per_sec: f64,
+    data_per_sec: f64,
+    satisfied_per_sec: f64,
+    /// Share of CS lookups answered over the interval, when there were any
+    cs_hit_rate: Option<f64>,
+    /// Share of CS lookups answered since udcnd started
+    cs_hit_ratio: f64,
+    pit_entries: usize,
+    faces: usize,
+    rtt_p50_ms: f64,
+    rtt_p99_ms: f64,
+    /// Rates of the XDP fast path, when loaded
+    kernel: Option<KernelRates>,
+    per_face: Vec<FaceRates>,
+    /// Face events since the last refresh
+    events: Vec<MgmtEvent>,
+}
+
+/// Rates of the XDP fast path
+#[derive(Debug, Clone, Serialize)]
+struct KernelRates {
+    interests_per_sec: f64,
+    bytes_per_sec: f64,
+    cs_replies_per_sec: f64,
+    redirects_per_sec: f64,
+    drops_per_sec: f64,
+}
+
+/// Rates of one face
+#[derive(Debug, Clone, Serialize)]
+struct FaceRates {
+    face: String,
+    interests_in: f64,
+    interests_out: f64,
+    data_in: f64,
+    data_out: f64,
+    bytes_in: f64,
+    bytes_out: f64,
+    rtt_ms: f64,
+}
+
+/// Counters read at one refresh
+struct Sample {
+    at: Instant,
+    stats: StatsInfo,
+    faces: HashMap<String, FaceInfo>,
+}
+
+impl Sample {
+    async fn read(client: &mut MgmtClient) -> Result<Self> {
+        let stats = request_stats(client).await?;
+        let faces = match client.request(&MgmtRequest::FaceList).await? {
+            MgmtBody::Faces(faces) => faces,
+            other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+        };
+
+        Ok(Self {
+            at: Instant::now(),
+            stats,
+            faces: faces.into_iter().map(|face| (face.id.clone(), face)).collect(),
+        })
+    }
+
+    /// Rates between `previous` and this sample
+    fn rates_since(&self, previous: &Sample, events: Vec<MgmtEvent>) -> Snapshot {
+        let secs = self.at.duration_since(previous.at).as_secs_f64().max(f64::EPSILON);
+        let rate = |now: u64, before: u64| now.saturating_sub(before) as f64 / secs;
+        let (stats, before) = (&self.stats, &previous.stats);
+
+        let hits = stats.cs_hits.saturating_sub(before.cs_hits);
+        let lookups = hits + stats.cs_misses.saturating_sub(before.cs_misses);
+
+        let kernel = stats.xdp.as_ref().map(|xdp| {
+            let drops = |xdp: &XdpStats| {
+                xdp.drops_duplicate
+                    + xdp.drops_loop
+                    + xdp.drops_rate_limited
+                    + xdp.drops_hop_limit
+                    + xdp.drops_unsolicited
+                    + xdp.aborted
+            };
+            // Counters restart when the program is loaded again
+            let (cs_replies, redirects, dropped) = match &before.xdp {
+                Some(old) => (old.cs_replies, old.interests_redirected, drops(old)),
+                None => (xdp.cs_replies, xdp.interests_redirected, drops(xdp)),
+            };
+            KernelRates {
+                interests_per_sec: xdp.interest_rate.last_1s,
+                bytes_per_sec: xdp.byte_rate.last_1s,
+                cs_replies_per_sec: rate(xdp.cs_replies, cs_replies),
+                redirects_per_sec: rate(xdp.interests_redirected, redirects),
+                drops_per_sec: rate(drops(xdp), dropped),
+            }
+        });
+
+        let mut per_face: Vec<FaceRates> = self
+            .faces
+            .values()
+            .map(|face| {
+                // A face opened since the last refresh counts from zero
+                let old = previous.faces.get(&face.id);
+                let counter = |get: fn(&FaceInfo) -> u64| rate(get(face), old.map_or(0, get));
+                FaceRates {
+                    face: face.id.clone(),
+                    interests_in: counter(|f| f.interests_received),
+                    interests_out: counter(|f| f.interests_sent),
+                    data_in: counter(|f| f.data_received),
+                    data_out: counter(|f| f.data_sent),
+                    bytes_in: counter(|f| f.bytes_received),
+                    bytes_out: counter(|f| f.bytes_sent),
+                    rtt_ms: face.rtt_avg_us / 1000.0,
+                }
+            })
+            .collect();
+        per_face.sort_by(|a, b| a.face.cmp(&b.face));
+
+        Snapshot {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64(),
+            interests_per_sec: rate(stats.interests_received, before.interests_received),
+            data_per_sec: rate(stats.data_received, before.data_received),
+            satisfied_per_sec: rate(stats.interests_satisfied, before.interests_satisfied),
+            cs_hit_rate: (lookups > 0).then(|| hits as f64 / lookups as f64),
+            cs_hit_ratio: stats.cs_hit_ratio,
+            pit_entries: stats.pit_entries,
+            faces: stats.faces,
+            rtt_p50_ms: stats.rtt_p50_us / 1000.0,
+            rtt_p99_ms: stats.rtt_p99_us / 1000.0,
+            kernel,
+            per_face,
+            events,
+        }
+    }
+}
+
+/// Show the daemon's packet rates, CS hit rate, PIT size and per-face
+/// rates, refreshed every `interval_ms` until Ctrl+C
+pub async fn run_monitor(socket: &Path, interval_ms: u64, output: OutputFormat) -> Result<()> {
+    let mut client = MgmtClient::connect(socket).await?;
+    let mut watcher = MgmtClient::connect(socket).await?;
+    watcher.request(&MgmtRequest::Watch).await?;
+
+    let started = Instant::now();
+    let mut previous = Sample::read(&mut client).await?;
+    let mut pending = Vec::new();
+    let mut recent = VecDeque::new();
+
+    let mut ticker = tokio::time::interval(Duration::from_millis(interval_ms.max(1)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // The first tick completes at once, and there is nothing to compare yet
+    ticker.tick().await;
+
+    let ctrl_c = signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            event = watcher.next_event() => {
+                let event = event?.ok_or_else(|| anyhow!("udcnd closed the control connection"))?;
+                if recent.len() == RECENT_EVENTS {
+                    recent.pop_front();
+                }
+                recent.push_back((started.elapsed(), event.clone()));
+                pending.push(event);
+                continue;
+            }
+            _ = &mut ctrl_c => break,
+        }
+
+        let sample = Sample::read(&mut client).await?;
+        let snapshot = sample.rates_since(&previous, std::mem::take(&mut pending));
+        previous = sample;
+
+        match output {
+            OutputFormat::Json => println!("{}", serde_json::to_string(&snapshot)?),
+            OutputFormat::Plain => print_row(&[
+                &snapshot.timestamp,
+                &snapshot.interests_per_sec,
+                &snapshot.data_per_sec,
+                &snapshot.satisfied_per_sec,
+                &snapshot.cs_hit_rate.unwrap_or_default(),
+                &snapshot.pit_entries,
+                &snapshot.faces,
+            ]),
+            OutputFormat::Table => render(&snapshot, &recent, started.elapsed()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Redraw the dashboard
+fn render(snapshot: &Snapshot, recent: &VecDeque<(Duration, MgmtEvent)>, elapsed: Duration) {
+    let per_sec = |bytes: f64| format!("{}/s", format_bytes(bytes as usize));
+
+    // Clear the screen and go home
+    print!("\x1b[2J\x1b[H");
+    println!("µDCN monitor, watching for {}, Ctrl+C to quit\n", format_duration(elapsed));
+
+    println!("Forwarder");
+    println!(
+        "  Interests: {:>10.1}/s   Data: {:>10.1}/s   Satisfied: {:>10.1}/s",
+        snapshot.interests_per_sec, snapshot.data_per_sec, snapshot.satisfied_per_sec
+    );
+    let hit_rate = snapshot
+        .cs_hit_rate
+        .map_or_else(|| "-".to_string(), |rate| format!("{:.1}%", rate * 100.0));
+    println!(
+        "  CS hit rate: {:>7} ({:.1}% overall)   PIT: {} entries   Faces: {}",
+        hit_rate,
+        snapshot.cs_hit_ratio * 100.0,
+        snapshot.pit_entries,
+        snapshot.faces
+    );
+    println!("  RTT: p50 {:.2} ms, p99 {:.2} ms", snapshot.rtt_p50_ms, snapshot.rtt_p99_ms);
+
+    match &snapshot.kernel {
+        Some(kernel) => {
+            println!("\nXDP fast path");
+            println!(
+                "  Interests: {:>10.1}/s   Traffic: {:>12}   CS replies: {:.1}/s",
+                kernel.interests_per_sec,
+                per_sec(kernel.bytes_per_sec),
+                kernel.cs_replies_per_sec
+            );
+            println!(
+                "  Redirected: {:.1}/s   Dropped: {:.1}/s",
+                kernel.redirects_per_sec, kernel.drops_per_sec
+            );
+        }
+        None => println!("\nThe XDP fast path is not loaded."),
+    }
+
+    if !snapshot.per_face.is_empty() {
+        println!(
+            "\n{:<40} {:>10} {:>10} {:>10} {:>10} {:>12} {:>12} {:>9}",
+            "FACE", "INT IN/s", "INT OUT/s", "DATA IN/s", "DATA OUT/s", "IN", "OUT", "RTT ms"
+        );
+        for face in &snapshot.per_face {
+            println!(
+                "{:<40} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>12} {:>12} {:>9.2}",
+                face.face,
+                face.interests_in,
+                face.interests_out,
+                face.data_in,
+                face.data_out,
+                per_sec(face.bytes_in),
+                per_sec(face.bytes_out),
+                face.rtt_ms
+            );
+        }
+    }
+
+    if !recent.is_empty() {
+        println!("\nRecent events");
+        for (at, event) in recent {
+            let line = match event {
+                MgmtEvent::FaceCreated { face } => format!("face {} opened", face),
+                MgmtEvent::FaceClosed { face } => format!("face {} closed", face),
+                MgmtEvent::Error { message } => format!("error: {}", message),
+            };
+            println!("  +{:<8} {}", format_duration(*at), line);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Ping and ping server commands for µDCN CLI, after ndnping
+
+use anyhow::{Context, Result};
+use rust_udcn_common::ndn::Name;
+use rust_udcn_quic::{connect_unix, NdnFace, PingOutcome, PingServer, PingStats, Pinger};
+use std::{path::Path, sync::Arc, time::Duration};
+use tokio::{signal, sync::mpsc, time::interval};
+
+/// Milliseconds in a duration, with their fraction
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Connect to the local forwarder
+async fn connect(unix_socket: &Path) -> Result<Arc<dyn NdnFace>> {
+    let face = connect_unix(unix_socket)
+        .await
+        .context("Failed to connect to udcnd (is it running?)")?;
+    Ok(Arc::new(face))
+}
+
+/// Ping a prefix every `interval_ms`, `count` times or until Ctrl+C
+pub async fn run_ping(
+    prefix: String,
+    count: Option<u64>,
+    interval_ms: u64,
+    timeout_ms: u64,
+    unix_socket: &Path,
+) -> Result<()> {
+    let prefix = Name::from_string(&prefix)?;
+    let face = connect(unix_socket).await?;
+    let pinger = Arc::new(Pinger::new(Arc::clone(&face), &prefix, Duration::from_millis(timeout_ms)));
+    
+    println!("PING {}", prefix);
+    
+    // Pings go out on schedule, whether or not the previous one came back
+    let (results, mut replies) = mpsc::unbounded_channel();
+    let mut ticker = interval(Duration::from_millis(interval_ms));
+    let ctrl_c = signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut stats = PingStats::default();
+    let mut sent = 0;
+    let mut outstanding = 0;
+    
+    loop {
+        tokio::select! {
+            _ = ticker.tick(), if count.is_none_or(|count| sent < count) => {
+                sent += 1;
+                outstanding += 1;
+                let pinger = Arc::clone(&pinger);
+                let results = results.clone();
+                tokio::spawn(async move {
+                    let _ = results.send(pinger.ping().await);
+                });
+            }
+            Some((seq, outcome)) = replies.recv() => {
+                outstanding -= 1;
+                match &outcome {
+                    PingOutcome::Reply { rtt, .. } => {
+                        println!("content from {}: seq={} time={:.3} ms", prefix, seq, millis(*rtt))
+                    }
+                    PingOutcome::Lost(reason) => println!("no reply from {}: seq={} ({})", prefix, seq, reason),
+                }
+                stats.record(&outcome);
+                if count.is_some_and(|count| sent >= count) && outstanding == 0 {
+                    break;
+                }
+            }
+            _ = &mut ctrl_c => break,
+        }
+    }
+    
+    println!("\n--- {} ping statistics ---", prefix);
+    println!("{} pings transmitted, {} received, {:.1}% lost",
+        stats.sent, stats.received, stats.loss() * 100.0);
+    if let (Some(min), Some(avg), Some(max)) = (stats.min_rtt, stats.avg_rtt(), stats.max_rtt) {
+        println!("rtt min/avg/max = {:.3}/{:.3}/{:.3} ms", millis(min), millis(avg), millis(max));
+    }
+    if let Some(jitter) = stats.jitter() {
+        println!("jitter = {:.3} ms", millis(jitter));
+    }
+    
+    face.close().await;
+    Ok(())
+}
+
+/// Answer pings under a prefix until Ctrl+C
+pub async fn run_ping_server(prefix: String, payload_size: usize, unix_socket: &Path) -> Result<()> {
+    let prefix = Name::from_string(&prefix)?;
+    let face = connect(unix_socket).await?;
+    let server = PingServer::new(Arc::clone(&face), &prefix, payload_size);
+    server.register().await?;
+    
+    println!("PING SERVER {}", server.prefix());
+    
+    let mut answered = 0;
+    tokio::select! {
+        result = server.run(|name| {
+            answered += 1;
+            println!("interest received: {}", name);
+        }) => {
+            result?;
+            println!("udcnd closed the connection");
+        }
+        _ = signal::ctrl_c() => {}
+    }
+    
+    println!("\n--- {} ping server statistics ---", server.prefix());
+    println!("{} pings answered", answered);
+    
+    face.close().await;
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! PIT command implementation for µDCN CLI
+
+use anyhow::Result;
+use rust_udcn_daemon::mgmt::{MgmtBody, MgmtClient, MgmtRequest};
+use std::{path::Path, time::Duration};
+
+use crate::{utils::format_duration, PitCommands};
+
+/// Handle PIT commands
+pub async fn handle_command(cmd: PitCommands, socket: &Path) -> Result<()> {
+    let mut client = MgmtClient::connect(socket).await?;
+    run_command(&mut client, cmd).await
+}
+
+/// Run a PIT command over an open control connection
+pub async fn run_command(client: &mut MgmtClient, cmd: PitCommands) -> Result<()> {
+    match cmd {
+        PitCommands::Show => {
+            let entries = match client.request(&MgmtRequest::PitList).await? {
+                MgmtBody::Pit(entries) => entries,
+                other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+            };
+            
+            if entries.is_empty() {
+                println!("No pending Interests.");
+                return Ok(());
+            }
+            
+            println!("{:<40} {:<30} {:<30} {:>10}  {}", "NAME", "IN", "OUT", "REMAINING", "KERNEL");
+            for entry in entries {
+                let remaining = match entry.remaining_ms {
+                    0 => "expired".to_string(),
+                    ms => format_duration(Duration::from_millis(ms)),
+                };
+                println!(
+                    "{:<40} {:<30} {:<30} {:>10}  {}",
+                    entry.name,
+                    entry.in_faces.join(","),
+                    entry.out_faces.join(","),
+                    remaining,
+                    if entry.kernel { "yes" } else { "no" }
+                );
+            }
+        }
+        PitCommands::Flush { prefix, all } => {
+            let flushed = match client.request(&MgmtRequest::PitFlush { prefix: prefix.clone(), all }).await? {
+                MgmtBody::Flushed(flushed) => flushed,
+                other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+            };
+            
+            let which = if all { "pending" } else { "expired" };
+            println!("Flushed {} {} Interest(s) under {}", flushed.entries, which, prefix);
+            if let Some(kernel_entries) = flushed.kernel_entries {
+                println!("Flushed from kernel: {}", kernel_entries);
+            }
+        }
+    }
+    
+    Ok(())
+}
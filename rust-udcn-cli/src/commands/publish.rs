@@ -1,171 +1,392 @@
 //! Data publishing command implementation for µDCN CLI
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use bytes::Bytes;
-use log::{debug, info, warn};
-use rust_udcn_common::ndn::{Data, Name, SignatureInfo, SignatureType};
-use rust_udcn_quic::{ServerOptions, NdnQuicServer, TransportConfig, TransportMode, NDN_QUIC_PORT};
-use std::{path::PathBuf, time::Duration};
-use tokio::signal;
-
-/// Publish data under a name
-pub async fn publish_data(
-    name_str: String,
-    content: String,
-    ttl: u32,
-    interface: Option<String>,
-) -> Result<()> {
-    info!(
-        "Publishing data: name={}, content_len={}, ttl={}ms",
-        name_str,
-        content.len(),
-        ttl
-    );
+use log::{debug, info};
+use rust_udcn_common::{
+    metrics::Counter,
+    ndn::{Data, NackReason, Name, NameComponent},
+};
+use rust_udcn_quic::{connect_unix, segment_content, FaceEvent, NdnFace, NdnQuicServer, ServerOptions};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    signal::{self, unix::SignalKind},
+    sync::broadcast::error::RecvError,
+};
+
+use crate::{
+    output::{print_fields, print_json, OutputFormat},
+    utils::{format_bytes, format_duration, write_self_signed_certificate},
+};
+
+/// Time a daemonized producer gets to fail before it is reported as running
+const DAEMON_STARTUP: Duration = Duration::from_secs(1);
+
+/// Log of daemonized producers, under the temporary directory
+const DAEMON_LOG: &str = "udcn-publish.log";
+
+/// Set in the environment of a daemonized producer, so it runs in the
+/// foreground of its own process
+const DAEMON_ENV: &str = "UDCN_PUBLISH_DAEMON";
+
+/// What to publish
+#[derive(Debug, Clone)]
+pub enum Content {
+    /// A string, as one Data named exactly as the prefix
+    Text(String),
+
+    /// A file, segmented under the prefix
+    File(PathBuf),
+
+    /// The files under a directory, each segmented under the prefix
+    /// followed by its path
+    Dir(PathBuf),
+}
+
+/// Options of a publish run
+#[derive(Debug, Clone)]
+pub struct PublishOptions {
+    /// Name to publish under
+    pub name: String,
 
-    // Parse the name string into a Name
-    let name = Name::from_string(&name_str)?;
+    /// What to publish
+    pub content: Content,
 
-    // Create a Data packet
-    let content_bytes = Bytes::from(content.clone());
-    let mut data = Data::new(name.clone(), content_bytes);
-    data.set_freshness_period_ms(ttl);
-    data.set_content_type(0); // BLOB content type
+    /// Freshness period of the Data in milliseconds
+    pub ttl: u32,
+
+    /// Bytes of content in each segment of a file
+    pub segment_size: usize,
+
+    /// Face socket of the local udcnd, when not listening for consumers
+    pub unix_socket: PathBuf,
+
+    /// Address to accept QUIC consumers on instead of registering with udcnd
+    pub listen: Option<String>,
+}
+
+/// Data answering the Interests of a publish run
+struct Catalog {
+    /// Prefix everything is published under
+    prefix: Name,
+
+    /// Data by name
+    data: HashMap<Name, Data>,
+
+    /// Objects published
+    objects: usize,
+
+    /// Bytes of content published
+    bytes: usize,
+}
 
-    // Create a simple signature (in production, you'd want a real signature)
-    let sig_info = SignatureInfo {
-        signature_type: SignatureType::DigestSha256,
-        key_locator: None,
-        validity_period: None,
+impl Catalog {
+    /// Read and segment `content` under `prefix`
+    fn load(prefix: Name, content: &Content, ttl: u32, segment_size: usize) -> Result<Self> {
+        let mut catalog = Self {
+            prefix: prefix.clone(),
+            data: HashMap::new(),
+            objects: 0,
+            bytes: 0,
+        };
+
+        match content {
+            Content::Text(text) => {
+                catalog.add(vec![Data::new(prefix, Bytes::from(text.clone()))], ttl, text.len());
+            }
+            Content::File(path) => {
+                let content = read(path)?;
+                catalog.add(segment_content(&prefix, &content, segment_size), ttl, content.len());
+            }
+            Content::Dir(dir) => {
+                let mut files = Vec::new();
+                walk(dir, Vec::new(), &mut files)?;
+                if files.is_empty() {
+                    bail!("No files under {}", dir.display());
+                }
+                for (components, path) in files {
+                    let mut name = prefix.clone();
+                    for component in components {
+                        name.push(NameComponent::new(component.into_bytes()));
+                    }
+                    let content = read(&path)?;
+                    debug!("Publishing {} as {}", path.display(), name);
+                    catalog.add(segment_content(&name, &content, segment_size), ttl, content.len());
+                }
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    /// Add the segments of one object
+    fn add(&mut self, segments: Vec<Data>, ttl: u32, size: usize) {
+        for data in segments {
+            let data = data.with_ttl(ttl);
+            self.data.insert(data.name().clone(), data);
+        }
+        self.objects += 1;
+        self.bytes += size;
+    }
+
+    /// Data named `name`
+    fn lookup(&self, name: &Name) -> Option<&Data> {
+        self.data.get(name)
+    }
+}
+
+/// Read a whole file
+fn read(path: &Path) -> Result<Bytes> {
+    let content = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(Bytes::from(content))
+}
+
+/// Collect the files under `dir` with their path components below the
+/// directory published, in name order
+fn walk(dir: &Path, components: Vec<String>, files: &mut Vec<(Vec<String>, PathBuf)>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let mut components = components.clone();
+        components.push(entry.file_name().to_string_lossy().into_owned());
+        // Follows symbolic links
+        let metadata = fs::metadata(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        if metadata.is_dir() {
+            walk(&path, components, files)?;
+        } else if metadata.is_file() {
+            files.push((components, path));
+        }
+    }
+    Ok(())
+}
+
+/// Counters of a publish run
+#[derive(Default)]
+struct Served {
+    interests: Counter,
+    data: Counter,
+    bytes: Counter,
+    unanswered: Counter,
+}
+
+/// Counters reported when a publish run ends
+#[derive(Debug, Serialize)]
+struct PublishSummary {
+    prefix: String,
+    objects: usize,
+    segments: usize,
+    uptime_secs: f64,
+    interests_received: u64,
+    data_served: u64,
+    bytes_served: u64,
+    interests_unanswered: u64,
+}
+
+/// Publish a string, a file or a directory under `name` and answer
+/// Interests for it until Ctrl+C or SIGTERM, through udcnd or straight to
+/// QUIC consumers, in the background with `daemonize`
+pub async fn publish_data(options: PublishOptions, daemonize: bool, output: OutputFormat) -> Result<()> {
+    info!("Publishing: {:?}", options);
+
+    let prefix = Name::from_string(&options.name)?;
+    let catalog = Arc::new(Catalog::load(prefix, &options.content, options.ttl, options.segment_size)?);
+
+    if daemonize && std::env::var_os(DAEMON_ENV).is_none() {
+        return spawn_daemon().await;
+    }
+
+    let served = Arc::new(Served::default());
+    let started = Instant::now();
+    match &options.listen {
+        Some(listen) => serve_quic(listen, &catalog, &served, output).await?,
+        None => serve_daemon(&options.unix_socket, &catalog, &served, output).await?,
+    }
+
+    let summary = PublishSummary {
+        prefix: catalog.prefix.to_string(),
+        objects: catalog.objects,
+        segments: catalog.data.len(),
+        uptime_secs: started.elapsed().as_secs_f64(),
+        interests_received: served.interests.value(),
+        data_served: served.data.value(),
+        bytes_served: served.bytes.value(),
+        interests_unanswered: served.unanswered.value(),
     };
-    data.set_signature_info(sig_info);
-    
-    // The real signing would happen here
-    // For this demo, we just set a dummy signature value
-    data.set_signature_value(Bytes::from(vec![0u8; 32]));
-
-    println!("Publishing Data:");
-    println!("  Name: {}", name_str);
-    println!("  Content ({}): {}", content.len(), 
-        if content.len() > 32 {
-            format!("{}...", &content[..30])
-        } else {
-            content.clone()
+    match output {
+        OutputFormat::Json => print_json(&summary)?,
+        OutputFormat::Plain => print_fields(&summary)?,
+        OutputFormat::Table => {
+            println!("\n--- {} publish statistics ---", summary.prefix);
+            println!("Up for {}", format_duration(started.elapsed()));
+            println!("{} Interests received, {} Data served ({}), {} unanswered",
+                summary.interests_received, summary.data_served,
+                format_bytes(summary.bytes_served as usize), summary.interests_unanswered);
         }
-    );
-    println!("  TTL: {}ms", ttl);
-
-    // Determine how to publish the Data:
-    // 1. If an interface is specified, use XDP to publish via that interface
-    // 2. Otherwise, use QUIC transport to publish via a server
-    if let Some(iface) = interface {
-        publish_via_xdp(data, &iface).await?;
-    } else {
-        publish_via_quic(data).await?;
     }
 
     Ok(())
 }
 
-/// Publish a Data packet via the XDP forwarder
-async fn publish_via_xdp(data: Data, interface: &str) -> Result<()> {
-    info!("Publishing Data via XDP on interface {}", interface);
-
-    // In a real implementation, we'd interact with the XDP forwarder
-    // to publish the Data via a specific interface
-    
-    println!("Note: Publishing via specific interface is not fully implemented.");
-    println!("In a real implementation, this would publish the Data via the XDP forwarder");
-    println!("on interface {}.", interface);
-
-    // For demonstration, show what we're trying to do
-    println!("\nData packet would be published via XDP on interface {}", interface);
-    println!("  Name: {}", data.name());
-    println!("  Content size: {} bytes", data.content().len());
-    
+/// Register the prefix with the local udcnd and answer the Interests it
+/// forwards until stopped
+async fn serve_daemon(unix_socket: &Path, catalog: &Arc<Catalog>, served: &Arc<Served>, output: OutputFormat) -> Result<()> {
+    let face: Arc<dyn NdnFace> = Arc::new(
+        connect_unix(unix_socket)
+            .await
+            .context("Failed to connect to udcnd (is it running?)")?,
+    );
+    face.register_prefix(&catalog.prefix).await?;
+
+    if output.is_table() {
+        print_banner(catalog, &format!("udcnd at {}", unix_socket.display()));
+    }
+
+    tokio::select! {
+        _ = answer(Arc::clone(&face), Arc::clone(catalog), Arc::clone(served)) => {
+            if output.is_table() {
+                println!("udcnd closed the connection");
+            }
+        }
+        result = stopped() => result?,
+    }
+
+    face.close().await;
     Ok(())
 }
 
-/// Publish a Data packet via QUIC transport
-async fn publish_via_quic(data: Data) -> Result<()> {
-    info!("Publishing Data via QUIC server");
-
-    // For a real server, we'd need certificates
-    // For testing, let's create them in a temporary directory
-    println!("Starting QUIC server on port {}...", NDN_QUIC_PORT);
-    println!("Press Ctrl+C to stop the server");
-
-    // In a real implementation, we would:
-    // 1. Configure a proper QUIC server with real certificates
-    // 2. Create a producer application that registers prefixes
-    // 3. Serve Data in response to matching Interests
-    
-    // For this demonstration, we'll create a simplified server
-    // that just serves our single Data packet for any matching Interest
-    let data_name = data.name().clone();
-    
-    // Create a simple echo server
-    println!("Server ready to respond to Interests for: {}", data_name);
-    println!("Waiting for Interests (press Ctrl+C to exit)...");
-    
-    // In a real implementation, this would be a full NDN producer
-    // For now, just simulate waiting until Ctrl+C
-    match signal::ctrl_c().await {
-        Ok(()) => {
-            println!("Shutting down server...");
+/// Accept QUIC consumers on `listen` and answer their Interests until
+/// stopped
+async fn serve_quic(listen: &str, catalog: &Arc<Catalog>, served: &Arc<Served>, output: OutputFormat) -> Result<()> {
+    let (cert_path, key_path) = write_self_signed_certificate(&std::env::temp_dir().join("udcn-publish"))
+        .context("Failed to generate a self-signed certificate")?;
+    let server = NdnQuicServer::new(ServerOptions {
+        listen_addr: listen.to_string(),
+        cert_path,
+        key_path,
+        ..Default::default()
+    })
+    .await
+    .context("Failed to start QUIC server")?;
+    let mut faces = server.subscribe_faces();
+    server.run().await?;
+
+    if output.is_table() {
+        print_banner(catalog, &format!("QUIC on {}", server.address()));
+    }
+
+    let stop = stopped();
+    tokio::pin!(stop);
+    loop {
+        tokio::select! {
+            face = faces.recv() => match face {
+                Ok(face) => {
+                    if output.is_table() {
+                        println!("Consumer connected from {}", face.remote_address());
+                    }
+                    tokio::spawn(answer(face, Arc::clone(catalog), Arc::clone(served)));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            result = &mut stop => {
+                result?;
+                break;
+            }
         }
-        Err(err) => {
-            eprintln!("Unable to listen for shutdown signal: {}", err);
+    }
+
+    server.stop().await?;
+    Ok(())
+}
+
+/// Describe what is being published
+fn print_banner(catalog: &Catalog, via: &str) {
+    println!("Publishing {} via {}", catalog.prefix, via);
+    println!("  {} objects, {} in {} Data packets",
+        catalog.objects, format_bytes(catalog.bytes), catalog.data.len());
+    println!("Press Ctrl+C to stop");
+}
+
+/// Answer the Interests arriving on `face` from the catalog, with a NoRoute
+/// Nack for names that were not published
+async fn answer(face: Arc<dyn NdnFace>, catalog: Arc<Catalog>, served: Arc<Served>) {
+    while let Some(event) = face.recv().await {
+        match event {
+            FaceEvent::InterestReceived(interest) => {
+                served.interests.increment();
+                match catalog.lookup(interest.name()) {
+                    Some(data) => {
+                        let size = data.content().len() as u64;
+                        if face.send_data(data.clone()).await.is_err() {
+                            break;
+                        }
+                        served.data.increment();
+                        served.bytes.add(size);
+                    }
+                    None => {
+                        debug!("Nothing published as {}", interest.name());
+                        served.unanswered.increment();
+                        let _ = face.send_nack(interest, NackReason::NoRoute).await;
+                    }
+                }
+            }
+            FaceEvent::Closed => break,
+            _ => {}
         }
     }
+}
 
+/// Wait for Ctrl+C or SIGTERM
+async fn stopped() -> Result<()> {
+    let mut terminate = signal::unix::signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    }
     Ok(())
 }
 
-/// Temporary function to generate a self-signed certificate for testing
-/// Not suitable for production use
-#[allow(dead_code)]
-async fn generate_test_certificates(dir: &PathBuf) -> Result<(PathBuf, PathBuf)> {
-    use rcgen::{Certificate, CertificateParams, DnType, SanType, KeyPair, KeyUsagePurpose};
-    use std::fs::File;
-    use std::io::Write;
-    
-    // Create the certificate directory if it doesn't exist
-    std::fs::create_dir_all(dir)?;
-    
-    // Generate a new key pair
-    let key_pair = KeyPair::generate()?;
-    
-    // Set up certificate parameters
-    let mut params = CertificateParams::default();
-    params.key_pair = Some(key_pair);
-    params.distinguished_name.push(DnType::CommonName, "localhost");
-    params.subject_alt_names = vec![
-        SanType::DnsName("localhost".to_string()),
-        SanType::IpAddress(std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1))),
-    ];
-    params.is_ca = rcgen::IsCa::SelfSignedOnly;
-    params.key_usages = vec![
-        KeyUsagePurpose::DigitalSignature,
-        KeyUsagePurpose::KeyEncipherment,
-        KeyUsagePurpose::KeyAgreement,
-    ];
-    
-    // Generate the self-signed certificate
-    let cert = Certificate::from_params(params)?;
-    
-    // Get the certificate and private key in PEM format
-    let cert_pem = cert.serialize_pem()?;
-    let key_pem = cert.serialize_private_key_pem();
-    
-    // Write the certificate and private key to files
-    let cert_path = dir.join("cert.pem");
-    let key_path = dir.join("key.pem");
-    
-    let mut cert_file = File::create(&cert_path)?;
-    let mut key_file = File::create(&key_path)?;
-    
-    cert_file.write_all(cert_pem.as_bytes())?;
-    key_file.write_all(key_pem.as_bytes())?;
-    
-    Ok((cert_path, key_path))
+/// Run this command again in the background, detached from the terminal,
+/// and report its process ID once it is up
+async fn spawn_daemon() -> Result<()> {
+    let log_path = std::env::temp_dir().join(DAEMON_LOG);
+    let log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .with_context(|| format!("Failed to open {}", log_path.display()))?;
+
+    let args = std::env::args_os().skip(1).filter(|arg| arg != "--daemonize");
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(args)
+        .env(DAEMON_ENV, "1")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        // Out of the terminal's process group, so Ctrl+C there leaves it be
+        .process_group(0)
+        .spawn()
+        .context("Failed to start the producer in the background")?;
+
+    tokio::time::sleep(DAEMON_STARTUP).await;
+    if let Some(status) = child.try_wait()? {
+        bail!("The producer exited at once ({}), see {}", status, log_path.display());
+    }
+
+    println!("Publishing in the background as PID {}, logging to {}", child.id(), log_path.display());
+    println!("Stop it with: kill {}", child.id());
+    Ok(())
 }
@@ -0,0 +1,70 @@
+//! Forwarding statistics for µDCN CLI
+
+use anyhow::Result;
+use rust_udcn_daemon::mgmt::{MgmtBody, MgmtClient, MgmtRequest, StatsInfo};
+use std::path::Path;
+
+use crate::utils::print_header;
+
+/// Fetch the daemon's counters
+pub async fn fetch_stats(socket: &Path) -> Result<StatsInfo> {
+    let mut client = MgmtClient::connect(socket).await?;
+    request_stats(&mut client).await
+}
+
+/// Fetch the daemon's counters over an open control connection
+pub async fn request_stats(client: &mut MgmtClient) -> Result<StatsInfo> {
+    match client.request(&MgmtRequest::Stats).await? {
+        MgmtBody::Stats(stats) => Ok(stats),
+        other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
+    }
+}
+
+/// Show the counters of the userspace forwarder and the XDP program
+pub async fn show_stats(socket: &Path) -> Result<()> {
+    print_stats(&fetch_stats(socket).await?);
+    Ok(())
+}
+
+/// Print the counters of the userspace forwarder and the XDP program
+pub fn print_stats(stats: &StatsInfo) {
+    print_header("Forwarder");
+    println!("Faces: {}", stats.faces);
+    println!("PIT entries: {}", stats.pit_entries);
+    println!("Interests: {} received, {} forwarded, {} satisfied",
+        stats.interests_received, stats.interests_forwarded, stats.interests_satisfied);
+    println!("Data: {} received, {} sent", stats.data_received, stats.data_sent);
+    println!("Content Store: {} hits, {} misses ({:.2}% hit ratio)",
+        stats.cs_hits, stats.cs_misses, hit_ratio(stats.cs_hits, stats.cs_misses));
+    if stats.xdp.is_some() {
+        println!("Node CS hit ratio, with the kernel CS: {:.2}%", stats.cs_hit_ratio * 100.0);
+    }
+    println!("Interest RTT: p50 {:.2} ms, p90 {:.2} ms, p99 {:.2} ms",
+        stats.rtt_p50_us / 1000.0, stats.rtt_p90_us / 1000.0, stats.rtt_p99_us / 1000.0);
+    
+    match &stats.xdp {
+        Some(xdp) => {
+            print_header("XDP fast path");
+            println!("Interfaces: {}", xdp.interfaces.join(", "));
+            println!("Interests: {}", xdp.interests_received);
+            println!("Data: {}", xdp.data_received);
+            println!("CS hits: {} ({} answered in the kernel, {} expired)",
+                xdp.cs_hits, xdp.cs_replies, xdp.cs_evictions);
+            println!("FIB hits: {} ({} redirected)", xdp.fib_hits, xdp.interests_redirected);
+            println!("Drops: {} duplicate, {} looping, {} rate limited, {} out of hops, {} unsolicited, {} aborted",
+                xdp.drops_duplicate, xdp.drops_loop, xdp.drops_rate_limited, xdp.drops_hop_limit,
+                xdp.drops_unsolicited, xdp.aborted);
+            println!("Bytes: {} Interest, {} Data", xdp.interest_bytes, xdp.data_bytes);
+        }
+        None => println!("\nThe XDP fast path is not loaded."),
+    }
+}
+
+/// Percentage of lookups that hit
+fn hit_ratio(hits: u64, misses: u64) -> f64 {
+    if hits + misses == 0 {
+        0.0
+    } else {
+        hits as f64 / (hits + misses) as f64 * 100.0
+    }
+}
@@ -0,0 +1,49 @@
+//! Traceroute command for µDCN CLI
+
+use anyhow::{Context, Result};
+use rust_udcn_common::ndn::Name;
+use rust_udcn_quic::{connect_unix, NdnFace, TraceHop, TraceOutcome, Tracer};
+use std::{path::Path, sync::Arc, time::Duration};
+
+/// Find the forwarders on the path toward `name`, one HopLimit at a time
+pub async fn run_traceroute(name: String, max_hops: u8, timeout_ms: u64, unix_socket: &Path) -> Result<()> {
+    let name = Name::from_string(&name)?;
+    let face: Arc<dyn NdnFace> = Arc::new(
+        connect_unix(unix_socket)
+            .await
+            .context("Failed to connect to udcnd (is it running?)")?,
+    );
+    let tracer = Tracer::new(Arc::clone(&face), &name, Duration::from_millis(timeout_ms));
+
+    println!("traceroute to {}, {} hops max", name, max_hops);
+    let hops = tracer.run(max_hops, print_hop).await?;
+
+    match hops.last().map(|hop| &hop.outcome) {
+        Some(TraceOutcome::Reached) => {}
+        Some(TraceOutcome::Nacked(..)) => println!("{} is unreachable", name),
+        _ => println!("No Data within {} hops", max_hops),
+    }
+
+    face.close().await;
+    Ok(())
+}
+
+/// Print the answer to one probe
+fn print_hop(hop: &TraceHop) {
+    let rtt = hop
+        .rtt
+        .map(|rtt| format!("{:.3} ms", rtt.as_secs_f64() * 1000.0))
+        .unwrap_or_default();
+    let forwarder = |reporter: &Option<Name>| {
+        reporter.as_ref().map_or_else(|| "unnamed forwarder".to_string(), |name| name.to_string())
+    };
+
+    match &hop.outcome {
+        TraceOutcome::Forwarder(reporter) => println!("{:>2}  {}  {}", hop.hop, forwarder(reporter), rtt),
+        TraceOutcome::Reached => println!("{:>2}  Data  {}", hop.hop, rtt),
+        TraceOutcome::Nacked(reason, reporter) => {
+            println!("{:>2}  {}  {}  (Nack: {})", hop.hop, forwarder(reporter), rtt, reason)
+        }
+        TraceOutcome::Timeout => println!("{:>2}  *", hop.hop),
+    }
+}
@@ -1,148 +1,113 @@
 //! XDP command implementation for µDCN CLI
 
-use anyhow::{Context, Result};
-use log::{debug, info, warn};
-use std::path::PathBuf;
-use rust_udcn_xdp::XdpManager;
+use anyhow::Result;
+use log::info;
+use rust_udcn_daemon::mgmt::{MgmtBody, MgmtClient, MgmtRequest};
+use std::path::Path;
 
-use crate::XdpCommands;
+use crate::{
+    output::{print_fields, print_json, OutputFormat},
+    utils::format_bytes,
+    XdpCommands,
+};
 
 /// Handle XDP-related commands
-pub async fn handle_command(cmd: XdpCommands) -> Result<()> {
+pub async fn handle_command(cmd: XdpCommands, socket: &Path, output: OutputFormat) -> Result<()> {
     match cmd {
-        XdpCommands::Load { file, interface, skb_mode, offload } => {
-            load_xdp(file, interface, skb_mode, offload).await
+        XdpCommands::Load { interface, skb_mode, offload, chain } => {
+            let mut client = MgmtClient::connect(socket).await?;
+            load_xdp(&mut client, interface, skb_mode, offload, chain).await
         }
         XdpCommands::Unload { interface } => {
-            unload_xdp(interface).await
+            let mut client = MgmtClient::connect(socket).await?;
+            unload_xdp(&mut client, interface).await
         }
         XdpCommands::Stats => {
-            show_xdp_stats().await
+            show_xdp_stats(socket, output).await
         }
     }
 }
 
-/// Load and attach the XDP program
+/// Have the daemon attach its XDP program to an interface
 async fn load_xdp(
-    file: Option<PathBuf>,
+    client: &mut MgmtClient,
     interface: String,
     skb_mode: bool,
     offload: bool,
+    chain: bool,
 ) -> Result<()> {
-    info!("Loading XDP program...");
+    info!("Attaching the XDP program of udcnd to interface: {}", interface);
     
-    // Create XDP manager
-    let mut xdp = match file {
-        Some(path) => {
-            info!("Loading XDP program from file: {}", path.display());
-            XdpManager::load_from_file(path).await?
-        }
-        None => {
-            info!("Loading embedded XDP program");
-            XdpManager::load_from_embedded().await?
-        }
+    let request = MgmtRequest::XdpAttach { interface: interface.clone(), skb_mode, offload, chain };
+    let mode = match client.request(&request).await? {
+        MgmtBody::XdpMode(mode) => mode,
+        other => anyhow::bail!("Unexpected reply from udcnd: {:?}", other),
     };
     
-    // Attach to the specified interface
-    info!("Attaching to interface: {}", interface);
-    xdp.attach(&interface)?;
-    
-    info!("XDP program loaded and attached to {}", interface);
-    
-    // Print status information
-    println!("XDP program loaded and attached to interface: {}", interface);
-    
-    // Show initial metrics
-    let metrics = xdp.get_metrics().await;
-    println!("\nInitial metrics:");
-    println!("  Packets processed: {}", metrics.packets_total.count());
-    println!("  Interests received: {}", metrics.interests_received.count());
-    println!("  Data packets received: {}", metrics.data_received.count());
+    println!("XDP program loaded and attached to interface: {} ({} mode)", interface, mode);
     
     Ok(())
 }
 
-/// Unload and detach the XDP program
-async fn unload_xdp(interface: String) -> Result<()> {
-    info!("Unloading XDP program from interface: {}", interface);
+/// Have the daemon detach its XDP program from an interface
+async fn unload_xdp(client: &mut MgmtClient, interface: String) -> Result<()> {
+    info!("Detaching the XDP program of udcnd from interface: {}", interface);
     
-    // Create XDP manager and detach
-    // Since we don't have persistent state, we need to reload the program first
-    let mut xdp = XdpManager::load_from_embedded().await?;
+    client.request(&MgmtRequest::XdpDetach { interface: interface.clone() }).await?;
     
-    // Attach and then detach to ensure proper cleanup
-    if let Err(e) = xdp.attach(&interface) {
-        warn!("Failed to attach to interface before detaching: {}", e);
-        // Continue anyway, as we still want to try to detach
-    }
-    
-    xdp.detach_all()?;
-    
-    info!("XDP program detached from interface: {}", interface);
     println!("XDP program detached from interface: {}", interface);
     
     Ok(())
 }
 
-/// Show XDP statistics
-async fn show_xdp_stats() -> Result<()> {
+/// Show XDP statistics of the running daemon
+async fn show_xdp_stats(socket: &Path, output: OutputFormat) -> Result<()> {
     info!("Fetching XDP statistics");
     
-    // We don't have persistent state across CLI invocations
-    // In a real implementation, we would have a daemon or persistent state
-    // For now, print a message
-    println!("XDP statistics are only available for running programs.");
-    println!("To see statistics, use this command after loading the XDP program,");
-    println!("preferably in another terminal while the program is still running.");
+    let stats = super::stats::fetch_stats(socket).await?;
+    match output {
+        // null without the XDP fast path
+        OutputFormat::Json => return print_json(&stats.xdp),
+        OutputFormat::Plain => return stats.xdp.as_ref().map_or(Ok(()), print_fields),
+        OutputFormat::Table => {}
+    }
+    let Some(xdp) = stats.xdp else {
+        println!("udcnd is running without the XDP fast path.");
+        return Ok(());
+    };
     
-    // Dummy implementation: try to load the program to get current metrics
-    println!("\nAttempting to get metrics from currently running XDP program...");
+    println!("Interfaces: {}", xdp.interfaces.join(", "));
+    println!("Interests received: {}", xdp.interests_received);
+    println!("Data received: {}", xdp.data_received);
+    println!("CS hits: {}", xdp.cs_hits);
+    println!("CS replies: {}", xdp.cs_replies);
+    println!("CS entries expired: {}", xdp.cs_evictions);
+    println!("FIB hits: {}", xdp.fib_hits);
+    println!("Interests redirected: {}", xdp.interests_redirected);
+    println!("Duplicate Interests dropped: {}", xdp.drops_duplicate);
+    println!("Looping Interests dropped: {}", xdp.drops_loop);
+    println!("Rate limited Interests: {}", xdp.drops_rate_limited);
+    println!("Interests out of hops dropped: {}", xdp.drops_hop_limit);
+    println!("Unsolicited Data dropped: {}", xdp.drops_unsolicited);
+    println!("Packets aborted: {}", xdp.aborted);
+    println!("Interest bytes: {}", xdp.interest_bytes);
+    println!("Data bytes: {}", xdp.data_bytes);
+    println!("TCP segments: {}", xdp.tcp_segments);
+    println!("Interests sent: {}", xdp.egress_interests);
+    println!("Data sent: {}", xdp.egress_data);
+    println!("Interests/s: {:.1} (1s), {:.1} (10s), {:.1} (60s)",
+        xdp.interest_rate.last_1s, xdp.interest_rate.last_10s, xdp.interest_rate.last_60s);
+    println!("Throughput: {}/s (1s), {}/s (10s), {}/s (60s)",
+        format_bytes(xdp.byte_rate.last_1s as usize),
+        format_bytes(xdp.byte_rate.last_10s as usize),
+        format_bytes(xdp.byte_rate.last_60s as usize));
     
-    match XdpManager::load_from_embedded().await {
-        Ok(xdp) => {
-            if xdp.is_attached() {
-                let metrics = xdp.get_metrics().await;
-                
-                println!("\nCurrent metrics:");
-                println!("Packets:");
-                println!("  Total processed: {}", metrics.packets_total.count());
-                println!("  Interests received: {}", metrics.interests_received.count());
-                println!("  Data received: {}", metrics.data_received.count());
-                println!("  Dropped: {}", metrics.packets_dropped.count());
-                
-                println!("\nContent Store:");
-                println!("  Hits: {}", metrics.cs_hits.count());
-                println!("  Misses: {}", metrics.cs_misses.count());
-                println!("  Hit ratio: {:.2}%", 
-                    if metrics.cs_hits.count() + metrics.cs_misses.count() > 0 {
-                        (metrics.cs_hits.count() as f64 / 
-                         (metrics.cs_hits.count() + metrics.cs_misses.count()) as f64) * 100.0
-                    } else {
-                        0.0
-                    });
-                
-                println!("\nPIT:");
-                println!("  Insertions: {}", metrics.pit_inserts.count());
-                println!("  Matches: {}", metrics.pit_matches.count());
-                println!("  Expirations: {}", metrics.pit_expirations.count());
-                
-                println!("\nFIB:");
-                println!("  Lookups: {}", metrics.fib_lookups.count());
-                println!("  Hits: {}", metrics.fib_hits.count());
-                println!("  Hit ratio: {:.2}%", 
-                    if metrics.fib_lookups.count() > 0 {
-                        (metrics.fib_hits.count() as f64 / metrics.fib_lookups.count() as f64) * 100.0
-                    } else {
-                        0.0
-                    });
-                
-                println!("\nInterfaces: {:?}", xdp.attached_interfaces());
-            } else {
-                println!("No XDP program is currently attached.");
-            }
-        }
-        Err(e) => {
-            println!("Could not get metrics: {}", e);
+    if !xdp.per_interface.is_empty() {
+        println!();
+        println!("{:<16} {:>12} {:>12} {:>12} {:>14}", "INTERFACE", "INTERESTS", "DATA", "DROPS", "BYTES");
+        for stats in &xdp.per_interface {
+            println!("{:<16} {:>12} {:>12} {:>12} {:>14}",
+                stats.interface, stats.interests, stats.data, stats.drops, stats.bytes);
         }
     }
     
@@ -2,13 +2,16 @@ use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use log::{debug, error, info, trace, warn};
 use rust_udcn_common::ndn::{Data, Interest, Name};
-use rust_udcn_xdp::XdpManager;
+use rust_udcn_quic::{DEFAULT_MAX_HOPS, DEFAULT_SEGMENT_SIZE};
 use std::{net::IpAddr, path::PathBuf, time::Duration};
 use tokio::time::sleep;
 
 mod commands;
+mod output;
 mod utils;
 
+use output::OutputFormat;
+
 /// µDCN Command Line Interface
 #[derive(Parser)]
 #[clap(author, version, about)]
@@ -16,6 +19,24 @@ struct Cli {
     /// Sets the level of verbosity
     #[clap(short, long, global = true)]
     verbose: bool,
+    
+    /// Control socket of the udcnd daemon (transport.mgmt_socket of the
+    /// settings by default)
+    #[clap(long, global = true)]
+    socket: Option<PathBuf>,
+    
+    /// Read the settings from this udcnd configuration file, also given by
+    /// UDCN_CONFIG; the UDCN_* environment variables take precedence
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+    
+    /// How to print command results
+    #[clap(long, global = true, value_enum, default_value = "table")]
+    output: OutputFormat,
+    
+    /// Print command results as JSON, same as --output json
+    #[clap(long, global = true)]
+    json: bool,
 
     /// Subcommand to execute
     #[clap(subcommand)]
@@ -36,7 +57,35 @@ enum Commands {
         cmd: FibCommands,
     },
     
-    /// Send Interest and receive Data packets
+    /// Manage the faces of the daemon
+    Face {
+        #[clap(subcommand)]
+        cmd: FaceCommands,
+    },
+    
+    /// Inspect and manage the content store
+    Cs {
+        #[clap(subcommand)]
+        cmd: CsCommands,
+    },
+    
+    /// Inspect and flush the Pending Interest Tables
+    Pit {
+        #[clap(subcommand)]
+        cmd: PitCommands,
+    },
+    
+    /// Show forwarding statistics
+    Stats,
+    
+    /// Watch packet rates, CS hit rate, PIT size and per-face rates live
+    Monitor {
+        /// Refresh interval in milliseconds
+        #[clap(short, long, default_value = "1000")]
+        interval: u64,
+    },
+    
+    /// Send Interest and receive Data packets, with -v showing their wire format
     Interest {
         /// Name to request (NDN URI format)
         name: String,
@@ -48,23 +97,188 @@ enum Commands {
         /// Interface to send from
         #[clap(short, long)]
         interface: Option<String>,
+        
+        /// Number of Interests to send
+        #[clap(short, long, default_value = "1")]
+        count: u64,
+        
+        /// Time between Interests in milliseconds
+        #[clap(long, default_value = "1000")]
+        interval: u64,
+        
+        /// Set CanBePrefix, accepting Data under the name
+        #[clap(long)]
+        prefix: bool,
+        
+        /// Set MustBeFresh, refusing stale cached Data
+        #[clap(long)]
+        fresh: bool,
+        
+        /// Prefer the rightmost child among the Data matching a CanBePrefix Interest
+        #[clap(long, requires = "prefix")]
+        rightmost: bool,
+        
+        /// ApplicationParameters, as hex digits or a file to read
+        #[clap(long)]
+        app_params: Option<String>,
     },
     
-    /// Publish Data under a name
+    /// Publish a string, a file or a directory and answer Interests for it
+    #[clap(group(clap::ArgGroup::new("source").required(true).args(&["content", "file", "dir"])))]
     Publish {
         /// Name to publish under (NDN URI format)
         name: String,
         
         /// Content to publish (string)
-        content: String,
+        content: Option<String>,
+        
+        /// Publish a file, segmented under the name
+        #[clap(short, long)]
+        file: Option<PathBuf>,
+        
+        /// Publish the files under a directory, each under the name
+        /// followed by its path
+        #[clap(short, long)]
+        dir: Option<PathBuf>,
         
         /// Time-to-live in milliseconds
         #[clap(short, long, default_value = "60000")]
         ttl: u32,
         
-        /// Interface to publish on
+        /// Bytes of content in each segment of a file
+        #[clap(short, long, default_value_t = DEFAULT_SEGMENT_SIZE)]
+        segment_size: usize,
+        
+        /// Accept QUIC consumers on this address instead of registering
+        /// the name with udcnd
         #[clap(short, long)]
-        interface: Option<String>,
+        listen: Option<String>,
+        
+        /// Keep publishing in the background
+        #[clap(long)]
+        daemonize: bool,
+        
+        /// Face socket of the local udcnd (transport.unix_socket of the
+        /// settings by default)
+        #[clap(long)]
+        unix_socket: Option<PathBuf>,
+    },
+    
+    /// Check that a prefix answers, the way ndnping does
+    Ping {
+        /// Name prefix a ping server answers under (NDN URI format)
+        prefix: String,
+        
+        /// Number of pings to send (until Ctrl+C if not given)
+        #[clap(short, long)]
+        count: Option<u64>,
+        
+        /// Time between pings in milliseconds
+        #[clap(short, long, default_value = "1000")]
+        interval: u64,
+        
+        /// Time to wait for each reply in milliseconds
+        #[clap(short, long, default_value = "4000")]
+        timeout: u64,
+        
+        /// Face socket of the local udcnd (transport.unix_socket of the
+        /// settings by default)
+        #[clap(long)]
+        unix_socket: Option<PathBuf>,
+    },
+    
+    /// Answer pings under a prefix, the way ndnpingserver does
+    Pingserver {
+        /// Name prefix to answer under (NDN URI format)
+        prefix: String,
+        
+        /// Bytes of padding in each reply
+        #[clap(short, long, default_value = "0")]
+        size: usize,
+        
+        /// Face socket of the local udcnd (transport.unix_socket of the
+        /// settings by default)
+        #[clap(long)]
+        unix_socket: Option<PathBuf>,
+    },
+    
+    /// Find the forwarders on the path toward a name, with their RTTs
+    Traceroute {
+        /// Name to trace (NDN URI format)
+        name: String,
+        
+        /// Number of hops to give up after
+        #[clap(short, long, default_value_t = DEFAULT_MAX_HOPS)]
+        max_hops: u8,
+        
+        /// Time to wait for each hop in milliseconds
+        #[clap(short, long, default_value = "2000")]
+        timeout: u64,
+        
+        /// Face socket of the local udcnd (transport.unix_socket of the
+        /// settings by default)
+        #[clap(long)]
+        unix_socket: Option<PathBuf>,
+    },
+    
+    /// Show the NDN packets on an interface as they pass, after tcpdump
+    Dump {
+        /// Interface to capture on
+        #[clap(short, long)]
+        interface: String,
+        
+        /// Only show packets under this name prefix (NDN URI format)
+        #[clap(short, long)]
+        prefix: Option<String>,
+        
+        /// Only show packets of this type
+        #[clap(short = 't', long = "type", value_enum)]
+        packet_type: Option<commands::dump::PacketKind>,
+        
+        /// Number of packets to show (until Ctrl+C if not given)
+        #[clap(short, long)]
+        count: Option<u64>,
+    },
+    
+    /// Serve a file under a name, or insert it in the content store
+    Putfile {
+        /// Name to serve the file under (NDN URI format)
+        name: String,
+        
+        /// File to serve
+        path: PathBuf,
+        
+        /// Bytes of content in each segment
+        #[clap(short, long, default_value_t = DEFAULT_SEGMENT_SIZE)]
+        segment_size: usize,
+        
+        /// Insert the segments in the content store of udcnd and exit,
+        /// instead of serving them
+        #[clap(long)]
+        repo: bool,
+        
+        /// Face socket of the local udcnd (transport.unix_socket of the
+        /// settings by default)
+        #[clap(long)]
+        unix_socket: Option<PathBuf>,
+    },
+    
+    /// Fetch a segmented file and write it out
+    Getfile {
+        /// Name of the file (NDN URI format)
+        name: String,
+        
+        /// Where to write the file
+        path: PathBuf,
+        
+        /// Lifetime of each segment Interest in milliseconds
+        #[clap(short, long, default_value = "4000")]
+        timeout: u64,
+        
+        /// Face socket of the local udcnd (transport.unix_socket of the
+        /// settings by default)
+        #[clap(long)]
+        unix_socket: Option<PathBuf>,
     },
     
     /// Benchmark the NDN forwarder
@@ -80,31 +294,95 @@ enum Commands {
         /// Number of concurrent requests
         #[clap(short, long, default_value = "1")]
         concurrent: usize,
+        
+        /// Target rate in Interests per second (as fast as possible if not given)
+        #[clap(short, long)]
+        rate: Option<f64>,
+        
+        /// Number of Interests to send before measuring
+        #[clap(short, long, default_value = "0")]
+        warmup: usize,
+        
+        /// File to export the results to, with each Interest's RTT in CSV
+        #[clap(short, long)]
+        export: Option<PathBuf>,
+        
+        /// Format of the exported results
+        #[clap(long, value_enum, default_value = "json")]
+        export_format: commands::benchmark::ExportFormat,
+        
+        /// Answer benchmark Interests instead of sending them
+        #[clap(long)]
+        serve: bool,
+        
+        /// Size of the content of each Data, when serving
+        #[clap(long, default_value = "1024")]
+        payload_size: usize,
+        
+        /// Artificial latency before answering each Interest in milliseconds, when serving
+        #[clap(long, default_value = "0")]
+        delay: u64,
+        
+        /// Address to listen on, when serving
+        #[clap(long, default_value = "0.0.0.0:6367")]
+        listen: String,
+        
+        /// Measure bulk Data throughput by fetching segmented objects from a responder
+        #[clap(long)]
+        throughput: bool,
+        
+        /// Size of the object fetched in each throughput run
+        #[clap(long, default_value = "16777216")]
+        object_size: usize,
+        
+        /// Segment sizes measured in throughput mode (comma-separated)
+        #[clap(long, value_delimiter = ',', default_value = "1024,4096,8192")]
+        segment_sizes: Vec<usize>,
+        
+        /// Pipeline windows in Interests measured in throughput mode (comma-separated)
+        #[clap(long, value_delimiter = ',', default_value = "1,8,32,128")]
+        windows: Vec<usize>,
+    },
+    
+    /// Run commands interactively over connections kept open between them
+    Console,
+    
+    /// Create keys and issue certificates for NDN identities
+    Cert {
+        /// Directory of the key chain (~/.udcn/keychain by default)
+        #[clap(long)]
+        keychain: Option<PathBuf>,
+        
+        #[clap(subcommand)]
+        cmd: CertCommands,
     },
 }
 
 #[derive(Subcommand)]
 enum XdpCommands {
-    /// Load the XDP program
+    /// Attach the XDP program of udcnd to an interface, the object
+    /// loaded being the one udcnd was started with
     Load {
-        /// Path to the XDP object file
-        #[clap(short, long)]
-        file: Option<PathBuf>,
-        
         /// Interface to attach to
         #[clap(short, long)]
         interface: String,
         
-        /// Use SKB mode (slower but more compatible)
-        #[clap(long)]
+        /// Use SKB mode (slower but more compatible), which is also
+        /// fallen back to when the driver lacks native XDP
+        #[clap(long, conflicts_with = "offload")]
         skb_mode: bool,
         
         /// Use hardware offload
         #[clap(long)]
         offload: bool,
+
+        /// Share the interface with an XDP program already attached to it,
+        /// which then gets the packets that are not NDN
+        #[clap(long)]
+        chain: bool,
     },
     
-    /// Unload the XDP program
+    /// Detach the XDP program of udcnd from an interface
     Unload {
         /// Interface to detach from
         #[clap(short, long)]
@@ -122,24 +400,158 @@ enum FibCommands {
         /// Name prefix (NDN URI format)
         prefix: String,
         
-        /// Next-hop face ID
-        face: u16,
+        /// Next-hop face ID, or index of an interface the XDP program is attached to
+        face: String,
         
         /// Route cost/metric
         #[clap(short, long, default_value = "10")]
-        cost: u8,
+        cost: u32,
     },
     
     /// Remove a route from the FIB
     Remove {
         /// Name prefix (NDN URI format)
         prefix: String,
+        
+        /// Next-hop face ID or interface index
+        face: String,
     },
     
     /// Show all routes in the FIB
     Show,
 }
 
+#[derive(Subcommand)]
+enum FaceCommands {
+    /// List faces with their counters
+    List,
+    
+    /// Close a face
+    Destroy {
+        /// Face ID
+        face: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum PitCommands {
+    /// List the pending Interests, in userspace and in the kernel
+    Show,
+    
+    /// Drop pending Interests, by default only those past their lifetime
+    Flush {
+        /// Name prefix (NDN URI format)
+        #[clap(default_value = "/")]
+        prefix: String,
+        
+        /// Drop the Interests still waiting for Data as well
+        #[clap(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CsCommands {
+    /// Show the content store size
+    Info,
+    
+    /// List the cached Data under a prefix
+    Show {
+        /// Name prefix (NDN URI format)
+        #[clap(default_value = "/")]
+        prefix: String,
+    },
+    
+    /// Cache a Data packet read from a file
+    Insert {
+        /// File holding a TLV-encoded Data packet, or the content with --name
+        file: PathBuf,
+        
+        /// Cache the file's bytes as the content of a Data with this name
+        #[clap(short, long)]
+        name: Option<String>,
+        
+        /// Freshness period in milliseconds, with --name
+        #[clap(short, long, default_value = "10000", requires = "name")]
+        freshness: u32,
+    },
+    
+    /// Show occupancy and hit ratio
+    Stats,
+    
+    /// Drop cached Data under a prefix
+    Erase {
+        /// Name prefix (NDN URI format)
+        prefix: String,
+    },
+    
+    /// Drop cached Data under a prefix, from the kernel CS as well
+    Purge {
+        /// Name prefix (NDN URI format)
+        prefix: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CertCommands {
+    /// Create a key for an identity with a certificate, self-signed unless
+    /// an issuer in the key chain signs it
+    Issue {
+        /// Identity to create the key for (NDN URI format)
+        identity: String,
+        
+        /// Identity whose key signs the certificate
+        #[clap(short, long)]
+        issuer: Option<String>,
+        
+        /// Start of the validity period as YYYYMMDDThhmmss in UTC (now by default)
+        #[clap(long)]
+        valid_from: Option<String>,
+        
+        /// Length of the validity period in days
+        #[clap(short, long, default_value = "365")]
+        days: u64,
+        
+        /// Write the encoded certificate to this file, e.g. to have it signed
+        #[clap(short, long)]
+        out: Option<PathBuf>,
+    },
+    
+    /// Sign the key in a self-signed certificate with the key of an issuer
+    Sign {
+        /// File holding the self-signed certificate to sign
+        request: PathBuf,
+        
+        /// Identity whose key signs the certificate
+        #[clap(short, long)]
+        issuer: String,
+        
+        /// Start of the validity period as YYYYMMDDThhmmss in UTC (now by default)
+        #[clap(long)]
+        valid_from: Option<String>,
+        
+        /// Length of the validity period in days
+        #[clap(short, long, default_value = "365")]
+        days: u64,
+        
+        /// Where to write the signed certificate
+        #[clap(short, long)]
+        out: PathBuf,
+    },
+    
+    /// Make a certificate signed by an issuer the current one of its key
+    Install {
+        /// File holding the certificate
+        file: PathBuf,
+    },
+    
+    /// Show the current certificate of an identity
+    Show {
+        /// Identity (NDN URI format)
+        identity: String,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Parse command line arguments
@@ -150,22 +562,119 @@ async fn main() -> Result<()> {
         if cli.verbose { "debug" } else { "info" }
     )).init();
     
+    let output = if cli.json { OutputFormat::Json } else { cli.output };
+    
+    // Socket paths not given come from the settings udcnd runs with
+    let settings = utils::load_settings(cli.config.as_deref())?;
+    let socket = cli.socket.clone().unwrap_or_else(|| settings.transport.mgmt_socket.clone());
+    let face_socket = |path: Option<PathBuf>| path.unwrap_or_else(|| settings.transport.unix_socket.clone());
+    
     // Execute the specified command
     match cli.command {
         Commands::Xdp { cmd } => {
-            commands::xdp::handle_command(cmd).await?;
+            commands::xdp::handle_command(cmd, &socket, output).await?;
         },
         Commands::Fib { cmd } => {
-            commands::fib::handle_command(cmd).await?;
+            commands::fib::handle_command(cmd, &socket, output).await?;
+        },
+        Commands::Face { cmd } => {
+            commands::face::handle_command(cmd, &socket, output).await?;
+        },
+        Commands::Cs { cmd } => {
+            commands::cs::handle_command(cmd, &socket).await?;
+        },
+        Commands::Pit { cmd } => {
+            commands::pit::handle_command(cmd, &socket).await?;
+        },
+        Commands::Stats => {
+            commands::stats::show_stats(&socket).await?;
+        },
+        Commands::Monitor { interval } => {
+            commands::monitor::run_monitor(&socket, interval, output).await?;
+        },
+        Commands::Interest { name, timeout, interface, count, interval, prefix, fresh, rightmost, app_params } => {
+            commands::interest::send_interest(name, commands::interest::InterestOptions {
+                timeout_ms: timeout,
+                count,
+                interval_ms: interval,
+                can_be_prefix: prefix,
+                must_be_fresh: fresh,
+                rightmost,
+                app_params: app_params.as_deref().map(commands::interest::parse_app_params).transpose()?,
+                hexdump: cli.verbose,
+            }, interface, output).await?;
+        },
+        Commands::Publish { name, content, file, dir, ttl, segment_size, listen, daemonize, unix_socket } => {
+            let content = match (content, file, dir) {
+                (Some(text), _, _) => commands::publish::Content::Text(text),
+                (_, Some(path), _) => commands::publish::Content::File(path),
+                (_, _, Some(path)) => commands::publish::Content::Dir(path),
+                _ => unreachable!("clap requires one source"),
+            };
+            commands::publish::publish_data(commands::publish::PublishOptions {
+                name,
+                content,
+                ttl,
+                segment_size,
+                unix_socket: face_socket(unix_socket),
+                listen,
+            }, daemonize, output).await?;
+        },
+        Commands::Ping { prefix, count, interval, timeout, unix_socket } => {
+            commands::ping::run_ping(prefix, count, interval, timeout, &face_socket(unix_socket)).await?;
+        },
+        Commands::Pingserver { prefix, size, unix_socket } => {
+            commands::ping::run_ping_server(prefix, size, &face_socket(unix_socket)).await?;
+        },
+        Commands::Traceroute { name, max_hops, timeout, unix_socket } => {
+            commands::traceroute::run_traceroute(name, max_hops, timeout, &face_socket(unix_socket)).await?;
+        },
+        Commands::Dump { interface, prefix, packet_type, count } => {
+            commands::dump::run_dump(interface, prefix, packet_type, count).await?;
+        },
+        Commands::Putfile { name, path, segment_size, repo, unix_socket } => {
+            commands::file::put_file(name, &path, segment_size, repo, &face_socket(unix_socket), &socket).await?;
+        },
+        Commands::Getfile { name, path, timeout, unix_socket } => {
+            commands::file::get_file(name, &path, timeout, &face_socket(unix_socket)).await?;
+        },
+        Commands::Benchmark { serve: true, prefix, payload_size, delay, listen, .. } => {
+            commands::benchmark::run_responder(commands::benchmark::ResponderOptions {
+                prefix,
+                payload_size,
+                delay: Duration::from_millis(delay),
+                listen_addr: listen,
+            }).await?;
+        },
+        Commands::Benchmark { throughput: true, prefix, object_size, segment_sizes, windows, export, export_format, .. } => {
+            commands::benchmark::run_throughput(commands::benchmark::ThroughputOptions {
+                prefix,
+                object_size,
+                segment_sizes,
+                windows,
+                export,
+                export_format,
+                output,
+            }).await?;
         },
-        Commands::Interest { name, timeout, interface } => {
-            commands::interest::send_interest(name, timeout, interface).await?;
+        Commands::Benchmark { count, prefix, concurrent, rate, warmup, export, export_format, .. } => {
+            commands::benchmark::run_benchmark(commands::benchmark::BenchmarkOptions {
+                count,
+                prefix,
+                concurrent,
+                rate,
+                warmup,
+                export,
+                export_format,
+                output,
+            }).await?;
         },
-        Commands::Publish { name, content, ttl, interface } => {
-            commands::publish::publish_data(name, content, ttl, interface).await?;
+        Commands::Console => {
+            commands::console::run_console(&socket, output).await?;
         },
-        Commands::Benchmark { count, prefix, concurrent } => {
-            commands::benchmark::run_benchmark(count, prefix, concurrent).await?;
+        Commands::Cert { keychain, cmd } => {
+            let keychain = keychain.unwrap_or_else(commands::cert::default_keychain_dir);
+            commands::cert::handle_command(cmd, &keychain)?;
         },
     }
     
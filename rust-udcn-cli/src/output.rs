@@ -0,0 +1,89 @@
+//! Output formats of command results for µDCN CLI
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+/// How command results are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned text for people, with progress and commentary
+    #[default]
+    Table,
+
+    /// JSON, for scripts and CI
+    Json,
+
+    /// Tab-separated values without headers or units, one record per line
+    Plain,
+}
+
+impl OutputFormat {
+    /// Whether progress and commentary go along with the results
+    pub fn is_table(self) -> bool {
+        self == OutputFormat::Table
+    }
+}
+
+/// Print `value` as pretty JSON
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Print the fields of `value` as `key<TAB>value` lines, nested fields
+/// under dotted keys
+pub fn print_fields<T: Serialize>(value: &T) -> Result<()> {
+    let mut fields = Vec::new();
+    flatten("", &serde_json::to_value(value)?, &mut fields);
+    for (key, value) in fields {
+        println!("{}\t{}", key, value);
+    }
+    Ok(())
+}
+
+/// Print one record as tab-separated values
+pub fn print_row(values: &[&dyn std::fmt::Display]) {
+    let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    println!("{}", values.join("\t"));
+}
+
+/// Collect the scalar fields under `value` with their dotted keys; lists of
+/// scalars stay one comma-separated field
+fn flatten(key: &str, value: &Value, fields: &mut Vec<(String, String)>) {
+    let nested = |child: &str| {
+        if key.is_empty() {
+            child.to_string()
+        } else {
+            format!("{}.{}", key, child)
+        }
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (child, value) in map {
+                flatten(&nested(child), value, fields);
+            }
+        }
+        Value::Array(items) if items.iter().any(|item| item.is_object() || item.is_array()) => {
+            for (index, item) in items.iter().enumerate() {
+                flatten(&nested(&index.to_string()), item, fields);
+            }
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(scalar).collect();
+            fields.push((key.to_string(), items.join(",")));
+        }
+        value => fields.push((key.to_string(), scalar(value))),
+    }
+}
+
+/// A JSON scalar as plain text, strings unquoted and null empty
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
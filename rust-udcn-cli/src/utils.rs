@@ -2,7 +2,25 @@
 
 use anyhow::Result;
 use log::info;
-use std::time::{Duration, Instant};
+use rust_udcn_common::config::{ConfigLoader, Settings, CONFIG_PATH_ENV};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Settings of the local node: the defaults, under those of the
+/// configuration file at `path` or UDCN_CONFIG, under the UDCN_* variables
+pub fn load_settings(path: Option<&Path>) -> Result<Settings> {
+    let path = path
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os(CONFIG_PATH_ENV).map(PathBuf::from));
+    let mut loader = ConfigLoader::new();
+    if let Some(path) = path {
+        loader = loader.file(path)?;
+    }
+    Ok(loader.env(std::env::vars())?.load()?)
+}
 
 /// Format a duration as a human-readable string
 pub fn format_duration(duration: Duration) -> String {
@@ -82,3 +100,34 @@ pub fn print_header(title: &str) {
     println!("\n{}", title);
     println!("{}", separator);
 }
+
+/// Format bytes the way `hexdump -C` does: 16 to a line, after their
+/// offset and before their ASCII
+pub fn hexdump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let (left, right) = hex.split_at(hex.len().min(8));
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        out.push_str(&format!("{:08x}  {:<23}  {:<23}  |{}|\n", line * 16, left.join(" "), right.join(" "), ascii));
+    }
+    out.push_str(&format!("{:08x}", bytes.len()));
+    out
+}
+
+/// Write a self-signed certificate for localhost and its key to `dir`,
+/// returning their paths. For testing only
+pub fn write_self_signed_certificate(dir: &Path) -> Result<(PathBuf, PathBuf)> {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string(), "127.0.0.1".to_string()])?;
+    fs::create_dir_all(dir)?;
+
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+    fs::write(&cert_path, cert.serialize_pem()?)?;
+    fs::write(&key_path, cert.serialize_private_key_pem())?;
+
+    Ok((cert_path, key_path))
+}
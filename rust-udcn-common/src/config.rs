@@ -0,0 +1,471 @@
+//! Layered configuration shared by the daemon, the CLI and the library
+//! constructors.
+//!
+//! [`ConfigLoader`] merges, from lowest to highest precedence, the built-in
+//! defaults, a TOML file, `UDCN_*` environment variables and command line
+//! flags into typed [`Settings`], validated once merged. The file is split
+//! in sections:
+//!
+//! ```toml
+//! log_level = "info"
+//!
+//! [transport]
+//! quic_listen = "[::]:6367"
+//! routes = ["/example=router.example.net:6367"]
+//!
+//! [xdp]
+//! interfaces = ["eth0", "eth1"]
+//!
+//! [cs]
+//! capacity = 100000
+//!
+//! [security]
+//! cert = "/etc/udcn/cert.pem"
+//! key = "/etc/udcn/key.pem"
+//! ```
+//!
+//! and each setting can be overridden by the environment variable named
+//! after its key, such as `UDCN_CS_CAPACITY=100000` for `cs.capacity`.
+//! Values are read as TOML, so lists are written `["eth0","eth1"]`, and
+//! anything else is taken as a string.
+
+use serde::{Deserialize, Serialize};
+use std::{fs, net::SocketAddr, path::Path, path::PathBuf};
+use toml::{Table, Value};
+
+use crate::{ndn::Name, Error, Result};
+
+/// Prefix of the environment variables overriding settings
+pub const ENV_PREFIX: &str = "UDCN_";
+
+/// Environment variable naming the configuration file, for the tools that
+/// have no flag for it
+pub const CONFIG_PATH_ENV: &str = "UDCN_CONFIG";
+
+/// Keys of every setting, as written in the file
+pub const KEYS: &[&str] = &[
+    "log_level",
+    "transport.quic_listen",
+    "transport.udp_listen",
+    "transport.unix_socket",
+    "transport.mgmt_socket",
+    "transport.routes",
+    "transport.discover",
+    "transport.node_id",
+    "xdp.enabled",
+    "xdp.interfaces",
+    "xdp.interface_pattern",
+    "xdp.object",
+    "xdp.tc_egress",
+    "cs.capacity",
+    "cs.path",
+    "cs.kernel_entries",
+    "cs.kernel_max_bytes",
+    "security.cert",
+    "security.key",
+];
+
+/// Log levels `log_level` accepts
+const LOG_LEVELS: &[&str] = &["off", "error", "warn", "info", "debug", "trace"];
+
+/// Settings of a µDCN node
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Log level: off, error, warn, info, debug or trace
+    pub log_level: Option<String>,
+
+    pub transport: TransportSettings,
+    pub xdp: XdpSettings,
+    pub cs: CsSettings,
+    pub security: SecuritySettings,
+}
+
+/// Where faces are accepted and how to reach other forwarders
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransportSettings {
+    /// Address to accept QUIC faces on
+    pub quic_listen: String,
+
+    /// Address to accept UDP faces on
+    pub udp_listen: SocketAddr,
+
+    /// Unix socket for local applications
+    pub unix_socket: PathBuf,
+
+    /// Control socket for management commands
+    pub mgmt_socket: PathBuf,
+
+    /// Static routes to upstream QUIC forwarders, as PREFIX=HOST:PORT
+    pub routes: Vec<String>,
+
+    /// Announce the node on the LAN and connect to the neighbors heard
+    pub discover: bool,
+
+    /// Node ID announced to neighbors (the host name by default)
+    pub node_id: Option<String>,
+}
+
+impl Default for TransportSettings {
+    fn default() -> Self {
+        Self {
+            quic_listen: "[::]:6367".to_string(),
+            udp_listen: SocketAddr::from(([0u16; 8], 6363)),
+            unix_socket: PathBuf::from("/run/nfd/nfd.sock"),
+            mgmt_socket: PathBuf::from("/run/udcn/udcnd.sock"),
+            routes: Vec::new(),
+            discover: false,
+            node_id: None,
+        }
+    }
+}
+
+/// The XDP fast path
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct XdpSettings {
+    /// Load the XDP program
+    pub enabled: bool,
+
+    /// Interfaces to attach it to
+    pub interfaces: Vec<String>,
+
+    /// Patterns (e.g. `eth*`) of more interfaces to attach it to
+    pub interface_pattern: Vec<String>,
+
+    /// Object file of the program (the embedded one otherwise)
+    pub object: Option<PathBuf>,
+
+    /// Also attach the TC egress program
+    pub tc_egress: bool,
+}
+
+impl Default for XdpSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interfaces: Vec::new(),
+            interface_pattern: Vec::new(),
+            object: None,
+            tc_egress: false,
+        }
+    }
+}
+
+/// The userspace and kernel content stores
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CsSettings {
+    /// Userspace content store capacity in Data packets
+    pub capacity: usize,
+
+    /// Repo log file persisting the userspace content store
+    pub path: Option<PathBuf>,
+
+    /// Names the kernel CS holds
+    pub kernel_entries: Option<u32>,
+
+    /// Bytes of Data the kernel CS records at most
+    pub kernel_max_bytes: Option<u64>,
+}
+
+impl Default for CsSettings {
+    fn default() -> Self {
+        Self {
+            capacity: 10000,
+            path: None,
+            kernel_entries: None,
+            kernel_max_bytes: None,
+        }
+    }
+}
+
+/// TLS credentials of the QUIC server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecuritySettings {
+    /// Certificate, in PEM
+    pub cert: PathBuf,
+
+    /// Private key, in PEM
+    pub key: PathBuf,
+}
+
+impl Default for SecuritySettings {
+    fn default() -> Self {
+        Self {
+            cert: PathBuf::from("/etc/udcn/cert.pem"),
+            key: PathBuf::from("/etc/udcn/key.pem"),
+        }
+    }
+}
+
+impl Settings {
+    /// Check the settings make sense together
+    pub fn validate(&self) -> Result<()> {
+        if let Some(level) = &self.log_level {
+            if !LOG_LEVELS.contains(&level.to_ascii_lowercase().as_str()) {
+                return Err(invalid("log_level", format!("unknown log level {}", level)));
+            }
+        }
+
+        let transport = &self.transport;
+        let port = transport.quic_listen.rsplit_once(':').map(|(_, port)| port.parse::<u16>());
+        if !matches!(port, Some(Ok(_))) {
+            return Err(invalid("transport.quic_listen", format!("expected HOST:PORT, got {}", transport.quic_listen)));
+        }
+        for route in &transport.routes {
+            let (prefix, addr) = route
+                .split_once('=')
+                .ok_or_else(|| invalid("transport.routes", format!("expected PREFIX=HOST:PORT, got {}", route)))?;
+            if addr.is_empty() {
+                return Err(invalid("transport.routes", format!("missing upstream address for {}", prefix)));
+            }
+            Name::from_string(prefix).map_err(|e| invalid("transport.routes", e.to_string()))?;
+        }
+
+        if self.cs.kernel_entries == Some(0) {
+            return Err(invalid("cs.kernel_entries", "must be positive"));
+        }
+        if self.cs.kernel_max_bytes == Some(0) {
+            return Err(invalid("cs.kernel_max_bytes", "must be positive"));
+        }
+        for (key, path) in [("security.cert", &self.security.cert), ("security.key", &self.security.key)] {
+            if path.as_os_str().is_empty() {
+                return Err(invalid(key, "must not be empty"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error for an invalid value of `key`
+fn invalid(key: &str, reason: impl std::fmt::Display) -> Error {
+    Error::Config(format!("{}: {}", key, reason))
+}
+
+/// Name of the environment variable overriding `key`
+pub fn env_var(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.replace('.', "_").to_ascii_uppercase())
+}
+
+/// Merges the configuration layers, each added one overriding those before
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    /// Settings merged so far, starting from the defaults
+    merged: Table,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigLoader {
+    /// Start from the default settings
+    pub fn new() -> Self {
+        let merged = Table::try_from(Settings::default()).expect("default settings serialize");
+        Self { merged }
+    }
+
+    /// Add the settings of the TOML file at `path`
+    pub fn file(self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| Error::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+        self.toml(&text, &path.display().to_string())
+    }
+
+    /// Add the settings of the TOML document `text`, read from `origin`
+    pub fn toml(self, text: &str, origin: &str) -> Result<Self> {
+        let layer: Table = toml::from_str(text).map_err(|e| Error::Config(format!("{}: {}", origin, e)))?;
+        self.layer(layer, origin)
+    }
+
+    /// Add the settings given by `UDCN_*` variables among `vars`, as read
+    /// from [`std::env::vars`]; other variables are left alone
+    pub fn env(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<Self> {
+        for (name, text) in vars {
+            let Some(key) = KEYS.iter().find(|key| env_var(key) == name) else {
+                continue;
+            };
+            // Not TOML, such as an unquoted path, is a string
+            let value = toml::from_str::<Table>(&format!("value = {}", text))
+                .ok()
+                .and_then(|mut table| table.remove("value"))
+                .unwrap_or(Value::String(text));
+            self = self.set(key, value, &name)?;
+        }
+        Ok(self)
+    }
+
+    /// Add the settings `keys` of `flags`, the settings the command line
+    /// stands for, for the flags it gave
+    pub fn flags<'a>(mut self, flags: &Settings, keys: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let flags = Table::try_from(flags).map_err(|e| Error::Config(e.to_string()))?;
+        for key in keys {
+            let (section, field) = split_key(key)?;
+            let value = match section {
+                Some(section) => flags.get(section).and_then(|s| s.get(field)),
+                None => flags.get(field),
+            };
+            match value {
+                Some(value) => self = self.set(key, value.clone(), "the command line")?,
+                // An unset option
+                None => self.remove(key),
+            }
+        }
+        Ok(self)
+    }
+
+    /// The merged settings, once checked
+    pub fn load(self) -> Result<Settings> {
+        let settings: Settings = self
+            .merged
+            .try_into()
+            .map_err(|e: toml::de::Error| Error::Config(e.to_string()))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Set `key` to `value`, given by `origin`
+    fn set(self, key: &str, value: Value, origin: &str) -> Result<Self> {
+        let (section, field) = split_key(key)?;
+        let mut layer = Table::new();
+        match section {
+            Some(section) => {
+                let mut table = Table::new();
+                table.insert(field.to_string(), value);
+                layer.insert(section.to_string(), Value::Table(table));
+            }
+            None => {
+                layer.insert(field.to_string(), value);
+            }
+        }
+        self.layer(layer, origin)
+    }
+
+    /// Unset the optional setting `key`
+    fn remove(&mut self, key: &str) {
+        let table = match key.split_once('.') {
+            Some((section, _)) => self.merged.get_mut(section).and_then(Value::as_table_mut),
+            None => Some(&mut self.merged),
+        };
+        if let Some(table) = table {
+            table.remove(key.rsplit('.').next().unwrap_or(key));
+        }
+    }
+
+    /// Merge `layer` over the settings so far, after checking it alone
+    fn layer(mut self, layer: Table, origin: &str) -> Result<Self> {
+        layer
+            .clone()
+            .try_into::<Settings>()
+            .map_err(|e| Error::Config(format!("{}: {}", origin, e)))?;
+        merge(&mut self.merged, layer);
+        Ok(self)
+    }
+}
+
+/// Section and field of `key`
+fn split_key(key: &str) -> Result<(Option<&str>, &str)> {
+    if !KEYS.contains(&key) {
+        return Err(Error::Config(format!("Unknown setting {}", key)));
+    }
+    Ok(match key.split_once('.') {
+        Some((section, field)) => (Some(section), field),
+        None => (None, key),
+    })
+}
+
+/// Merge `layer` into `base`, section by section
+fn merge(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(layer)) => merge(base, layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers() {
+        let file = r#"
+            log_level = "debug"
+
+            [transport]
+            routes = ["/example=router.example.net:6367"]
+
+            [xdp]
+            interfaces = ["eth0", "eth1"]
+
+            [cs]
+            capacity = 100
+            path = "/var/lib/udcn/cs.log"
+        "#;
+        let env = [
+            ("UDCN_CS_CAPACITY".to_string(), "200".to_string()),
+            ("UDCN_XDP_INTERFACES".to_string(), "[\"eth2\"]".to_string()),
+            ("UDCN_SECURITY_CERT".to_string(), "/tmp/cert.pem".to_string()),
+            ("UDCN_PUBLISH_DAEMON".to_string(), "1".to_string()),
+        ];
+        let flags = Settings {
+            cs: CsSettings {
+                capacity: 5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let settings = ConfigLoader::new()
+            .toml(file, "udcnd.toml")
+            .unwrap()
+            .env(env)
+            .unwrap()
+            .flags(&flags, ["cs.capacity", "cs.path"])
+            .unwrap()
+            .load()
+            .unwrap();
+
+        assert_eq!(settings.log_level.as_deref(), Some("debug"));
+        assert_eq!(settings.transport.routes, ["/example=router.example.net:6367"]);
+        // The environment wins over the file, and flags over both
+        assert_eq!(settings.xdp.interfaces, ["eth2"]);
+        assert_eq!(settings.security.cert, PathBuf::from("/tmp/cert.pem"));
+        assert_eq!(settings.cs.capacity, 5);
+        // An option the command line left unset
+        assert_eq!(settings.cs.path, None);
+        // Left alone
+        assert_eq!(settings.transport, TransportSettings {
+            routes: settings.transport.routes.clone(),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let load = |text: &str| ConfigLoader::new().toml(text, "test").and_then(ConfigLoader::load);
+
+        assert!(load("").is_ok());
+        // Misspelled and misplaced keys
+        assert!(load("[xdp]\ninterface = [\"eth0\"]").is_err());
+        assert!(load("capacity = 5").is_err());
+        assert!(load("[cs]\ncapacity = \"many\"").is_err());
+        assert!(load("log_level = \"loud\"").is_err());
+        assert!(load("[transport]\nquic_listen = \"localhost\"").is_err());
+        assert!(load("[transport]\nroutes = [\"/example\"]").is_err());
+        assert!(load("[cs]\nkernel_max_bytes = 0").is_err());
+
+        let env = [("UDCN_CS_CAPACITY".to_string(), "lots".to_string())];
+        assert!(ConfigLoader::new().env(env).is_err());
+        assert_eq!(env_var("transport.quic_listen"), "UDCN_TRANSPORT_QUIC_LISTEN");
+    }
+}
@@ -21,6 +21,10 @@ pub enum Error {
     #[error("eBPF/XDP error: {0}")]
     Ebpf(String),
     
+    /// Invalid or unreadable configuration
+    #[error("Configuration error: {0}")]
+    Config(String),
+    
     /// I/O error
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
@@ -8,6 +8,7 @@ pub mod tlv;
 pub mod metrics;
 pub mod types;
 pub mod error;
+pub mod config;
 
 /// Reexport of common types
 pub use error::Error;
@@ -1,9 +1,15 @@
 //! Metrics collection and reporting for μDCN.
 //!
 //! This module provides utilities for tracking and reporting performance metrics
-//! across both userspace and kernel components.
+//! across both userspace and kernel components. Metrics can be broken down by
+//! face, prefix and packet type through [`Labels`].
 
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::Deref;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::time::{Duration, Instant};
 
 /* ---------------------------------------------------------------- *
@@ -174,6 +180,217 @@ impl Histogram {
     pub fn sum(&self) -> u64 {
         self.sum.load(Ordering::Relaxed)
     }
+
+    /// Estimate the `q` quantile, 0.0 to 1.0, from the buckets
+    pub fn quantile(&self, q: f64) -> f64 {
+        self.snapshot().quantile(q)
+    }
+
+    /// Copy of the current counts
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            boundaries: self.boundaries.clone(),
+            buckets: self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect(),
+            underflow: self.underflow.load(Ordering::Relaxed),
+            overflow: self.overflow.load(Ordering::Relaxed),
+            sum: self.sum.load(Ordering::Relaxed),
+            count: self.count.load(Ordering::Relaxed),
+        }
+    }
+
+    /// What was observed since `previous`, a snapshot of this histogram
+    pub fn delta(&self, previous: &HistogramSnapshot) -> HistogramSnapshot {
+        self.snapshot().delta(previous)
+    }
+}
+
+/// Counts of a [`Histogram`] at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistogramSnapshot {
+    /// Upper bounds of the buckets
+    pub boundaries: Vec<u64>,
+    /// Observations per bucket; bucket `i` holds values up to
+    /// `boundaries[i]`, above the previous boundary
+    pub buckets: Vec<u64>,
+    /// Observations below the first boundary
+    pub underflow: u64,
+    /// Observations above the last boundary
+    pub overflow: u64,
+    pub sum: u64,
+    pub count: u64,
+}
+
+impl HistogramSnapshot {
+    pub fn average(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum as f64 / self.count as f64
+        }
+    }
+
+    /// Estimate the `q` quantile, 0.0 to 1.0, interpolating linearly within
+    /// the bucket it falls in
+    ///
+    /// Values above the last boundary are reported as the last boundary.
+    pub fn quantile(&self, q: f64) -> f64 {
+        let total = self.underflow + self.overflow + self.buckets.iter().sum::<u64>();
+        if total == 0 || self.boundaries.is_empty() {
+            return 0.0;
+        }
+        let rank = q.clamp(0.0, 1.0) * total as f64;
+
+        // Each range of values with its count, in order
+        let first = self.boundaries[0] as f64;
+        let ranges = std::iter::once((0.0, first, self.underflow)).chain(
+            self.buckets.iter().enumerate().map(|(i, &count)| {
+                let lower = if i == 0 { first } else { self.boundaries[i - 1] as f64 };
+                (lower, self.boundaries[i] as f64, count)
+            }),
+        );
+
+        let mut seen = 0.0;
+        for (lower, upper, count) in ranges {
+            if count == 0 {
+                continue;
+            }
+            let count = count as f64;
+            if seen + count >= rank {
+                return lower + (upper - lower) * (rank - seen) / count;
+            }
+            seen += count;
+        }
+        *self.boundaries.last().unwrap() as f64
+    }
+
+    /// What was observed between `previous` and this snapshot, both taken
+    /// from the same histogram
+    pub fn delta(&self, previous: &HistogramSnapshot) -> HistogramSnapshot {
+        HistogramSnapshot {
+            boundaries: self.boundaries.clone(),
+            buckets: self
+                .buckets
+                .iter()
+                .zip(previous.buckets.iter().chain(std::iter::repeat(&0)))
+                .map(|(now, before)| now.saturating_sub(*before))
+                .collect(),
+            underflow: self.underflow.saturating_sub(previous.underflow),
+            overflow: self.overflow.saturating_sub(previous.overflow),
+            sum: self.sum.saturating_sub(previous.sum),
+            count: self.count.saturating_sub(previous.count),
+        }
+    }
+}
+
+/* ---------------------------------------------------------------- *
+ * Meter
+ * ---------------------------------------------------------------- */
+
+/// Seconds a meter remembers: the longest window, plus the current second
+const METER_SLOTS: u64 = 61;
+/// Each slot packs the second it counts, in the high bits, with its count
+const METER_COUNT_BITS: u32 = 40;
+const METER_COUNT_MASK: u64 = (1 << METER_COUNT_BITS) - 1;
+const METER_EPOCH_MASK: u64 = (1 << (64 - METER_COUNT_BITS)) - 1;
+
+/// Moving rates over the last second, 10 seconds and minute
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Rates {
+    pub last_1s: f64,
+    pub last_10s: f64,
+    pub last_60s: f64,
+}
+
+/// Rate of events per second, over sliding windows of up to a minute
+///
+/// Events are counted per second of a ring, so marking is a single atomic
+/// update.
+#[derive(Debug)]
+pub struct Meter {
+    started: Instant,
+    slots: Vec<AtomicU64>,
+    /// Last total passed to `update`, `u64::MAX` before the first
+    last_total: AtomicU64,
+}
+
+impl Meter {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            slots: (0..METER_SLOTS).map(|_| AtomicU64::new(0)).collect(),
+            last_total: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// Count `value` events now
+    pub fn mark(&self, value: u64) {
+        self.mark_at(self.started.elapsed().as_secs(), value);
+    }
+
+    /// Count the increase of a cumulative counter since the previous
+    /// update; the first one only sets the baseline
+    pub fn update(&self, total: u64) {
+        let previous = self.last_total.swap(total, Ordering::Relaxed);
+        if previous != u64::MAX {
+            self.mark(total.saturating_sub(previous));
+        }
+    }
+
+    /// Events per second over the last `window`, up to a minute
+    pub fn rate(&self, window: Duration) -> f64 {
+        self.rate_at(self.started.elapsed().as_secs_f64(), window.as_secs())
+    }
+
+    pub fn rates(&self) -> Rates {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        Rates {
+            last_1s: self.rate_at(elapsed, 1),
+            last_10s: self.rate_at(elapsed, 10),
+            last_60s: self.rate_at(elapsed, 60),
+        }
+    }
+
+    fn mark_at(&self, second: u64, value: u64) {
+        let epoch = (second & METER_EPOCH_MASK) << METER_COUNT_BITS;
+        let slot = &self.slots[(second % METER_SLOTS) as usize];
+        let _ = slot.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+            // A slot still holding an older second starts over
+            let count = if current & !METER_COUNT_MASK == epoch {
+                current & METER_COUNT_MASK
+            } else {
+                0
+            };
+            Some(epoch | count.saturating_add(value).min(METER_COUNT_MASK))
+        });
+    }
+
+    /// Rate over the `window` seconds before `elapsed`; the second that
+    /// only partly falls in the window is weighted by how much of it does
+    fn rate_at(&self, elapsed: f64, window: u64) -> f64 {
+        let window = window.clamp(1, METER_SLOTS - 1);
+        let span = (window as f64).min(elapsed);
+        if span <= 0.0 {
+            return 0.0;
+        }
+
+        let now = elapsed as u64;
+        let mut events = 0.0;
+        for second in now.saturating_sub(window)..=now {
+            let slot = self.slots[(second % METER_SLOTS) as usize].load(Ordering::Relaxed);
+            if slot >> METER_COUNT_BITS != second & METER_EPOCH_MASK {
+                continue;
+            }
+            let weight = if second + window == now { 1.0 - elapsed.fract() } else { 1.0 };
+            events += (slot & METER_COUNT_MASK) as f64 * weight;
+        }
+        events / span
+    }
+}
+
+impl Default for Meter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /* ---------------------------------------------------------------- *
@@ -187,11 +404,12 @@ pub struct Timer {
 }
 
 impl Timer {
+    /// Timer with buckets from 10µs to 10s, about 40% apart, fine enough
+    /// for quantiles
     pub fn new() -> Self {
-        let boundaries = vec![10, 100, 1_000, 10_000, 100_000, 1_000_000, 10_000_000];
         Self {
             start: None,
-            histogram: Histogram::new(boundaries),
+            histogram: Histogram::exponential(10, 10_000_000, 40),
         }
     }
 
@@ -228,51 +446,603 @@ impl Default for Timer {
 }
 
 /* ---------------------------------------------------------------- *
- * Aggregate metrics for µDCN
+ * Labels
  * ---------------------------------------------------------------- */
 
+/// Kind of NDN packet a series counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PacketType {
+    Interest,
+    Data,
+    Nack,
+}
+
+impl PacketType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PacketType::Interest => "interest",
+            PacketType::Data => "data",
+            PacketType::Nack => "nack",
+        }
+    }
+}
+
+impl fmt::Display for PacketType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What a series is broken down by; unset labels match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Labels {
+    /// Face the traffic went through
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub face: Option<String>,
+    /// Name prefix, usually the FIB entry the traffic matched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// Kind of packet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packet_type: Option<PacketType>,
+}
+
+impl Labels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn face(mut self, face: impl Into<String>) -> Self {
+        self.face = Some(face.into());
+        self
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn packet_type(mut self, packet_type: PacketType) -> Self {
+        self.packet_type = Some(packet_type);
+        self
+    }
+
+    /// Whether every label set in `filter` has the same value here
+    pub fn matches(&self, filter: &Labels) -> bool {
+        fn field<T: PartialEq>(value: &Option<T>, filter: &Option<T>) -> bool {
+            filter.is_none() || value == filter
+        }
+        field(&self.face, &filter.face)
+            && field(&self.prefix, &filter.prefix)
+            && field(&self.packet_type, &filter.packet_type)
+    }
+}
+
+impl fmt::Display for Labels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut labels = Vec::new();
+        if let Some(face) = &self.face {
+            labels.push(format!("face={}", face));
+        }
+        if let Some(prefix) = &self.prefix {
+            labels.push(format!("prefix={}", prefix));
+        }
+        if let Some(packet_type) = &self.packet_type {
+            labels.push(format!("type={}", packet_type));
+        }
+        f.write_str(&labels.join(","))
+    }
+}
+
+/* ---------------------------------------------------------------- *
+ * Labeled family
+ * ---------------------------------------------------------------- */
+
+/// A metric broken down by labels
+///
+/// The total counts everything and derefs to the metric itself, so an
+/// unlabeled family is used like a plain counter or gauge. Series are
+/// created on first use and updated through their own atomics; only
+/// finding one takes a read lock.
 #[derive(Debug, Default)]
-pub struct UdcnMetrics {
-    // Packet processing metrics
-    pub interests_received: Counter,
-    pub interests_satisfied: Counter,
-    pub interests_timed_out: Counter,
-    /// Number of Interests sent out
-    pub interests_sent: Counter,
-    pub interests_forwarded: Counter,
-    pub data_received: Counter,
-    pub data_sent: Counter,
-
-    // Cache metrics
-    pub cs_hits: Counter,
-    pub cs_misses: Counter,
-    pub cs_inserts: Counter,
-    pub cs_evictions: Counter,
-    pub cs_size: Gauge,
-
-    // PIT metrics
-    pub pit_inserts: Counter,
-    pub pit_hits: Counter,
-    pub pit_misses: Counter,
-    pub pit_expirations: Counter,
-    pub pit_size: Gauge,
-
-    // FIB metrics
-    pub fib_hits: Counter,
-    pub fib_misses: Counter,
-    pub fib_size: Gauge,
-
-    // Performance metrics
-    pub interest_processing_time: Timer,
-    pub data_processing_time: Timer,
-
-    // Transport metrics
-    pub bytes_received: Counter,
-    pub bytes_sent: Counter,
+pub struct Family<M> {
+    total: M,
+    series: RwLock<HashMap<Labels, Arc<M>>>,
+}
+
+impl<M> Family<M> {
+    /// The series for `labels`, if it was ever recorded
+    pub fn get(&self, labels: &Labels) -> Option<Arc<M>> {
+        self.read().get(labels).cloned()
+    }
+
+    /// Every series, ordered by labels
+    pub fn series(&self) -> Vec<(Labels, Arc<M>)> {
+        let mut series: Vec<_> = self
+            .read()
+            .iter()
+            .map(|(labels, metric)| (labels.clone(), Arc::clone(metric)))
+            .collect();
+        series.sort_by(|a, b| a.0.cmp(&b.0));
+        series
+    }
+
+    /// Drop the series matching `filter`; the total is unaffected
+    pub fn remove(&self, filter: &Labels) {
+        self.write().retain(|labels, _| !labels.matches(filter));
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<Labels, Arc<M>>> {
+        self.series.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<Labels, Arc<M>>> {
+        self.series.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<M: Default> Family<M> {
+    /// The series for `labels`, created if needed
+    pub fn with(&self, labels: &Labels) -> Arc<M> {
+        if let Some(metric) = self.read().get(labels) {
+            return Arc::clone(metric);
+        }
+        Arc::clone(self.write().entry(labels.clone()).or_default())
+    }
+}
+
+impl<M> Family<M> {
+    /// Copy of the total and every series, as read by `value`
+    pub fn snapshot<T>(&self, value: impl Fn(&M) -> T) -> FamilySnapshot<T> {
+        FamilySnapshot {
+            total: value(&self.total),
+            series: self
+                .series()
+                .into_iter()
+                .map(|(labels, metric)| SeriesSnapshot {
+                    labels,
+                    value: value(&metric),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<M> Deref for Family<M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        &self.total
+    }
+}
+
+impl Family<Counter> {
+    /// Count `value` in the total and in the series for `labels`
+    pub fn add_labeled(&self, labels: &Labels, value: u64) {
+        self.total.add(value);
+        self.with(labels).add(value);
+    }
+
+    /// Sum of the series matching `filter`
+    pub fn sum(&self, filter: &Labels) -> u64 {
+        self.read()
+            .iter()
+            .filter(|(labels, _)| labels.matches(filter))
+            .map(|(_, counter)| counter.value())
+            .sum()
+    }
+}
+
+impl Family<Timer> {
+    /// Record `value` in the total and in the series for `labels`
+    pub fn observe_labeled(&self, labels: &Labels, value: u64) {
+        self.total.histogram().observe(value);
+        self.with(labels).histogram().observe(value);
+    }
+}
+
+/* ---------------------------------------------------------------- *
+ * Snapshots
+ * ---------------------------------------------------------------- */
+
+/// A [`Family`] at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FamilySnapshot<T> {
+    pub total: T,
+    /// Labeled series, ordered by labels
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    pub series: Vec<SeriesSnapshot<T>>,
+}
+
+/// One labeled series of a [`FamilySnapshot`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeriesSnapshot<T> {
+    pub labels: Labels,
+    pub value: T,
+}
+
+/// Every metric of a node at one point in time, by name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub counters: BTreeMap<String, FamilySnapshot<u64>>,
+    pub gauges: BTreeMap<String, FamilySnapshot<u64>>,
+    /// Timers, in microseconds
+    pub histograms: BTreeMap<String, FamilySnapshot<HistogramSnapshot>>,
+    /// Meters, in events per second
+    #[serde(default)]
+    pub meters: BTreeMap<String, FamilySnapshot<Rates>>,
+}
+
+/* ---------------------------------------------------------------- *
+ * Aggregate metrics for µDCN
+ * ---------------------------------------------------------------- */
+
+/// Declare the metrics of [`UdcnMetrics`], by kind, so they can be listed
+/// by name
+macro_rules! udcn_metrics {
+    (
+        counters { $($(#[$counter_doc:meta])* $counter:ident,)* }
+        gauges { $($(#[$gauge_doc:meta])* $gauge:ident,)* }
+        timers { $($(#[$timer_doc:meta])* $timer:ident,)* }
+        meters { $($(#[$meter_doc:meta])* $meter:ident,)* }
+    ) => {
+        /// Registry of the metrics of a node
+        ///
+        /// Each metric is a [`Family`]: a node-wide total, plus series
+        /// broken down by face, prefix or packet type where recorded with
+        /// labels.
+        #[derive(Debug, Default)]
+        pub struct UdcnMetrics {
+            $($(#[$counter_doc])* pub $counter: Family<Counter>,)*
+            $($(#[$gauge_doc])* pub $gauge: Family<Gauge>,)*
+            $($(#[$timer_doc])* pub $timer: Family<Timer>,)*
+            $($(#[$meter_doc])* pub $meter: Family<Meter>,)*
+        }
+
+        impl UdcnMetrics {
+            /// Every counter, by name
+            pub fn counters(&self) -> Vec<(&'static str, &Family<Counter>)> {
+                vec![$((stringify!($counter), &self.$counter),)*]
+            }
+
+            /// Every gauge, by name
+            pub fn gauges(&self) -> Vec<(&'static str, &Family<Gauge>)> {
+                vec![$((stringify!($gauge), &self.$gauge),)*]
+            }
+
+            /// Every timer, by name
+            pub fn timers(&self) -> Vec<(&'static str, &Family<Timer>)> {
+                vec![$((stringify!($timer), &self.$timer),)*]
+            }
+
+            /// Every meter, by name
+            pub fn meters(&self) -> Vec<(&'static str, &Family<Meter>)> {
+                vec![$((stringify!($meter), &self.$meter),)*]
+            }
+
+            /// Drop the series matching `filter` from every metric
+            pub fn remove(&self, filter: &Labels) {
+                $(self.$counter.remove(filter);)*
+                $(self.$gauge.remove(filter);)*
+                $(self.$timer.remove(filter);)*
+                $(self.$meter.remove(filter);)*
+            }
+        }
+    };
+}
+
+udcn_metrics! {
+    counters {
+        // Packet processing metrics
+        interests_received,
+        interests_satisfied,
+        interests_timed_out,
+        /// Number of Interests sent out
+        interests_sent,
+        interests_forwarded,
+        /// Number of Interests retransmitted after going unanswered
+        interests_retransmitted,
+        /// Number of Interests that joined an identical outstanding Interest
+        interests_aggregated,
+        data_received,
+        data_sent,
+        /// Number of network Nacks received
+        nacks_received,
+        /// Number of network Nacks sent
+        nacks_sent,
+
+        // Cache metrics
+        cs_hits,
+        cs_misses,
+        cs_inserts,
+        cs_evictions,
+
+        // PIT metrics
+        pit_inserts,
+        pit_hits,
+        pit_misses,
+        pit_expirations,
+
+        // FIB metrics
+        fib_hits,
+        fib_misses,
+
+        // Transport metrics
+        bytes_received,
+        bytes_sent,
+
+        // Fragmentation metrics
+        fragments_sent,
+        fragments_received,
+        /// Messages reassembled from their fragments
+        messages_reassembled,
+        /// Incomplete messages dropped to stay within the reassembly budget
+        /// of their face, or because their fragments disagreed
+        reassembly_drops,
+        /// Incomplete messages dropped because fragments were still missing
+        /// after the reassembly timeout
+        reassembly_timeouts,
+
+        // Face lifecycle metrics
+        /// Faces closed because they stayed idle too long
+        faces_evicted,
+        /// Connections rejected because the connection limit was reached
+        connections_refused,
+
+        // Forwarder scheduling metrics
+        /// Packets the forwarder dropped because the output queue of their
+        /// face was full
+        forwarder_queue_drops,
+    }
+    gauges {
+        cs_size,
+        pit_size,
+        fib_size,
+        /// Number of currently open server faces
+        faces_active,
+        /// Packets waiting in the forwarder's output queues, by face
+        forwarder_queue_depth,
+        /// Fragment payload bytes held for incomplete messages, by face
+        reassembly_buffered_bytes,
+
+        // Kernel fast path metrics, mirrored from the XDP program's counters
+        /// NDN packets the XDP program saw
+        kernel_packets,
+        /// Interests the XDP program received
+        kernel_interests_received,
+        /// Data packets the XDP program received
+        kernel_data_received,
+        /// Interests answered from the kernel CS, which never reach userspace
+        kernel_cs_hits,
+        /// Data recorded in the kernel CS
+        kernel_cs_inserts,
+        /// Interests matching a kernel FIB entry
+        kernel_fib_hits,
+        /// Interests the kernel forwarded itself
+        kernel_interests_forwarded,
+        /// Packets the kernel dropped, for any reason
+        kernel_drops,
+        /// Bytes of NDN packets the XDP program received
+        kernel_bytes_received,
+        /// Bytes of Data the kernel CS sent
+        kernel_bytes_sent,
+    }
+    timers {
+        // Performance metrics
+        interest_processing_time,
+        data_processing_time,
+    }
+    meters {
+        /// Interests per second the XDP program received
+        kernel_interest_rate,
+        /// Bytes per second of NDN packets the XDP program received
+        kernel_byte_rate,
+    }
 }
 
 impl UdcnMetrics {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Copy of every metric, for reporting
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            counters: self
+                .counters()
+                .into_iter()
+                .map(|(name, family)| (name.to_string(), family.snapshot(Counter::value)))
+                .collect(),
+            gauges: self
+                .gauges()
+                .into_iter()
+                .map(|(name, family)| (name.to_string(), family.snapshot(Gauge::value)))
+                .collect(),
+            histograms: self
+                .timers()
+                .into_iter()
+                .map(|(name, family)| (name.to_string(), family.snapshot(|timer| timer.histogram().snapshot())))
+                .collect(),
+            meters: self
+                .meters()
+                .into_iter()
+                .map(|(name, family)| (name.to_string(), family.snapshot(Meter::rates)))
+                .collect(),
+        }
+    }
+
+    /// Drop the series of a face that went away
+    pub fn remove_face(&self, face: &str) {
+        self.remove(&Labels::new().face(face));
+    }
+
+    /// Share of the Interests looked up in a CS, kernel or userspace, that
+    /// were answered from it
+    ///
+    /// Interests the kernel CS misses go on to the userspace CS, so they
+    /// are counted once, there.
+    pub fn cs_hit_ratio(&self) -> f64 {
+        let hits = self.cs_hits.value() + self.kernel_cs_hits.value();
+        let lookups = hits + self.cs_misses.value();
+        if lookups == 0 {
+            0.0
+        } else {
+            hits as f64 / lookups as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cs_hit_ratio() {
+        let metrics = UdcnMetrics::new();
+        assert_eq!(metrics.cs_hit_ratio(), 0.0);
+
+        metrics.cs_hits.add(1);
+        metrics.cs_misses.add(2);
+        metrics.kernel_cs_hits.set(5);
+        assert_eq!(metrics.cs_hit_ratio(), 0.75);
+    }
+
+    #[test]
+    fn test_histogram_quantiles() {
+        let histogram = Histogram::new(vec![10, 20, 30, 40]);
+        assert_eq!(histogram.quantile(0.5), 0.0);
+
+        // Ten values in each of (10, 20] and (20, 30]
+        for _ in 0..10 {
+            histogram.observe(15);
+            histogram.observe(25);
+        }
+        assert_eq!(histogram.quantile(0.5), 20.0);
+        assert_eq!(histogram.quantile(0.25), 15.0);
+        assert_eq!(histogram.quantile(1.0), 30.0);
+
+        // Values past the last boundary are reported at it
+        for _ in 0..80 {
+            histogram.observe(1000);
+        }
+        assert_eq!(histogram.quantile(0.99), 40.0);
+    }
+
+    #[test]
+    fn test_histogram_delta() {
+        let histogram = Histogram::new(vec![10, 100]);
+        histogram.observe(5);
+        histogram.observe(50);
+        let before = histogram.snapshot();
+
+        histogram.observe(50);
+        histogram.observe(500);
+        let delta = histogram.delta(&before);
+        assert_eq!(delta.buckets, vec![0, 1]);
+        assert_eq!(delta.underflow, 0);
+        assert_eq!(delta.overflow, 1);
+        assert_eq!(delta.count, 2);
+        assert_eq!(delta.average(), 275.0);
+        // The snapshot does not move with the histogram
+        assert_eq!(before.count, 2);
+    }
+
+    #[test]
+    fn test_meter_rates() {
+        let meter = Meter::new();
+        assert_eq!(meter.rate_at(0.0, 1), 0.0);
+
+        // 10 events per second for 20 seconds
+        for second in 0..20 {
+            meter.mark_at(second, 10);
+        }
+        assert_eq!(meter.rate_at(20.0, 1), 10.0);
+        assert_eq!(meter.rate_at(20.0, 10), 10.0);
+        // Only 20 of the 60 seconds went by
+        assert_eq!(meter.rate_at(20.0, 60), 10.0);
+        // Half of second 19 is still in the last second
+        assert_eq!(meter.rate_at(20.5, 1), 5.0);
+
+        // Second 61 takes the slot of second 0 over
+        meter.mark_at(61, 100);
+        assert_eq!(meter.rate_at(61.5, 1), 100.0);
+        assert_eq!(meter.rate_at(61.5, 60), (5.0 + 180.0 + 100.0) / 60.0);
+        assert_eq!(meter.rate_at(62.5, 1), 50.0);
+    }
+
+    #[test]
+    fn test_meter_update() {
+        let meter = Meter::new();
+        meter.update(1000);
+        meter.update(1010);
+        meter.update(1015);
+        let second = meter.started.elapsed().as_secs();
+        assert_eq!(meter.slots[(second % METER_SLOTS) as usize].load(Ordering::Relaxed) & METER_COUNT_MASK, 15);
+    }
+
+    #[test]
+    fn test_labeled_family() {
+        let metrics = UdcnMetrics::new();
+        let a = Labels::new().face("a").packet_type(PacketType::Interest);
+        let b = Labels::new().face("b").packet_type(PacketType::Interest);
+        let a_data = Labels::new().face("a").packet_type(PacketType::Data);
+
+        metrics.bytes_sent.add_labeled(&a, 10);
+        metrics.bytes_sent.add_labeled(&b, 20);
+        metrics.bytes_sent.add_labeled(&a_data, 100);
+        // Unlabeled updates only count in the total
+        metrics.bytes_sent.add(1);
+
+        assert_eq!(metrics.bytes_sent.value(), 131);
+        assert_eq!(metrics.bytes_sent.get(&a).unwrap().value(), 10);
+        assert_eq!(metrics.bytes_sent.sum(&Labels::new().face("a")), 110);
+        assert_eq!(metrics.bytes_sent.sum(&Labels::new().packet_type(PacketType::Interest)), 30);
+        assert_eq!(metrics.bytes_sent.series().len(), 3);
+
+        metrics.remove_face("a");
+        assert_eq!(metrics.bytes_sent.series().len(), 1);
+        assert!(metrics.bytes_sent.get(&a).is_none());
+        assert_eq!(metrics.bytes_sent.value(), 131);
+    }
+
+    #[test]
+    fn test_snapshot_serialization() {
+        let metrics = UdcnMetrics::new();
+        metrics.cs_hits.increment();
+        metrics.interests_sent.add_labeled(&Labels::new().face("a"), 2);
+        metrics.interest_processing_time.observe_labeled(&Labels::new().face("a"), 500);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.counters["cs_hits"].total, 1);
+        assert_eq!(snapshot.counters["interests_sent"].series[0].value, 2);
+        assert_eq!(snapshot.histograms["interest_processing_time"].total.count, 1);
+        assert_eq!(snapshot.gauges.len(), metrics.gauges().len());
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["counters"]["cs_hits"], serde_json::json!({"total": 1}));
+        assert_eq!(
+            json["counters"]["interests_sent"]["series"][0],
+            serde_json::json!({"labels": {"face": "a"}, "value": 2})
+        );
+        let decoded: MetricsSnapshot = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, snapshot);
+    }
+
+    #[test]
+    fn test_labels_display() {
+        assert_eq!(Labels::new().to_string(), "");
+        let labels = Labels::new().face("a").prefix("/x").packet_type(PacketType::Nack);
+        assert_eq!(labels.to_string(), "face=a,prefix=/x,type=nack");
+    }
+
+    #[test]
+    fn test_listed_by_name() {
+        let metrics = UdcnMetrics::new();
+        metrics.cs_hits.increment();
+        let (_, cs_hits) = metrics.counters().into_iter().find(|(name, _)| *name == "cs_hits").unwrap();
+        assert_eq!(cs_hits.value(), 1);
+        assert!(metrics.gauges().iter().any(|(name, _)| *name == "pit_size"));
+        assert_eq!(metrics.timers().len(), 2);
+    }
 }
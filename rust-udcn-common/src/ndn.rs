@@ -8,6 +8,7 @@ use crate::tlv::{self, TlvElement};
 use crate::Result;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::fmt;
 use std::time::{Duration, Instant};
 
@@ -17,6 +18,8 @@ pub const MAX_NAME_COMPONENT_LENGTH: usize = 255;
 pub const MAX_NAME_COMPONENTS: usize = 16;
 /// Maximum size of an NDN packet.
 pub const MAX_NDN_PACKET_SIZE: usize = 8800;
+/// Prefix marking a name component as a segment number.
+pub const SEGMENT_MARKER: &str = "seg=";
 
 /* ---------------------------------------------------------------- *\
  * Name and NameComponent
@@ -48,6 +51,33 @@ impl NameComponent {
         }
         Ok(Self(element.value.clone()))
     }
+
+    /// Build a segment number component (`seg=<n>`).
+    pub fn from_segment(segment: u64) -> Self {
+        Self::new(format!("{}{}", SEGMENT_MARKER, segment).into_bytes())
+    }
+
+    /// Parse this component as a segment number, if it is one.
+    pub fn to_segment(&self) -> Option<u64> {
+        std::str::from_utf8(&self.0)
+            .ok()?
+            .strip_prefix(SEGMENT_MARKER)?
+            .parse()
+            .ok()
+    }
+}
+
+/// Canonical order: shorter components first, then byte by byte
+impl Ord for NameComponent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.len().cmp(&other.0.len()).then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl PartialOrd for NameComponent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl fmt::Display for NameComponent {
@@ -130,6 +160,18 @@ impl Name {
         }
     }
 
+    /// Return a copy of this name with a segment number component appended.
+    pub fn with_segment(&self, segment: u64) -> Self {
+        let mut name = self.clone();
+        name.push(NameComponent::from_segment(segment));
+        name
+    }
+
+    /// Segment number carried by the last component, if any.
+    pub fn segment(&self) -> Option<u64> {
+        self.components.last()?.to_segment()
+    }
+
     pub fn is_prefix_of(&self, other: &Self) -> bool {
         self.components
             .iter()
@@ -176,6 +218,20 @@ impl fmt::Display for Name {
     }
 }
 
+/// Canonical order: component by component, a name sorting right before
+/// the names it is a proper prefix of
+impl Ord for Name {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.components.cmp(&other.components)
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Default for Name {
     fn default() -> Self {
         Self::new()
@@ -186,6 +242,16 @@ impl Default for Name {
  * Interest
 \* ---------------------------------------------------------------- */
 
+/// Which Data a CanBePrefix Interest prefers when several match
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChildSelector {
+    /// The first child of the name in canonical order
+    #[default]
+    Leftmost,
+    /// The last child of the name in canonical order, such as the latest version
+    Rightmost,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Interest {
     pub name: Name,
@@ -194,6 +260,12 @@ pub struct Interest {
     pub hop_limit: Option<u8>,
     pub can_be_prefix: bool,
     pub must_be_fresh: bool,
+    /// Child preferred among the Data matching a CanBePrefix Interest
+    #[serde(default)]
+    pub child_selector: ChildSelector,
+    /// Arguments for the producer, carried after the other fields
+    #[serde(default)]
+    pub application_parameters: Option<Bytes>,
 }
 
 impl Interest {
@@ -212,6 +284,8 @@ impl Interest {
             hop_limit: Some(32),
             can_be_prefix: false,
             must_be_fresh: true,
+            child_selector: ChildSelector::Leftmost,
+            application_parameters: None,
         }
     }
 
@@ -231,9 +305,18 @@ impl Interest {
         self.must_be_fresh = must_be_fresh;
         self
     }
+    pub fn with_child_selector(mut self, child_selector: ChildSelector) -> Self {
+        self.child_selector = child_selector;
+        self
+    }
+    pub fn with_application_parameters(mut self, parameters: impl Into<Bytes>) -> Self {
+        self.application_parameters = Some(parameters.into());
+        self
+    }
 
     pub fn wire_size(&self) -> Result<usize> {
-        Ok(self.name.to_tlv()?.len() + 20) // rough estimate
+        let parameters = self.application_parameters.as_ref().map_or(0, |parameters| parameters.len() + 4);
+        Ok(self.name.to_tlv()?.len() + parameters + 20) // rough estimate
     }
 
     /// Return the Interest name
@@ -248,10 +331,14 @@ impl Interest {
         // Name
         self.name.to_tlv()?.encode(&mut inner);
 
-        // Selectors (encode CanBePrefix and MustBeFresh as two bytes)
+        // Selectors (encode CanBePrefix and MustBeFresh as two bytes, and
+        // ChildSelector as a third one when it is not the default)
         let mut selectors = BytesMut::new();
         selectors.put_u8(self.can_be_prefix as u8);
         selectors.put_u8(self.must_be_fresh as u8);
+        if self.child_selector == ChildSelector::Rightmost {
+            selectors.put_u8(1);
+        }
         TlvElement::new(tlv::TLV_SELECTORS, selectors.freeze()).encode(&mut inner);
 
         // Nonce
@@ -271,6 +358,11 @@ impl Interest {
             TlvElement::new(0x22, hop_buf.freeze()).encode(&mut inner);
         }
 
+        // ApplicationParameters if present
+        if let Some(parameters) = &self.application_parameters {
+            TlvElement::new(tlv::TLV_APPLICATION_PARAMETERS, parameters.clone()).encode(&mut inner);
+        }
+
         TlvElement::new(tlv::TLV_INTEREST, inner.freeze()).encode(buf);
         Ok(())
     }
@@ -294,6 +386,8 @@ impl Interest {
         let mut hop_limit = None;
         let mut can_be_prefix = false;
         let mut must_be_fresh = false;
+        let mut child_selector = ChildSelector::Leftmost;
+        let mut application_parameters = None;
 
         while inner.has_remaining() {
             let e = TlvElement::decode(&mut inner)?;
@@ -322,12 +416,18 @@ impl Interest {
                         can_be_prefix = e.value[0] != 0;
                         must_be_fresh = e.value[1] != 0;
                     }
+                    if e.value.len() >= 3 && e.value[2] != 0 {
+                        child_selector = ChildSelector::Rightmost;
+                    }
                 }
                 0x22 => {
                     if !e.value.is_empty() {
                         hop_limit = Some(e.value[0]);
                     }
                 }
+                tlv::TLV_APPLICATION_PARAMETERS => {
+                    application_parameters = Some(e.value.clone());
+                }
                 _ => {}
             }
         }
@@ -339,6 +439,8 @@ impl Interest {
             hop_limit,
             can_be_prefix,
             must_be_fresh,
+            child_selector,
+            application_parameters,
         })
     }
 }
@@ -353,13 +455,30 @@ struct DataHelper {
     name: Name,
     content: Bytes,
     ttl_ms: u32,
+    #[serde(default = "default_freshness_period")]
+    freshness_period_ms: u32,
+    #[serde(default)]
+    final_block_id: Option<u64>,
+}
+
+/// FreshnessPeriod of Data that does not carry one (in milliseconds)
+pub const DEFAULT_FRESHNESS_PERIOD_MS: u32 = 10_000;
+
+fn default_freshness_period() -> u32 {
+    DEFAULT_FRESHNESS_PERIOD_MS
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Data {
     pub name: Name,
     pub content: Bytes,
+    /// How long caches keep the Data (in milliseconds), stale or not
     pub ttl_ms: u32,
+    /// FreshnessPeriod: how long after arrival a cached copy still
+    /// satisfies MustBeFresh Interests (in milliseconds)
+    pub freshness_period_ms: u32,
+    /// Segment number of the last segment of the enclosing object, if known.
+    pub final_block_id: Option<u64>,
 
     /// Creation timestamp – not serialised, regenerated on deserialisation.
     #[serde(skip_serializing)]
@@ -376,6 +495,8 @@ impl<'de> Deserialize<'de> for Data {
             name: helper.name,
             content: helper.content,
             ttl_ms: helper.ttl_ms,
+            freshness_period_ms: helper.freshness_period_ms,
+            final_block_id: helper.final_block_id,
             creation_time: Instant::now(), // fresh timestamp
         })
     }
@@ -387,6 +508,8 @@ impl Data {
             name,
             content: content.into(),
             ttl_ms: 10_000,
+            freshness_period_ms: DEFAULT_FRESHNESS_PERIOD_MS,
+            final_block_id: None,
             creation_time: Instant::now(),
         }
     }
@@ -395,6 +518,22 @@ impl Data {
         self.ttl_ms = ttl_ms;
         self
     }
+    pub fn with_freshness_period(mut self, freshness_period_ms: u32) -> Self {
+        self.freshness_period_ms = freshness_period_ms;
+        self
+    }
+    pub fn with_final_block_id(mut self, final_block_id: u64) -> Self {
+        self.final_block_id = Some(final_block_id);
+        self
+    }
+
+    /// Whether this Data is the last segment of a segmented object.
+    pub fn is_final_segment(&self) -> bool {
+        match (self.final_block_id, self.name.segment()) {
+            (Some(last), Some(seg)) => seg >= last,
+            _ => false,
+        }
+    }
 
     pub fn is_expired(&self) -> bool {
         self.creation_time.elapsed() > Duration::from_millis(self.ttl_ms as u64)
@@ -421,9 +560,22 @@ impl Data {
         // Name
         self.name.to_tlv()?.encode(&mut inner);
 
+        // FreshnessPeriod, ahead of the content so it is found without
+        // walking past it
+        let mut freshness_buf = BytesMut::new();
+        freshness_buf.put_u32(self.freshness_period_ms);
+        TlvElement::new(tlv::TLV_FRESHNESS_PERIOD, freshness_buf.freeze()).encode(&mut inner);
+
         // Content
         TlvElement::new(tlv::TLV_CONTENT, self.content.clone()).encode(&mut inner);
 
+        // FinalBlockId if present
+        if let Some(last) = self.final_block_id {
+            let mut fbi_buf = BytesMut::new();
+            fbi_buf.put_u64(last);
+            TlvElement::new(tlv::TLV_FINAL_BLOCK_ID, fbi_buf.freeze()).encode(&mut inner);
+        }
+
         TlvElement::new(tlv::TLV_DATA, inner.freeze()).encode(buf);
         Ok(())
     }
@@ -443,6 +595,8 @@ impl Data {
         let mut inner = outer.value.clone();
         let mut name = None;
         let mut content = Bytes::new();
+        let mut freshness_period_ms = DEFAULT_FRESHNESS_PERIOD_MS;
+        let mut final_block_id = None;
 
         while inner.has_remaining() {
             let e = TlvElement::decode(&mut inner)?;
@@ -453,6 +607,19 @@ impl Data {
                 tlv::TLV_CONTENT => {
                     content = e.value.clone();
                 }
+                tlv::TLV_FRESHNESS_PERIOD => {
+                    // A nonNegativeInteger of 1, 2, 4 or 8 bytes
+                    if matches!(e.value.len(), 1 | 2 | 4 | 8) {
+                        let period = e.value.iter().fold(0u64, |period, b| (period << 8) | *b as u64);
+                        freshness_period_ms = period.min(u32::MAX as u64) as u32;
+                    }
+                }
+                tlv::TLV_FINAL_BLOCK_ID => {
+                    let mut fbuf = e.value.clone();
+                    if fbuf.remaining() == 8 {
+                        final_block_id = Some(fbuf.get_u64());
+                    }
+                }
                 _ => {}
             }
         }
@@ -461,11 +628,156 @@ impl Data {
             name: name.ok_or_else(|| Error::NdnPacket("Data missing name".into()))?,
             content,
             ttl_ms: 10_000,
+            freshness_period_ms,
+            final_block_id,
             creation_time: Instant::now(),
         })
     }
 }
 
+/* ---------------------------------------------------------------- *\
+ * Nack
+\* ---------------------------------------------------------------- */
+
+/// Reason carried by a network Nack (codes follow NDNLPv2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NackReason {
+    /// No reason given
+    None,
+    /// The upstream is congested
+    Congestion,
+    /// The Interest was a duplicate (looping) Interest
+    Duplicate,
+    /// No route toward the Interest name
+    NoRoute,
+    /// The Interest ran out of HopLimit at the reporting forwarder
+    HopLimit,
+}
+
+impl NackReason {
+    pub fn code(self) -> u8 {
+        match self {
+            NackReason::None => 0,
+            NackReason::Congestion => 50,
+            NackReason::Duplicate => 100,
+            NackReason::NoRoute => 150,
+            NackReason::HopLimit => 200,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            50 => NackReason::Congestion,
+            100 => NackReason::Duplicate,
+            150 => NackReason::NoRoute,
+            200 => NackReason::HopLimit,
+            _ => NackReason::None,
+        }
+    }
+}
+
+impl fmt::Display for NackReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NackReason::None => write!(f, "None"),
+            NackReason::Congestion => write!(f, "Congestion"),
+            NackReason::Duplicate => write!(f, "Duplicate"),
+            NackReason::NoRoute => write!(f, "NoRoute"),
+            NackReason::HopLimit => write!(f, "HopLimit"),
+        }
+    }
+}
+
+/// A network Nack returned in place of Data for an Interest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Nack {
+    pub interest: Interest,
+    pub reason: NackReason,
+    /// Forwarder that originated the Nack, when it identifies itself
+    #[serde(default)]
+    pub reporter: Option<Name>,
+}
+
+impl Nack {
+    pub fn new(interest: Interest, reason: NackReason) -> Self {
+        Self {
+            interest,
+            reason,
+            reporter: None,
+        }
+    }
+
+    /// Identify the forwarder originating the Nack
+    pub fn with_reporter(mut self, reporter: Name) -> Self {
+        self.reporter = Some(reporter);
+        self
+    }
+
+    /// Return the name of the Nacked Interest
+    pub fn name(&self) -> &Name {
+        self.interest.name()
+    }
+
+    /// Encode the Nack into TLV wire format
+    pub fn encode(&self, buf: &mut BytesMut) -> Result<()> {
+        let mut inner = BytesMut::new();
+
+        TlvElement::new(tlv::TLV_NACK_REASON, vec![self.reason.code()]).encode(&mut inner);
+        if let Some(reporter) = &self.reporter {
+            let mut reporter_buf = BytesMut::new();
+            reporter.to_tlv()?.encode(&mut reporter_buf);
+            TlvElement::new(tlv::TLV_NACK_REPORTER, reporter_buf.freeze()).encode(&mut inner);
+        }
+        self.interest.encode(&mut inner)?;
+
+        TlvElement::new(tlv::TLV_NACK, inner.freeze()).encode(buf);
+        Ok(())
+    }
+
+    /// Decode a Nack from TLV wire format
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut buf = Bytes::from(bytes.to_vec());
+        let outer = TlvElement::decode(&mut buf)?;
+        if outer.tlv_type != tlv::TLV_NACK {
+            return Err(Error::NdnPacket(format!(
+                "Expected Nack type {}, got {}",
+                tlv::TLV_NACK,
+                outer.tlv_type
+            )));
+        }
+
+        let mut inner = outer.value.clone();
+        let mut reason = NackReason::None;
+        let mut reporter = None;
+        let mut interest = None;
+
+        while inner.has_remaining() {
+            let start = inner.clone();
+            let e = TlvElement::decode(&mut inner)?;
+            match e.tlv_type {
+                tlv::TLV_NACK_REASON if !e.value.is_empty() => {
+                    reason = NackReason::from_code(e.value[0]);
+                }
+                tlv::TLV_NACK_REPORTER => {
+                    let mut value = e.value.clone();
+                    reporter = Some(Name::from_tlv(&TlvElement::decode(&mut value)?)?);
+                }
+                tlv::TLV_INTEREST => {
+                    let consumed = start.len() - inner.len();
+                    interest = Some(Interest::decode(&start[..consumed])?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            interest: interest.ok_or_else(|| Error::NdnPacket("Nack missing Interest".into()))?,
+            reason,
+            reporter,
+        })
+    }
+}
+
 /* ---------------------------------------------------------------- *\
  * Misc
 \* ---------------------------------------------------------------- */
@@ -474,6 +786,8 @@ impl Data {
 pub enum InterestResult {
     /// The Interest was satisfied with Data
     Data(Data),
+    /// The Interest was answered with a network Nack
+    Nacked(NackReason),
     /// The Interest timed out
     Timeout,
     /// The Interest could not be satisfied and was dropped with a reason
@@ -18,6 +18,11 @@ pub const TLV_NONCE: u8             = 0x0A;
 pub const TLV_INTEREST_LIFETIME: u8 = 0x0C;
 pub const TLV_SELECTORS: u8         = 0x09;
 pub const TLV_CONTENT: u8           = 0x15;
+pub const TLV_FRESHNESS_PERIOD: u8  = 0x19;
+pub const TLV_FINAL_BLOCK_ID: u8    = 0x1A;
+pub const TLV_NACK_REASON: u8       = 0x21;
+pub const TLV_NACK_REPORTER: u8     = 0x23;
+pub const TLV_APPLICATION_PARAMETERS: u8 = 0x24;
 
 /* ---------------------------------------------------------------- *
  * Encoding helpers
@@ -108,6 +108,9 @@ impl ForwardInfo {
     }
 }
 
+/// Maximum number of Interest nonces a kernel PIT entry remembers.
+pub const MAX_PIT_NONCES: usize = 4;
+
 /// A key used for the PIT table in the kernel.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(C)]
@@ -116,8 +119,6 @@ pub struct PitKey {
     pub name_hash: u32,
     /// Full name length (in components)
     pub name_len: u8,
-    /// Nonce value from the Interest
-    pub nonce: u32,
 }
 
 /// A value stored in the PIT table in the kernel.
@@ -132,6 +133,60 @@ pub struct PitValue {
     pub lifetime_ms: u32,
     /// Number of components in the name
     pub name_component_count: u8,
+    /// Number of nonces in `nonces`
+    pub nonce_count: u8,
+    /// Nonces of the Interests pending in this entry, oldest first
+    pub nonces: [u32; MAX_PIT_NONCES],
+}
+
+impl PitValue {
+    /// The nonces of the Interests pending in this entry.
+    pub fn nonces(&self) -> &[u32] {
+        &self.nonces[..(self.nonce_count as usize).min(MAX_PIT_NONCES)]
+    }
+
+    /// Whether an Interest with this nonce is pending in this entry.
+    pub fn has_nonce(&self, nonce: u32) -> bool {
+        self.nonces().contains(&nonce)
+    }
+
+    /// Record the nonce of another Interest for this entry, forgetting the
+    /// oldest one when the entry is full.
+    pub fn add_nonce(&mut self, nonce: u32) {
+        let count = self.nonces().len();
+        if count < MAX_PIT_NONCES {
+            self.nonces[count] = nonce;
+            self.nonce_count += 1;
+        } else {
+            self.nonces.copy_within(1.., 0);
+            self.nonces[MAX_PIT_NONCES - 1] = nonce;
+        }
+    }
+
+    /// The dead nonce list keys of the Interests in the entry at `key`, to
+    /// remember once the entry is satisfied or expires.
+    pub fn dead_nonces(&self, key: &PitKey) -> impl Iterator<Item = DeadNonceKey> + '_ {
+        let key = *key;
+        self.nonces().iter().map(move |&nonce| DeadNonceKey {
+            name_hash: key.name_hash,
+            name_len: key.name_len,
+            nonce,
+        })
+    }
+}
+
+/// A key used for the dead nonce list in the kernel, remembering the name and
+/// nonce of Interests that are no longer pending so they are caught if they
+/// loop back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct DeadNonceKey {
+    /// Truncated hash of the NDN name
+    pub name_hash: u32,
+    /// Full name length (in components)
+    pub name_len: u8,
+    /// Nonce value from the Interest
+    pub nonce: u32,
 }
 
 /// A key used for the FIB table in the kernel.
@@ -176,4 +231,184 @@ pub struct CsValue {
     pub content_size: u32,
     /// TTL in milliseconds
     pub ttl_ms: u32,
+    /// FreshnessPeriod of the Data in milliseconds, past which the entry
+    /// no longer answers MustBeFresh Interests
+    pub freshness_ms: u32,
+    pub _pad: u32,
+}
+
+/// Maximum size of an encoded Data packet the kernel can answer Interests with.
+pub const MAX_CS_DATA_SIZE: usize = 1024;
+
+/// An encoded Data packet stored in the kernel, which the XDP program
+/// sends back in place of a matching Interest.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct CsData {
+    /// Length of the encoded Data
+    pub len: u16,
+    /// One's complement sum of the encoded Data, for the UDP checksum
+    pub checksum: u16,
+    /// The encoded Data, zero-padded
+    pub bytes: [u8; MAX_CS_DATA_SIZE],
+}
+
+impl CsData {
+    /// Wrap an encoded Data packet, if it is small enough for the kernel
+    pub fn new(wire: &[u8]) -> Option<Self> {
+        if wire.is_empty() || wire.len() > MAX_CS_DATA_SIZE {
+            return None;
+        }
+
+        let mut bytes = [0u8; MAX_CS_DATA_SIZE];
+        bytes[..wire.len()].copy_from_slice(wire);
+
+        // Sum big-endian 16-bit words; the zero padding completes an odd tail
+        let mut sum: u32 = bytes[..wire.len() + wire.len() % 2]
+            .chunks_exact(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+            .sum();
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+
+        Some(Self {
+            len: wire.len() as u16,
+            checksum: sum as u16,
+            bytes,
+        })
+    }
+}
+
+/// Maximum number of faces the kernel can redirect Interests to.
+pub const MAX_EGRESS_FACES: usize = 256;
+
+/// How the kernel sends packets out of a face: the interface to redirect
+/// to and the headers to rewrite on the way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct EgressFace {
+    /// Index of the egress interface
+    pub ifindex: u32,
+    /// MAC address of the egress interface
+    pub src_mac: [u8; 6],
+    /// MAC address of the next hop
+    pub dst_mac: [u8; 6],
+    /// IPv6 address of the egress interface
+    pub src_addr: [u8; 16],
+    /// IPv6 address of the next hop
+    pub dst_addr: [u8; 16],
+}
+
+/// Maximum number of ingress interfaces the kernel keeps counters for.
+pub const MAX_INTERFACE_COUNTERS: usize = 64;
+
+/// Counters of the NDN traffic arriving on one interface, kept per CPU by
+/// the kernel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct InterfaceCounters {
+    /// Interests received
+    pub interests: u64,
+    /// Data packets received
+    pub data: u64,
+    /// Interests and Data dropped
+    pub drops: u64,
+    /// Bytes of the NDN packets received
+    pub bytes: u64,
+}
+
+/// Longest encoded name the kernel reports in a `NameEvent`.
+pub const MAX_NAME_EVENT_LENGTH: usize = 128;
+
+/// The name of an Interest the kernel added to its PIT, read from the
+/// NAME_EVENTS ring buffer.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct NameEvent {
+    /// Hash of the name, as in the PIT key
+    pub name_hash: u32,
+    /// Number of components in the name
+    pub name_len: u8,
+    pub _pad: u8,
+    /// Bytes of `components` filled in
+    pub components_len: u16,
+    /// The encoded name components, truncated to `MAX_NAME_EVENT_LENGTH`
+    pub components: [u8; MAX_NAME_EVENT_LENGTH],
+}
+
+impl NameEvent {
+    /// Read an event from a ring buffer record
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < std::mem::size_of::<Self>() {
+            return None;
+        }
+
+        // SAFETY: the record holds a whole event and every bit pattern is
+        // a valid NameEvent
+        let event = unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const Self) };
+        if event.components_len as usize > MAX_NAME_EVENT_LENGTH {
+            return None;
+        }
+        Some(event)
+    }
+
+    /// The encoded name components reported
+    pub fn components(&self) -> &[u8] {
+        &self.components[..self.components_len as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ndn::{Interest, Name};
+    use std::collections::HashMap;
+
+    fn pit_value(nonce: u32) -> PitValue {
+        let mut value = PitValue {
+            face_id: FaceId(1),
+            timestamp: 0,
+            lifetime_ms: 4000,
+            name_component_count: 2,
+            nonce_count: 0,
+            nonces: [0; MAX_PIT_NONCES],
+        };
+        value.add_nonce(nonce);
+        value
+    }
+
+    #[test]
+    fn test_dead_nonce_after_satisfaction() {
+        let interest = Interest::new(Name::from_string("/a/b").unwrap());
+        let key = PitKey { name_hash: 0x1234_5678, name_len: 2 };
+        let mut pit = HashMap::new();
+        let mut dead_nonces = HashMap::new();
+
+        pit.insert(key, pit_value(interest.nonce));
+
+        // A retransmission with a new nonce joins the entry
+        let retransmission = interest.nonce.wrapping_add(1);
+        assert!(!pit[&key].has_nonce(retransmission));
+        pit.get_mut(&key).unwrap().add_nonce(retransmission);
+
+        // Data finds the entry by name alone and retires both nonces
+        let entry = pit.remove(&key).unwrap();
+        dead_nonces.extend(entry.dead_nonces(&key).map(|dead| (dead, 0u64)));
+
+        let looped = DeadNonceKey { name_hash: key.name_hash, name_len: key.name_len, nonce: interest.nonce };
+        assert!(dead_nonces.contains_key(&looped));
+        assert!(dead_nonces.contains_key(&DeadNonceKey { nonce: retransmission, ..looped }));
+        assert!(!dead_nonces.contains_key(&DeadNonceKey { nonce: interest.nonce ^ 0xffff_0000, ..looped }));
+    }
+
+    #[test]
+    fn test_pit_nonces_keep_newest() {
+        let mut value = pit_value(1);
+        for nonce in 2..=6 {
+            value.add_nonce(nonce);
+        }
+        assert_eq!(value.nonces(), &[3, 4, 5, 6]);
+        assert!(!value.has_nonce(1));
+    }
 }
@@ -0,0 +1,307 @@
+//! Coherence between the kernel fast path and the userspace forwarder.
+//!
+//! The XDP program keeps its own FIB, PIT and CS maps, keyed by name hash,
+//! and passes every packet it cannot finish to userspace. The bridge mirrors
+//! userspace state into those maps: routes to QUIC faces are registered in
+//! the kernel RIB, which installs them in the kernel FIB, cached Data is announced in the kernel CS so matching
+//! Interests are flagged as CS hits (and answered from the userspace CS, or
+//! straight from the kernel when the Data is small enough to be stored there),
+//! and kernel PIT entries are cleared once their Data has been cached. It also
+//! lists the kernel PIT, naming its entries from the kernel's name reports.
+
+use anyhow::{anyhow, Result};
+use bytes::BytesMut;
+use log::{debug, trace, warn};
+use rust_udcn_common::{
+    ndn::{Data, Name},
+    types::FaceId,
+};
+use rust_udcn_quic::ContentStore;
+use rust_udcn_xdp::{
+    clock, ContentStore as KernelCs, NameTable, PendingInterestTable as KernelPit, RibManager, Route, RouteOrigin,
+    XdpManager,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::Mutex, task::JoinHandle};
+
+/// First kernel face ID given to userspace faces. Lower IDs are interface
+/// indexes, which the XDP program uses as face IDs.
+const USERSPACE_FACE_ID_BASE: u16 = 0x8000;
+
+/// How long the kernel reports a mirrored CS entry as a hit
+const KERNEL_CS_TTL_MS: u32 = 60_000;
+
+/// Numeric kernel face IDs for userspace faces
+#[derive(Debug, Default)]
+struct FaceIds {
+    ids: HashMap<String, FaceId>,
+    next: u16,
+}
+
+impl FaceIds {
+    fn get_or_assign(&mut self, face_id: &str) -> FaceId {
+        if let Some(id) = self.ids.get(face_id) {
+            return *id;
+        }
+        let id = FaceId(USERSPACE_FACE_ID_BASE + self.next);
+        self.next = (self.next + 1) % (u16::MAX - USERSPACE_FACE_ID_BASE);
+        self.ids.insert(face_id.to_string(), id);
+        id
+    }
+}
+
+/// A pending Interest in the kernel PIT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelPitEntry {
+    /// Interest name, if the kernel reported it
+    pub name: Option<Name>,
+
+    /// Hash of the name, which keys the entry
+    pub name_hash: u32,
+
+    /// Face the Interest arrived on, a userspace face ID or interface index
+    pub face: String,
+
+    /// Lifetime left, zero once expired
+    pub remaining: Duration,
+}
+
+/// Mirrors userspace forwarding state into the XDP program's maps
+pub struct KernelBridge {
+    /// Kernel RIB
+    rib: Arc<RibManager>,
+
+    /// Kernel PIT
+    pit: Arc<KernelPit>,
+
+    /// Kernel CS
+    cs: Arc<KernelCs>,
+
+    /// Names behind the hashes of the kernel maps
+    names: Arc<NameTable>,
+
+    /// Kernel face IDs of userspace faces
+    face_ids: Mutex<FaceIds>,
+
+    /// Names currently announced in the kernel CS
+    mirrored: Mutex<HashSet<Name>>,
+}
+
+impl KernelBridge {
+    /// Create a bridge to the maps of a loaded XDP program
+    pub fn new(xdp: &XdpManager) -> Self {
+        Self {
+            rib: xdp.rib(),
+            pit: xdp.pit(),
+            cs: xdp.cs(),
+            names: xdp.names(),
+            face_ids: Mutex::new(FaceIds::default()),
+            mirrored: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Register a route to a userspace face in the kernel RIB
+    pub async fn install_route(&self, prefix: &Name, face_id: &str, cost: u32) -> Result<()> {
+        let kernel_id = self.face_ids.lock().await.get_or_assign(face_id);
+        let route = Route::new(kernel_id, RouteOrigin::Static, cost);
+        Ok(self.rib.register(prefix.clone(), route).await?)
+    }
+
+    /// Route `prefix` to the interface with index `ifindex` in the kernel RIB
+    pub async fn install_interface_route(&self, prefix: &Name, ifindex: u16, cost: u32) -> Result<()> {
+        if ifindex >= USERSPACE_FACE_ID_BASE {
+            return Err(anyhow!("Interface index {} is out of range", ifindex));
+        }
+        let route = Route::new(FaceId(ifindex), RouteOrigin::Static, cost);
+        Ok(self.rib.register(prefix.clone(), route).await?)
+    }
+
+    /// Remove the kernel route of `prefix` to a userspace face or interface
+    /// index, if there is one. Returns whether a route was removed.
+    pub async fn remove_route(&self, prefix: &Name, face_id: &str) -> Result<bool> {
+        let kernel_id = match face_id.parse::<u16>() {
+            Ok(ifindex) if ifindex < USERSPACE_FACE_ID_BASE => FaceId(ifindex),
+            _ => match self.face_ids.lock().await.ids.get(face_id) {
+                Some(id) => *id,
+                None => return Ok(false),
+            },
+        };
+        Ok(self.rib.unregister(prefix, kernel_id, RouteOrigin::Static).await?)
+    }
+
+    /// List the kernel routes as (prefix, face, cost), naming interfaces by
+    /// their index
+    pub async fn routes(&self) -> Vec<(Name, String, u32)> {
+        let names = self.face_names().await;
+
+        self.rib
+            .routes()
+            .await
+            .into_iter()
+            .map(|(prefix, route)| {
+                let face = names.get(&route.face_id).cloned().unwrap_or_else(|| route.face_id.0.to_string());
+                (prefix, face, route.cost)
+            })
+            .collect()
+    }
+
+    /// Number of names announced in the kernel CS
+    pub async fn mirrored_count(&self) -> usize {
+        self.mirrored.lock().await.len()
+    }
+
+    /// Names announced in the kernel CS
+    pub async fn mirrored(&self) -> HashSet<Name> {
+        self.mirrored.lock().await.clone()
+    }
+
+    /// Drop the kernel CS entries under `prefix`, including Data the kernel
+    /// cached by itself. Returns the number of entries dropped.
+    pub async fn purge_prefix(&self, prefix: &Name) -> Result<usize> {
+        let mut mirrored = self.mirrored.lock().await;
+        let purged = self.cs.purge_prefix(prefix).await?;
+        // Still cached Data is announced again on the next sync
+        mirrored.retain(|name| !(prefix.len() <= name.len() && prefix.is_prefix_of(name)));
+        Ok(purged)
+    }
+
+    /// List the kernel PIT entries
+    pub async fn pit_entries(&self) -> Result<Vec<KernelPitEntry>> {
+        let faces = self.face_names().await;
+        let now_ms = clock::kernel_now_ms();
+
+        let mut entries = Vec::new();
+        for (key, value) in self.pit.get_all_entries().await? {
+            let expiry_ms = value.timestamp + value.lifetime_ms as u64;
+            let remaining = match clock::is_expired(value.timestamp, value.lifetime_ms as u64, now_ms) {
+                true => Duration::ZERO,
+                false => Duration::from_millis(expiry_ms.saturating_sub(now_ms)),
+            };
+            entries.push(KernelPitEntry {
+                name: self.names.get(key.name_hash, key.name_len).await,
+                name_hash: key.name_hash,
+                face: faces.get(&value.face_id).cloned().unwrap_or_else(|| value.face_id.0.to_string()),
+                remaining,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Drop the kernel PIT entries under `prefix`, only the expired ones
+    /// unless `all`. Returns the number of entries dropped.
+    ///
+    /// Entries whose name the kernel did not report only match the root
+    /// prefix.
+    pub async fn flush_pit(&self, prefix: &Name, all: bool) -> Result<usize> {
+        let now_ms = clock::kernel_now_ms();
+
+        let mut keys = Vec::new();
+        for (key, value) in self.pit.get_all_entries().await? {
+            if !all && !clock::is_expired(value.timestamp, value.lifetime_ms as u64, now_ms) {
+                continue;
+            }
+            let under = match self.names.get(key.name_hash, key.name_len).await {
+                Some(name) => prefix.len() <= name.len() && prefix.is_prefix_of(&name),
+                None => prefix.is_empty(),
+            };
+            if under {
+                keys.push(key);
+            }
+        }
+        Ok(self.pit.remove_keys(&keys).await?)
+    }
+
+    /// Userspace face IDs by kernel face ID
+    async fn face_names(&self) -> HashMap<FaceId, String> {
+        self.face_ids
+            .lock()
+            .await
+            .ids
+            .iter()
+            .map(|(name, id)| (*id, name.clone()))
+            .collect()
+    }
+
+    /// Remove the kernel routes of a face that has closed
+    pub async fn release_face(&self, face_id: &str) {
+        let Some(kernel_id) = self.face_ids.lock().await.ids.remove(face_id) else {
+            return;
+        };
+        if let Err(e) = self.rib.remove_face(kernel_id).await {
+            warn!("Failed to remove kernel routes of {}: {}", face_id, e);
+        }
+    }
+
+    /// Bring the kernel CS and PIT in line with the userspace content store
+    pub async fn sync(&self, content_store: &ContentStore) -> Result<()> {
+        let cached: HashMap<Name, Data> = content_store
+            .enumerate(&Name::new())
+            .into_iter()
+            .map(|entry| (entry.data.name().clone(), entry.data))
+            .collect();
+
+        let mut mirrored = self.mirrored.lock().await;
+
+        for (name, data) in &cached {
+            if mirrored.contains(name) {
+                continue;
+            }
+            self.cs
+                .insert_name(name, data.content().len() as u32, KERNEL_CS_TTL_MS, data.freshness_period_ms)
+                .await?;
+            // Small Data is answered by the kernel itself
+            let mut wire = BytesMut::new();
+            data.encode(&mut wire)?;
+            if !self.cs.insert_data(name, &wire).await? {
+                trace!("{} is too large for the kernel CS, answering from userspace", name);
+            }
+            // The Data is here, so the kernel no longer waits for it
+            let cleared = self.pit.remove_name(name).await?;
+            if cleared > 0 {
+                trace!("Cleared {} kernel PIT entries for {}", cleared, name);
+            }
+            mirrored.insert(name.clone());
+        }
+
+        let evicted: Vec<Name> = mirrored.iter().filter(|name| !cached.contains_key(*name)).cloned().collect();
+        for name in evicted {
+            self.cs.remove_name(&name).await?;
+            mirrored.remove(&name);
+        }
+
+        debug!("Kernel CS mirrors {} entries", mirrored.len());
+        Ok(())
+    }
+
+    /// Periodically synchronize the kernel maps with `content_store`
+    pub fn spawn_sync(self: Arc<Self>, content_store: Arc<ContentStore>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.sync(&content_store).await {
+                    warn!("Failed to synchronize kernel maps: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_face_ids_above_interface_indexes() {
+        let mut ids = FaceIds::default();
+        let a = ids.get_or_assign("quic://a");
+        let b = ids.get_or_assign("quic://b");
+        assert_eq!(a, FaceId(USERSPACE_FACE_ID_BASE));
+        assert_eq!(b, FaceId(USERSPACE_FACE_ID_BASE + 1));
+        assert_eq!(ids.get_or_assign("quic://a"), a);
+    }
+}
@@ -0,0 +1,435 @@
+//! Daemon configuration, parsed from the command line over the layered
+//! [`Settings`] of an optional TOML configuration file and the environment.
+
+use anyhow::{anyhow, Result};
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches, Parser};
+use log::LevelFilter;
+use rust_udcn_common::{
+    config::{ConfigLoader, CsSettings, SecuritySettings, Settings, TransportSettings, XdpSettings},
+    ndn::Name,
+};
+use rust_udcn_quic::DEFAULT_UNIX_SOCKET_PATH;
+use rust_udcn_xdp::DEFAULT_PIN_PATH;
+use std::{ffi::OsString, fmt, net::SocketAddr, path::PathBuf, str::FromStr};
+
+use crate::mgmt::DEFAULT_MGMT_SOCKET_PATH;
+
+/// A static route to an upstream forwarder reached over QUIC
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpstreamRoute {
+    /// Name prefix routed to the upstream
+    pub prefix: Name,
+
+    /// QUIC address of the upstream forwarder
+    pub addr: String,
+}
+
+impl fmt::Display for UpstreamRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.prefix, self.addr)
+    }
+}
+
+impl FromStr for UpstreamRoute {
+    type Err = anyhow::Error;
+
+    /// Parse `PREFIX=HOST:PORT`
+    fn from_str(s: &str) -> Result<Self> {
+        let (prefix, addr) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected PREFIX=HOST:PORT, got {}", s))?;
+        if addr.is_empty() {
+            return Err(anyhow!("Missing upstream address for {}", prefix));
+        }
+        let prefix = Name::from_string(prefix).map_err(|e| anyhow!("Invalid prefix {}: {}", prefix, e))?;
+
+        Ok(Self {
+            prefix,
+            addr: addr.to_string(),
+        })
+    }
+}
+
+/// µDCN forwarding daemon
+#[derive(Debug, Clone, Parser)]
+#[clap(author, version, about)]
+pub struct DaemonConfig {
+    /// Read settings from this TOML file; the UDCN_* environment variables
+    /// and the options given on the command line take precedence, and
+    /// SIGHUP reads it again
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Interfaces to attach the XDP program to
+    #[clap(short, long = "interface")]
+    pub interfaces: Vec<String>,
+
+    /// Also attach the XDP program to the interfaces matching this pattern
+    /// (e.g. `eth*`), including those added or brought up later
+    #[clap(long)]
+    pub interface_pattern: Vec<String>,
+
+    /// Path to the XDP object file (the embedded program is used otherwise)
+    #[clap(long)]
+    pub xdp_object: Option<PathBuf>,
+
+    /// Run without the XDP fast path
+    #[clap(long)]
+    pub no_xdp: bool,
+
+    /// Share interfaces with the XDP programs of other tools: a program
+    /// found attached runs after udcn's for the packets that are not NDN,
+    /// instead of udcn failing to attach
+    #[clap(long)]
+    pub xdp_chain: bool,
+
+    /// Match names in the kernel FIB with an LPM trie on the encoded name
+    /// instead of per-prefix hashes
+    #[clap(long)]
+    pub fib_trie: bool,
+
+    /// Directory in the BPF filesystem to pin the kernel tables and XDP
+    /// links in, shared with `udcn xdp` commands
+    #[clap(long, default_value = DEFAULT_PIN_PATH)]
+    pub pin_path: PathBuf,
+
+    /// Entries of the kernel PIT, instead of the size built into the program
+    #[clap(long)]
+    pub kernel_pit_entries: Option<u32>,
+
+    /// Prefixes the kernel FIB holds
+    #[clap(long)]
+    pub kernel_fib_entries: Option<u32>,
+
+    /// Names the kernel CS holds
+    #[clap(long)]
+    pub kernel_cs_entries: Option<u32>,
+
+    /// Bytes of Data the kernel CS records at most, beyond which its oldest
+    /// entries are evicted
+    #[clap(long)]
+    pub kernel_cs_max_bytes: Option<u64>,
+
+    /// Smallest Data, in bytes, the kernel CS records
+    #[clap(long)]
+    pub kernel_cs_min_size: Option<u32>,
+
+    /// Largest Data, in bytes, the kernel CS records
+    #[clap(long)]
+    pub kernel_cs_max_size: Option<u32>,
+
+    /// Only record Data under this prefix in the kernel CS
+    #[clap(long)]
+    pub kernel_cs_allow: Vec<String>,
+
+    /// Never record Data under this prefix in the kernel CS
+    #[clap(long)]
+    pub kernel_cs_deny: Vec<String>,
+
+    /// Interests per second the kernel admits for each first-component
+    /// prefix on each interface, dropping the rest
+    #[clap(long)]
+    pub interest_rate_limit: Option<u32>,
+
+    /// Also attach the TC egress program, to count and cache the NDN
+    /// packets sent out of the interfaces
+    #[clap(long)]
+    pub tc_egress: bool,
+
+    /// Address to accept QUIC faces on
+    #[clap(long, default_value = "[::]:6367")]
+    pub quic_listen: String,
+
+    /// TLS certificate of the QUIC server
+    #[clap(long, default_value = "/etc/udcn/cert.pem")]
+    pub cert: PathBuf,
+
+    /// TLS private key of the QUIC server
+    #[clap(long, default_value = "/etc/udcn/key.pem")]
+    pub key: PathBuf,
+
+    /// Address to accept UDP faces on, which also receives the packets the
+    /// XDP program passes up
+    #[clap(long, default_value = "[::]:6363")]
+    pub udp_listen: SocketAddr,
+
+    /// Unix socket for local applications
+    #[clap(long, default_value = DEFAULT_UNIX_SOCKET_PATH)]
+    pub unix_socket: PathBuf,
+
+    /// Control socket for management commands
+    #[clap(long, default_value = DEFAULT_MGMT_SOCKET_PATH)]
+    pub mgmt_socket: PathBuf,
+
+    /// Static route to an upstream QUIC forwarder, as PREFIX=HOST:PORT
+    #[clap(short, long = "route")]
+    pub routes: Vec<UpstreamRoute>,
+
+    /// Announce this node on the LAN and connect to the neighbors heard
+    #[clap(long)]
+    pub discover: bool,
+
+    /// Node ID announced to neighbors (the host name by default)
+    #[clap(long)]
+    pub node_id: Option<String>,
+
+    /// Content store capacity in Data packets
+    #[clap(long, default_value = "10000")]
+    pub cs_capacity: usize,
+
+    /// Persist the content store to this repo log file
+    #[clap(long)]
+    pub cs_path: Option<PathBuf>,
+
+    /// Accept repo insert and delete commands under this prefix
+    #[clap(long)]
+    pub repo_prefix: Option<String>,
+
+    /// Serve content to HTTP clients on this address, mapping URL paths
+    /// to names under --http-prefix
+    #[clap(long)]
+    pub http_listen: Option<SocketAddr>,
+
+    /// Prefix the URL paths of HTTP requests are mapped under
+    #[clap(long, default_value = "/")]
+    pub http_prefix: String,
+
+    /// How often kernel maps are synchronized with userspace state
+    #[clap(long, default_value = "1000")]
+    pub sync_interval_ms: u64,
+
+    /// How often expired kernel PIT entries are removed
+    #[clap(long, default_value = "1000")]
+    pub pit_gc_interval_ms: u64,
+
+    /// Export tracing spans to this OTLP collector, e.g.
+    /// http://localhost:4317; needs udcnd built with the otlp feature
+    #[clap(long)]
+    pub otlp_endpoint: Option<String>,
+
+    /// Serve the gRPC management API on this address, e.g.
+    /// 127.0.0.1:6368; needs udcnd built with the grpc feature and
+    /// --grpc-token-file. The API can change routes, faces and the content
+    /// store like the control socket, but over plain TCP: only loopback
+    /// addresses are accepted, and any local user holding the token has
+    /// full control of the node
+    #[clap(long, requires = "grpc_token_file")]
+    pub grpc_listen: Option<SocketAddr>,
+
+    /// File holding the token gRPC clients must send as
+    /// `authorization: Bearer <token>`; keep it readable by the daemon's
+    /// user and the management clients only
+    #[clap(long)]
+    pub grpc_token_file: Option<PathBuf>,
+
+    /// Log level: error, warn, info, debug or trace
+    #[clap(long)]
+    pub log_level: Option<LevelFilter>,
+
+    /// Enable debug logging
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+/// Settings the command line options stand for, by option ID
+const SETTINGS_FLAGS: &[(&str, &str)] = &[
+    ("interfaces", "xdp.interfaces"),
+    ("interface_pattern", "xdp.interface_pattern"),
+    ("no_xdp", "xdp.enabled"),
+    ("xdp_object", "xdp.object"),
+    ("tc_egress", "xdp.tc_egress"),
+    ("quic_listen", "transport.quic_listen"),
+    ("cert", "security.cert"),
+    ("key", "security.key"),
+    ("udp_listen", "transport.udp_listen"),
+    ("unix_socket", "transport.unix_socket"),
+    ("mgmt_socket", "transport.mgmt_socket"),
+    ("routes", "transport.routes"),
+    ("discover", "transport.discover"),
+    ("node_id", "transport.node_id"),
+    ("cs_capacity", "cs.capacity"),
+    ("cs_path", "cs.path"),
+    ("kernel_cs_entries", "cs.kernel_entries"),
+    ("kernel_cs_max_bytes", "cs.kernel_max_bytes"),
+    ("log_level", "log_level"),
+    // --verbose stands for a log level too
+    ("verbose", "log_level"),
+];
+
+impl DaemonConfig {
+    /// Parse the command line, taking the settings it leaves out from the
+    /// `UDCN_*` environment variables and the configuration file when one
+    /// is given
+    pub fn load() -> Result<Self> {
+        Self::load_from(std::env::args_os(), std::env::vars())
+    }
+
+    /// Same as [`DaemonConfig::load`], from the arguments `args` and the
+    /// environment `vars`
+    pub fn load_from<I, T>(args: I, vars: impl IntoIterator<Item = (String, String)>) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().get_matches_from(args);
+        let mut config = Self::from_arg_matches(&matches)?;
+
+        let mut loader = ConfigLoader::new();
+        if let Some(path) = &config.config {
+            loader = loader.file(path)?;
+        }
+        let given = SETTINGS_FLAGS
+            .iter()
+            .filter(|(id, _)| matches.value_source(id) == Some(ValueSource::CommandLine))
+            .map(|(_, key)| *key);
+        let settings = loader.env(vars)?.flags(&config.settings(), given)?.load()?;
+        config.apply(settings)?;
+        Ok(config)
+    }
+
+    /// The settings the options stand for
+    pub fn settings(&self) -> Settings {
+        let log_level = match self.log_level {
+            Some(level) => Some(level.to_string().to_lowercase()),
+            None if self.verbose => Some("debug".to_string()),
+            None => None,
+        };
+
+        Settings {
+            log_level,
+            transport: TransportSettings {
+                quic_listen: self.quic_listen.clone(),
+                udp_listen: self.udp_listen,
+                unix_socket: self.unix_socket.clone(),
+                mgmt_socket: self.mgmt_socket.clone(),
+                routes: self.routes.iter().map(UpstreamRoute::to_string).collect(),
+                discover: self.discover,
+                node_id: self.node_id.clone(),
+            },
+            xdp: XdpSettings {
+                enabled: !self.no_xdp,
+                interfaces: self.interfaces.clone(),
+                interface_pattern: self.interface_pattern.clone(),
+                object: self.xdp_object.clone(),
+                tc_egress: self.tc_egress,
+            },
+            cs: CsSettings {
+                capacity: self.cs_capacity,
+                path: self.cs_path.clone(),
+                kernel_entries: self.kernel_cs_entries,
+                kernel_max_bytes: self.kernel_cs_max_bytes,
+            },
+            security: SecuritySettings {
+                cert: self.cert.clone(),
+                key: self.key.clone(),
+            },
+        }
+    }
+
+    /// Take the options standing for settings from `settings`
+    pub fn apply(&mut self, settings: Settings) -> Result<()> {
+        let Settings {
+            log_level,
+            transport,
+            xdp,
+            cs,
+            security,
+        } = settings;
+
+        self.log_level = log_level
+            .map(|level| level.parse().map_err(|_| anyhow!("Invalid log level {}", level)))
+            .transpose()?;
+        self.quic_listen = transport.quic_listen;
+        self.udp_listen = transport.udp_listen;
+        self.unix_socket = transport.unix_socket;
+        self.mgmt_socket = transport.mgmt_socket;
+        self.routes = transport.routes.iter().map(|route| route.parse()).collect::<Result<_>>()?;
+        self.discover = transport.discover;
+        self.node_id = transport.node_id;
+        self.no_xdp = !xdp.enabled;
+        self.interfaces = xdp.interfaces;
+        self.interface_pattern = xdp.interface_pattern;
+        self.xdp_object = xdp.object;
+        self.tc_egress = xdp.tc_egress;
+        self.cs_capacity = cs.capacity;
+        self.cs_path = cs.path;
+        self.kernel_cs_entries = cs.kernel_entries;
+        self.kernel_cs_max_bytes = cs.kernel_max_bytes;
+        self.cert = security.cert;
+        self.key = security.key;
+        Ok(())
+    }
+
+    /// Level to log at
+    pub fn log_level(&self) -> LevelFilter {
+        match self.log_level {
+            Some(level) => level,
+            None if self.verbose => LevelFilter::Debug,
+            None => LevelFilter::Info,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_upstream_route() {
+        let route: UpstreamRoute = "/example/video=router.example.net:6367".parse().unwrap();
+        assert_eq!(route.prefix, Name::from_string("/example/video").unwrap());
+        assert_eq!(route.addr, "router.example.net:6367");
+
+        assert!("/example".parse::<UpstreamRoute>().is_err());
+        assert!("/example=".parse::<UpstreamRoute>().is_err());
+    }
+
+    #[test]
+    fn test_default_settings() {
+        // The defaults of the options are those of the settings
+        let config = DaemonConfig::parse_from(["udcnd"]);
+        assert_eq!(config.settings(), Settings::default());
+    }
+
+    #[test]
+    fn test_layered_settings() {
+        let file = r#"
+            log_level = "debug"
+
+            [transport]
+            routes = ["/example=router.example.net:6367"]
+
+            [xdp]
+            interfaces = ["eth0", "eth1"]
+
+            [cs]
+            capacity = 100
+            path = "/var/lib/udcn/cs.log"
+        "#;
+        let env = [("UDCN_XDP_ENABLED".to_string(), "false".to_string())];
+        let mut config = DaemonConfig::parse_from(["udcnd", "--cs-capacity", "5"]);
+        let settings = ConfigLoader::new()
+            .toml(file, "udcnd.toml")
+            .unwrap()
+            .env(env)
+            .unwrap()
+            .flags(&config.settings(), ["cs.capacity"])
+            .unwrap()
+            .load()
+            .unwrap();
+        config.apply(settings).unwrap();
+
+        assert_eq!(config.interfaces, ["eth0", "eth1"]);
+        assert_eq!(config.routes[0].addr, "router.example.net:6367");
+        assert_eq!(config.cs_path, Some(PathBuf::from("/var/lib/udcn/cs.log")));
+        assert_eq!(config.log_level(), LevelFilter::Debug);
+        assert!(config.no_xdp);
+        // The command line wins
+        assert_eq!(config.cs_capacity, 5);
+        // Left alone
+        assert_eq!(config.quic_listen, "[::]:6367");
+
+        let verbose = DaemonConfig::parse_from(["udcnd", "--verbose"]);
+        assert_eq!(verbose.settings().log_level.as_deref(), Some("debug"));
+    }
+}
@@ -0,0 +1,900 @@
+//! gRPC north-bound management API.
+//!
+//! Serves the `udcn.mgmt.v1.Management` service of `proto/udcn_mgmt.proto`,
+//! so orchestration systems can manage the node without the control socket.
+//! Every call runs the matching control socket command. The messages and the
+//! service routing below are written out as tonic-build would generate them,
+//! so building udcnd needs no protoc; keep them in step with the proto file.
+//!
+//! The API runs over plain TCP, so it is only served on loopback addresses,
+//! and every call must carry the token of `--grpc-token-file`.
+
+use anyhow::{bail, Context as _, Result};
+use log::{debug, info};
+use prost::Message;
+use rust_udcn_quic::{EventFilter, EventKind, NdnQuicTransport, TransportEvent};
+use std::{
+    convert::Infallible,
+    future::Future,
+    fs,
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{net::TcpListener, sync::mpsc};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{
+    body::{empty_body, BoxBody},
+    codec::ProstCodec,
+    codegen::{http, Body, BoxFuture, Context, Poll, Service, StdError},
+    server::{Grpc, NamedService},
+    Code, Request, Response, Status,
+};
+
+use crate::mgmt::{self, Handler, MgmtBody, MgmtRequest};
+
+/// Events buffered for a watcher before the oldest are dropped
+const WATCH_BUFFER: usize = 256;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Face {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(uint64, tag = "2")]
+    pub interests_sent: u64,
+    #[prost(uint64, tag = "3")]
+    pub interests_received: u64,
+    #[prost(uint64, tag = "4")]
+    pub data_sent: u64,
+    #[prost(uint64, tag = "5")]
+    pub data_received: u64,
+    #[prost(uint64, tag = "6")]
+    pub nacks_sent: u64,
+    #[prost(uint64, tag = "7")]
+    pub nacks_received: u64,
+    #[prost(uint64, tag = "8")]
+    pub bytes_sent: u64,
+    #[prost(uint64, tag = "9")]
+    pub bytes_received: u64,
+    #[prost(double, tag = "10")]
+    pub rtt_avg_us: f64,
+    #[prost(uint64, tag = "11")]
+    pub idle_ms: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ListFacesRequest {}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ListFacesResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub faces: Vec<Face>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DestroyFaceRequest {
+    #[prost(string, tag = "1")]
+    pub face: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DestroyFaceResponse {}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Route {
+    #[prost(string, tag = "1")]
+    pub prefix: String,
+    #[prost(string, tag = "2")]
+    pub face: String,
+    #[prost(uint32, tag = "3")]
+    pub cost: u32,
+    #[prost(bool, tag = "4")]
+    pub kernel: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ListRoutesRequest {}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ListRoutesResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub routes: Vec<Route>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AddRouteRequest {
+    #[prost(string, tag = "1")]
+    pub prefix: String,
+    #[prost(string, tag = "2")]
+    pub face: String,
+    #[prost(uint32, tag = "3")]
+    pub cost: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AddRouteResponse {}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RemoveRouteRequest {
+    #[prost(string, tag = "1")]
+    pub prefix: String,
+    #[prost(string, tag = "2")]
+    pub face: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RemoveRouteResponse {}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GetContentStoreRequest {}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ContentStoreStats {
+    #[prost(uint64, tag = "1")]
+    pub entries: u64,
+    #[prost(uint64, tag = "2")]
+    pub max_entries: u64,
+    #[prost(uint64, tag = "3")]
+    pub bytes: u64,
+    #[prost(uint64, tag = "4")]
+    pub max_bytes: u64,
+    #[prost(uint64, tag = "5")]
+    pub inserts: u64,
+    #[prost(uint64, tag = "6")]
+    pub hits: u64,
+    #[prost(uint64, tag = "7")]
+    pub misses: u64,
+    #[prost(uint64, optional, tag = "8")]
+    pub kernel_hits: Option<u64>,
+    #[prost(uint64, optional, tag = "9")]
+    pub kernel_entries: Option<u64>,
+    #[prost(double, tag = "10")]
+    pub hit_ratio: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CachedData {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(uint64, tag = "2")]
+    pub bytes: u64,
+    #[prost(uint64, tag = "3")]
+    pub age_ms: u64,
+    #[prost(uint32, tag = "4")]
+    pub freshness_ms: u32,
+    #[prost(bool, tag = "5")]
+    pub kernel: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ListContentRequest {
+    #[prost(string, tag = "1")]
+    pub prefix: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ListContentResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub entries: Vec<CachedData>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct InsertContentRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub wire: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct InsertContentResponse {}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PurgeContentRequest {
+    #[prost(string, tag = "1")]
+    pub prefix: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PurgeContentResponse {
+    #[prost(uint64, tag = "1")]
+    pub entries: u64,
+    #[prost(uint64, optional, tag = "2")]
+    pub kernel_entries: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GetStatsRequest {}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct XdpStats {
+    #[prost(string, repeated, tag = "1")]
+    pub interfaces: Vec<String>,
+    #[prost(uint64, tag = "2")]
+    pub interests_received: u64,
+    #[prost(uint64, tag = "3")]
+    pub data_received: u64,
+    #[prost(uint64, tag = "4")]
+    pub cs_hits: u64,
+    #[prost(uint64, tag = "5")]
+    pub cs_replies: u64,
+    #[prost(uint64, tag = "6")]
+    pub fib_hits: u64,
+    #[prost(uint64, tag = "7")]
+    pub interests_redirected: u64,
+    #[prost(uint64, tag = "8")]
+    pub drops_duplicate: u64,
+    #[prost(uint64, tag = "9")]
+    pub drops_loop: u64,
+    #[prost(uint64, tag = "10")]
+    pub drops_rate_limited: u64,
+    #[prost(uint64, tag = "11")]
+    pub drops_hop_limit: u64,
+    #[prost(uint64, tag = "12")]
+    pub drops_unsolicited: u64,
+    #[prost(uint64, tag = "13")]
+    pub aborted: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Stats {
+    #[prost(uint64, tag = "1")]
+    pub faces: u64,
+    #[prost(uint64, tag = "2")]
+    pub pit_entries: u64,
+    #[prost(uint64, tag = "3")]
+    pub interests_received: u64,
+    #[prost(uint64, tag = "4")]
+    pub interests_forwarded: u64,
+    #[prost(uint64, tag = "5")]
+    pub interests_satisfied: u64,
+    #[prost(uint64, tag = "6")]
+    pub data_received: u64,
+    #[prost(uint64, tag = "7")]
+    pub data_sent: u64,
+    #[prost(uint64, tag = "8")]
+    pub cs_hits: u64,
+    #[prost(uint64, tag = "9")]
+    pub cs_misses: u64,
+    #[prost(double, tag = "10")]
+    pub cs_hit_ratio: f64,
+    #[prost(double, tag = "11")]
+    pub rtt_p50_us: f64,
+    #[prost(double, tag = "12")]
+    pub rtt_p90_us: f64,
+    #[prost(double, tag = "13")]
+    pub rtt_p99_us: f64,
+    #[prost(message, optional, tag = "14")]
+    pub xdp: Option<XdpStats>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct WatchEventsRequest {
+    #[prost(bool, tag = "1")]
+    pub packets: bool,
+    #[prost(string, optional, tag = "2")]
+    pub face: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Packet {
+    #[prost(string, tag = "1")]
+    pub face: String,
+    #[prost(string, tag = "2")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Event {
+    #[prost(uint64, tag = "1")]
+    pub timestamp_ms: u64,
+    #[prost(oneof = "event::Event", tags = "2, 3, 4, 5, 6, 7")]
+    pub event: Option<event::Event>,
+}
+
+/// Nested types of [`Event`]
+pub mod event {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Event {
+        #[prost(string, tag = "2")]
+        FaceCreated(String),
+        #[prost(string, tag = "3")]
+        FaceClosed(String),
+        #[prost(message, tag = "4")]
+        Interest(super::Packet),
+        #[prost(message, tag = "5")]
+        Data(super::Packet),
+        #[prost(message, tag = "6")]
+        Nack(super::Packet),
+        #[prost(string, tag = "7")]
+        Error(String),
+    }
+}
+
+impl From<mgmt::FaceInfo> for Face {
+    fn from(face: mgmt::FaceInfo) -> Self {
+        Self {
+            id: face.id,
+            interests_sent: face.interests_sent,
+            interests_received: face.interests_received,
+            data_sent: face.data_sent,
+            data_received: face.data_received,
+            nacks_sent: face.nacks_sent,
+            nacks_received: face.nacks_received,
+            bytes_sent: face.bytes_sent,
+            bytes_received: face.bytes_received,
+            rtt_avg_us: face.rtt_avg_us,
+            idle_ms: face.idle_ms,
+        }
+    }
+}
+
+impl From<mgmt::RouteInfo> for Route {
+    fn from(route: mgmt::RouteInfo) -> Self {
+        Self {
+            prefix: route.prefix,
+            face: route.face,
+            cost: route.cost,
+            kernel: route.kernel,
+        }
+    }
+}
+
+impl From<mgmt::CsStatsInfo> for ContentStoreStats {
+    fn from(stats: mgmt::CsStatsInfo) -> Self {
+        Self {
+            entries: stats.entries as u64,
+            max_entries: stats.max_entries as u64,
+            bytes: stats.bytes as u64,
+            max_bytes: stats.max_bytes as u64,
+            inserts: stats.inserts,
+            hits: stats.hits,
+            misses: stats.misses,
+            kernel_hits: stats.kernel_hits,
+            kernel_entries: stats.kernel_entries.map(|entries| entries as u64),
+            hit_ratio: stats.hit_ratio,
+        }
+    }
+}
+
+impl From<mgmt::CsEntryInfo> for CachedData {
+    fn from(entry: mgmt::CsEntryInfo) -> Self {
+        Self {
+            name: entry.name,
+            bytes: entry.bytes as u64,
+            age_ms: entry.age_ms,
+            freshness_ms: entry.freshness_ms,
+            kernel: entry.kernel,
+        }
+    }
+}
+
+impl From<mgmt::StatsInfo> for Stats {
+    fn from(stats: mgmt::StatsInfo) -> Self {
+        Self {
+            faces: stats.faces as u64,
+            pit_entries: stats.pit_entries as u64,
+            interests_received: stats.interests_received,
+            interests_forwarded: stats.interests_forwarded,
+            interests_satisfied: stats.interests_satisfied,
+            data_received: stats.data_received,
+            data_sent: stats.data_sent,
+            cs_hits: stats.cs_hits,
+            cs_misses: stats.cs_misses,
+            cs_hit_ratio: stats.cs_hit_ratio,
+            rtt_p50_us: stats.rtt_p50_us,
+            rtt_p90_us: stats.rtt_p90_us,
+            rtt_p99_us: stats.rtt_p99_us,
+            xdp: stats.xdp.map(|xdp| XdpStats {
+                interfaces: xdp.interfaces,
+                interests_received: xdp.interests_received,
+                data_received: xdp.data_received,
+                cs_hits: xdp.cs_hits,
+                cs_replies: xdp.cs_replies,
+                fib_hits: xdp.fib_hits,
+                interests_redirected: xdp.interests_redirected,
+                drops_duplicate: xdp.drops_duplicate,
+                drops_loop: xdp.drops_loop,
+                drops_rate_limited: xdp.drops_rate_limited,
+                drops_hop_limit: xdp.drops_hop_limit,
+                drops_unsolicited: xdp.drops_unsolicited,
+                aborted: xdp.aborted,
+            }),
+        }
+    }
+}
+
+impl Event {
+    /// The event to stream for a transport event
+    fn from_transport(event: TransportEvent) -> Self {
+        let packet = |face: String, name: String| Packet { face, name };
+        let event = match event {
+            TransportEvent::FaceCreated(face) => event::Event::FaceCreated(face),
+            TransportEvent::FaceClosed(face) => event::Event::FaceClosed(face),
+            TransportEvent::InterestReceived { interest, face_id } => {
+                event::Event::Interest(packet(face_id, interest.name().to_string()))
+            }
+            TransportEvent::DataReceived { data, face_id } => {
+                event::Event::Data(packet(face_id, data.name().to_string()))
+            }
+            TransportEvent::NackReceived { nack, face_id } => {
+                event::Event::Nack(packet(face_id, nack.name().to_string()))
+            }
+            TransportEvent::Error(message) => event::Event::Error(message),
+        };
+        let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+
+        Self {
+            timestamp_ms,
+            event: Some(event),
+        }
+    }
+}
+
+/// Calls of the service, run through the control socket commands
+struct Api {
+    handler: Handler,
+    transport: Arc<NdnQuicTransport>,
+}
+
+/// Status of a failed command
+fn status(error: anyhow::Error) -> Status {
+    Status::unknown(format!("{:#}", error))
+}
+
+/// Status of a command answered with the wrong kind of result
+fn unexpected(body: MgmtBody) -> Status {
+    Status::internal(format!("Unexpected result {:?}", body))
+}
+
+impl Api {
+    async fn run(&self, request: MgmtRequest) -> Result<MgmtBody, Status> {
+        debug!("gRPC management command: {:?}", request);
+        self.handler.handle(request).await.map_err(status)
+    }
+
+    /// Run a command that has no result
+    async fn done(&self, request: MgmtRequest) -> Result<(), Status> {
+        match self.run(request).await? {
+            MgmtBody::Done => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    async fn list_faces(&self, _: ListFacesRequest) -> Result<ListFacesResponse, Status> {
+        match self.run(MgmtRequest::FaceList).await? {
+            MgmtBody::Faces(faces) => Ok(ListFacesResponse {
+                faces: faces.into_iter().map(Face::from).collect(),
+            }),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    async fn destroy_face(&self, request: DestroyFaceRequest) -> Result<DestroyFaceResponse, Status> {
+        self.done(MgmtRequest::FaceDestroy { face: request.face }).await?;
+        Ok(DestroyFaceResponse {})
+    }
+
+    async fn list_routes(&self, _: ListRoutesRequest) -> Result<ListRoutesResponse, Status> {
+        match self.run(MgmtRequest::FibList).await? {
+            MgmtBody::Routes(routes) => Ok(ListRoutesResponse {
+                routes: routes.into_iter().map(Route::from).collect(),
+            }),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    async fn add_route(&self, request: AddRouteRequest) -> Result<AddRouteResponse, Status> {
+        let AddRouteRequest { prefix, face, cost } = request;
+        self.done(MgmtRequest::FibAdd { prefix, face, cost }).await?;
+        Ok(AddRouteResponse {})
+    }
+
+    async fn remove_route(&self, request: RemoveRouteRequest) -> Result<RemoveRouteResponse, Status> {
+        let RemoveRouteRequest { prefix, face } = request;
+        self.done(MgmtRequest::FibRemove { prefix, face }).await?;
+        Ok(RemoveRouteResponse {})
+    }
+
+    async fn get_content_store(&self, _: GetContentStoreRequest) -> Result<ContentStoreStats, Status> {
+        match self.run(MgmtRequest::CsStats).await? {
+            MgmtBody::CsStats(stats) => Ok(stats.into()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    async fn list_content(&self, request: ListContentRequest) -> Result<ListContentResponse, Status> {
+        match self.run(MgmtRequest::CsList { prefix: request.prefix }).await? {
+            MgmtBody::CsEntries(entries) => Ok(ListContentResponse {
+                entries: entries.into_iter().map(CachedData::from).collect(),
+            }),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    async fn insert_content(&self, request: InsertContentRequest) -> Result<InsertContentResponse, Status> {
+        self.done(MgmtRequest::CsInsert { wire: request.wire }).await?;
+        Ok(InsertContentResponse {})
+    }
+
+    async fn purge_content(&self, request: PurgeContentRequest) -> Result<PurgeContentResponse, Status> {
+        match self.run(MgmtRequest::CsPurge { prefix: request.prefix }).await? {
+            MgmtBody::Purged(purged) => Ok(PurgeContentResponse {
+                entries: purged.entries as u64,
+                kernel_entries: purged.kernel_entries.map(|entries| entries as u64),
+            }),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    async fn get_stats(&self, _: GetStatsRequest) -> Result<Stats, Status> {
+        match self.run(MgmtRequest::Stats).await? {
+            MgmtBody::Stats(stats) => Ok(stats.into()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Stream the events asked for until the client goes away
+    async fn watch_events(&self, request: WatchEventsRequest) -> Result<ReceiverStream<Result<Event, Status>>, Status> {
+        let mut filter = EventFilter::default()
+            .kind(EventKind::FaceCreated)
+            .kind(EventKind::FaceClosed)
+            .kind(EventKind::Error);
+        if request.packets {
+            filter = filter.kind(EventKind::Interest).kind(EventKind::Data).kind(EventKind::Nack);
+        }
+        if let Some(face) = request.face {
+            filter = filter.face(face);
+        }
+        let mut events = self.transport.subscribe(filter);
+
+        let (sender, receiver) = mpsc::channel(WATCH_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    event = events.recv() => event,
+                    _ = sender.closed() => break,
+                };
+                let Some(event) = event else {
+                    break;
+                };
+                // A watcher that cannot keep up misses events rather than
+                // holding up the others
+                if sender.try_send(Ok(Event::from_transport(event))).is_err() && sender.is_closed() {
+                    break;
+                }
+            }
+        });
+
+        Ok(ReceiverStream::new(receiver))
+    }
+}
+
+/// One call of a gRPC method, answered by `F` with the request message
+struct Call<F>(Option<F>);
+
+impl<M, R, F, Fut> Service<Request<M>> for Call<F>
+where
+    F: FnOnce(M) -> Fut,
+    Fut: Future<Output = Result<R, Status>> + Send + 'static,
+{
+    type Response = Response<R>;
+    type Error = Status;
+    type Future = BoxFuture<Response<R>, Status>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Status>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<M>) -> Self::Future {
+        let answer = self.0.take().expect("a call is made once")(request.into_inner());
+        Box::pin(async move { answer.await.map(Response::new) })
+    }
+}
+
+/// Answer a unary call with `answer`
+async fn unary<M, R, F, Fut, B>(request: http::Request<B>, answer: F) -> http::Response<BoxBody>
+where
+    M: Message + Default + Send + 'static,
+    R: Message + Send + 'static,
+    F: FnOnce(M) -> Fut + Send,
+    Fut: Future<Output = Result<R, Status>> + Send + 'static,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send,
+{
+    Grpc::new(ProstCodec::default()).unary(Call(Some(answer)), request).await
+}
+
+/// Answer a server streaming call with the stream `answer` returns
+async fn server_streaming<M, R, F, Fut, B>(request: http::Request<B>, answer: F) -> http::Response<BoxBody>
+where
+    M: Message + Default + Send + 'static,
+    R: Message + Send + 'static,
+    F: FnOnce(M) -> Fut + Send,
+    Fut: Future<Output = Result<ReceiverStream<Result<R, Status>>, Status>> + Send + 'static,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send,
+{
+    Grpc::new(ProstCodec::default()).server_streaming(Call(Some(answer)), request).await
+}
+
+/// The `udcn.mgmt.v1.Management` service
+#[derive(Clone)]
+pub struct ManagementService {
+    api: Arc<Api>,
+
+    /// Token every call must carry
+    token: Arc<str>,
+}
+
+impl NamedService for ManagementService {
+    const NAME: &'static str = "udcn.mgmt.v1.Management";
+}
+
+impl<B> Service<http::Request<B>> for ManagementService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Infallible>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let api = Arc::clone(&self.api);
+        let authorized = is_authorized(&request, &self.token);
+        Box::pin(async move {
+            if !authorized {
+                debug!("Rejecting gRPC call to {} without the API token", request.uri().path());
+                return Ok(status_response(Code::Unauthenticated));
+            }
+            let method = request.uri().path().rsplit('/').next().unwrap_or_default().to_string();
+            let response = match method.as_str() {
+                "ListFaces" => unary(request, |m| async move { api.list_faces(m).await }).await,
+                "DestroyFace" => unary(request, |m| async move { api.destroy_face(m).await }).await,
+                "ListRoutes" => unary(request, |m| async move { api.list_routes(m).await }).await,
+                "AddRoute" => unary(request, |m| async move { api.add_route(m).await }).await,
+                "RemoveRoute" => unary(request, |m| async move { api.remove_route(m).await }).await,
+                "GetContentStore" => unary(request, |m| async move { api.get_content_store(m).await }).await,
+                "ListContent" => unary(request, |m| async move { api.list_content(m).await }).await,
+                "InsertContent" => unary(request, |m| async move { api.insert_content(m).await }).await,
+                "PurgeContent" => unary(request, |m| async move { api.purge_content(m).await }).await,
+                "GetStats" => unary(request, |m| async move { api.get_stats(m).await }).await,
+                "WatchEvents" => server_streaming(request, |m| async move { api.watch_events(m).await }).await,
+                _ => status_response(Code::Unimplemented),
+            };
+            Ok(response)
+        })
+    }
+}
+
+/// A response ending the call with `code` and no message
+fn status_response(code: Code) -> http::Response<BoxBody> {
+    let mut response = http::Response::new(empty_body());
+    let headers = response.headers_mut();
+    headers.insert("grpc-status", (code as i32).into());
+    headers.insert(http::header::CONTENT_TYPE, http::HeaderValue::from_static("application/grpc"));
+    response
+}
+
+/// Whether `request` carries `authorization: Bearer <token>`
+fn is_authorized<B>(request: &http::Request<B>, token: &str) -> bool {
+    let Some(value) = request.headers().get(http::header::AUTHORIZATION) else {
+        return false;
+    };
+    let expected = format!("Bearer {}", token);
+    // Compared in constant time, so the token cannot be guessed a byte at
+    // a time
+    value.len() == expected.len()
+        && value
+            .as_bytes()
+            .iter()
+            .zip(expected.as_bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Read the API token from the file at `path`
+pub fn read_token(path: &Path) -> Result<String> {
+    let token = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read gRPC API token from {}", path.display()))?;
+    let token = token.trim();
+    if token.is_empty() {
+        bail!("gRPC API token file {} is empty", path.display());
+    }
+    Ok(token.to_string())
+}
+
+/// Serves the management API over gRPC
+pub struct GrpcServer {
+    /// The listening socket
+    listener: TcpListener,
+
+    /// The service answering the calls
+    service: ManagementService,
+}
+
+impl GrpcServer {
+    /// Bind the API to `addr`, which must be a loopback address, the calls
+    /// carrying `token` acting on the same state as the control socket
+    /// commands
+    pub(crate) async fn bind(
+        addr: SocketAddr,
+        token: String,
+        handler: Handler,
+        transport: Arc<NdnQuicTransport>,
+    ) -> Result<Self> {
+        // Without TLS the token would cross the network in the clear
+        if !addr.ip().is_loopback() {
+            bail!("Refusing to serve the gRPC API on non-loopback address {}", addr);
+        }
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind gRPC API to {}", addr))?;
+        info!("Serving the gRPC management API on {}", listener.local_addr()?);
+
+        Ok(Self {
+            listener,
+            service: ManagementService {
+                api: Arc::new(Api { handler, transport }),
+                token: token.into(),
+            },
+        })
+    }
+
+    /// Address the API is served on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve clients until the task is cancelled
+    pub async fn run(self) {
+        let result = tonic::transport::Server::builder()
+            .add_service(self.service)
+            .serve_with_incoming(TcpListenerStream::new(self.listener))
+            .await;
+        if let Err(e) = result {
+            log::warn!("gRPC management API stopped: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mgmt::tests::add_peer_face;
+    use rust_udcn_quic::TransportConfig;
+    use tonic::{client::Grpc as Client, codegen::http::uri::PathAndQuery, transport::Channel};
+
+    const TOKEN: &str = "secret";
+
+    /// `message` as a request carrying `token`
+    fn request<M>(message: M, token: &str) -> Request<M> {
+        let mut request = Request::new(message);
+        request
+            .metadata_mut()
+            .insert("authorization", format!("Bearer {}", token).parse().unwrap());
+        request
+    }
+
+    async fn call<M, R>(client: &mut Client<Channel>, method: &'static str, message: M) -> Result<R, Status>
+    where
+        M: Message + Send + Sync + 'static,
+        R: Message + Default + Send + Sync + 'static,
+    {
+        client.ready().await.unwrap();
+        let path = PathAndQuery::from_static(method);
+        client
+            .unary(request(message, TOKEN), path, ProstCodec::default())
+            .await
+            .map(Response::into_inner)
+    }
+
+    #[tokio::test]
+    async fn test_calls_need_token_and_loopback() {
+        let transport = Arc::new(NdnQuicTransport::new(TransportConfig::default()).await.unwrap());
+        let handler = Handler::new(Arc::clone(&transport), None, None);
+        assert!(GrpcServer::bind("0.0.0.0:0".parse().unwrap(), TOKEN.to_string(), handler, Arc::clone(&transport))
+            .await
+            .is_err());
+
+        let handler = Handler::new(Arc::clone(&transport), None, None);
+        let server = GrpcServer::bind("127.0.0.1:0".parse().unwrap(), TOKEN.to_string(), handler, transport)
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let channel = Channel::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap();
+        let mut client = Client::new(channel);
+        for message in [Request::new(ListFacesRequest {}), request(ListFacesRequest {}, "guess")] {
+            client.ready().await.unwrap();
+            let error = client
+                .unary::<_, ListFacesResponse, _>(
+                    message,
+                    PathAndQuery::from_static("/udcn.mgmt.v1.Management/ListFaces"),
+                    ProstCodec::default(),
+                )
+                .await
+                .unwrap_err();
+            assert_eq!(error.code(), Code::Unauthenticated);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_manage_over_grpc() {
+        let transport = Arc::new(
+            NdnQuicTransport::new(TransportConfig {
+                enable_forwarding: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+        );
+        let handler = Handler::new(Arc::clone(&transport), None, None);
+        let server = GrpcServer::bind("127.0.0.1:0".parse().unwrap(), TOKEN.to_string(), handler, Arc::clone(&transport))
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let channel = Channel::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap();
+        let mut client = Client::new(channel.clone());
+
+        let mut watch = Client::new(channel);
+        watch.ready().await.unwrap();
+        let mut events = watch
+            .server_streaming(
+                request(WatchEventsRequest::default(), TOKEN),
+                PathAndQuery::from_static("/udcn.mgmt.v1.Management/WatchEvents"),
+                ProstCodec::<WatchEventsRequest, Event>::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+
+        let _peer = add_peer_face(&transport).await;
+        let event = events.message().await.unwrap().unwrap();
+        assert_eq!(event.event, Some(event::Event::FaceCreated("test://peer".to_string())));
+
+        let faces: ListFacesResponse =
+            call(&mut client, "/udcn.mgmt.v1.Management/ListFaces", ListFacesRequest {}).await.unwrap();
+        assert_eq!(faces.faces.len(), 1);
+        assert_eq!(faces.faces[0].id, "test://peer");
+
+        let add = AddRouteRequest {
+            prefix: "/example".to_string(),
+            face: "test://peer".to_string(),
+            cost: 5,
+        };
+        let _: AddRouteResponse = call(&mut client, "/udcn.mgmt.v1.Management/AddRoute", add).await.unwrap();
+        let routes: ListRoutesResponse =
+            call(&mut client, "/udcn.mgmt.v1.Management/ListRoutes", ListRoutesRequest {}).await.unwrap();
+        assert_eq!(
+            routes.routes,
+            vec![Route {
+                prefix: "/example".to_string(),
+                face: "test://peer".to_string(),
+                cost: 5,
+                kernel: false,
+            }]
+        );
+
+        // Command errors come back as the call's status
+        let remove = RemoveRouteRequest {
+            prefix: "/other".to_string(),
+            face: "test://peer".to_string(),
+        };
+        let error = call::<_, RemoveRouteResponse>(&mut client, "/udcn.mgmt.v1.Management/RemoveRoute", remove)
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Code::Unknown);
+
+        let stats: Stats = call(&mut client, "/udcn.mgmt.v1.Management/GetStats", GetStatsRequest {}).await.unwrap();
+        assert_eq!(stats.faces, 1);
+        assert_eq!(stats.xdp, None);
+    }
+}
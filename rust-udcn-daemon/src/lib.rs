@@ -0,0 +1,525 @@
+//! µDCN forwarding daemon (udcnd)
+//!
+//! Runs the XDP fast path and the userspace forwarder as one node. The XDP
+//! program handles what it can in the kernel and passes the rest up on the
+//! NDN UDP port, where the daemon's UDP faces feed it to the userspace
+//! forwarder together with QUIC, Unix socket and UDP peers. The
+//! [`bridge::KernelBridge`] keeps the kernel maps coherent with userspace.
+
+pub mod bridge;
+pub mod config;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod mgmt;
+#[cfg(feature = "otlp")]
+pub mod telemetry;
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use rust_udcn_common::{metrics::UdcnMetrics, ndn::Name};
+use rust_udcn_quic::{
+    Discovery, DiscoveryOptions, EventFilter, EventKind, GatewayOptions, HttpGateway, LinkFace, NdnFace,
+    NdnQuicTransport, RepoServer, StreamLink, TransportConfig, TransportEvent, UdpFaceListener, UnixFaceListener,
+};
+use rust_udcn_xdp::{CsAdmission, CsPrefixRule, FibMode, HotplugEvent, RateLimit, XdpManager, XdpOptions};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    bridge::KernelBridge,
+    config::{DaemonConfig, UpstreamRoute},
+    mgmt::ManagementServer,
+};
+#[cfg(feature = "grpc")]
+use crate::{grpc::GrpcServer, mgmt::Handler};
+
+/// How long a shutting-down daemon waits for outstanding exchanges
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a shutting-down daemon waits for the background tasks of the
+/// transport and the fast path to stop
+const SHUTDOWN_TASKS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// ID of the face the HTTP gateway fetches content through
+const HTTP_GATEWAY_FACE: &str = "internal://http-gateway";
+
+/// Bytes buffered between the HTTP gateway and the forwarder
+const HTTP_GATEWAY_BUFFER: usize = 256 * 1024;
+
+/// A running forwarding daemon
+pub struct Daemon {
+    /// Userspace forwarder and its faces
+    transport: Arc<NdnQuicTransport>,
+
+    /// The XDP program, unless running without the fast path
+    xdp: Option<Arc<Mutex<XdpManager>>>,
+
+    /// Kernel map synchronization, when XDP is loaded
+    bridge: Option<Arc<KernelBridge>>,
+
+    /// Neighbor discovery, when enabled
+    discovery: Option<Discovery>,
+
+    /// Repo command server, when enabled
+    repo: Option<RepoServer>,
+
+    /// Background tasks stopped on shutdown
+    tasks: Vec<JoinHandle<()>>,
+
+    /// Cancelled on shutdown to stop the tasks of the transport and the
+    /// fast path
+    cancellation: CancellationToken,
+
+    /// Configuration the daemon runs with
+    config: DaemonConfig,
+
+    /// Static routes installed, with the face of their upstream
+    upstreams: Vec<(UpstreamRoute, String)>,
+}
+
+impl Daemon {
+    /// Load the fast path, start listening and install the configured routes
+    pub async fn start(config: DaemonConfig) -> Result<Self> {
+        let cancellation = CancellationToken::new();
+        let xdp = if config.no_xdp {
+            info!("Running without the XDP fast path");
+            None
+        } else {
+            let mut options = XdpOptions {
+                fib_mode: if config.fib_trie { FibMode::LpmTrie } else { FibMode::Hash },
+                pin_path: config.pin_path.clone(),
+                cancellation: cancellation.child_token(),
+                ..Default::default()
+            };
+            options.attach_flags.chain = config.xdp_chain;
+            if let Some(entries) = config.kernel_pit_entries {
+                options.map_sizes.pit_entries = entries;
+            }
+            if let Some(entries) = config.kernel_fib_entries {
+                options.map_sizes.fib_entries = entries;
+            }
+            if let Some(entries) = config.kernel_cs_entries {
+                options.map_sizes.cs_entries = entries;
+            }
+            let mut xdp = match &config.xdp_object {
+                Some(path) => XdpManager::load_from_file_with_options(path, options).await?,
+                None => XdpManager::load_from_embedded_with_options(options).await?,
+            };
+            let xdp_config = xdp.config();
+            let mut settings = xdp_config.get().await?;
+            if let Some(interests_per_sec) = config.interest_rate_limit {
+                settings.rate_limit = Some(RateLimit::new(interests_per_sec));
+            }
+            if let Some(min_size) = config.kernel_cs_min_size {
+                settings.cs_min_size = min_size;
+            }
+            if config.kernel_cs_max_size.is_some() {
+                settings.cs_max_size = config.kernel_cs_max_size;
+            }
+            if !config.kernel_cs_allow.is_empty() {
+                settings.cs_admission = CsAdmission::Listed;
+            }
+            xdp_config.set(&settings).await?;
+            let cs_prefixes = xdp.cs_prefixes();
+            let rules = [
+                (&config.kernel_cs_allow, CsPrefixRule::Allow),
+                (&config.kernel_cs_deny, CsPrefixRule::Deny),
+            ];
+            for (prefixes, rule) in rules {
+                for prefix in prefixes {
+                    let prefix = Name::from_string(prefix)
+                        .map_err(|e| anyhow!("Invalid kernel CS prefix {}: {}", prefix, e))?;
+                    cs_prefixes.set(&prefix, rule).await?;
+                }
+            }
+            for interface in &config.interfaces {
+                xdp.attach(interface)
+                    .with_context(|| format!("Failed to attach XDP program to {}", interface))?;
+                if config.tc_egress {
+                    xdp.attach_egress(interface)
+                        .with_context(|| format!("Failed to attach TC egress program to {}", interface))?;
+                }
+            }
+            Some(xdp)
+        };
+        let bridge = xdp.as_ref().map(|xdp| Arc::new(KernelBridge::new(xdp)));
+        let kernel_pit = xdp.as_ref().map(|xdp| xdp.pit());
+        let kernel_cs = xdp.as_ref().map(|xdp| xdp.cs());
+        let kernel_tasks = xdp.as_ref().map(|xdp| xdp.cancellation_token());
+        let xdp = xdp.map(|xdp| Arc::new(Mutex::new(xdp)));
+
+        let transport = Arc::new(
+            NdnQuicTransport::with_cancellation(
+                TransportConfig::from_settings(&config.settings()),
+                cancellation.child_token(),
+            )
+            .await?,
+        );
+
+        let mut daemon = Self {
+            transport,
+            xdp,
+            bridge,
+            discovery: None,
+            repo: None,
+            tasks: Vec::new(),
+            cancellation,
+            config: config.clone(),
+            upstreams: Vec::new(),
+        };
+
+        daemon.listen_udp(&config).await?;
+        daemon.listen_unix(&config)?;
+        daemon.serve_management(&config)?;
+        if let Some(addr) = config.grpc_listen {
+            daemon.serve_grpc(addr, &config).await?;
+        }
+        daemon.watch_faces();
+        daemon.watch_interfaces(&config)?;
+        daemon.sync_kernel_metrics(&config);
+        if let Some(prefix) = &config.repo_prefix {
+            let prefix = Name::from_string(prefix).map_err(|e| anyhow!("Invalid repo prefix {}: {}", prefix, e))?;
+            daemon.repo = Some(RepoServer::start(Arc::clone(&daemon.transport), prefix).await?);
+        }
+        if let Some(addr) = config.http_listen {
+            daemon.serve_http(addr, &config).await?;
+        }
+        if config.discover {
+            daemon.start_discovery(&config).await?;
+        }
+
+        if let (Some(bridge), Some(content_store)) = (&daemon.bridge, daemon.transport.content_store()) {
+            let interval = Duration::from_millis(config.sync_interval_ms);
+            daemon.tasks.push(Arc::clone(bridge).spawn_sync(content_store, interval));
+        }
+        if let (Some(pit), Some(token)) = (kernel_pit, &kernel_tasks) {
+            let interval = Duration::from_millis(config.pit_gc_interval_ms);
+            daemon.tasks.push(pit.spawn_gc(interval, daemon.transport.metrics(), token.clone()));
+        }
+        if let (Some(cs), Some(max_bytes), Some(token)) = (kernel_cs, config.kernel_cs_max_bytes, &kernel_tasks) {
+            let interval = Duration::from_millis(config.sync_interval_ms);
+            daemon.tasks.push(cs.spawn_eviction(interval, max_bytes, token.clone()));
+        }
+
+        daemon.install_routes().await;
+
+        info!("udcnd started");
+        Ok(daemon)
+    }
+
+    /// Get the userspace transport
+    pub fn transport(&self) -> Arc<NdnQuicTransport> {
+        Arc::clone(&self.transport)
+    }
+
+    /// Accept UDP faces, including the packets passed up by the XDP program
+    async fn listen_udp(&mut self, config: &DaemonConfig) -> Result<()> {
+        let listener = UdpFaceListener::bind(config.udp_listen).await?;
+        let transport = Arc::clone(&self.transport);
+        self.tasks.push(tokio::spawn(async move {
+            while let Ok(face) = listener.accept().await {
+                transport.add_face(face).await;
+            }
+        }));
+        Ok(())
+    }
+
+    /// Accept local applications on the Unix socket
+    fn listen_unix(&mut self, config: &DaemonConfig) -> Result<()> {
+        let listener = UnixFaceListener::bind(&config.unix_socket)?;
+        let transport = Arc::clone(&self.transport);
+        self.tasks.push(tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok(face) => transport.add_face(face).await,
+                    Err(e) => warn!("Failed to accept Unix socket face: {}", e),
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    /// Accept management commands on the control socket
+    fn serve_management(&mut self, config: &DaemonConfig) -> Result<()> {
+        let server = ManagementServer::bind(
+            &config.mgmt_socket,
+            Arc::clone(&self.transport),
+            self.bridge.clone(),
+            self.xdp.clone(),
+        )?;
+        self.tasks.push(tokio::spawn(server.run()));
+        Ok(())
+    }
+
+    /// Serve the management API over gRPC on `addr`
+    #[cfg(feature = "grpc")]
+    async fn serve_grpc(&mut self, addr: SocketAddr, config: &DaemonConfig) -> Result<()> {
+        let path = config
+            .grpc_token_file
+            .as_ref()
+            .ok_or_else(|| anyhow!("--grpc-listen needs --grpc-token-file"))?;
+        let token = grpc::read_token(path)?;
+        let handler = Handler::new(Arc::clone(&self.transport), self.bridge.clone(), self.xdp.clone());
+        let server = GrpcServer::bind(addr, token, handler, Arc::clone(&self.transport)).await?;
+        self.tasks.push(tokio::spawn(server.run()));
+        Ok(())
+    }
+
+    #[cfg(not(feature = "grpc"))]
+    async fn serve_grpc(&mut self, _addr: SocketAddr, _config: &DaemonConfig) -> Result<()> {
+        warn!("udcnd was built without the grpc feature, not serving the gRPC API");
+        Ok(())
+    }
+
+    /// Serve content to HTTP clients on `addr`, fetching it through a face
+    /// of the gateway's own so the FIB and the content store apply
+    async fn serve_http(&mut self, addr: SocketAddr, config: &DaemonConfig) -> Result<()> {
+        let prefix = Name::from_string(&config.http_prefix)
+            .map_err(|e| anyhow!("Invalid HTTP gateway prefix {}: {}", config.http_prefix, e))?;
+        let (forwarder_end, gateway_end) = tokio::io::duplex(HTTP_GATEWAY_BUFFER);
+        let face = LinkFace::new(
+            HTTP_GATEWAY_FACE.to_string(),
+            Arc::new(StreamLink::new(forwarder_end)),
+            self.transport.metrics(),
+        )
+        .with_local(true);
+        self.transport.add_face(Arc::new(face)).await;
+        let face = LinkFace::new(
+            HTTP_GATEWAY_FACE.to_string(),
+            Arc::new(StreamLink::new(gateway_end)),
+            Arc::new(UdcnMetrics::new()),
+        );
+
+        let options = GatewayOptions {
+            prefix,
+            ..Default::default()
+        };
+        let gateway = HttpGateway::bind(addr, Arc::new(face), options)
+            .await?
+            .with_metrics(self.transport.metrics());
+        self.tasks.push(tokio::spawn(gateway.run()));
+        Ok(())
+    }
+
+    /// Announce the QUIC server to neighbors and connect to theirs
+    async fn start_discovery(&mut self, config: &DaemonConfig) -> Result<()> {
+        let quic_port = self
+            .transport
+            .server_addresses()
+            .first()
+            .map(|addr| addr.port())
+            .context("Discovery requires a QUIC server")?;
+        let mut options = DiscoveryOptions {
+            quic_port,
+            ..Default::default()
+        };
+        if let Some(node_id) = &config.node_id {
+            options.node_id = node_id.clone();
+        }
+        self.discovery = Some(Discovery::start(Arc::clone(&self.transport), options).await?);
+        Ok(())
+    }
+
+    /// Drop the kernel routes of faces as they close
+    fn watch_faces(&mut self) {
+        let Some(bridge) = self.bridge.clone() else {
+            return;
+        };
+        let mut closed = self.transport.subscribe(EventFilter::default().kind(EventKind::FaceClosed));
+        self.tasks.push(tokio::spawn(async move {
+            while let Some(event) = closed.recv().await {
+                if let TransportEvent::FaceClosed(face_id) = event {
+                    bridge.release_face(&face_id).await;
+                }
+            }
+        }));
+    }
+
+    /// Mirror the counters of the XDP program into the transport's metrics,
+    /// so they cover the whole node
+    fn sync_kernel_metrics(&mut self, config: &DaemonConfig) {
+        let Some(xdp) = self.xdp.clone() else {
+            return;
+        };
+        let metrics = self.transport.metrics();
+        let interval = Duration::from_millis(config.sync_interval_ms);
+        self.tasks.push(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match xdp.lock().await.get_metrics().await {
+                    Ok(kernel) => kernel.record(&metrics),
+                    Err(e) => warn!("Failed to read kernel metrics: {}", e),
+                }
+            }
+        }));
+    }
+
+    /// Keep the XDP program attached to the interfaces matching the
+    /// configured patterns
+    fn watch_interfaces(&mut self, config: &DaemonConfig) -> Result<()> {
+        let Some(xdp) = self.xdp.clone() else {
+            return Ok(());
+        };
+        if config.interface_pattern.is_empty() {
+            return Ok(());
+        }
+
+        let (events_tx, mut events) = mpsc::channel(64);
+        self.tasks.push(rust_udcn_xdp::watch_interfaces(
+            xdp,
+            config.interface_pattern.clone(),
+            config.tc_egress,
+            events_tx,
+        )?);
+        self.tasks.push(tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                match event {
+                    HotplugEvent::Link(link) => info!("Interface {} {:?}", link.name, link.kind),
+                    HotplugEvent::Attached(interface) => info!("Attached XDP program to new interface {}", interface),
+                    HotplugEvent::AttachFailed { interface, error } => {
+                        warn!("Failed to attach XDP program to {}: {}", interface, error)
+                    }
+                    HotplugEvent::Detached(interface) => info!("Interface {} removed", interface),
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    /// Install the configured static routes not installed yet
+    async fn install_routes(&mut self) {
+        for route in self.config.routes.clone() {
+            if self.upstreams.iter().any(|(installed, _)| *installed == route) {
+                continue;
+            }
+            match self.add_upstream(&route).await {
+                Ok(face_id) => self.upstreams.push((route, face_id)),
+                Err(e) => warn!("Failed to add route {} via {}: {}", route.prefix, route.addr, e),
+            }
+        }
+    }
+
+    /// Apply a configuration read again on SIGHUP
+    ///
+    /// Static routes, XDP interfaces and the certificate follow it; other
+    /// settings, like the listening addresses, only take effect on restart.
+    pub async fn reload(&mut self, config: DaemonConfig) {
+        let previous = std::mem::replace(&mut self.config, config);
+
+        let (kept, dropped) = std::mem::take(&mut self.upstreams)
+            .into_iter()
+            .partition(|(route, _)| self.config.routes.contains(route));
+        self.upstreams = kept;
+        for (route, face_id) in dropped {
+            if let Some(forwarder) = self.transport.forwarder() {
+                forwarder.remove_route(&route.prefix, &face_id).await;
+            }
+            if let Some(bridge) = &self.bridge {
+                if let Err(e) = bridge.remove_route(&route.prefix, &face_id).await {
+                    warn!("Failed to remove kernel route {}: {}", route.prefix, e);
+                }
+            }
+            info!("Removed route {} via {}", route.prefix, route.addr);
+        }
+        self.install_routes().await;
+
+        let config = &self.config;
+        if let Some(xdp) = &self.xdp {
+            let mut xdp = xdp.lock().await;
+            for interface in previous.interfaces.iter().filter(|i| !config.interfaces.contains(i)) {
+                match xdp.detach(interface) {
+                    Ok(()) => info!("Detached XDP program from {}", interface),
+                    Err(e) => warn!("Failed to detach XDP program from {}: {}", interface, e),
+                }
+            }
+            for interface in config.interfaces.iter().filter(|i| !previous.interfaces.contains(i)) {
+                let result = xdp.attach(interface).and_then(|()| match config.tc_egress {
+                    true => xdp.attach_egress(interface),
+                    false => Ok(()),
+                });
+                match result {
+                    Ok(()) => info!("Attached XDP program to {}", interface),
+                    Err(e) => warn!("Failed to attach XDP program to {}: {}", interface, e),
+                }
+            }
+        }
+
+        if let Err(e) = self.transport.reload_certificates().await {
+            warn!("Failed to reload certificates: {:#}", e);
+        }
+
+        let restart_needed: Vec<&str> = [
+            ("quic_listen", previous.quic_listen != config.quic_listen),
+            ("udp_listen", previous.udp_listen != config.udp_listen),
+            ("unix_socket", previous.unix_socket != config.unix_socket),
+            ("mgmt_socket", previous.mgmt_socket != config.mgmt_socket),
+            ("cert", previous.cert != config.cert),
+            ("key", previous.key != config.key),
+            ("no_xdp", previous.no_xdp != config.no_xdp),
+            ("xdp_object", previous.xdp_object != config.xdp_object),
+            ("interface_pattern", previous.interface_pattern != config.interface_pattern),
+            ("cs_capacity", previous.cs_capacity != config.cs_capacity),
+            ("cs_path", previous.cs_path != config.cs_path),
+            ("discover", previous.discover != config.discover),
+        ]
+        .into_iter()
+        .filter_map(|(name, changed)| changed.then_some(name))
+        .collect();
+        if !restart_needed.is_empty() {
+            warn!("Restart udcnd to apply the new {}", restart_needed.join(", "));
+        }
+
+        info!("Configuration reloaded");
+    }
+
+    /// Connect to an upstream forwarder and route a prefix to it, in
+    /// userspace and in the kernel; returns the upstream's face
+    async fn add_upstream(&self, route: &UpstreamRoute) -> Result<String> {
+        let face = self.transport.connect(route.addr.as_str()).await?;
+        let face_id = NdnFace::id(face.as_ref()).to_string();
+        if let Some(forwarder) = self.transport.forwarder() {
+            forwarder.add_route(route.prefix.clone(), &face_id, 0).await;
+        }
+        if let Some(bridge) = &self.bridge {
+            bridge.install_route(&route.prefix, &face_id, 0).await?;
+        }
+        info!("Routed {} to {}", route.prefix, face_id);
+        Ok(face_id)
+    }
+
+    /// Drain the transport and detach the fast path
+    pub async fn shutdown(mut self) -> Result<()> {
+        self.discovery.take();
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+        if let Err(e) = self.transport.shutdown(SHUTDOWN_DRAIN_TIMEOUT).await {
+            warn!("Failed to shut down transport: {}", e);
+        }
+        if let Some(xdp) = &self.xdp {
+            xdp.lock().await.detach_all()?;
+        }
+
+        // The forwarder is drained, stop what runs behind it
+        self.cancellation.cancel();
+        let mut tasks = self.transport.stop_tasks();
+        if let Some(xdp) = &self.xdp {
+            tasks.extend(xdp.lock().await.stop_tasks());
+        }
+        let stopped = async {
+            for task in tasks {
+                let _ = task.await;
+            }
+        };
+        if tokio::time::timeout(SHUTDOWN_TASKS_TIMEOUT, stopped).await.is_err() {
+            warn!("Background tasks did not stop within {:?}", SHUTDOWN_TASKS_TIMEOUT);
+        }
+        info!("udcnd stopped");
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+//! udcnd: µDCN forwarding daemon
+
+use anyhow::Result;
+use log::{info, warn};
+#[cfg(feature = "otlp")]
+use rust_udcn_daemon::telemetry::Telemetry;
+use rust_udcn_daemon::{config::DaemonConfig, Daemon};
+use tokio::signal::unix::{signal, SignalKind};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let config = DaemonConfig::load()?;
+
+    // RUST_LOG, when set, wins over the configured level, also on reload
+    let log_from_env = std::env::var_os("RUST_LOG").is_some();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("trace")).init();
+    if !log_from_env {
+        log::set_max_level(config.log_level());
+    }
+
+    #[cfg(feature = "otlp")]
+    let telemetry = config.otlp_endpoint.as_deref().map(Telemetry::init).transpose()?;
+    #[cfg(not(feature = "otlp"))]
+    if config.otlp_endpoint.is_some() {
+        warn!("udcnd was built without the otlp feature, not exporting spans");
+    }
+
+    let mut daemon = Daemon::start(config).await?;
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    loop {
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => break result?,
+            _ = terminate.recv() => break,
+            _ = hangup.recv() => {
+                info!("Reloading configuration");
+                match DaemonConfig::load() {
+                    Ok(config) => {
+                        if !log_from_env {
+                            log::set_max_level(config.log_level());
+                        }
+                        daemon.reload(config).await;
+                    }
+                    Err(e) => warn!("Keeping the current configuration: {:#}", e),
+                }
+            }
+        }
+    }
+    info!("Shutting down");
+
+    let result = daemon.shutdown().await;
+    #[cfg(feature = "otlp")]
+    if let Some(telemetry) = telemetry {
+        telemetry.shutdown();
+    }
+    result
+}
@@ -0,0 +1,1281 @@
+//! Management protocol served on the daemon's control socket.
+//!
+//! Each request and each response is one JSON object on its own line, and a
+//! connection may carry any number of requests. The CLI uses [`MgmtClient`],
+//! but anything that can write a line to a Unix socket can manage the daemon.
+//!
+//! A `watch` request turns the connection into a stream of [`MgmtEvent`]
+//! lines, one per face change, until the client hangs up.
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use rust_udcn_common::{
+    metrics::Rates,
+    ndn::{Data, Name},
+};
+use rust_udcn_quic::{ContentStore, EventFilter, EventKind, FaceStats, NdnQuicTransport, TransportEvent};
+use rust_udcn_xdp::{XdpAttachFlags, XdpManager};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs,
+    io::ErrorKind,
+    os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixListener, UnixStream,
+    },
+    sync::Mutex,
+};
+
+use crate::bridge::KernelBridge;
+
+/// Control socket path of the daemon
+pub const DEFAULT_MGMT_SOCKET_PATH: &str = "/run/udcn/udcnd.sock";
+
+/// A management command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum MgmtRequest {
+    /// Route `prefix` to `face`, a userspace face ID or the index of an
+    /// interface the XDP program is attached to
+    FibAdd {
+        prefix: String,
+        face: String,
+        cost: u32,
+    },
+
+    /// Remove the route of `prefix` to `face`
+    FibRemove { prefix: String, face: String },
+
+    /// List the routes
+    FibList,
+
+    /// List the faces with their counters
+    FaceList,
+
+    /// Close a face
+    FaceDestroy { face: String },
+
+    /// Describe the content store
+    CsInfo,
+
+    /// List the cached Data under `prefix`
+    CsList { prefix: String },
+
+    /// Cache a Data packet, given in TLV wire format
+    CsInsert { wire: Vec<u8> },
+
+    /// Occupancy and hit counters of the content stores
+    CsStats,
+
+    /// Drop cached Data under `prefix`
+    CsErase { prefix: String },
+
+    /// Drop cached Data under `prefix` from the userspace and kernel
+    /// content stores
+    CsPurge { prefix: String },
+
+    /// List the pending Interests of the userspace and kernel PITs
+    PitList,
+
+    /// Drop the pending Interests under `prefix` from both PITs, only
+    /// those past their lifetime unless `all`
+    PitFlush { prefix: String, all: bool },
+
+    /// Forwarding counters of the userspace and kernel paths
+    Stats,
+
+    /// Attach the XDP program to `interface`, in SKB or offload mode when
+    /// asked, taking over from an XDP program already there with `chain`
+    XdpAttach {
+        interface: String,
+        skb_mode: bool,
+        offload: bool,
+        chain: bool,
+    },
+
+    /// Detach the XDP program from `interface`
+    XdpDetach { interface: String },
+
+    /// Stream face events on this connection from now on
+    Watch,
+}
+
+/// Reply to a [`MgmtRequest`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "body", rename_all = "kebab-case")]
+pub enum MgmtResponse {
+    /// The command succeeded
+    Ok(MgmtBody),
+
+    /// The command failed with this message
+    Error(String),
+}
+
+/// Result of a successful command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "kebab-case")]
+pub enum MgmtBody {
+    /// The command has no result
+    Done,
+
+    /// Routes, for `fib-list`
+    Routes(Vec<RouteInfo>),
+
+    /// Faces, for `face-list`
+    Faces(Vec<FaceInfo>),
+
+    /// Content store state, for `cs-info`
+    ContentStore(CsInfo),
+
+    /// Cached Data, for `cs-list`
+    CsEntries(Vec<CsEntryInfo>),
+
+    /// Content store counters, for `cs-stats`
+    CsStats(CsStatsInfo),
+
+    /// Number of Data packets dropped, for `cs-erase`
+    Erased(usize),
+
+    /// Entries dropped from each content store, for `cs-purge`
+    Purged(PurgeInfo),
+
+    /// Pending Interests, for `pit-list`
+    Pit(Vec<PitEntryInfo>),
+
+    /// Entries dropped from each PIT, for `pit-flush`
+    Flushed(FlushInfo),
+
+    /// Counters, for `stats`
+    Stats(StatsInfo),
+
+    /// Mode the XDP program runs in, for `xdp-attach`
+    XdpMode(String),
+}
+
+/// Something that happened in the daemon, streamed after `watch`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum MgmtEvent {
+    /// A face was opened
+    FaceCreated { face: String },
+
+    /// A face was closed
+    FaceClosed { face: String },
+
+    /// The transport reported an error
+    Error { message: String },
+}
+
+impl MgmtEvent {
+    /// The event to stream for a transport event, if any
+    fn from_transport(event: TransportEvent) -> Option<Self> {
+        match event {
+            TransportEvent::FaceCreated(face) => Some(MgmtEvent::FaceCreated { face }),
+            TransportEvent::FaceClosed(face) => Some(MgmtEvent::FaceClosed { face }),
+            TransportEvent::Error(message) => Some(MgmtEvent::Error { message }),
+            _ => None,
+        }
+    }
+}
+
+/// A route to a face
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteInfo {
+    /// Name prefix
+    pub prefix: String,
+
+    /// Face ID, or interface index for kernel-only routes
+    pub face: String,
+
+    /// Routing cost
+    pub cost: u32,
+
+    /// Whether the route is installed in the kernel FIB
+    pub kernel: bool,
+}
+
+/// A face and its counters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaceInfo {
+    /// Face ID
+    pub id: String,
+
+    /// Interests sent
+    pub interests_sent: u64,
+
+    /// Interests received
+    pub interests_received: u64,
+
+    /// Data packets sent
+    pub data_sent: u64,
+
+    /// Data packets received
+    pub data_received: u64,
+
+    /// Nacks sent
+    pub nacks_sent: u64,
+
+    /// Nacks received
+    pub nacks_received: u64,
+
+    /// Bytes sent
+    pub bytes_sent: u64,
+
+    /// Bytes received
+    pub bytes_received: u64,
+
+    /// Average RTT of satisfied Interests (in microseconds)
+    pub rtt_avg_us: f64,
+
+    /// Time since the last packet (in milliseconds)
+    pub idle_ms: u64,
+}
+
+impl From<FaceStats> for FaceInfo {
+    fn from(stats: FaceStats) -> Self {
+        Self {
+            id: stats.face_id,
+            interests_sent: stats.interests_sent,
+            interests_received: stats.interests_received,
+            data_sent: stats.data_sent,
+            data_received: stats.data_received,
+            nacks_sent: stats.nacks_sent,
+            nacks_received: stats.nacks_received,
+            bytes_sent: stats.bytes_sent,
+            bytes_received: stats.bytes_received,
+            rtt_avg_us: stats.rtt_avg_us,
+            idle_ms: stats.idle.as_millis() as u64,
+        }
+    }
+}
+
+/// State of the content store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CsInfo {
+    /// Cached Data packets
+    pub entries: usize,
+
+    /// Total content size in bytes
+    pub bytes: usize,
+
+    /// Whether the cache survives restarts
+    pub persistent: bool,
+
+    /// Entries announced in the kernel CS, when XDP is loaded
+    pub kernel_entries: Option<usize>,
+}
+
+/// A cached Data packet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CsEntryInfo {
+    /// Data name
+    pub name: String,
+
+    /// Content size in bytes
+    pub bytes: usize,
+
+    /// Time since the Data was cached (in milliseconds)
+    pub age_ms: u64,
+
+    /// Freshness period of the Data (in milliseconds)
+    pub freshness_ms: u32,
+
+    /// Whether the Data is announced in the kernel CS
+    pub kernel: bool,
+}
+
+/// Occupancy and hit counters of the content stores
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CsStatsInfo {
+    /// Cached Data packets
+    pub entries: usize,
+
+    /// Most Data packets cached at once
+    pub max_entries: usize,
+
+    /// Total content size in bytes
+    pub bytes: usize,
+
+    /// Most content bytes cached at once, 0 for unlimited
+    pub max_bytes: usize,
+
+    /// Data packets inserted
+    pub inserts: u64,
+
+    /// Lookups answered
+    pub hits: u64,
+
+    /// Lookups not answered
+    pub misses: u64,
+
+    /// Hits of the kernel CS, when XDP is loaded
+    pub kernel_hits: Option<u64>,
+
+    /// Entries announced in the kernel CS, when XDP is loaded
+    pub kernel_entries: Option<usize>,
+
+    /// Share of lookups answered, kernel and userspace together
+    pub hit_ratio: f64,
+}
+
+/// Content store entries dropped by a purge
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PurgeInfo {
+    /// Data packets dropped from the userspace content store
+    pub entries: usize,
+
+    /// Entries dropped from the kernel CS, when XDP is loaded
+    pub kernel_entries: Option<usize>,
+}
+
+/// A pending Interest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PitEntryInfo {
+    /// Interest name, or the hash keying a kernel entry whose name the
+    /// kernel did not report
+    pub name: String,
+
+    /// Faces waiting for Data
+    pub in_faces: Vec<String>,
+
+    /// Faces the Interest was forwarded to, unknown for kernel entries
+    pub out_faces: Vec<String>,
+
+    /// Lifetime left (in milliseconds), 0 once expired
+    pub remaining_ms: u64,
+
+    /// Whether the entry is in the kernel PIT
+    pub kernel: bool,
+}
+
+/// PIT entries dropped by a flush
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlushInfo {
+    /// Entries dropped from the userspace PIT
+    pub entries: usize,
+
+    /// Entries dropped from the kernel PIT, when XDP is loaded
+    pub kernel_entries: Option<usize>,
+}
+
+/// Forwarding counters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsInfo {
+    /// Open faces
+    pub faces: usize,
+
+    /// Pending Interest Table entries
+    pub pit_entries: usize,
+
+    /// Interests received
+    pub interests_received: u64,
+
+    /// Interests forwarded
+    pub interests_forwarded: u64,
+
+    /// Interests satisfied
+    pub interests_satisfied: u64,
+
+    /// Data packets received
+    pub data_received: u64,
+
+    /// Data packets sent
+    pub data_sent: u64,
+
+    /// Content store hits
+    pub cs_hits: u64,
+
+    /// Content store misses
+    pub cs_misses: u64,
+
+    /// Share of CS lookups answered, kernel and userspace together
+    pub cs_hit_ratio: f64,
+
+    /// Median RTT of the Interests sent upstream (in microseconds)
+    pub rtt_p50_us: f64,
+
+    /// 90th percentile RTT (in microseconds)
+    pub rtt_p90_us: f64,
+
+    /// 99th percentile RTT (in microseconds)
+    pub rtt_p99_us: f64,
+
+    /// Counters of the XDP program, when loaded
+    pub xdp: Option<XdpStats>,
+}
+
+/// Counters of the XDP program
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct XdpStats {
+    /// Interfaces the program is attached to
+    pub interfaces: Vec<String>,
+
+    /// Interests seen in the kernel
+    pub interests_received: u64,
+
+    /// Data packets seen in the kernel
+    pub data_received: u64,
+
+    /// Kernel CS hits
+    pub cs_hits: u64,
+
+    /// Kernel FIB hits
+    pub fib_hits: u64,
+
+    /// Interests answered from the kernel CS
+    pub cs_replies: u64,
+
+    /// Expired kernel CS entries evicted on lookup
+    pub cs_evictions: u64,
+
+    /// Interests redirected to an egress interface
+    pub interests_redirected: u64,
+
+    /// Interests dropped as duplicates
+    pub drops_duplicate: u64,
+
+    /// Looping Interests dropped
+    pub drops_loop: u64,
+
+    /// Interests over the kernel rate limit
+    pub drops_rate_limited: u64,
+
+    /// Interests dropped with no HopLimit left
+    pub drops_hop_limit: u64,
+
+    /// Unsolicited Data dropped
+    pub drops_unsolicited: u64,
+
+    /// Packets aborted on a processing error
+    pub aborted: u64,
+
+    /// Bytes of Interests seen in the kernel
+    pub interest_bytes: u64,
+
+    /// Bytes of Data seen in the kernel
+    pub data_bytes: u64,
+
+    /// NDN-over-TCP segments passed up by the kernel
+    pub tcp_segments: u64,
+
+    /// Interests seen leaving by the TC egress program
+    pub egress_interests: u64,
+
+    /// Data seen leaving by the TC egress program
+    pub egress_data: u64,
+
+    /// Interests per second seen in the kernel
+    pub interest_rate: Rates,
+
+    /// Bytes per second of NDN packets seen in the kernel
+    pub byte_rate: Rates,
+
+    /// Traffic of each interface packets arrived on
+    pub per_interface: Vec<XdpInterfaceStats>,
+}
+
+/// NDN traffic the XDP program saw arrive on one interface
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct XdpInterfaceStats {
+    /// Interface name, or its index if it is gone
+    pub interface: String,
+
+    /// Interests received
+    pub interests: u64,
+
+    /// Data packets received
+    pub data: u64,
+
+    /// Interests and Data dropped
+    pub drops: u64,
+
+    /// Bytes of NDN packets received
+    pub bytes: u64,
+}
+
+/// Remove a socket left behind by a previous run, refusing to take over a
+/// socket that is still served
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(anyhow!("Socket {} is in use", path.display()));
+            }
+            debug!("Removing stale socket {}", path.display());
+            fs::remove_file(path)?;
+            Ok(())
+        }
+        Ok(_) => Err(anyhow!("{} exists and is not a socket", path.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Permissions of the control socket: read and write for the daemon's
+/// user and group
+const SOCKET_MODE: u32 = 0o660;
+
+/// Serves management commands on a Unix socket
+pub struct ManagementServer {
+    /// Path the socket is bound to
+    path: PathBuf,
+
+    /// The listening socket
+    listener: UnixListener,
+
+    /// User and group of the daemon, which own the socket
+    uid: u32,
+    gid: u32,
+
+    /// State the commands act on
+    handler: Arc<Handler>,
+}
+
+/// Executes management commands, for the control socket and the gRPC API
+pub(crate) struct Handler {
+    transport: Arc<NdnQuicTransport>,
+    bridge: Option<Arc<KernelBridge>>,
+    xdp: Option<Arc<Mutex<XdpManager>>>,
+}
+
+impl ManagementServer {
+    /// Bind the control socket at `path`
+    pub fn bind(
+        path: impl AsRef<Path>,
+        transport: Arc<NdnQuicTransport>,
+        bridge: Option<Arc<KernelBridge>>,
+        xdp: Option<Arc<Mutex<XdpManager>>>,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        remove_stale_socket(&path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Failed to bind control socket {}", path.display()))?;
+        // Only the daemon's user and group may connect, whatever the umask
+        fs::set_permissions(&path, fs::Permissions::from_mode(SOCKET_MODE))
+            .with_context(|| format!("Failed to restrict control socket {}", path.display()))?;
+        let owner = fs::metadata(&path)?;
+        info!("Accepting management commands on {}", path.display());
+
+        Ok(Self {
+            path,
+            listener,
+            uid: owner.uid(),
+            gid: owner.gid(),
+            handler: Arc::new(Handler::new(transport, bridge, xdp)),
+        })
+    }
+
+    /// Get the socket path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Serve clients until the task is cancelled
+    pub async fn run(self) {
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, _)) => {
+                    if !self.is_authorized(&stream) {
+                        continue;
+                    }
+                    let handler = Arc::clone(&self.handler);
+                    tokio::spawn(async move {
+                        if let Err(e) = handler.serve(stream).await {
+                            debug!("Management connection failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Failed to accept management connection: {}", e),
+            }
+        }
+    }
+
+    /// Whether the peer of a connection runs as root or as the user or
+    /// group of the daemon
+    fn is_authorized(&self, stream: &UnixStream) -> bool {
+        match stream.peer_cred() {
+            Ok(cred) if cred.uid() == 0 || cred.uid() == self.uid || cred.gid() == self.gid => true,
+            Ok(cred) => {
+                warn!("Refusing management connection from uid {} gid {}", cred.uid(), cred.gid());
+                false
+            }
+            Err(e) => {
+                warn!("Refusing management connection without peer credentials: {}", e);
+                false
+            }
+        }
+    }
+}
+
+impl Drop for ManagementServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Handler {
+    /// Handler acting on the daemon's forwarder, kernel maps and XDP program
+    pub(crate) fn new(
+        transport: Arc<NdnQuicTransport>,
+        bridge: Option<Arc<KernelBridge>>,
+        xdp: Option<Arc<Mutex<XdpManager>>>,
+    ) -> Self {
+        Self { transport, bridge, xdp }
+    }
+
+    /// Answer every request sent on `stream`
+    async fn serve(&self, stream: UnixStream) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Some(line) = lines.next_line().await? {
+            let response = match serde_json::from_str::<MgmtRequest>(&line) {
+                Ok(MgmtRequest::Watch) => return self.watch(lines, writer).await,
+                Ok(request) => {
+                    debug!("Management command: {:?}", request);
+                    match self.handle(request).await {
+                        Ok(body) => MgmtResponse::Ok(body),
+                        Err(e) => MgmtResponse::Error(e.to_string()),
+                    }
+                }
+                Err(e) => MgmtResponse::Error(format!("Invalid request: {}", e)),
+            };
+
+            let mut reply = serde_json::to_vec(&response)?;
+            reply.push(b'\n');
+            writer.write_all(&reply).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Run one command
+    pub(crate) async fn handle(&self, request: MgmtRequest) -> Result<MgmtBody> {
+        match request {
+            MgmtRequest::FibAdd { prefix, face, cost } => self.fib_add(&prefix, &face, cost).await,
+            MgmtRequest::FibRemove { prefix, face } => self.fib_remove(&prefix, &face).await,
+            MgmtRequest::FibList => self.fib_list().await,
+            MgmtRequest::FaceList => {
+                let faces = self.transport.face_stats().await;
+                Ok(MgmtBody::Faces(faces.into_iter().map(FaceInfo::from).collect()))
+            }
+            MgmtRequest::FaceDestroy { face } => {
+                let faces = self.transport.get_faces().await;
+                let face = faces
+                    .iter()
+                    .find(|f| f.id() == face)
+                    .ok_or_else(|| anyhow!("Unknown face {}", face))?;
+                face.close().await;
+                Ok(MgmtBody::Done)
+            }
+            MgmtRequest::CsInfo => self.cs_info().await,
+            MgmtRequest::CsList { prefix } => self.cs_list(&prefix).await,
+            MgmtRequest::CsInsert { wire } => {
+                let data = Data::decode(&wire)?;
+                info!("Inserting {} in the content store", data.name());
+                self.content_store()?.insert(data)?;
+                Ok(MgmtBody::Done)
+            }
+            MgmtRequest::CsStats => self.cs_stats().await,
+            MgmtRequest::CsErase { prefix } => {
+                let prefix = Name::from_string(&prefix)?;
+                Ok(MgmtBody::Erased(self.cs_erase(&prefix)?))
+            }
+            MgmtRequest::CsPurge { prefix } => self.cs_purge(&prefix).await,
+            MgmtRequest::PitList => self.pit_list().await,
+            MgmtRequest::PitFlush { prefix, all } => self.pit_flush(&prefix, all).await,
+            MgmtRequest::Stats => self.stats().await,
+            MgmtRequest::XdpAttach { interface, skb_mode, offload, chain } => {
+                let flags = XdpAttachFlags { offload, skb_mode, chain };
+                let mode = self
+                    .xdp()?
+                    .lock()
+                    .await
+                    .attach_with_flags(&interface, flags)
+                    .with_context(|| format!("Failed to attach XDP program to {}", interface))?;
+                info!("Attached XDP program to {} in {} mode", interface, mode);
+                Ok(MgmtBody::XdpMode(mode.to_string()))
+            }
+            MgmtRequest::XdpDetach { interface } => {
+                self.xdp()?.lock().await.detach(&interface)?;
+                Ok(MgmtBody::Done)
+            }
+            MgmtRequest::Watch => Err(anyhow!("watch takes over the connection")),
+        }
+    }
+
+    /// Acknowledge a `watch` and stream face events until the client hangs up
+    async fn watch(&self, mut lines: Lines<BufReader<OwnedReadHalf>>, mut writer: OwnedWriteHalf) -> Result<()> {
+        let filter = EventFilter::default()
+            .kind(EventKind::FaceCreated)
+            .kind(EventKind::FaceClosed)
+            .kind(EventKind::Error);
+        let mut events = self.transport.subscribe(filter);
+
+        let mut reply = serde_json::to_vec(&MgmtResponse::Ok(MgmtBody::Done))?;
+        reply.push(b'\n');
+        writer.write_all(&reply).await?;
+
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => event,
+                // Nothing more is read, so a line or EOF both end the stream
+                _ = lines.next_line() => return Ok(()),
+            };
+            let Some(event) = event else {
+                return Ok(());
+            };
+            if let Some(event) = MgmtEvent::from_transport(event) {
+                let mut line = serde_json::to_vec(&event)?;
+                line.push(b'\n');
+                writer.write_all(&line).await?;
+            }
+        }
+    }
+
+    async fn fib_add(&self, prefix: &str, face: &str, cost: u32) -> Result<MgmtBody> {
+        let prefix = Name::from_string(prefix)?;
+        let known = self.transport.get_faces().await.iter().any(|f| f.id() == face);
+
+        if known {
+            let forwarder = self
+                .transport
+                .forwarder()
+                .ok_or_else(|| anyhow!("Forwarding is disabled"))?;
+            forwarder.add_route(prefix.clone(), face, cost).await;
+            if let Some(bridge) = &self.bridge {
+                bridge.install_route(&prefix, face, cost).await?;
+            }
+        } else if let Ok(ifindex) = face.parse::<u16>() {
+            let bridge = self
+                .bridge
+                .as_ref()
+                .ok_or_else(|| anyhow!("Interface routes need the XDP fast path"))?;
+            bridge.install_interface_route(&prefix, ifindex, cost).await?;
+        } else {
+            return Err(anyhow!("Unknown face {}", face));
+        }
+
+        info!("Added route {} -> {} (cost {})", prefix, face, cost);
+        Ok(MgmtBody::Done)
+    }
+
+    async fn fib_remove(&self, prefix: &str, face: &str) -> Result<MgmtBody> {
+        let prefix = Name::from_string(prefix)?;
+
+        let mut removed = false;
+        if let Some(forwarder) = self.transport.forwarder() {
+            removed |= forwarder.remove_route(&prefix, face).await;
+        }
+        if let Some(bridge) = &self.bridge {
+            removed |= bridge.remove_route(&prefix, face).await?;
+        }
+
+        if !removed {
+            return Err(anyhow!("No route {} -> {}", prefix, face));
+        }
+        info!("Removed route {} -> {}", prefix, face);
+        Ok(MgmtBody::Done)
+    }
+
+    async fn fib_list(&self) -> Result<MgmtBody> {
+        let kernel_routes = match &self.bridge {
+            Some(bridge) => bridge.routes().await,
+            None => Vec::new(),
+        };
+        let in_kernel = |prefix: &Name, face: &str| kernel_routes.iter().any(|(p, f, _)| p == prefix && f == face);
+
+        let mut routes = Vec::new();
+        if let Some(forwarder) = self.transport.forwarder() {
+            for entry in forwarder.routes().await {
+                for hop in &entry.next_hops {
+                    routes.push(RouteInfo {
+                        prefix: entry.prefix.to_string(),
+                        face: hop.face_id.clone(),
+                        cost: hop.cost,
+                        kernel: in_kernel(&entry.prefix, &hop.face_id),
+                    });
+                }
+            }
+        }
+        // Routes to interfaces only exist in the kernel
+        for (prefix, face, cost) in &kernel_routes {
+            if !routes.iter().any(|r| r.kernel && r.prefix == prefix.to_string() && &r.face == face) {
+                routes.push(RouteInfo {
+                    prefix: prefix.to_string(),
+                    face: face.clone(),
+                    cost: *cost,
+                    kernel: true,
+                });
+            }
+        }
+        routes.sort_by(|a, b| a.prefix.cmp(&b.prefix).then(a.cost.cmp(&b.cost)));
+
+        Ok(MgmtBody::Routes(routes))
+    }
+
+    fn xdp(&self) -> Result<&Arc<Mutex<XdpManager>>> {
+        self.xdp
+            .as_ref()
+            .ok_or_else(|| anyhow!("udcnd is running without the XDP fast path"))
+    }
+
+    fn content_store(&self) -> Result<Arc<ContentStore>> {
+        self.transport
+            .content_store()
+            .ok_or_else(|| anyhow!("Content store is disabled"))
+    }
+
+    async fn cs_info(&self) -> Result<MgmtBody> {
+        let cs = self.content_store()?;
+        let kernel_entries = match &self.bridge {
+            Some(bridge) => Some(bridge.mirrored_count().await),
+            None => None,
+        };
+
+        Ok(MgmtBody::ContentStore(CsInfo {
+            entries: cs.len(),
+            bytes: cs.size_bytes(),
+            persistent: cs.is_persistent(),
+            kernel_entries,
+        }))
+    }
+
+    /// Drop the Data under `prefix` from the userspace content store
+    fn cs_erase(&self, prefix: &Name) -> Result<usize> {
+        let cs = self.content_store()?;
+        let mut erased = 0;
+        for entry in cs.enumerate(prefix) {
+            if cs.remove(entry.data.name())? {
+                erased += 1;
+            }
+        }
+        Ok(erased)
+    }
+
+    async fn cs_list(&self, prefix: &str) -> Result<MgmtBody> {
+        let prefix = Name::from_string(prefix)?;
+        let cs = self.content_store()?;
+        let mirrored = match &self.bridge {
+            Some(bridge) => bridge.mirrored().await,
+            None => HashSet::new(),
+        };
+
+        let now = SystemTime::now();
+        let entries = cs
+            .enumerate(&prefix)
+            .into_iter()
+            .map(|entry| CsEntryInfo {
+                name: entry.data.name().to_string(),
+                bytes: entry.data.content().len(),
+                age_ms: now.duration_since(entry.inserted_at).unwrap_or_default().as_millis() as u64,
+                freshness_ms: entry.data.freshness_period_ms,
+                kernel: mirrored.contains(entry.data.name()),
+            })
+            .collect();
+
+        Ok(MgmtBody::CsEntries(entries))
+    }
+
+    async fn cs_stats(&self) -> Result<MgmtBody> {
+        let cs = self.content_store()?;
+        let metrics = self.transport.metrics();
+        let (kernel_hits, kernel_entries) = match (&self.xdp, &self.bridge) {
+            (Some(xdp), Some(bridge)) => {
+                xdp.lock().await.get_metrics().await?.record(&metrics);
+                (Some(metrics.kernel_cs_hits.value()), Some(bridge.mirrored_count().await))
+            }
+            _ => (None, None),
+        };
+
+        Ok(MgmtBody::CsStats(CsStatsInfo {
+            entries: cs.len(),
+            max_entries: cs.limits().max_entries,
+            bytes: cs.size_bytes(),
+            max_bytes: cs.limits().max_bytes,
+            inserts: metrics.cs_inserts.value(),
+            hits: metrics.cs_hits.value(),
+            misses: metrics.cs_misses.value(),
+            kernel_hits,
+            kernel_entries,
+            hit_ratio: metrics.cs_hit_ratio(),
+        }))
+    }
+
+    async fn cs_purge(&self, prefix: &str) -> Result<MgmtBody> {
+        let prefix = Name::from_string(prefix)?;
+        // Userspace first, so the bridge does not announce the Data again
+        let entries = self.cs_erase(&prefix)?;
+        let kernel_entries = match &self.bridge {
+            Some(bridge) => Some(bridge.purge_prefix(&prefix).await?),
+            None => None,
+        };
+
+        Ok(MgmtBody::Purged(PurgeInfo {
+            entries,
+            kernel_entries,
+        }))
+    }
+
+    async fn pit_list(&self) -> Result<MgmtBody> {
+        let mut entries = Vec::new();
+        if let Some(forwarder) = self.transport.forwarder() {
+            for entry in forwarder.pit_entries().await {
+                entries.push(PitEntryInfo {
+                    name: entry.name.to_string(),
+                    in_faces: entry.in_faces,
+                    out_faces: entry.out_faces,
+                    remaining_ms: entry.remaining.as_millis() as u64,
+                    kernel: false,
+                });
+            }
+        }
+        if let Some(bridge) = &self.bridge {
+            for entry in bridge.pit_entries().await? {
+                entries.push(PitEntryInfo {
+                    name: match entry.name {
+                        Some(name) => name.to_string(),
+                        None => format!("<hash {:08x}>", entry.name_hash),
+                    },
+                    in_faces: vec![entry.face],
+                    out_faces: Vec::new(),
+                    remaining_ms: entry.remaining.as_millis() as u64,
+                    kernel: true,
+                });
+            }
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name).then(a.kernel.cmp(&b.kernel)));
+
+        Ok(MgmtBody::Pit(entries))
+    }
+
+    async fn pit_flush(&self, prefix: &str, all: bool) -> Result<MgmtBody> {
+        let prefix = Name::from_string(prefix)?;
+        let entries = match self.transport.forwarder() {
+            Some(forwarder) => forwarder.flush_pit(&prefix, all).await,
+            None => 0,
+        };
+        let kernel_entries = match &self.bridge {
+            Some(bridge) => Some(bridge.flush_pit(&prefix, all).await?),
+            None => None,
+        };
+
+        info!("Flushed {} PIT entries under {}", entries + kernel_entries.unwrap_or(0), prefix);
+        Ok(MgmtBody::Flushed(FlushInfo {
+            entries,
+            kernel_entries,
+        }))
+    }
+
+    async fn stats(&self) -> Result<MgmtBody> {
+        let metrics = self.transport.metrics();
+        let pit_entries = match self.transport.forwarder() {
+            Some(forwarder) => forwarder.pit_size().await,
+            None => 0,
+        };
+
+        let xdp = match &self.xdp {
+            Some(xdp) => {
+                let xdp = xdp.lock().await;
+                let kernel = xdp.get_metrics().await?;
+                kernel.record(&metrics);
+                let per_interface = xdp
+                    .interface_stats()
+                    .await?
+                    .into_iter()
+                    .map(|stats| XdpInterfaceStats {
+                        interface: stats.name.unwrap_or_else(|| stats.ifindex.to_string()),
+                        interests: stats.interests,
+                        data: stats.data,
+                        drops: stats.drops,
+                        bytes: stats.bytes,
+                    })
+                    .collect();
+                Some(XdpStats {
+                    interfaces: xdp.attached_interfaces(),
+                    interests_received: kernel.interests_received,
+                    data_received: kernel.data_received,
+                    cs_hits: kernel.cs_hits,
+                    fib_hits: kernel.fib_hits,
+                    cs_replies: kernel.cs_replies,
+                    cs_evictions: kernel.cs_evictions,
+                    interests_redirected: kernel.interests_redirected,
+                    drops_duplicate: kernel.drops_duplicate,
+                    drops_loop: kernel.drops_loop,
+                    drops_rate_limited: kernel.drops_rate_limited,
+                    drops_hop_limit: kernel.drops_hop_limit,
+                    drops_unsolicited: kernel.drops_unsolicited,
+                    aborted: kernel.aborted,
+                    interest_bytes: kernel.interest_bytes,
+                    data_bytes: kernel.data_bytes,
+                    tcp_segments: kernel.tcp_segments,
+                    egress_interests: kernel.egress_interests,
+                    egress_data: kernel.egress_data,
+                    interest_rate: metrics.kernel_interest_rate.rates(),
+                    byte_rate: metrics.kernel_byte_rate.rates(),
+                    per_interface,
+                })
+            }
+            None => None,
+        };
+
+        let rtt = metrics.interest_processing_time.histogram().snapshot();
+        Ok(MgmtBody::Stats(StatsInfo {
+            faces: self.transport.get_faces().await.len(),
+            pit_entries,
+            interests_received: metrics.interests_received.value(),
+            interests_forwarded: metrics.interests_forwarded.value(),
+            interests_satisfied: metrics.interests_satisfied.value(),
+            data_received: metrics.data_received.value(),
+            data_sent: metrics.data_sent.value(),
+            cs_hits: metrics.cs_hits.value(),
+            cs_misses: metrics.cs_misses.value(),
+            cs_hit_ratio: metrics.cs_hit_ratio(),
+            rtt_p50_us: rtt.quantile(0.5),
+            rtt_p90_us: rtt.quantile(0.9),
+            rtt_p99_us: rtt.quantile(0.99),
+            xdp,
+        }))
+    }
+}
+
+/// Client side of the control socket
+pub struct MgmtClient {
+    /// Responses from the daemon
+    reader: Lines<BufReader<OwnedReadHalf>>,
+
+    /// Requests to the daemon
+    writer: OwnedWriteHalf,
+}
+
+impl MgmtClient {
+    /// Connect to the daemon's control socket at `path`
+    pub async fn connect(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let stream = UnixStream::connect(path)
+            .await
+            .with_context(|| format!("Failed to connect to udcnd at {} (is it running?)", path.display()))?;
+        let (reader, writer) = stream.into_split();
+
+        Ok(Self {
+            reader: BufReader::new(reader).lines(),
+            writer,
+        })
+    }
+
+    /// Send a command and wait for its result
+    pub async fn request(&mut self, request: &MgmtRequest) -> Result<MgmtBody> {
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await?;
+
+        let reply = self
+            .reader
+            .next_line()
+            .await?
+            .ok_or_else(|| anyhow!("udcnd closed the control connection"))?;
+        match serde_json::from_str(&reply)? {
+            MgmtResponse::Ok(body) => Ok(body),
+            MgmtResponse::Error(message) => Err(anyhow!(message)),
+        }
+    }
+
+    /// Wait for the next event after a `watch`, or None once udcnd hangs up
+    pub async fn next_event(&mut self) -> Result<Option<MgmtEvent>> {
+        match self.reader.next_line().await? {
+            Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rust_udcn_quic::{LinkFace, StreamLink, TransportConfig};
+    use rust_udcn_common::metrics::UdcnMetrics;
+
+    /// Add a face named `test://peer` to `transport`, which stays up as long
+    /// as the returned far end is kept
+    pub(crate) async fn add_peer_face(transport: &NdnQuicTransport) -> tokio::io::DuplexStream {
+        let (a, b) = tokio::io::duplex(4096);
+        let face = LinkFace::new("test://peer".to_string(), Arc::new(StreamLink::new(a)), Arc::new(UdcnMetrics::new()));
+        transport.add_face(Arc::new(face)).await;
+        b
+    }
+
+    #[test]
+    fn test_request_wire_format() {
+        let request = MgmtRequest::FibAdd {
+            prefix: "/example".to_string(),
+            face: "quic://[::1]:6367".to_string(),
+            cost: 10,
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#"{"command":"fib-add","prefix":"/example","face":"quic://[::1]:6367","cost":10}"#);
+        assert_eq!(serde_json::from_str::<MgmtRequest>(&json).unwrap(), request);
+
+        let json = serde_json::to_string(&MgmtResponse::Ok(MgmtBody::Done)).unwrap();
+        assert_eq!(json, r#"{"status":"ok","body":{"type":"done"}}"#);
+    }
+
+    #[tokio::test]
+    async fn test_manage_routes_over_socket() {
+        let transport = Arc::new(
+            NdnQuicTransport::new(TransportConfig {
+                enable_forwarding: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+        );
+        let _peer = add_peer_face(&transport).await;
+
+        let path = std::env::temp_dir().join(format!("udcnd-mgmt-test-{}.sock", std::process::id()));
+        let server = ManagementServer::bind(&path, Arc::clone(&transport), None, None).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, SOCKET_MODE);
+        tokio::spawn(server.run());
+        let mut client = MgmtClient::connect(&path).await.unwrap();
+
+        let add = MgmtRequest::FibAdd {
+            prefix: "/example".to_string(),
+            face: "test://peer".to_string(),
+            cost: 5,
+        };
+        assert_eq!(client.request(&add).await.unwrap(), MgmtBody::Done);
+
+        let routes = match client.request(&MgmtRequest::FibList).await.unwrap() {
+            MgmtBody::Routes(routes) => routes,
+            other => panic!("Unexpected reply {:?}", other),
+        };
+        assert_eq!(
+            routes,
+            vec![RouteInfo {
+                prefix: "/example".to_string(),
+                face: "test://peer".to_string(),
+                cost: 5,
+                kernel: false,
+            }]
+        );
+
+        // Interface routes are refused without the XDP fast path
+        let add_interface = MgmtRequest::FibAdd {
+            prefix: "/example".to_string(),
+            face: "2".to_string(),
+            cost: 5,
+        };
+        assert!(client.request(&add_interface).await.is_err());
+
+        // So is the XDP program
+        let attach = MgmtRequest::XdpAttach {
+            interface: "eth0".to_string(),
+            skb_mode: false,
+            offload: false,
+            chain: false,
+        };
+        assert!(client.request(&attach).await.is_err());
+
+        let remove = MgmtRequest::FibRemove {
+            prefix: "/example".to_string(),
+            face: "test://peer".to_string(),
+        };
+        assert_eq!(client.request(&remove).await.unwrap(), MgmtBody::Done);
+        assert!(client.request(&remove).await.is_err());
+
+        assert_eq!(client.request(&MgmtRequest::PitList).await.unwrap(), MgmtBody::Pit(Vec::new()));
+        let flush = MgmtRequest::PitFlush {
+            prefix: "/".to_string(),
+            all: true,
+        };
+        assert_eq!(
+            client.request(&flush).await.unwrap(),
+            MgmtBody::Flushed(FlushInfo {
+                entries: 0,
+                kernel_entries: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watch_face_events() {
+        let transport = Arc::new(NdnQuicTransport::new(TransportConfig::default()).await.unwrap());
+
+        let path = std::env::temp_dir().join(format!("udcnd-mgmt-watch-test-{}.sock", std::process::id()));
+        let server = ManagementServer::bind(&path, Arc::clone(&transport), None, None).unwrap();
+        tokio::spawn(server.run());
+        let mut client = MgmtClient::connect(&path).await.unwrap();
+        assert_eq!(client.request(&MgmtRequest::Watch).await.unwrap(), MgmtBody::Done);
+
+        let _peer = add_peer_face(&transport).await;
+
+        let event = tokio::time::timeout(std::time::Duration::from_secs(1), client.next_event())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            Some(MgmtEvent::FaceCreated {
+                face: "test://peer".to_string()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manage_content_store_over_socket() {
+        let transport = Arc::new(
+            NdnQuicTransport::new(TransportConfig {
+                content_store: Some(Default::default()),
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+        );
+
+        let path = std::env::temp_dir().join(format!("udcnd-mgmt-cs-test-{}.sock", std::process::id()));
+        let server = ManagementServer::bind(&path, Arc::clone(&transport), None, None).unwrap();
+        tokio::spawn(server.run());
+        let mut client = MgmtClient::connect(&path).await.unwrap();
+
+        for name in ["/example/a", "/example/b", "/other"] {
+            let mut wire = bytes::BytesMut::new();
+            Data::new(Name::from_string(name).unwrap(), "hello").encode(&mut wire).unwrap();
+            let insert = MgmtRequest::CsInsert { wire: wire.to_vec() };
+            assert_eq!(client.request(&insert).await.unwrap(), MgmtBody::Done);
+        }
+        let garbage = MgmtRequest::CsInsert { wire: vec![1, 2, 3] };
+        assert!(client.request(&garbage).await.is_err());
+
+        let list = MgmtRequest::CsList {
+            prefix: "/example".to_string(),
+        };
+        let entries = match client.request(&list).await.unwrap() {
+            MgmtBody::CsEntries(entries) => entries,
+            other => panic!("Unexpected reply {:?}", other),
+        };
+        let mut names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["/example/a", "/example/b"]);
+        assert!(entries.iter().all(|e| e.bytes == 5 && !e.kernel));
+
+        let stats = match client.request(&MgmtRequest::CsStats).await.unwrap() {
+            MgmtBody::CsStats(stats) => stats,
+            other => panic!("Unexpected reply {:?}", other),
+        };
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.bytes, 15);
+        assert_eq!(stats.inserts, 3);
+        assert_eq!(stats.kernel_hits, None);
+    }
+}
@@ -0,0 +1,48 @@
+//! OTLP export of the forwarding spans.
+//!
+//! The transport and forwarder record a span per Interest, face stream and
+//! forwarding decision. With an OTLP endpoint configured, udcnd ships them to
+//! a collector so per-request latency can be followed through the node.
+//! Logging keeps going through `log`; only spans are exported.
+
+use anyhow::{Context, Result};
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Service name the spans are reported under
+const SERVICE_NAME: &str = "udcnd";
+
+/// Exports spans until shut down
+pub struct Telemetry {
+    provider: TracerProvider,
+}
+
+impl Telemetry {
+    /// Start exporting spans to the OTLP collector at `endpoint`, over gRPC
+    pub fn init(endpoint: &str) -> Result<Self> {
+        let exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()
+            .context(format!("Failed to create OTLP exporter for {}", endpoint))?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new(vec![KeyValue::new("service.name", SERVICE_NAME)]))
+            .build();
+
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME));
+        tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))
+            .context("Failed to install the tracing subscriber")?;
+
+        Ok(Self { provider })
+    }
+
+    /// Flush the spans not exported yet
+    pub fn shutdown(self) {
+        if let Err(e) = self.provider.shutdown() {
+            log::warn!("Failed to flush spans: {}", e);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Content Store admission.
+//!
+//! This module decides which Data the kernel CS records. Userspace bounds
+//! the size of cached Data in the config map, and allows or denies name
+//! prefixes in CS_PREFIXES, where the longest listed prefix of a name wins.
+
+use aya_ebpf::maps::HashMap;
+
+use crate::maps::{config, FibKey};
+use crate::ndn::{NameHashes, MAX_NAME_COMPONENTS};
+
+// Reference to the prefix rules map
+extern "C" {
+    #[link_name = "CS_PREFIXES"]
+    static mut CS_PREFIXES: HashMap<FibKey, u8>;
+}
+
+/// Whether Data named `name`, `size` bytes long, may enter the CS.
+///
+/// Data outside the configured size bounds never does. Otherwise the rule
+/// of the longest listed prefix of the name decides, and without one the
+/// admission setting does.
+pub fn admit(name: &NameHashes, size: u32) -> bool {
+    let admission = crate::config(config::CS_ADMISSION, config::CS_ADMIT_ALL);
+    if admission == config::CS_ADMIT_NONE {
+        return false;
+    }
+
+    let min_size = crate::config(config::CS_MIN_SIZE, 0);
+    let max_size = crate::config(config::CS_MAX_SIZE, u32::MAX);
+    if size < min_size || size > max_size {
+        return false;
+    }
+
+    let mut prefix_len = MAX_NAME_COMPONENTS;
+    while prefix_len > 0 {
+        if prefix_len <= name.len as usize {
+            let key = FibKey {
+                prefix_hash: name.prefixes[prefix_len - 1],
+                prefix_len: prefix_len as u8,
+            };
+            if let Some(rule) = unsafe { CS_PREFIXES.get(&key) } {
+                return *rule == config::CS_PREFIX_ALLOW;
+            }
+        }
+        prefix_len -= 1;
+    }
+
+    admission != config::CS_ADMIT_LISTED
+}
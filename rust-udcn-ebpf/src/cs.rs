@@ -27,7 +27,21 @@ pub fn lookup(name_hash: u32) -> Result<bool, ()> {
         name_len: 0, // Simplified for now
     };
     
-    let found = unsafe { CS_TABLE.get(&key).is_some() };
+    let entry = unsafe { CS_TABLE.get(&key).copied() };
+    let found = match entry {
+        Some(entry) if is_entry_expired(&entry) => {
+            // Never answer with stale content, drop the entry instead
+            unsafe {
+                let _ = CS_TABLE.remove(&key);
+                if let Some(counter) = METRICS.get_ptr_mut(&metrics::CS_EVICTIONS) {
+                    *counter += 1;
+                }
+            }
+            false
+        }
+        Some(_) => true,
+        None => false,
+    };
     if found {
         // Increment CS hit counter
         unsafe {
@@ -43,7 +57,7 @@ pub fn lookup(name_hash: u32) -> Result<bool, ()> {
 /// Add a Data packet to the Content Store.
 ///
 /// Given a name hash and content details, add to the CS.
-pub fn insert(name_hash: u32, content_size: u32, ttl_ms: u32) -> Result<(), ()> {
+pub fn insert(name_hash: u32, content_size: u32, ttl_ms: u32, freshness_ms: u32) -> Result<(), ()> {
     let key = CsKey {
         name_hash,
         name_len: 0, // Simplified for now
@@ -54,6 +68,8 @@ pub fn insert(name_hash: u32, content_size: u32, ttl_ms: u32) -> Result<(), ()>
         timestamp: utils::get_timestamp(),
         content_size,
         ttl_ms,
+        freshness_ms,
+        _pad: 0,
     };
     
     unsafe {
@@ -73,8 +89,7 @@ pub fn insert(name_hash: u32, content_size: u32, ttl_ms: u32) -> Result<(), ()>
 /// An entry is expired if the current time is greater than the creation time
 /// plus the TTL.
 pub fn is_entry_expired(entry: &CsValue) -> bool {
-    let current_time = utils::get_timestamp();
-    current_time > entry.timestamp + entry.ttl_ms as u64
+    utils::is_expired(entry.timestamp, entry.ttl_ms as u64)
 }
 
 /// Clean up expired CS entries.
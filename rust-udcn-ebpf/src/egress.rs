@@ -0,0 +1,141 @@
+//! TC egress processing.
+//!
+//! XDP only sees the packets arriving on an interface. This companion
+//! program runs on the clsact egress hook to count the NDN packets leaving
+//! it, cache outgoing Data in the kernel CS, and set ECN congestion marks
+//! on interfaces userspace reports as congested.
+
+use aya_ebpf::{
+    maps::{HashMap, LruHashMap},
+    programs::TcContext,
+};
+
+use crate::admission;
+use crate::maps::{config, metrics, CsKey, CsValue};
+use crate::ndn;
+use crate::parser::{
+    self, EtherHeader, Ipv4Header, Ipv6Header, PacketBytes, UdpHeader, ETH_HDR_SIZE, ETH_P_IP, ETH_P_IPV6,
+    IPPROTO_UDP, IPV4_HDR_SIZE, IPV6_HDR_SIZE, UDP_HDR_SIZE,
+};
+use crate::utils;
+
+/// ECN field values: any ECN-capable transport, and congestion experienced
+const ECN_MASK: u8 = 0x03;
+const ECN_CE: u8 = 0x03;
+
+// Reference to the maps shared with the XDP program
+extern "C" {
+    #[link_name = "CS_TABLE"]
+    static mut CS_TABLE: LruHashMap<CsKey, CsValue>;
+
+    #[link_name = "CONGESTED"]
+    static mut CONGESTED: HashMap<u32, u8>;
+}
+
+/// Count, cache and mark an outgoing packet.
+///
+/// Egress traffic is never dropped here; anything that is not NDN over UDP
+/// goes out untouched.
+pub fn process(ctx: &mut TcContext) -> Result<(), ()> {
+    let eth: EtherHeader = ctx.load(0).map_err(|_| ())?;
+    let ip_offset = ETH_HDR_SIZE;
+
+    let (ipv4, l4_offset) = match u16::from_be(eth.eth_type) {
+        ETH_P_IPV6 => {
+            let ipv6: Ipv6Header = ctx.load(ip_offset).map_err(|_| ())?;
+            if ipv6.next_hdr != IPPROTO_UDP {
+                return Ok(());
+            }
+            (false, ip_offset + IPV6_HDR_SIZE)
+        }
+        ETH_P_IP => {
+            let ipv4: Ipv4Header = ctx.load(ip_offset).map_err(|_| ())?;
+            let header_len = (ipv4.version_ihl & 0x0f) as usize * 4;
+            if ipv4.protocol != IPPROTO_UDP || header_len < IPV4_HDR_SIZE {
+                return Ok(());
+            }
+            (true, ip_offset + header_len)
+        }
+        _ => return Ok(()),
+    };
+
+    // NDN faces send from and to the NDN port
+    let udp: UdpHeader = ctx.load(l4_offset).map_err(|_| ())?;
+    let ndn_port = crate::config(config::NDN_PORT, parser::NDN_PORT as u32) as u16;
+    if u16::from_be(udp.dst_port) != ndn_port && u16::from_be(udp.src_port) != ndn_port {
+        return Ok(());
+    }
+    let data_offset = l4_offset + UDP_HDR_SIZE;
+    let data_len = (u16::from_be(udp.length) as usize).checked_sub(UDP_HDR_SIZE).ok_or(())?;
+
+    match ctx.byte_at(data_offset) {
+        Some(ndn::TLV_INTEREST) => crate::count(metrics::EGRESS_INTERESTS, 1)?,
+        Some(ndn::TLV_DATA) => {
+            crate::count(metrics::EGRESS_DATA, 1)?;
+            cache_data(ctx, data_offset, data_len)?;
+        }
+        _ => return Ok(()),
+    }
+    crate::count(metrics::EGRESS_BYTES, data_len as u64)?;
+
+    let ifindex = unsafe { (*ctx.skb.skb).ifindex };
+    if unsafe { CONGESTED.get(&ifindex) }.is_some() {
+        mark_congestion(ctx, ip_offset, ipv4)?;
+    }
+
+    Ok(())
+}
+
+/// Record outgoing Data in the CS, so the XDP program sees the Interests
+/// it answers as hits
+fn cache_data(ctx: &TcContext, data_offset: usize, data_len: usize) -> Result<(), ()> {
+    let name = match parser::hash_name(ctx, data_offset) {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    if !admission::admit(&name, data_len as u32) {
+        return Ok(());
+    }
+
+    let key = CsKey {
+        name_hash: name.full,
+        name_len: name.len,
+    };
+    let value = CsValue {
+        content_hash: 0,
+        timestamp: utils::get_timestamp(),
+        content_size: data_len as u32,
+        ttl_ms: crate::config(config::CS_TTL_MS, crate::DEFAULT_CS_TTL_MS),
+        freshness_ms: parser::data_freshness(ctx, &name, data_offset + data_len).unwrap_or(ndn::DEFAULT_FRESHNESS_MS),
+        _pad: 0,
+    };
+    unsafe {
+        CS_TABLE.insert(&key, &value, 0).map_err(|_| ())?;
+    }
+    crate::count(metrics::CS_INSERTS, 1)
+}
+
+/// Turn an ECN-capable packet's ECN field to Congestion Experienced
+fn mark_congestion(ctx: &mut TcContext, ip_offset: usize, ipv4: bool) -> Result<(), ()> {
+    // The ECN bits are the low bits of the IPv4 TOS byte, and bits 4-5 of
+    // the second byte of the IPv6 header
+    let offset = ip_offset + 1;
+    let shift = if ipv4 { 0 } else { 4 };
+    let byte = ctx.byte_at(offset).ok_or(())?;
+    let ecn = (byte >> shift) & ECN_MASK;
+    if ecn == 0 || ecn == ECN_CE {
+        return Ok(());
+    }
+    let marked = byte | (ECN_CE << shift);
+
+    if ipv4 {
+        // Patch the header checksum for the word holding the TOS byte
+        let first = ctx.byte_at(ip_offset).ok_or(())?;
+        let old = u16::from_ne_bytes([first, byte]) as u64;
+        let new = u16::from_ne_bytes([first, marked]) as u64;
+        ctx.l3_csum_replace(ip_offset + 10, old, new, 2).map_err(|_| ())?;
+    }
+    ctx.store(offset, &marked, 0).map_err(|_| ())?;
+
+    crate::count(metrics::CONGESTION_MARKS, 1)
+}
@@ -3,50 +3,88 @@
 //! This module handles operations for the Forwarding Information Base,
 //! which maps name prefixes to next-hop faces.
 
-use aya_ebpf::maps::HashMap;
+use aya_ebpf::{
+    maps::{lpm_trie::Key, HashMap, LpmTrie},
+    programs::XdpContext,
+};
 
-use crate::maps::{FibKey, FibValue, FaceId};
+use crate::maps::{FibKey, FibTrieName, FibValue, FaceId, MAX_FIB_TRIE_NAME_LENGTH};
+use crate::ndn::{NameHashes, FNV_OFFSET_BASIS, MAX_NAME_COMPONENTS};
+use crate::utils;
 
-// Reference to the FIB table map
+// Reference to the FIB table maps
 extern "C" {
     #[link_name = "FIB_TABLE"]
     static mut FIB_TABLE: HashMap<FibKey, FibValue>;
+
+    #[link_name = "FIB_TRIE"]
+    static mut FIB_TRIE: LpmTrie<FibTrieName, FibValue>;
     
     #[link_name = "METRICS"]
-    static mut METRICS: aya_ebpf::maps::HashMap<u32, u64>;
+    static mut METRICS: aya_ebpf::maps::PerCpuArray<u64>;
 }
 
 /// Longest prefix match in the FIB.
 ///
-/// Given a name hash, find the FIB entry with the longest matching prefix.
+/// Probes the FIB with the hash of each prefix of the name, from the
+/// longest to the shortest, then with the empty prefix for a default
+/// route. Only the first `MAX_NAME_COMPONENTS` components are considered.
 /// Returns the face ID of the next hop if found.
-pub fn longest_prefix_match(name_hash: u32) -> Result<FaceId, ()> {
-    let mut best_match: Option<(FibValue, u8)> = None;
-    
-    // In eBPF, we can't dynamically iterate through map entries.
-    // For simplicity, we'll try different prefix lengths from longest to shortest.
-    // A real implementation would use more sophisticated techniques.
-    for prefix_len in (0..=16).rev() {
-        let key = FibKey {
-            prefix_hash: name_hash,
-            prefix_len,
-        };
-        
-        unsafe {
-            if let Some(value) = FIB_TABLE.get(&key) {
-                // If we find a match, we update best_match if this is longer than previous matches
-                if best_match.is_none() || prefix_len > best_match.as_ref().unwrap().1 {
-                    best_match = Some((*value, prefix_len));
+pub fn longest_prefix_match(name: &NameHashes) -> Result<FaceId, ()> {
+    let mut prefix_len = MAX_NAME_COMPONENTS;
+    while prefix_len > 0 {
+        if prefix_len <= name.len as usize {
+            let key = FibKey {
+                prefix_hash: name.prefixes[prefix_len - 1],
+                prefix_len: prefix_len as u8,
+            };
+
+            unsafe {
+                if let Some(value) = FIB_TABLE.get(&key) {
+                    return Ok(value.face_id);
                 }
             }
         }
+        prefix_len -= 1;
     }
-    
-    // Return the face ID of the best match if found
-    if let Some((value, _)) = best_match {
-        Ok(value.face_id)
-    } else {
-        Err(())
+
+    let key = FibKey {
+        prefix_hash: FNV_OFFSET_BASIS,
+        prefix_len: 0,
+    };
+
+    unsafe {
+        match FIB_TABLE.get(&key) {
+            Some(value) => Ok(value.face_id),
+            None => Err(()),
+        }
+    }
+}
+
+/// Longest prefix match in the LPM trie FIB.
+///
+/// Looks up the encoded name components, so prefixes only match on whole
+/// components and hash collisions cannot cause false matches. Names are
+/// truncated to `MAX_FIB_TRIE_NAME_LENGTH` bytes, which no route exceeds.
+pub fn trie_longest_prefix_match(ctx: &XdpContext, name: &NameHashes) -> Result<FaceId, ()> {
+    let mut key = Key::new(0, [0u8; MAX_FIB_TRIE_NAME_LENGTH]);
+
+    let mut i = 0;
+    while i < MAX_FIB_TRIE_NAME_LENGTH {
+        if i >= name.components_len {
+            break;
+        }
+        let byte = utils::byte_ptr_at(ctx, name.components_offset + i)?;
+        key.data[i] = unsafe { *byte };
+        i += 1;
+    }
+    key.prefix_len = (i * 8) as u32;
+
+    unsafe {
+        match FIB_TRIE.get(&key) {
+            Some(value) => Ok(value.face_id),
+            None => Err(()),
+        }
     }
 }
 
@@ -69,10 +107,10 @@ pub fn count_entries() -> u32 {
     42 // Placeholder value
 }
 
-/// Find the next hop face for a given name hash.
+/// Find the next hop face for a given name.
 ///
-/// This is the main forwarding function that takes a name hash and returns
-/// the face ID to forward to.
-pub fn find_next_hop(name_hash: u32) -> Option<FaceId> {
-    longest_prefix_match(name_hash).ok()
+/// This is the main forwarding function that takes the hashes of a name and
+/// returns the face ID to forward to.
+pub fn find_next_hop(name: &NameHashes) -> Option<FaceId> {
+    longest_prefix_match(name).ok()
 }
@@ -6,215 +6,814 @@
 #![no_main]
 
 use aya_ebpf::{
-    bindings::xdp_action,
-    macros::{map, xdp},
-    maps::{HashMap, LruHashMap},
-    programs::XdpContext,
+    bindings::{xdp_action, TC_ACT_OK},
+    helpers::bpf_xdp_adjust_tail,
+    macros::{classifier, map, xdp},
+    maps::{Array, DevMapHash, HashMap, LpmTrie, LruHashMap, PerCpuArray, PerCpuHashMap, ProgramArray, RingBuf, XskMap},
+    programs::{TcContext, XdpContext},
 };
 use aya_log_ebpf::info;
 use core::mem;
 use memoffset::offset_of;
+use parser::PacketBytes;
 
 // Use our common code with no_std compatibility
+mod admission;
 mod bindings;
+mod egress;
 mod ndn;
 mod maps;
 mod parser;
+mod pipeline;
 mod pit;
 mod fib;
+mod ratelimit;
 mod utils;
 
 // Define map sizes
 const MAX_PIT_ENTRIES: usize = 2048;
 const MAX_FIB_ENTRIES: usize = 1024;
 const MAX_CS_ENTRIES: usize = 4096;
+const MAX_CS_DATA_ENTRIES: usize = 1024;
+const MAX_EGRESS_FACES: usize = 256;
+const MAX_DEAD_NONCES: usize = 4096;
+const MAX_INTERFACE_COUNTERS: usize = 64;
+const MAX_RATE_LIMIT_BUCKETS: usize = 4096;
+const MAX_CONGESTED_INTERFACES: usize = 64;
+const MAX_XSK_QUEUES: usize = 64;
+const MAX_CS_PREFIXES: usize = 256;
+const MAX_CHAINED_INTERFACES: usize = 1024;
+const NAME_EVENTS_SIZE: usize = 256 * 1024;
 
-// Create eBPF maps
+/// LPM tries must be created without preallocation
+const BPF_F_NO_PREALLOC: u32 = 1;
+
+/// Hop limit of Data packets sent back from the kernel CS
+const CS_REPLY_HOP_LIMIT: u8 = 64;
+
+/// Defaults of the settings userspace can change in CONFIG
+const DEFAULT_PIT_LIFETIME_MS: u32 = 4000;
+const DEFAULT_CS_TTL_MS: u32 = 10000;
+
+/// Which FIB Interests are matched against, set by userspace at load time
+#[no_mangle]
+static FIB_MODE: u8 = maps::FIB_MODE_HASH;
+
+// Create eBPF maps, pinned by name under the loader's pin path so every
+// process loading the program shares the live tables
 #[map(name = "PIT_TABLE")]
 static mut PIT_TABLE: LruHashMap<maps::PitKey, maps::PitValue> =
-    LruHashMap::<maps::PitKey, maps::PitValue>::with_max_entries(MAX_PIT_ENTRIES as u32, 0);
+    LruHashMap::<maps::PitKey, maps::PitValue>::pinned(MAX_PIT_ENTRIES as u32, 0);
+
+#[map(name = "DEAD_NONCES")]
+static mut DEAD_NONCES: LruHashMap<maps::DeadNonceKey, u64> =
+    LruHashMap::<maps::DeadNonceKey, u64>::pinned(MAX_DEAD_NONCES as u32, 0);
 
 #[map(name = "FIB_TABLE")]
 static mut FIB_TABLE: HashMap<maps::FibKey, maps::FibValue> =
-    HashMap::<maps::FibKey, maps::FibValue>::with_max_entries(MAX_FIB_ENTRIES as u32, 0);
+    HashMap::<maps::FibKey, maps::FibValue>::pinned(MAX_FIB_ENTRIES as u32, 0);
+
+#[map(name = "FIB_TRIE")]
+static mut FIB_TRIE: LpmTrie<maps::FibTrieName, maps::FibValue> =
+    LpmTrie::<maps::FibTrieName, maps::FibValue>::pinned(MAX_FIB_ENTRIES as u32, BPF_F_NO_PREALLOC);
 
 #[map(name = "CS_TABLE")]
 static mut CS_TABLE: LruHashMap<maps::CsKey, maps::CsValue> =
-    LruHashMap::<maps::CsKey, maps::CsValue>::with_max_entries(MAX_CS_ENTRIES as u32, 0);
+    LruHashMap::<maps::CsKey, maps::CsValue>::pinned(MAX_CS_ENTRIES as u32, 0);
+
+#[map(name = "CS_DATA")]
+static mut CS_DATA: LruHashMap<maps::CsKey, maps::CsData> =
+    LruHashMap::<maps::CsKey, maps::CsData>::pinned(MAX_CS_DATA_ENTRIES as u32, 0);
+
+#[map(name = "EGRESS")]
+static mut EGRESS: DevMapHash = DevMapHash::pinned(MAX_EGRESS_FACES as u32, 0);
+
+/// AF_XDP sockets of userspace forwarders, indexed by receive queue
+#[map(name = "XSKS")]
+static mut XSKS: XskMap = XskMap::pinned(MAX_XSK_QUEUES as u32, 0);
+
+#[map(name = "EGRESS_FACES")]
+static mut EGRESS_FACES: HashMap<u32, maps::EgressFace> =
+    HashMap::<u32, maps::EgressFace>::pinned(MAX_EGRESS_FACES as u32, 0);
 
 #[map(name = "METRICS")]
-static mut METRICS: HashMap<u32, u64> = HashMap::<u32, u64>::with_max_entries(32, 0);
+static mut METRICS: PerCpuArray<u64> = PerCpuArray::<u64>::pinned(maps::metrics::COUNT, 0);
+
+#[map(name = "INTERFACE_COUNTERS")]
+static mut INTERFACE_COUNTERS: PerCpuHashMap<u32, maps::InterfaceCounters> =
+    PerCpuHashMap::<u32, maps::InterfaceCounters>::pinned(MAX_INTERFACE_COUNTERS as u32, 0);
+
+#[map(name = "RATE_LIMITS")]
+static mut RATE_LIMITS: LruHashMap<maps::RateLimitKey, maps::TokenBucket> =
+    LruHashMap::<maps::RateLimitKey, maps::TokenBucket>::pinned(MAX_RATE_LIMIT_BUCKETS as u32, 0);
+
+#[map(name = "CONGESTED")]
+static mut CONGESTED: HashMap<u32, u8> =
+    HashMap::<u32, u8>::pinned(MAX_CONGESTED_INTERFACES as u32, 0);
+
+/// Name prefixes allowed in or denied from the CS, keyed like FIB_TABLE
+#[map(name = "CS_PREFIXES")]
+static mut CS_PREFIXES: HashMap<maps::FibKey, u8> =
+    HashMap::<maps::FibKey, u8>::pinned(MAX_CS_PREFIXES as u32, 0);
+
+#[map(name = "CONFIG")]
+static mut CONFIG: Array<u32> = Array::<u32>::pinned(maps::config::COUNT, 0);
+
+/// Names of the Interests added to the PIT, for userspace to display
+#[map(name = "NAME_EVENTS")]
+static mut NAME_EVENTS: RingBuf = RingBuf::pinned(NAME_EVENTS_SIZE as u32, 0);
+
+#[map(name = "PIPELINE")]
+static mut PIPELINE: ProgramArray = ProgramArray::pinned(pipeline::STAGE_COUNT, 0);
+
+/// Programs udcn took interfaces over from, by interface index; packets
+/// that are not NDN go on to them
+#[map(name = "CHAIN")]
+static mut CHAIN: ProgramArray = ProgramArray::pinned(MAX_CHAINED_INTERFACES as u32, 0);
+
+#[map(name = "PIPELINE_STATE")]
+static mut PIPELINE_STATE: PerCpuArray<pipeline::PipelineState> =
+    PerCpuArray::<pipeline::PipelineState>::pinned(1, 0);
 
 /// XDP program entry point for NDN packet processing
 #[xdp]
 pub fn ndn_xdp(ctx: XdpContext) -> u32 {
-    match try_ndn_xdp(ctx) {
+    verdict(try_ndn_xdp(&ctx))
+}
+
+/// Interest stage of the pipeline
+#[xdp]
+pub fn ndn_interest(ctx: XdpContext) -> u32 {
+    verdict(try_interest_stage(&ctx))
+}
+
+/// Data stage of the pipeline
+#[xdp]
+pub fn ndn_data(ctx: XdpContext) -> u32 {
+    verdict(try_data_stage(&ctx))
+}
+
+/// Forwarding stage of the pipeline, sending Interests out of the kernel
+#[xdp]
+pub fn ndn_forward(ctx: XdpContext) -> u32 {
+    verdict(try_forward_stage(&ctx))
+}
+
+/// TC egress entry point, for the NDN packets XDP never sees leave
+#[classifier]
+pub fn ndn_tc_egress(mut ctx: TcContext) -> i32 {
+    if egress::process(&mut ctx).is_err() {
+        let _ = count(maps::metrics::ABORTED, 1);
+    }
+    TC_ACT_OK
+}
+
+/// Turn the result of a stage into an XDP action
+#[inline(always)]
+fn verdict(result: Result<u32, ()>) -> u32 {
+    match result {
         Ok(ret) => ret,
-        Err(_) => xdp_action::XDP_ABORTED,
+        Err(_) => {
+            let _ = count(maps::metrics::ABORTED, 1);
+            xdp_action::XDP_ABORTED
+        }
+    }
+}
+
+/// Hand the packet over to a stage of the pipeline
+///
+/// A successful tail call never returns; if userspace has not loaded the
+/// stage the packet is passed up instead.
+#[inline(always)]
+fn next_stage(ctx: &XdpContext, stage: u32) -> Result<u32, ()> {
+    unsafe {
+        let _ = PIPELINE.tail_call(ctx, stage);
+    }
+    count(maps::metrics::PIPELINE_MISSES, 1)?;
+    Ok(xdp_action::XDP_PASS)
+}
+
+/// Hand a packet udcn does not process to the program it took the
+/// interface over from, or up the stack if there was none
+#[inline(always)]
+fn pass_on(ctx: &XdpContext) -> Result<u32, ()> {
+    let ifindex = unsafe { (*ctx.ctx).ingress_ifindex };
+    unsafe {
+        let _ = CHAIN.tail_call(ctx, ifindex);
+    }
+    Ok(xdp_action::XDP_PASS)
+}
+
+/// Add `value` to a counter of the current CPU
+///
+/// Each CPU has its own copy of the metrics, so the update needs no atomics;
+/// userspace sums the copies.
+#[inline(always)]
+fn count(index: u32, value: u64) -> Result<(), ()> {
+    unsafe {
+        let counter = METRICS.get_ptr_mut(index).ok_or(())?;
+        *counter += value;
+    }
+    Ok(())
+}
+
+/// Read a setting from CONFIG, or `default` if userspace has not set it
+#[inline(always)]
+fn config(index: u32, default: u32) -> u32 {
+    match unsafe { CONFIG.get(index) } {
+        Some(&value) if value != 0 => value,
+        _ => default,
     }
 }
 
+/// Whether userspace has set a `maps::config::FLAG_*` bit
+#[inline(always)]
+fn has_flag(flag: u32) -> bool {
+    config(maps::config::FLAGS, 0) & flag != 0
+}
+
 /// Main NDN XDP packet processing logic
-fn try_ndn_xdp(ctx: XdpContext) -> Result<u32, ()> {
-    // Basic packet parser
-    let packet = parser::Packet::parse(&ctx)?;
+fn try_ndn_xdp(ctx: &XdpContext) -> Result<u32, ()> {
+    // Basic packet parser; what is not NDN over IP is someone else's
+    let packet = match parser::Packet::parse(ctx) {
+        Ok(packet) => packet,
+        Err(_) => return pass_on(ctx),
+    };
 
     // Increment the packet counter
-    unsafe {
-        let counter = METRICS.get_ptr_mut(&0).ok_or(())?;
-        *counter += 1;
+    count(maps::metrics::PACKETS_TOTAL, 1)?;
+
+    // TCP segments cannot be processed without reassembling the stream,
+    // count them and leave them to userspace
+    #[cfg(feature = "tcp")]
+    if packet.is_tcp() {
+        return punt_tcp_segment(ctx, &packet);
     }
 
     // Check if this is a NDN packet and what type it is
-    match parser::parse_ndn_packet(&packet) {
-        Ok(ndn::PacketType::Interest) => {
-            process_interest(&ctx, packet)
+    let stage = match parser::parse_ndn_packet(&packet) {
+        Ok(ndn::PacketType::Interest) => pipeline::STAGE_INTEREST,
+        Ok(ndn::PacketType::Data) => pipeline::STAGE_DATA,
+        _ => {
+            // Not an NDN packet or not a supported type, pass it on
+            return pass_on(ctx);
+        }
+    };
+
+    let state = pipeline::state()?;
+    unsafe { (*state).packet = packet.offsets() };
+    next_stage(ctx, stage)
+}
+
+/// Process the Interest the entry program found
+fn try_interest_stage(ctx: &XdpContext) -> Result<u32, ()> {
+    let state = pipeline::state()?;
+    let packet = parser::Packet::from_offsets(ctx, unsafe { (*state).packet });
+    let bytes = packet.data_len() as u64;
+
+    let action = process_interest(ctx, packet, state)?;
+    finish(ctx, true, bytes, action)
+}
+
+/// Process the Data the entry program found
+fn try_data_stage(ctx: &XdpContext) -> Result<u32, ()> {
+    let state = pipeline::state()?;
+    let packet = parser::Packet::from_offsets(ctx, unsafe { (*state).packet });
+    let bytes = packet.data_len() as u64;
+
+    let action = process_data(ctx, packet)?;
+    finish(ctx, false, bytes, action)
+}
+
+/// Send out the Interest the Interest stage found a next hop for
+fn try_forward_stage(ctx: &XdpContext) -> Result<u32, ()> {
+    let state = pipeline::state()?;
+    let (offsets, fields, face_id) = unsafe { ((*state).packet, (*state).fields, (*state).face_id) };
+    let packet = parser::Packet::from_offsets(ctx, offsets);
+    let bytes = packet.data_len() as u64;
+
+    let action = redirect_interest(ctx, &packet, &fields, face_id)?;
+    finish(ctx, true, bytes, action)
+}
+
+/// Count the packet against its interface once the last stage decided on it
+#[inline(always)]
+fn finish(ctx: &XdpContext, interest: bool, bytes: u64, action: u32) -> Result<u32, ()> {
+    count_interface(ctx, interest, bytes, action)?;
+    if action == xdp_action::XDP_PASS && has_flag(maps::config::FLAG_AF_XDP) {
+        return redirect_to_xsk(ctx);
+    }
+    Ok(action)
+}
+
+/// Send a packet userspace has to process to the AF_XDP socket bound to
+/// its receive queue, or up the stack if no socket is bound there
+#[inline(always)]
+fn redirect_to_xsk(ctx: &XdpContext) -> Result<u32, ()> {
+    let queue = unsafe { (*ctx.ctx).rx_queue_index };
+    match unsafe { XSKS.redirect(queue, xdp_action::XDP_PASS as u64) } {
+        Ok(action) if action == xdp_action::XDP_REDIRECT => {
+            count(maps::metrics::XSK_REDIRECTS, 1)?;
+            Ok(action)
         }
-        Ok(ndn::PacketType::Data) => {
-            process_data(&ctx, packet)
+        _ => Ok(xdp_action::XDP_PASS),
+    }
+}
+
+/// Report the name of a new PIT entry to userspace. Nothing is reported
+/// when the ring buffer is full, userspace then shows the bare hash.
+#[inline(always)]
+fn report_name(ctx: &XdpContext, name: &ndn::NameHashes) {
+    let mut entry = match unsafe { NAME_EVENTS.reserve::<maps::NameEvent>(0) } {
+        Some(entry) => entry,
+        None => return,
+    };
+    let event = entry.as_mut_ptr();
+
+    let mut i = 0;
+    while i < maps::MAX_NAME_EVENT_LENGTH {
+        if i >= name.components_len {
+            break;
         }
-        _ => {
-            // Not an NDN packet or not a supported type, pass it up the stack
-            Ok(xdp_action::XDP_PASS)
+        match utils::byte_ptr_at(ctx, name.components_offset + i) {
+            Ok(byte) => unsafe { (*event).components[i] = *byte },
+            Err(()) => {
+                entry.discard(0);
+                return;
+            }
         }
+        i += 1;
     }
+
+    unsafe {
+        (*event).name_hash = name.full;
+        (*event).name_len = name.len;
+        (*event)._pad = 0;
+        (*event).components_len = i as u16;
+    }
+    entry.submit(0);
 }
 
-/// Process an Interest packet
-fn process_interest(ctx: &XdpContext, packet: parser::Packet) -> Result<u32, ()> {
+/// Count an Interest or Data against the interface it arrived on
+#[inline(always)]
+fn count_interface(ctx: &XdpContext, interest: bool, bytes: u64, action: u32) -> Result<(), ()> {
+    let ifindex = unsafe { (*ctx.ctx).ingress_ifindex };
+
+    let counters = match unsafe { INTERFACE_COUNTERS.get_ptr_mut(&ifindex) } {
+        Some(counters) => counters,
+        None => {
+            let zero = maps::InterfaceCounters { interests: 0, data: 0, drops: 0, bytes: 0 };
+            unsafe {
+                INTERFACE_COUNTERS.insert(&ifindex, &zero, 0).map_err(|_| ())?;
+                INTERFACE_COUNTERS.get_ptr_mut(&ifindex).ok_or(())?
+            }
+        }
+    };
+
     unsafe {
-        // Increment interest counter
-        let counter = METRICS.get_ptr_mut(&1).ok_or(())?;
-        *counter += 1;
+        if interest {
+            (*counters).interests += 1;
+        } else {
+            (*counters).data += 1;
+        }
+        if action == xdp_action::XDP_DROP {
+            (*counters).drops += 1;
+        }
+        (*counters).bytes += bytes;
     }
+    Ok(())
+}
+
+/// Marks the metadata of NDN-over-TCP segments passed up to the stack
+#[cfg(feature = "tcp")]
+const TCP_PUNT_MARK: u32 = 0x4e44_4e54;
+
+/// Metadata written ahead of an NDN-over-TCP segment passed up to the stack
+#[cfg(feature = "tcp")]
+#[repr(C)]
+struct TcpPuntMeta {
+    /// Always `TCP_PUNT_MARK`
+    mark: u32,
+    /// TLV type of the NDN packet the segment starts with, 0 if it
+    /// continues an earlier one
+    packet_type: u8,
+    _pad: [u8; 3],
+}
+
+/// Count an NDN-over-TCP segment and pass it up, tagged for TC programs
+/// and AF_XDP readers
+#[cfg(feature = "tcp")]
+fn punt_tcp_segment(ctx: &XdpContext, packet: &parser::Packet) -> Result<u32, ()> {
+    count(maps::metrics::TCP_SEGMENTS, 1)?;
+    count(maps::metrics::TCP_BYTES, packet.data_len() as u64)?;
+
+    // A segment starting with an Interest or Data type most likely starts
+    // that packet, anything else continues an earlier one
+    let packet_type = match parser::parse_ndn_packet(packet) {
+        Ok(ndn::PacketType::Interest) => ndn::TLV_INTEREST,
+        Ok(ndn::PacketType::Data) => ndn::TLV_DATA,
+        _ => 0,
+    };
+
+    // Drivers without metadata support still get the segment, just untagged
+    let meta_len = mem::size_of::<TcpPuntMeta>();
+    if unsafe { aya_ebpf::helpers::bpf_xdp_adjust_meta(ctx.ctx, -(meta_len as i32)) } == 0 {
+        let meta = ctx.metadata();
+        if meta + meta_len <= ctx.data() {
+            unsafe {
+                *(meta as *mut TcpPuntMeta) = TcpPuntMeta {
+                    mark: TCP_PUNT_MARK,
+                    packet_type,
+                    _pad: [0; 3],
+                };
+            }
+        }
+    }
+
+    Ok(xdp_action::XDP_PASS)
+}
+
+/// Process an Interest packet
+fn process_interest(ctx: &XdpContext, packet: parser::Packet, state: *mut pipeline::PipelineState) -> Result<u32, ()> {
+    // Increment interest counters
+    count(maps::metrics::INTERESTS_RECEIVED, 1)?;
+    count(maps::metrics::INTEREST_BYTES, packet.data_len() as u64)?;
 
     // Parse name from Interest packet
-    let name_hash = match parser::extract_name_hash(&packet) {
-        Some(hash) => hash,
+    let name = match parser::parse_name(&packet) {
+        Some(name) => name,
         None => return Ok(xdp_action::XDP_PASS),
     };
+    let name_hash = name.full;
+
+    // Keep floods of Interests under one prefix out of the PIT
+    let ifindex = unsafe { (*ctx.ctx).ingress_ifindex };
+    if !ratelimit::admit(ifindex, &name) {
+        count(maps::metrics::INTERESTS_RATE_LIMITED, 1)?;
+        if has_flag(maps::config::FLAG_RATE_LIMIT_PASS) {
+            return Ok(xdp_action::XDP_PASS);
+        }
+        return Ok(xdp_action::XDP_DROP);
+    }
+
+    // Extract nonce and hop limit from Interest
+    let fields = parser::extract_interest_fields(&packet, &name);
+    let nonce = fields.nonce;
+
+    // An Interest that has used up its hops must not go any further
+    if fields.hop_limit(&packet) == Some(0) {
+        count(maps::metrics::INTERESTS_HOP_LIMIT, 1)?;
+        return Ok(xdp_action::XDP_DROP);
+    }
+
+    let pit_key = maps::PitKey {
+        name_hash,
+        name_len: name.len,
+    };
+
+    // An Interest whose name and nonce were recently satisfied has looped
+    // back to us, drop it
+    if let Some(nonce) = nonce {
+        let dead = maps::DeadNonceKey {
+            name_hash,
+            name_len: name.len,
+            nonce,
+        };
+        if pit::is_dead_nonce(&dead) {
+            count(maps::metrics::INTERESTS_LOOPED, 1)?;
+            return Ok(xdp_action::XDP_DROP);
+        }
+    }
 
     // Check the CS (Content Store) for cached data
     let cs_key = maps::CsKey {
         name_hash,
-        name_len: 0, // Simplified for now
+        name_len: name.len,
     };
 
     unsafe {
-        if let Some(_cs_value) = CS_TABLE.get(&cs_key) {
-            // Found in CS! In a full implementation, would retrieve and return the data
-            // For now, just increment the CS hit counter
-            let counter = METRICS.get_ptr_mut(&3).ok_or(())?;
-            *counter += 1;
-            
-            // In real implementation would return cached data
-            // For now just pass up to userspace to handle
-            return Ok(xdp_action::XDP_PASS);
+        if let Some(cs_value) = CS_TABLE.get(&cs_key) {
+            // Expired content must not satisfy the Interest, evict it and
+            // carry on as a miss
+            if utils::is_expired(cs_value.timestamp, cs_value.ttl_ms as u64) {
+                let _ = CS_TABLE.remove(&cs_key);
+                let _ = CS_DATA.remove(&cs_key);
+                count(maps::metrics::CS_EVICTIONS, 1)?;
+            } else if fields.must_be_fresh && utils::is_expired(cs_value.timestamp, cs_value.freshness_ms as u64) {
+                // Stale content stays for Interests that accept it, this
+                // one carries on as a miss
+            } else {
+                // Found in CS! Increment the CS hit counter
+                count(maps::metrics::CS_HITS, 1)?;
+
+                // Answer from the kernel when the Data itself is stored here,
+                // otherwise pass up to userspace to answer from its CS. The
+                // key is only a hash, so the stored name must match as well
+                if let Some(cached) = CS_DATA.get(&cs_key) {
+                    if packet.is_rewritable()
+                        && !has_flag(maps::config::FLAG_NO_CS_REPLY)
+                        && cached_name_matches(ctx, &name, cached)
+                    {
+                        return reply_from_cs(ctx, &packet, cached);
+                    }
+                }
+                return Ok(xdp_action::XDP_PASS);
+            }
         }
     }
 
     // CS miss, check PIT for duplicate Interest
-    // Extract nonce from Interest
-    let nonce = parser::extract_nonce(&packet).unwrap_or(0);
+    // Check if in PIT; an expired entry is replaced below
+    let pending = unsafe { PIT_TABLE.get(&pit_key).copied() };
+    match pending {
+        Some(mut pending) if !pit::is_pit_entry_expired(&pending) => {
+            match nonce {
+                // A retransmission with a new nonce is forwarded again
+                Some(nonce) if !pending.has_nonce(nonce) => {
+                    pending.add_nonce(nonce);
+                    unsafe {
+                        PIT_TABLE.insert(&pit_key, &pending, 0).map_err(|_| ())?;
+                    }
+                }
+                _ => {
+                    // Duplicate Interest, drop
+                    count(maps::metrics::INTERESTS_DUPLICATE, 1)?;
+                    return Ok(xdp_action::XDP_DROP);
+                }
+            }
+        }
+        _ => {
+            // New Interest, add to PIT
+            let face_id = parser::extract_face_id(&ctx)?;
+            let mut pit_value = maps::PitValue {
+                face_id,
+                timestamp: utils::get_timestamp(),
+                lifetime_ms: config(maps::config::PIT_LIFETIME_MS, DEFAULT_PIT_LIFETIME_MS),
+                name_component_count: name.len,
+                nonce_count: 0,
+                nonces: [0; maps::MAX_PIT_NONCES],
+            };
+            if let Some(nonce) = nonce {
+                pit_value.add_nonce(nonce);
+            }
 
-    let pit_key = maps::PitKey {
-        name_hash,
-        name_len: 0, // Simplified for now
-        nonce,
-    };
+            unsafe {
+                PIT_TABLE.insert(&pit_key, &pit_value, 0).map_err(|_| ())?;
+            }
 
-    // Check if in PIT
-    unsafe {
-        if PIT_TABLE.get(&pit_key).is_some() {
-            // Duplicate Interest, drop
-            let counter = METRICS.get_ptr_mut(&4).ok_or(())?;
-            *counter += 1;
-            return Ok(xdp_action::XDP_DROP);
+            // Increment PIT insert counter
+            count(maps::metrics::PIT_INSERTS, 1)?;
+            report_name(ctx, &name);
         }
     }
 
-    // New Interest, add to PIT
-    let face_id = parser::extract_face_id(&ctx)?;
-    let pit_value = maps::PitValue {
-        face_id,
-        timestamp: utils::get_timestamp(),
-        lifetime_ms: 4000, // 4 seconds default
-        name_component_count: 0, // Simplified for now
+    // Check FIB for forwarding, longest prefix first
+    let fib_mode = unsafe { core::ptr::read_volatile(&FIB_MODE) };
+    let next_hop = if fib_mode == maps::FIB_MODE_LPM_TRIE {
+        fib::trie_longest_prefix_match(ctx, &name)
+    } else {
+        fib::longest_prefix_match(&name)
     };
 
-    unsafe {
-        PIT_TABLE.insert(&pit_key, &pit_value, 0).map_err(|_| ())?;
-        
-        // Increment PIT insert counter
-        let counter = METRICS.get_ptr_mut(&5).ok_or(())?;
-        *counter += 1;
+    if let Ok(face_id) = next_hop {
+        // Found in FIB! Increment FIB hit counter
+        count(maps::metrics::FIB_HITS, 1)?;
+
+        // Forward from the kernel if the face is an interface it can
+        // redirect to, otherwise pass up to userspace to forward
+        if packet.is_rewritable() && !has_flag(maps::config::FLAG_NO_REDIRECT) {
+            unsafe {
+                (*state).fields = fields;
+                (*state).face_id = face_id;
+            }
+            return next_stage(ctx, pipeline::STAGE_FORWARD);
+        }
     }
 
-    // Check FIB for forwarding
-    let fib_key = maps::FibKey {
-        prefix_hash: name_hash, // Simplified lookup for now 
-        prefix_len: 0,
+    // No matching FIB entry, pass to userspace for further processing
+    Ok(xdp_action::XDP_PASS)
+}
+
+/// Send an Interest straight out of the egress interface of `face_id`
+fn redirect_interest(
+    ctx: &XdpContext,
+    packet: &parser::Packet,
+    fields: &parser::InterestFields,
+    face_id: maps::FaceId,
+) -> Result<u32, ()> {
+    let key = face_id.0 as u32;
+    let egress = match unsafe { EGRESS_FACES.get(&key) } {
+        Some(egress) => *egress,
+        None => return Ok(xdp_action::XDP_PASS),
+    };
+    if unsafe { EGRESS.get(key) }.is_none() {
+        return Ok(xdp_action::XDP_PASS);
+    }
+
+    // The Interest may only leave with a HopLimit left after this hop;
+    // one on its last hop is left to userspace, which may deliver it locally
+    let hop_limit = match fields.hop_limit_offset {
+        Some(offset) => {
+            let hop_limit = utils::byte_ptr_at_mut(ctx, offset)?;
+            if unsafe { *hop_limit } <= 1 {
+                return Ok(xdp_action::XDP_PASS);
+            }
+            Some((offset, hop_limit))
+        }
+        None => None,
     };
 
+    let eth = utils::ptr_at_mut::<parser::EtherHeader>(ctx, 0)?;
+    let ipv6 = utils::ptr_at_mut::<parser::Ipv6Header>(ctx, packet.ip_offset())?;
+    let udp = utils::ptr_at_mut::<parser::UdpHeader>(ctx, packet.udp_offset())?;
+
     unsafe {
-        if let Some(_fib_value) = FIB_TABLE.get(&fib_key) {
-            // Found in FIB! In full implementation, would forward to face
-            // For now, just increment FIB hit counter
-            let counter = METRICS.get_ptr_mut(&6).ok_or(())?;
-            *counter += 1;
-            
-            // Pass up to userspace to handle the forwarding
+        // Leave Interests about to expire to userspace, which drops them
+        if (*ipv6).hop_limit <= 1 {
             return Ok(xdp_action::XDP_PASS);
         }
+        (*ipv6).hop_limit -= 1;
+
+        (*eth).src_addr = egress.src_mac;
+        (*eth).dst_addr = egress.dst_mac;
+
+        // The addresses are part of the UDP pseudo-header, so patch the
+        // checksum for the new ones
+        let mut old = utils::csum_addr(&(*ipv6).src_addr) + utils::csum_addr(&(*ipv6).dst_addr);
+        let mut new = utils::csum_addr(&egress.src_addr) + utils::csum_addr(&egress.dst_addr);
+        (*ipv6).src_addr = egress.src_addr;
+        (*ipv6).dst_addr = egress.dst_addr;
+
+        // So is the HopLimit, the high byte of its checksum word when at an
+        // even distance from the UDP header
+        if let Some((offset, hop_limit)) = hop_limit {
+            let shift = if (offset - packet.udp_offset()) % 2 == 0 { 8 } else { 0 };
+            old += (*hop_limit as u32) << shift;
+            *hop_limit -= 1;
+            new += (*hop_limit as u32) << shift;
+        }
+
+        let checksum = match utils::csum_replace(u16::from_be((*udp).checksum), old, new) {
+            0 => 0xffff,
+            checksum => checksum,
+        };
+        (*udp).checksum = checksum.to_be();
     }
 
-    // No matching FIB entry, pass to userspace for further processing
-    Ok(xdp_action::XDP_PASS)
+    count(maps::metrics::INTERESTS_REDIRECTED, 1)?;
+
+    unsafe { Ok(EGRESS.redirect(key, 0).unwrap_or(xdp_action::XDP_ABORTED)) }
 }
 
-/// Process a Data packet
-fn process_data(ctx: &XdpContext, packet: parser::Packet) -> Result<u32, ()> {
+/// Whether the Data stored in `cached` carries exactly the name of the
+/// Interest, compared byte by byte in a bounded loop
+fn cached_name_matches(ctx: &XdpContext, name: &ndn::NameHashes, cached: &maps::CsData) -> bool {
+    let Some((start, len)) = parser::name_components(cached, 0) else {
+        return false;
+    };
+    if len != name.components_len {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < ndn::MAX_PARSED_NAME_LENGTH {
+        if i >= len {
+            break;
+        }
+        let Some(wanted) = ctx.byte_at(name.components_offset + i) else {
+            return false;
+        };
+        if cached.byte_at(start + i) != Some(wanted) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Turn an Interest around into the cached Data answering it
+fn reply_from_cs(ctx: &XdpContext, packet: &parser::Packet, cached: &maps::CsData) -> Result<u32, ()> {
+    let data_len = cached.len as usize;
+    if data_len == 0 || data_len > maps::MAX_CS_DATA_SIZE {
+        return Ok(xdp_action::XDP_PASS);
+    }
+
+    // Resize the packet first, so a failure leaves the Interest intact.
+    // Any VLAN tags stay in place ahead of the IP header
+    let payload_offset = packet.data_offset();
+    let packet_len = ctx.data_end() - ctx.data();
+    let delta = (payload_offset + data_len) as i32 - packet_len as i32;
+    if delta != 0 && unsafe { bpf_xdp_adjust_tail(ctx.ctx, delta) } != 0 {
+        return Ok(xdp_action::XDP_PASS);
+    }
+
+    // Copy the Data over the Interest, one byte at a time to keep the verifier happy
+    let mut i = 0;
+    while i < maps::MAX_CS_DATA_SIZE {
+        if i >= data_len {
+            break;
+        }
+        let byte = utils::byte_ptr_at_mut(ctx, payload_offset + i)?;
+        unsafe { *byte = cached.bytes[i] };
+        i += 1;
+    }
+
+    // Packet pointers are only valid again after the resize
+    let eth = utils::ptr_at_mut::<parser::EtherHeader>(ctx, 0)?;
+    let ipv6 = utils::ptr_at_mut::<parser::Ipv6Header>(ctx, packet.ip_offset())?;
+    let udp = utils::ptr_at_mut::<parser::UdpHeader>(ctx, packet.udp_offset())?;
+    let udp_len = (parser::UDP_HDR_SIZE + data_len) as u16;
+
     unsafe {
-        // Increment data counter
-        let counter = METRICS.get_ptr_mut(&2).ok_or(())?;
-        *counter += 1;
+        // Send back where the Interest came from
+        let mac = (*eth).src_addr;
+        (*eth).src_addr = (*eth).dst_addr;
+        (*eth).dst_addr = mac;
+
+        let addr = (*ipv6).src_addr;
+        (*ipv6).src_addr = (*ipv6).dst_addr;
+        (*ipv6).dst_addr = addr;
+        (*ipv6).payload_len = udp_len.to_be();
+        (*ipv6).hop_limit = CS_REPLY_HOP_LIMIT;
+
+        let port = (*udp).src_port;
+        (*udp).src_port = (*udp).dst_port;
+        (*udp).dst_port = port;
+        (*udp).length = udp_len.to_be();
+
+        // The UDP checksum is mandatory over IPv6: sum the pseudo-header,
+        // the UDP header and the precomputed sum of the Data
+        let src_addr = (*ipv6).src_addr;
+        let dst_addr = (*ipv6).dst_addr;
+        let sum = utils::csum_addr(&src_addr)
+            + utils::csum_addr(&dst_addr)
+            + 2 * udp_len as u32
+            + parser::IPPROTO_UDP as u32
+            + u16::from_be((*udp).src_port) as u32
+            + u16::from_be((*udp).dst_port) as u32
+            + cached.checksum as u32;
+        let checksum = match !utils::csum_fold(sum) {
+            0 => 0xffff,
+            checksum => checksum,
+        };
+        (*udp).checksum = checksum.to_be();
     }
 
+    count(maps::metrics::CS_REPLIES, 1)?;
+    count(maps::metrics::CS_REPLY_BYTES, data_len as u64)?;
+
+    Ok(xdp_action::XDP_TX)
+}
+
+/// Process a Data packet
+fn process_data(ctx: &XdpContext, packet: parser::Packet) -> Result<u32, ()> {
+    // Increment data counters
+    count(maps::metrics::DATA_RECEIVED, 1)?;
+    count(maps::metrics::DATA_BYTES, packet.data_len() as u64)?;
+
     // Parse name from Data packet
-    let name_hash = match parser::extract_name_hash(&packet) {
-        Some(hash) => hash,
+    let name = match parser::parse_name(&packet) {
+        Some(name) => name,
         None => return Ok(xdp_action::XDP_PASS),
     };
+    let name_hash = name.full;
 
     // Check PIT for matching Interest
-    let pit_matched = pit::find_matching_interests(name_hash)?;
+    let pit_matched = pit::find_matching_interests(name_hash, name.len)?;
     
     if !pit_matched {
         // No matching PIT entry, unsolicited data, drop
+        count(maps::metrics::DATA_UNSOLICITED, 1)?;
         return Ok(xdp_action::XDP_DROP);
     }
 
+    // Userspace decides which Data the kernel CS records
+    if !admission::admit(&name, packet.data_len() as u32) {
+        return Ok(xdp_action::XDP_PASS);
+    }
+
     // Add to CS for future Interest matching
     let cs_key = maps::CsKey {
         name_hash,
-        name_len: 0, // Simplified for now
+        name_len: name.len,
     };
     
     let cs_value = maps::CsValue {
         content_hash: 0, // Would calculate content hash in real implementation
         timestamp: utils::get_timestamp(),
         content_size: packet.data_len() as u32,
-        ttl_ms: 10000, // 10 seconds default
+        ttl_ms: config(maps::config::CS_TTL_MS, DEFAULT_CS_TTL_MS),
+        freshness_ms: parser::parse_freshness(&packet, &name).unwrap_or(ndn::DEFAULT_FRESHNESS_MS),
+        _pad: 0,
     };
 
     unsafe {
         CS_TABLE.insert(&cs_key, &cs_value, 0).map_err(|_| ())?;
-        
-        // Increment CS insert counter
-        let counter = METRICS.get_ptr_mut(&7).ok_or(())?;
-        *counter += 1;
     }
 
+    // Increment CS insert counter
+    count(maps::metrics::CS_INSERTS, 1)?;
+
     // Pass to userspace for full processing
     Ok(xdp_action::XDP_PASS)
 }
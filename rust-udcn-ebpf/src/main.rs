@@ -192,7 +192,7 @@ fn try_ndn_xdp(ctx: XdpContext) -> Result<u32, ()> {
             let name_hash = pkt_type_and_name.1;
             
             // Check PIT for matching Interest
-            if let Ok(true) = pit::has_matching_interest(name_hash) {
+            if let Ok(true) = pit::has_matching_interest(name_hash, pkt_type_and_name.3 as u8) {
                 unsafe {
                     if let Some(count) = METRICS.get_ptr_mut(&maps::metrics::PIT_MATCHES) {
                         *count += 1;
@@ -8,6 +8,9 @@
 #[repr(transparent)]
 pub struct FaceId(pub u16);
 
+/// Maximum number of Interest nonces a PIT entry remembers
+pub const MAX_PIT_NONCES: usize = 4;
+
 /// A key used for the PIT (Pending Interest Table) map.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(C)]
@@ -16,8 +19,6 @@ pub struct PitKey {
     pub name_hash: u32,
     /// Number of components in the name
     pub name_len: u8,
-    /// Nonce value from the Interest
-    pub nonce: u32,
 }
 
 /// A value stored in the PIT map.
@@ -32,6 +33,53 @@ pub struct PitValue {
     pub lifetime_ms: u32,
     /// Number of components in the name
     pub name_component_count: u8,
+    /// Number of nonces in `nonces`
+    pub nonce_count: u8,
+    /// Nonces of the Interests pending in this entry, oldest first
+    pub nonces: [u32; MAX_PIT_NONCES],
+}
+
+impl PitValue {
+    /// Whether an Interest with this nonce is pending in this entry
+    pub fn has_nonce(&self, nonce: u32) -> bool {
+        let mut i = 0;
+        while i < MAX_PIT_NONCES {
+            if i < self.nonce_count as usize && self.nonces[i] == nonce {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    /// Record the nonce of another Interest for this entry, forgetting the
+    /// oldest one when the entry is full
+    pub fn add_nonce(&mut self, nonce: u32) {
+        if (self.nonce_count as usize) < MAX_PIT_NONCES {
+            self.nonces[self.nonce_count as usize % MAX_PIT_NONCES] = nonce;
+            self.nonce_count += 1;
+            return;
+        }
+        let mut i = 0;
+        while i + 1 < MAX_PIT_NONCES {
+            self.nonces[i] = self.nonces[i + 1];
+            i += 1;
+        }
+        self.nonces[MAX_PIT_NONCES - 1] = nonce;
+    }
+}
+
+/// A key used for the dead nonce list, remembering the name and nonce of
+/// Interests that are no longer pending so they are caught if they loop back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct DeadNonceKey {
+    /// Hash of the NDN name
+    pub name_hash: u32,
+    /// Number of components in the name
+    pub name_len: u8,
+    /// Nonce value from the Interest
+    pub nonce: u32,
 }
 
 /// A key used for the FIB (Forwarding Information Base) map.
@@ -76,6 +124,107 @@ pub struct CsValue {
     pub content_size: u32,
     /// TTL in milliseconds
     pub ttl_ms: u32,
+    /// FreshnessPeriod of the Data in milliseconds, past which the entry
+    /// no longer answers MustBeFresh Interests
+    pub freshness_ms: u32,
+    pub _pad: u32,
+}
+
+/// Maximum size of an encoded Data packet the kernel can answer Interests with
+pub const MAX_CS_DATA_SIZE: usize = 1024;
+
+/// An encoded Data packet that is sent back in place of a matching Interest
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct CsData {
+    /// Length of the encoded Data
+    pub len: u16,
+    /// One's complement sum of the encoded Data, for the UDP checksum
+    pub checksum: u16,
+    /// The encoded Data, zero-padded
+    pub bytes: [u8; MAX_CS_DATA_SIZE],
+}
+
+/// Longest encoded name prefix the LPM trie FIB holds
+pub const MAX_FIB_TRIE_NAME_LENGTH: usize = 64;
+
+/// Key data of the LPM trie FIB: the encoded name components, zero-padded
+pub type FibTrieName = [u8; MAX_FIB_TRIE_NAME_LENGTH];
+
+/// FIB matching prefix hashes in `FIB_TABLE`
+pub const FIB_MODE_HASH: u8 = 0;
+
+/// FIB matching encoded names in `FIB_TRIE`
+pub const FIB_MODE_LPM_TRIE: u8 = 1;
+
+/// How packets are sent out of a face: the interface to redirect to and
+/// the headers to rewrite on the way
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct EgressFace {
+    /// Index of the egress interface
+    pub ifindex: u32,
+    /// MAC address of the egress interface
+    pub src_mac: [u8; 6],
+    /// MAC address of the next hop
+    pub dst_mac: [u8; 6],
+    /// IPv6 address of the egress interface
+    pub src_addr: [u8; 16],
+    /// IPv6 address of the next hop
+    pub dst_addr: [u8; 16],
+}
+
+/// Counters of the NDN traffic arriving on one interface
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct InterfaceCounters {
+    /// Interests received
+    pub interests: u64,
+    /// Data packets received
+    pub data: u64,
+    /// Interests and Data dropped
+    pub drops: u64,
+    /// Bytes of the NDN packets received
+    pub bytes: u64,
+}
+
+/// A key of the Interest rate limiting map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct RateLimitKey {
+    /// Index of the ingress interface
+    pub ifindex: u32,
+    /// Hash of the rate limited name prefix
+    pub prefix_hash: u32,
+}
+
+/// Token bucket limiting the Interests of one prefix on one interface
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TokenBucket {
+    /// Tokens left, in thousandths of an Interest
+    pub tokens: u64,
+    /// Timestamp of the last refill, in milliseconds
+    pub last_refill: u64,
+}
+
+/// Longest encoded name reported to userspace in a `NameEvent`
+pub const MAX_NAME_EVENT_LENGTH: usize = 128;
+
+/// The name of an Interest added to the PIT, reported to userspace, which
+/// only finds its hash in the PIT
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct NameEvent {
+    /// Hash of the name, as in the PIT key
+    pub name_hash: u32,
+    /// Number of components in the name
+    pub name_len: u8,
+    pub _pad: u8,
+    /// Bytes of `components` filled in
+    pub components_len: u16,
+    /// The encoded name components, truncated to `MAX_NAME_EVENT_LENGTH`
+    pub components: [u8; MAX_NAME_EVENT_LENGTH],
 }
 
 /// Constants and enumerations related to metrics counters in the metrics map
@@ -85,9 +234,93 @@ pub mod metrics {
     pub const INTERESTS_RECEIVED: u32 = 1;
     pub const DATA_RECEIVED: u32 = 2;
     pub const CS_HITS: u32 = 3;
+    /// Interests dropped as duplicates of a pending one
     pub const INTERESTS_DUPLICATE: u32 = 4;
     pub const PIT_INSERTS: u32 = 5;
     pub const FIB_HITS: u32 = 6; 
     pub const CS_INSERTS: u32 = 7;
     pub const PIT_MATCHES: u32 = 8;
+    pub const CS_REPLIES: u32 = 9;
+    pub const INTERESTS_REDIRECTED: u32 = 10;
+    /// Data dropped because no Interest was pending for it
+    pub const DATA_UNSOLICITED: u32 = 11;
+    /// Packets aborted on a processing error
+    pub const ABORTED: u32 = 12;
+    /// Bytes of the NDN packets received, by type
+    pub const INTEREST_BYTES: u32 = 13;
+    pub const DATA_BYTES: u32 = 14;
+    /// Bytes of Data sent back from the kernel CS
+    pub const CS_REPLY_BYTES: u32 = 15;
+    /// Interests dropped because their nonce is in the dead nonce list
+    pub const INTERESTS_LOOPED: u32 = 16;
+    /// CS entries removed on lookup because their TTL had run out
+    pub const CS_EVICTIONS: u32 = 17;
+    /// NDN-over-TCP segments, and their payload bytes, punted to userspace
+    pub const TCP_SEGMENTS: u32 = 18;
+    pub const TCP_BYTES: u32 = 19;
+    /// Interests over the rate limit of their prefix
+    pub const INTERESTS_RATE_LIMITED: u32 = 20;
+    /// Interests dropped because their HopLimit ran out
+    pub const INTERESTS_HOP_LIMIT: u32 = 21;
+    /// Packets passed up because a pipeline stage was not loaded
+    pub const PIPELINE_MISSES: u32 = 22;
+    /// NDN packets sent, counted by the TC egress program
+    pub const EGRESS_INTERESTS: u32 = 23;
+    pub const EGRESS_DATA: u32 = 24;
+    pub const EGRESS_BYTES: u32 = 25;
+    /// Outgoing packets marked Congestion Experienced
+    pub const CONGESTION_MARKS: u32 = 26;
+    /// NDN packets redirected to an AF_XDP socket instead of the stack
+    pub const XSK_REDIRECTS: u32 = 27;
+
+    /// Number of metrics
+    pub const COUNT: u32 = 28;
+}
+
+/// Indices and values of the runtime settings in the config map, where 0
+/// leaves a setting at its built-in default
+pub mod config {
+    /// UDP port NDN packets are sent to
+    pub const NDN_PORT: u32 = 0;
+    /// Lifetime of kernel PIT entries
+    pub const PIT_LIFETIME_MS: u32 = 1;
+    /// Freshness of Data cached in the kernel CS
+    pub const CS_TTL_MS: u32 = 2;
+    /// Which Data the kernel CS records, one of the `CS_ADMIT_*` values
+    pub const CS_ADMISSION: u32 = 3;
+    /// Switches, a combination of the `FLAG_*` bits
+    pub const FLAGS: u32 = 4;
+    /// Interests per second admitted for each prefix on each interface,
+    /// unlimited if 0
+    pub const RATE_LIMIT_PPS: u32 = 5;
+    /// Interests admitted at once after a quiet period
+    pub const RATE_LIMIT_BURST: u32 = 6;
+    /// Components of the name prefixes rate limited separately
+    pub const RATE_LIMIT_PREFIX_LEN: u32 = 7;
+    /// Smallest and largest Data, in bytes, the kernel CS records
+    pub const CS_MIN_SIZE: u32 = 8;
+    pub const CS_MAX_SIZE: u32 = 9;
+
+    /// Number of settings
+    pub const COUNT: u32 = 10;
+
+    pub const CS_ADMIT_ALL: u32 = 0;
+    pub const CS_ADMIT_NONE: u32 = 1;
+    /// Only Data under prefixes allowed in CS_PREFIXES
+    pub const CS_ADMIT_LISTED: u32 = 2;
+
+    /// Rules of the name prefixes in CS_PREFIXES
+    pub const CS_PREFIX_ALLOW: u8 = 1;
+    pub const CS_PREFIX_DENY: u8 = 2;
+
+    /// Never answer Interests from the kernel CS
+    pub const FLAG_NO_CS_REPLY: u32 = 1 << 0;
+    /// Never redirect Interests out of egress faces
+    pub const FLAG_NO_REDIRECT: u32 = 1 << 1;
+    /// Pass Interests over the rate limit up to userspace instead of
+    /// dropping them
+    pub const FLAG_RATE_LIMIT_PASS: u32 = 1 << 2;
+    /// Hand the NDN packets the kernel passes up to the AF_XDP socket of
+    /// their receive queue, when there is one
+    pub const FLAG_AF_XDP: u32 = 1 << 3;
 }
@@ -10,9 +10,13 @@ pub const TLV_NACK: u8 = 0x03;
 pub const TLV_NAME: u8 = 0x07;
 pub const TLV_COMPONENT: u8 = 0x08;
 pub const TLV_NONCE: u8 = 0x0A;
+pub const TLV_SELECTORS: u8 = 0x09;
 pub const TLV_META_INFO: u8 = 0x14;
 pub const TLV_CONTENT: u8 = 0x15;
+pub const TLV_FRESHNESS_PERIOD: u8 = 0x19;
 pub const TLV_INTEREST_LIFETIME: u8 = 0x0C;
+pub const TLV_HOP_LIMIT: u8 = 0x22;
+pub const TLV_APPLICATION_PARAMETERS: u8 = 0x24;
 
 /// NDN packet types supported by the XDP program
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -34,6 +38,72 @@ pub const MAX_NAME_COMPONENTS: usize = 16;
 /// Maximum size of an NDN packet
 pub const MAX_NDN_PACKET_SIZE: usize = 8800;
 
+/// Maximum length of an encoded name the kernel parses; longer names are
+/// left to userspace
+pub const MAX_PARSED_NAME_LENGTH: usize = 256;
+
+/// Maximum number of Interest fields walked after the name
+pub const MAX_INTEREST_FIELDS: usize = 8;
+
+/// Maximum number of Data fields walked after the name, up to the content
+pub const MAX_DATA_FIELDS: usize = 4;
+
+/// FreshnessPeriod of Data that does not carry one, as userspace assumes
+pub const DEFAULT_FRESHNESS_MS: u32 = 10_000;
+
+/// FNV-1a offset basis
+pub const FNV_OFFSET_BASIS: u32 = 2166136261;
+
+/// FNV-1a prime
+pub const FNV_PRIME: u32 = 16777619;
+
+/// Feed one byte to an FNV-1a hash
+#[inline(always)]
+pub fn fnv1a(hash: u32, byte: u8) -> u32 {
+    (hash ^ byte as u32).wrapping_mul(FNV_PRIME)
+}
+
+/// Hashes of a name and of each of its prefixes
+///
+/// The hashes cover the encoded name components, so the hash of a prefix
+/// is the hash the full name had after its last component.
+#[derive(Clone, Copy)]
+pub struct NameHashes {
+    /// Hash of the whole name
+    pub full: u32,
+    /// Number of components in the name
+    pub len: u8,
+    /// `prefixes[i]` is the hash of the first `i + 1` components
+    pub prefixes: [u32; MAX_NAME_COMPONENTS],
+    /// Packet offset of the first component
+    pub components_offset: usize,
+    /// Length of the encoded components
+    pub components_len: usize,
+}
+
+impl NameHashes {
+    /// Hashes of the empty name
+    pub fn new() -> Self {
+        Self {
+            full: FNV_OFFSET_BASIS,
+            len: 0,
+            prefixes: [0; MAX_NAME_COMPONENTS],
+            components_offset: 0,
+            components_len: 0,
+        }
+    }
+
+    /// Record the hash of the prefix ending with the next component
+    #[inline(always)]
+    pub fn push_prefix(&mut self, hash: u32) {
+        let index = self.len as usize;
+        if index < MAX_NAME_COMPONENTS {
+            self.prefixes[index] = hash;
+        }
+        self.len = self.len.saturating_add(1);
+    }
+}
+
 /// eBPF-safe function to compute a hash of an NDN name
 /// 
 /// Since we can't use string operations and have limited functionality in eBPF,
@@ -3,7 +3,7 @@
 use core::mem;
 use aya_ebpf::{
     bindings::xdp_action,
-    programs::XdpContext,
+    programs::{TcContext, XdpContext},
 };
 use aya_log_ebpf::info;
 
@@ -28,6 +28,40 @@ pub struct Ipv6Header {
     pub dst_addr: [u8; 16],
 }
 
+/// IPv4 header structure, without options
+#[repr(C, packed)]
+pub struct Ipv4Header {
+    // Version (4 bits) and header length in 32-bit words (4 bits)
+    pub version_ihl: u8,
+    // Type of service
+    pub tos: u8,
+    // Total length of header and payload
+    pub total_len: u16,
+    // Identification
+    pub id: u16,
+    // Flags (3 bits) and fragment offset (13 bits)
+    pub frag_off: u16,
+    // Time to live
+    pub ttl: u8,
+    // Protocol of the payload
+    pub protocol: u8,
+    // Header checksum
+    pub checksum: u16,
+    // Source address
+    pub src_addr: [u8; 4],
+    // Destination address
+    pub dst_addr: [u8; 4],
+}
+
+/// 802.1Q VLAN tag, following the outer EtherType
+#[repr(C, packed)]
+pub struct VlanHeader {
+    // Priority (3 bits), drop eligible (1 bit), and VLAN ID (12 bits)
+    pub tci: u16,
+    // EtherType of the encapsulated frame
+    pub eth_type: u16,
+}
+
 /// UDP header structure
 #[repr(C, packed)]
 pub struct UdpHeader {
@@ -41,6 +75,53 @@ pub struct UdpHeader {
     pub checksum: u16,
 }
 
+/// TCP header structure, without options
+#[repr(C, packed)]
+pub struct TcpHeader {
+    // Source port
+    pub src_port: u16,
+    // Destination port
+    pub dst_port: u16,
+    // Sequence number
+    pub seq: u32,
+    // Acknowledgment number
+    pub ack_seq: u32,
+    // Data offset in 32-bit words (4 bits), reserved bits, and flags
+    pub doff_flags: u16,
+    // Receive window
+    pub window: u16,
+    // Checksum
+    pub checksum: u16,
+    // Urgent pointer
+    pub urg_ptr: u16,
+}
+
+/// GTP-U header structure, without the optional fields
+#[cfg(feature = "gtpu")]
+#[repr(C, packed)]
+pub struct GtpuHeader {
+    // Version (3 bits), protocol type, reserved, and the E, S and PN flags
+    pub flags: u8,
+    // Message type
+    pub msg_type: u8,
+    // Length of the payload and optional fields
+    pub length: u16,
+    // Tunnel endpoint identifier
+    pub teid: u32,
+}
+
+/// VXLAN header structure
+#[cfg(feature = "vxlan")]
+#[repr(C, packed)]
+pub struct VxlanHeader {
+    // Flags, with the I flag marking a valid VNI
+    pub flags: u8,
+    // Reserved
+    pub reserved: [u8; 3],
+    // VXLAN network identifier (24 bits) and a reserved byte
+    pub vni: [u8; 4],
+}
+
 /// Ethernet header structure
 #[repr(C, packed)]
 pub struct EtherHeader {
@@ -53,23 +134,234 @@ pub struct EtherHeader {
 }
 
 /// Protocol types
+pub const IPPROTO_TCP: u8 = 6;
 pub const IPPROTO_UDP: u8 = 17;
+pub const ETH_P_IP: u16 = 0x0800;
 pub const ETH_P_IPV6: u16 = 0x86DD;
+pub const ETH_P_8021Q: u16 = 0x8100;
+pub const ETH_P_8021AD: u16 = 0x88A8;
 pub const ETH_HDR_SIZE: usize = mem::size_of::<EtherHeader>();
+pub const VLAN_HDR_SIZE: usize = mem::size_of::<VlanHeader>();
+pub const IPV4_HDR_SIZE: usize = mem::size_of::<Ipv4Header>();
 pub const IPV6_HDR_SIZE: usize = mem::size_of::<Ipv6Header>();
 pub const UDP_HDR_SIZE: usize = mem::size_of::<UdpHeader>();
+pub const TCP_HDR_SIZE: usize = mem::size_of::<TcpHeader>();
 
-/// NDN default port
+/// Most VLAN tags parsed before the IP header, enough for QinQ
+const MAX_VLAN_TAGS: usize = 2;
+
+/// Mask of the More Fragments flag and fragment offset in an IPv4 header
+const IPV4_FRAGMENT_MASK: u16 = 0x3fff;
+
+/// NDN default port, unless set in the config map
 pub const NDN_PORT: u16 = 6363;
 
+/// GTP-U user plane port
+#[cfg(feature = "gtpu")]
+pub const GTPU_PORT: u16 = 2152;
+#[cfg(feature = "gtpu")]
+pub const GTPU_HDR_SIZE: usize = mem::size_of::<GtpuHeader>();
+/// GTP-U message type of user data
+#[cfg(feature = "gtpu")]
+const GTPU_G_PDU: u8 = 0xff;
+/// E, S and PN flags, any of which adds the optional GTP-U fields
+#[cfg(feature = "gtpu")]
+const GTPU_OPTIONAL_FLAGS: u8 = 0x07;
+/// Most GTP-U extension headers skipped before the user packet
+#[cfg(feature = "gtpu")]
+const MAX_GTPU_EXTENSIONS: usize = 4;
+
+/// VXLAN port
+#[cfg(feature = "vxlan")]
+pub const VXLAN_PORT: u16 = 4789;
+#[cfg(feature = "vxlan")]
+pub const VXLAN_HDR_SIZE: usize = mem::size_of::<VxlanHeader>();
+/// I flag, set when the VXLAN header carries a valid VNI
+#[cfg(feature = "vxlan")]
+const VXLAN_FLAG_VNI: u8 = 0x08;
+
 /// Wrapper around packet data that allows safe parsing
 pub struct Packet<'a> {
     ctx: &'a XdpContext,
     eth_offset: usize,
-    ipv6_offset: usize,
-    udp_offset: usize,
+    ip_offset: usize,
+    ipv4: bool,
+    tunneled: bool,
+    tcp: bool,
+    l4_offset: usize,
+    data_offset: usize,
+    data_len: usize,
+}
+
+/// Where the headers and NDN packet of a parsed packet are, to parse it
+/// again without walking the headers
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct PacketOffsets {
+    eth_offset: usize,
+    ip_offset: usize,
+    l4_offset: usize,
     data_offset: usize,
     data_len: usize,
+    ipv4: bool,
+    tunneled: bool,
+    tcp: bool,
+}
+
+/// Where the IP header and its payload sit in a packet
+struct IpInfo {
+    ipv4: bool,
+    protocol: u8,
+    ip_offset: usize,
+    l4_offset: usize,
+    end: usize,
+}
+
+impl IpInfo {
+    /// Parse the IPv4 or IPv6 header of EtherType `eth_type` at `ip_offset`
+    #[inline(always)]
+    fn parse(ctx: &XdpContext, eth_type: u16, ip_offset: usize) -> Result<Self, ()> {
+        match eth_type {
+            ETH_P_IPV6 => {
+                // Ensure we can read the IPv6 header
+                let ipv6_ptr = utils::ptr_at::<Ipv6Header>(ctx, ip_offset)?;
+                let ipv6 = unsafe { &*ipv6_ptr };
+                
+                let l4_offset = ip_offset + IPV6_HDR_SIZE;
+                Ok(Self {
+                    ipv4: false,
+                    protocol: ipv6.next_hdr,
+                    ip_offset,
+                    l4_offset,
+                    end: l4_offset + u16::from_be(ipv6.payload_len) as usize,
+                })
+            }
+            ETH_P_IP => {
+                // Ensure we can read the IPv4 header
+                let ipv4_ptr = utils::ptr_at::<Ipv4Header>(ctx, ip_offset)?;
+                let ipv4 = unsafe { &*ipv4_ptr };
+                
+                // Only the first fragment carries the transport header, and
+                // the NDN packet cannot be parsed from it alone
+                if u16::from_be(ipv4.frag_off) & IPV4_FRAGMENT_MASK != 0 {
+                    return Err(());
+                }
+                
+                // Skip any options after the fixed header
+                let header_len = (ipv4.version_ihl & 0x0f) as usize * 4;
+                if header_len < IPV4_HDR_SIZE {
+                    return Err(());
+                }
+                
+                Ok(Self {
+                    ipv4: true,
+                    protocol: ipv4.protocol,
+                    ip_offset,
+                    l4_offset: ip_offset + header_len,
+                    end: ip_offset + u16::from_be(ipv4.total_len) as usize,
+                })
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+/// Skip single (802.1Q) or double (802.1ad QinQ) VLAN tags after an
+/// Ethernet header, returning the inner EtherType and the offset past the tags
+#[inline(always)]
+fn skip_vlan_tags(ctx: &XdpContext, mut eth_type: u16, mut offset: usize) -> Result<(u16, usize), ()> {
+    for _ in 0..MAX_VLAN_TAGS {
+        if eth_type != ETH_P_8021Q && eth_type != ETH_P_8021AD {
+            break;
+        }
+        let vlan_ptr = utils::ptr_at::<VlanHeader>(ctx, offset)?;
+        eth_type = u16::from_be(unsafe { (*vlan_ptr).eth_type });
+        offset += VLAN_HDR_SIZE;
+    }
+    Ok((eth_type, offset))
+}
+
+/// Look inside a GTP-U or VXLAN tunnel, returning the EtherType and offset
+/// of the inner IP header, or None if the packet is not tunneled
+#[cfg(any(feature = "gtpu", feature = "vxlan"))]
+#[inline(always)]
+fn decapsulate(ctx: &XdpContext, ip: &IpInfo) -> Result<Option<(u16, usize)>, ()> {
+    if ip.protocol != IPPROTO_UDP {
+        return Ok(None);
+    }
+    let udp_ptr = utils::ptr_at::<UdpHeader>(ctx, ip.l4_offset)?;
+    let port = u16::from_be(unsafe { (*udp_ptr).dst_port });
+    let tunnel_offset = ip.l4_offset + UDP_HDR_SIZE;
+    
+    #[cfg(feature = "gtpu")]
+    if port == GTPU_PORT {
+        return decapsulate_gtpu(ctx, tunnel_offset).map(Some);
+    }
+    #[cfg(feature = "vxlan")]
+    if port == VXLAN_PORT {
+        return decapsulate_vxlan(ctx, tunnel_offset).map(Some);
+    }
+    Ok(None)
+}
+
+/// Skip a GTP-U header and its extension headers to the user IP packet
+#[cfg(feature = "gtpu")]
+#[inline(always)]
+fn decapsulate_gtpu(ctx: &XdpContext, offset: usize) -> Result<(u16, usize), ()> {
+    let gtpu_ptr = utils::ptr_at::<GtpuHeader>(ctx, offset)?;
+    let gtpu = unsafe { &*gtpu_ptr };
+    
+    // Only version 1 G-PDUs carry user packets
+    if gtpu.flags >> 5 != 1 || gtpu.msg_type != GTPU_G_PDU {
+        return Err(());
+    }
+    
+    let mut inner_offset = offset + GTPU_HDR_SIZE;
+    if gtpu.flags & GTPU_OPTIONAL_FLAGS != 0 {
+        // Sequence number, N-PDU number and the first extension header type
+        let mut next_type = unsafe { *utils::byte_ptr_at(ctx, inner_offset + 3)? };
+        inner_offset += 4;
+        
+        // Each extension header gives its length in 4 byte units and ends
+        // with the type of the next
+        for _ in 0..MAX_GTPU_EXTENSIONS {
+            if next_type == 0 {
+                break;
+            }
+            let len = unsafe { *utils::byte_ptr_at(ctx, inner_offset)? } as usize * 4;
+            if len == 0 {
+                return Err(());
+            }
+            next_type = unsafe { *utils::byte_ptr_at(ctx, inner_offset + len - 1)? };
+            inner_offset += len;
+        }
+        if next_type != 0 {
+            return Err(());
+        }
+    }
+    
+    // No link layer header inside, the IP version tells the protocol
+    let version = unsafe { *utils::byte_ptr_at(ctx, inner_offset)? } >> 4;
+    match version {
+        4 => Ok((ETH_P_IP, inner_offset)),
+        6 => Ok((ETH_P_IPV6, inner_offset)),
+        _ => Err(()),
+    }
+}
+
+/// Skip a VXLAN header and the inner Ethernet frame header
+#[cfg(feature = "vxlan")]
+#[inline(always)]
+fn decapsulate_vxlan(ctx: &XdpContext, offset: usize) -> Result<(u16, usize), ()> {
+    let vxlan_ptr = utils::ptr_at::<VxlanHeader>(ctx, offset)?;
+    if unsafe { (*vxlan_ptr).flags } & VXLAN_FLAG_VNI == 0 {
+        return Err(());
+    }
+    
+    let eth_offset = offset + VXLAN_HDR_SIZE;
+    let eth_ptr = utils::ptr_at::<EtherHeader>(ctx, eth_offset)?;
+    let eth_type = u16::from_be(unsafe { (*eth_ptr).eth_type });
+    skip_vlan_tags(ctx, eth_type, eth_offset + ETH_HDR_SIZE)
 }
 
 impl<'a> Packet<'a> {
@@ -81,35 +373,61 @@ impl<'a> Packet<'a> {
         let eth_ptr = utils::ptr_at::<EtherHeader>(ctx, eth_offset)?;
         let eth = unsafe { &*eth_ptr };
         
-        // Check if this is IPv6
-        if u16::from_be(eth.eth_type) != ETH_P_IPV6 {
-            return Err(());
-        }
-        
-        let ipv6_offset = eth_offset + ETH_HDR_SIZE;
-        
-        // Ensure we can read the IPv6 header
-        let ipv6_ptr = utils::ptr_at::<Ipv6Header>(ctx, ipv6_offset)?;
-        let ipv6 = unsafe { &*ipv6_ptr };
+        let (eth_type, ip_offset) = skip_vlan_tags(ctx, u16::from_be(eth.eth_type), eth_offset + ETH_HDR_SIZE)?;
+        let ip = IpInfo::parse(ctx, eth_type, ip_offset)?;
         
-        // Check if this is UDP
-        if ipv6.next_hdr != IPPROTO_UDP {
-            return Err(());
-        }
-        
-        let udp_offset = ipv6_offset + IPV6_HDR_SIZE;
-        
-        // Ensure we can read the UDP header
-        let udp_ptr = utils::ptr_at::<UdpHeader>(ctx, udp_offset)?;
-        let udp = unsafe { &*udp_ptr };
-        
-        // Check if this is on the NDN port
-        if u16::from_be(udp.dst_port) != NDN_PORT {
-            return Err(());
-        }
+        // With tunnel support built in, the NDN packet may be carried
+        // inside the outer UDP payload
+        #[cfg(any(feature = "gtpu", feature = "vxlan"))]
+        let (ip, tunneled) = match decapsulate(ctx, &ip)? {
+            Some((eth_type, ip_offset)) => (IpInfo::parse(ctx, eth_type, ip_offset)?, true),
+            None => (ip, false),
+        };
+        #[cfg(not(any(feature = "gtpu", feature = "vxlan")))]
+        let tunneled = false;
         
-        let data_offset = udp_offset + UDP_HDR_SIZE;
-        let data_len = u16::from_be(udp.length) as usize - UDP_HDR_SIZE;
+        let ndn_port = crate::config(maps::config::NDN_PORT, NDN_PORT as u32) as u16;
+        let l4_offset = ip.l4_offset;
+        let (tcp, data_offset, data_len) = match ip.protocol {
+            IPPROTO_UDP => {
+                // Ensure we can read the UDP header
+                let udp_ptr = utils::ptr_at::<UdpHeader>(ctx, l4_offset)?;
+                let udp = unsafe { &*udp_ptr };
+                
+                // Check if this is on the NDN port
+                if u16::from_be(udp.dst_port) != ndn_port {
+                    return Err(());
+                }
+                
+                let data_offset = l4_offset + UDP_HDR_SIZE;
+                let data_len = (u16::from_be(udp.length) as usize).checked_sub(UDP_HDR_SIZE).ok_or(())?;
+                if data_offset + data_len > ip.end {
+                    return Err(());
+                }
+                (false, data_offset, data_len)
+            }
+            #[cfg(feature = "tcp")]
+            IPPROTO_TCP => {
+                // Ensure we can read the TCP header
+                let tcp_ptr = utils::ptr_at::<TcpHeader>(ctx, l4_offset)?;
+                let tcp = unsafe { &*tcp_ptr };
+                
+                // Streams to and from the NDN port
+                if u16::from_be(tcp.dst_port) != ndn_port && u16::from_be(tcp.src_port) != ndn_port {
+                    return Err(());
+                }
+                
+                // Skip any options after the fixed header
+                let header_len = (u16::from_be(tcp.doff_flags) >> 12) as usize * 4;
+                if header_len < TCP_HDR_SIZE {
+                    return Err(());
+                }
+                
+                let data_offset = l4_offset + header_len;
+                (true, data_offset, ip.end.checked_sub(data_offset).ok_or(())?)
+            }
+            _ => return Err(()),
+        };
         
         // Ensure data isn't larger than packet
         let packet_end = ctx.data() + ctx.data_end();
@@ -120,28 +438,112 @@ impl<'a> Packet<'a> {
         Ok(Self {
             ctx,
             eth_offset,
-            ipv6_offset,
-            udp_offset,
+            ip_offset: ip.ip_offset,
+            ipv4: ip.ipv4,
+            tunneled,
+            tcp,
+            l4_offset,
             data_offset,
             data_len,
         })
     }
     
+    /// Where the headers were found, for a later stage to pick up
+    pub fn offsets(&self) -> PacketOffsets {
+        PacketOffsets {
+            eth_offset: self.eth_offset,
+            ip_offset: self.ip_offset,
+            l4_offset: self.l4_offset,
+            data_offset: self.data_offset,
+            data_len: self.data_len,
+            ipv4: self.ipv4,
+            tunneled: self.tunneled,
+            tcp: self.tcp,
+        }
+    }
+    
+    /// A packet parsed earlier, at `offsets`
+    pub fn from_offsets(ctx: &'a XdpContext, offsets: PacketOffsets) -> Self {
+        Self {
+            ctx,
+            eth_offset: offsets.eth_offset,
+            ip_offset: offsets.ip_offset,
+            ipv4: offsets.ipv4,
+            tunneled: offsets.tunneled,
+            tcp: offsets.tcp,
+            l4_offset: offsets.l4_offset,
+            data_offset: offsets.data_offset,
+            data_len: offsets.data_len,
+        }
+    }
+    
+    /// Whether the packet arrived over IPv4 rather than IPv6
+    pub fn is_ipv4(&self) -> bool {
+        self.ipv4
+    }
+    
+    /// Whether the packet was found inside a GTP-U or VXLAN tunnel
+    pub fn is_tunneled(&self) -> bool {
+        self.tunneled
+    }
+    
+    /// Whether the kernel can rewrite the packet in place, to answer it or
+    /// send it on: it only builds plain IPv6 headers
+    pub fn is_rewritable(&self) -> bool {
+        !self.ipv4 && !self.tunneled
+    }
+    
+    /// Offset of the IP header, after any VLAN tags
+    pub fn ip_offset(&self) -> usize {
+        self.ip_offset
+    }
+    
+    /// Whether the packet is a segment of an NDN-over-TCP stream, whose
+    /// payload need not start at an NDN packet
+    pub fn is_tcp(&self) -> bool {
+        self.tcp
+    }
+    
+    /// Offset of the UDP header
+    pub fn udp_offset(&self) -> usize {
+        self.l4_offset
+    }
+    
+    /// Offset of the NDN packet in the UDP payload
+    pub fn data_offset(&self) -> usize {
+        self.data_offset
+    }
+    
     /// Get ethernet header
     pub fn eth_header(&self) -> Result<&EtherHeader, ()> {
         let eth_ptr = utils::ptr_at::<EtherHeader>(self.ctx, self.eth_offset)?;
         Ok(unsafe { &*eth_ptr })
     }
     
+    /// Get IPv4 header
+    pub fn ipv4_header(&self) -> Result<&Ipv4Header, ()> {
+        if !self.ipv4 {
+            return Err(());
+        }
+        let ipv4_ptr = utils::ptr_at::<Ipv4Header>(self.ctx, self.ip_offset)?;
+        Ok(unsafe { &*ipv4_ptr })
+    }
+    
     /// Get IPv6 header
     pub fn ipv6_header(&self) -> Result<&Ipv6Header, ()> {
-        let ipv6_ptr = utils::ptr_at::<Ipv6Header>(self.ctx, self.ipv6_offset)?;
+        if self.ipv4 {
+            return Err(());
+        }
+        let ipv6_ptr = utils::ptr_at::<Ipv6Header>(self.ctx, self.ip_offset)?;
         Ok(unsafe { &*ipv6_ptr })
     }
     
     /// Get UDP header
     pub fn udp_header(&self) -> Result<&UdpHeader, ()> {
-        let udp_ptr = utils::ptr_at::<UdpHeader>(self.ctx, self.udp_offset)?;
+        if self.tcp {
+            return Err(());
+        }
+        let udp_ptr = utils::ptr_at::<UdpHeader>(self.ctx, self.l4_offset)?;
         Ok(unsafe { &*udp_ptr })
     }
     
@@ -176,53 +578,251 @@ pub fn parse_ndn_packet(packet: &Packet) -> Result<ndn::PacketType, ()> {
     }
 }
 
-/// Extract a hash of the name from an NDN packet
-/// This is a simplified version that just grabs a few bytes from where
-/// the name would be and computes a hash from them
-pub fn extract_name_hash(packet: &Packet) -> Option<u32> {
-    let data_ptr = match packet.data_ptr() {
-        Ok(ptr) => ptr,
-        Err(_) => return None,
-    };
-    
-    let data_len = packet.data_len();
-    
-    if data_len < 10 {
+/// Byte access to the packet a program runs on, so the TLV parsing is
+/// shared by the XDP and TC programs
+pub trait PacketBytes {
+    /// Read the byte at `offset` in the packet
+    fn byte_at(&self, offset: usize) -> Option<u8>;
+}
+
+impl PacketBytes for XdpContext {
+    #[inline(always)]
+    fn byte_at(&self, offset: usize) -> Option<u8> {
+        Some(unsafe { *utils::byte_ptr_at(self, offset).ok()? })
+    }
+}
+
+impl PacketBytes for TcContext {
+    #[inline(always)]
+    fn byte_at(&self, offset: usize) -> Option<u8> {
+        self.load::<u8>(offset).ok()
+    }
+}
+
+impl PacketBytes for maps::CsData {
+    #[inline(always)]
+    fn byte_at(&self, offset: usize) -> Option<u8> {
+        if offset >= self.len as usize {
+            return None;
+        }
+        self.bytes.get(offset).copied()
+    }
+}
+
+/// Read a TLV length at `offset`, returning the length and the size of
+/// its encoding
+#[inline(always)]
+fn read_tlv_length<C: PacketBytes>(ctx: &C, offset: usize) -> Option<(usize, usize)> {
+    let first = ctx.byte_at(offset)?;
+    match first {
+        0..=252 => Some((first as usize, 1)),
+        253 => {
+            let high = ctx.byte_at(offset + 1)?;
+            let low = ctx.byte_at(offset + 2)?;
+            Some((((high as usize) << 8) | low as usize, 3))
+        }
+        // Longer lengths cannot occur within a frame
+        _ => None,
+    }
+}
+
+/// Walk the Name TLV at the start of an Interest or Data, hashing it
+/// component by component
+///
+/// Each byte of the encoded components is fed to FNV-1a in a single
+/// bounded loop, and the running hash is recorded at the end of every
+/// component, giving the hash of each prefix along with the full name.
+pub fn parse_name(packet: &Packet) -> Option<ndn::NameHashes> {
+    hash_name(packet.ctx, packet.data_offset)
+}
+
+/// Find the encoded components of the name of the Interest or Data at
+/// `data_offset`, returning their offset and length
+#[inline(always)]
+pub fn name_components<C: PacketBytes>(ctx: &C, data_offset: usize) -> Option<(usize, usize)> {
+    // Skip the type and length of the Interest or Data
+    let (_, outer_len_size) = read_tlv_length(ctx, data_offset + 1)?;
+    let name_offset = data_offset + 1 + outer_len_size;
+
+    if ctx.byte_at(name_offset)? != ndn::TLV_NAME {
         return None;
     }
-    
-    // Skip packet type (1 byte) and length (1 byte) to get to name TLV
-    // This is a simplification; real parser would navigate the TLV structure
-    let name_ptr = unsafe { data_ptr.add(2) };
-    
-    // Read 4 bytes from after the name TLV type and length
-    // Again, this is a simplification
-    let name_hash_ptr = unsafe { name_ptr.add(2) as *const u32 };
-    let name_hash = unsafe { *name_hash_ptr };
-    
-    Some(name_hash)
+    let (name_len, name_len_size) = read_tlv_length(ctx, name_offset + 1)?;
+    if name_len > ndn::MAX_PARSED_NAME_LENGTH {
+        return None;
+    }
+    Some((name_offset + 1 + name_len_size, name_len))
 }
 
-/// Extract the nonce from an Interest packet
-pub fn extract_nonce(packet: &Packet) -> Option<u32> {
-    let data_ptr = match packet.data_ptr() {
-        Ok(ptr) => ptr,
-        Err(_) => return None,
-    };
-    
-    let data_len = packet.data_len();
-    
-    if data_len < 15 {
+/// Hash the name of the Interest or Data at `data_offset`, see `parse_name`
+#[inline(always)]
+pub fn hash_name<C: PacketBytes>(ctx: &C, data_offset: usize) -> Option<ndn::NameHashes> {
+    let (start, name_len) = name_components(ctx, data_offset)?;
+    let end = start + name_len;
+
+    let mut hashes = ndn::NameHashes::new();
+    let mut hash = ndn::FNV_OFFSET_BASIS;
+    let mut component_end = start;
+
+    let mut i = 0;
+    while i < ndn::MAX_PARSED_NAME_LENGTH {
+        let offset = start + i;
+        if offset >= end {
+            break;
+        }
+
+        // A component starts here: find out where it ends
+        if offset == component_end {
+            let (len, len_size) = read_tlv_length(ctx, offset + 1)?;
+            component_end = offset + 1 + len_size + len;
+            if component_end > end {
+                return None;
+            }
+        }
+
+        let byte = ctx.byte_at(offset)?;
+        hash = ndn::fnv1a(hash, byte);
+
+        if offset + 1 == component_end {
+            hashes.push_prefix(hash);
+        }
+        i += 1;
+    }
+
+    // The last component ran past the end of the packet
+    if component_end != end {
         return None;
     }
-    
-    // Skip to where the nonce might be
-    // In a real implementation, this would parse the TLV structure
-    // This is a simplified version that assumes a fixed offset
-    let nonce_ptr = unsafe { data_ptr.add(10) as *const u32 };
-    let nonce = unsafe { *nonce_ptr };
-    
-    Some(nonce)
+
+    hashes.full = hash;
+    hashes.components_offset = start;
+    hashes.components_len = name_len;
+    Some(hashes)
+}
+
+/// Fields of an Interest after its name that the kernel acts on
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct InterestFields {
+    /// Nonce of the Interest
+    pub nonce: Option<u32>,
+    /// Packet offset of the one byte HopLimit value, if present
+    pub hop_limit_offset: Option<usize>,
+    /// Whether only fresh Data may satisfy the Interest
+    pub must_be_fresh: bool,
+}
+
+impl InterestFields {
+    /// Read the HopLimit, if the Interest carries one
+    pub fn hop_limit(&self, packet: &Packet) -> Option<u8> {
+        let offset = self.hop_limit_offset?;
+        Some(unsafe { *utils::byte_ptr_at(packet.ctx, offset).ok()? })
+    }
+}
+
+/// Extract the Nonce, HopLimit and MustBeFresh of an Interest packet
+///
+/// Walks the TLVs following the name, whose hashes `parse_name` returned,
+/// up to the ApplicationParameters, which come after both.
+pub fn extract_interest_fields(packet: &Packet, name: &ndn::NameHashes) -> InterestFields {
+    let ctx = packet.ctx;
+    let end = packet.data_offset + packet.data_len;
+    let mut offset = name.components_offset + name.components_len;
+    let mut fields = InterestFields {
+        nonce: None,
+        hop_limit_offset: None,
+        must_be_fresh: false,
+    };
+
+    let mut i = 0;
+    while i < ndn::MAX_INTEREST_FIELDS {
+        if offset >= end {
+            break;
+        }
+
+        let tlv_type = match utils::byte_ptr_at(ctx, offset) {
+            Ok(ptr) => unsafe { *ptr },
+            Err(_) => break,
+        };
+        let (len, len_size) = match read_tlv_length(ctx, offset + 1) {
+            Some(length) => length,
+            None => break,
+        };
+        let value = offset + 1 + len_size;
+
+        match tlv_type {
+            ndn::TLV_NONCE if len == 4 => {
+                let mut nonce: u32 = 0;
+                let mut j = 0;
+                while j < 4 {
+                    match utils::byte_ptr_at(ctx, value + j) {
+                        Ok(ptr) => nonce = (nonce << 8) | unsafe { *ptr } as u32,
+                        Err(_) => return fields,
+                    }
+                    j += 1;
+                }
+                fields.nonce = Some(nonce);
+            }
+            ndn::TLV_HOP_LIMIT if len == 1 => fields.hop_limit_offset = Some(value),
+            // CanBePrefix, then MustBeFresh
+            ndn::TLV_SELECTORS if len >= 2 => match utils::byte_ptr_at(ctx, value + 1) {
+                Ok(ptr) => fields.must_be_fresh = unsafe { *ptr } != 0,
+                Err(_) => return fields,
+            },
+            ndn::TLV_APPLICATION_PARAMETERS => break,
+            _ => {}
+        }
+
+        offset = value + len;
+        i += 1;
+    }
+
+    fields
+}
+
+/// Read the FreshnessPeriod of a Data packet, see `data_freshness`
+pub fn parse_freshness(packet: &Packet, name: &ndn::NameHashes) -> Option<u32> {
+    data_freshness(packet.ctx, name, packet.data_offset + packet.data_len)
+}
+
+/// Read the FreshnessPeriod of the Data whose name `hash_name` returned,
+/// if it carries one ahead of its content
+#[inline(always)]
+pub fn data_freshness<C: PacketBytes>(ctx: &C, name: &ndn::NameHashes, end: usize) -> Option<u32> {
+    let mut offset = name.components_offset + name.components_len;
+
+    let mut i = 0;
+    while i < ndn::MAX_DATA_FIELDS {
+        if offset >= end {
+            return None;
+        }
+        let tlv_type = ctx.byte_at(offset)?;
+        let (len, len_size) = read_tlv_length(ctx, offset + 1)?;
+        let value = offset + 1 + len_size;
+
+        match tlv_type {
+            ndn::TLV_FRESHNESS_PERIOD if len == 1 || len == 2 || len == 4 || len == 8 => {
+                // A nonNegativeInteger, saturated to 32 bits
+                let mut period: u64 = 0;
+                let mut j = 0;
+                while j < 8 {
+                    if j >= len {
+                        break;
+                    }
+                    period = (period << 8) | ctx.byte_at(value + j)? as u64;
+                    j += 1;
+                }
+                return Some(if period > u32::MAX as u64 { u32::MAX } else { period as u32 });
+            }
+            ndn::TLV_CONTENT => return None,
+            _ => {}
+        }
+
+        offset = value + len;
+        i += 1;
+    }
+
+    None
 }
 
 /// Extract a face ID based on interface and addresses
@@ -0,0 +1,44 @@
+//! Tail call pipeline of the XDP program.
+//!
+//! `ndn_xdp` parses each packet and tail calls the stage for its type, and
+//! the Interest stage tail calls forwarding. Keeping the stages apart keeps
+//! each of them within the verifier's limits, and lets userspace swap one
+//! out at runtime. Stages hand over what they found through a per-CPU
+//! scratch entry, which a tail call keeps since it runs on the same CPU.
+
+use aya_ebpf::maps::PerCpuArray;
+
+use crate::maps::FaceId;
+use crate::parser::{InterestFields, PacketOffsets};
+
+/// Indices of the stages in the PIPELINE program array
+pub const STAGE_INTEREST: u32 = 0;
+pub const STAGE_DATA: u32 = 1;
+pub const STAGE_FORWARD: u32 = 2;
+
+/// Number of stages
+pub const STAGE_COUNT: u32 = 3;
+
+/// What a stage hands to the next
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct PipelineState {
+    /// Where the headers of the packet are
+    pub packet: PacketOffsets,
+    /// Interest fields found by the Interest stage
+    pub fields: InterestFields,
+    /// Face the Interest stage chose to forward to
+    pub face_id: FaceId,
+}
+
+// Reference to the scratch map
+extern "C" {
+    #[link_name = "PIPELINE_STATE"]
+    static mut PIPELINE_STATE: PerCpuArray<PipelineState>;
+}
+
+/// The state of the packet being processed on this CPU
+#[inline(always)]
+pub fn state() -> Result<*mut PipelineState, ()> {
+    unsafe { PIPELINE_STATE.get_ptr_mut(0).ok_or(()) }
+}
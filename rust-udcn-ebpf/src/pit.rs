@@ -5,20 +5,23 @@
 
 use aya_ebpf::maps::LruHashMap;
 
-use crate::maps::{PitKey, PitValue};
+use crate::maps::{DeadNonceKey, PitKey, PitValue, MAX_PIT_NONCES};
 use crate::utils;
 use crate::maps::metrics;
 
-/// Max number of faces to check when finding matching interests
-const MAX_FACE_CHECK: usize = 8;
+/// How long the nonce of a satisfied Interest is remembered, in milliseconds
+const DEAD_NONCE_LIFETIME_MS: u64 = 6000;
 
 // Reference to the PIT table map
 extern "C" {
     #[link_name = "PIT_TABLE"]
     static mut PIT_TABLE: LruHashMap<PitKey, PitValue>;
+
+    #[link_name = "DEAD_NONCES"]
+    static mut DEAD_NONCES: LruHashMap<DeadNonceKey, u64>;
     
     #[link_name = "METRICS"]
-    static mut METRICS: aya_ebpf::maps::HashMap<u32, u64>;
+    static mut METRICS: aya_ebpf::maps::PerCpuArray<u64>;
 }
 
 /// Clean up expired PIT entries.
@@ -38,39 +41,74 @@ pub fn cleanup_expired_entries() -> Result<u32, ()> {
 
 /// Find matching PIT entries for a Data packet.
 ///
-/// Given a name hash, find all PIT entries with the same name hash
-/// and return whether any match was found.
-pub fn find_matching_interests(name_hash: u32) -> Result<bool, ()> {
-    let mut found_match = false;
-    
-    // Since iterating over eBPF maps is tricky, we'll simulate it with a simplified approach
-    // We'll loop through a few potential PIT keys with varying nonces
-    for nonce in 0..MAX_FACE_CHECK {
-        let key = PitKey {
-            name_hash,
-            name_len: 0, // Simplified for now
-            nonce: nonce as u32,
+/// Given a name hash and length, find the PIT entry for the same name
+/// and return whether a match was found.
+pub fn find_matching_interests(name_hash: u32, name_len: u8) -> Result<bool, ()> {
+    let key = PitKey { name_hash, name_len };
+
+    unsafe {
+        let value = match PIT_TABLE.get(&key) {
+            Some(value) => *value,
+            None => return Ok(false),
         };
-        
-        unsafe {
-            if let Some(_value) = PIT_TABLE.get(&key) {
-                found_match = true;
-                
-                // Remove the matching PIT entry since it's been satisfied
-                PIT_TABLE.remove(&key).unwrap_or(());
-                
-                // Increment PIT match counter
-                if let Some(counter) = METRICS.get_ptr_mut(&metrics::PIT_MATCHES) {
-                    *counter += 1;
-                }
-                
-                // In a real implementation, we would check all faces that requested
-                // this Interest and forward the Data to them
+
+        // Remove the PIT entry since it's been satisfied, or its Interests
+        // were given up on, remembering their nonces to catch them if they
+        // loop
+        PIT_TABLE.remove(&key).unwrap_or(());
+        add_dead_nonces(&key, &value);
+
+        // The Interest of an expired entry no longer asks for this Data
+        if is_pit_entry_expired(&value) {
+            return Ok(false);
+        }
+
+        // Increment PIT match counter
+        if let Some(counter) = METRICS.get_ptr_mut(metrics::PIT_MATCHES) {
+            *counter += 1;
+        }
+
+        // In a real implementation, we would check all faces that requested
+        // this Interest and forward the Data to them
+    }
+
+    Ok(true)
+}
+
+/// Add the nonces of the Interests in a PIT entry to the dead nonce list.
+fn add_dead_nonces(key: &PitKey, value: &PitValue) {
+    let now = utils::get_timestamp();
+    let mut i = 0;
+    while i < MAX_PIT_NONCES {
+        if i < value.nonce_count as usize {
+            let dead = DeadNonceKey {
+                name_hash: key.name_hash,
+                name_len: key.name_len,
+                nonce: value.nonces[i],
+            };
+            unsafe {
+                DEAD_NONCES.insert(&dead, &now, 0).unwrap_or(());
             }
         }
+        i += 1;
+    }
+}
+
+/// Check if an Interest's name and nonce are in the dead nonce list.
+///
+/// A match means the Interest was satisfied recently and has come back
+/// around a loop. Stale entries are removed on the way.
+pub fn is_dead_nonce(key: &DeadNonceKey) -> bool {
+    unsafe {
+        match DEAD_NONCES.get(key) {
+            Some(added) if !utils::is_expired(*added, DEAD_NONCE_LIFETIME_MS) => true,
+            Some(_) => {
+                DEAD_NONCES.remove(key).unwrap_or(());
+                false
+            }
+            None => false,
+        }
     }
-    
-    Ok(found_match)
 }
 
 /// Check if a PIT entry has expired.
@@ -78,8 +116,7 @@ pub fn find_matching_interests(name_hash: u32) -> Result<bool, ()> {
 /// An entry is expired if the current time is greater than the creation time
 /// plus the lifetime.
 pub fn is_pit_entry_expired(entry: &PitValue) -> bool {
-    let current_time = utils::get_timestamp();
-    current_time > entry.timestamp + entry.lifetime_ms as u64
+    utils::is_expired(entry.timestamp, entry.lifetime_ms as u64)
 }
 
 /// Add a new PIT entry.
@@ -109,10 +146,10 @@ pub fn has_duplicate(key: &PitKey) -> bool {
 
 /// Wrapper for [`find_matching_interests`].
 ///
-/// Returns `true` if a Data packet with the provided name hash matches
-/// an Interest in the PIT.
-pub fn has_matching_interest(name_hash: u32) -> Result<bool, ()> {
-    find_matching_interests(name_hash)
+/// Returns `true` if a Data packet with the provided name hash and length
+/// matches an Interest in the PIT.
+pub fn has_matching_interest(name_hash: u32, name_len: u8) -> Result<bool, ()> {
+    find_matching_interests(name_hash, name_len)
 }
 
 /// Get the face ID for a PIT entry.
@@ -0,0 +1,74 @@
+//! Interest rate limiting.
+//!
+//! This module keeps a token bucket per ingress interface and name prefix,
+//! so a flood of Interests under one prefix cannot fill the PIT.
+
+use aya_ebpf::maps::LruHashMap;
+
+use crate::maps::{config, RateLimitKey, TokenBucket};
+use crate::ndn::{NameHashes, FNV_OFFSET_BASIS, MAX_NAME_COMPONENTS};
+use crate::utils;
+
+/// Components of the prefix buckets are kept for, unless configured
+const DEFAULT_PREFIX_LEN: u32 = 1;
+
+/// Tokens are counted in thousandths, so a bucket refills smoothly at
+/// rates below one Interest per millisecond
+const TOKEN_SCALE: u64 = 1000;
+
+// Reference to the token bucket map
+extern "C" {
+    #[link_name = "RATE_LIMITS"]
+    static mut RATE_LIMITS: LruHashMap<RateLimitKey, TokenBucket>;
+}
+
+/// Take a token for an Interest arriving on `ifindex`.
+///
+/// Returns false if the bucket of the Interest's prefix is empty. Every
+/// Interest is admitted while userspace has not set a rate.
+pub fn admit(ifindex: u32, name: &NameHashes) -> bool {
+    let rate = crate::config(config::RATE_LIMIT_PPS, 0) as u64;
+    if rate == 0 {
+        return true;
+    }
+    let burst = crate::config(config::RATE_LIMIT_BURST, rate as u32) as u64 * TOKEN_SCALE;
+
+    // Bucket by the first components of the name, or the whole name if shorter
+    let prefix_len = crate::config(config::RATE_LIMIT_PREFIX_LEN, DEFAULT_PREFIX_LEN) as usize;
+    let prefix_len = prefix_len.min(name.len as usize).min(MAX_NAME_COMPONENTS);
+    let prefix_hash = match prefix_len {
+        0 => FNV_OFFSET_BASIS,
+        len => name.prefixes[len - 1],
+    };
+    let key = RateLimitKey { ifindex, prefix_hash };
+    let now = utils::get_timestamp();
+
+    let bucket = match unsafe { RATE_LIMITS.get_ptr_mut(&key) } {
+        Some(bucket) => bucket,
+        None => {
+            // A new prefix starts with a full bucket, less this Interest
+            let bucket = TokenBucket {
+                tokens: burst.saturating_sub(TOKEN_SCALE),
+                last_refill: now,
+            };
+            unsafe {
+                let _ = RATE_LIMITS.insert(&key, &bucket, 0);
+            }
+            return true;
+        }
+    };
+
+    unsafe {
+        // `rate` tokens per second is `rate` thousandths per millisecond
+        let elapsed = now.saturating_sub((*bucket).last_refill);
+        let tokens = ((*bucket).tokens + elapsed * rate).min(burst);
+        (*bucket).last_refill = now;
+
+        if tokens < TOKEN_SCALE {
+            (*bucket).tokens = tokens;
+            return false;
+        }
+        (*bucket).tokens = tokens - TOKEN_SCALE;
+    }
+    true
+}
@@ -80,14 +80,53 @@ pub fn check_bounds(ctx: &XdpContext, start: usize, len: usize) -> Result<(), ()
 
 /// Get the current timestamp in milliseconds.
 ///
-/// This is a simplified version that just returns the current
-/// monotonic clock value from bpf_ktime_get_ns() divided by 1_000_000.
+/// This is CLOCK_MONOTONIC time from `bpf_ktime_get_ns()`: milliseconds
+/// since boot, not counting suspend. All PIT and CS timestamps use it, and
+/// userspace reads the same clock to set and convert them.
 #[inline]
 pub fn get_timestamp() -> u64 {
     // Safety: bpf_ktime_get_ns is always safe to call in eBPF programs
     unsafe { aya_bpf::helpers::bpf_ktime_get_ns() / 1_000_000 }
 }
 
+/// Check whether `lifetime_ms` has passed since `timestamp`.
+///
+/// A timestamp slightly in the future, written by another CPU or by
+/// userspace after this program read the clock, counts as just created
+/// rather than wrapping around.
+#[inline]
+pub fn is_expired(timestamp: u64, lifetime_ms: u64) -> bool {
+    get_timestamp().saturating_sub(timestamp) > lifetime_ms
+}
+
+/// Fold a 32-bit one's complement sum into 16 bits.
+#[inline]
+pub fn csum_fold(mut sum: u32) -> u16 {
+    sum = (sum & 0xffff) + (sum >> 16);
+    sum = (sum & 0xffff) + (sum >> 16);
+    sum as u16
+}
+
+/// Update a checksum after words summing to `old` were replaced by words
+/// summing to `new` (RFC 1624).
+#[inline]
+pub fn csum_replace(check: u16, old: u32, new: u32) -> u16 {
+    let sum = (!check) as u32 + (!csum_fold(old)) as u32 + csum_fold(new) as u32;
+    !csum_fold(sum)
+}
+
+/// One's complement sum of the big-endian 16-bit words of an address.
+#[inline]
+pub fn csum_addr(addr: &[u8; 16]) -> u32 {
+    let mut sum: u32 = 0;
+    let mut i = 0;
+    while i < 16 {
+        sum += ((addr[i] as u32) << 8) | (addr[i + 1] as u32);
+        i += 2;
+    }
+    sum
+}
+
 /// Extract a u16 from two bytes in network byte order (big endian).
 #[inline]
 pub fn extract_be_u16(data: &[u8], offset: usize) -> Option<u16> {
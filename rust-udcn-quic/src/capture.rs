@@ -0,0 +1,308 @@
+//! Capturing NDN packets off a network interface.
+//!
+//! A [`Capture`] reads every frame of an interface through an AF_PACKET
+//! socket, the way tcpdump does, and picks out the NDN packets: those in
+//! Ethernet frames with the NDN EtherType, and those in UDP datagrams to or
+//! from port 6363 over IPv4 or IPv6. Packets the XDP program answers,
+//! redirects or drops never reach the socket, so they do not show up.
+//! Opening the socket requires CAP_NET_RAW.
+
+use bytes::{Bytes, BytesMut};
+use log::debug;
+use std::{
+    ffi::CString,
+    io, mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+use tokio::io::unix::AsyncFd;
+
+use crate::{
+    error::TransportError,
+    ether::{format_mac, NDN_ETHERTYPE},
+    packet::{split_stream_packet, NdnPacket},
+    udp::NDN_UDP_PORT,
+};
+
+const ETH_HDR_SIZE: usize = 14;
+const ETH_P_ALL: u16 = 0x0003;
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86DD;
+const ETH_P_8021Q: u16 = 0x8100;
+const IPV6_HDR_SIZE: usize = 40;
+const UDP_HDR_SIZE: usize = 8;
+const IPPROTO_UDP: u8 = 17;
+
+/// Largest frame read, with room for offloaded segments
+const CAPTURE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Where a captured NDN packet came from and went to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoints {
+    /// Straight over Ethernet, between MAC addresses
+    Ethernet { source: [u8; 6], destination: [u8; 6] },
+
+    /// Over UDP, between socket addresses
+    Udp { source: SocketAddr, destination: SocketAddr },
+}
+
+impl std::fmt::Display for Endpoints {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoints::Ethernet { source, destination } => {
+                write!(f, "{} > {}", format_mac(source), format_mac(destination))
+            }
+            Endpoints::Udp { source, destination } => write!(f, "{} > {}", source, destination),
+        }
+    }
+}
+
+/// An NDN packet seen on the interface
+#[derive(Debug, Clone)]
+pub struct CapturedPacket {
+    /// Whether this node sent the packet rather than received it
+    pub outgoing: bool,
+
+    /// Who sent it to whom
+    pub endpoints: Endpoints,
+
+    /// Encoded size of the NDN packet
+    pub size: usize,
+
+    /// The decoded packet
+    pub packet: NdnPacket,
+}
+
+/// Captures the NDN packets of one interface
+pub struct Capture {
+    /// Non-blocking packet socket bound to the interface
+    socket: AsyncFd<OwnedFd>,
+
+    /// Interface captured on
+    interface: String,
+}
+
+impl Capture {
+    /// Open a packet socket capturing every frame of `interface`
+    pub fn open(interface: &str) -> Result<Self, TransportError> {
+        let failed = |source: io::Error| TransportError::Capture {
+            interface: interface.to_string(),
+            source,
+        };
+        let name = CString::new(interface).map_err(|e| failed(e.into()))?;
+        // SAFETY: name is a valid NUL-terminated string
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(failed(io::Error::new(io::ErrorKind::NotFound, "unknown network interface")));
+        }
+
+        // SAFETY: plain socket(2) call, the descriptor is owned right away
+        let socket = unsafe {
+            let fd = libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                ETH_P_ALL.to_be() as libc::c_int,
+            );
+            if fd < 0 {
+                let e = io::Error::last_os_error();
+                return Err(failed(io::Error::new(
+                    e.kind(),
+                    format!("cannot open packet socket, CAP_NET_RAW required ({})", e),
+                )));
+            }
+            OwnedFd::from_raw_fd(fd)
+        };
+
+        // SAFETY: sockaddr_ll is plain old data, all zeroes is a valid value
+        let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as u16;
+        addr.sll_protocol = ETH_P_ALL.to_be();
+        addr.sll_ifindex = ifindex as i32;
+        // SAFETY: addr is a valid sockaddr_ll of the given size
+        let ret = unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(failed(io::Error::last_os_error()));
+        }
+
+        Ok(Self {
+            socket: AsyncFd::new(socket).map_err(failed)?,
+            interface: interface.to_string(),
+        })
+    }
+
+    /// Interface captured on
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    /// Wait for the next NDN packet, skipping other traffic
+    pub async fn next(&self) -> io::Result<CapturedPacket> {
+        let mut buf = BytesMut::zeroed(CAPTURE_BUFFER_SIZE);
+
+        loop {
+            let mut guard = self.socket.readable().await?;
+            let result = guard.try_io(|socket| {
+                // SAFETY: sockaddr_ll is plain old data, all zeroes is a valid value
+                let mut from: libc::sockaddr_ll = unsafe { mem::zeroed() };
+                let mut from_len = mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+                // SAFETY: buf and from are valid for writes of their full length
+                let len = unsafe {
+                    libc::recvfrom(
+                        socket.as_raw_fd(),
+                        buf.as_mut_ptr() as *mut libc::c_void,
+                        buf.len(),
+                        0,
+                        &mut from as *mut libc::sockaddr_ll as *mut libc::sockaddr,
+                        &mut from_len,
+                    )
+                };
+                if len < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok((len as usize, from.sll_pkttype == libc::PACKET_OUTGOING))
+            });
+            let (len, outgoing) = match result {
+                Ok(received) => received?,
+                Err(_would_block) => continue,
+            };
+
+            let Some((endpoints, payload)) = dissect_frame(&buf[..len]) else {
+                continue;
+            };
+            match NdnPacket::from_bytes(&payload) {
+                Ok(packet) => {
+                    return Ok(CapturedPacket {
+                        outgoing,
+                        endpoints,
+                        size: payload.len(),
+                        packet,
+                    })
+                }
+                Err(e) => debug!("Skipping undecodable NDN packet from {}: {}", endpoints, e),
+            }
+        }
+    }
+}
+
+/// Find the NDN packet in an Ethernet frame, if it carries one
+pub(crate) fn dissect_frame(frame: &[u8]) -> Option<(Endpoints, Bytes)> {
+    if frame.len() < ETH_HDR_SIZE {
+        return None;
+    }
+    let destination: [u8; 6] = frame[0..6].try_into().ok()?;
+    let source: [u8; 6] = frame[6..12].try_into().ok()?;
+    let mut ethertype = u16::from_be_bytes([frame[12], frame[13]]);
+    let mut offset = ETH_HDR_SIZE;
+    if ethertype == ETH_P_8021Q {
+        ethertype = u16::from_be_bytes([*frame.get(16)?, *frame.get(17)?]);
+        offset += 4;
+    }
+    let payload = frame.get(offset..)?;
+
+    let (endpoints, ndn) = match ethertype {
+        NDN_ETHERTYPE => (Endpoints::Ethernet { source, destination }, payload),
+        ETH_P_IP => {
+            if payload.len() < 20 || payload[9] != IPPROTO_UDP {
+                return None;
+            }
+            let header_len = (payload[0] & 0x0f) as usize * 4;
+            let source = IpAddr::V4(Ipv4Addr::new(payload[12], payload[13], payload[14], payload[15]));
+            let destination = IpAddr::V4(Ipv4Addr::new(payload[16], payload[17], payload[18], payload[19]));
+            dissect_udp(source, destination, payload.get(header_len..)?)?
+        }
+        ETH_P_IPV6 => {
+            if payload.len() < IPV6_HDR_SIZE || payload[6] != IPPROTO_UDP {
+                return None;
+            }
+            let source: [u8; 16] = payload[8..24].try_into().ok()?;
+            let destination: [u8; 16] = payload[24..40].try_into().ok()?;
+            dissect_udp(
+                IpAddr::V6(Ipv6Addr::from(source)),
+                IpAddr::V6(Ipv6Addr::from(destination)),
+                &payload[IPV6_HDR_SIZE..],
+            )?
+        }
+        _ => return None,
+    };
+
+    // Short frames are padded, so trim to the TLV length
+    let mut packet = BytesMut::from(ndn);
+    let packet = split_stream_packet(&mut packet).ok()??;
+    Some((endpoints, packet))
+}
+
+/// Find the NDN packet in a UDP datagram, if it is to or from the NDN port
+fn dissect_udp(source: IpAddr, destination: IpAddr, datagram: &[u8]) -> Option<(Endpoints, &[u8])> {
+    if datagram.len() < UDP_HDR_SIZE {
+        return None;
+    }
+    let source_port = u16::from_be_bytes([datagram[0], datagram[1]]);
+    let destination_port = u16::from_be_bytes([datagram[2], datagram[3]]);
+    if source_port != NDN_UDP_PORT && destination_port != NDN_UDP_PORT {
+        return None;
+    }
+    let endpoints = Endpoints::Udp {
+        source: SocketAddr::new(source, source_port),
+        destination: SocketAddr::new(destination, destination_port),
+    };
+    Some((endpoints, &datagram[UDP_HDR_SIZE..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_udcn_common::ndn::{Interest, Name};
+
+    #[test]
+    fn test_dissect_frame() {
+        let interest = Interest::new(Name::from_string("/udcn/a").unwrap());
+        let mut encoded = BytesMut::new();
+        interest.encode(&mut encoded).unwrap();
+
+        // NDN over UDP over IPv4
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ETH_P_IP.to_be_bytes());
+        let mut ip = vec![0u8; 20];
+        ip[0] = 0x45;
+        ip[9] = IPPROTO_UDP;
+        ip[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        ip[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        frame.extend_from_slice(&ip);
+        frame.extend_from_slice(&40000u16.to_be_bytes());
+        frame.extend_from_slice(&NDN_UDP_PORT.to_be_bytes());
+        frame.extend_from_slice(&[0; 4]);
+        frame.extend_from_slice(&encoded);
+
+        let (endpoints, packet) = dissect_frame(&frame).unwrap();
+        assert_eq!(endpoints.to_string(), "10.0.0.1:40000 > 10.0.0.2:6363");
+        assert_eq!(packet, encoded.clone().freeze());
+        assert!(matches!(NdnPacket::from_bytes(&packet), Ok(NdnPacket::Interest(_))));
+
+        // Straight over Ethernet, padded to the minimum frame size
+        let mut frame = vec![0x01, 0x00, 0x5e, 0x00, 0x17, 0xaa, 2, 0, 0, 0, 0, 1];
+        frame.extend_from_slice(&NDN_ETHERTYPE.to_be_bytes());
+        frame.extend_from_slice(&encoded);
+        frame.resize(frame.len().max(60) + 8, 0);
+        let (endpoints, packet) = dissect_frame(&frame).unwrap();
+        assert!(matches!(endpoints, Endpoints::Ethernet { .. }));
+        assert_eq!(packet.len(), encoded.len());
+
+        // Other UDP traffic
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ETH_P_IPV6.to_be_bytes());
+        let mut ip = vec![0u8; IPV6_HDR_SIZE];
+        ip[6] = IPPROTO_UDP;
+        frame.extend_from_slice(&ip);
+        frame.extend_from_slice(&53u16.to_be_bytes());
+        frame.extend_from_slice(&53u16.to_be_bytes());
+        frame.extend_from_slice(&[0; 4]);
+        frame.extend_from_slice(&encoded);
+        assert!(dissect_frame(&frame).is_none());
+    }
+}
@@ -2,18 +2,26 @@
 //!
 //! This module provides configuration options for QUIC servers and clients.
 
-use anyhow::{Context, Result};
 use quinn::{ClientConfig, ServerConfig, TransportConfig, VarInt};
-use rustls::{Certificate, PrivateKey};
+use rustls::{
+    server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient},
+    Certificate, PrivateKey, RootCertStore,
+};
 use std::{
     fs::File,
-    io::{BufReader, Read, Seek},
+    io::{BufReader, Seek},
+    net::SocketAddr,
     path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
 };
 
-use crate::NDN_QUIC_ALPN;
+use crate::{
+    error::TransportError,
+    fragmentation::ReassemblyOptions,
+    send_queue::{DEFAULT_MAX_INFLIGHT_STREAMS, DEFAULT_SEND_QUEUE_CAPACITY},
+    version::default_alpn_protocols,
+};
 
 /// Server configuration options
 #[derive(Debug, Clone)]
@@ -21,6 +29,11 @@ pub struct ServerOptions {
     /// Address to listen on
     pub listen_addr: String,
     
+    /// Additional addresses to listen on, sharing one face table and metrics.
+    /// An IPv6 wildcard address such as `[::]:6367` usually also accepts
+    /// IPv4 clients (dual-stack)
+    pub additional_listen_addrs: Vec<String>,
+    
     /// Path to the certificate file
     pub cert_path: PathBuf,
     
@@ -35,18 +48,70 @@ pub struct ServerOptions {
     
     /// Maximum connections
     pub max_connections: Option<u32>,
+    
+    /// Maximum outgoing streams each face may have open at once
+    pub max_inflight_streams: usize,
+    
+    /// Maximum sends per face waiting for a stream before sends fail
+    pub send_queue_capacity: usize,
+    
+    /// ALPN protocols accepted, most preferred first
+    pub alpn_protocols: Vec<Vec<u8>>,
+    
+    /// Close server faces that have not sent or received a packet for this
+    /// long (in milliseconds), even if the connection itself is kept alive
+    pub face_idle_timeout_ms: Option<u64>,
+    
+    /// CA bundle used to verify client certificates (enables mutual TLS)
+    pub client_ca_path: Option<PathBuf>,
+    
+    /// Reject clients that present no certificate (requires `client_ca_path`)
+    pub require_client_cert: bool,
+    
+    /// Poll the certificate and key files for changes at this interval
+    /// (in milliseconds) and reload them without dropping connections
+    pub cert_reload_interval_ms: Option<u64>,
+    
+    /// Timeout and per-face budget of the reassembly of fragmented messages
+    pub reassembly: ReassemblyOptions,
 }
 
 impl Default for ServerOptions {
     fn default() -> Self {
         Self {
             listen_addr: "0.0.0.0:6367".to_string(),
+            additional_listen_addrs: Vec::new(),
             cert_path: PathBuf::from("cert.pem"),
             key_path: PathBuf::from("key.pem"),
             idle_timeout_ms: Some(30000),
             keep_alive_interval_ms: Some(5000),
             max_connections: Some(1000),
+            max_inflight_streams: DEFAULT_MAX_INFLIGHT_STREAMS,
+            send_queue_capacity: DEFAULT_SEND_QUEUE_CAPACITY,
+            alpn_protocols: default_alpn_protocols(),
+            face_idle_timeout_ms: None,
+            client_ca_path: None,
+            require_client_cert: true,
+            cert_reload_interval_ms: None,
+            reassembly: ReassemblyOptions::default(),
+        }
+    }
+}
+
+impl ServerOptions {
+    /// Parse all listen addresses, IPv6 first so that a dual-stack socket is
+    /// bound before an IPv4 socket on the same port
+    pub fn listen_addresses(&self) -> Result<Vec<SocketAddr>, TransportError> {
+        let mut addrs: Vec<SocketAddr> = Vec::new();
+        for addr in std::iter::once(&self.listen_addr).chain(&self.additional_listen_addrs) {
+            let addr = addr.parse()
+                .map_err(|_| TransportError::Address(addr.clone()))?;
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
         }
+        addrs.sort_by_key(|addr| addr.is_ipv4());
+        Ok(addrs)
     }
 }
 
@@ -64,6 +129,21 @@ pub struct ClientOptions {
     
     /// Whether to verify the server certificate
     pub verify_certificate: bool,
+    
+    /// Maximum outgoing streams each face may have open at once
+    pub max_inflight_streams: usize,
+    
+    /// Maximum sends per face waiting for a stream before sends fail
+    pub send_queue_capacity: usize,
+    
+    /// ALPN protocols offered to servers, most preferred first
+    pub alpn_protocols: Vec<Vec<u8>>,
+    
+    /// Certificate chain presented to servers that require client authentication
+    pub client_cert_path: Option<PathBuf>,
+    
+    /// Private key of the client certificate
+    pub client_key_path: Option<PathBuf>,
 }
 
 impl Default for ClientOptions {
@@ -73,22 +153,43 @@ impl Default for ClientOptions {
             idle_timeout_ms: Some(30000),
             keep_alive_interval_ms: Some(5000),
             verify_certificate: true,
+            max_inflight_streams: DEFAULT_MAX_INFLIGHT_STREAMS,
+            send_queue_capacity: DEFAULT_SEND_QUEUE_CAPACITY,
+            alpn_protocols: default_alpn_protocols(),
+            client_cert_path: None,
+            client_key_path: None,
         }
     }
 }
 
 /// Configure a QUIC server
-pub async fn configure_server(options: &ServerOptions) -> Result<ServerConfig> {
+pub async fn configure_server(options: &ServerOptions) -> Result<ServerConfig, TransportError> {
     // Read certificate and private key
     let cert = read_certificate(&options.cert_path)?;
     let key = read_private_key(&options.key_path)?;
     
-    // Create server configuration
-    let mut server_crypto = rustls::ServerConfig::builder()
-        .with_safe_defaults()
-        .with_no_client_auth()
-        .with_single_cert(vec![cert], key)?;
-    server_crypto.alpn_protocols = vec![NDN_QUIC_ALPN.to_vec()];
+    // Create server configuration, verifying client certificates if a CA is configured
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = match &options.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for ca_cert in read_certificates(ca_path)? {
+                roots.add(&ca_cert).map_err(|_| TransportError::Certificate {
+                    what: "client CA certificate",
+                    path: ca_path.clone(),
+                })?;
+            }
+            let verifier = if options.require_client_cert {
+                AllowAnyAuthenticatedClient::new(roots)
+            } else {
+                AllowAnyAnonymousOrAuthenticatedClient::new(roots)
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_crypto = builder.with_single_cert(vec![cert], key)?;
+    server_crypto.alpn_protocols = options.alpn_protocols.clone();
 
     let mut server_config = ServerConfig::with_crypto(Arc::new(server_crypto));
     
@@ -116,40 +217,53 @@ pub async fn configure_server(options: &ServerOptions) -> Result<ServerConfig> {
 }
 
 /// Configure a QUIC client
-pub async fn configure_client(options: &ClientOptions) -> Result<ClientConfig> {
+pub async fn configure_client(options: &ClientOptions) -> Result<ClientConfig, TransportError> {
     // Create client crypto configuration
     let mut root_store = rustls::RootCertStore::empty();
-    let mut client_crypto = rustls::ClientConfig::builder()
+    let identity = read_client_identity(options)?;
+    let builder = rustls::ClientConfig::builder()
         .with_safe_defaults()
-        .with_root_certificates(root_store.clone())
-        .with_no_client_auth();
+        .with_root_certificates(root_store.clone());
+    let mut client_crypto = match identity.clone() {
+        Some((chain, key)) => builder.with_single_cert(chain, key)?,
+        None => builder.with_no_client_auth(),
+    };
     
     // Add custom CA certificate if specified
     if let Some(ca_path) = &options.ca_cert_path {
         let ca_cert = read_certificate(ca_path)?;
-        root_store.add(&ca_cert)?;
-        client_crypto = rustls::ClientConfig::builder()
+        root_store.add(&ca_cert).map_err(|_| TransportError::Certificate {
+            what: "CA certificate",
+            path: ca_path.clone(),
+        })?;
+        let builder = rustls::ClientConfig::builder()
             .with_safe_defaults()
-            .with_root_certificates(root_store.clone())
-            .with_no_client_auth();
+            .with_root_certificates(root_store.clone());
+        client_crypto = match identity.clone() {
+            Some((chain, key)) => builder.with_single_cert(chain, key)?,
+            None => builder.with_no_client_auth(),
+        };
     }
     
     // If verification is disabled (not recommended), skip verification
     if !options.verify_certificate {
-        let mut dangerous_config = rustls::ClientConfig::builder()
+        let builder = rustls::ClientConfig::builder()
             .with_safe_defaults()
-            .with_custom_certificate_verifier(Arc::new(SkipServerVerification {}))
-            .with_no_client_auth();
+            .with_custom_certificate_verifier(Arc::new(SkipServerVerification {}));
+        let mut dangerous_config = match identity {
+            Some((chain, key)) => builder.with_single_cert(chain, key)?,
+            None => builder.with_no_client_auth(),
+        };
         
         dangerous_config.enable_early_data = true;
-        dangerous_config.alpn_protocols = vec![NDN_QUIC_ALPN.to_vec()];
+        dangerous_config.alpn_protocols = options.alpn_protocols.clone();
         
         return Ok(ClientConfig::new(Arc::new(dangerous_config)));
     }
     
     // Enable early data and set ALPN protocols
     client_crypto.enable_early_data = true;
-    client_crypto.alpn_protocols = vec![NDN_QUIC_ALPN.to_vec()];
+    client_crypto.alpn_protocols = options.alpn_protocols.clone();
     
     // Create QUIC client configuration
     let mut client_config = ClientConfig::new(Arc::new(client_crypto));
@@ -170,27 +284,52 @@ pub async fn configure_client(options: &ClientOptions) -> Result<ClientConfig> {
     Ok(client_config)
 }
 
-/// Read a certificate from a file
-fn read_certificate<P: AsRef<Path>>(path: P) -> Result<Certificate> {
-    let file = File::open(path.as_ref())
-        .with_context(|| format!("Failed to open certificate file: {}", path.as_ref().display()))?;
-    let mut reader = BufReader::new(file);
-    
-    let certs = rustls_pemfile::certs(&mut reader)
-        .map_err(|_| anyhow::anyhow!("Failed to parse certificate"))?;
+/// Read the client certificate chain and key, if client authentication is configured
+fn read_client_identity(options: &ClientOptions) -> Result<Option<(Vec<Certificate>, PrivateKey)>, TransportError> {
+    match (&options.client_cert_path, &options.client_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            Ok(Some((read_certificates(cert_path)?, read_private_key(key_path)?)))
+        }
+        (None, None) => Ok(None),
+        _ => Err(TransportError::Config(
+            "both client_cert_path and client_key_path must be set for client authentication".to_string(),
+        )),
+    }
+}
+
+/// Open a certificate or key file
+fn open(path: &Path) -> Result<BufReader<File>, TransportError> {
+    let file = File::open(path).map_err(|source| TransportError::ReadFile {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(BufReader::new(file))
+}
+
+/// Read all certificates from a PEM file
+fn read_certificates<P: AsRef<Path>>(path: P) -> Result<Vec<Certificate>, TransportError> {
+    let path = path.as_ref();
+    let certs = rustls_pemfile::certs(&mut open(path)?).unwrap_or_default();
     
     if certs.is_empty() {
-        return Err(anyhow::anyhow!("No certificate found"));
+        return Err(TransportError::Certificate {
+            what: "certificate",
+            path: path.to_path_buf(),
+        });
     }
     
-    Ok(Certificate(certs[0].clone()))
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// Read a certificate from a file
+fn read_certificate<P: AsRef<Path>>(path: P) -> Result<Certificate, TransportError> {
+    Ok(read_certificates(path)?.swap_remove(0))
 }
 
 /// Read a private key from a file
-fn read_private_key<P: AsRef<Path>>(path: P) -> Result<PrivateKey> {
-    let file = File::open(path.as_ref())
-        .with_context(|| format!("Failed to open key file: {}", path.as_ref().display()))?;
-    let mut reader = BufReader::new(file);
+fn read_private_key<P: AsRef<Path>>(path: P) -> Result<PrivateKey, TransportError> {
+    let path = path.as_ref();
+    let mut reader = open(path)?;
     
     // Try PKCS8 format first
     if let Ok(keys) = rustls_pemfile::pkcs8_private_keys(&mut reader) {
@@ -200,7 +339,10 @@ fn read_private_key<P: AsRef<Path>>(path: P) -> Result<PrivateKey> {
     }
     
     // Rewind the reader
-    reader.seek(std::io::SeekFrom::Start(0))?;
+    reader.seek(std::io::SeekFrom::Start(0)).map_err(|source| TransportError::ReadFile {
+        path: path.to_path_buf(),
+        source,
+    })?;
     
     // Try RSA format
     if let Ok(keys) = rustls_pemfile::rsa_private_keys(&mut reader) {
@@ -209,7 +351,10 @@ fn read_private_key<P: AsRef<Path>>(path: P) -> Result<PrivateKey> {
         }
     }
     
-    Err(anyhow::anyhow!("No private key found"))
+    Err(TransportError::Certificate {
+        what: "private key",
+        path: path.to_path_buf(),
+    })
 }
 
 /// A certificate verifier that accepts any server certificate
@@ -228,3 +373,30 @@ impl rustls::client::ServerCertVerifier for SkipServerVerification {
         Ok(rustls::client::ServerCertVerified::assertion())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_addresses_ipv6_first() {
+        let options = ServerOptions {
+            listen_addr: "0.0.0.0:6367".to_string(),
+            additional_listen_addrs: vec![
+                "[::]:6367".to_string(),
+                "127.0.0.1:7000".to_string(),
+                "0.0.0.0:6367".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let addrs: Vec<String> = options.listen_addresses().unwrap().iter().map(|a| a.to_string()).collect();
+        assert_eq!(addrs, vec!["[::]:6367", "0.0.0.0:6367", "127.0.0.1:7000"]);
+
+        let invalid = ServerOptions {
+            additional_listen_addrs: vec!["not an address".to_string()],
+            ..Default::default()
+        };
+        assert!(invalid.listen_addresses().is_err());
+    }
+}
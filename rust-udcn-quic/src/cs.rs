@@ -0,0 +1,510 @@
+//! Userspace content store.
+//!
+//! Caches Data packets in canonical name order, so that a CanBePrefix
+//! Interest finds the Data under its name in a range of the index. A store
+//! can optionally be backed by a repository log on disk so that published
+//! Data survives restarts.
+
+use log::{debug, warn};
+use rust_udcn_common::{
+    metrics::UdcnMetrics,
+    ndn::{ChildSelector, Data, Interest, Name},
+    Result,
+};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use crate::repo::{RepoLog, RepoWriter, StoredData};
+
+/// Default maximum number of cached Data packets
+pub const DEFAULT_CS_CAPACITY: usize = 10_000;
+
+/// Number of removal records after which the repo log is compacted
+const COMPACTION_THRESHOLD: usize = 1024;
+
+/// Limits applied to a content store
+#[derive(Debug, Clone)]
+pub struct ContentStoreLimits {
+    /// Maximum number of entries
+    pub max_entries: usize,
+
+    /// Maximum total content size in bytes (0 for unlimited)
+    pub max_bytes: usize,
+}
+
+impl Default for ContentStoreLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: DEFAULT_CS_CAPACITY,
+            max_bytes: 0,
+        }
+    }
+}
+
+/// A cached entry and its place in the insertion order
+#[derive(Debug)]
+struct Entry {
+    stored: StoredData,
+    seq: u64,
+}
+
+/// State behind the content store lock
+#[derive(Debug, Default)]
+struct Inner {
+    entries: BTreeMap<Name, Entry>,
+
+    /// Names of the entries in insertion order, for eviction
+    order: BTreeMap<u64, Name>,
+    next_seq: u64,
+
+    total_bytes: usize,
+    repo: Option<RepoWriter>,
+    stale_records: usize,
+}
+
+impl Inner {
+    /// Insert an entry, returning the one it replaced
+    fn insert(&mut self, stored: StoredData) -> Option<StoredData> {
+        let replaced = self.remove(&stored.data.name);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.total_bytes += stored.data.content.len();
+        self.order.insert(seq, stored.data.name.clone());
+        self.entries.insert(stored.data.name.clone(), Entry { stored, seq });
+        replaced
+    }
+
+    fn remove(&mut self, name: &Name) -> Option<StoredData> {
+        let entry = self.entries.remove(name)?;
+        self.order.remove(&entry.seq);
+        self.total_bytes -= entry.stored.data.content.len();
+        Some(entry.stored)
+    }
+
+    fn get(&self, name: &Name) -> Option<&StoredData> {
+        self.entries.get(name).map(|entry| &entry.stored)
+    }
+
+    /// Entries from the oldest inserted to the newest
+    fn oldest(&self) -> impl Iterator<Item = &StoredData> {
+        self.order.values().map(|name| &self.entries[name].stored)
+    }
+
+    /// Entries named `prefix` or under it, in canonical order
+    fn under<'a>(&'a self, prefix: &'a Name) -> impl Iterator<Item = &'a StoredData> {
+        self.entries
+            .range(prefix..)
+            .take_while(move |(name, _)| prefix.len() <= name.len() && prefix.is_prefix_of(name))
+            .map(|(_, entry)| &entry.stored)
+    }
+
+    /// Entry answering a CanBePrefix Interest for `prefix`
+    ///
+    /// The rightmost child is the one whose component after the prefix
+    /// sorts last; the first entry under that child answers, as the
+    /// leftmost one does for the leftmost child.
+    fn find_prefix<'a>(
+        &'a self,
+        prefix: &'a Name,
+        child_selector: ChildSelector,
+        must_be_fresh: bool,
+    ) -> Option<&'a StoredData> {
+        let mut candidates = self.under(prefix).filter(|entry| !must_be_fresh || entry.is_fresh());
+        match child_selector {
+            ChildSelector::Leftmost => candidates.next(),
+            ChildSelector::Rightmost => candidates.fold(None, |best: Option<&StoredData>, entry| match best {
+                Some(best) if best.data.name.get(prefix.len()) >= entry.data.name.get(prefix.len()) => Some(best),
+                _ => Some(entry),
+            }),
+        }
+    }
+}
+
+/// In-memory content store with optional on-disk persistence
+#[derive(Debug)]
+pub struct ContentStore {
+    inner: Mutex<Inner>,
+    limits: ContentStoreLimits,
+    metrics: Arc<UdcnMetrics>,
+}
+
+impl ContentStore {
+    /// Create an in-memory content store
+    pub fn new(limits: ContentStoreLimits, metrics: Arc<UdcnMetrics>) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            limits,
+            metrics,
+        }
+    }
+
+    /// Open a content store persisted in the repo log at `path`
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        limits: ContentStoreLimits,
+        metrics: Arc<UdcnMetrics>,
+    ) -> Result<Self> {
+        let (repo, mut stored) = RepoLog::open(path)?;
+
+        let mut inner = Inner {
+            repo: Some(RepoWriter::spawn(repo)?),
+            ..Default::default()
+        };
+        // The oldest entries are evicted first, as before the restart
+        stored.sort_by_key(|entry| entry.inserted_at);
+        for entry in stored {
+            inner.insert(entry);
+        }
+
+        let cs = Self {
+            inner: Mutex::new(inner),
+            limits,
+            metrics,
+        };
+        cs.enforce_limits()?;
+        cs.metrics.cs_size.set(cs.len() as u64);
+        Ok(cs)
+    }
+
+    /// Limits this store enforces
+    pub fn limits(&self) -> &ContentStoreLimits {
+        &self.limits
+    }
+
+    /// Whether this store writes through to disk
+    pub fn is_persistent(&self) -> bool {
+        self.inner.lock().unwrap().repo.is_some()
+    }
+
+    /// Path of the repo log backing this store, if any
+    pub fn repo_path(&self) -> Option<PathBuf> {
+        let inner = self.inner.lock().unwrap();
+        inner.repo.as_ref().map(|repo| repo.path().to_path_buf())
+    }
+
+    /// Insert a Data packet, replacing any entry with the same name
+    ///
+    /// A persistent store queues the insertion for its repo writer, which
+    /// logs write failures rather than returning them.
+    pub fn insert(&self, data: Data) -> Result<()> {
+        let inserted_at = SystemTime::now();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(repo) = inner.repo.as_ref() {
+                repo.insert(data.clone(), inserted_at);
+            }
+            if inner.insert(StoredData { data, inserted_at }).is_some() {
+                inner.stale_records += 1;
+            }
+        }
+        self.metrics.cs_inserts.increment();
+
+        self.enforce_limits()?;
+        self.metrics.cs_size.set(self.len() as u64);
+        Ok(())
+    }
+
+    /// Look up a Data packet by exact name
+    pub fn get(&self, name: &Name) -> Option<Data> {
+        let inner = self.inner.lock().unwrap();
+        match inner.get(name) {
+            Some(entry) => {
+                self.metrics.cs_hits.increment();
+                Some(entry.data.clone())
+            }
+            None => {
+                self.metrics.cs_misses.increment();
+                None
+            }
+        }
+    }
+
+    /// Look up the Data answering `interest`, unless the Interest asks for
+    /// fresh Data and the cached copy is stale
+    ///
+    /// A CanBePrefix Interest is answered by the Data with its name or
+    /// under it, picked by the child selector of the Interest. Stale
+    /// entries are kept for the Interests that accept them, until evicted.
+    pub fn lookup(&self, interest: &Interest) -> Option<Data> {
+        let inner = self.inner.lock().unwrap();
+        let entry = if interest.can_be_prefix {
+            inner.find_prefix(interest.name(), interest.child_selector, interest.must_be_fresh)
+        } else {
+            inner.get(interest.name())
+        };
+        match entry {
+            Some(entry) if !interest.must_be_fresh || entry.is_fresh() => {
+                self.metrics.cs_hits.increment();
+                Some(entry.data.clone())
+            }
+            _ => {
+                self.metrics.cs_misses.increment();
+                None
+            }
+        }
+    }
+
+    /// Remove a Data packet by exact name. Returns whether it was present.
+    pub fn remove(&self, name: &Name) -> Result<bool> {
+        let removed = {
+            let mut inner = self.inner.lock().unwrap();
+            let removed = inner.remove(name).is_some();
+            if removed {
+                if let Some(repo) = inner.repo.as_ref() {
+                    repo.remove(name.clone());
+                }
+                inner.stale_records += 2;
+            }
+            removed
+        };
+        if removed {
+            self.maybe_compact()?;
+            self.metrics.cs_size.set(self.len() as u64);
+        }
+        Ok(removed)
+    }
+
+    /// Enumerate all stored entries under a name prefix
+    pub fn enumerate(&self, prefix: &Name) -> Vec<StoredData> {
+        let inner = self.inner.lock().unwrap();
+        let mut entries: Vec<StoredData> = inner.under(prefix).cloned().collect();
+        entries.sort_by_key(|e| e.inserted_at);
+        entries
+    }
+
+    /// Evict every entry inserted more than `max_age` ago
+    pub fn evict_older_than(&self, max_age: Duration) -> Result<usize> {
+        let cutoff = SystemTime::now()
+            .checked_sub(max_age)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+
+        let expired: Vec<Name> = {
+            let inner = self.inner.lock().unwrap();
+            inner
+                .oldest()
+                .take_while(|e| e.inserted_at < cutoff)
+                .map(|e| e.data.name.clone())
+                .collect()
+        };
+
+        self.evict(&expired)
+    }
+
+    /// Evict the oldest entries until the total content size is at most `max_bytes`
+    pub fn evict_to_size(&self, max_bytes: usize) -> Result<usize> {
+        let mut victims = Vec::new();
+        {
+            let inner = self.inner.lock().unwrap();
+            let mut total = inner.total_bytes;
+            for entry in inner.oldest() {
+                if total <= max_bytes {
+                    break;
+                }
+                total -= entry.data.content.len();
+                victims.push(entry.data.name.clone());
+            }
+        }
+
+        self.evict(&victims)
+    }
+
+    /// Rewrite the repo log so it only contains live entries
+    ///
+    /// The rewrite is queued behind the records already queued for the
+    /// repo writer.
+    pub fn compact(&self) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(repo) = inner.repo.as_ref() {
+            repo.compact(inner.oldest().cloned().collect());
+        }
+        inner.stale_records = 0;
+        Ok(())
+    }
+
+    /// Number of cached Data packets
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Whether the store is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total content size of all cached Data packets
+    pub fn size_bytes(&self) -> usize {
+        self.inner.lock().unwrap().total_bytes
+    }
+
+    /// Remove the given entries and record them in the repo log
+    fn evict(&self, names: &[Name]) -> Result<usize> {
+        if names.is_empty() {
+            return Ok(0);
+        }
+
+        let mut evicted = 0;
+        {
+            let mut inner = self.inner.lock().unwrap();
+            for name in names {
+                if inner.remove(name).is_none() {
+                    continue;
+                }
+                if let Some(repo) = inner.repo.as_ref() {
+                    repo.remove(name.clone());
+                }
+                inner.stale_records += 2;
+                evicted += 1;
+            }
+        }
+
+        self.metrics.cs_evictions.add(evicted as u64);
+        self.metrics.cs_size.set(self.len() as u64);
+        debug!("Evicted {} entries from the content store", evicted);
+
+        self.maybe_compact()?;
+        Ok(evicted)
+    }
+
+    /// Evict the oldest entries while the store exceeds its limits
+    fn enforce_limits(&self) -> Result<()> {
+        let mut victims = Vec::new();
+        {
+            let inner = self.inner.lock().unwrap();
+            let mut count = inner.entries.len();
+            let mut total = inner.total_bytes;
+            for entry in inner.oldest() {
+                let over_bytes = self.limits.max_bytes > 0 && total > self.limits.max_bytes;
+                if count <= self.limits.max_entries && !over_bytes {
+                    break;
+                }
+                count -= 1;
+                total -= entry.data.content.len();
+                victims.push(entry.data.name.clone());
+            }
+        }
+
+        self.evict(&victims).map(|_| ())
+    }
+
+    /// Compact the repo log once enough stale records have accumulated
+    fn maybe_compact(&self) -> Result<()> {
+        let needs_compaction = {
+            let inner = self.inner.lock().unwrap();
+            inner.repo.is_some() && inner.stale_records >= COMPACTION_THRESHOLD
+        };
+        if needs_compaction {
+            if let Err(e) = self.compact() {
+                warn!("Failed to compact content store repo: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(name: &str, size: usize) -> Data {
+        Data::new(Name::from_string(name).unwrap(), vec![0u8; size])
+    }
+
+    fn temp_repo(label: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("udcn-cs-{}-{}.log", label, std::process::id()))
+    }
+
+    #[test]
+    fn test_eviction_by_limits() {
+        let limits = ContentStoreLimits {
+            max_entries: 2,
+            max_bytes: 0,
+        };
+        let cs = ContentStore::new(limits, Arc::new(UdcnMetrics::new()));
+        cs.insert(data("/a/1", 10)).unwrap();
+        cs.insert(data("/a/2", 10)).unwrap();
+        cs.insert(data("/b/1", 10)).unwrap();
+
+        assert_eq!(cs.len(), 2);
+        assert!(cs.get(&Name::from_string("/a/1").unwrap()).is_none());
+        assert_eq!(cs.enumerate(&Name::from_string("/a").unwrap()).len(), 1);
+
+        // Inserting a name again makes it the newest
+        cs.insert(data("/a/2", 10)).unwrap();
+        cs.insert(data("/c/1", 10)).unwrap();
+        assert!(cs.get(&Name::from_string("/b/1").unwrap()).is_none());
+        assert!(cs.get(&Name::from_string("/a/2").unwrap()).is_some());
+
+        assert_eq!(cs.evict_to_size(10).unwrap(), 1);
+        assert_eq!(cs.size_bytes(), 10);
+    }
+
+    #[test]
+    fn test_must_be_fresh_skips_stale_entries() {
+        let cs = ContentStore::new(ContentStoreLimits::default(), Arc::new(UdcnMetrics::new()));
+        cs.insert(data("/fresh", 4)).unwrap();
+        cs.insert(data("/stale", 4).with_freshness_period(0)).unwrap();
+
+        let interest = |name: &str| Interest::new(Name::from_string(name).unwrap());
+        assert!(cs.lookup(&interest("/fresh")).is_some());
+        assert!(cs.lookup(&interest("/stale")).is_none());
+        assert!(cs.lookup(&interest("/stale").with_must_be_fresh(false)).is_some());
+        assert_eq!(cs.len(), 2);
+    }
+
+    #[test]
+    fn test_can_be_prefix_selects_child() {
+        let cs = ContentStore::new(ContentStoreLimits::default(), Arc::new(UdcnMetrics::new()));
+        for name in ["/video/v=9/seg=0", "/video/v=10/seg=1", "/video/v=10/seg=0", "/video/v=1/seg=0", "/videos/1"] {
+            cs.insert(data(name, 4)).unwrap();
+        }
+        cs.insert(data("/video/v=11/seg=0", 4).with_freshness_period(0)).unwrap();
+
+        let lookup = |name: &str, child_selector, must_be_fresh| {
+            let interest = Interest::new(Name::from_string(name).unwrap())
+                .with_can_be_prefix(true)
+                .with_child_selector(child_selector)
+                .with_must_be_fresh(must_be_fresh);
+            cs.lookup(&interest).map(|data| data.name.to_string())
+        };
+        // Shorter components sort first, so v=10 comes after v=9
+        assert_eq!(lookup("/video", ChildSelector::Leftmost, true).as_deref(), Some("/video/v=1/seg=0"));
+        assert_eq!(lookup("/video", ChildSelector::Rightmost, true).as_deref(), Some("/video/v=10/seg=0"));
+        assert_eq!(lookup("/video", ChildSelector::Rightmost, false).as_deref(), Some("/video/v=11/seg=0"));
+        assert_eq!(lookup("/video/v=9/seg=0", ChildSelector::Rightmost, true).as_deref(), Some("/video/v=9/seg=0"));
+        assert_eq!(lookup("/vid", ChildSelector::Leftmost, true), None);
+
+        // Without CanBePrefix only the exact name answers
+        assert!(cs.lookup(&Interest::new(Name::from_string("/video").unwrap())).is_none());
+        assert_eq!(cs.enumerate(&Name::from_string("/video/v=10").unwrap()).len(), 2);
+    }
+
+    #[test]
+    fn test_repo_survives_reopen() {
+        let path = temp_repo("reopen");
+        let _ = std::fs::remove_file(&path);
+        let metrics = Arc::new(UdcnMetrics::new());
+
+        {
+            let cs = ContentStore::open(&path, ContentStoreLimits::default(), Arc::clone(&metrics)).unwrap();
+            cs.insert(data("/repo/1", 4).with_final_block_id(1)).unwrap();
+            cs.insert(data("/repo/2", 4)).unwrap();
+            cs.remove(&Name::from_string("/repo/2").unwrap()).unwrap();
+        }
+
+        let cs = ContentStore::open(&path, ContentStoreLimits::default(), Arc::clone(&metrics)).unwrap();
+        assert_eq!(cs.len(), 1);
+        let stored = cs.get(&Name::from_string("/repo/1").unwrap()).unwrap();
+        assert_eq!(stored.final_block_id, Some(1));
+
+        cs.compact().unwrap();
+        drop(cs);
+        let cs = ContentStore::open(&path, ContentStoreLimits::default(), metrics).unwrap();
+        assert_eq!(cs.len(), 1);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
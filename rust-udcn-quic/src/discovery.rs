@@ -0,0 +1,452 @@
+//! Neighbor discovery over UDP multicast.
+//!
+//! Every node periodically multicasts a Data packet named
+//! `/localhop/udcn/discovery/<node-id>`, carrying the port of its QUIC
+//! server, to the NDN multicast group. A node hearing a new neighbor opens a
+//! QUIC face to it, so small deployments form a mesh without configured
+//! connections. Of each pair of neighbors only the one with the lower node
+//! ID connects, which leaves exactly one face between them.
+
+use bytes::BytesMut;
+use log::{debug, info, trace, warn};
+use rust_udcn_common::{
+    ndn::{Data, Name, NameComponent},
+    Error,
+};
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    io, mem,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{net::UdpSocket, sync::Mutex, task::JoinHandle};
+
+use crate::{
+    error::TransportError,
+    events::{EventFilter, EventKind},
+    transport::{NdnQuicTransport, TransportEvent},
+    NDN_QUIC_PORT,
+};
+
+/// IPv4 multicast group of NDN nodes, as used by NFD's multicast UDP faces
+pub const NDN_MULTICAST_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 23, 170);
+
+/// UDP port announcements are multicast to
+pub const NDN_DISCOVERY_PORT: u16 = 56363;
+
+/// Name prefix of discovery announcements
+pub const DISCOVERY_PREFIX: &str = "/localhop/udcn/discovery";
+
+/// How long an announcement is fresh, in milliseconds
+const ANNOUNCEMENT_TTL_MS: u32 = 10_000;
+
+/// Largest announcement accepted
+const MAX_ANNOUNCEMENT_SIZE: usize = 1500;
+
+/// Options for neighbor discovery
+#[derive(Debug, Clone)]
+pub struct DiscoveryOptions {
+    /// Identifier of this node, unique among its neighbors
+    pub node_id: String,
+
+    /// Multicast group and port to announce on
+    pub group: SocketAddrV4,
+
+    /// Address of the local interface to join the group on
+    pub interface: Ipv4Addr,
+
+    /// Port of the local QUIC server, announced to neighbors
+    pub quic_port: u16,
+
+    /// How often this node announces itself
+    pub announce_interval: Duration,
+
+    /// How long a silent neighbor is kept
+    pub neighbor_timeout: Duration,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            node_id: hostname().unwrap_or_else(|| format!("udcn-{}", std::process::id())),
+            group: SocketAddrV4::new(NDN_MULTICAST_GROUP, NDN_DISCOVERY_PORT),
+            interface: Ipv4Addr::UNSPECIFIED,
+            quic_port: NDN_QUIC_PORT,
+            announce_interval: Duration::from_secs(5),
+            neighbor_timeout: Duration::from_secs(20),
+        }
+    }
+}
+
+/// A node heard on the discovery group
+#[derive(Debug, Clone)]
+pub struct Neighbor {
+    /// Node ID of the neighbor
+    pub node_id: String,
+
+    /// QUIC address of the neighbor
+    pub addr: SocketAddr,
+
+    /// When the neighbor was last heard
+    pub last_seen: Instant,
+
+    /// Face this node opened to the neighbor, if any. Neighbors that
+    /// connect to this node have their face accepted by the server instead.
+    pub face_id: Option<String>,
+}
+
+/// Neighbors heard so far, by node ID
+#[derive(Debug, Default)]
+struct NeighborTable {
+    neighbors: HashMap<String, Neighbor>,
+}
+
+impl NeighborTable {
+    /// Record an announcement. Returns whether the neighbor is new or has
+    /// moved to another address.
+    fn heard(&mut self, node_id: &str, addr: SocketAddr, now: Instant) -> bool {
+        match self.neighbors.get_mut(node_id) {
+            Some(neighbor) if neighbor.addr == addr => {
+                neighbor.last_seen = now;
+                false
+            }
+            _ => {
+                self.neighbors.insert(
+                    node_id.to_string(),
+                    Neighbor {
+                        node_id: node_id.to_string(),
+                        addr,
+                        last_seen: now,
+                        face_id: None,
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    /// Whether this node still has to open a face to `node_id`
+    fn needs_face(&self, local_id: &str, node_id: &str) -> bool {
+        local_id < node_id
+            && self
+                .neighbors
+                .get(node_id)
+                .is_some_and(|neighbor| neighbor.face_id.is_none())
+    }
+
+    fn set_face(&mut self, node_id: &str, face_id: &str) {
+        if let Some(neighbor) = self.neighbors.get_mut(node_id) {
+            neighbor.face_id = Some(face_id.to_string());
+        }
+    }
+
+    /// Forget the face of a neighbor after it closed
+    fn face_closed(&mut self, face_id: &str) {
+        for neighbor in self.neighbors.values_mut() {
+            if neighbor.face_id.as_deref() == Some(face_id) {
+                neighbor.face_id = None;
+            }
+        }
+    }
+
+    /// Remove and return the neighbors not heard for `timeout`
+    fn expire(&mut self, now: Instant, timeout: Duration) -> Vec<Neighbor> {
+        let silent: Vec<String> = self
+            .neighbors
+            .values()
+            .filter(|neighbor| now.duration_since(neighbor.last_seen) >= timeout)
+            .map(|neighbor| neighbor.node_id.clone())
+            .collect();
+        silent
+            .iter()
+            .filter_map(|node_id| self.neighbors.remove(node_id))
+            .collect()
+    }
+}
+
+/// Encode the announcement of `node_id` serving QUIC on `quic_port`
+fn encode_announcement(node_id: &str, quic_port: u16) -> rust_udcn_common::Result<BytesMut> {
+    let mut name = Name::from_string(DISCOVERY_PREFIX)?;
+    name.push(NameComponent::new(node_id.as_bytes().to_vec()));
+    let data = Data::new(name, quic_port.to_be_bytes().to_vec())
+        .with_freshness_period(ANNOUNCEMENT_TTL_MS);
+    let mut buf = BytesMut::new();
+    data.encode(&mut buf)?;
+    Ok(buf)
+}
+
+/// Decode an announcement into the node ID and QUIC port it carries
+fn decode_announcement(packet: &[u8]) -> rust_udcn_common::Result<(String, u16)> {
+    let data = Data::decode(packet)?;
+    let prefix = Name::from_string(DISCOVERY_PREFIX)?;
+    let name = data.name();
+    if name.len() != prefix.len() + 1 || !prefix.is_prefix_of(name) {
+        return Err(Error::NdnPacket(format!("{} is not a discovery announcement", name)));
+    }
+    let node_id = name.get(prefix.len()).map(|c| c.to_string()).unwrap_or_default();
+    let port: [u8; 2] = data.content()[..]
+        .try_into()
+        .map_err(|_| Error::NdnPacket(format!("Malformed announcement from {}", node_id)))?;
+    Ok((node_id, u16::from_be_bytes(port)))
+}
+
+/// Host name of this machine
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer outlives the call and its length is passed along
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if ret != 0 {
+        return None;
+    }
+    let name = CStr::from_bytes_until_nul(&buf).ok()?.to_str().ok()?;
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Bind a socket to the port of `group` and join the group on `interface`.
+/// The port is shared, so several nodes on one host can discover each other.
+fn bind_multicast(group: SocketAddrV4, interface: Ipv4Addr) -> io::Result<UdpSocket> {
+    // SAFETY: socket(2) has no memory preconditions, and the descriptor is
+    // owned as soon as it is known to be valid
+    let fd = unsafe { OwnedFd::from_raw_fd(check(libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0))?) };
+
+    let one: libc::c_int = 1;
+    for option in [libc::SO_REUSEADDR, libc::SO_REUSEPORT] {
+        // SAFETY: the option value is a live c_int of the given size
+        check(unsafe {
+            libc::setsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                option,
+                &one as *const libc::c_int as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        })?;
+    }
+
+    // SAFETY: sockaddr_in is plain old data, all zeroes is a valid value
+    let mut addr: libc::sockaddr_in = unsafe { mem::zeroed() };
+    addr.sin_family = libc::AF_INET as libc::sa_family_t;
+    addr.sin_port = group.port().to_be();
+    // SAFETY: the address is a live sockaddr_in of the given size
+    check(unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        )
+    })?;
+
+    let socket = std::net::UdpSocket::from(fd);
+    socket.join_multicast_v4(group.ip(), &interface)?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket)
+}
+
+/// Announces this node and connects to the neighbors it hears
+pub struct Discovery {
+    /// Options discovery was started with
+    options: DiscoveryOptions,
+
+    /// Neighbors heard so far
+    neighbors: Arc<Mutex<NeighborTable>>,
+
+    /// Announcement, listener and face tracking tasks
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Discovery {
+    /// Start announcing on the discovery group and open faces on
+    /// `transport` to the neighbors heard there
+    pub async fn start(transport: Arc<NdnQuicTransport>, options: DiscoveryOptions) -> Result<Self, TransportError> {
+        let socket = bind_multicast(options.group, options.interface).map_err(|source| TransportError::Bind {
+            addr: options.group.to_string(),
+            source,
+        })?;
+        let socket = Arc::new(socket);
+        let announcement = encode_announcement(&options.node_id, options.quic_port)
+            .map_err(|e| TransportError::Config(format!("Node ID {}: {}", options.node_id, e)))?;
+        info!("Announcing node {} on {}", options.node_id, options.group);
+
+        let neighbors = Arc::new(Mutex::new(NeighborTable::default()));
+        let tasks = vec![
+            tokio::spawn(Self::announce(
+                Arc::clone(&socket),
+                announcement,
+                options.clone(),
+                Arc::clone(&transport),
+                Arc::clone(&neighbors),
+            )),
+            tokio::spawn(Self::listen(
+                socket,
+                options.clone(),
+                Arc::clone(&transport),
+                Arc::clone(&neighbors),
+            )),
+            tokio::spawn(Self::track_faces(transport, Arc::clone(&neighbors))),
+        ];
+
+        Ok(Self {
+            options,
+            neighbors,
+            tasks,
+        })
+    }
+
+    /// Get the ID this node announces
+    pub fn node_id(&self) -> &str {
+        &self.options.node_id
+    }
+
+    /// List the neighbors currently known, ordered by node ID
+    pub async fn neighbors(&self) -> Vec<Neighbor> {
+        let mut neighbors: Vec<Neighbor> = self.neighbors.lock().await.neighbors.values().cloned().collect();
+        neighbors.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        neighbors
+    }
+
+    /// Multicast the announcement periodically and drop silent neighbors
+    async fn announce(
+        socket: Arc<UdpSocket>,
+        announcement: BytesMut,
+        options: DiscoveryOptions,
+        transport: Arc<NdnQuicTransport>,
+        neighbors: Arc<Mutex<NeighborTable>>,
+    ) {
+        let mut ticker = tokio::time::interval(options.announce_interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = socket.send_to(&announcement, options.group).await {
+                warn!("Failed to send discovery announcement: {}", e);
+            }
+
+            let silent = neighbors.lock().await.expire(Instant::now(), options.neighbor_timeout);
+            for neighbor in silent {
+                info!("Neighbor {} at {} went silent", neighbor.node_id, neighbor.addr);
+                let Some(face_id) = neighbor.face_id else {
+                    continue;
+                };
+                if let Some(face) = transport.get_faces().await.into_iter().find(|f| f.id() == face_id) {
+                    face.close().await;
+                }
+            }
+        }
+    }
+
+    /// Receive announcements and connect to new neighbors
+    async fn listen(
+        socket: Arc<UdpSocket>,
+        options: DiscoveryOptions,
+        transport: Arc<NdnQuicTransport>,
+        neighbors: Arc<Mutex<NeighborTable>>,
+    ) {
+        let mut buf = vec![0u8; MAX_ANNOUNCEMENT_SIZE];
+        loop {
+            let (len, from) = match socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    warn!("Failed to receive discovery announcement: {}", e);
+                    continue;
+                }
+            };
+            let (node_id, port) = match decode_announcement(&buf[..len]) {
+                Ok(announcement) => announcement,
+                Err(e) => {
+                    trace!("Ignoring datagram from {}: {}", from, e);
+                    continue;
+                }
+            };
+            if node_id == options.node_id {
+                continue;
+            }
+
+            let addr = SocketAddr::new(from.ip(), port);
+            let needs_face = {
+                let mut table = neighbors.lock().await;
+                if table.heard(&node_id, addr, Instant::now()) {
+                    info!("Discovered neighbor {} at {}", node_id, addr);
+                }
+                table.needs_face(&options.node_id, &node_id)
+            };
+            if !needs_face {
+                continue;
+            }
+
+            // A failed attempt is retried on the next announcement
+            match transport.connect(addr).await {
+                Ok(face) => {
+                    debug!("Opened face {} to neighbor {}", face.id(), node_id);
+                    neighbors.lock().await.set_face(&node_id, face.id());
+                }
+                Err(e) => warn!("Failed to connect to neighbor {} at {}: {}", node_id, addr, e),
+            }
+        }
+    }
+
+    /// Reconnect to neighbors whose face has closed
+    async fn track_faces(transport: Arc<NdnQuicTransport>, neighbors: Arc<Mutex<NeighborTable>>) {
+        let mut closed = transport.subscribe(EventFilter::default().kind(EventKind::FaceClosed));
+        while let Some(event) = closed.recv().await {
+            if let TransportEvent::FaceClosed(face_id) = event {
+                neighbors.lock().await.face_closed(&face_id);
+            }
+        }
+    }
+}
+
+impl Drop for Discovery {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_announcement_round_trip() {
+        let packet = encode_announcement("node-a", 6367).unwrap();
+        assert_eq!(decode_announcement(&packet).unwrap(), ("node-a".to_string(), 6367));
+
+        let mut other = BytesMut::new();
+        Data::new(Name::from_string("/localhop/udcn/other/node-a").unwrap(), vec![0x18, 0xdf])
+            .encode(&mut other)
+            .unwrap();
+        assert!(decode_announcement(&other).is_err());
+    }
+
+    #[test]
+    fn test_lower_node_id_connects() {
+        let now = Instant::now();
+        let addr: SocketAddr = "192.0.2.1:6367".parse().unwrap();
+        let mut table = NeighborTable::default();
+
+        assert!(table.heard("node-b", addr, now));
+        assert!(!table.heard("node-b", addr, now));
+        assert!(table.needs_face("node-a", "node-b"));
+        assert!(!table.needs_face("node-c", "node-b"));
+
+        table.set_face("node-b", "quic-1");
+        assert!(!table.needs_face("node-a", "node-b"));
+        table.face_closed("quic-1");
+        assert!(table.needs_face("node-a", "node-b"));
+
+        let expired = table.expire(now + Duration::from_secs(30), Duration::from_secs(20));
+        assert_eq!(expired.len(), 1);
+        assert!(table.neighbors.is_empty());
+    }
+}
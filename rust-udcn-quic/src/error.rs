@@ -0,0 +1,315 @@
+//! Errors of faces and transports.
+//!
+//! Exchanges on a face fail with a [`FaceError`] telling why, so callers can
+//! retry after a timeout, try elsewhere after a Nack or give up on a closed
+//! face without parsing messages. Setting up servers, clients and listeners
+//! fails with a [`TransportError`], fetching a segmented object with a
+//! [`FetchError`] and repo commands with a [`RepoError`].
+
+use quinn::{ConnectError, ConnectionError, ReadError, SendDatagramError, WriteError};
+use rust_udcn_common::ndn::{NackReason, Name};
+use std::{io, path::PathBuf};
+use thiserror::Error;
+use tokio_tungstenite::tungstenite;
+
+use crate::{face::PacketMode, send_queue::SendQueueFull, version::ProtocolVersion};
+
+/// Why a packet could not be sent on a face or an Interest went unanswered
+#[derive(Debug, Error)]
+pub enum FaceError {
+    /// No Data came back within the Interest lifetime
+    #[error("Interest timed out")]
+    Timeout,
+
+    /// The Interest was Nacked upstream for a reason other than NoRoute
+    #[error("Interest Nacked: {0}")]
+    Nacked(NackReason),
+
+    /// No face to send the Interest on, or a NoRoute Nack from upstream
+    #[error("Interest Nacked: no route")]
+    NoRoute,
+
+    /// The face was closed before or while the packet was sent
+    #[error("Face is closed")]
+    Closed,
+
+    /// The Interest was dropped with the face's pending Interests
+    #[error("Network error: {0}")]
+    Dropped(String),
+
+    /// No face has this ID
+    #[error("Face not found: {0}")]
+    UnknownFace(String),
+
+    /// The transport is shutting down and takes no new Interests
+    #[error("Transport is shutting down")]
+    Draining,
+
+    /// Too many packets are waiting for a stream
+    #[error(transparent)]
+    QueueFull(#[from] SendQueueFull),
+
+    /// The encoded packet does not fit the link
+    #[error("Packet of {size} bytes exceeds the limit of {limit} bytes")]
+    TooLarge { size: usize, limit: usize },
+
+    /// The negotiated protocol version lacks the packet mode
+    #[error("Packet mode {mode:?} is not supported by protocol {version}")]
+    UnsupportedMode { mode: PacketMode, version: ProtocolVersion },
+
+    /// The packet could not be encoded or decoded
+    #[error(transparent)]
+    Packet(#[from] rust_udcn_common::Error),
+
+    /// The QUIC connection of the face is gone
+    #[error("Connection lost: {0}")]
+    Connection(#[from] ConnectionError),
+
+    /// Writing to a QUIC stream failed
+    #[error("Stream write failed: {0}")]
+    Write(#[from] WriteError),
+
+    /// Reading from a QUIC stream failed
+    #[error("Stream read failed: {0}")]
+    Read(#[from] ReadError),
+
+    /// Sending a QUIC datagram failed
+    #[error("Datagram send failed: {0}")]
+    Datagram(#[from] SendDatagramError),
+
+    /// The WebSocket of the face failed
+    #[error("WebSocket error: {0}")]
+    WebSocket(Box<tungstenite::Error>),
+
+    /// The socket of the face failed
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl FaceError {
+    /// Error of an Interest Nacked for `reason`
+    pub fn nacked(reason: NackReason) -> Self {
+        match reason {
+            NackReason::NoRoute => FaceError::NoRoute,
+            reason => FaceError::Nacked(reason),
+        }
+    }
+
+    /// Whether expressing the Interest again may succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            FaceError::Timeout | FaceError::QueueFull(_) | FaceError::Nacked(NackReason::Congestion)
+        )
+    }
+}
+
+impl From<tungstenite::Error> for FaceError {
+    fn from(e: tungstenite::Error) -> Self {
+        FaceError::WebSocket(Box::new(e))
+    }
+}
+
+/// Why a segmented object could not be fetched
+#[derive(Debug, Error)]
+pub enum FetchError {
+    /// A segment got no Data, even after retrying
+    #[error("{name} timed out")]
+    Timeout { name: Name },
+
+    /// A segment was Nacked upstream, for NoRoute or another reason
+    #[error("{name} was Nacked: {reason}")]
+    Nacked { name: Name, reason: NackReason },
+
+    /// Expressing a segment Interest failed for another reason
+    #[error("Failed to fetch {name}: {source}")]
+    Face { name: Name, source: FaceError },
+
+    /// No segment told where the object ends
+    #[error("No FinalBlockId received for {0}")]
+    NoFinalBlock(Name),
+}
+
+impl FetchError {
+    /// Error of fetching `name` that failed with `error`
+    pub fn new(name: Name, error: FaceError) -> Self {
+        match error {
+            FaceError::Timeout => FetchError::Timeout { name },
+            FaceError::NoRoute => FetchError::Nacked {
+                name,
+                reason: NackReason::NoRoute,
+            },
+            FaceError::Nacked(reason) => FetchError::Nacked { name, reason },
+            source => FetchError::Face { name, source },
+        }
+    }
+}
+
+/// Why a repo could not be started or a repo command failed
+#[derive(Debug, Error)]
+pub enum RepoError {
+    /// The transport has no content store to keep inserted Data in
+    #[error("A repo requires the transport's content store")]
+    NoContentStore,
+
+    /// The repo answered that the command is malformed
+    #[error("Repo rejected {verb} command for {name}")]
+    Rejected { verb: String, name: Name },
+
+    /// A command or status could not be encoded or decoded
+    #[error("Malformed repo message: {0}")]
+    Encoding(#[from] bincode::Error),
+
+    /// The command went unanswered
+    #[error(transparent)]
+    Face(#[from] FaceError),
+
+    /// The Data to insert could not be fetched
+    #[error(transparent)]
+    Fetch(#[from] FetchError),
+
+    /// The fetched Data could not be stored
+    #[error(transparent)]
+    Store(#[from] rust_udcn_common::Error),
+}
+
+/// Why a key or certificate could not be created, stored or used
+#[derive(Debug, Error)]
+pub enum KeyChainError {
+    /// The key chain holds no key or certificate for the identity
+    #[error("No key for identity {0}")]
+    UnknownIdentity(Name),
+
+    /// A certificate does not carry a valid signature of its signer
+    #[error("Certificate {0} has an invalid signature")]
+    BadSignature(Name),
+
+    /// The bytes do not encode a certificate
+    #[error("Malformed certificate: {0}")]
+    Malformed(String),
+
+    /// Generating, loading or using a key failed
+    #[error("Key operation failed")]
+    Crypto,
+
+    /// A TLV or name of the certificate could not be decoded
+    #[error(transparent)]
+    Packet(#[from] rust_udcn_common::Error),
+
+    /// The key chain directory could not be read or written
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Why a server, client or listener could not be set up or connect
+#[derive(Debug, Error)]
+pub enum TransportError {
+    /// A certificate, key or CA file could not be read
+    #[error("Failed to read {}: {source}", path.display())]
+    ReadFile { path: PathBuf, source: io::Error },
+
+    /// A certificate, key or CA file holds nothing usable
+    #[error("Invalid {what} in {}", path.display())]
+    Certificate { what: &'static str, path: PathBuf },
+
+    /// rustls refused the certificates or keys
+    #[error("TLS configuration error: {0}")]
+    Tls(#[from] rustls::Error),
+
+    /// The options contradict each other or miss something required
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    /// An address could not be parsed or resolved
+    #[error("Invalid address: {0}")]
+    Address(String),
+
+    /// A socket could not be bound
+    #[error("Failed to listen on {addr}: {source}")]
+    Bind { addr: String, source: io::Error },
+
+    /// A socket could not be connected
+    #[error("Failed to connect to {addr}: {source}")]
+    Connect { addr: String, source: io::Error },
+
+    /// The QUIC endpoint could not start connecting
+    #[error("Failed to connect: {0}")]
+    Endpoint(#[from] ConnectError),
+
+    /// The TLS handshake failed on a certificate, ours or the peer's
+    #[error("Certificate rejected: {0}")]
+    CertificateRejected(String),
+
+    /// The connection failed for another reason
+    #[error("Failed to connect: {0}")]
+    Connection(ConnectionError),
+
+    /// The repo log of the content store could not be opened
+    #[error("Failed to open content store repo: {0}")]
+    ContentStore(#[source] rust_udcn_common::Error),
+
+    /// Packets could not be captured on a network interface
+    #[error("Failed to capture on {interface}: {source}")]
+    Capture { interface: String, source: io::Error },
+
+    /// The listener or server no longer accepts faces
+    #[error("{0} stopped")]
+    Stopped(String),
+
+    /// A face of the transport failed
+    #[error(transparent)]
+    Face(#[from] FaceError),
+
+    /// A socket failed
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl From<ConnectionError> for TransportError {
+    fn from(e: ConnectionError) -> Self {
+        // TLS alerts travel as QUIC crypto errors 0x100 to 0x1ff
+        let code = match &e {
+            ConnectionError::TransportError(error) => Some(u64::from(error.code)),
+            ConnectionError::ConnectionClosed(close) => Some(u64::from(close.error_code)),
+            _ => None,
+        };
+        match code {
+            Some(code) if code & !0xff == 0x100 => TransportError::CertificateRejected(e.to_string()),
+            _ => TransportError::Connection(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nack_errors() {
+        assert!(matches!(FaceError::nacked(NackReason::NoRoute), FaceError::NoRoute));
+        assert!(matches!(
+            FaceError::nacked(NackReason::Duplicate),
+            FaceError::Nacked(NackReason::Duplicate)
+        ));
+        assert!(FaceError::nacked(NackReason::Congestion).is_transient());
+        assert!(!FaceError::NoRoute.is_transient());
+        assert_eq!(FaceError::Timeout.to_string(), "Interest timed out");
+    }
+
+    #[test]
+    fn test_fetch_errors() {
+        let name = Name::from_string("/a/b").unwrap();
+        assert!(matches!(
+            FetchError::new(name.clone(), FaceError::NoRoute),
+            FetchError::Nacked { reason: NackReason::NoRoute, .. }
+        ));
+        assert!(matches!(
+            FetchError::new(name.clone(), FaceError::Timeout),
+            FetchError::Timeout { .. }
+        ));
+        assert!(matches!(
+            FetchError::new(name, FaceError::Closed),
+            FetchError::Face { source: FaceError::Closed, .. }
+        ));
+    }
+}
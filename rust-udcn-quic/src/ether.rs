@@ -0,0 +1,215 @@
+//! Ethernet multicast faces over AF_PACKET sockets.
+//!
+//! Packets are sent in Ethernet frames with the NDN EtherType to the NDN
+//! multicast group, as NFD's multicast Ethernet faces do, so neighbors on
+//! the same LAN can reach each other without any IP configuration. Opening
+//! the socket requires CAP_NET_RAW.
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use log::{debug, info};
+use rust_udcn_common::metrics::UdcnMetrics;
+use std::{
+    ffi::CString,
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::Arc,
+};
+use tokio::io::unix::AsyncFd;
+
+use crate::{
+    error::{FaceError, TransportError},
+    link::Link,
+    link_face::LinkFace,
+    packet::split_stream_packet,
+};
+
+/// EtherType assigned to NDN
+pub const NDN_ETHERTYPE: u16 = 0x8624;
+
+/// Multicast group NDN nodes on a LAN listen to
+pub const NDN_ETHER_MULTICAST_ADDR: [u8; 6] = [0x01, 0x00, 0x5e, 0x00, 0x17, 0xaa];
+
+/// Largest NDN packet that fits into a standard Ethernet frame
+const ETHERNET_MTU: usize = 1500;
+
+/// Format a MAC address as colon-separated hex
+pub(crate) fn format_mac(addr: &[u8; 6]) -> String {
+    addr.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+/// Link-layer address of `group` on the interface with index `ifindex`
+fn link_addr(ifindex: i32, group: &[u8; 6]) -> libc::sockaddr_ll {
+    // SAFETY: sockaddr_ll is plain old data, all zeroes is a valid value
+    let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+    addr.sll_family = libc::AF_PACKET as u16;
+    addr.sll_protocol = NDN_ETHERTYPE.to_be();
+    addr.sll_ifindex = ifindex;
+    addr.sll_halen = 6;
+    addr.sll_addr[..6].copy_from_slice(group);
+    addr
+}
+
+fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Link sending to and receiving from an Ethernet multicast group
+pub struct EthernetLink {
+    /// Non-blocking packet socket bound to the interface
+    socket: AsyncFd<OwnedFd>,
+    
+    /// Link-layer destination of every frame sent
+    destination: libc::sockaddr_ll,
+}
+
+impl EthernetLink {
+    /// Open a packet socket on `interface` and join `group`
+    pub fn open(interface: &str, group: [u8; 6]) -> Result<Self, TransportError> {
+        let bind_error = |source| TransportError::Bind {
+            addr: interface.to_string(),
+            source,
+        };
+        let name = CString::new(interface).map_err(|_| TransportError::Address(interface.to_string()))?;
+        // SAFETY: name is a valid NUL-terminated string
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(bind_error(io::Error::new(io::ErrorKind::NotFound, "unknown network interface")));
+        }
+        let ifindex = ifindex as i32;
+
+        // SAFETY: plain socket(2) call, the descriptor is owned right away
+        let socket = unsafe {
+            let fd = check(libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                NDN_ETHERTYPE.to_be() as libc::c_int,
+            ))
+            .map_err(|e| bind_error(io::Error::new(e.kind(), format!("{} (CAP_NET_RAW required)", e))))?;
+            OwnedFd::from_raw_fd(fd)
+        };
+
+        let bind_addr = link_addr(ifindex, &[0; 6]);
+        // SAFETY: bind_addr is a valid sockaddr_ll of the given size
+        check(unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                &bind_addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        })
+        .map_err(bind_error)?;
+
+        let mut membership = libc::packet_mreq {
+            mr_ifindex: ifindex,
+            mr_type: libc::PACKET_MR_MULTICAST as u16,
+            mr_alen: 6,
+            mr_address: [0; 8],
+        };
+        membership.mr_address[..6].copy_from_slice(&group);
+        // SAFETY: membership is a valid packet_mreq of the given size
+        check(unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_PACKET,
+                libc::PACKET_ADD_MEMBERSHIP,
+                &membership as *const libc::packet_mreq as *const libc::c_void,
+                mem::size_of::<libc::packet_mreq>() as libc::socklen_t,
+            )
+        })
+        .map_err(|e| bind_error(io::Error::new(e.kind(), format!("failed to join {}: {}", format_mac(&group), e))))?;
+
+        info!("Joined NDN multicast group {} on {}", format_mac(&group), interface);
+
+        Ok(Self {
+            socket: AsyncFd::new(socket).map_err(bind_error)?,
+            destination: link_addr(ifindex, &group),
+        })
+    }
+}
+
+#[async_trait]
+impl Link for EthernetLink {
+    async fn send(&self, packet: Bytes) -> Result<(), FaceError> {
+        if packet.len() > ETHERNET_MTU {
+            return Err(FaceError::TooLarge {
+                size: packet.len(),
+                limit: ETHERNET_MTU,
+            });
+        }
+
+        loop {
+            let mut guard = self.socket.writable().await?;
+            let result = guard.try_io(|socket| {
+                // SAFETY: packet and destination outlive the call
+                check(unsafe {
+                    libc::sendto(
+                        socket.as_raw_fd(),
+                        packet.as_ptr() as *const libc::c_void,
+                        packet.len(),
+                        0,
+                        &self.destination as *const libc::sockaddr_ll as *const libc::sockaddr,
+                        mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+                    ) as libc::c_int
+                })
+            });
+            match result {
+                Ok(sent) => return sent.map(|_| ()).map_err(Into::into),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    async fn recv(&self) -> Result<Option<Bytes>, FaceError> {
+        let mut buf = BytesMut::zeroed(ETHERNET_MTU);
+
+        loop {
+            let mut guard = self.socket.readable().await?;
+            let result = guard.try_io(|socket| {
+                // SAFETY: buf is valid for writes of its full length
+                let len = unsafe {
+                    libc::recv(socket.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+                };
+                check(len as libc::c_int).map(|_| len as usize)
+            });
+            let len = match result {
+                Ok(received) => received?,
+                Err(_would_block) => continue,
+            };
+
+            // Short frames are padded, so trim to the TLV length
+            let mut frame = BytesMut::from(&buf[..len]);
+            match split_stream_packet(&mut frame) {
+                Ok(Some(packet)) => return Ok(Some(packet)),
+                _ => debug!("Dropping malformed Ethernet frame of {} bytes", len),
+            }
+        }
+    }
+
+    async fn close(&self) {}
+}
+
+/// Open a face to the NDN multicast group on `interface`
+pub fn open_ethernet_multicast(interface: &str) -> Result<LinkFace, TransportError> {
+    let link = EthernetLink::open(interface, NDN_ETHER_MULTICAST_ADDR)?;
+    let face_id = format!("ether://[{}]/{}", format_mac(&NDN_ETHER_MULTICAST_ADDR), interface);
+
+    Ok(LinkFace::new(face_id, Arc::new(link), Arc::new(UdcnMetrics::new())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_addr() {
+        let addr = link_addr(3, &NDN_ETHER_MULTICAST_ADDR);
+        assert_eq!(u16::from_be(addr.sll_protocol), NDN_ETHERTYPE);
+        assert_eq!(&addr.sll_addr[..6], &NDN_ETHER_MULTICAST_ADDR);
+        assert_eq!(format_mac(&NDN_ETHER_MULTICAST_ADDR), "01:00:5e:00:17:aa");
+    }
+}
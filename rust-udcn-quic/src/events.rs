@@ -0,0 +1,153 @@
+//! Subscriptions to transport events.
+//!
+//! Every subscriber receives its own copy of the events it is interested in,
+//! so separate tasks can, for example, handle Interests and monitor faces
+//! independently. Subscribers that fall behind lose the oldest events rather
+//! than stalling the transport.
+
+use log::warn;
+use tokio::sync::broadcast;
+
+use crate::transport::TransportEvent;
+
+/// Kinds of transport events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// [`TransportEvent::FaceCreated`]
+    FaceCreated,
+
+    /// [`TransportEvent::FaceClosed`]
+    FaceClosed,
+
+    /// [`TransportEvent::InterestReceived`]
+    Interest,
+
+    /// [`TransportEvent::DataReceived`]
+    Data,
+
+    /// [`TransportEvent::NackReceived`]
+    Nack,
+
+    /// [`TransportEvent::Error`]
+    Error,
+}
+
+impl TransportEvent {
+    /// Kind of this event
+    pub fn kind(&self) -> EventKind {
+        match self {
+            TransportEvent::FaceCreated(_) => EventKind::FaceCreated,
+            TransportEvent::FaceClosed(_) => EventKind::FaceClosed,
+            TransportEvent::InterestReceived { .. } => EventKind::Interest,
+            TransportEvent::DataReceived { .. } => EventKind::Data,
+            TransportEvent::NackReceived { .. } => EventKind::Nack,
+            TransportEvent::Error(_) => EventKind::Error,
+        }
+    }
+
+    /// ID of the face the event concerns, if any
+    pub fn face_id(&self) -> Option<&str> {
+        match self {
+            TransportEvent::FaceCreated(face_id) | TransportEvent::FaceClosed(face_id) => Some(face_id),
+            TransportEvent::InterestReceived { face_id, .. }
+            | TransportEvent::DataReceived { face_id, .. }
+            | TransportEvent::NackReceived { face_id, .. } => Some(face_id),
+            TransportEvent::Error(_) => None,
+        }
+    }
+}
+
+/// Selects the events delivered to a subscription. The default filter
+/// accepts every event.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    /// Only events concerning this face
+    pub face_id: Option<String>,
+
+    /// Only events of these kinds (all kinds if empty)
+    pub kinds: Vec<EventKind>,
+}
+
+impl EventFilter {
+    /// Accept only events concerning `face_id`
+    pub fn face(mut self, face_id: impl Into<String>) -> Self {
+        self.face_id = Some(face_id.into());
+        self
+    }
+
+    /// Also accept events of `kind`
+    pub fn kind(mut self, kind: EventKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// Whether `event` passes the filter
+    pub fn matches(&self, event: &TransportEvent) -> bool {
+        let face_matches = match &self.face_id {
+            Some(face_id) => event.face_id() == Some(face_id.as_str()),
+            None => true,
+        };
+        face_matches && (self.kinds.is_empty() || self.kinds.contains(&event.kind()))
+    }
+}
+
+/// An independent stream of transport events
+#[derive(Debug)]
+pub struct EventSubscription {
+    receiver: broadcast::Receiver<TransportEvent>,
+    filter: EventFilter,
+}
+
+impl EventSubscription {
+    pub(crate) fn new(receiver: broadcast::Receiver<TransportEvent>, filter: EventFilter) -> Self {
+        Self { receiver, filter }
+    }
+
+    /// Get the next matching event, or None once the transport is gone
+    pub async fn recv(&mut self) -> Option<TransportEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.filter.matches(&event) => return Some(event),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Event subscriber fell behind and missed {} events", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_filtered_subscriptions() {
+        let (sender, _) = broadcast::channel(16);
+        let mut all = EventSubscription::new(sender.subscribe(), EventFilter::default());
+        let mut closed = EventSubscription::new(
+            sender.subscribe(),
+            EventFilter::default().face("quic:b").kind(EventKind::FaceClosed),
+        );
+
+        for event in [
+            TransportEvent::FaceCreated("quic:a".to_string()),
+            TransportEvent::FaceClosed("quic:a".to_string()),
+            TransportEvent::FaceClosed("quic:b".to_string()),
+        ] {
+            sender.send(event).unwrap();
+        }
+        drop(sender);
+
+        let mut count = 0;
+        while all.recv().await.is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 3);
+
+        let event = closed.recv().await.unwrap();
+        assert_eq!(event.face_id(), Some("quic:b"));
+        assert!(closed.recv().await.is_none());
+    }
+}
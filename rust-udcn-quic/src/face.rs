@@ -2,29 +2,43 @@
 //!
 //! This module provides an implementation of NDN faces that operate over QUIC connections.
 
-use anyhow::{anyhow, Context, Result};
 use bytes::{Bytes, BytesMut};
 use futures::{SinkExt, StreamExt};
 use log::{debug, error, info, trace, warn};
-use quinn::{Connection, ConnectionError, RecvStream, SendStream, StreamId};
+use quinn::{Connection, ConnectionError, ReadExactError, RecvStream, SendStream, StreamId};
 use rust_udcn_common::{
-    ndn::{Data, Interest, InterestResult, Name},
-    metrics::UdcnMetrics,
+    ndn::{ChildSelector, Data, Interest, InterestResult, Nack, NackReason, Name},
+    metrics::{Counter, Family, Labels, PacketType, Timer, UdcnMetrics},
+    Error,
 };
 use std::{
-    collections::{HashMap, VecDeque},
-    sync::Arc,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 use tokio::{
     sync::{mpsc, oneshot, Mutex, RwLock},
     time::timeout,
 };
+use tracing::instrument;
 
 use crate::{
-    fragmentation::{assemble_fragments, fragment_packet},
-    packet::NdnPacket,
-    DEFAULT_FRAGMENT_SIZE, DEFAULT_INTEREST_TIMEOUT_MS,
+    error::FaceError,
+    forwarder::{RIB_REGISTER_PREFIX, RIB_UNREGISTER_PREFIX},
+    fragmentation::{
+        adaptive_fragment_size, fragment_packet, needs_fragmentation, parse_fragment, Reassembler,
+        ReassemblyOptions, FRAGMENT_HEADER_SIZE, FRAGMENT_MARKER,
+    },
+    identity::{self, PeerIdentity},
+    packet::{NdnPacket, FRAMED_STREAM_MARKER, MAX_FRAME_SIZE},
+    send_queue::{SendPriority, SendQueue, DEFAULT_MAX_INFLIGHT_STREAMS, DEFAULT_SEND_QUEUE_CAPACITY},
+    version::ProtocolVersion,
+    CLOSE_CODE_GOAWAY, CLOSE_CODE_NORMAL, DEFAULT_FRAGMENT_SIZE, DEFAULT_INTEREST_TIMEOUT_MS,
 };
 
 /// Events emitted by a Face
@@ -36,6 +50,9 @@ pub enum FaceEvent {
     /// A new Data packet was received
     DataReceived(Data),
     
+    /// A network Nack was received
+    NackReceived(Nack),
+    
     /// The face was closed
     Closed,
     
@@ -43,6 +60,391 @@ pub enum FaceEvent {
     Error(String),
 }
 
+/// How a face carries NDN packets over its QUIC connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PacketMode {
+    /// Open a new bidirectional stream for every packet
+    #[default]
+    Stream,
+    
+    /// Send packets that fit in one QUIC DATAGRAM frame as datagrams,
+    /// falling back to a stream for larger packets
+    Datagram,
+    
+    /// Send all packets on a single long-lived stream, each prefixed
+    /// with its length
+    PersistentStream,
+}
+
+/// Retransmission of unanswered Interests in [`Face::express_interest`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetransmissionPolicy {
+    /// Maximum number of retransmissions after the first transmission
+    pub max_retries: u32,
+    
+    /// Time to wait for Data before the first retransmission (in milliseconds)
+    pub initial_timeout_ms: u64,
+    
+    /// Factor applied to the wait after each retransmission
+    pub backoff_factor: f64,
+    
+    /// Upper bound on the wait between retransmissions (in milliseconds)
+    pub max_timeout_ms: u64,
+}
+
+impl Default for RetransmissionPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            initial_timeout_ms: 1000,
+            backoff_factor: 2.0,
+            max_timeout_ms: 4000,
+        }
+    }
+}
+
+impl RetransmissionPolicy {
+    /// A policy that never retransmits
+    pub fn disabled() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Time to wait after transmission number `attempt` (starting at 0)
+    pub fn timeout_for(&self, attempt: u32) -> Duration {
+        let wait = self.initial_timeout_ms as f64 * self.backoff_factor.powi(attempt as i32);
+        Duration::from_millis(wait.min(self.max_timeout_ms as f64) as u64)
+    }
+}
+
+/// Interval at which expired pending Interests are swept
+pub(crate) const PENDING_SWEEP_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Room left in the event channel by events that may be dropped, so the
+/// Closed event still fits when nobody reads the events
+const EVENT_CHANNEL_HEADROOM: usize = 1;
+
+/// A caller waiting for the Data of a pending Interest
+#[derive(Debug)]
+struct Waiter {
+    id: u64,
+    sender: oneshot::Sender<InterestResult>,
+    expiry: Instant,
+}
+
+/// Selectors of an expressed Interest: callers only share an outstanding
+/// Interest of the same name when these match too
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Selectors {
+    can_be_prefix: bool,
+    must_be_fresh: bool,
+    child_selector: ChildSelector,
+}
+
+impl Selectors {
+    fn of(interest: &Interest) -> Self {
+        Self {
+            can_be_prefix: interest.can_be_prefix,
+            must_be_fresh: interest.must_be_fresh,
+            child_selector: interest.child_selector,
+        }
+    }
+}
+
+/// Outstanding Interests by name and selectors, each with every caller
+/// waiting on it
+#[derive(Debug, Default)]
+pub(crate) struct PendingInterests {
+    entries: HashMap<Name, HashMap<Selectors, Vec<Waiter>>>,
+    next_waiter_id: u64,
+}
+
+impl PendingInterests {
+    /// Add a waiter for `interest` that expires at `expiry`.
+    ///
+    /// Returns the waiter ID, its receiver, and whether the same Interest
+    /// was already outstanding.
+    pub(crate) fn add(&mut self, interest: &Interest, expiry: Instant) -> (u64, oneshot::Receiver<InterestResult>, bool) {
+        let (sender, receiver) = oneshot::channel();
+        let id = self.next_waiter_id;
+        self.next_waiter_id += 1;
+        
+        let waiters = self
+            .entries
+            .entry(interest.name.clone())
+            .or_default()
+            .entry(Selectors::of(interest))
+            .or_default();
+        let aggregated = !waiters.is_empty();
+        waiters.push(Waiter { id, sender, expiry });
+        (id, receiver, aggregated)
+    }
+
+    /// Remove a single waiter, e.g. after it gave up
+    pub(crate) fn remove_waiter(&mut self, interest: &Interest, id: u64) {
+        let Some(entries) = self.entries.get_mut(&interest.name) else {
+            return;
+        };
+        let selectors = Selectors::of(interest);
+        if let Some(waiters) = entries.get_mut(&selectors) {
+            waiters.retain(|w| w.id != id);
+            if waiters.is_empty() {
+                entries.remove(&selectors);
+            }
+        }
+        if entries.is_empty() {
+            self.entries.remove(&interest.name);
+        }
+    }
+
+    /// Whether waiter `id` retransmits `interest`: the oldest waiter whose
+    /// caller is still there does, so one retransmission goes out per
+    /// timeout however many callers share the Interest
+    pub(crate) fn retransmits(&self, interest: &Interest, id: u64) -> bool {
+        self.entries
+            .get(&interest.name)
+            .and_then(|entries| entries.get(&Selectors::of(interest)))
+            .and_then(|waiters| waiters.iter().find(|w| !w.sender.is_closed()))
+            .is_some_and(|w| w.id == id)
+    }
+
+    /// Resolve every waiter Data named `name` satisfies: those of Interests
+    /// for that very name, and those of CanBePrefix Interests for its
+    /// prefixes. Returns how many were waiting.
+    pub(crate) fn resolve(&mut self, name: &Name, result: InterestResult) -> usize {
+        let mut count = 0;
+        for len in 0..=name.len() {
+            let key = name.prefix(len);
+            let Some(entries) = self.entries.get_mut(&key) else {
+                continue;
+            };
+            let satisfied: Vec<Selectors> = entries
+                .keys()
+                .filter(|selectors| len == name.len() || selectors.can_be_prefix)
+                .copied()
+                .collect();
+            for waiter in satisfied.iter().flat_map(|selectors| entries.remove(selectors)).flatten() {
+                let _ = waiter.sender.send(result.clone());
+                count += 1;
+            }
+            if entries.is_empty() {
+                self.entries.remove(&key);
+            }
+        }
+        count
+    }
+
+    /// Resolve the waiters of `interest` alone, e.g. when it was Nacked.
+    /// Returns how many were waiting.
+    pub(crate) fn resolve_interest(&mut self, interest: &Interest, result: InterestResult) -> usize {
+        let Some(entries) = self.entries.get_mut(&interest.name) else {
+            return 0;
+        };
+        let waiters = entries.remove(&Selectors::of(interest)).unwrap_or_default();
+        if entries.is_empty() {
+            self.entries.remove(&interest.name);
+        }
+        let count = waiters.len();
+        for waiter in waiters {
+            let _ = waiter.sender.send(result.clone());
+        }
+        count
+    }
+
+    /// Time out expired waiters and drop waiters whose caller went away.
+    /// Returns how many waiters were removed.
+    pub(crate) fn expire(&mut self, now: Instant) -> usize {
+        let mut removed = 0;
+        for entries in self.entries.values_mut() {
+            for waiters in entries.values_mut() {
+                let before = waiters.len();
+                let (expired, live): (Vec<Waiter>, Vec<Waiter>) = waiters
+                    .drain(..)
+                    .partition(|w| w.expiry <= now || w.sender.is_closed());
+                *waiters = live;
+                removed += before - waiters.len();
+                for waiter in expired {
+                    let _ = waiter.sender.send(InterestResult::Timeout);
+                }
+            }
+            entries.retain(|_, waiters| !waiters.is_empty());
+        }
+        self.entries.retain(|_, entries| !entries.is_empty());
+        removed
+    }
+
+    /// Number of outstanding Interests
+    pub(crate) fn len(&self) -> usize {
+        self.entries.values().map(HashMap::len).sum()
+    }
+
+    /// Fail every outstanding Interest
+    pub(crate) fn drain(&mut self, reason: &str) {
+        for waiter in self.entries.drain().flat_map(|(_, entries)| entries).flat_map(|(_, waiters)| waiters) {
+            let _ = waiter.sender.send(InterestResult::Dropped(reason.to_string()));
+        }
+    }
+}
+
+/// Snapshot of the traffic statistics of one face
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceStats {
+    /// Face ID
+    pub face_id: String,
+    
+    /// Interests sent (including retransmissions and forwarded Interests)
+    pub interests_sent: u64,
+    
+    /// Interests received
+    pub interests_received: u64,
+    
+    /// Expressed Interests answered with Data
+    pub interests_satisfied: u64,
+    
+    /// Expressed Interests that timed out
+    pub interests_timed_out: u64,
+    
+    /// Data packets sent
+    pub data_sent: u64,
+    
+    /// Data packets received
+    pub data_received: u64,
+    
+    /// Nacks sent
+    pub nacks_sent: u64,
+    
+    /// Nacks received
+    pub nacks_received: u64,
+    
+    /// Bytes sent
+    pub bytes_sent: u64,
+    
+    /// Bytes received
+    pub bytes_received: u64,
+    
+    /// Number of RTT samples
+    pub rtt_samples: u64,
+    
+    /// Average RTT of satisfied Interests (in microseconds)
+    pub rtt_avg_us: f64,
+    
+    /// Time since the face last sent or received a packet
+    pub idle: Duration,
+    
+    /// Outgoing streams currently open
+    pub streams_inflight: usize,
+    
+    /// Sends waiting for a stream
+    pub sends_queued: usize,
+    
+    /// Largest packet the path carries in one QUIC datagram (0 for faces
+    /// that are not on a QUIC connection)
+    pub mtu: usize,
+}
+
+/// Records a face's traffic in the metrics shared with the other faces of
+/// a client or server, both node-wide and in series labeled with the face
+#[derive(Debug, Clone)]
+pub(crate) struct FaceMetrics {
+    pub(crate) shared: Arc<UdcnMetrics>,
+    labels: Labels,
+}
+
+impl FaceMetrics {
+    pub(crate) fn new(shared: Arc<UdcnMetrics>, face_id: &str) -> Self {
+        Self {
+            shared,
+            labels: Labels::new().face(face_id),
+        }
+    }
+
+    /// Count `value` in a counter
+    pub(crate) fn add(&self, counter: impl Fn(&UdcnMetrics) -> &Family<Counter>, value: u64) {
+        counter(&self.shared).add_labeled(&self.labels, value);
+    }
+
+    /// Count `value` in a counter, for one kind of packet
+    pub(crate) fn add_packet(&self, counter: impl Fn(&UdcnMetrics) -> &Family<Counter>, packet: &NdnPacket, value: u64) {
+        let labels = self.labels.clone().packet_type(packet_type(packet));
+        counter(&self.shared).add_labeled(&labels, value);
+    }
+
+    /// Record `value` in a timer
+    pub(crate) fn observe(&self, timer: impl Fn(&UdcnMetrics) -> &Family<Timer>, value: u64) {
+        timer(&self.shared).observe_labeled(&self.labels, value);
+    }
+
+    /// The face's share of a counter
+    fn value(&self, counter: &Family<Counter>) -> u64 {
+        counter.sum(&self.labels)
+    }
+
+    /// Snapshot of the counters of the face
+    pub(crate) fn stats(&self, face_id: &str, idle: Duration) -> FaceStats {
+        let m = &self.shared;
+        let (rtt_samples, rtt_avg_us) = m
+            .interest_processing_time
+            .get(&self.labels)
+            .map_or((0, 0.0), |rtt| (rtt.histogram().count(), rtt.histogram().average()));
+        
+        FaceStats {
+            face_id: face_id.to_string(),
+            interests_sent: self.value(&m.interests_sent),
+            interests_received: self.value(&m.interests_received),
+            interests_satisfied: self.value(&m.interests_satisfied),
+            interests_timed_out: self.value(&m.interests_timed_out),
+            data_sent: self.value(&m.data_sent),
+            data_received: self.value(&m.data_received),
+            nacks_sent: self.value(&m.nacks_sent),
+            nacks_received: self.value(&m.nacks_received),
+            bytes_sent: self.value(&m.bytes_sent),
+            bytes_received: self.value(&m.bytes_received),
+            rtt_samples,
+            rtt_avg_us,
+            idle,
+            streams_inflight: 0,
+            sends_queued: 0,
+            mtu: 0,
+        }
+    }
+}
+
+/// Label value for the kind of a packet
+pub(crate) fn packet_type(packet: &NdnPacket) -> PacketType {
+    match packet {
+        NdnPacket::Interest(_) => PacketType::Interest,
+        NdnPacket::Data(_) => PacketType::Data,
+        NdnPacket::Nack(_) => PacketType::Nack,
+    }
+}
+
+/// Records when a face last sent or received an NDN packet
+#[derive(Debug)]
+pub(crate) struct ActivityClock {
+    created: Instant,
+    last_ms: AtomicU64,
+}
+
+impl ActivityClock {
+    pub(crate) fn new() -> Self {
+        Self {
+            created: Instant::now(),
+            last_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn touch(&self) {
+        let elapsed = self.created.elapsed().as_millis() as u64;
+        self.last_ms.fetch_max(elapsed, Ordering::Relaxed);
+    }
+
+    pub(crate) fn last(&self) -> Instant {
+        self.created + Duration::from_millis(self.last_ms.load(Ordering::Relaxed))
+    }
+}
+
 /// An NDN face over QUIC transport
 #[derive(Debug)]
 pub struct Face {
@@ -52,11 +454,14 @@ pub struct Face {
     /// QUIC connection
     connection: Connection,
     
+    /// Wire protocol version negotiated through ALPN
+    version: ProtocolVersion,
+    
     /// Whether the face is closed
     closed: Arc<Mutex<bool>>,
     
     /// Pending Interests waiting for Data
-    pending_interests: Arc<Mutex<HashMap<String, oneshot::Sender<InterestResult>>>>,
+    pending_interests: Arc<Mutex<PendingInterests>>,
     
     /// Receiver for face events
     event_receiver: Arc<Mutex<Option<mpsc::Receiver<FaceEvent>>>>,
@@ -65,7 +470,31 @@ pub struct Face {
     event_sender: Arc<Mutex<mpsc::Sender<FaceEvent>>>,
     
     /// Metrics for this face
-    metrics: Arc<UdcnMetrics>,
+    metrics: FaceMetrics,
+    
+    /// How outgoing packets are sent
+    packet_mode: Arc<RwLock<PacketMode>>,
+    
+    /// Long-lived outgoing stream (in persistent stream mode)
+    persistent_stream: Arc<Mutex<Option<SendStream>>>,
+    
+    /// Fragments of incoming messages awaiting reassembly
+    reassembler: Arc<Reassembler>,
+    
+    /// Identity of the peer, if it presented a certificate
+    peer_identity: Option<PeerIdentity>,
+    
+    /// ID of the next outgoing fragmented message
+    next_message_id: AtomicU32,
+    
+    /// Retransmission policy for expressed Interests
+    retx_policy: Arc<RwLock<RetransmissionPolicy>>,
+    
+    /// Time of the last packet sent or received
+    activity: Arc<ActivityClock>,
+    
+    /// Limits the outgoing streams in flight
+    send_queue: Arc<SendQueue>,
 }
 
 impl Face {
@@ -74,21 +503,53 @@ impl Face {
         id: String,
         connection: Connection,
         metrics: Arc<UdcnMetrics>,
+    ) -> Self {
+        let reassembler = Arc::new(Reassembler::new(ReassemblyOptions::default(), Arc::clone(&metrics)));
+        Self::new_with_reassembler(id, connection, metrics, reassembler)
+    }
+
+    /// Create a new face from a QUIC connection, reassembling the fragments
+    /// it receives in `reassembler`, which other faces may share
+    pub(crate) fn new_with_reassembler(
+        id: String,
+        connection: Connection,
+        metrics: Arc<UdcnMetrics>,
+        reassembler: Arc<Reassembler>,
     ) -> Self {
         let (event_sender, event_receiver) = mpsc::channel(100);
         
+        let version = negotiated_version(&connection);
+        debug!("[Face {}] Negotiated protocol {}", id, version);
+        let metrics = FaceMetrics::new(metrics, &id);
+        let peer_identity = PeerIdentity::of_connection(&connection);
+        
         let face = Self {
+            send_queue: Arc::new(SendQueue::new(
+                id.clone(),
+                DEFAULT_MAX_INFLIGHT_STREAMS,
+                DEFAULT_SEND_QUEUE_CAPACITY,
+            )),
             id,
             connection,
+            version,
             closed: Arc::new(Mutex::new(false)),
-            pending_interests: Arc::new(Mutex::new(HashMap::new())),
+            pending_interests: Arc::new(Mutex::new(PendingInterests::default())),
             event_receiver: Arc::new(Mutex::new(Some(event_receiver))),
             event_sender: Arc::new(Mutex::new(event_sender)),
             metrics,
+            packet_mode: Arc::new(RwLock::new(version.default_packet_mode())),
+            persistent_stream: Arc::new(Mutex::new(None)),
+            reassembler,
+            peer_identity,
+            next_message_id: AtomicU32::new(0),
+            retx_policy: Arc::new(RwLock::new(RetransmissionPolicy::default())),
+            activity: Arc::new(ActivityClock::new()),
         };
         
-        // Start processing incoming streams
+        // Start processing incoming streams and datagrams
         face.process_incoming_streams();
+        face.process_incoming_datagrams();
+        face.expire_pending_interests();
         
         face
     }
@@ -98,38 +559,200 @@ impl Face {
         &self.id
     }
 
-    /// Send an Interest and wait for Data
+    /// Certificate chain the peer presented during the TLS handshake, leaf first.
+    ///
+    /// Servers only receive one when client authentication is configured.
+    pub fn peer_certificates(&self) -> Option<Vec<rustls::Certificate>> {
+        identity::peer_certificates(&self.connection)
+    }
+
+    /// Identity of the peer, from the certificate it presented
+    ///
+    /// Server faces of authenticated peers take their ID from it.
+    pub fn peer_identity(&self) -> Option<&PeerIdentity> {
+        self.peer_identity.as_ref()
+    }
+
+    /// Wire protocol version negotiated with the peer
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Remote address of the underlying connection
+    pub fn remote_address(&self) -> std::net::SocketAddr {
+        self.connection.remote_address()
+    }
+
+    /// Whether the peer connected over loopback, from this host
+    pub fn is_local(&self) -> bool {
+        is_loopback(self.connection.remote_address().ip())
+    }
+
+    /// Largest packet the path of the connection carries in one QUIC
+    /// datagram, as quinn currently estimates it
+    ///
+    /// The estimate follows the UDP payload size both ends accept. When
+    /// the peer takes no datagrams quinn does not tell, and the face
+    /// assumes a path that carries [`DEFAULT_FRAGMENT_SIZE`] byte fragments.
+    pub fn effective_mtu(&self) -> usize {
+        self.connection
+            .max_datagram_size()
+            .unwrap_or(DEFAULT_FRAGMENT_SIZE + FRAGMENT_HEADER_SIZE)
+    }
+
+    /// Payload size of the fragments of the packets sent next, for the
+    /// current MTU and congestion window of the connection
+    pub fn fragment_size(&self) -> usize {
+        adaptive_fragment_size(self.effective_mtu(), self.connection.stats().path.cwnd)
+    }
+
+    /// Time the face last sent or received an NDN packet (or was created)
+    pub fn last_activity(&self) -> Instant {
+        self.activity.last()
+    }
+
+    /// How long the face has gone without sending or receiving a packet
+    pub fn idle_time(&self) -> Duration {
+        self.activity.last().elapsed()
+    }
+
+    /// Metrics the face records in, its own series labeled with its ID
+    pub fn metrics(&self) -> Arc<UdcnMetrics> {
+        Arc::clone(&self.metrics.shared)
+    }
+
+    /// Snapshot of this face's traffic statistics
+    pub fn stats(&self) -> FaceStats {
+        FaceStats {
+            streams_inflight: self.send_queue.inflight(),
+            sends_queued: self.send_queue.queued(),
+            mtu: self.effective_mtu(),
+            ..self.metrics.stats(&self.id, self.idle_time())
+        }
+    }
+
+    /// Limit the outgoing streams in flight to `max_inflight`, with at most
+    /// `capacity` sends waiting for a stream.
+    ///
+    /// Sends beyond that fail with [`SendQueueFull`](crate::SendQueueFull).
+    pub fn set_send_queue_limits(&self, max_inflight: usize, capacity: usize) {
+        self.send_queue.set_limits(max_inflight, capacity);
+    }
+
+    /// Wait until the underlying connection is closed, locally or by the peer
+    pub async fn wait_closed(&self) {
+        let reason = self.connection.closed().await;
+        trace!("[Face {}] Connection closed: {}", self.id, reason);
+    }
+
+    /// Get the mode used for outgoing packets
+    pub async fn packet_mode(&self) -> PacketMode {
+        *self.packet_mode.read().await
+    }
+
+    /// Set the mode used for outgoing packets.
+    ///
+    /// Incoming packets are accepted in every mode regardless of this setting.
+    /// Modes the negotiated protocol version does not support are rejected.
+    pub async fn set_packet_mode(&self, mode: PacketMode) -> Result<(), FaceError> {
+        if !self.version.supports(mode) {
+            return Err(FaceError::UnsupportedMode {
+                mode,
+                version: self.version,
+            });
+        }
+        debug!("[Face {}] Packet mode set to {:?}", self.id, mode);
+        *self.packet_mode.write().await = mode;
+        Ok(())
+    }
+
+    /// Get the retransmission policy for expressed Interests
+    pub async fn retransmission_policy(&self) -> RetransmissionPolicy {
+        self.retx_policy.read().await.clone()
+    }
+
+    /// Set the retransmission policy for expressed Interests
+    pub async fn set_retransmission_policy(&self, policy: RetransmissionPolicy) {
+        *self.retx_policy.write().await = policy;
+    }
+
+    /// Send an Interest and wait for Data.
+    ///
+    /// Unanswered Interests are retransmitted with a fresh nonce according to
+    /// the face's [`RetransmissionPolicy`]; `timeout_ms` bounds the total wait.
+    /// A caller sharing an Interest already outstanding takes over its
+    /// retransmissions once every earlier caller gave up.
+    #[instrument(skip_all, fields(name = %interest.name(), face = %self.id))]
     pub async fn express_interest(
         &self,
-        interest: Interest,
+        mut interest: Interest,
         timeout_ms: u64,
-    ) -> Result<Data> {
+    ) -> Result<Data, FaceError> {
         // Check if the face is closed
         if *self.closed.lock().await {
-            return Err(anyhow!("Face is closed"));
+            return Err(FaceError::Closed);
         }
         
         let name = interest.name().to_string();
         
         debug!("[Face {}] Express Interest: {}", self.id, name);
         
-        // Create a channel for receiving the Data
-        let (sender, receiver) = oneshot::channel();
+        // Register as a waiter; callers for a name that is already
+        // outstanding share its transmission instead of sending again
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(timeout_ms);
+        let (waiter_id, mut receiver, aggregated) = self.pending_interests.lock().await.add(&interest, deadline);
         
-        // Store the sender in our pending interests
-        self.pending_interests.lock().await.insert(name.clone(), sender);
+        let policy = self.retx_policy.read().await.clone();
         
-        // Start a timer to track processing time
-        let start = Instant::now();
+        let mut attempt = 0;
         
-        // Send the Interest packet
-        self.send_packet(NdnPacket::Interest(interest.clone())).await?;
+        if aggregated {
+            debug!("[Face {}] Aggregated Interest: {}", self.id, name);
+            self.metrics.add(|m| &m.interests_aggregated, 1);
+        }
         
-        // Increment the counter
-        self.metrics.interests_sent.increment();
+        // An aggregated caller waits on the transmission already out
+        let mut send = !aggregated;
+        let outcome = loop {
+            if send {
+                // Send the Interest packet
+                if let Err(e) = self.send_packet(NdnPacket::Interest(interest.clone())).await {
+                    self.pending_interests.lock().await.remove_waiter(&interest, waiter_id);
+                    return Err(e);
+                }
+                
+                // Increment the counter
+                self.metrics.add(|m| &m.interests_sent, 1);
+            }
+            
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let retry_allowed = attempt < policy.max_retries;
+            let wait = if retry_allowed {
+                policy.timeout_for(attempt).min(remaining)
+            } else {
+                remaining
+            };
+            
+            match timeout(wait, &mut receiver).await {
+                Ok(result) => break Ok(result),
+                Err(_) if retry_allowed && Instant::now() < deadline => {
+                    attempt += 1;
+                    interest.nonce = fresh_nonce(interest.nonce);
+                    send = self.pending_interests.lock().await.retransmits(&interest, waiter_id);
+                    if send {
+                        self.metrics.add(|m| &m.interests_retransmitted, 1);
+                        debug!(
+                            "[Face {}] Retransmitting Interest {} (attempt {}, nonce {:08x})",
+                            self.id, name, attempt + 1, interest.nonce
+                        );
+                    }
+                }
+                Err(elapsed) => break Err(elapsed),
+            }
+        };
         
-        // Wait for the Data with a timeout
-        let result = match timeout(Duration::from_millis(timeout_ms), receiver).await {
+        let result = match outcome {
             Ok(result) => match result {
                 Ok(InterestResult::Data(data)) => {
                     // Measure the RTT
@@ -137,60 +760,112 @@ impl Face {
                     debug!("[Face {}] Received Data for {}, RTT: {}µs", self.id, name, rtt);
                     
                     // Record the RTT in the metrics
-                    self.metrics.interest_processing_time.histogram().observe(rtt);
+                    self.metrics.observe(|m| &m.interest_processing_time, rtt);
                     
                     // Increment the counter
-                    self.metrics.interests_satisfied.increment();
+                    self.metrics.add(|m| &m.interests_satisfied, 1);
                     
                     Ok(data)
                 }
+                Ok(InterestResult::Nacked(reason)) => {
+                    debug!("[Face {}] Interest {} Nacked: {}", self.id, name, reason);
+                    Err(FaceError::nacked(reason))
+                }
                 Ok(InterestResult::Timeout) => {
                     debug!("[Face {}] Interest timed out: {}", self.id, name);
                     
                     // Increment the counter
-                    self.metrics.interests_timed_out.increment();
+                    self.metrics.add(|m| &m.interests_timed_out, 1);
                     
-                    Err(anyhow!("Interest timed out"))
+                    Err(FaceError::Timeout)
                 }
                 Ok(InterestResult::Dropped(err)) => {
                     debug!("[Face {}] Network error for Interest {}: {}", self.id, name, err);
-                    Err(anyhow!("Network error: {}", err))
+                    Err(FaceError::Dropped(err))
                 }
                 Err(_) => {
                     debug!("[Face {}] Channel closed for Interest {}", self.id, name);
-                    Err(anyhow!("Channel closed"))
+                    Err(FaceError::Closed)
                 }
             },
             Err(_) => {
-                debug!("[Face {}] Interest timed out: {}", self.id, name);
-                
-                // Remove from pending interests
-                self.pending_interests.lock().await.remove(&name);
+                debug!("[Face {}] Interest timed out after {} transmission(s): {}", self.id, attempt + 1, name);
                 
                 // Increment the counter
-                self.metrics.interests_timed_out.increment();
+                self.metrics.add(|m| &m.interests_timed_out, 1);
                 
-                Err(anyhow!("Interest timed out"))
+                Err(FaceError::Timeout)
             }
         };
         
         // Clean up the pending interest if still there
-        if !result.is_ok() {
-            self.pending_interests.lock().await.remove(&name);
+        if result.is_err() {
+            self.pending_interests.lock().await.remove_waiter(&interest, waiter_id);
         }
         
         result
     }
 
+    /// Send an Interest without waiting for Data.
+    ///
+    /// Used when forwarding: the Data is delivered as a face event.
+    pub async fn send_interest(&self, interest: Interest) -> Result<(), FaceError> {
+        debug!("[Face {}] Send Interest: {}", self.id, interest.name());
+        
+        self.send_packet(NdnPacket::Interest(interest)).await?;
+        
+        self.metrics.add(|m| &m.interests_sent, 1);
+        
+        Ok(())
+    }
+
+    /// Ask the remote forwarder to route Interests under `prefix` to this face
+    pub async fn register_prefix(&self, prefix: &Name) -> Result<(), FaceError> {
+        self.rib_command(RIB_REGISTER_PREFIX, prefix).await
+    }
+
+    /// Withdraw a prefix registration from the remote forwarder
+    pub async fn unregister_prefix(&self, prefix: &Name) -> Result<(), FaceError> {
+        self.rib_command(RIB_UNREGISTER_PREFIX, prefix).await
+    }
+
+    /// Send a RIB command Interest for `prefix`
+    async fn rib_command(&self, command: &str, prefix: &Name) -> Result<(), FaceError> {
+        let mut name = Name::from_string(command)?;
+        for component in prefix.components() {
+            name.push(component.clone());
+        }
+        
+        self.express_interest(Interest::new(name), DEFAULT_INTEREST_TIMEOUT_MS).await?;
+        
+        Ok(())
+    }
+
     /// Send a Data packet
-    pub async fn send_data(&self, data: Data) -> Result<()> {
+    pub async fn send_data(&self, data: Data) -> Result<(), FaceError> {
         debug!("[Face {}] Send Data: {}", self.id, data.name());
         
         // Send the Data packet
         self.send_packet(NdnPacket::Data(data)).await?;
         
         // Increment the counter
-        self.metrics.data_sent.increment();
+        self.metrics.add(|m| &m.data_sent, 1);
+        
+        Ok(())
+    }
+
+    /// Send a Nack for an Interest
+    pub async fn send_nack(&self, interest: Interest, reason: NackReason) -> Result<(), FaceError> {
+        self.send_nack_packet(Nack::new(interest, reason)).await
+    }
+
+    /// Send a Nack, as received from upstream or built by the caller
+    pub async fn send_nack_packet(&self, nack: Nack) -> Result<(), FaceError> {
+        debug!("[Face {}] Send Nack ({}): {}", self.id, nack.reason, nack.name());
+        
+        self.send_packet(NdnPacket::Nack(nack)).await?;
+        
+        self.metrics.add(|m| &m.nacks_sent, 1);
         
         Ok(())
     }
@@ -204,6 +879,11 @@ impl Face {
 
     /// Close the face
     pub async fn close(&self) {
+        self.close_with(CLOSE_CODE_NORMAL, b"Face closed").await;
+    }
+
+    /// Close the face, sending the peer an application close `code` and `reason`
+    pub async fn close_with(&self, code: u32, reason: &[u8]) {
         // Set the closed flag
         let mut closed = self.closed.lock().await;
         if *closed {
@@ -214,13 +894,10 @@ impl Face {
         debug!("[Face {}] Closing", self.id);
         
         // Close all streams
-        self.connection.close(0u32.into(), b"Face closed");
+        self.connection.close(code.into(), reason);
         
         // Notify all pending interests
-        let mut pending = self.pending_interests.lock().await;
-        for (name, sender) in pending.drain() {
-            let _ = sender.send(InterestResult::Dropped("Face closed".to_string()));
-        }
+        self.pending_interests.lock().await.drain("Face closed");
         
         // Send a closed event
         if let Ok(sender) = self.event_sender.lock().await.send(FaceEvent::Closed).await {
@@ -233,13 +910,41 @@ impl Face {
         *self.closed.lock().await
     }
 
+    /// Number of outstanding expressed Interests
+    pub async fn pending_interest_count(&self) -> usize {
+        self.pending_interests.lock().await.len()
+    }
+
+    /// Periodically expire pending Interests, so entries whose caller
+    /// stopped waiting do not accumulate
+    fn expire_pending_interests(&self) {
+        let closed = Arc::clone(&self.closed);
+        let pending_interests = Arc::clone(&self.pending_interests);
+        let id = self.id.clone();
+        
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PENDING_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                if *closed.lock().await {
+                    break;
+                }
+                
+                let removed = pending_interests.lock().await.expire(Instant::now());
+                if removed > 0 {
+                    trace!("[Face {}] Expired {} pending Interest waiter(s)", id, removed);
+                }
+            }
+        });
+    }
+
     /// Process incoming streams from the QUIC connection
     fn process_incoming_streams(&self) {
         let connection = self.connection.clone();
         let closed = Arc::clone(&self.closed);
         let pending_interests = Arc::clone(&self.pending_interests);
         let event_sender = Arc::clone(&self.event_sender);
-        let metrics = Arc::clone(&self.metrics);
+        let inbound = self.inbound();
         let id = self.id.clone();
         
         tokio::spawn(async move {
@@ -256,31 +961,23 @@ impl Face {
                 debug!("[Face {}] Accepted bi-directional stream {}", id, stream_id);
                 
                 // Process this stream
-                let _stream_closed = Arc::clone(&closed);
-                let stream_pending_interests = Arc::clone(&pending_interests);
-                let stream_event_sender = Arc::clone(&event_sender);
-                let stream_metrics = Arc::clone(&metrics);
-                let face_id_clone = id.clone();
+                let stream_inbound = inbound.clone();
                 
                 tokio::spawn(async move {
-                    if let Err(e) = process_stream(
-                        face_id_clone.clone(),
-                        stream_id,
-                        send,
-                        recv,
-                        stream_pending_interests,
-                        stream_event_sender,
-                        stream_metrics,
-                    ).await {
-                        warn!("[Face {}] Error processing stream {}: {}", face_id_clone, stream_id, e);
+                    if let Err(e) = stream_inbound.process_stream(stream_id, recv).await {
+                        warn!("[Face {}] Error processing stream {}: {}", stream_inbound.face_id, stream_id, e);
                     }
                 });
             }
             
             debug!("[Face {}] Stopped processing incoming streams", id);
+            inbound.reassembler.remove_face(&id);
 
             if let Some(err) = connection.close_reason() {
                 match err {
+                    ConnectionError::ApplicationClosed(close) if close.error_code == CLOSE_CODE_GOAWAY.into() => {
+                        info!("[Face {}] Peer is shutting down: {}", id, String::from_utf8_lossy(&close.reason));
+                    }
                     ConnectionError::ApplicationClosed { .. }
                     | ConnectionError::ConnectionClosed(_)
                     | ConnectionError::LocallyClosed
@@ -299,10 +996,7 @@ impl Face {
                 *closed_guard = true;
                 
                 // Notify all pending interests
-                let mut pending = pending_interests.lock().await;
-                for (name, sender) in pending.drain() {
-                    let _ = sender.send(InterestResult::Dropped("Connection closed".to_string()));
-                }
+                pending_interests.lock().await.drain("Connection closed");
                 
                 // Send a closed event
                 if let Ok(sender) = event_sender.lock().await.send(FaceEvent::Closed).await {
@@ -312,33 +1006,120 @@ impl Face {
         });
     }
 
+    /// Process incoming QUIC datagrams
+    fn process_incoming_datagrams(&self) {
+        let connection = self.connection.clone();
+        let inbound = self.inbound();
+        let id = self.id.clone();
+        
+        tokio::spawn(async move {
+            // Ends when the connection closes; stream processing reports the close
+            while let Ok(datagram) = connection.read_datagram().await {
+                inbound.metrics.add(|m| &m.bytes_received, datagram.len() as u64);
+                
+                if datagram.first() == Some(&FRAGMENT_MARKER) {
+                    let result = async {
+                        for message in inbound.reassemble(&datagram)? {
+                            for packet in NdnPacket::from_bytes_multi(&message)? {
+                                inbound.dispatch(packet).await?;
+                            }
+                        }
+                        Ok::<_, FaceError>(())
+                    }.await;
+                    if let Err(e) = result {
+                        warn!("[Face {}] Error processing fragmented datagram: {}", id, e);
+                    }
+                    continue;
+                }
+                
+                let packet = match NdnPacket::from_bytes(&datagram) {
+                    Ok(packet) => packet,
+                    Err(e) => {
+                        warn!("[Face {}] Invalid datagram: {}", id, e);
+                        continue;
+                    }
+                };
+                
+                trace!("[Face {}] Received datagram ({} bytes)", id, datagram.len());
+                
+                if let Err(e) = inbound.dispatch(packet).await {
+                    warn!("[Face {}] Error processing datagram: {}", id, e);
+                }
+            }
+            
+            debug!("[Face {}] Stopped processing incoming datagrams", id);
+        });
+    }
+
+    /// State needed by tasks handling received packets
+    fn inbound(&self) -> Inbound {
+        Inbound {
+            face_id: self.id.clone(),
+            pending_interests: Arc::clone(&self.pending_interests),
+            event_sender: Arc::clone(&self.event_sender),
+            metrics: self.metrics.clone(),
+            reassembler: Arc::clone(&self.reassembler),
+            activity: Arc::clone(&self.activity),
+        }
+    }
+
     /// Send a packet over the face
-    async fn send_packet(&self, packet: NdnPacket) -> Result<()> {
+    async fn send_packet(&self, packet: NdnPacket) -> Result<(), FaceError> {
         // Check if the face is closed
         if *self.closed.lock().await {
-            return Err(anyhow!("Face is closed"));
+            return Err(FaceError::Closed);
         }
         
         // Serialize the packet to bytes
         let bytes = packet.to_bytes()?;
         
         // Update metrics
-        self.metrics.bytes_sent.add(bytes.len() as u64);
+        self.metrics.add_packet(|m| &m.bytes_sent, &packet, bytes.len() as u64);
+        self.activity.touch();
+        
+        let mode = *self.packet_mode.read().await;
+        
+        if mode == PacketMode::PersistentStream {
+            return self.send_framed(&packet).await;
+        }
+        
+        // Small packets go out as a single datagram if the peer supports it
+        if mode == PacketMode::Datagram {
+            match self.connection.max_datagram_size() {
+                Some(max_size) if bytes.len() <= max_size => {
+                    trace!("[Face {}] Sending {} byte packet as datagram", self.id, bytes.len());
+                    self.connection.send_datagram(bytes)?;
+                    return Ok(());
+                }
+                Some(_) => {
+                    trace!("[Face {}] Packet of {} bytes too large for a datagram", self.id, bytes.len());
+                }
+                None => {
+                    trace!("[Face {}] Peer does not support datagrams", self.id);
+                }
+            }
+        }
+        
+        // Wait for a stream slot; fails right away if the queue is full
+        let _permit = self.send_queue.acquire(send_priority(&packet)).await?;
         
         // Open a new bi-directional stream
         let (mut send, _recv) = self.connection.open_bi().await?;
         
-        // Check if we need fragmentation
-        if bytes.len() > DEFAULT_FRAGMENT_SIZE {
+        // Check if we need fragmentation; fragments follow the path MTU
+        let fragment_size = self.fragment_size();
+        if needs_fragmentation(bytes.len(), fragment_size) {
             debug!(
                 "[Face {}] Fragmenting packet of size {} into chunks of {}",
                 self.id,
                 bytes.len(),
-                DEFAULT_FRAGMENT_SIZE
+                fragment_size
             );
             
             // Fragment the packet
-            let fragments = fragment_packet(&bytes, DEFAULT_FRAGMENT_SIZE);
+            let message_id = self.next_message_id.fetch_add(1, Ordering::Relaxed);
+            let fragments = fragment_packet(&bytes, fragment_size, message_id)?;
+            self.metrics.add(|m| &m.fragments_sent, fragments.len() as u64);
             
             // Send each fragment
             for fragment in fragments {
@@ -354,78 +1135,385 @@ impl Face {
         
         Ok(())
     }
-}
 
-/// Process a QUIC stream
-async fn process_stream(
-    face_id: String,
-    stream_id: StreamId,
-    _send: SendStream,
-    mut recv: RecvStream,
-    pending_interests: Arc<Mutex<HashMap<String, oneshot::Sender<InterestResult>>>>,
-    event_sender: Arc<Mutex<mpsc::Sender<FaceEvent>>>,
-    metrics: Arc<UdcnMetrics>,
-) -> Result<()> {
-    let _buffer = BytesMut::with_capacity(8192);
-    let mut fragments = VecDeque::new();
-    
-    // Read from the stream
-    while let Some(chunk) = recv.read_chunk(1024, false).await? {
-        // Update metrics
-        metrics.bytes_received.add(chunk.bytes.len() as u64);
+    /// Send a length-prefixed packet on the long-lived stream, opening it
+    /// if needed
+    async fn send_framed(&self, packet: &NdnPacket) -> Result<(), FaceError> {
+        let frame = packet.to_frame()?;
+        let mut stream = self.persistent_stream.lock().await;
+        
+        // Retry once on a fresh stream if the current one has failed
+        for attempt in 0..2 {
+            if stream.is_none() {
+                let (mut send, _recv) = self.connection.open_bi().await?;
+                send.write_all(&[FRAMED_STREAM_MARKER]).await?;
+                debug!("[Face {}] Opened persistent stream {}", self.id, send.id());
+                *stream = Some(send);
+            }
+            
+            let send = stream.as_mut().expect("persistent stream was just opened");
+            match send.write_all(&frame).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt == 0 => {
+                    warn!("[Face {}] Persistent stream failed, reopening: {}", self.id, e);
+                    *stream = None;
+                }
+                Err(e) => {
+                    *stream = None;
+                    return Err(e.into());
+                }
+            }
+        }
         
-        // Add to our fragments
-        fragments.push_back(chunk.bytes);
+        unreachable!("persistent stream send loop always returns")
     }
-    
-    // Try to assemble the fragments
-    let packet_bytes = assemble_fragments(fragments)?;
-    
-    // Parse as an NDN packet
-    let packet = NdnPacket::from_bytes(&packet_bytes)?;
-    
+}
+
+/// Protocol version selected by the TLS handshake. Peers that did not
+/// negotiate ALPN speak the original format.
+fn negotiated_version(connection: &Connection) -> ProtocolVersion {
+    connection
+        .handshake_data()
+        .and_then(|data| data.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+        .and_then(|data| data.protocol)
+        .and_then(|alpn| ProtocolVersion::from_alpn(&alpn))
+        .unwrap_or(ProtocolVersion::Ndn1)
+}
+
+/// Queue priority of an outgoing packet
+fn send_priority(packet: &NdnPacket) -> SendPriority {
     match packet {
-        NdnPacket::Interest(interest) => {
-            debug!(
-                "[Face {}] Received Interest on stream {}: {}",
-                face_id,
-                stream_id,
-                interest.name()
-            );
-            
+        NdnPacket::Data(_) => SendPriority::Data,
+        NdnPacket::Interest(interest) if is_localhost(interest.name()) => SendPriority::Control,
+        NdnPacket::Interest(_) => SendPriority::Interest,
+        NdnPacket::Nack(_) => SendPriority::Control,
+    }
+}
+
+/// Whether a name is under the `/localhost` management namespace
+fn is_localhost(name: &Name) -> bool {
+    name.get(0).is_some_and(|c| c.as_bytes().as_ref() == b"localhost")
+}
+
+/// Whether an address is a loopback one, IPv4-mapped ones included
+pub(crate) fn is_loopback(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback(),
+        IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or(ip.is_loopback(), |ip| ip.is_loopback()),
+    }
+}
+
+/// Pick a random nonce different from `previous`
+fn fresh_nonce(previous: u32) -> u32 {
+    loop {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(previous);
+        let nonce = hasher.finish() as u32;
+        if nonce != previous {
+            return nonce;
+        }
+    }
+}
+
+/// State shared by the tasks handling packets received on a face
+#[derive(Debug, Clone)]
+pub(crate) struct Inbound {
+    pub(crate) face_id: String,
+    pub(crate) pending_interests: Arc<Mutex<PendingInterests>>,
+    pub(crate) event_sender: Arc<Mutex<mpsc::Sender<FaceEvent>>>,
+    pub(crate) metrics: FaceMetrics,
+    pub(crate) reassembler: Arc<Reassembler>,
+    pub(crate) activity: Arc<ActivityClock>,
+}
+
+impl Inbound {
+    /// Process a QUIC stream
+    #[instrument(skip_all, fields(face = %self.face_id, stream = ?stream_id))]
+    async fn process_stream(&self, stream_id: StreamId, mut recv: RecvStream) -> Result<(), FaceError> {
+        // The first byte tells a long-lived framed stream from a per-packet stream
+        let mut first = [0u8; 1];
+        match recv.read_exact(&mut first).await {
+            Ok(()) => {}
+            Err(ReadExactError::FinishedEarly) => return Ok(()),
+            Err(ReadExactError::ReadError(e)) => return Err(e.into()),
+        }
+        self.metrics.add(|m| &m.bytes_received, 1);
+        
+        if first[0] == FRAMED_STREAM_MARKER {
+            debug!("[Face {}] Stream {} carries framed packets", self.face_id, stream_id);
+            return self.process_framed_stream(recv).await;
+        }
+        
+        let mut stream_bytes = BytesMut::from(&first[..]);
+        
+        // Read from the stream
+        while let Some(chunk) = recv.read_chunk(1024, false).await? {
             // Update metrics
-            metrics.interests_received.increment();
+            self.metrics.add(|m| &m.bytes_received, chunk.bytes.len() as u64);
+            stream_bytes.extend_from_slice(&chunk.bytes);
+        }
+        let stream_bytes = stream_bytes.freeze();
+        
+        // Fragmented messages complete once all their fragments have arrived
+        let messages = if first[0] == FRAGMENT_MARKER {
+            self.reassemble(&stream_bytes)?
+        } else {
+            vec![stream_bytes]
+        };
+        
+        for message in messages {
+            // Parse the NDN packets carried in the message
+            let packets = NdnPacket::from_bytes_multi(&message)?;
+            
+            trace!("[Face {}] Received {} packet(s) on stream {}", self.face_id, packets.len(), stream_id);
             
-            // Send an event
-            let event = FaceEvent::InterestReceived(interest);
-            event_sender.lock().await.send(event).await?;
+            for packet in packets {
+                self.dispatch(packet).await?;
+            }
         }
-        NdnPacket::Data(data) => {
-            let name = data.name().to_string();
-            debug!(
-                "[Face {}] Received Data on stream {}: {}",
-                face_id,
-                stream_id,
-                name
-            );
+        
+        Ok(())
+    }
+
+    /// Feed back-to-back fragments into the reassembler and return the
+    /// messages they complete
+    fn reassemble(&self, mut bytes: &[u8]) -> Result<Vec<Bytes>, FaceError> {
+        let mut complete = Vec::new();
+        
+        while !bytes.is_empty() {
+            let (header, payload, consumed) = parse_fragment(bytes)?;
+            bytes = &bytes[consumed..];
+            self.metrics.add(|m| &m.fragments_received, 1);
+            complete.extend(self.reassembler.insert(&self.face_id, header, payload, Instant::now()));
+        }
+        
+        trace!(
+            "[Face {}] {} message(s) awaiting reassembly ({} bytes)",
+            self.face_id,
+            self.reassembler.pending(&self.face_id),
+            self.reassembler.buffered_bytes(&self.face_id)
+        );
+        
+        Ok(complete)
+    }
+
+    /// Read length-prefixed packets from a long-lived stream until it finishes
+    async fn process_framed_stream(&self, mut recv: RecvStream) -> Result<(), FaceError> {
+        loop {
+            let mut len_buf = [0u8; 4];
+            match recv.read_exact(&mut len_buf).await {
+                Ok(()) => {}
+                Err(ReadExactError::FinishedEarly) => break,
+                Err(ReadExactError::ReadError(e)) => return Err(e.into()),
+            }
             
-            // Update metrics
-            metrics.data_received.increment();
+            let len = u32::from_be_bytes(len_buf) as usize;
+            if len == 0 || len > MAX_FRAME_SIZE {
+                return Err(Error::NdnPacket(format!("Invalid frame length: {}", len)).into());
+            }
             
-            // Check if we have a pending interest for this data
-            let mut pending = pending_interests.lock().await;
-            if let Some(sender) = pending.remove(&name) {
-                // Send the data to the waiting Interest
-                if sender.send(InterestResult::Data(data.clone())).is_err() {
-                    debug!("[Face {}] Failed to send Data to pending Interest", face_id);
+            let mut payload = vec![0u8; len];
+            match recv.read_exact(&mut payload).await {
+                Ok(()) => {}
+                Err(ReadExactError::FinishedEarly) => {
+                    return Err(Error::NdnPacket(format!("Truncated frame of {} bytes", len)).into());
                 }
+                Err(ReadExactError::ReadError(e)) => return Err(e.into()),
             }
+            self.metrics.add(|m| &m.bytes_received, (len + len_buf.len()) as u64);
             
-            // Always send an event as well
-            let event = FaceEvent::DataReceived(data);
-            event_sender.lock().await.send(event).await?;
+            match NdnPacket::from_bytes(&payload) {
+                Ok(packet) => self.dispatch(packet).await?,
+                Err(e) => warn!("[Face {}] Invalid framed packet: {}", self.face_id, e),
+            }
+        }
+        
+        debug!("[Face {}] Persistent stream finished", self.face_id);
+        Ok(())
+    }
+
+    /// Send `event` on the face event channel. A packet a waiter already
+    /// took is not worth stalling the face for when nobody reads the events,
+    /// as with a consumer only expressing Interests, so its event is dropped
+    /// rather than waited for when the channel is full.
+    async fn notify(&self, event: FaceEvent, taken: bool) -> Result<(), FaceError> {
+        let sender = self.event_sender.lock().await;
+        if !taken {
+            sender.send(event).await.map_err(|_| FaceError::Closed)?;
+        } else if sender.capacity() > EVENT_CHANNEL_HEADROOM {
+            let _ = sender.try_send(event);
         }
+        Ok(())
+    }
+
+    /// Deliver a received packet to pending Interests and the face event channel
+    pub(crate) async fn dispatch(&self, packet: NdnPacket) -> Result<(), FaceError> {
+        self.activity.touch();
+        
+        match packet {
+            NdnPacket::Interest(interest) => {
+                debug!("[Face {}] Received Interest: {}", self.face_id, interest.name());
+                
+                // Update metrics
+                self.metrics.add(|m| &m.interests_received, 1);
+                
+                // Send an event
+                let event = FaceEvent::InterestReceived(interest);
+                self.event_sender.lock().await.send(event).await.map_err(|_| FaceError::Closed)?;
+            }
+            NdnPacket::Data(data) => {
+                let name = data.name().to_string();
+                debug!("[Face {}] Received Data: {}", self.face_id, name);
+                
+                // Update metrics
+                self.metrics.add(|m| &m.data_received, 1);
+                
+                // Check if we have a pending interest for this data
+                let waiters = self.pending_interests.lock().await.resolve(data.name(), InterestResult::Data(data.clone()));
+                if waiters > 1 {
+                    trace!("[Face {}] Data {} satisfied {} waiters", self.face_id, name, waiters);
+                }
+                
+                // Always send an event as well
+                self.notify(FaceEvent::DataReceived(data), waiters > 0).await?;
+            }
+            NdnPacket::Nack(nack) => {
+                let name = nack.name().to_string();
+                debug!("[Face {}] Received Nack ({}): {}", self.face_id, nack.reason, name);
+                
+                self.metrics.add(|m| &m.nacks_received, 1);
+                
+                // Fail the pending Interest with the Nack reason
+                let waiters = self.pending_interests.lock().await.resolve_interest(&nack.interest, InterestResult::Nacked(nack.reason));
+                
+                self.notify(FaceEvent::NackReceived(nack), waiters > 0).await?;
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retransmission_backoff() {
+        let policy = RetransmissionPolicy {
+            max_retries: 3,
+            initial_timeout_ms: 100,
+            backoff_factor: 2.0,
+            max_timeout_ms: 300,
+        };
+        assert_eq!(policy.timeout_for(0), Duration::from_millis(100));
+        assert_eq!(policy.timeout_for(1), Duration::from_millis(200));
+        assert_eq!(policy.timeout_for(2), Duration::from_millis(300));
+    }
+
+    fn interest(name: &str) -> Interest {
+        Interest::new(Name::from_string(name).unwrap())
+    }
+
+    #[test]
+    fn test_pending_interests_aggregation() {
+        let mut pending = PendingInterests::default();
+        let expiry = Instant::now() + Duration::from_secs(1);
+        let a = interest("/a");
+        let (first, mut first_rx, aggregated) = pending.add(&a, expiry);
+        assert!(!aggregated);
+        let (second, mut second_rx, aggregated) = pending.add(&a.clone().with_nonce(7), expiry);
+        assert!(aggregated);
+        assert!(pending.retransmits(&a, first));
+        assert!(!pending.retransmits(&a, second));
+
+        // Other selectors make another Interest
+        let (_, _fresh_rx, aggregated) = pending.add(&a.clone().with_must_be_fresh(false), expiry);
+        assert!(!aggregated);
+        assert_eq!(pending.len(), 2);
+
+        // A waiter giving up leaves the others in place, the next one
+        // retransmitting in its stead
+        pending.remove_waiter(&a, first);
+        assert!(first_rx.try_recv().is_err());
+        assert!(pending.retransmits(&a, second));
+
+        let data = Data::new(a.name.clone(), "x");
+        assert_eq!(pending.resolve(&a.name, InterestResult::Data(data)), 2);
+        assert!(matches!(second_rx.try_recv(), Ok(InterestResult::Data(_))));
+        assert_eq!(pending.resolve(&a.name, InterestResult::Timeout), 0);
+    }
+
+    #[test]
+    fn test_pending_interests_can_be_prefix() {
+        let mut pending = PendingInterests::default();
+        let expiry = Instant::now() + Duration::from_secs(1);
+        let prefix = interest("/a").with_can_be_prefix(true);
+        let (_, mut prefix_rx, _) = pending.add(&prefix, expiry);
+        let (_, mut exact_rx, _) = pending.add(&interest("/a"), expiry);
+        let (_, _other_rx, _) = pending.add(&interest("/b").with_can_be_prefix(true), expiry);
+
+        // Data under the prefix only answers the CanBePrefix Interest
+        let name = Name::from_string("/a/1").unwrap();
+        assert_eq!(pending.resolve(&name, InterestResult::Data(Data::new(name.clone(), "x"))), 1);
+        assert!(matches!(prefix_rx.try_recv(), Ok(InterestResult::Data(_))));
+        assert!(exact_rx.try_recv().is_err());
+        assert_eq!(pending.len(), 2);
+
+        // A Nack only fails the Interest it carries
+        assert_eq!(pending.resolve_interest(&prefix, InterestResult::Nacked(NackReason::NoRoute)), 0);
+        assert_eq!(pending.resolve_interest(&interest("/a"), InterestResult::Nacked(NackReason::NoRoute)), 1);
+        assert!(matches!(exact_rx.try_recv(), Ok(InterestResult::Nacked(NackReason::NoRoute))));
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn test_pending_interests_expire() {
+        let mut pending = PendingInterests::default();
+        let now = Instant::now();
+        let (_, mut expired_rx, _) = pending.add(&interest("/a"), now);
+        let (_, dropped_rx, _) = pending.add(&interest("/b"), now + Duration::from_secs(1));
+        let (_, _live_rx, _) = pending.add(&interest("/c"), now + Duration::from_secs(1));
+        drop(dropped_rx);
+
+        assert_eq!(pending.expire(now), 2);
+        assert!(matches!(expired_rx.try_recv(), Ok(InterestResult::Timeout)));
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn test_fresh_nonce_differs() {
+        for previous in [0, 1, u32::MAX] {
+            assert_ne!(fresh_nonce(previous), previous);
+        }
+    }
+
+    #[test]
+    fn test_face_metrics_record_both() {
+        let shared = Arc::new(UdcnMetrics::new());
+        let first = FaceMetrics::new(Arc::clone(&shared), "first");
+        let second = FaceMetrics::new(Arc::clone(&shared), "second");
+        
+        first.add(|m| &m.interests_sent, 1);
+        second.add(|m| &m.interests_sent, 2);
+        let interest = NdnPacket::Interest(Interest::new(Name::from_string("/a").unwrap()));
+        first.add_packet(|m| &m.bytes_sent, &interest, 10);
+        
+        assert_eq!(first.stats("first", Duration::ZERO).interests_sent, 1);
+        assert_eq!(second.stats("second", Duration::ZERO).interests_sent, 2);
+        assert_eq!(shared.interests_sent.value(), 3);
+        
+        let labels = Labels::new().face("first").packet_type(PacketType::Interest);
+        assert_eq!(shared.bytes_sent.get(&labels).unwrap().value(), 10);
+        assert_eq!(first.stats("first", Duration::ZERO).bytes_sent, 10);
+    }
+
+    #[test]
+    fn test_activity_clock() {
+        let clock = ActivityClock::new();
+        let created = clock.last();
+        std::thread::sleep(Duration::from_millis(5));
+        clock.touch();
+        assert!(clock.last() >= created + Duration::from_millis(5));
     }
-    
-    Ok(())
 }
@@ -0,0 +1,576 @@
+//! Segmented object retrieval.
+//!
+//! This module provides a segment fetcher that pipelines Interests for the
+//! segments of a large object, adapting the number of outstanding Interests
+//! with an AIMD congestion window instead of using a fixed pipeline size.
+//! The window shrinks on timeouts and congestion Nacks, and optionally on
+//! RTT samples well above the smallest one seen; a NoRoute Nack ends the
+//! fetch right away.
+
+use bytes::{Bytes, BytesMut};
+use futures::{stream::FuturesUnordered, StreamExt};
+use log::{debug, trace};
+use rust_udcn_common::{
+    metrics::{Histogram, Labels, UdcnMetrics},
+    ndn::{Data, Interest, NackReason, Name},
+};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{FaceError, FetchError},
+    ndn_face::NdnFace,
+    DEFAULT_INTEREST_TIMEOUT_MS,
+};
+
+/// Options controlling the segment fetcher pipeline
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    /// Initial congestion window (in Interests)
+    pub initial_window: f64,
+
+    /// Lower bound for the congestion window
+    pub min_window: f64,
+
+    /// Upper bound for the congestion window
+    pub max_window: f64,
+
+    /// Initial slow-start threshold
+    pub initial_ssthresh: f64,
+
+    /// Window increase per window's worth of Data in congestion avoidance
+    pub additive_increase: f64,
+
+    /// Factor applied to the window on a timeout, a congestion Nack or a
+    /// congestion mark
+    pub multiplicative_decrease: f64,
+
+    /// Treat RTT samples above `min_rtt * factor` as congestion marks. Off
+    /// by default, since a slow producer looks the same as a full queue.
+    pub rtt_congestion_factor: Option<f64>,
+
+    /// Maximum number of retransmissions per segment
+    pub max_retries: u32,
+
+    /// Lifetime of each segment Interest (in milliseconds)
+    pub interest_lifetime_ms: u64,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            initial_window: 2.0,
+            min_window: 1.0,
+            max_window: 128.0,
+            initial_ssthresh: f64::MAX,
+            additive_increase: 1.0,
+            multiplicative_decrease: 0.5,
+            rtt_congestion_factor: None,
+            max_retries: 3,
+            interest_lifetime_ms: DEFAULT_INTEREST_TIMEOUT_MS,
+        }
+    }
+}
+
+/// Statistics collected while fetching an object
+#[derive(Debug, Clone, Default)]
+pub struct FetchStats {
+    /// Number of segments retrieved
+    pub segments: u64,
+
+    /// Total content bytes retrieved
+    pub bytes: u64,
+
+    /// Number of retransmitted segment Interests
+    pub retransmissions: u64,
+
+    /// Number of window decreases
+    pub congestion_events: u64,
+
+    /// Smoothed RTT at the end of the transfer
+    pub srtt: Duration,
+
+    /// Congestion window at the end of the transfer
+    pub final_window: f64,
+
+    /// Total transfer duration
+    pub elapsed: Duration,
+}
+
+/// A fully retrieved segmented object
+#[derive(Debug, Clone)]
+pub struct FetchedObject {
+    /// Reassembled content of all segments
+    pub content: Bytes,
+
+    /// Transfer statistics
+    pub stats: FetchStats,
+}
+
+/// How far along a fetch is, reported as segments arrive
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FetchProgress {
+    /// Segments received so far
+    pub segments: u64,
+
+    /// Number of segments in the object, once a FinalBlockId came back
+    pub total_segments: Option<u64>,
+
+    /// Content bytes received so far
+    pub bytes: u64,
+
+    /// Time since the fetch started
+    pub elapsed: Duration,
+}
+
+/// AIMD congestion window with slow start
+#[derive(Debug, Clone)]
+pub struct AimdWindow {
+    cwnd: f64,
+    ssthresh: f64,
+    options: FetchOptions,
+    last_decrease: Option<Instant>,
+}
+
+impl AimdWindow {
+    /// Create a new window from the fetch options
+    pub fn new(options: &FetchOptions) -> Self {
+        Self {
+            cwnd: options.initial_window,
+            ssthresh: options.initial_ssthresh,
+            options: options.clone(),
+            last_decrease: None,
+        }
+    }
+
+    /// Current window size as a number of Interests
+    pub fn size(&self) -> usize {
+        self.cwnd.floor().max(1.0) as usize
+    }
+
+    /// Current window value
+    pub fn cwnd(&self) -> f64 {
+        self.cwnd
+    }
+
+    /// Grow the window after a Data arrived without congestion
+    pub fn on_data(&mut self) {
+        if self.cwnd < self.ssthresh {
+            self.cwnd += 1.0;
+        } else {
+            self.cwnd += self.options.additive_increase / self.cwnd;
+        }
+        self.cwnd = self.cwnd.min(self.options.max_window);
+    }
+
+    /// Shrink the window after a congestion signal.
+    ///
+    /// At most one decrease is applied per `rtt`, so a burst of losses from
+    /// the same window is only counted once. Returns whether the window shrank.
+    pub fn on_congestion(&mut self, now: Instant, rtt: Duration) -> bool {
+        if let Some(last) = self.last_decrease {
+            if now.duration_since(last) < rtt {
+                return false;
+            }
+        }
+        self.ssthresh = (self.cwnd * self.options.multiplicative_decrease)
+            .max(self.options.min_window);
+        self.cwnd = self.ssthresh;
+        self.last_decrease = Some(now);
+        true
+    }
+}
+
+/// Smoothed RTT estimator (RFC 6298 style)
+#[derive(Debug, Clone, Default)]
+struct RttEstimator {
+    srtt: Option<Duration>,
+    min_rtt: Option<Duration>,
+}
+
+impl RttEstimator {
+    /// Estimator starting from the RTT samples recorded in `histogram`
+    fn from_history(histogram: &Histogram) -> Self {
+        let srtt = (histogram.count() > 0).then(|| Duration::from_micros(histogram.average() as u64));
+        Self { srtt, min_rtt: None }
+    }
+
+    fn add_sample(&mut self, rtt: Duration) {
+        self.srtt = Some(match self.srtt {
+            Some(srtt) => srtt.mul_f64(0.875) + rtt.mul_f64(0.125),
+            None => rtt,
+        });
+        self.min_rtt = Some(self.min_rtt.map_or(rtt, |m| m.min(rtt)));
+    }
+
+    fn srtt(&self) -> Duration {
+        self.srtt.unwrap_or(Duration::from_millis(100))
+    }
+}
+
+/// Fetches all segments of an object under a name prefix
+#[derive(Debug, Clone)]
+pub struct SegmentFetcher {
+    face: Arc<dyn NdnFace>,
+    options: FetchOptions,
+    metrics: Option<Arc<UdcnMetrics>>,
+}
+
+impl SegmentFetcher {
+    /// Create a fetcher that sends Interests over the given face
+    pub fn new(face: Arc<dyn NdnFace>, options: FetchOptions) -> Self {
+        Self {
+            face,
+            options,
+            metrics: None,
+        }
+    }
+
+    /// Record the RTT of each segment in the Interest processing time of
+    /// `metrics`, under the prefix fetched, and start later fetches of the
+    /// same prefix from the RTT recorded there
+    pub fn with_metrics(mut self, metrics: Arc<UdcnMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Fetch every segment under `prefix` and reassemble the content.
+    ///
+    /// The object ends at the segment named by the FinalBlockId of any
+    /// received Data packet. Without one, the fetch fails with
+    /// [`FetchError::NoFinalBlock`] once the segments past the highest one
+    /// answered are Nacked or time out.
+    pub async fn fetch(&self, prefix: &Name) -> Result<FetchedObject, FetchError> {
+        self.fetch_with_progress(prefix, |_| {}).await
+    }
+
+    /// Fetch every segment under `prefix` like [`fetch`](Self::fetch),
+    /// calling `on_progress` each time a segment arrives
+    pub async fn fetch_with_progress(
+        &self,
+        prefix: &Name,
+        mut on_progress: impl FnMut(&FetchProgress),
+    ) -> Result<FetchedObject, FetchError> {
+        let start = Instant::now();
+        let mut window = AimdWindow::new(&self.options);
+        // The face records each RTT in the total, the fetcher under the prefix
+        let rtt_history = self
+            .metrics
+            .as_ref()
+            .map(|metrics| metrics.interest_processing_time.with(&Labels::new().prefix(prefix.to_string())));
+        let mut rtt = rtt_history
+            .as_ref()
+            .map_or_else(RttEstimator::default, |timer| RttEstimator::from_history(timer.histogram()));
+        let mut stats = FetchStats::default();
+
+        let mut segments: BTreeMap<u64, Bytes> = BTreeMap::new();
+        let mut retries: HashMap<u64, u32> = HashMap::new();
+        let mut retry_queue: VecDeque<u64> = VecDeque::new();
+        let mut final_block: Option<u64> = None;
+        // Segments received up to the final block, or all of them before it is known
+        let mut received = 0u64;
+        let mut next_segment = 0u64;
+        // Without a final block, a failure past the highest segment answered
+        // may mean the end of the object: stop opening new segments and set
+        // the failed ones aside until a higher segment or a FinalBlockId
+        // shows they exist
+        let mut highest_answered: Option<u64> = None;
+        let mut probing = true;
+        let mut past_answered: Vec<u64> = Vec::new();
+        let mut in_flight = FuturesUnordered::new();
+
+        debug!("Fetching segmented object {}", prefix);
+
+        loop {
+            // Fill the window
+            while in_flight.len() < window.size() {
+                let segment = match retry_queue.pop_front() {
+                    Some(seg) => seg,
+                    None if final_block.map_or(probing, |last| next_segment <= last) => {
+                        next_segment += 1;
+                        next_segment - 1
+                    }
+                    None => break,
+                };
+                in_flight.push(request_segment(
+                    Arc::clone(&self.face),
+                    prefix.with_segment(segment),
+                    segment,
+                    self.options.interest_lifetime_ms,
+                ));
+            }
+
+            let (segment, sent_at, result) = match in_flight.next().await {
+                Some(completed) => completed,
+                None => break,
+            };
+
+            // Segments past the end of the object are expected to fail
+            if final_block.is_some_and(|last| segment > last) {
+                continue;
+            }
+
+            let now = Instant::now();
+            match result {
+                Ok(data) => {
+                    let sample = now.duration_since(sent_at);
+                    rtt.add_sample(sample);
+                    if let Some(timer) = &rtt_history {
+                        timer.histogram().observe(sample.as_micros() as u64);
+                    }
+
+                    let marked = match (self.options.rtt_congestion_factor, rtt.min_rtt) {
+                        (Some(factor), Some(min_rtt)) => sample > min_rtt.mul_f64(factor),
+                        _ => false,
+                    };
+                    if marked {
+                        if window.on_congestion(now, rtt.srtt()) {
+                            stats.congestion_events += 1;
+                        }
+                    } else {
+                        window.on_data();
+                    }
+
+                    if let Some(last) = data.final_block_id {
+                        if final_block != Some(last) {
+                            final_block = Some(last);
+                            received = segments.range(..=last).count() as u64;
+                            retry_queue.retain(|&seg| seg <= last);
+                        }
+                    }
+
+                    // Segments set aside below this one or the final block exist after all
+                    highest_answered = highest_answered.max(Some(segment));
+                    let (missing, past): (Vec<u64>, Vec<u64>) = past_answered
+                        .drain(..)
+                        .partition(|&seg| seg < segment || final_block.is_some_and(|last| seg <= last));
+                    stats.retransmissions += missing.len() as u64;
+                    retry_queue.extend(missing);
+                    past_answered = past;
+                    probing = past_answered.is_empty();
+
+                    trace!(
+                        "Segment {} of {} received, window {:.2}",
+                        segment,
+                        prefix,
+                        window.cwnd()
+                    );
+                    stats.bytes += data.content.len() as u64;
+                    if segments.insert(segment, data.content).is_none()
+                        && final_block.is_none_or(|last| segment <= last)
+                    {
+                        received += 1;
+                    }
+                    on_progress(&FetchProgress {
+                        segments: segments.len() as u64,
+                        total_segments: final_block.map(|last| last + 1),
+                        bytes: stats.bytes,
+                        elapsed: start.elapsed(),
+                    });
+                }
+                Err(e) => {
+                    match e {
+                        // Retrying does not make a route or a face appear
+                        FaceError::NoRoute | FaceError::Closed => {
+                            return Err(FetchError::new(prefix.with_segment(segment), e));
+                        }
+                        FaceError::Timeout | FaceError::Nacked(NackReason::Congestion) => {
+                            if window.on_congestion(now, rtt.srtt()) {
+                                stats.congestion_events += 1;
+                            }
+                        }
+                        _ => {}
+                    }
+
+                    if final_block.is_none() {
+                        probing = false;
+                        // The first segment has to exist, so it is retried as usual
+                        if highest_answered.is_some_and(|highest| segment > highest) {
+                            debug!("Segment {} of {} failed past the last one answered: {}", segment, prefix, e);
+                            past_answered.push(segment);
+                            continue;
+                        }
+                    }
+
+                    let count = retries.entry(segment).or_insert(0);
+                    *count += 1;
+                    if *count > self.options.max_retries {
+                        debug!("Segment {} of {} failed after {} retries", segment, prefix, self.options.max_retries);
+                        return Err(FetchError::new(prefix.with_segment(segment), e));
+                    }
+
+                    debug!("Retrying segment {} of {}: {}", segment, prefix, e);
+                    stats.retransmissions += 1;
+                    retry_queue.push_back(segment);
+                }
+            }
+
+            if final_block.is_some_and(|last| received == last + 1) {
+                break;
+            }
+        }
+
+        let last = final_block.ok_or_else(|| FetchError::NoFinalBlock(prefix.clone()))?;
+
+        let mut content = BytesMut::with_capacity(stats.bytes as usize);
+        for (_, segment) in segments.range(0..=last) {
+            content.extend_from_slice(segment);
+        }
+
+        stats.segments = last + 1;
+        stats.srtt = rtt.srtt();
+        stats.final_window = window.cwnd();
+        stats.elapsed = start.elapsed();
+
+        debug!(
+            "Fetched {} ({} segments, {} bytes) in {:?}",
+            prefix, stats.segments, stats.bytes, stats.elapsed
+        );
+
+        Ok(FetchedObject {
+            content: content.freeze(),
+            stats,
+        })
+    }
+}
+
+/// Express one segment Interest and report when it was sent
+async fn request_segment(
+    face: Arc<dyn NdnFace>,
+    name: Name,
+    segment: u64,
+    lifetime_ms: u64,
+) -> (u64, Instant, Result<Data, FaceError>) {
+    // The segments of an object never change, so stale cached copies do
+    let interest = Interest::new(name)
+        .with_lifetime(lifetime_ms as u32)
+        .with_must_be_fresh(false);
+    let sent_at = Instant::now();
+    let result = face.express_interest(interest, lifetime_ms).await;
+    (segment, sent_at, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{face::FaceEvent, link_face::link_face_pair};
+
+    #[test]
+    fn test_slow_start_then_additive_increase() {
+        let options = FetchOptions {
+            initial_window: 2.0,
+            initial_ssthresh: 4.0,
+            ..Default::default()
+        };
+        let mut window = AimdWindow::new(&options);
+
+        window.on_data();
+        window.on_data();
+        assert_eq!(window.size(), 4);
+
+        // Congestion avoidance grows by roughly one per window
+        for _ in 0..4 {
+            window.on_data();
+        }
+        assert_eq!(window.size(), 4);
+        assert!(window.cwnd() > 4.9);
+    }
+
+    #[test]
+    fn test_multiplicative_decrease_once_per_rtt() {
+        let options = FetchOptions {
+            initial_window: 16.0,
+            ..Default::default()
+        };
+        let mut window = AimdWindow::new(&options);
+        let now = Instant::now();
+        let rtt = Duration::from_millis(50);
+
+        assert!(window.on_congestion(now, rtt));
+        assert_eq!(window.size(), 8);
+
+        // A second loss from the same window is ignored
+        assert!(!window.on_congestion(now + Duration::from_millis(10), rtt));
+        assert_eq!(window.size(), 8);
+
+        assert!(window.on_congestion(now + Duration::from_millis(60), rtt));
+        assert_eq!(window.size(), 4);
+    }
+
+    #[test]
+    fn test_window_bounds() {
+        let options = FetchOptions {
+            initial_window: 1.0,
+            max_window: 3.0,
+            ..Default::default()
+        };
+        let mut window = AimdWindow::new(&options);
+        for _ in 0..10 {
+            window.on_data();
+        }
+        assert_eq!(window.size(), 3);
+
+        let now = Instant::now();
+        for i in 0..10 {
+            window.on_congestion(now + Duration::from_secs(i), Duration::from_millis(1));
+        }
+        assert_eq!(window.size(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_route_fails_fast() {
+        let (consumer, producer) = link_face_pair("consumer", "producer", &Arc::new(UdcnMetrics::new()));
+        let consumer: Arc<dyn NdnFace> = Arc::new(consumer);
+
+        let nacking = tokio::spawn(async move {
+            let mut interests = 0;
+            while let Some(FaceEvent::InterestReceived(interest)) = producer.next_event().await {
+                interests += 1;
+                producer.send_nack(interest, NackReason::NoRoute).await.unwrap();
+            }
+            interests
+        });
+
+        let prefix = Name::from_string("/nowhere/object").unwrap();
+        let fetcher = SegmentFetcher::new(Arc::clone(&consumer), FetchOptions::default());
+        let error = fetcher.fetch(&prefix).await.unwrap_err();
+        assert!(matches!(error, FetchError::Nacked { reason: NackReason::NoRoute, .. }));
+
+        // Only the initial window went out, nothing was retried
+        consumer.close().await;
+        assert!(nacking.await.unwrap() <= 2);
+    }
+
+    #[tokio::test]
+    async fn test_no_final_block() {
+        let (consumer, producer) = link_face_pair("consumer", "producer", &Arc::new(UdcnMetrics::new()));
+        let consumer: Arc<dyn NdnFace> = Arc::new(consumer);
+
+        // Three segments, none of them naming the last
+        let serving = tokio::spawn(async move {
+            let mut interests = 0;
+            while let Some(FaceEvent::InterestReceived(interest)) = producer.next_event().await {
+                interests += 1;
+                match interest.name.segment() {
+                    Some(segment) if segment < 3 => {
+                        producer.send_data(Data::new(interest.name, vec![segment as u8])).await.unwrap()
+                    }
+                    _ => producer.send_nack(interest, NackReason::None).await.unwrap(),
+                }
+            }
+            interests
+        });
+
+        let prefix = Name::from_string("/unterminated/object").unwrap();
+        let fetcher = SegmentFetcher::new(Arc::clone(&consumer), FetchOptions::default());
+        let error = fetcher.fetch(&prefix).await.unwrap_err();
+        assert!(matches!(error, FetchError::NoFinalBlock(name) if name == prefix));
+
+        // Segments past the end were not retried
+        consumer.close().await;
+        assert!(serving.await.unwrap() < 3 + 8);
+    }
+}
\ No newline at end of file
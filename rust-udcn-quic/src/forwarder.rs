@@ -0,0 +1,1080 @@
+//! Userspace NDN forwarding engine.
+//!
+//! This module forwards Interests between QUIC faces using a Pending
+//! Interest Table and a Forwarding Information Base, and returns Data
+//! along the reverse path recorded in the PIT. With a [`FaceScheduler`],
+//! what it sends is queued per face and sent in fair turns.
+
+use log::{debug, trace, warn};
+use rust_udcn_common::{
+    metrics::{Labels, UdcnMetrics},
+    ndn::{Data, Interest, Nack, NackReason, Name, NameComponent},
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{field, instrument, Span};
+
+use crate::{
+    error::FaceError,
+    ndn_face::NdnFace,
+    packet::NdnPacket,
+    scheduler::{self, FaceScheduler, SchedulerOptions},
+    strategy::{Strategy, StrategyChoice},
+};
+
+/// Name prefix of prefix registration commands
+pub const RIB_REGISTER_PREFIX: &str = "/localhost/udcn/rib/register";
+
+/// Name prefix of prefix unregistration commands
+pub const RIB_UNREGISTER_PREFIX: &str = "/localhost/udcn/rib/unregister";
+
+/// How long after forwarding an Interest further Interests for the same name
+/// from new downstream faces are aggregated rather than forwarded again
+const RETRANSMISSION_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A next hop of a FIB entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextHop {
+    /// ID of the face to forward to
+    pub face_id: String,
+
+    /// Routing cost (lower is preferred)
+    pub cost: u32,
+}
+
+/// An entry in the FIB
+#[derive(Debug, Clone)]
+pub struct FibEntry {
+    /// Name prefix of this entry
+    pub prefix: Name,
+
+    /// Next hops, sorted by increasing cost
+    pub next_hops: Vec<NextHop>,
+}
+
+/// A pending Interest, as listed by [`Forwarder::pit_entries`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingInterest {
+    /// Interest name
+    pub name: Name,
+
+    /// Downstream faces waiting for Data
+    pub in_faces: Vec<String>,
+
+    /// Upstream faces the Interest was forwarded to
+    pub out_faces: Vec<String>,
+
+    /// Lifetime left, zero once expired
+    pub remaining: Duration,
+}
+
+/// Forwarding Information Base with longest-prefix match
+#[derive(Debug, Default)]
+struct Fib {
+    entries: HashMap<Name, FibEntry>,
+
+    /// Expiry of learned next hops, by prefix and face
+    expiries: HashMap<(Name, String), Instant>,
+}
+
+impl Fib {
+    fn add_next_hop(&mut self, prefix: Name, face_id: &str, cost: u32) {
+        // A configured route replaces a learned one
+        self.expiries.remove(&(prefix.clone(), face_id.to_string()));
+        let entry = self.entries.entry(prefix.clone()).or_insert_with(|| FibEntry {
+            prefix,
+            next_hops: Vec::new(),
+        });
+        entry.next_hops.retain(|hop| hop.face_id != face_id);
+        entry.next_hops.push(NextHop {
+            face_id: face_id.to_string(),
+            cost,
+        });
+        entry.next_hops.sort_by_key(|hop| hop.cost);
+    }
+
+    /// Add or refresh a next hop that goes away at `expiry`. Configured
+    /// next hops are left alone.
+    fn learn_next_hop(&mut self, prefix: Name, face_id: &str, cost: u32, expiry: Instant) {
+        let key = (prefix.clone(), face_id.to_string());
+        let configured = !self.expiries.contains_key(&key)
+            && self
+                .entries
+                .get(&prefix)
+                .is_some_and(|entry| entry.next_hops.iter().any(|hop| hop.face_id == face_id));
+        if configured {
+            return;
+        }
+        self.add_next_hop(prefix, face_id, cost);
+        self.expiries.insert(key, expiry);
+    }
+
+    /// Push back the expiry of a learned next hop
+    fn refresh_next_hop(&mut self, prefix: &Name, face_id: &str, expiry: Instant) {
+        if let Some(at) = self.expiries.get_mut(&(prefix.clone(), face_id.to_string())) {
+            *at = expiry;
+        }
+    }
+
+    fn remove_next_hop(&mut self, prefix: &Name, face_id: &str) -> bool {
+        self.expiries.remove(&(prefix.clone(), face_id.to_string()));
+        let Some(entry) = self.entries.get_mut(prefix) else {
+            return false;
+        };
+        let before = entry.next_hops.len();
+        entry.next_hops.retain(|hop| hop.face_id != face_id);
+        let removed = entry.next_hops.len() != before;
+        if entry.next_hops.is_empty() {
+            self.entries.remove(prefix);
+        }
+        removed
+    }
+
+    /// Give the next hops of face `from` to face `to`, except where `to`
+    /// has a next hop of its own
+    fn move_face(&mut self, from: &str, to: &str) {
+        for entry in self.entries.values_mut() {
+            if entry.next_hops.iter().any(|hop| hop.face_id == to) {
+                continue;
+            }
+            let Some(hop) = entry.next_hops.iter_mut().find(|hop| hop.face_id == from) else {
+                continue;
+            };
+            hop.face_id = to.to_string();
+            if let Some(expiry) = self.expiries.remove(&(entry.prefix.clone(), from.to_string())) {
+                self.expiries.insert((entry.prefix.clone(), to.to_string()), expiry);
+            }
+        }
+    }
+
+    fn remove_face(&mut self, face_id: &str) {
+        self.expiries.retain(|(_, face), _| face != face_id);
+        for entry in self.entries.values_mut() {
+            entry.next_hops.retain(|hop| hop.face_id != face_id);
+        }
+        self.entries.retain(|_, entry| !entry.next_hops.is_empty());
+    }
+
+    /// Remove learned next hops that expired before `now`
+    fn expire(&mut self, now: Instant) {
+        let expired: Vec<(Name, String)> = self
+            .expiries
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for (prefix, face_id) in expired {
+            trace!("Learned route {} -> {} expired", prefix, face_id);
+            self.remove_next_hop(&prefix, &face_id);
+        }
+    }
+
+    fn is_live(&self, prefix: &Name, face_id: &str, now: Instant) -> bool {
+        self.expiries
+            .get(&(prefix.clone(), face_id.to_string()))
+            .is_none_or(|at| *at > now)
+    }
+
+    /// Longest prefix with live next hops, and those next hops
+    fn longest_prefix_match(&self, name: &Name, now: Instant) -> Option<(Name, Vec<NextHop>)> {
+        (0..=name.len()).rev().find_map(|len| {
+            let entry = self.entries.get(&name.prefix(len))?;
+            let next_hops: Vec<NextHop> = entry
+                .next_hops
+                .iter()
+                .filter(|hop| self.is_live(&entry.prefix, &hop.face_id, now))
+                .cloned()
+                .collect();
+            (!next_hops.is_empty()).then(|| (entry.prefix.clone(), next_hops))
+        })
+    }
+}
+
+/// A downstream face waiting for Data
+#[derive(Debug, Clone)]
+struct InRecord {
+    face_id: String,
+    nonce: u32,
+    interest: Interest,
+    expiry: Instant,
+}
+
+/// An upstream face an Interest was forwarded to
+#[derive(Debug, Clone)]
+struct OutRecord {
+    face_id: String,
+    nonce: u32,
+    sent_at: Instant,
+}
+
+/// A pending Interest
+#[derive(Debug, Clone)]
+struct PitEntry {
+    in_records: Vec<InRecord>,
+    out_records: Vec<OutRecord>,
+    expiry: Instant,
+
+    /// FIB prefix and strategy the Interest was forwarded under
+    fib_prefix: Name,
+    strategy: Arc<dyn Strategy>,
+
+    /// Whether the Interest was flooded for lack of a route
+    flooded: bool,
+}
+
+/// Userspace forwarder connecting QUIC faces
+#[derive(Debug)]
+pub struct Forwarder {
+    fib: RwLock<Fib>,
+    pit: Mutex<HashMap<Name, PitEntry>>,
+    strategies: RwLock<StrategyChoice>,
+    metrics: Arc<UdcnMetrics>,
+
+    /// Name this forwarder signs the Nacks it originates with
+    node_id: Option<Name>,
+
+    /// Output queues of the faces, if sending is scheduled
+    scheduler: Option<Arc<FaceScheduler>>,
+}
+
+impl Forwarder {
+    /// Create a new forwarder reporting into the given metrics
+    pub fn new(metrics: Arc<UdcnMetrics>) -> Self {
+        Self {
+            fib: RwLock::new(Fib::default()),
+            pit: Mutex::new(HashMap::new()),
+            strategies: RwLock::new(StrategyChoice::default()),
+            metrics,
+            node_id: None,
+            scheduler: None,
+        }
+    }
+
+    /// Name the Nacks this forwarder originates with `node_id`
+    pub fn with_node_id(mut self, node_id: Name) -> Self {
+        self.node_id = Some(node_id);
+        self
+    }
+
+    /// Queue what this forwarder sends per face, to be sent in deficit
+    /// round-robin turns by [`FaceScheduler::run`]
+    pub fn with_scheduler(mut self, options: SchedulerOptions) -> Self {
+        self.scheduler = Some(Arc::new(FaceScheduler::new(options, Arc::clone(&self.metrics))));
+        self
+    }
+
+    /// Get the output queues of the faces, if sending is scheduled
+    pub fn scheduler(&self) -> Option<Arc<FaceScheduler>> {
+        self.scheduler.clone()
+    }
+
+    /// Send a packet on a face, through its output queue if scheduled
+    ///
+    /// With a scheduler, success only means the packet was queued. An
+    /// Interest that then fails to go out reaches the strategy as a
+    /// failure of its upstream once the PIT entry expires unanswered.
+    async fn send(&self, face: &Arc<dyn NdnFace>, packet: NdnPacket) -> Result<(), FaceError> {
+        match &self.scheduler {
+            Some(scheduler) => scheduler.enqueue(face, packet),
+            None => scheduler::send_packet(face, packet).await,
+        }
+    }
+
+    /// Add a route toward a face
+    pub async fn add_route(&self, prefix: Name, face_id: &str, cost: u32) {
+        debug!("Adding route {} -> {} (cost {})", prefix, face_id, cost);
+        let mut fib = self.fib.write().await;
+        fib.add_next_hop(prefix, face_id, cost);
+        self.metrics.fib_size.set(fib.entries.len() as u64);
+    }
+
+    /// Remove a route toward a face. Returns whether it existed.
+    pub async fn remove_route(&self, prefix: &Name, face_id: &str) -> bool {
+        let mut fib = self.fib.write().await;
+        let removed = fib.remove_next_hop(prefix, face_id);
+        self.metrics.fib_size.set(fib.entries.len() as u64);
+        drop(fib);
+
+        if removed {
+            self.strategy_for(prefix).await.on_next_hop_removed(prefix, face_id);
+        }
+        removed
+    }
+
+    /// Get all FIB entries, including learned routes
+    pub async fn routes(&self) -> Vec<FibEntry> {
+        let mut fib = self.fib.write().await;
+        fib.expire(Instant::now());
+        self.metrics.fib_size.set(fib.entries.len() as u64);
+        fib.entries.values().cloned().collect()
+    }
+
+    /// Use `strategy` for Interests under `prefix`
+    pub async fn set_strategy(&self, prefix: Name, strategy: Arc<dyn Strategy>) {
+        debug!("Setting strategy for {} to {}", prefix, strategy.name());
+        self.strategies.write().await.set(prefix, strategy);
+    }
+
+    /// Revert `prefix` to the strategy of its parent prefix.
+    /// Returns whether a strategy was set for it.
+    pub async fn unset_strategy(&self, prefix: &Name) -> bool {
+        self.strategies.write().await.unset(prefix)
+    }
+
+    /// Get the strategy governing `name`
+    pub async fn strategy_for(&self, name: &Name) -> Arc<dyn Strategy> {
+        self.strategies.read().await.find(name)
+    }
+
+    /// List the configured strategy choices as (prefix, strategy name)
+    pub async fn strategy_choices(&self) -> Vec<(Name, &'static str)> {
+        self.strategies.read().await.list()
+    }
+
+    /// Hand the routes of the closed face `from` over to face `to`, of the
+    /// same peer, and drop everything else about `from`
+    pub async fn move_face(&self, from: &str, to: &str) {
+        self.fib.write().await.move_face(from, to);
+        self.remove_face(from).await;
+    }
+
+    /// Drop all routes, pending records, queued packets and strategy
+    /// measurements of a closed face
+    pub async fn remove_face(&self, face_id: &str) {
+        if let Some(scheduler) = &self.scheduler {
+            scheduler.remove_face(face_id);
+        }
+        for strategy in self.strategies.read().await.strategies() {
+            strategy.on_face_removed(face_id);
+        }
+
+        let mut fib = self.fib.write().await;
+        fib.remove_face(face_id);
+        self.metrics.fib_size.set(fib.entries.len() as u64);
+        drop(fib);
+
+        let mut pit = self.pit.lock().await;
+        for entry in pit.values_mut() {
+            entry.in_records.retain(|r| r.face_id != face_id);
+            entry.out_records.retain(|r| r.face_id != face_id);
+        }
+        pit.retain(|_, entry| !entry.in_records.is_empty());
+        self.metrics.pit_size.set(pit.len() as u64);
+    }
+
+    /// Number of pending Interests
+    pub async fn pit_size(&self) -> usize {
+        self.pit.lock().await.len()
+    }
+
+    /// List the pending Interests, including expired ones not removed yet
+    pub async fn pit_entries(&self) -> Vec<PendingInterest> {
+        let now = Instant::now();
+        let pit = self.pit.lock().await;
+        pit.iter()
+            .map(|(name, entry)| PendingInterest {
+                name: name.clone(),
+                in_faces: entry.in_records.iter().map(|r| r.face_id.clone()).collect(),
+                out_faces: entry.out_records.iter().map(|r| r.face_id.clone()).collect(),
+                remaining: entry.expiry.saturating_duration_since(now),
+            })
+            .collect()
+    }
+
+    /// Drop the pending Interests under `prefix`, only the expired ones
+    /// unless `all`. Returns the number of entries dropped.
+    pub async fn flush_pit(&self, prefix: &Name, all: bool) -> usize {
+        let now = Instant::now();
+        let mut pit = self.pit.lock().await;
+        let before = pit.len();
+        pit.retain(|name, entry| {
+            let under = prefix.len() <= name.len() && prefix.is_prefix_of(name);
+            !under || (!all && entry.expiry > now)
+        });
+        self.metrics.pit_size.set(pit.len() as u64);
+        before - pit.len()
+    }
+
+    /// Process an Interest received on `in_face`.
+    ///
+    /// Returns `false` if the forwarder has no route for the Interest, in
+    /// which case the caller may deliver it to the local application.
+    #[instrument(
+        skip_all,
+        fields(name = %interest.name(), face = %in_face.id(), decision = field::Empty, out_faces = field::Empty)
+    )]
+    pub async fn process_interest(
+        &self,
+        interest: Interest,
+        in_face: &Arc<dyn NdnFace>,
+        faces: &[Arc<dyn NdnFace>],
+    ) -> Result<bool, FaceError> {
+        let name = interest.name().clone();
+
+        let command = [RIB_REGISTER_PREFIX, RIB_UNREGISTER_PREFIX]
+            .iter()
+            .any(|command| command_argument(&name, command).is_some());
+        if command && !in_face.is_local() {
+            // Routes are managed from this host only, never by neighbors
+            warn!("Dropping RIB command {} from non-local face {}", name, in_face.id());
+            Span::current().record("decision", "non_local_command");
+            return Ok(true);
+        }
+        if let Some(prefix) = command_argument(&name, RIB_REGISTER_PREFIX) {
+            self.add_route(prefix, in_face.id(), 0).await;
+            self.send(in_face, NdnPacket::Data(Data::new(name, "OK"))).await?;
+            return Ok(true);
+        }
+        if let Some(prefix) = command_argument(&name, RIB_UNREGISTER_PREFIX) {
+            self.remove_route(&prefix, in_face.id()).await;
+            self.send(in_face, NdnPacket::Data(Data::new(name, "OK"))).await?;
+            return Ok(true);
+        }
+
+        let now = Instant::now();
+        let lifetime = Duration::from_millis(interest.lifetime_ms as u64);
+
+        let strategy = self.strategy_for(&name).await;
+
+        let matched = self.fib.read().await.longest_prefix_match(&name, now);
+        let (fib_prefix, next_hops, flooded) = match matched {
+            Some((prefix, next_hops)) => {
+                self.metrics.fib_hits.add_labeled(&Labels::new().prefix(prefix.to_string()), 1);
+                (prefix, next_hops, false)
+            }
+            None => {
+                self.metrics.fib_misses.increment();
+                if !strategy.floods_unmatched() {
+                    Span::current().record("decision", "no_route");
+                    return Ok(false);
+                }
+                let everywhere = faces
+                    .iter()
+                    .map(|face| NextHop {
+                        face_id: face.id().to_string(),
+                        cost: 0,
+                    })
+                    .collect();
+                (name.clone(), everywhere, true)
+            }
+        };
+
+        // An Interest whose HopLimit ran out goes no further. The Nack names
+        // this forwarder, so traceroute can tell the hops apart.
+        let mut interest = interest;
+        match interest.hop_limit {
+            Some(0) => {
+                debug!("HopLimit of Interest {} from {} ran out", name, in_face.id());
+                Span::current().record("decision", "hop_limit");
+                let mut nack = Nack::new(interest, NackReason::HopLimit);
+                if let Some(node_id) = &self.node_id {
+                    nack = nack.with_reporter(node_id.clone());
+                }
+                self.send(in_face, NdnPacket::Nack(nack)).await?;
+                return Ok(true);
+            }
+            Some(hop_limit) => interest.hop_limit = Some(hop_limit - 1),
+            None => {}
+        }
+
+        let mut pit = self.pit.lock().await;
+        self.expire_entries(&mut pit, now);
+
+        let entry = pit.entry(name.clone()).or_insert_with(|| {
+            self.metrics.pit_inserts.increment();
+            PitEntry {
+                in_records: Vec::new(),
+                out_records: Vec::new(),
+                expiry: now + lifetime,
+                fib_prefix,
+                strategy,
+                flooded,
+            }
+        });
+
+        // Loop detection: the same nonce arriving from a different face
+        if entry
+            .in_records
+            .iter()
+            .any(|r| r.nonce == interest.nonce && r.face_id != in_face.id())
+        {
+            drop(pit);
+            debug!("Looping Interest {} from {}", name, in_face.id());
+            Span::current().record("decision", "loop");
+            self.send(in_face, NdnPacket::Nack(Nack::new(interest, NackReason::Duplicate))).await?;
+            return Ok(true);
+        }
+
+        // A retransmission from the same downstream, or an Interest after
+        // the upstreams had time to answer, is forwarded again. Only
+        // Interests from new downstreams are aggregated meanwhile.
+        let retransmitted = entry
+            .in_records
+            .iter()
+            .any(|r| r.face_id == in_face.id() && r.nonce != interest.nonce);
+        let suppressed = entry
+            .out_records
+            .iter()
+            .any(|r| now.duration_since(r.sent_at) < RETRANSMISSION_INTERVAL);
+        let aggregated = !entry.out_records.is_empty() && !retransmitted && suppressed;
+        entry.in_records.retain(|r| r.face_id != in_face.id());
+        entry.in_records.push(InRecord {
+            face_id: in_face.id().to_string(),
+            nonce: interest.nonce,
+            interest: interest.clone(),
+            expiry: now + lifetime,
+        });
+        entry.expiry = entry.expiry.max(now + lifetime);
+
+        if aggregated {
+            trace!("Aggregated Interest {} from {}", name, in_face.id());
+            Span::current().record("decision", "aggregated");
+            self.metrics.pit_hits.increment();
+            return Ok(true);
+        }
+
+        // Let the strategy pick among the usable next hops, unless flooding
+        let usable: Vec<NextHop> = next_hops
+            .into_iter()
+            .filter(|hop| hop.face_id != in_face.id())
+            .filter(|hop| faces.iter().any(|f| f.id() == hop.face_id))
+            .collect();
+        let flooded = entry.flooded;
+        // Flooded Interests have no FIB prefix to break them down by
+        let labels = if flooded {
+            Labels::new()
+        } else {
+            Labels::new().prefix(entry.fib_prefix.to_string())
+        };
+        let selected = if flooded {
+            debug!("Flooding Interest {} without a route", name);
+            usable.into_iter().map(|hop| hop.face_id).collect()
+        } else {
+            entry.strategy.select_next_hops(&entry.fib_prefix, &interest, &usable)
+        };
+        let out_faces: Vec<Arc<dyn NdnFace>> = selected
+            .iter()
+            .filter_map(|id| faces.iter().find(|f| f.id() == id))
+            .map(Arc::clone)
+            .collect();
+
+        if out_faces.is_empty() {
+            pit.remove(&name);
+            self.metrics.pit_size.set(pit.len() as u64);
+            drop(pit);
+            debug!("No usable next hop for {}", name);
+            Span::current().record("decision", "no_next_hop");
+            self.send(in_face, NdnPacket::Nack(Nack::new(interest, NackReason::NoRoute))).await?;
+            return Ok(true);
+        }
+
+        for out_face in &out_faces {
+            entry.out_records.retain(|r| r.face_id != out_face.id());
+            entry.out_records.push(OutRecord {
+                face_id: out_face.id().to_string(),
+                nonce: interest.nonce,
+                sent_at: now,
+            });
+        }
+        self.metrics.pit_size.set(pit.len() as u64);
+        drop(pit);
+
+        let span = Span::current();
+        span.record("decision", if flooded { "flooded" } else { "forwarded" });
+        span.record("out_faces", field::display(selected.join(",")));
+        for out_face in out_faces {
+            debug!("Forwarding Interest {} from {} to {}", name, in_face.id(), out_face.id());
+            if let Err(e) = self.send(&out_face, NdnPacket::Interest(interest.clone())).await {
+                warn!("Failed to forward Interest {} to {}: {}", name, out_face.id(), e);
+                self.forwarding_failed(&name, out_face.id()).await;
+                continue;
+            }
+            self.metrics.interests_forwarded.add_labeled(&labels.clone().face(out_face.id()), 1);
+        }
+
+        Ok(true)
+    }
+
+    /// Tell the strategy that an Interest could not be sent to `face_id`,
+    /// which no longer counts as pending upstream
+    async fn forwarding_failed(&self, name: &Name, face_id: &str) {
+        let mut pit = self.pit.lock().await;
+        if let Some(entry) = pit.get_mut(name) {
+            entry.strategy.on_failure(&entry.fib_prefix, face_id);
+            entry.out_records.retain(|r| r.face_id != face_id);
+        }
+    }
+
+    /// Process a Data packet received on `in_face`.
+    ///
+    /// Returns `false` if the Data did not match any pending Interest
+    /// forwarded to `in_face`.
+    #[instrument(skip_all, fields(name = %data.name(), face = %in_face.id()))]
+    pub async fn process_data(&self, data: &Data, in_face: &Arc<dyn NdnFace>, faces: &[Arc<dyn NdnFace>]) -> bool {
+        let entries = {
+            let mut pit = self.pit.lock().await;
+            let entries = take_satisfied(&mut pit, data.name(), in_face.id());
+            self.metrics.pit_size.set(pit.len() as u64);
+            entries
+        };
+
+        if entries.is_empty() {
+            self.metrics.pit_misses.increment();
+            return false;
+        }
+
+        let now = Instant::now();
+        let mut returned_to: Vec<&str> = Vec::new();
+        for entry in &entries {
+            if let Some(out) = entry.out_records.iter().find(|r| r.face_id == in_face.id()) {
+                let rtt = now.duration_since(out.sent_at);
+                entry.strategy.on_data(&entry.fib_prefix, in_face.id(), rtt);
+                self.metrics
+                    .interest_processing_time
+                    .observe_labeled(&Labels::new().face(in_face.id()), rtt.as_micros() as u64);
+            }
+
+            if let Some((prefix, lifetime)) = entry.strategy.learn_route(data.name(), in_face.id()) {
+                let mut fib = self.fib.write().await;
+                fib.expire(now);
+                if entry.flooded {
+                    debug!("Learned route {} -> {}", prefix, in_face.id());
+                    fib.learn_next_hop(prefix, in_face.id(), 0, now + lifetime);
+                } else {
+                    fib.refresh_next_hop(&entry.fib_prefix, in_face.id(), now + lifetime);
+                }
+                self.metrics.fib_size.set(fib.entries.len() as u64);
+            }
+
+            for record in entry.in_records.iter().filter(|r| r.expiry > now) {
+                // A downstream in several satisfied entries gets the Data once
+                if record.face_id == in_face.id() || returned_to.contains(&record.face_id.as_str()) {
+                    continue;
+                }
+                returned_to.push(&record.face_id);
+                match faces.iter().find(|f| f.id() == record.face_id) {
+                    Some(face) => {
+                        trace!("Returning Data {} to {}", data.name(), face.id());
+                        if let Err(e) = self.send(face, NdnPacket::Data(data.clone())).await {
+                            warn!("Failed to return Data to {}: {}", face.id(), e);
+                        }
+                    }
+                    None => debug!("Downstream face {} is gone", record.face_id),
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Process a Nack received on `in_face`.
+    ///
+    /// Returns `false` if the Nack did not match any pending Interest
+    /// forwarded to `in_face` with the nonce of the Nack.
+    pub async fn process_nack(&self, nack: &Nack, in_face: &Arc<dyn NdnFace>, faces: &[Arc<dyn NdnFace>]) -> bool {
+        let entry = {
+            let mut pit = self.pit.lock().await;
+            let Some(entry) = pit.get_mut(nack.name()) else {
+                return false;
+            };
+            if !entry
+                .out_records
+                .iter()
+                .any(|r| r.face_id == in_face.id() && r.nonce == nack.interest.nonce)
+            {
+                debug!("Ignoring Nack {} from {}, which was not sent the Interest", nack.name(), in_face.id());
+                return false;
+            }
+            entry.strategy.on_failure(&entry.fib_prefix, in_face.id());
+            entry.out_records.retain(|r| r.face_id != in_face.id());
+            if !entry.out_records.is_empty() {
+                // Still waiting on other upstreams
+                return true;
+            }
+            let entry = pit.remove(nack.name());
+            self.metrics.pit_size.set(pit.len() as u64);
+            entry
+        };
+
+        if let Some(entry) = entry {
+            for record in entry.in_records {
+                if let Some(face) = faces.iter().find(|f| f.id() == record.face_id) {
+                    let mut returned = Nack::new(record.interest, nack.reason);
+                    returned.reporter = nack.reporter.clone();
+                    if let Err(e) = self.send(face, NdnPacket::Nack(returned)).await {
+                        warn!("Failed to return Nack to {}: {}", face.id(), e);
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Remove expired PIT entries
+    fn expire_entries(&self, pit: &mut HashMap<Name, PitEntry>, now: Instant) {
+        let before = pit.len();
+        pit.retain(|_, entry| {
+            if entry.expiry > now {
+                return true;
+            }
+            // Upstreams that never answered count as failures
+            for record in &entry.out_records {
+                entry.strategy.on_failure(&entry.fib_prefix, &record.face_id);
+            }
+            false
+        });
+        let expired = before - pit.len();
+        if expired > 0 {
+            self.metrics.pit_expirations.add(expired as u64);
+        }
+    }
+}
+
+/// Take the PIT entries Data named `name` from `face_id` satisfies out of
+/// the PIT: the entry of that very name, and the in-records with
+/// CanBePrefix of the entries of its prefixes. Only entries forwarded to
+/// `face_id` are satisfied, so unsolicited Data is not returned downstream.
+fn take_satisfied(pit: &mut HashMap<Name, PitEntry>, name: &Name, face_id: &str) -> Vec<PitEntry> {
+    let mut satisfied = Vec::new();
+    for len in (0..=name.len()).rev() {
+        let key = name.prefix(len);
+        let Some(entry) = pit.get_mut(&key) else {
+            continue;
+        };
+        if !entry.out_records.iter().any(|r| r.face_id == face_id) {
+            continue;
+        }
+        if len == name.len() {
+            satisfied.extend(pit.remove(&key));
+            continue;
+        }
+
+        // Interests for the exact prefix keep waiting
+        let (matched, rest): (Vec<InRecord>, Vec<InRecord>) = std::mem::take(&mut entry.in_records)
+            .into_iter()
+            .partition(|r| r.interest.can_be_prefix);
+        entry.in_records = rest;
+        if matched.is_empty() {
+            continue;
+        }
+        let remaining = (!entry.in_records.is_empty()).then(|| entry.clone());
+        let mut taken = match remaining {
+            Some(remaining) => remaining,
+            None => pit.remove(&key).expect("entry was just found"),
+        };
+        taken.in_records = matched;
+        satisfied.push(taken);
+    }
+    satisfied
+}
+
+/// Extract the prefix argument of a command Interest name
+fn command_argument(name: &Name, command: &str) -> Option<Name> {
+    let command = Name::from_string(command).ok()?;
+    if name.len() <= command.len() || !command.is_prefix_of(name) {
+        return None;
+    }
+
+    let mut prefix = Name::new();
+    for component in name.components().skip(command.len()) {
+        prefix.push(NameComponent::new(component.as_bytes().clone()));
+    }
+    Some(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{face::FaceEvent, link_face::{link_face_pair, LinkFace}};
+
+    /// A face named `id` and, at its other end, the application behind it
+    fn pair(id: &str, metrics: &Arc<UdcnMetrics>) -> (LinkFace, Arc<dyn NdnFace>) {
+        let (app, face) = link_face_pair(&format!("{}-app", id), id, metrics);
+        (app, Arc::new(face))
+    }
+
+    #[test]
+    fn test_fib_longest_prefix_match() {
+        let mut fib = Fib::default();
+        fib.add_next_hop(Name::from_string("/a").unwrap(), "face1", 10);
+        fib.add_next_hop(Name::from_string("/a/b").unwrap(), "face2", 10);
+        fib.add_next_hop(Name::from_string("/a/b").unwrap(), "face3", 5);
+
+        let now = Instant::now();
+        let (prefix, next_hops) = fib
+            .longest_prefix_match(&Name::from_string("/a/b/c").unwrap(), now)
+            .unwrap();
+        assert_eq!(prefix, Name::from_string("/a/b").unwrap());
+        assert_eq!(next_hops[0].face_id, "face3");
+
+        let (_, next_hops) = fib
+            .longest_prefix_match(&Name::from_string("/a/x").unwrap(), now)
+            .unwrap();
+        assert_eq!(next_hops[0].face_id, "face1");
+
+        assert!(fib
+            .longest_prefix_match(&Name::from_string("/z").unwrap(), now)
+            .is_none());
+
+        fib.remove_face("face1");
+        assert!(fib
+            .longest_prefix_match(&Name::from_string("/a/x").unwrap(), now)
+            .is_none());
+    }
+
+    #[test]
+    fn test_fib_learned_next_hops_expire() {
+        let mut fib = Fib::default();
+        let prefix = Name::from_string("/a").unwrap();
+        let now = Instant::now();
+        fib.add_next_hop(prefix.clone(), "configured", 10);
+        fib.learn_next_hop(prefix.clone(), "learned", 0, now + Duration::from_secs(1));
+        // Learning never turns a configured next hop into a learned one
+        fib.learn_next_hop(prefix.clone(), "configured", 0, now + Duration::from_secs(1));
+
+        let (_, next_hops) = fib.longest_prefix_match(&prefix, now).unwrap();
+        assert_eq!(next_hops.len(), 2);
+
+        let later = now + Duration::from_secs(2);
+        let (_, next_hops) = fib.longest_prefix_match(&prefix, later).unwrap();
+        assert_eq!(next_hops, vec![NextHop { face_id: "configured".to_string(), cost: 10 }]);
+
+        fib.expire(later);
+        assert_eq!(fib.entries[&prefix].next_hops.len(), 1);
+    }
+
+    #[test]
+    fn test_fib_move_face() {
+        let mut fib = Fib::default();
+        let a = Name::from_string("/a").unwrap();
+        let b = Name::from_string("/b").unwrap();
+        let now = Instant::now();
+        fib.add_next_hop(a.clone(), "peer#1", 10);
+        fib.learn_next_hop(b.clone(), "peer#1", 0, now + Duration::from_secs(1));
+        fib.add_next_hop(b.clone(), "peer#2", 5);
+
+        fib.move_face("peer#1", "peer#2");
+        assert_eq!(fib.entries[&a].next_hops, vec![NextHop { face_id: "peer#2".to_string(), cost: 10 }]);
+        // The next hop of its own stays as it was
+        fib.remove_face("peer#1");
+        assert_eq!(fib.entries[&b].next_hops, vec![NextHop { face_id: "peer#2".to_string(), cost: 5 }]);
+        assert!(fib.expiries.is_empty());
+    }
+
+    #[test]
+    fn test_command_argument() {
+        let name = Name::from_string("/localhost/udcn/rib/register/example/data").unwrap();
+        assert_eq!(
+            command_argument(&name, RIB_REGISTER_PREFIX),
+            Some(Name::from_string("/example/data").unwrap())
+        );
+        assert_eq!(command_argument(&name, RIB_UNREGISTER_PREFIX), None);
+    }
+
+    #[tokio::test]
+    async fn test_forward_between_link_faces() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (consumer, downstream) = pair("downstream", &metrics);
+        let (producer, upstream) = pair("upstream", &metrics);
+        let faces = vec![Arc::clone(&downstream), Arc::clone(&upstream)];
+
+        let forwarder = Forwarder::new(Arc::clone(&metrics));
+        forwarder.add_route(Name::from_string("/p").unwrap(), "upstream", 0).await;
+
+        let name = Name::from_string("/p/x").unwrap();
+        assert!(forwarder.process_interest(Interest::new(name.clone()), &downstream, &faces).await.unwrap());
+        assert!(matches!(producer.next_event().await, Some(FaceEvent::InterestReceived(_))));
+
+        let data = Data::new(name, "x");
+        assert!(forwarder.process_data(&data, &upstream, &faces).await);
+        assert!(matches!(consumer.next_event().await, Some(FaceEvent::DataReceived(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rib_commands_only_from_local_faces() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (local_app, local) = link_face_pair("local-app", "local", &metrics);
+        let local: Arc<dyn NdnFace> = Arc::new(local.with_local(true));
+        let (_neighbor_app, neighbor) = pair("neighbor", &metrics);
+        let faces = vec![Arc::clone(&local), Arc::clone(&neighbor)];
+
+        let forwarder = Forwarder::new(Arc::clone(&metrics));
+        let command = Name::from_string(&format!("{}/p", RIB_REGISTER_PREFIX)).unwrap();
+        assert!(forwarder.process_interest(Interest::new(command.clone()), &neighbor, &faces).await.unwrap());
+        assert!(forwarder.routes().await.is_empty());
+
+        assert!(forwarder.process_interest(Interest::new(command), &local, &faces).await.unwrap());
+        assert!(matches!(local_app.next_event().await, Some(FaceEvent::DataReceived(_))));
+        assert_eq!(forwarder.routes().await[0].next_hops[0].face_id, "local");
+    }
+
+    #[tokio::test]
+    async fn test_data_satisfies_forwarded_interests_only() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (consumer, downstream) = pair("downstream", &metrics);
+        let (other_consumer, other_downstream) = pair("other", &metrics);
+        let (producer, upstream) = pair("upstream", &metrics);
+        let (_attacker, attacker) = pair("attacker", &metrics);
+        let faces = vec![
+            Arc::clone(&downstream),
+            Arc::clone(&other_downstream),
+            Arc::clone(&upstream),
+            Arc::clone(&attacker),
+        ];
+
+        let forwarder = Forwarder::new(Arc::clone(&metrics));
+        forwarder.add_route(Name::from_string("/p").unwrap(), "upstream", 0).await;
+        let prefix = Name::from_string("/p/video").unwrap();
+        let interest = Interest::new(prefix.clone()).with_can_be_prefix(true).with_nonce(1);
+        assert!(forwarder.process_interest(interest, &downstream, &faces).await.unwrap());
+        assert!(matches!(producer.next_event().await, Some(FaceEvent::InterestReceived(_))));
+        // Aggregated onto the same entry, without CanBePrefix
+        let interest = Interest::new(prefix.clone()).with_nonce(2);
+        assert!(forwarder.process_interest(interest, &other_downstream, &faces).await.unwrap());
+
+        // Data from a face the Interest was not forwarded to is unsolicited
+        let data = Data::new(Name::from_string("/p/video/seg=0").unwrap(), "x");
+        assert!(!forwarder.process_data(&data, &attacker, &faces).await);
+        assert_eq!(forwarder.pit_size().await, 1);
+
+        // Longer Data satisfies the CanBePrefix Interest, not the exact one
+        assert!(forwarder.process_data(&data, &upstream, &faces).await);
+        assert!(matches!(consumer.next_event().await, Some(FaceEvent::DataReceived(d)) if d.name() == data.name()));
+        let entries = forwarder.pit_entries().await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].in_faces, vec!["other".to_string()]);
+        assert!(tokio::time::timeout(Duration::from_millis(50), other_consumer.next_event()).await.is_err());
+
+        assert!(forwarder.process_data(&Data::new(prefix, "x"), &upstream, &faces).await);
+        assert!(matches!(other_consumer.next_event().await, Some(FaceEvent::DataReceived(_))));
+        assert_eq!(forwarder.pit_size().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_retransmission_forwarded_again() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (_consumer, downstream) = pair("downstream", &metrics);
+        let (_other_consumer, other_downstream) = pair("other", &metrics);
+        let (producer, upstream) = pair("upstream", &metrics);
+        let faces = vec![Arc::clone(&downstream), Arc::clone(&other_downstream), Arc::clone(&upstream)];
+
+        let forwarder = Forwarder::new(Arc::clone(&metrics));
+        forwarder.add_route(Name::from_string("/p").unwrap(), "upstream", 0).await;
+        let name = Name::from_string("/p/x").unwrap();
+        let interest = |nonce| Interest::new(name.clone()).with_nonce(nonce);
+
+        forwarder.process_interest(interest(1), &downstream, &faces).await.unwrap();
+        assert!(matches!(producer.next_event().await, Some(FaceEvent::InterestReceived(i)) if i.nonce == 1));
+
+        // The consumer retransmits with a new nonce
+        forwarder.process_interest(interest(2), &downstream, &faces).await.unwrap();
+        assert!(matches!(producer.next_event().await, Some(FaceEvent::InterestReceived(i)) if i.nonce == 2));
+
+        // Another consumer is aggregated while the upstream has time to answer
+        forwarder.process_interest(interest(3), &other_downstream, &faces).await.unwrap();
+        assert!(tokio::time::timeout(Duration::from_millis(50), producer.next_event()).await.is_err());
+
+        // Later it is worth asking again
+        tokio::time::sleep(RETRANSMISSION_INTERVAL).await;
+        forwarder.process_interest(interest(4), &other_downstream, &faces).await.unwrap();
+        assert!(matches!(producer.next_event().await, Some(FaceEvent::InterestReceived(i)) if i.nonce == 4));
+        assert_eq!(forwarder.pit_entries().await[0].out_faces, vec!["upstream".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_nack_only_from_upstream() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (consumer, downstream) = pair("downstream", &metrics);
+        let (_producer, upstream) = pair("upstream", &metrics);
+        let (_attacker, attacker) = pair("attacker", &metrics);
+        let faces = vec![Arc::clone(&downstream), Arc::clone(&upstream), Arc::clone(&attacker)];
+
+        let forwarder = Forwarder::new(Arc::clone(&metrics));
+        forwarder.add_route(Name::from_string("/p").unwrap(), "upstream", 0).await;
+        let interest = Interest::new(Name::from_string("/p/x").unwrap()).with_nonce(1);
+        forwarder.process_interest(interest.clone(), &downstream, &faces).await.unwrap();
+
+        // Neither a face the Interest was not sent to, nor another nonce, can
+        // Nack it
+        let nack = Nack::new(interest.clone(), NackReason::NoRoute);
+        assert!(!forwarder.process_nack(&nack, &attacker, &faces).await);
+        let stale = Nack::new(interest.clone().with_nonce(2), NackReason::NoRoute);
+        assert!(!forwarder.process_nack(&stale, &upstream, &faces).await);
+        assert_eq!(forwarder.pit_size().await, 1);
+
+        assert!(forwarder.process_nack(&nack, &upstream, &faces).await);
+        assert!(matches!(consumer.next_event().await, Some(FaceEvent::NackReceived(_))));
+        assert_eq!(forwarder.pit_size().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_self_learning_floods_then_follows_data() {
+        use crate::strategy::SelfLearningStrategy;
+
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (consumer, downstream) = pair("downstream", &metrics);
+        let (silent, upstream1) = pair("upstream1", &metrics);
+        let (producer, upstream2) = pair("upstream2", &metrics);
+        let faces = vec![Arc::clone(&downstream), Arc::clone(&upstream1), Arc::clone(&upstream2)];
+
+        let forwarder = Forwarder::new(Arc::clone(&metrics));
+        forwarder.set_strategy(Name::new(), Arc::new(SelfLearningStrategy::default())).await;
+
+        // No route yet: the Interest goes everywhere
+        let name = Name::from_string("/p/x").unwrap();
+        assert!(forwarder.process_interest(Interest::new(name.clone()), &downstream, &faces).await.unwrap());
+        assert!(matches!(silent.next_event().await, Some(FaceEvent::InterestReceived(_))));
+        assert!(matches!(producer.next_event().await, Some(FaceEvent::InterestReceived(_))));
+
+        assert!(forwarder.process_data(&Data::new(name, "x"), &upstream2, &faces).await);
+        assert!(matches!(consumer.next_event().await, Some(FaceEvent::DataReceived(_))));
+
+        // The Data taught the forwarder where /p lives
+        let routes = forwarder.routes().await;
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].prefix, Name::from_string("/p").unwrap());
+        assert_eq!(routes[0].next_hops[0].face_id, "upstream2");
+
+        let name = Name::from_string("/p/y").unwrap();
+        assert!(forwarder.process_interest(Interest::new(name), &downstream, &faces).await.unwrap());
+        assert!(matches!(producer.next_event().await, Some(FaceEvent::InterestReceived(_))));
+        assert!(tokio::time::timeout(Duration::from_millis(100), silent.next_event()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_and_flush_pit() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        // The far ends stay open, so that forwarding succeeds
+        let (_downstream_app, downstream) = pair("downstream", &metrics);
+        let (_upstream_app, upstream) = pair("upstream", &metrics);
+        let faces = vec![Arc::clone(&downstream), Arc::clone(&upstream)];
+
+        let forwarder = Forwarder::new(Arc::clone(&metrics));
+        forwarder.add_route(Name::from_string("/p").unwrap(), "upstream", 0).await;
+        forwarder.add_route(Name::from_string("/q").unwrap(), "upstream", 0).await;
+        for (name, lifetime_ms) in [("/p/short", 10), ("/p/long", 60_000), ("/q", 60_000)] {
+            let interest = Interest::new(Name::from_string(name).unwrap()).with_lifetime(lifetime_ms);
+            forwarder.process_interest(interest, &downstream, &faces).await.unwrap();
+        }
+
+        let entries = forwarder.pit_entries().await;
+        assert_eq!(entries.len(), 3);
+        let long = entries.iter().find(|e| e.name == Name::from_string("/p/long").unwrap()).unwrap();
+        assert_eq!(long.in_faces, vec!["downstream".to_string()]);
+        assert_eq!(long.out_faces, vec!["upstream".to_string()]);
+        assert!(long.remaining > Duration::from_secs(50));
+
+        // Only the expired entry goes unless all are asked for
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let p = Name::from_string("/p").unwrap();
+        assert_eq!(forwarder.flush_pit(&p, false).await, 1);
+        assert_eq!(forwarder.flush_pit(&p, true).await, 1);
+        assert_eq!(forwarder.pit_size().await, 1);
+        assert_eq!(forwarder.flush_pit(&Name::new(), true).await, 1);
+    }
+}
@@ -3,49 +3,429 @@
 //! This module provides utilities for fragmenting large NDN packets
 //! into smaller chunks for transmission over QUIC, and reassembling
 //! them on the receiving end.
+//!
+//! Every fragment carries a header with the message ID, its index and the
+//! total fragment count. A [`Reassembler`] keeps a context per face and
+//! message ID, so fragments may arrive out of order and interleaved with
+//! those of other messages. Each face may hold a budget of bytes in
+//! incomplete messages, counting the slots kept for their fragments as
+//! well as the fragments themselves, and a limited number of them. A
+//! message still missing fragments after the timeout is dropped.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use log::{debug, trace};
+use rust_udcn_common::{
+    metrics::{Labels, UdcnMetrics},
+    Error,
+};
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::error::FaceError;
+
+/// First byte of a fragment (distinct from every NDN packet type)
+pub const FRAGMENT_MARKER: u8 = 0xFE;
+
+/// Size of the fragment header: marker, message ID, index, count, payload length
+pub const FRAGMENT_HEADER_SIZE: usize = 1 + 4 + 2 + 2 + 2;
+
+/// Smallest fragment payload, however small the path MTU or congestion
+/// window seem
+pub const MIN_FRAGMENT_SIZE: usize = 256;
+
+/// Default time to wait for the missing fragments of a message
+pub const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default limit on the payload bytes each face may buffer for incomplete
+/// messages
+pub const DEFAULT_REASSEMBLY_BUDGET: usize = 4 * 1024 * 1024;
+
+/// Default limit on the incomplete messages of each face
+pub const DEFAULT_REASSEMBLY_MESSAGES: usize = 256;
+
+/// Header preceding each fragment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FragmentHeader {
+    /// ID shared by all fragments of one message
+    pub message_id: u32,
+
+    /// Position of this fragment in the message
+    pub index: u16,
+
+    /// Total number of fragments in the message
+    pub count: u16,
+}
+
+/// Fragment a large packet into chunks of at most `fragment_size` payload bytes
+pub fn fragment_packet(packet: &[u8], fragment_size: usize, message_id: u32) -> Result<Vec<Bytes>, FaceError> {
+    let fragment_size = fragment_size.min(u16::MAX as usize);
+    let count = calculate_fragment_count(packet.len(), fragment_size);
+    if count > u16::MAX as usize {
+        return Err(FaceError::TooLarge {
+            size: packet.len(),
+            limit: fragment_size * u16::MAX as usize,
+        });
+    }
+
+    let fragments: Vec<Bytes> = packet
+        .chunks(fragment_size)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut fragment = BytesMut::with_capacity(FRAGMENT_HEADER_SIZE + chunk.len());
+            fragment.put_u8(FRAGMENT_MARKER);
+            fragment.put_u32(message_id);
+            fragment.put_u16(index as u16);
+            fragment.put_u16(count as u16);
+            fragment.put_u16(chunk.len() as u16);
+            fragment.extend_from_slice(chunk);
+            fragment.freeze()
+        })
+        .collect();
+
+    trace!(
+        "Fragmented message {} of size {} into {} fragments",
+        message_id,
+        packet.len(),
+        fragments.len()
+    );
+
+    Ok(fragments)
+}
+
+/// Parse one fragment from the start of `buf`.
+///
+/// Returns the header, the payload and the number of bytes consumed.
+pub fn parse_fragment(buf: &[u8]) -> Result<(FragmentHeader, Bytes, usize), Error> {
+    if buf.len() < FRAGMENT_HEADER_SIZE {
+        return Err(Error::NdnPacket("Truncated fragment header".to_string()));
+    }
+
+    let mut cursor = buf;
+    if cursor.get_u8() != FRAGMENT_MARKER {
+        return Err(Error::NdnPacket("Not a fragment".to_string()));
+    }
+    let header = FragmentHeader {
+        message_id: cursor.get_u32(),
+        index: cursor.get_u16(),
+        count: cursor.get_u16(),
+    };
+    let len = cursor.get_u16() as usize;
+
+    if header.count == 0 || header.index >= header.count {
+        return Err(Error::NdnPacket(format!(
+            "Invalid fragment {} of {} for message {}",
+            header.index, header.count, header.message_id
+        )));
+    }
+    if cursor.len() < len {
+        return Err(Error::NdnPacket("Truncated fragment payload".to_string()));
+    }
+
+    let payload = Bytes::copy_from_slice(&cursor[..len]);
+    Ok((header, payload, FRAGMENT_HEADER_SIZE + len))
+}
+
+/// Options of a [`Reassembler`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReassemblyOptions {
+    /// Time to wait for the missing fragments of a message
+    pub timeout: Duration,
+
+    /// Bytes each face may hold in incomplete messages
+    pub face_budget_bytes: usize,
+
+    /// Incomplete messages each face may hold
+    pub face_max_messages: usize,
+}
+
+impl Default for ReassemblyOptions {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_REASSEMBLY_TIMEOUT,
+            face_budget_bytes: DEFAULT_REASSEMBLY_BUDGET,
+            face_max_messages: DEFAULT_REASSEMBLY_MESSAGES,
+        }
+    }
+}
+
+/// Fragments of one message received so far
+#[derive(Debug)]
+struct Context {
+    /// The fragments by index, `None` until received
+    fragments: Vec<Option<Bytes>>,
+    received: usize,
+    bytes: usize,
+    started: Instant,
+}
+
+/// Bytes taken by the fragment slots of a message of `count` fragments
+fn slots_size(count: usize) -> usize {
+    count * mem::size_of::<Option<Bytes>>()
+}
+
+impl Context {
+    /// Bytes charged to the budget of the face: the payload received and
+    /// the slots of the fragments
+    fn charge(&self) -> usize {
+        self.bytes + slots_size(self.fragments.len())
+    }
+
+    /// Indexes of the fragments not received yet
+    fn missing(&self) -> Vec<u16> {
+        (0..self.fragments.len())
+            .filter(|index| self.fragments[*index].is_none())
+            .map(|index| index as u16)
+            .collect()
+    }
+}
+
+/// A message is told apart by the face it arrives on and its ID
+type MessageKey = (String, u32);
+
+#[derive(Debug, Default)]
+struct State {
+    contexts: HashMap<MessageKey, Context>,
+
+    /// Bytes charged to each face for its contexts
+    buffered: HashMap<String, usize>,
+}
+
+impl State {
+    fn remove(&mut self, key: &MessageKey) -> Option<Context> {
+        let context = self.contexts.remove(key)?;
+        if let Some(buffered) = self.buffered.get_mut(&key.0) {
+            *buffered -= context.charge();
+            if *buffered == 0 {
+                self.buffered.remove(&key.0);
+            }
+        }
+        Some(context)
+    }
+
+    /// Number of incomplete messages of a face
+    fn pending(&self, face_id: &str) -> usize {
+        self.contexts.keys().filter(|(face, _)| face == face_id).count()
+    }
+
+    /// Oldest incomplete message of a face, other than `except`
+    fn oldest(&self, face_id: &str, except: u32) -> Option<MessageKey> {
+        self.contexts
+            .iter()
+            .filter(|((face, message_id), _)| face == face_id && *message_id != except)
+            .min_by_key(|(_, context)| context.started)
+            .map(|(key, _)| key.clone())
+    }
+}
+
+/// Collects the fragments of the messages arriving on one or more faces
+/// until each message is complete
+#[derive(Debug)]
+pub struct Reassembler {
+    options: ReassemblyOptions,
+    state: Mutex<State>,
+    metrics: Arc<UdcnMetrics>,
+}
+
+impl Reassembler {
+    /// Create a reassembler reporting into `metrics`
+    pub fn new(options: ReassemblyOptions, metrics: Arc<UdcnMetrics>) -> Self {
+        Self {
+            options,
+            state: Mutex::new(State::default()),
+            metrics,
+        }
+    }
+
+    /// Options of this reassembler
+    pub fn options(&self) -> &ReassemblyOptions {
+        &self.options
+    }
 
-use anyhow::{anyhow, Result};
-use bytes::{Bytes, BytesMut};
-use log::trace;
-use std::collections::VecDeque;
-
-/// Fragment a large packet into smaller chunks
-pub fn fragment_packet(packet: &[u8], fragment_size: usize) -> Vec<Bytes> {
-    let mut fragments = Vec::new();
-    let mut offset = 0;
-    
-    while offset < packet.len() {
-        let end = std::cmp::min(offset + fragment_size, packet.len());
-        let fragment = Bytes::copy_from_slice(&packet[offset..end]);
-        fragments.push(fragment);
-        offset = end;
-    }
-    
-    trace!("Fragmented packet of size {} into {} fragments", packet.len(), fragments.len());
-    
-    fragments
-}
-
-/// Reassemble fragments into a complete packet
-pub fn assemble_fragments(fragments: VecDeque<Bytes>) -> Result<Bytes> {
-    // Calculate the total size
-    let total_size: usize = fragments.iter().map(|f| f.len()).sum();
-    
-    if total_size == 0 {
-        return Err(anyhow!("No fragments to assemble"));
-    }
-    
-    // Create a buffer large enough for all fragments
-    let mut buffer = BytesMut::with_capacity(total_size);
-    
-    // Add all fragments to the buffer
-    for fragment in fragments {
-        buffer.extend_from_slice(&fragment);
-    }
-    
-    trace!("Assembled {} bytes from fragments", buffer.len());
-    
-    Ok(buffer.freeze())
+    /// Add a fragment received on `face_id`
+    ///
+    /// Returns the message once its last fragment arrives, in whichever
+    /// order the fragments came. Messages past the timeout are dropped
+    /// first; then, if the face holds more than its budget or its number
+    /// of messages, its oldest incomplete messages, and this one last. A
+    /// message whose fragment slots alone exceed the budget is dropped
+    /// before any room is made for it.
+    pub fn insert(&self, face_id: &str, header: FragmentHeader, payload: Bytes, now: Instant) -> Option<Bytes> {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        self.expire_locked(state, now);
+
+        let key = (face_id.to_string(), header.message_id);
+        if !state.contexts.contains_key(&key) {
+            let slots = slots_size(header.count as usize);
+            if slots > self.options.face_budget_bytes {
+                debug!(
+                    "[Face {}] Message {} of {} fragments exceeds the reassembly budget, dropping it",
+                    face_id, header.message_id, header.count
+                );
+                self.record_drops(state, face_id, 1);
+                return None;
+            }
+
+            let mut dropped = 0;
+            while state.pending(face_id) >= self.options.face_max_messages {
+                let Some(victim) = state.oldest(face_id, header.message_id) else {
+                    break;
+                };
+                debug!("[Face {}] Too many incomplete messages, dropping message {}", face_id, victim.1);
+                state.remove(&victim);
+                dropped += 1;
+            }
+            if dropped > 0 {
+                self.record_drops(state, face_id, dropped);
+            }
+
+            state.contexts.insert(
+                key.clone(),
+                Context {
+                    fragments: vec![None; header.count as usize],
+                    received: 0,
+                    bytes: 0,
+                    started: now,
+                },
+            );
+            *state.buffered.entry(face_id.to_string()).or_default() += slots;
+        }
+        let context = state.contexts.get_mut(&key).expect("message context was just created");
+
+        if context.fragments.len() != header.count as usize {
+            debug!(
+                "[Face {}] Fragment count mismatch for message {} ({} then {}), dropping it",
+                face_id,
+                header.message_id,
+                context.fragments.len(),
+                header.count
+            );
+            state.remove(&key);
+            self.record_drops(state, face_id, 1);
+            return None;
+        }
+
+        let slot = &mut context.fragments[header.index as usize];
+        if slot.is_some() {
+            trace!("[Face {}] Duplicate fragment {} of message {}", face_id, header.index, header.message_id);
+            return None;
+        }
+        context.received += 1;
+        context.bytes += payload.len();
+        *state.buffered.entry(face_id.to_string()).or_default() += payload.len();
+        *slot = Some(payload);
+
+        if context.received == context.fragments.len() {
+            let context = state.remove(&key).expect("message was just inserted");
+            let mut buffer = BytesMut::with_capacity(context.bytes);
+            for fragment in context.fragments.into_iter().flatten() {
+                buffer.extend_from_slice(&fragment);
+            }
+            trace!("[Face {}] Reassembled message {} ({} bytes)", face_id, header.message_id, buffer.len());
+            self.metrics.messages_reassembled.add_labeled(&Labels::new().face(face_id), 1);
+            self.record_buffered(state, face_id);
+            return Some(buffer.freeze());
+        }
+
+        // Make room by dropping the oldest other incomplete messages
+        let mut dropped = 0;
+        while state.buffered.get(face_id).copied().unwrap_or_default() > self.options.face_budget_bytes {
+            let victim = state.oldest(face_id, header.message_id).unwrap_or_else(|| key.clone());
+            debug!("[Face {}] Reassembly budget exceeded, dropping message {}", face_id, victim.1);
+            state.remove(&victim);
+            dropped += 1;
+            if victim == key {
+                break;
+            }
+        }
+        self.record_drops(state, face_id, dropped);
+        None
+    }
+
+    /// Drop the incomplete messages older than the timeout. Returns how many.
+    pub fn expire(&self, now: Instant) -> usize {
+        let mut state = self.lock();
+        self.expire_locked(&mut state, now)
+    }
+
+    /// Forget the incomplete messages of a closed face
+    pub fn remove_face(&self, face_id: &str) {
+        let mut state = self.lock();
+        let keys: Vec<MessageKey> = state.contexts.keys().filter(|(face, _)| face == face_id).cloned().collect();
+        for key in &keys {
+            state.remove(key);
+        }
+        if !keys.is_empty() {
+            debug!("[Face {}] Discarded {} incomplete message(s) of the closed face", face_id, keys.len());
+        }
+        self.record_buffered(&state, face_id);
+    }
+
+    /// Indexes of the fragments a message of `face_id` still misses, or
+    /// `None` when no fragment of it is held
+    pub fn missing(&self, face_id: &str, message_id: u32) -> Option<Vec<u16>> {
+        let key = (face_id.to_string(), message_id);
+        self.lock().contexts.get(&key).map(Context::missing)
+    }
+
+    /// Number of incomplete messages of a face
+    pub fn pending(&self, face_id: &str) -> usize {
+        self.lock().pending(face_id)
+    }
+
+    /// Bytes charged to a face for its incomplete messages: their payload
+    /// and fragment slots
+    pub fn buffered_bytes(&self, face_id: &str) -> usize {
+        self.lock().buffered.get(face_id).copied().unwrap_or_default()
+    }
+
+    fn expire_locked(&self, state: &mut State, now: Instant) -> usize {
+        let expired: Vec<MessageKey> = state
+            .contexts
+            .iter()
+            .filter(|(_, context)| now.duration_since(context.started) > self.options.timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in &expired {
+            let Some(context) = state.remove(key) else {
+                continue;
+            };
+            debug!(
+                "[Face {}] Reassembly of message {} timed out missing fragments {:?} of {}",
+                key.0,
+                key.1,
+                context.missing(),
+                context.fragments.len()
+            );
+            self.metrics.reassembly_timeouts.add_labeled(&Labels::new().face(&key.0), 1);
+            self.record_buffered(state, &key.0);
+        }
+        expired.len()
+    }
+
+    fn record_drops(&self, state: &State, face_id: &str, dropped: u64) {
+        if dropped > 0 {
+            self.metrics.reassembly_drops.add_labeled(&Labels::new().face(face_id), dropped);
+        }
+        self.record_buffered(state, face_id);
+    }
+
+    /// Update the buffered bytes gauges after the contexts of `face_id` changed
+    fn record_buffered(&self, state: &State, face_id: &str) {
+        let buffered = state.buffered.get(face_id).copied().unwrap_or_default();
+        let total = state.buffered.values().sum::<usize>();
+        self.metrics.reassembly_buffered_bytes.with(&Labels::new().face(face_id)).set(buffered as u64);
+        self.metrics.reassembly_buffered_bytes.set(total as u64);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("reassembler lock poisoned")
+    }
 }
 
 /// Check if a packet needs to be fragmented
@@ -53,40 +433,187 @@ pub fn needs_fragmentation(packet_size: usize, mtu: usize) -> bool {
     packet_size > mtu
 }
 
+/// Payload size of the fragments sent on a path carrying `mtu` bytes per
+/// datagram, with a congestion window of `congestion_window` bytes
+///
+/// A fragment and its header fill one datagram, and no more than half the
+/// window, so that a loss under congestion costs less than the window.
+pub fn adaptive_fragment_size(mtu: usize, congestion_window: u64) -> usize {
+    let window_share = usize::try_from(congestion_window / 2).unwrap_or(usize::MAX);
+    mtu.min(window_share)
+        .saturating_sub(FRAGMENT_HEADER_SIZE)
+        .clamp(MIN_FRAGMENT_SIZE, u16::MAX as usize)
+}
+
 /// Calculate the number of fragments needed
 pub fn calculate_fragment_count(packet_size: usize, fragment_size: usize) -> usize {
-    (packet_size + fragment_size - 1) / fragment_size
+    packet_size.div_ceil(fragment_size)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    fn reassembler(options: ReassemblyOptions) -> (Reassembler, Arc<UdcnMetrics>) {
+        let metrics = Arc::new(UdcnMetrics::new());
+        (Reassembler::new(options, Arc::clone(&metrics)), metrics)
+    }
+
     #[test]
     fn test_fragmentation_and_reassembly() {
         // Create a test packet
-        let packet = vec![0u8; 10000];
-        
+        let packet: Vec<u8> = (0..10000).map(|i| i as u8).collect();
+
         // Fragment it
-        let fragments = fragment_packet(&packet, 1000);
-        
+        let fragments = fragment_packet(&packet, 1000, 7).unwrap();
+
         // Check that we have the right number of fragments
         assert_eq!(fragments.len(), 10);
-        
-        // Reassemble the fragments
-        let reassembled = assemble_fragments(fragments.into()).unwrap();
-        
+
+        // Reassemble the fragments, out of order and with a duplicate
+        let (reassembler, metrics) = reassembler(ReassemblyOptions::default());
+        let now = Instant::now();
+        let insert = |fragment: &Bytes| {
+            let (header, payload, consumed) = parse_fragment(fragment).unwrap();
+            assert_eq!(consumed, fragment.len());
+            assert_eq!(header.message_id, 7);
+            reassembler.insert("a", header, payload, now)
+        };
+        for fragment in fragments[1..].iter().rev().chain(&fragments[2..3]) {
+            assert_eq!(insert(fragment), None);
+        }
+        assert_eq!(reassembler.missing("a", 7), Some(vec![0]));
+
         // Check that the reassembled packet matches the original
-        assert_eq!(reassembled.len(), packet.len());
-        assert_eq!(reassembled, Bytes::from(packet));
+        assert_eq!(insert(&fragments[0]), Some(Bytes::from(packet)));
+        assert_eq!(reassembler.pending("a"), 0);
+        assert_eq!(reassembler.buffered_bytes("a"), 0);
+        assert_eq!(metrics.messages_reassembled.value(), 1);
+        assert_eq!(metrics.reassembly_drops.value(), 0);
+    }
+
+    #[test]
+    fn test_interleaved_messages() {
+        let a = fragment_packet(&[1u8; 30], 10, 1).unwrap();
+        let b = fragment_packet(&[2u8; 30], 10, 2).unwrap();
+
+        // The same message ID on another face is another message
+        let (reassembler, _) = reassembler(ReassemblyOptions::default());
+        let now = Instant::now();
+        let mut complete = Vec::new();
+        for (face, fragment) in a.iter().zip(b.iter()).flat_map(|(x, y)| [("a", x), ("a", y), ("b", x)]) {
+            let (header, payload, _) = parse_fragment(fragment).unwrap();
+            complete.extend(reassembler.insert(face, header, payload, now));
+        }
+
+        assert_eq!(
+            complete,
+            vec![Bytes::from(vec![1u8; 30]), Bytes::from(vec![2u8; 30]), Bytes::from(vec![1u8; 30])]
+        );
+    }
+
+    #[test]
+    fn test_incomplete_messages_are_dropped() {
+        // Room for one message of two 10 byte fragments
+        let message_size = 20 + slots_size(2);
+        let options = ReassemblyOptions {
+            timeout: Duration::from_millis(100),
+            face_budget_bytes: message_size - 5,
+            face_max_messages: DEFAULT_REASSEMBLY_MESSAGES,
+        };
+        let (reassembler, metrics) = reassembler(options);
+        let now = Instant::now();
+        let first_fragment = |message_id| {
+            let fragments = fragment_packet(&[0u8; 20], 10, message_id).unwrap();
+            parse_fragment(&fragments[0]).unwrap()
+        };
+
+        let (header, payload, _) = first_fragment(1);
+        assert_eq!(reassembler.insert("a", header, payload, now), None);
+
+        // Another face has a budget of its own
+        let (header, payload, _) = first_fragment(1);
+        assert_eq!(reassembler.insert("b", header, payload, now), None);
+        assert_eq!(metrics.reassembly_buffered_bytes.value(), 2 * (message_size - 10) as u64);
+
+        // Exceeding the budget of the face drops its older message
+        let (header, payload, _) = first_fragment(2);
+        assert_eq!(reassembler.insert("a", header, payload, now), None);
+        assert_eq!(reassembler.missing("a", 1), None);
+        assert_eq!(reassembler.missing("a", 2), Some(vec![1]));
+        assert_eq!(metrics.reassembly_drops.with(&Labels::new().face("a")).value(), 1);
+
+        // Fragments disagreeing on the count drop their message
+        let (mut header, payload, _) = first_fragment(2);
+        header.count = 3;
+        assert_eq!(reassembler.insert("a", header, payload, now), None);
+        assert_eq!(reassembler.pending("a"), 0);
+        assert_eq!(metrics.reassembly_drops.value(), 2);
+
+        // And the remaining one times out
+        assert_eq!(reassembler.expire(now + Duration::from_millis(200)), 1);
+        assert_eq!(reassembler.pending("b"), 0);
+        assert_eq!(metrics.reassembly_timeouts.value(), 1);
+        assert_eq!(metrics.reassembly_buffered_bytes.value(), 0);
     }
-    
+
+    #[test]
+    fn test_message_limits() {
+        let options = ReassemblyOptions {
+            face_budget_bytes: 64 * 1024,
+            face_max_messages: 2,
+            ..ReassemblyOptions::default()
+        };
+        let (reassembler, metrics) = reassembler(options);
+        let now = Instant::now();
+        let fragment = |message_id, count| {
+            let header = FragmentHeader {
+                message_id,
+                index: 0,
+                count,
+            };
+            // Later messages start later, so the oldest is always the first
+            let at = now + Duration::from_millis(message_id as u64);
+            reassembler.insert("a", header, Bytes::from_static(b"x"), at)
+        };
+
+        // Slots for the largest fragment count exceed the budget by themselves
+        assert_eq!(fragment(1, u16::MAX), None);
+        assert_eq!(reassembler.pending("a"), 0);
+        assert_eq!(reassembler.buffered_bytes("a"), 0);
+        assert_eq!(metrics.reassembly_drops.value(), 1);
+
+        // The slots of a message count against the budget
+        assert_eq!(fragment(2, 100), None);
+        assert_eq!(reassembler.buffered_bytes("a"), 1 + slots_size(100));
+
+        // A third message drops the oldest
+        assert_eq!(fragment(3, 2), None);
+        assert_eq!(fragment(4, 2), None);
+        assert_eq!(reassembler.pending("a"), 2);
+        assert_eq!(reassembler.missing("a", 2), None);
+        assert_eq!(reassembler.buffered_bytes("a"), 2 * (1 + slots_size(2)));
+        assert_eq!(metrics.reassembly_drops.value(), 2);
+    }
+
     #[test]
     fn test_needs_fragmentation() {
         assert!(needs_fragmentation(1500, 1200));
         assert!(!needs_fragmentation(1000, 1200));
     }
-    
+
+    #[test]
+    fn test_adaptive_fragment_size() {
+        // Follows the MTU while the window is large
+        assert_eq!(adaptive_fragment_size(1200, 12_000), 1200 - FRAGMENT_HEADER_SIZE);
+        assert_eq!(adaptive_fragment_size(1452, 12_000), 1452 - FRAGMENT_HEADER_SIZE);
+
+        // Shrinks with the window, down to a floor
+        assert_eq!(adaptive_fragment_size(1452, 1600), 800 - FRAGMENT_HEADER_SIZE);
+        assert_eq!(adaptive_fragment_size(1452, 100), MIN_FRAGMENT_SIZE);
+        assert_eq!(adaptive_fragment_size(0, u64::MAX), MIN_FRAGMENT_SIZE);
+    }
+
     #[test]
     fn test_calculate_fragment_count() {
         assert_eq!(calculate_fragment_count(1000, 1000), 1);
@@ -0,0 +1,329 @@
+//! HTTP-to-NDN gateway for legacy clients.
+//!
+//! Each HTTP GET is mapped to the NDN object named by the URL path under a
+//! configured prefix, fetched segment by segment over a face and answered
+//! with the reassembled content. Browsers, curl and other clients that do
+//! not speak NDN can then read published content, most of it straight out
+//! of the forwarders' content stores.
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::{
+    body::Incoming,
+    header::{self, HeaderValue},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use log::{debug, info, warn};
+use percent_encoding::percent_decode_str;
+use rust_udcn_common::{
+    metrics::UdcnMetrics,
+    ndn::{Interest, Name, NameComponent},
+};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use tokio::net::TcpListener;
+
+use crate::{
+    error::{FetchError, TransportError},
+    fetcher::{FetchOptions, SegmentFetcher},
+    ndn_face::NdnFace,
+    DEFAULT_INTEREST_TIMEOUT_MS,
+};
+
+/// Header naming the NDN object a response was fetched from
+pub const NDN_NAME_HEADER: &str = "x-ndn-name";
+
+/// Options of an HTTP gateway
+#[derive(Debug, Clone)]
+pub struct GatewayOptions {
+    /// Prefix the URL paths are mapped under
+    pub prefix: Name,
+
+    /// Last component added to URL paths ending in a slash, as a web
+    /// server would serve index.html for a directory
+    pub index: Option<String>,
+
+    /// Lifetime of the Interest for the first segment (in milliseconds)
+    pub timeout_ms: u64,
+
+    /// Options of the fetches of segmented objects
+    pub fetch: FetchOptions,
+}
+
+impl Default for GatewayOptions {
+    fn default() -> Self {
+        Self {
+            prefix: Name::new(),
+            index: Some("index.html".to_string()),
+            timeout_ms: DEFAULT_INTEREST_TIMEOUT_MS,
+            fetch: FetchOptions::default(),
+        }
+    }
+}
+
+/// Name of the object a URL path stands for: the percent-decoded path
+/// segments appended to `prefix`, and `index` for a path ending in a slash
+pub fn url_to_name(prefix: &Name, path: &str, index: Option<&str>) -> Name {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let mut name = prefix.clone();
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        name.push(NameComponent::new(percent_decode_str(segment).collect::<Vec<u8>>()));
+    }
+    if let Some(index) = index {
+        if path.is_empty() || path.ends_with('/') {
+            name.push(NameComponent::new(index.as_bytes().to_vec()));
+        }
+    }
+    name
+}
+
+/// An object fetched for a request
+struct Object {
+    content: Bytes,
+
+    /// Freshness period of its first segment (in milliseconds)
+    freshness_period_ms: u32,
+}
+
+/// Serves NDN content to HTTP clients
+pub struct HttpGateway {
+    /// The listening socket
+    listener: TcpListener,
+
+    /// State shared by the connections
+    state: Arc<GatewayState>,
+}
+
+/// What the connections of a gateway share
+struct GatewayState {
+    /// Face the Interests are sent on
+    face: Arc<dyn NdnFace>,
+
+    options: GatewayOptions,
+
+    /// Where the fetchers record segment RTTs
+    metrics: Option<Arc<UdcnMetrics>>,
+}
+
+impl HttpGateway {
+    /// Accept HTTP clients on `addr`, fetching what they ask for over `face`
+    pub async fn bind(addr: SocketAddr, face: Arc<dyn NdnFace>, options: GatewayOptions) -> Result<Self, TransportError> {
+        let listener = TcpListener::bind(addr).await.map_err(|source| TransportError::Bind {
+            addr: addr.to_string(),
+            source,
+        })?;
+        info!("Serving {} over HTTP on {}", options.prefix, listener.local_addr()?);
+
+        Ok(Self {
+            listener,
+            state: Arc::new(GatewayState {
+                face,
+                options,
+                metrics: None,
+            }),
+        })
+    }
+
+    /// Record the RTTs of the segments fetched in `metrics`
+    pub fn with_metrics(mut self, metrics: Arc<UdcnMetrics>) -> Self {
+        // The state is only shared once the gateway runs
+        if let Some(state) = Arc::get_mut(&mut self.state) {
+            state.metrics = Some(metrics);
+        }
+        self
+    }
+
+    /// Get the local address the gateway is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr, TransportError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve clients until the task is cancelled
+    pub async fn run(self) {
+        loop {
+            let (stream, peer) = match self.listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Failed to accept HTTP client: {}", e);
+                    continue;
+                }
+            };
+            let state = Arc::clone(&self.state);
+            tokio::spawn(async move {
+                let service = service_fn(|request| {
+                    let state = Arc::clone(&state);
+                    async move { Ok::<_, Infallible>(state.respond(request).await) }
+                });
+                if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+                    debug!("HTTP connection from {} failed: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+impl GatewayState {
+    /// Answer one request
+    async fn respond(&self, request: Request<Incoming>) -> Response<Full<Bytes>> {
+        if request.method() != Method::GET && request.method() != Method::HEAD {
+            let mut response = plain(StatusCode::METHOD_NOT_ALLOWED, "Only GET and HEAD are supported\n");
+            response.headers_mut().insert(header::ALLOW, HeaderValue::from_static("GET, HEAD"));
+            return response;
+        }
+
+        let name = url_to_name(&self.options.prefix, request.uri().path(), self.options.index.as_deref());
+        debug!("HTTP {} {} for {}", request.method(), request.uri(), name);
+        let object = match self.fetch(&name).await {
+            Ok(object) => object,
+            Err(e) => {
+                debug!("Failed to fetch {}: {}", name, e);
+                return plain(error_status(&e), &format!("Failed to fetch {}: {}\n", name, e));
+            }
+        };
+
+        let content_type = name
+            .components()
+            .last()
+            .and_then(|last| std::str::from_utf8(last.as_bytes()).ok())
+            .and_then(|last| mime_guess::from_path(last).first_raw())
+            .unwrap_or("application/octet-stream");
+        let mut response = Response::new(Full::new(object.content));
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        // Caches downstream keep the content as long as the CS would
+        headers.insert(header::CACHE_CONTROL, format!("max-age={}", object.freshness_period_ms / 1000).parse().expect("valid header"));
+        if let Ok(value) = HeaderValue::from_str(&name.to_string()) {
+            headers.insert(NDN_NAME_HEADER, value);
+        }
+        response
+    }
+
+    /// Fetch the object named `name`: its segments, or the Data of that
+    /// very name when it is not segmented
+    async fn fetch(&self, name: &Name) -> Result<Object, FetchError> {
+        let timeout_ms = self.options.timeout_ms;
+        // Published objects are served from the content stores, stale or not
+        let interest = |name: Name| Interest::new(name).with_must_be_fresh(false);
+        let first = match self.face.express_interest(interest(name.with_segment(0)), timeout_ms).await {
+            Ok(first) => first,
+            Err(_) => {
+                let data = self
+                    .face
+                    .express_interest(interest(name.clone()), timeout_ms)
+                    .await
+                    .map_err(|e| FetchError::new(name.clone(), e))?;
+                return Ok(Object {
+                    content: data.content,
+                    freshness_period_ms: data.freshness_period_ms,
+                });
+            }
+        };
+
+        let content = match first.final_block_id {
+            Some(last) if last > 0 => {
+                // Asks for the first segment again, which the CS answers
+                let mut fetcher = SegmentFetcher::new(Arc::clone(&self.face), self.options.fetch.clone());
+                if let Some(metrics) = &self.metrics {
+                    fetcher = fetcher.with_metrics(Arc::clone(metrics));
+                }
+                fetcher.fetch(name).await?.content
+            }
+            _ => first.content,
+        };
+        Ok(Object {
+            content,
+            freshness_period_ms: first.freshness_period_ms,
+        })
+    }
+}
+
+/// Status answering a request whose object could not be fetched
+fn error_status(error: &FetchError) -> StatusCode {
+    match error {
+        FetchError::Nacked { .. } => StatusCode::NOT_FOUND,
+        FetchError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+        FetchError::Face { .. } | FetchError::NoFinalBlock(_) => StatusCode::BAD_GATEWAY,
+    }
+}
+
+/// Response with a plain text body
+fn plain(status: StatusCode, body: &str) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body.to_string())));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{link_face::link_face_pair, FileServer};
+    use rust_udcn_common::metrics::UdcnMetrics;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    fn name(s: &str) -> Name {
+        Name::from_string(s).unwrap()
+    }
+
+    /// Send a request and read the whole response
+    async fn get(addr: SocketAddr, path: &str) -> (String, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+
+        let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8(response[..end].to_vec()).unwrap();
+        (head, response[end + 4..].to_vec())
+    }
+
+    #[test]
+    fn test_url_to_name() {
+        let prefix = name("/site");
+        assert_eq!(url_to_name(&prefix, "/docs/a%20b.txt?x=1", None), name("/site/docs/a b.txt"));
+        assert_eq!(url_to_name(&prefix, "/docs/", Some("index.html")), name("/site/docs/index.html"));
+        assert_eq!(url_to_name(&prefix, "/", None), prefix);
+        assert_eq!(url_to_name(&Name::new(), "//a//b", Some("index.html")), name("/a/b"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_segmented_object() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (consumer, producer) = link_face_pair("gateway", "producer", &metrics);
+        let consumer: Arc<dyn NdnFace> = Arc::new(consumer);
+        let producer: Arc<dyn NdnFace> = Arc::new(producer);
+
+        let content: Bytes = (0..10_000u32).map(|i| i as u8).collect::<Vec<u8>>().into();
+        let server = FileServer::new(producer, &name("/site/data/report.pdf"), content.clone(), 1024);
+        tokio::spawn(async move { server.run(|_| {}).await });
+
+        let options = GatewayOptions {
+            prefix: name("/site"),
+            timeout_ms: 200,
+            ..Default::default()
+        };
+        let gateway = HttpGateway::bind("127.0.0.1:0".parse().unwrap(), consumer, options)
+            .await
+            .unwrap();
+        let addr = gateway.local_addr().unwrap();
+        tokio::spawn(gateway.run());
+
+        let (head, body) = get(addr, "/data/report.pdf").await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+        assert!(head.contains("content-type: application/pdf"), "{}", head);
+        assert!(head.contains("x-ndn-name: /site/data/report.pdf"), "{}", head);
+        assert_eq!(body, content);
+
+        // The producer leaves other names unanswered
+        let (head, _) = get(addr, "/data/missing").await;
+        assert!(head.starts_with("HTTP/1.1 504"), "{}", head);
+    }
+}
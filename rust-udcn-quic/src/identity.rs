@@ -0,0 +1,189 @@
+//! Identities of peers authenticated by their TLS certificates.
+//!
+//! A face whose peer presented a certificate is known by that certificate
+//! rather than by the address it connected from. Each connection of the
+//! peer still gets a face of its own, so two processes sharing a
+//! certificate do not push each other off, but the routes of the peer go
+//! from one of its faces to the next and survive it reconnecting from
+//! elsewhere. The identity is the NDN name the certificate carries as an
+//! `ndn:` URI in its subjectAltName, or the SHA-256 fingerprint of the
+//! certificate when it carries none. A name is only as trustworthy as the
+//! verification of the certificate: servers get client certificates
+//! verified against their client CA, clients may skip verifying servers.
+
+use quinn::Connection;
+use ring::digest;
+use rust_udcn_common::ndn::Name;
+use std::fmt;
+
+/// URI scheme of the subjectAltName naming the NDN identity of a certificate
+pub const NDN_URI_SCHEME: &str = "ndn:";
+
+/// DER tag of an object identifier
+const TAG_OID: u8 = 0x06;
+
+/// DER tag of an octet string
+const TAG_OCTET_STRING: u8 = 0x04;
+
+/// DER tag of the extensions of a TBSCertificate (`[3]`, explicit)
+const TAG_EXTENSIONS: u8 = 0xa3;
+
+/// DER tag of a uniformResourceIdentifier GeneralName (`[6]`, implicit)
+const TAG_URI: u8 = 0x86;
+
+/// Encoded object identifier of the subjectAltName extension (2.5.29.17)
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+/// Identity of a peer authenticated by its certificate
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PeerIdentity {
+    /// NDN name the certificate carries as an `ndn:` URI
+    Name(Name),
+
+    /// SHA-256 fingerprint of a certificate carrying no NDN name
+    Fingerprint([u8; 32]),
+}
+
+impl PeerIdentity {
+    /// Identity of the holder of a certificate
+    pub fn from_certificate(certificate: &rustls::Certificate) -> Self {
+        match ndn_name(&certificate.0) {
+            Some(name) => PeerIdentity::Name(name),
+            None => {
+                let mut fingerprint = [0u8; 32];
+                fingerprint.copy_from_slice(digest::digest(&digest::SHA256, &certificate.0).as_ref());
+                PeerIdentity::Fingerprint(fingerprint)
+            }
+        }
+    }
+
+    /// Identity of the peer of a connection, from the leaf of the
+    /// certificate chain it presented, if any
+    pub fn of_connection(connection: &Connection) -> Option<Self> {
+        peer_certificates(connection)?.first().map(Self::from_certificate)
+    }
+
+    /// ID of this peer, which the IDs of its faces start with
+    pub fn face_id(&self) -> String {
+        format!("quic:{}", self)
+    }
+
+    /// ID of the face of this peer over its connection number `connection`
+    pub fn connection_face_id(&self, connection: u64) -> String {
+        format!("{}#{}", self.face_id(), connection)
+    }
+}
+
+impl fmt::Display for PeerIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerIdentity::Name(name) => write!(f, "{}", name),
+            PeerIdentity::Fingerprint(fingerprint) => {
+                write!(f, "sha256:")?;
+                for b in fingerprint {
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Certificate chain the peer of a connection presented, leaf first
+pub(crate) fn peer_certificates(connection: &Connection) -> Option<Vec<rustls::Certificate>> {
+    connection
+        .peer_identity()?
+        .downcast::<Vec<rustls::Certificate>>()
+        .ok()
+        .map(|certs| *certs)
+}
+
+/// NDN name of the first `ndn:` URI in the subjectAltName of a certificate
+fn ndn_name(certificate: &[u8]) -> Option<Name> {
+    let (_, certificate, _) = der_element(certificate)?;
+    let (_, tbs, _) = der_element(certificate)?;
+    let (_, extensions) = der_elements(tbs).find(|(tag, _)| *tag == TAG_EXTENSIONS)?;
+    let (_, extensions, _) = der_element(extensions)?;
+
+    let (_, alt_names) = der_elements(extensions).find_map(|(_, extension)| {
+        let mut fields = der_elements(extension);
+        match fields.next() {
+            // The critical flag may come before the value
+            Some((TAG_OID, OID_SUBJECT_ALT_NAME)) => fields.find(|(tag, _)| *tag == TAG_OCTET_STRING),
+            _ => None,
+        }
+    })?;
+    let (_, alt_names, _) = der_element(alt_names)?;
+
+    der_elements(alt_names)
+        .filter(|(tag, _)| *tag == TAG_URI)
+        .filter_map(|(_, uri)| std::str::from_utf8(uri).ok()?.strip_prefix(NDN_URI_SCHEME))
+        .filter_map(|name| Name::from_string(name).ok())
+        .find(|name| !name.is_empty())
+}
+
+/// Split the DER element at the start of `der` into its tag, its contents
+/// and the bytes following it
+fn der_element(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count].iter().fold(0usize, |len, &b| (len << 8) | b as usize);
+        (len, &rest[count..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+/// Tags and contents of the DER elements laid end to end in `der`
+fn der_elements(mut der: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    std::iter::from_fn(move || {
+        let (tag, contents, rest) = der_element(der)?;
+        der = rest;
+        Some((tag, contents))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{CertificateParams, SanType};
+
+    fn certificate(alt_names: Vec<SanType>) -> rustls::Certificate {
+        let mut params = CertificateParams::new(Vec::new());
+        params.subject_alt_names = alt_names;
+        rustls::Certificate(rcgen::Certificate::from_params(params).unwrap().serialize_der().unwrap())
+    }
+
+    #[test]
+    fn test_identity_from_certificate() {
+        let named = certificate(vec![
+            SanType::DnsName("alice.example".to_string()),
+            SanType::URI("https://alice.example/".to_string()),
+            SanType::URI("ndn:/alice/KEY/1".to_string()),
+        ]);
+        let identity = PeerIdentity::from_certificate(&named);
+        assert_eq!(identity, PeerIdentity::Name(Name::from_string("/alice/KEY/1").unwrap()));
+        assert_eq!(identity.face_id(), "quic:/alice/KEY/1");
+        assert_eq!(identity.connection_face_id(7), "quic:/alice/KEY/1#7");
+
+        // Without a name, the same certificate gives the same fingerprint
+        let unnamed = certificate(vec![SanType::DnsName("localhost".to_string())]);
+        let identity = PeerIdentity::from_certificate(&unnamed);
+        assert!(matches!(identity, PeerIdentity::Fingerprint(_)));
+        assert_eq!(identity, PeerIdentity::from_certificate(&unnamed));
+        assert_ne!(identity, PeerIdentity::from_certificate(&certificate(Vec::new())));
+        let face_id = identity.face_id();
+        assert!(face_id.starts_with("quic:sha256:") && face_id.len() == 12 + 64, "{}", face_id);
+
+        assert!(ndn_name(b"\x30\x82\xff").is_none());
+    }
+}
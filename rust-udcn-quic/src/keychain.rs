@@ -0,0 +1,628 @@
+//! Keys of NDN identities and the certificates binding them.
+//!
+//! A [`KeyChain`] keeps one ECDSA P-256 key per identity in a directory,
+//! along with the current certificate of that key. Certificates follow the
+//! NDN certificate format: a Data named `/<identity>/KEY/<key-id>/<issuer>/<version>`
+//! whose content is the SubjectPublicKeyInfo of the key, signed with
+//! SignatureSha256WithEcdsa by the key its KeyLocator names and valid for
+//! the ValidityPeriod in its SignatureInfo. An identity gets a self-signed
+//! certificate, or one signed by the key of another identity, which builds
+//! trust hierarchies: a testbed root signs its sites, which sign their
+//! nodes.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, UnparsedPublicKey, ECDSA_P256_SHA256_ASN1, ECDSA_P256_SHA256_ASN1_SIGNING},
+};
+use rust_udcn_common::{
+    ndn::{Name, NameComponent},
+    tlv::{self, TlvElement},
+};
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::KeyChainError;
+
+/// Component separating an identity from the ID of its key
+pub const KEY_COMPONENT: &str = "KEY";
+
+/// Issuer component of self-signed certificates
+pub const SELF_ISSUER: &str = "self";
+
+/// How long certificates are valid unless told otherwise
+pub const DEFAULT_VALIDITY: Duration = Duration::from_secs(365 * 24 * 3600);
+
+/// FreshnessPeriod of certificates served as Data (in milliseconds)
+const CERTIFICATE_FRESHNESS_MS: u32 = 3_600_000;
+
+const TLV_META_INFO: u16 = 0x14;
+const TLV_CONTENT_TYPE: u16 = 0x18;
+const TLV_SIGNATURE_INFO: u16 = 0x16;
+const TLV_SIGNATURE_VALUE: u16 = 0x17;
+const TLV_SIGNATURE_TYPE: u16 = 0x1b;
+const TLV_KEY_LOCATOR: u16 = 0x1c;
+const TLV_VALIDITY_PERIOD: u16 = 0xfd;
+const TLV_NOT_BEFORE: u16 = 0xfe;
+const TLV_NOT_AFTER: u16 = 0xff;
+
+/// ContentType of a Data carrying a public key
+const CONTENT_TYPE_KEY: u8 = 2;
+
+/// SignatureType of SignatureSha256WithEcdsa
+const SIGNATURE_SHA256_WITH_ECDSA: u8 = 3;
+
+/// DER prefix of the SubjectPublicKeyInfo of an uncompressed P-256 point
+const P256_SPKI_PREFIX: &[u8] = &[
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48,
+    0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+/// Time span over which a certificate is valid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidityPeriod {
+    pub not_before: SystemTime,
+    pub not_after: SystemTime,
+}
+
+impl ValidityPeriod {
+    /// Validity from `not_before` for `duration`, to the second
+    pub fn new(not_before: SystemTime, duration: Duration) -> Self {
+        let not_before = UNIX_EPOCH + Duration::from_secs(unix_secs(not_before));
+        Self {
+            not_before,
+            not_after: not_before + duration,
+        }
+    }
+
+    /// Validity from now for `duration`
+    pub fn from_now(duration: Duration) -> Self {
+        Self::new(SystemTime::now(), duration)
+    }
+
+    /// Whether `time` falls within the period
+    pub fn contains(&self, time: SystemTime) -> bool {
+        self.not_before <= time && time <= self.not_after
+    }
+
+    fn encode(&self, buf: &mut BytesMut) {
+        let mut value = BytesMut::new();
+        put_tlv(&mut value, TLV_NOT_BEFORE, format_timestamp(self.not_before).as_bytes());
+        put_tlv(&mut value, TLV_NOT_AFTER, format_timestamp(self.not_after).as_bytes());
+        put_tlv(buf, TLV_VALIDITY_PERIOD, &value);
+    }
+
+    fn decode(mut value: &[u8]) -> Result<Self, KeyChainError> {
+        let mut not_before = None;
+        let mut not_after = None;
+        while !value.is_empty() {
+            let (tlv_type, field) = read_tlv(&mut value)?;
+            let time = || {
+                std::str::from_utf8(field)
+                    .ok()
+                    .and_then(parse_timestamp)
+                    .ok_or_else(|| malformed("invalid ValidityPeriod timestamp"))
+            };
+            match tlv_type {
+                TLV_NOT_BEFORE => not_before = Some(time()?),
+                TLV_NOT_AFTER => not_after = Some(time()?),
+                _ => {}
+            }
+        }
+        match (not_before, not_after) {
+            (Some(not_before), Some(not_after)) => Ok(Self { not_before, not_after }),
+            _ => Err(malformed("incomplete ValidityPeriod")),
+        }
+    }
+}
+
+/// A public key bound to the identity named in it, signed by an issuer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NdnCertificate {
+    name: Name,
+    public_key: Bytes,
+    signer: Name,
+    validity: ValidityPeriod,
+    signature: Bytes,
+}
+
+impl NdnCertificate {
+    /// Full name: `/<identity>/KEY/<key-id>/<issuer>/<version>`
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// Identity the key belongs to
+    pub fn identity(&self) -> Name {
+        self.name.prefix(self.name.len().saturating_sub(4))
+    }
+
+    /// Name of the key: `/<identity>/KEY/<key-id>`
+    pub fn key_name(&self) -> Name {
+        self.name.prefix(self.name.len().saturating_sub(2))
+    }
+
+    /// SubjectPublicKeyInfo of the key, DER-encoded
+    pub fn public_key(&self) -> &Bytes {
+        &self.public_key
+    }
+
+    /// Name of the key that signed the certificate
+    pub fn signer(&self) -> &Name {
+        &self.signer
+    }
+
+    pub fn validity(&self) -> &ValidityPeriod {
+        &self.validity
+    }
+
+    /// Whether the key signed its own certificate
+    pub fn is_self_signed(&self) -> bool {
+        self.signer == self.key_name()
+    }
+
+    /// Whether `issuer` holds the key this certificate is signed with,
+    /// and the signature checks out against it
+    pub fn is_signed_by(&self, issuer: &NdnCertificate) -> bool {
+        if self.signer != issuer.key_name() {
+            return false;
+        }
+        let Some(point) = issuer.public_key.strip_prefix(P256_SPKI_PREFIX) else {
+            return false;
+        };
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, point)
+            .verify(&self.signed_portion(), &self.signature)
+            .is_ok()
+    }
+
+    /// Encode as an NDN Data packet
+    pub fn encode(&self) -> Bytes {
+        let mut value = self.signed_portion();
+        put_tlv(&mut value, TLV_SIGNATURE_VALUE, &self.signature);
+        let mut buf = BytesMut::new();
+        put_tlv(&mut buf, tlv::TLV_DATA as u16, &value);
+        buf.freeze()
+    }
+
+    /// Decode a certificate encoded by [`NdnCertificate::encode`]
+    pub fn decode(mut bytes: &[u8]) -> Result<Self, KeyChainError> {
+        let (tlv_type, mut value) = read_tlv(&mut bytes)?;
+        if tlv_type != tlv::TLV_DATA as u16 {
+            return Err(malformed("not a Data packet"));
+        }
+
+        let mut name = None;
+        let mut public_key = None;
+        let mut signature_info = None;
+        let mut signature = None;
+        while !value.is_empty() {
+            let (tlv_type, field) = read_tlv(&mut value)?;
+            match tlv_type {
+                t if t == tlv::TLV_NAME as u16 => name = Some(decode_name(field)?),
+                t if t == tlv::TLV_CONTENT as u16 => public_key = Some(Bytes::copy_from_slice(field)),
+                TLV_SIGNATURE_INFO => signature_info = Some(field),
+                TLV_SIGNATURE_VALUE => signature = Some(Bytes::copy_from_slice(field)),
+                _ => {}
+            }
+        }
+        let (Some(name), Some(public_key), Some(mut info), Some(signature)) =
+            (name, public_key, signature_info, signature)
+        else {
+            return Err(malformed("missing Name, Content or signature"));
+        };
+        if name.len() < 4 || name.get(name.len() - 4).map(|c| c.as_bytes().as_ref()) != Some(KEY_COMPONENT.as_bytes()) {
+            return Err(malformed("name is not /<identity>/KEY/<key-id>/<issuer>/<version>"));
+        }
+
+        let mut signer = None;
+        let mut validity = None;
+        while !info.is_empty() {
+            let (tlv_type, field) = read_tlv(&mut info)?;
+            match tlv_type {
+                TLV_SIGNATURE_TYPE if field != [SIGNATURE_SHA256_WITH_ECDSA] => {
+                    return Err(malformed("unsupported SignatureType"));
+                }
+                TLV_KEY_LOCATOR => {
+                    let mut locator = field;
+                    let (_, name) = read_tlv(&mut locator)?;
+                    signer = Some(decode_name(name)?);
+                }
+                TLV_VALIDITY_PERIOD => validity = Some(ValidityPeriod::decode(field)?),
+                _ => {}
+            }
+        }
+        let (Some(signer), Some(validity)) = (signer, validity) else {
+            return Err(malformed("missing KeyLocator or ValidityPeriod"));
+        };
+
+        Ok(Self {
+            name,
+            public_key,
+            signer,
+            validity,
+            signature,
+        })
+    }
+
+    /// Name, MetaInfo, Content and SignatureInfo, which the signature covers
+    fn signed_portion(&self) -> BytesMut {
+        let mut buf = BytesMut::new();
+        encode_name(&self.name, &mut buf);
+
+        let mut meta_info = BytesMut::new();
+        put_tlv(&mut meta_info, TLV_CONTENT_TYPE, &[CONTENT_TYPE_KEY]);
+        put_tlv(&mut meta_info, tlv::TLV_FRESHNESS_PERIOD as u16, &CERTIFICATE_FRESHNESS_MS.to_be_bytes());
+        put_tlv(&mut buf, TLV_META_INFO, &meta_info);
+
+        put_tlv(&mut buf, tlv::TLV_CONTENT as u16, &self.public_key);
+
+        let mut signature_info = BytesMut::new();
+        put_tlv(&mut signature_info, TLV_SIGNATURE_TYPE, &[SIGNATURE_SHA256_WITH_ECDSA]);
+        let mut locator = BytesMut::new();
+        encode_name(&self.signer, &mut locator);
+        put_tlv(&mut signature_info, TLV_KEY_LOCATOR, &locator);
+        self.validity.encode(&mut signature_info);
+        put_tlv(&mut buf, TLV_SIGNATURE_INFO, &signature_info);
+        buf
+    }
+}
+
+/// Keys of identities and their certificates, kept in a directory
+#[derive(Debug, Clone)]
+pub struct KeyChain {
+    dir: PathBuf,
+}
+
+impl KeyChain {
+    /// Open the key chain kept in `dir`, creating the directory if needed
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, KeyChainError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Directory the keys and certificates are kept in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether the key chain holds a key for `identity`
+    pub fn has_identity(&self, identity: &Name) -> bool {
+        self.key_path(identity).exists()
+    }
+
+    /// Create a key for `identity` and a certificate for it, signed by the
+    /// key of `issuer` or by the new key itself. A key `identity` already
+    /// has is replaced.
+    pub fn issue(
+        &self,
+        identity: &Name,
+        issuer: Option<&Name>,
+        validity: ValidityPeriod,
+    ) -> Result<NdnCertificate, KeyChainError> {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng)
+            .map_err(|_| KeyChainError::Crypto)?;
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref())
+            .map_err(|_| KeyChainError::Crypto)?;
+        let public_key = spki(&key);
+
+        let certificate = match issuer {
+            Some(issuer) => self.certify(identity, public_key, issuer, validity)?,
+            None => {
+                let mut name = key_name(identity, &public_key);
+                let signer = name.clone();
+                name.push(NameComponent::new(SELF_ISSUER));
+                name.push(version_component());
+                sign(&key, name, public_key, signer, validity)?
+            }
+        };
+        write_private(&self.key_path(identity), pkcs8.as_ref())?;
+        self.add_certificate(&certificate)?;
+        Ok(certificate)
+    }
+
+    /// Sign the key in `request` with the key of `issuer`, for `validity`.
+    /// The request is the self-signed certificate of the key, which proves
+    /// its holder has the private key.
+    pub fn sign(
+        &self,
+        request: &NdnCertificate,
+        issuer: &Name,
+        validity: ValidityPeriod,
+    ) -> Result<NdnCertificate, KeyChainError> {
+        if !request.is_self_signed() || !request.is_signed_by(request) {
+            return Err(KeyChainError::BadSignature(request.name().clone()));
+        }
+        self.certify(&request.identity(), request.public_key().clone(), issuer, validity)
+    }
+
+    /// Current certificate of the key of `identity`
+    pub fn certificate(&self, identity: &Name) -> Result<NdnCertificate, KeyChainError> {
+        match fs::read(self.certificate_path(identity)) {
+            Ok(bytes) => NdnCertificate::decode(&bytes),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(KeyChainError::UnknownIdentity(identity.clone())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Make `certificate` the current one of the key it certifies, such as
+    /// one an issuer signed for a key of this key chain
+    pub fn add_certificate(&self, certificate: &NdnCertificate) -> Result<(), KeyChainError> {
+        fs::write(self.certificate_path(&certificate.identity()), certificate.encode())?;
+        Ok(())
+    }
+
+    /// NdnCertificate for `public_key` of `identity`, signed by the key of `issuer`
+    fn certify(
+        &self,
+        identity: &Name,
+        public_key: Bytes,
+        issuer: &Name,
+        validity: ValidityPeriod,
+    ) -> Result<NdnCertificate, KeyChainError> {
+        let issuer_certificate = self.certificate(issuer)?;
+        let pkcs8 = match fs::read(self.key_path(issuer)) {
+            Ok(pkcs8) => pkcs8,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(KeyChainError::UnknownIdentity(issuer.clone())),
+            Err(e) => return Err(e.into()),
+        };
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8).map_err(|_| KeyChainError::Crypto)?;
+
+        let mut name = key_name(identity, &public_key);
+        // The issuer component is the last component of the issuer identity
+        let issuer_id = issuer.components().last().cloned();
+        name.push(issuer_id.unwrap_or_else(|| NameComponent::new(SELF_ISSUER)));
+        name.push(version_component());
+        sign(&key, name, public_key, issuer_certificate.key_name(), validity)
+    }
+
+    fn key_path(&self, identity: &Name) -> PathBuf {
+        self.dir.join(format!("{}.key", file_stem(identity)))
+    }
+
+    fn certificate_path(&self, identity: &Name) -> PathBuf {
+        self.dir.join(format!("{}.cert", file_stem(identity)))
+    }
+}
+
+/// Sign a new certificate named `name` with `key`
+fn sign(
+    key: &EcdsaKeyPair,
+    name: Name,
+    public_key: Bytes,
+    signer: Name,
+    validity: ValidityPeriod,
+) -> Result<NdnCertificate, KeyChainError> {
+    let mut certificate = NdnCertificate {
+        name,
+        public_key,
+        signer,
+        validity,
+        signature: Bytes::new(),
+    };
+    let signature = key
+        .sign(&SystemRandom::new(), &certificate.signed_portion())
+        .map_err(|_| KeyChainError::Crypto)?;
+    certificate.signature = Bytes::copy_from_slice(signature.as_ref());
+    Ok(certificate)
+}
+
+/// SubjectPublicKeyInfo of the public half of `key`
+fn spki(key: &EcdsaKeyPair) -> Bytes {
+    let mut spki = BytesMut::from(P256_SPKI_PREFIX);
+    spki.extend_from_slice(key.public_key().as_ref());
+    spki.freeze()
+}
+
+/// `/<identity>/KEY/<key-id>`, the key ID being the first 8 bytes of the
+/// SHA-256 digest of the public key, in hex
+fn key_name(identity: &Name, public_key: &[u8]) -> Name {
+    let digest = digest::digest(&digest::SHA256, public_key);
+    let mut name = identity.clone();
+    name.push(NameComponent::new(KEY_COMPONENT));
+    name.push(NameComponent::new(hex(&digest.as_ref()[..8])));
+    name
+}
+
+/// Version component of a new certificate: `v=` and the Unix time in
+/// milliseconds
+fn version_component() -> NameComponent {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    NameComponent::new(format!("v={}", millis))
+}
+
+/// File name under which the key and certificate of `identity` are kept
+fn file_stem(identity: &Name) -> String {
+    hex(&digest::digest(&digest::SHA256, identity.to_string().as_bytes()).as_ref()[..16])
+}
+
+/// Write a file only the current user can read
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    io::Write::write_all(&mut options.open(path)?, contents)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut s, b| {
+        let _ = write!(s, "{:02x}", b);
+        s
+    })
+}
+
+fn malformed(what: &str) -> KeyChainError {
+    KeyChainError::Malformed(what.to_string())
+}
+
+fn encode_name(name: &Name, buf: &mut BytesMut) {
+    // Names of components only fail to encode past TLV length limits
+    if let Ok(element) = name.to_tlv() {
+        element.encode(buf);
+    }
+}
+
+fn decode_name(value: &[u8]) -> Result<Name, KeyChainError> {
+    Ok(Name::from_tlv(&TlvElement::new(tlv::TLV_NAME, Bytes::copy_from_slice(value)))?)
+}
+
+/// Write a TLV whose type may take the three byte encoding
+fn put_tlv(buf: &mut BytesMut, tlv_type: u16, value: &[u8]) {
+    if tlv_type < 253 {
+        buf.put_u8(tlv_type as u8);
+    } else {
+        buf.put_u8(253);
+        buf.put_u16(tlv_type);
+    }
+    tlv::encode_tlv_length(value.len(), buf);
+    buf.extend_from_slice(value);
+}
+
+/// Read the TLV at the front of `buf`, advancing past it
+fn read_tlv<'a>(buf: &mut &'a [u8]) -> Result<(u16, &'a [u8]), KeyChainError> {
+    let tlv_type = match tlv::decode_tlv_type(buf)? {
+        253 if buf.remaining() >= 2 => buf.get_u16(),
+        253 => return Err(malformed("truncated TLV type")),
+        tlv_type => tlv_type as u16,
+    };
+    let length = tlv::decode_tlv_length(buf)?;
+    if buf.len() < length {
+        return Err(malformed("truncated TLV value"));
+    }
+    let (value, rest) = buf.split_at(length);
+    *buf = rest;
+    Ok((tlv_type, value))
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Format a time as the `YYYYMMDDThhmmss` of ValidityPeriod, in UTC
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = unix_secs(time);
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let seconds = secs % 86_400;
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Parse a `YYYYMMDDThhmmss` time, in UTC
+pub fn parse_timestamp(s: &str) -> Option<SystemTime> {
+    let (date, time) = s.split_once('T')?;
+    if date.len() != 8 || time.len() != 6 || !date.bytes().chain(time.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let field = |s: &str, range: std::ops::Range<usize>| s[range].parse::<u32>().ok();
+    let (year, month, day) = (field(date, 0..4)?, field(date, 4..6)?, field(date, 6..8)?);
+    let (hour, minute, second) = (field(time, 0..2)?, field(time, 2..4)?, field(time, 4..6)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(year as i64, month, day)).ok()?;
+    let secs = days * 86_400 + (hour as u64) * 3600 + (minute as u64) * 60 + second as u64;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Proleptic Gregorian date of a day since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(s: &str) -> Name {
+        Name::from_string(s).unwrap()
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(format_timestamp(time), "20240229T123456");
+        assert_eq!(parse_timestamp("20240229T123456"), Some(time));
+        assert_eq!(parse_timestamp("19700101T000000"), Some(UNIX_EPOCH));
+        assert_eq!(parse_timestamp("20241301T000000"), None);
+        assert_eq!(parse_timestamp("2024-02-29"), None);
+    }
+
+    #[test]
+    fn test_issue_and_sign() {
+        let root_dir = std::env::temp_dir().join(format!("udcn-keychain-root-{}", std::process::id()));
+        let site_dir = std::env::temp_dir().join(format!("udcn-keychain-site-{}", std::process::id()));
+        let root_chain = KeyChain::open(&root_dir).unwrap();
+        let site_chain = KeyChain::open(&site_dir).unwrap();
+
+        let validity = ValidityPeriod::from_now(DEFAULT_VALIDITY);
+        let root = root_chain.issue(&name("/testbed"), None, validity).unwrap();
+        assert!(root.is_self_signed());
+        assert!(root.is_signed_by(&root));
+        assert_eq!(root.identity(), name("/testbed"));
+        assert_eq!(NdnCertificate::decode(&root.encode()).unwrap(), root);
+
+        // The site sends its self-signed certificate to the root to sign
+        let request = site_chain.issue(&name("/testbed/site"), None, validity).unwrap();
+        let short = ValidityPeriod::new(SystemTime::now(), Duration::from_secs(3600));
+        let signed = root_chain.sign(&request, &name("/testbed"), short).unwrap();
+        assert_eq!(signed.key_name(), request.key_name());
+        assert_eq!(signed.signer(), &root.key_name());
+        assert!(signed.is_signed_by(&root));
+        assert!(!signed.is_signed_by(&request));
+        assert!(signed.validity().contains(SystemTime::now()));
+        assert!(!signed.validity().contains(SystemTime::now() + Duration::from_secs(7200)));
+        site_chain.add_certificate(&signed).unwrap();
+        assert_eq!(site_chain.certificate(&name("/testbed/site")).unwrap(), signed);
+
+        // The root issues node keys directly, and refuses tampered requests
+        let node = root_chain.issue(&name("/testbed/node"), Some(&name("/testbed")), validity).unwrap();
+        assert!(node.is_signed_by(&root));
+        let mut forged = NdnCertificate::decode(&request.encode()).unwrap();
+        forged.validity.not_after += Duration::from_secs(1);
+        assert!(matches!(
+            root_chain.sign(&forged, &name("/testbed"), validity),
+            Err(KeyChainError::BadSignature(_))
+        ));
+        assert!(matches!(
+            root_chain.sign(&request, &name("/elsewhere"), validity),
+            Err(KeyChainError::UnknownIdentity(_))
+        ));
+
+        let _ = fs::remove_dir_all(root_dir);
+        let _ = fs::remove_dir_all(site_dir);
+    }
+}
@@ -5,7 +5,6 @@
 //! It enables the transport of NDN packets over QUIC, with support for
 //! fragmentation, reassembly, and congestion control.
 
-use anyhow::{anyhow, Context, Result};
 use bytes::{Bytes, BytesMut};
 use futures::StreamExt;
 use log::{debug, error, info, trace, warn};
@@ -20,31 +19,120 @@ use std::{
     fmt::Debug,
     io::Cursor,
     net::{IpAddr, SocketAddr, ToSocketAddrs},
-    path::Path,
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 use tokio::{
     fs,
     io::AsyncReadExt,
-    sync::{mpsc, Mutex, RwLock},
+    sync::{broadcast, mpsc, Mutex, RwLock},
+    task::JoinHandle,
     time::timeout,
 };
+use tokio_util::sync::CancellationToken;
 
+#[cfg(target_os = "linux")]
+mod capture;
 mod config;
+mod cs;
+mod discovery;
+#[cfg(target_os = "linux")]
+mod ether;
+mod error;
+mod events;
 mod face;
+mod fetcher;
+mod file;
+mod forwarder;
 mod fragmentation;
+mod gateway;
+mod identity;
+mod keychain;
+mod link;
+mod link_face;
+mod ndn_face;
 mod packet;
+mod ping;
+mod producer;
+mod pubsub;
+mod reload;
+mod repo;
+mod repo_protocol;
+mod scheduler;
+mod send_queue;
+mod strategy;
+mod sync;
+mod tasks;
+mod tcp;
+mod traceroute;
 mod transport;
+mod udp;
+mod unix;
+mod version;
+mod ws;
 
+#[cfg(target_os = "linux")]
+pub use capture::{Capture, CapturedPacket, Endpoints};
 pub use config::{ClientOptions, ServerOptions};
-pub use face::{Face, FaceEvent};
-pub use transport::NdnQuicTransport;
+pub use cs::{ContentStore, ContentStoreLimits, DEFAULT_CS_CAPACITY};
+pub use discovery::{
+    Discovery, DiscoveryOptions, Neighbor, DISCOVERY_PREFIX, NDN_DISCOVERY_PORT, NDN_MULTICAST_GROUP,
+};
+#[cfg(target_os = "linux")]
+pub use ether::{open_ethernet_multicast, EthernetLink, NDN_ETHERTYPE, NDN_ETHER_MULTICAST_ADDR};
+pub use error::{FaceError, FetchError, KeyChainError, RepoError, TransportError};
+pub use events::{EventFilter, EventKind, EventSubscription};
+pub use face::{Face, FaceEvent, FaceStats, PacketMode, RetransmissionPolicy};
+pub use fetcher::{AimdWindow, FetchOptions, FetchProgress, FetchStats, FetchedObject, SegmentFetcher};
+pub use file::{segment_content, FileServer};
+pub use forwarder::{FibEntry, Forwarder, NextHop, PendingInterest, RIB_REGISTER_PREFIX, RIB_UNREGISTER_PREFIX};
+pub use fragmentation::{
+    adaptive_fragment_size, Reassembler, ReassemblyOptions, DEFAULT_REASSEMBLY_BUDGET, DEFAULT_REASSEMBLY_MESSAGES,
+    DEFAULT_REASSEMBLY_TIMEOUT, MIN_FRAGMENT_SIZE,
+};
+pub use gateway::{url_to_name, GatewayOptions, HttpGateway, NDN_NAME_HEADER};
+pub use identity::{PeerIdentity, NDN_URI_SCHEME};
+pub use keychain::{
+    format_timestamp, parse_timestamp, KeyChain, NdnCertificate, ValidityPeriod, DEFAULT_VALIDITY, KEY_COMPONENT,
+    SELF_ISSUER,
+};
+pub use link::{Link, StreamLink};
+pub use link_face::LinkFace;
+pub use ndn_face::NdnFace;
+pub use packet::NdnPacket;
+pub use ping::{PingOutcome, PingServer, PingStats, Pinger, PING_COMPONENT};
+pub use producer::{HandlerLimits, HandlerOverflow, HandlerRegistry, InterestHandler, ProducerResponse};
+pub use pubsub::{PubSub, PubSubOptions, DEFAULT_SEGMENT_SIZE};
+pub use repo::StoredData;
+pub use repo_protocol::{
+    RepoClient, RepoCommand, RepoServer, RepoStatus, REPO_DELETE, REPO_INSERT, REPO_INSERT_CHECK, STATUS_FAILED,
+    STATUS_IN_PROGRESS, STATUS_MALFORMED, STATUS_NOT_FOUND, STATUS_OK, STATUS_STARTED,
+};
+pub use scheduler::{FaceScheduler, SchedulerOptions, DEFAULT_OUTPUT_QUEUE_CAPACITY, DEFAULT_QUANTUM_BYTES};
+pub use send_queue::{SendPriority, SendQueueFull, DEFAULT_MAX_INFLIGHT_STREAMS, DEFAULT_SEND_QUEUE_CAPACITY};
+pub use strategy::{
+    strategy_by_name, AsfStrategy, BestRouteStrategy, LoadBalanceStrategy, MulticastStrategy,
+    SelfLearningStrategy, Strategy, DEFAULT_LEARNED_ROUTE_LIFETIME, DEFAULT_MEASUREMENT_LIFETIME, DEFAULT_STRATEGY,
+};
+pub use sync::{StateVector, StateVectorSync, SvsOptions, SyncUpdate};
+pub use tcp::{connect_tcp, TcpFaceListener, NDN_TCP_PORT};
+pub use traceroute::{TraceHop, TraceOutcome, Tracer, DEFAULT_MAX_HOPS};
+pub use transport::{NdnQuicTransport, TransportConfig, TransportEvent, TransportMode, PEER_ROUTE_GRACE_PERIOD};
+pub use udp::{connect_udp, UdpFaceListener, NDN_UDP_PORT};
+pub use unix::{connect_unix, UnixFaceListener, DEFAULT_UNIX_SOCKET_PATH};
+pub use version::{default_alpn_protocols, ProtocolVersion, UDCN2_ALPN};
+pub use ws::{WebSocketFaceListener, WebSocketLink, NDN_WS_PORT};
+
+use tasks::TaskSet;
 
 /// Default QUIC port for NDN
 pub const NDN_QUIC_PORT: u16 = 6367;
 
-/// Default ALPN protocol string for NDN over QUIC
+/// ALPN protocol string for NDN over QUIC (the original wire format)
 pub const NDN_QUIC_ALPN: &[u8] = b"ndn1";
 
 /// Maximum datagram size for NDN over QUIC
@@ -56,94 +144,227 @@ pub const MAX_PACKET_SIZE: usize = 8800; // RFC 8609 specifies 8800 bytes max
 /// Timeout for Interest packets in milliseconds
 pub const DEFAULT_INTEREST_TIMEOUT_MS: u64 = 4000;
 
-/// Fragment size for large packets
+/// Fragment size for large packets on a connection whose path MTU quinn
+/// does not tell
 pub const DEFAULT_FRAGMENT_SIZE: usize = 1000;
 
+/// Application close code for a normal close
+pub const CLOSE_CODE_NORMAL: u32 = 0;
+
+/// Application close code telling the peer that we are shutting down and
+/// it should reconnect elsewhere or later (GOAWAY)
+pub const CLOSE_CODE_GOAWAY: u32 = 1;
+
+/// Application close code for connections refused at the connection limit
+pub const CLOSE_CODE_REFUSED: u32 = 2;
+
 /// Server configuration for NDN over QUIC
 #[derive(Debug, Clone)]
 pub struct NdnQuicServer {
-    /// The QUIC endpoint
-    endpoint: Endpoint,
+    /// QUIC endpoints, one per listen address
+    endpoints: Vec<Endpoint>,
+    
+    /// Server configuration (replaced when certificates are reloaded)
+    server_config: Arc<RwLock<ServerConfig>>,
+    
+    /// Options the server was created with
+    options: ServerOptions,
     
-    /// Server configuration
-    server_config: ServerConfig,
+    /// Background tasks (accept loops, certificate watcher, idle face sweep)
+    tasks: TaskSet,
     
-    /// Address the server is listening on
-    address: SocketAddr,
+    /// Addresses the server is listening on
+    addresses: Vec<SocketAddr>,
     
     /// Connected faces
     faces: Arc<RwLock<HashMap<String, Arc<Face>>>>,
     
     /// Server metrics
     metrics: Arc<UdcnMetrics>,
+    
+    /// Reassembles the fragments received on all faces
+    reassembler: Arc<Reassembler>,
+    
+    /// Notifies subscribers of newly accepted faces
+    face_sender: broadcast::Sender<Arc<Face>>,
+
+    /// Connections accepted on all endpoints, numbering them to tell the
+    /// faces of a peer apart
+    connection_count: Arc<AtomicU64>,
 }
 
 impl NdnQuicServer {
     /// Create a new QUIC server with the given options
-    pub async fn new(options: ServerOptions) -> Result<Self> {
+    pub async fn new(options: ServerOptions) -> Result<Self, TransportError> {
         let server_config = config::configure_server(&options).await?;
         
-        // Create a QUIC endpoint
-        let endpoint = Endpoint::server(
-            server_config.clone(),
-            options.listen_addr.parse().context("Invalid listen address")?,
-        )?;
-        
-        let address = endpoint.local_addr()?;
+        // Create a QUIC endpoint for every listen address
+        let mut endpoints: Vec<Endpoint> = Vec::new();
+        let mut addresses = Vec::new();
+        for addr in options.listen_addresses()? {
+            let endpoint = match Endpoint::server(server_config.clone(), addr) {
+                Ok(endpoint) => endpoint,
+                // A dual-stack IPv6 wildcard socket already covers this IPv4 port
+                Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && covered_by_dual_stack(addr, &addresses) => {
+                    info!("Address {} is served by the dual-stack IPv6 socket", addr);
+                    continue;
+                }
+                Err(source) => return Err(TransportError::Bind { addr: addr.to_string(), source }),
+            };
+            let address = endpoint.local_addr()?;
+            info!("NDN QUIC server listening on {}", address);
+            endpoints.push(endpoint);
+            addresses.push(address);
+        }
         
-        info!("NDN QUIC server listening on {}", address);
+        let (face_sender, _) = broadcast::channel(64);
+        let metrics = Arc::new(UdcnMetrics::new());
+        let reassembler = Arc::new(Reassembler::new(options.reassembly.clone(), Arc::clone(&metrics)));
         
         Ok(Self {
-            endpoint,
-            server_config,
-            address,
+            endpoints,
+            server_config: Arc::new(RwLock::new(server_config)),
+            options,
+            tasks: TaskSet::default(),
+            addresses,
             faces: Arc::new(RwLock::new(HashMap::new())),
-            metrics: Arc::new(UdcnMetrics::new()),
+            metrics,
+            reassembler,
+            face_sender,
+            connection_count: Arc::new(AtomicU64::new(0)),
         })
     }
 
+    /// Stop the background tasks of the server, closing its endpoints,
+    /// once `token` is cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.tasks = TaskSet::new(token);
+        self
+    }
+
     /// Start accepting incoming connections
-    pub async fn run(&self) -> Result<()> {
-        let endpoint = self.endpoint.clone();
+    pub async fn run(&self) -> Result<(), TransportError> {
+        if let Some(interval_ms) = self.options.cert_reload_interval_ms {
+            reload::spawn_certificate_watcher(self.clone(), Duration::from_millis(interval_ms));
+        }
+        if let Some(idle_ms) = self.options.face_idle_timeout_ms {
+            self.spawn_idle_sweep(Duration::from_millis(idle_ms));
+        }
+        
         let faces = Arc::clone(&self.faces);
         let metrics = Arc::clone(&self.metrics);
+        let face_sender = self.face_sender.clone();
+        let max_connections = self.options.max_connections.map(|max| max as usize);
+        let send_limits = (self.options.max_inflight_streams, self.options.send_queue_capacity);
         
-        tokio::spawn(async move {
-            info!("QUIC server accepting connections");
-            
-            while let Some(conn) = endpoint.accept().await {
-                debug!("Incoming connection from {:?}", conn.remote_address());
+        // All endpoints share one face table and metrics
+        for endpoint in &self.endpoints {
+            let endpoint = endpoint.clone();
+            let local_addr = endpoint.local_addr().ok();
+            let faces = Arc::clone(&faces);
+            let metrics = Arc::clone(&metrics);
+            let reassembler = Arc::clone(&self.reassembler);
+            let face_sender = face_sender.clone();
+            let connection_count = Arc::clone(&self.connection_count);
+            let token = self.tasks.token().clone();
                 
-                let metrics_clone = Arc::clone(&metrics);
-                let faces_clone = Arc::clone(&faces);
+            self.tasks.spawn(async move {
+                info!("QUIC server accepting connections on {:?}", local_addr);
                 
-                tokio::spawn(async move {
-                    match conn.await {
-                        Ok(connection) => {
-                            let remote_addr = connection.remote_address();
-                            
-                            info!("Connection established from {}", remote_addr);
-                            
-                            // Create a face for this connection
-                            let face_id = format!("quic:{}", remote_addr);
-                            let face = Face::new_from_connection(face_id.clone(), connection, metrics_clone);
-                            
-                            // Add the face to our map
-                            faces_clone.write().await.insert(face_id.clone(), Arc::new(face));
-                            
-                            // Process the connection
-                            // In a real implementation, we'd handle the connection lifecycle here
+                loop {
+                    let conn = tokio::select! {
+                        _ = token.cancelled() => {
+                            // Closes the connections of the endpoint too
+                            endpoint.close(CLOSE_CODE_GOAWAY.into(), b"Server shutting down");
+                            break;
                         }
-                        Err(e) => {
-                            warn!("Connection failed: {}", e);
+                        conn = endpoint.accept() => match conn {
+                            Some(conn) => conn,
+                            None => break,
+                        },
+                    };
+                    debug!("Incoming connection from {:?}", conn.remote_address());
+                    let connection_number = connection_count.fetch_add(1, Ordering::Relaxed) + 1;
+                    
+                    let metrics_clone = Arc::clone(&metrics);
+                    let reassembler_clone = Arc::clone(&reassembler);
+                    let faces_clone = Arc::clone(&faces);
+                    let face_sender_clone = face_sender.clone();
+                    
+                    tokio::spawn(async move {
+                        match conn.await {
+                            Ok(connection) => {
+                                let remote_addr = connection.remote_address();
+                                
+                                info!("Connection established from {}", remote_addr);
+                                
+                                // Create a face for this connection; authenticated peers are
+                                // known by their certificate, wherever they connect from, with
+                                // one face per connection
+                                let face_id = match PeerIdentity::of_connection(&connection) {
+                                    Some(identity) => identity.connection_face_id(connection_number),
+                                    None => format!("quic:{}", remote_addr),
+                                };
+                                
+                                // Add the face to our map, unless we are at the connection limit
+                                let face = {
+                                    let mut faces = faces_clone.write().await;
+                                    if max_connections.is_some_and(|max| faces.len() >= max && !faces.contains_key(&face_id)) {
+                                        warn!("Connection limit reached, refusing {}", remote_addr);
+                                        metrics_clone.connections_refused.increment();
+                                        connection.close(CLOSE_CODE_REFUSED.into(), b"Too many connections");
+                                        return;
+                                    }
+                                    
+                                    let face = Arc::new(Face::new_with_reassembler(
+                                        face_id.clone(),
+                                        connection,
+                                        Arc::clone(&metrics_clone),
+                                        reassembler_clone,
+                                    ));
+                                    face.set_send_queue_limits(send_limits.0, send_limits.1);
+                                    if let Some(previous) = faces.insert(face_id.clone(), Arc::clone(&face)) {
+                                        // The peer reconnected from the same address
+                                        previous.close().await;
+                                    } else {
+                                        metrics_clone.faces_active.increment();
+                                    }
+                                    face
+                                };
+                                
+                                if let Some(chain) = face.peer_certificates() {
+                                    info!(
+                                        "Face {} from {} authenticated with a {} certificate chain",
+                                        face_id,
+                                        remote_addr,
+                                        chain.len()
+                                    );
+                                }
+                                
+                                // Notify subscribers (no receivers is not an error)
+                                let _ = face_sender_clone.send(Arc::clone(&face));
+                                
+                                // Forget the face once its connection is gone
+                                face.wait_closed().await;
+                                let mut faces = faces_clone.write().await;
+                                if faces.get(&face_id).is_some_and(|current| Arc::ptr_eq(current, &face)) {
+                                    faces.remove(&face_id);
+                                    metrics_clone.faces_active.decrement();
+                                    metrics_clone.remove_face(&face_id);
+                                    info!("Face {} removed", face_id);
+                                }
+                            }
+                            Err(e) => {
+                                warn!("Connection failed: {}", e);
+                            }
                         }
-                    }
-                });
-            }
+                    });
+                }
+                
+                info!("QUIC server stopped accepting connections on {:?}", local_addr);
+            });
+        }
             
-            info!("QUIC server stopped accepting connections");
-        });
-        
         Ok(())
     }
 
@@ -153,31 +374,131 @@ impl NdnQuicServer {
         faces.values().cloned().collect()
     }
 
+    /// Get the face currently connected under `face_id`
+    pub async fn face(&self, face_id: &str) -> Option<Arc<Face>> {
+        self.faces.read().await.get(face_id).cloned()
+    }
+
+    /// Re-read the certificate and key files and use them for new connections.
+    ///
+    /// Existing connections are not affected.
+    pub async fn reload_certificates(&self) -> Result<(), TransportError> {
+        let server_config = config::configure_server(&self.options).await?;
+        
+        for endpoint in &self.endpoints {
+            endpoint.set_server_config(Some(server_config.clone()));
+        }
+        *self.server_config.write().await = server_config;
+        
+        info!("Reloaded server certificate from {}", self.options.cert_path.display());
+        Ok(())
+    }
+
+    /// Periodically close faces that have been idle for longer than `idle_timeout`.
+    ///
+    /// Faces with outstanding expressed Interests are left alone.
+    fn spawn_idle_sweep(&self, idle_timeout: Duration) {
+        let faces = Arc::clone(&self.faces);
+        let metrics = Arc::clone(&self.metrics);
+        let period = (idle_timeout / 4).clamp(Duration::from_millis(100), Duration::from_secs(5));
+        let token = self.tasks.token().clone();
+        
+        self.tasks.spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+                
+                let candidates: Vec<Arc<Face>> = faces
+                    .read()
+                    .await
+                    .values()
+                    .filter(|face| face.idle_time() > idle_timeout)
+                    .cloned()
+                    .collect();
+                
+                for face in candidates {
+                    if face.pending_interest_count().await > 0 {
+                        continue;
+                    }
+                    info!("Face {} idle for {:?}, closing", face.id(), face.idle_time());
+                    metrics.faces_evicted.increment();
+                    // The accept task removes the face once the connection is closed
+                    face.close().await;
+                }
+            }
+        });
+    }
+
+    /// Stop accepting new connections while keeping existing ones open
+    pub fn stop_accepting(&self) {
+        info!("Server no longer accepting connections on {:?}", self.addresses);
+        for endpoint in &self.endpoints {
+            endpoint.set_server_config(None);
+        }
+    }
+
+    /// Paths of the certificate and key files used by this server
+    pub fn certificate_paths(&self) -> Vec<PathBuf> {
+        vec![self.options.cert_path.clone(), self.options.key_path.clone()]
+    }
+
+    /// Subscribe to faces accepted by this server
+    pub fn subscribe_faces(&self) -> broadcast::Receiver<Arc<Face>> {
+        self.face_sender.subscribe()
+    }
+
     /// Get the metrics for this server
     pub fn metrics(&self) -> Arc<UdcnMetrics> {
         Arc::clone(&self.metrics)
     }
 
-    /// Get the server address
+    /// Get the server address (the first listen address)
     pub fn address(&self) -> SocketAddr {
-        self.address
+        self.addresses[0]
+    }
+
+    /// Get every address the server is listening on
+    pub fn addresses(&self) -> &[SocketAddr] {
+        &self.addresses
+    }
+    
+    /// Cancel the background tasks of the server and hand back their
+    /// handles, to await them
+    pub fn stop_tasks(&self) -> Vec<JoinHandle<()>> {
+        self.tasks.cancel()
     }
     
     /// Stop the server
-    pub async fn stop(&self) -> Result<()> {
+    pub async fn stop(&self) -> Result<(), TransportError> {
         // Close all faces
         let faces = self.faces.read().await;
         for (_, face) in faces.iter() {
             face.close().await;
         }
         
-        // Close the endpoint
-        self.endpoint.close(0u32.into(), b"Server shutting down");
+        // Close the endpoints
+        for endpoint in &self.endpoints {
+            endpoint.close(CLOSE_CODE_NORMAL.into(), b"Server shutting down");
+        }
+        self.tasks.cancel();
         
         Ok(())
     }
 }
 
+/// Whether an IPv4 wildcard address is reachable through an already bound
+/// IPv6 wildcard socket on the same port
+fn covered_by_dual_stack(addr: SocketAddr, bound: &[SocketAddr]) -> bool {
+    addr.is_ipv4()
+        && addr.ip().is_unspecified()
+        && bound
+            .iter()
+            .any(|b| b.is_ipv6() && b.ip().is_unspecified() && b.port() == addr.port())
+}
+
 /// Client configuration for NDN over QUIC
 #[derive(Debug, Clone)]
 pub struct NdnQuicClient {
@@ -187,6 +508,9 @@ pub struct NdnQuicClient {
     /// Client configuration
     client_config: ClientConfig,
     
+    /// Options the client was created with
+    options: ClientOptions,
+    
     /// Connected faces
     faces: Arc<RwLock<HashMap<String, Arc<Face>>>>,
     
@@ -196,43 +520,47 @@ pub struct NdnQuicClient {
 
 impl NdnQuicClient {
     /// Create a new QUIC client with the given options
-    pub async fn new(options: ClientOptions) -> Result<Self> {
+    pub async fn new(options: ClientOptions) -> Result<Self, TransportError> {
         // Configure the client
         let client_config = config::configure_client(&options).await?;
         
         // Create a QUIC endpoint bound to ANY address
-        let mut endpoint = Endpoint::client("0.0.0.0:0".parse()?)?;
+        let local_addr = SocketAddr::from(([0, 0, 0, 0], 0));
+        let mut endpoint = Endpoint::client(local_addr).map_err(|source| TransportError::Bind {
+            addr: local_addr.to_string(),
+            source,
+        })?;
         endpoint.set_default_client_config(client_config.clone());
         
         Ok(Self {
             endpoint,
             client_config,
+            options,
             faces: Arc::new(RwLock::new(HashMap::new())),
             metrics: Arc::new(UdcnMetrics::new()),
         })
     }
 
     /// Connect to a remote NDN forwarder
-    pub async fn connect<T: ToSocketAddrs + Debug>(&self, addr: T) -> Result<Arc<Face>> {
+    pub async fn connect<T: ToSocketAddrs + Debug>(&self, addr: T) -> Result<Arc<Face>, TransportError> {
         // Resolve the address
         let addr = addr
-            .to_socket_addrs()?
-            .next()
-            .ok_or_else(|| anyhow!("Failed to resolve address"))?;
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| TransportError::Address(format!("{:?}", addr)))?;
         
         debug!("Connecting to {}", addr);
         
         // Connect to the remote endpoint
-        let connection = self.endpoint
-            .connect(addr, "localhost")?
-            .await
-            .map_err(|e| anyhow!("Failed to connect: {}", e))?;
+        let connection = self.endpoint.connect(addr, "localhost")?.await?;
         
         info!("Connected to {}", addr);
         
         // Create a face for this connection
         let face_id = format!("quic:{}", addr);
         let face = Face::new_from_connection(face_id.clone(), connection, Arc::clone(&self.metrics));
+        face.set_send_queue_limits(self.options.max_inflight_streams, self.options.send_queue_capacity);
         
         let face_arc = Arc::new(face);
         
@@ -248,7 +576,7 @@ impl NdnQuicClient {
         face: &Face,
         interest: Interest,
         timeout_ms: Option<u64>,
-    ) -> Result<Data> {
+    ) -> Result<Data, FaceError> {
         face.express_interest(interest, timeout_ms.unwrap_or(DEFAULT_INTEREST_TIMEOUT_MS)).await
     }
 
@@ -264,7 +592,7 @@ impl NdnQuicClient {
     }
     
     /// Disconnect from all remote endpoints and close the client
-    pub async fn close(&self) -> Result<()> {
+    pub async fn close(&self) -> Result<(), TransportError> {
         // Close all faces
         let faces = self.faces.read().await;
         for (_, face) in faces.iter() {
@@ -277,3 +605,66 @@ impl NdnQuicClient {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, SanType};
+
+    #[tokio::test]
+    async fn test_peer_faces_unique_across_endpoints() {
+        let dir = std::env::temp_dir().join(format!("udcn-quic-endpoints-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |file: &str, pem: String| {
+            let path = dir.join(file);
+            std::fs::write(&path, pem).unwrap();
+            path
+        };
+
+        let mut params = CertificateParams::new(Vec::new());
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = rcgen::Certificate::from_params(params).unwrap();
+        let server = rcgen::Certificate::from_params(CertificateParams::new(vec!["localhost".to_string()])).unwrap();
+        let mut params = CertificateParams::new(Vec::new());
+        params.subject_alt_names = vec![SanType::URI("ndn:/alice".to_string())];
+        let client = rcgen::Certificate::from_params(params).unwrap();
+
+        let ca_path = write("ca.pem", ca.serialize_pem().unwrap());
+        let server_options = ServerOptions {
+            listen_addr: "127.0.0.1:0".to_string(),
+            additional_listen_addrs: vec!["127.0.0.2:0".to_string()],
+            cert_path: write("server.pem", server.serialize_pem_with_signer(&ca).unwrap()),
+            key_path: write("server.key", server.serialize_private_key_pem()),
+            client_ca_path: Some(ca_path.clone()),
+            ..Default::default()
+        };
+        let server = NdnQuicServer::new(server_options).await.unwrap();
+        server.run().await.unwrap();
+        assert_eq!(server.addresses().len(), 2);
+
+        let client = NdnQuicClient::new(ClientOptions {
+            ca_cert_path: Some(ca_path),
+            client_cert_path: Some(write("client.pem", client.serialize_pem_with_signer(&ca).unwrap())),
+            client_key_path: Some(write("client.key", client.serialize_private_key_pem())),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let mut accepted = server.subscribe_faces();
+        for addr in server.addresses() {
+            client.connect(*addr).await.unwrap();
+        }
+
+        // The same peer on both endpoints gets two faces, neither closing
+        // the other
+        let first = accepted.recv().await.unwrap();
+        let second = accepted.recv().await.unwrap();
+        assert_ne!(first.id(), second.id());
+        assert!(first.id().starts_with("quic:/alice#") && second.id().starts_with("quic:/alice#"));
+        assert!(!first.is_closed().await && !second.is_closed().await);
+        assert_eq!(server.get_faces().await.len(), 2);
+
+        server.stop().await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,93 @@
+//! Lower-layer links carrying whole NDN packets.
+//!
+//! A [`Link`] hides how packets reach the peer: a byte stream delimits them
+//! by their TLV length, as NFD does on TCP and Unix sockets, while a datagram
+//! socket carries one packet per datagram. [`LinkFace`](crate::LinkFace)
+//! builds the NDN face logic on top of any link.
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use log::trace;
+use std::io;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf},
+    sync::Mutex,
+};
+
+use crate::{error::FaceError, packet::split_stream_packet};
+
+/// Initial capacity of a stream link's receive buffer
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+/// A transport that sends and receives encoded NDN packets
+#[async_trait]
+pub trait Link: Send + Sync + 'static {
+    /// Send one encoded packet
+    async fn send(&self, packet: Bytes) -> Result<(), FaceError>;
+
+    /// Receive the next encoded packet, or None once the peer has gone
+    async fn recv(&self) -> Result<Option<Bytes>, FaceError>;
+
+    /// Stop sending and release the link
+    async fn close(&self);
+}
+
+/// Link over a reliable byte stream such as a TCP or Unix socket
+pub struct StreamLink<S> {
+    /// Read half of the stream and the bytes received but not yet consumed
+    reader: Mutex<(ReadHalf<S>, BytesMut)>,
+    
+    /// Write half of the stream
+    writer: Mutex<WriteHalf<S>>,
+}
+
+impl<S> StreamLink<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Wrap a connected stream
+    pub fn new(stream: S) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self {
+            reader: Mutex::new((reader, BytesMut::with_capacity(READ_BUFFER_SIZE))),
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> Link for StreamLink<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    async fn send(&self, packet: Bytes) -> Result<(), FaceError> {
+        let mut writer = self.writer.lock().await;
+        writer.write_all(&packet).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    async fn recv(&self) -> Result<Option<Bytes>, FaceError> {
+        let mut reader = self.reader.lock().await;
+        let (stream, buf) = &mut *reader;
+
+        loop {
+            if let Some(packet) = split_stream_packet(buf)? {
+                return Ok(Some(packet));
+            }
+            if stream.read_buf(buf).await? == 0 {
+                if !buf.is_empty() {
+                    let message = format!("Stream ended inside a packet ({} bytes left)", buf.len());
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, message).into());
+                }
+                return Ok(None);
+            }
+        }
+    }
+
+    async fn close(&self) {
+        if let Err(e) = self.writer.lock().await.shutdown().await {
+            trace!("Error shutting down stream: {}", e);
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! NDN faces over non-QUIC links.
+//!
+//! A [`LinkFace`] offers the same operations as a QUIC [`Face`](crate::Face)
+//! on top of any [`Link`], so the forwarder can talk to NFD and other routers
+//! over TCP, UDP and Unix sockets. Interests are not retransmitted: on
+//! datagram links that is left to the consumer, as in NFD.
+
+use log::{debug, warn};
+use rust_udcn_common::{
+    metrics::UdcnMetrics,
+    ndn::{Data, Interest, InterestResult, Nack, NackReason},
+};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{mpsc, Mutex, Notify},
+    time::timeout,
+};
+use tracing::instrument;
+
+use crate::{
+    error::FaceError,
+    face::{ActivityClock, FaceEvent, FaceMetrics, FaceStats, Inbound, PendingInterests, PENDING_SWEEP_INTERVAL},
+    fragmentation::{Reassembler, ReassemblyOptions},
+    link::Link,
+    packet::NdnPacket,
+};
+
+/// An NDN face over a [`Link`]
+pub struct LinkFace {
+    /// Unique identifier for this face
+    id: String,
+    
+    /// Link carrying the packets
+    link: Arc<dyn Link>,
+    
+    /// Whether the face is closed
+    closed: Arc<Mutex<bool>>,
+    
+    /// Wakes the receive task when the face is closed locally
+    shutdown: Arc<Notify>,
+    
+    /// Pending Interests waiting for Data
+    pending_interests: Arc<Mutex<PendingInterests>>,
+    
+    /// Receiver for face events
+    event_receiver: Mutex<mpsc::Receiver<FaceEvent>>,
+    
+    /// Sender for face events
+    event_sender: Arc<Mutex<mpsc::Sender<FaceEvent>>>,
+    
+    /// Metrics for this face
+    metrics: FaceMetrics,
+    
+    /// Time of the last packet sent or received
+    activity: Arc<ActivityClock>,
+    
+    /// Whether the peer is on this host
+    local: bool,
+}
+
+impl fmt::Debug for LinkFace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LinkFace").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+impl LinkFace {
+    /// Create a face over `link` and start receiving packets from it
+    pub fn new(id: String, link: Arc<dyn Link>, metrics: Arc<UdcnMetrics>) -> Self {
+        let (event_sender, event_receiver) = mpsc::channel(100);
+        let metrics = FaceMetrics::new(metrics, &id);
+
+        let face = Self {
+            id,
+            link,
+            closed: Arc::new(Mutex::new(false)),
+            shutdown: Arc::new(Notify::new()),
+            pending_interests: Arc::new(Mutex::new(PendingInterests::default())),
+            event_receiver: Mutex::new(event_receiver),
+            event_sender: Arc::new(Mutex::new(event_sender)),
+            metrics,
+            activity: Arc::new(ActivityClock::new()),
+            local: false,
+        };
+
+        face.receive_packets();
+        face.expire_pending_interests();
+
+        face
+    }
+
+    /// Mark the peer as being on this host, so it may manage the forwarder
+    pub fn with_local(mut self, local: bool) -> Self {
+        self.local = local;
+        self
+    }
+
+    /// Get the face ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether the peer is on this host, as a local application or over
+    /// loopback
+    pub fn is_local(&self) -> bool {
+        self.local
+    }
+
+    /// How long the face has gone without sending or receiving a packet
+    pub fn idle_time(&self) -> Duration {
+        self.activity.last().elapsed()
+    }
+
+    /// Snapshot of this face's traffic statistics
+    pub fn stats(&self) -> FaceStats {
+        self.metrics.stats(&self.id, self.idle_time())
+    }
+
+    /// Send an Interest and wait up to `timeout_ms` for Data
+    #[instrument(skip_all, fields(name = %interest.name(), face = %self.id))]
+    pub async fn express_interest(&self, interest: Interest, timeout_ms: u64) -> Result<Data, FaceError> {
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(timeout_ms);
+
+        let (waiter_id, receiver, aggregated) = self.pending_interests.lock().await.add(&interest, deadline);
+        if aggregated {
+            self.metrics.add(|m| &m.interests_aggregated, 1);
+        } else if let Err(e) = self.send_interest(interest.clone()).await {
+            self.pending_interests.lock().await.remove_waiter(&interest, waiter_id);
+            return Err(e);
+        }
+
+        let result = timeout(Duration::from_millis(timeout_ms), receiver).await;
+        match result {
+            Ok(Ok(InterestResult::Data(data))) => {
+                let rtt = start.elapsed().as_micros() as u64;
+                self.metrics.observe(|m| &m.interest_processing_time, rtt);
+                self.metrics.add(|m| &m.interests_satisfied, 1);
+                Ok(data)
+            }
+            Ok(Ok(InterestResult::Nacked(reason))) => Err(FaceError::nacked(reason)),
+            Ok(Ok(InterestResult::Dropped(err))) => Err(FaceError::Dropped(err)),
+            Ok(Ok(InterestResult::Timeout)) | Err(_) => {
+                self.pending_interests.lock().await.remove_waiter(&interest, waiter_id);
+                self.metrics.add(|m| &m.interests_timed_out, 1);
+                Err(FaceError::Timeout)
+            }
+            Ok(Err(_)) => Err(FaceError::Closed),
+        }
+    }
+
+    /// Send an Interest without waiting for Data
+    pub async fn send_interest(&self, interest: Interest) -> Result<(), FaceError> {
+        debug!("[Face {}] Send Interest: {}", self.id, interest.name());
+        self.send_packet(NdnPacket::Interest(interest)).await?;
+        self.metrics.add(|m| &m.interests_sent, 1);
+        Ok(())
+    }
+
+    /// Send a Data packet
+    pub async fn send_data(&self, data: Data) -> Result<(), FaceError> {
+        debug!("[Face {}] Send Data: {}", self.id, data.name());
+        self.send_packet(NdnPacket::Data(data)).await?;
+        self.metrics.add(|m| &m.data_sent, 1);
+        Ok(())
+    }
+
+    /// Send a Nack for an Interest
+    pub async fn send_nack(&self, interest: Interest, reason: NackReason) -> Result<(), FaceError> {
+        self.send_nack_packet(Nack::new(interest, reason)).await
+    }
+
+    /// Send a Nack, as received from upstream or built by the caller
+    pub async fn send_nack_packet(&self, nack: Nack) -> Result<(), FaceError> {
+        debug!("[Face {}] Send Nack ({}): {}", self.id, nack.reason, nack.name());
+        self.send_packet(NdnPacket::Nack(nack)).await?;
+        self.metrics.add(|m| &m.nacks_sent, 1);
+        Ok(())
+    }
+
+    /// Get the next event from this face
+    pub async fn next_event(&self) -> Option<FaceEvent> {
+        self.event_receiver.lock().await.recv().await
+    }
+
+    /// Close the face
+    pub async fn close(&self) {
+        if mark_closed(&self.closed).await {
+            debug!("[Face {}] Closing", self.id);
+            self.link.close().await;
+            self.shutdown.notify_one();
+            self.pending_interests.lock().await.drain("Face closed");
+            let _ = self.event_sender.lock().await.send(FaceEvent::Closed).await;
+        }
+    }
+
+    /// Check if the face is closed
+    pub async fn is_closed(&self) -> bool {
+        *self.closed.lock().await
+    }
+
+    /// Number of outstanding expressed Interests
+    pub async fn pending_interest_count(&self) -> usize {
+        self.pending_interests.lock().await.len()
+    }
+
+    /// Encode a packet and hand it to the link
+    async fn send_packet(&self, packet: NdnPacket) -> Result<(), FaceError> {
+        if *self.closed.lock().await {
+            return Err(FaceError::Closed);
+        }
+
+        let bytes = packet.to_bytes()?;
+        let len = bytes.len();
+        self.link.send(bytes).await?;
+
+        self.metrics.add_packet(|m| &m.bytes_sent, &packet, len as u64);
+        self.activity.touch();
+        Ok(())
+    }
+
+    /// Receive packets from the link until it ends or the face is closed
+    fn receive_packets(&self) {
+        let link = Arc::clone(&self.link);
+        let closed = Arc::clone(&self.closed);
+        let shutdown = Arc::clone(&self.shutdown);
+        let inbound = Inbound {
+            face_id: self.id.clone(),
+            pending_interests: Arc::clone(&self.pending_interests),
+            event_sender: Arc::clone(&self.event_sender),
+            metrics: self.metrics.clone(),
+            reassembler: Arc::new(Reassembler::new(
+                ReassemblyOptions::default(),
+                Arc::clone(&self.metrics.shared),
+            )),
+            activity: Arc::clone(&self.activity),
+        };
+
+        tokio::spawn(async move {
+            let id = inbound.face_id.clone();
+
+            loop {
+                let bytes = tokio::select! {
+                    result = link.recv() => match result {
+                        Ok(Some(bytes)) => bytes,
+                        Ok(None) => break,
+                        Err(e) => {
+                            warn!("[Face {}] Receive error: {}", id, e);
+                            break;
+                        }
+                    },
+                    _ = shutdown.notified() => break,
+                };
+
+                inbound.metrics.add(|m| &m.bytes_received, bytes.len() as u64);
+                let packet = match NdnPacket::from_bytes(&bytes) {
+                    Ok(packet) => packet,
+                    Err(e) => {
+                        warn!("[Face {}] Dropping invalid packet: {}", id, e);
+                        continue;
+                    }
+                };
+                if let Err(e) = inbound.dispatch(packet).await {
+                    warn!("[Face {}] Error processing packet: {}", id, e);
+                }
+            }
+
+            debug!("[Face {}] Link ended", id);
+            if mark_closed(&closed).await {
+                link.close().await;
+                inbound.pending_interests.lock().await.drain("Connection closed");
+                let _ = inbound.event_sender.lock().await.send(FaceEvent::Closed).await;
+            }
+        });
+    }
+
+    /// Periodically expire pending Interests whose caller stopped waiting
+    fn expire_pending_interests(&self) {
+        let closed = Arc::clone(&self.closed);
+        let pending_interests = Arc::clone(&self.pending_interests);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PENDING_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                if *closed.lock().await {
+                    break;
+                }
+                pending_interests.lock().await.expire(Instant::now());
+            }
+        });
+    }
+}
+
+/// Set the closed flag. Returns false if it was already set.
+async fn mark_closed(closed: &Mutex<bool>) -> bool {
+    let mut closed = closed.lock().await;
+    !std::mem::replace(&mut *closed, true)
+}
+
+/// Two faces named `id` and `peer_id`, connected to each other over an
+/// in-memory stream
+#[cfg(test)]
+pub(crate) fn link_face_pair(id: &str, peer_id: &str, metrics: &Arc<UdcnMetrics>) -> (LinkFace, LinkFace) {
+    use crate::link::StreamLink;
+
+    let (a, b) = tokio::io::duplex(64 * 1024);
+    let face = LinkFace::new(id.to_string(), Arc::new(StreamLink::new(a)), Arc::clone(metrics));
+    let peer = LinkFace::new(peer_id.to_string(), Arc::new(StreamLink::new(b)), Arc::clone(metrics));
+    (face, peer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_udcn_common::ndn::Name;
+
+    #[tokio::test]
+    async fn test_interest_data_exchange() {
+        let (consumer, producer) = link_face_pair("a", "b", &Arc::new(UdcnMetrics::new()));
+        let producer = Arc::new(producer);
+
+        let responder = Arc::clone(&producer);
+        tokio::spawn(async move {
+            if let Some(FaceEvent::InterestReceived(interest)) = responder.next_event().await {
+                let data = Data::new(interest.name().clone(), b"hello".to_vec());
+                responder.send_data(data).await.unwrap();
+            }
+        });
+
+        let name = Name::from_string("/test/stream").unwrap();
+        let data = consumer.express_interest(Interest::new(name), 1000).await.unwrap();
+        assert_eq!(data.content().as_ref(), b"hello");
+        assert_eq!(consumer.stats().data_received, 1);
+
+        producer.close().await;
+        assert!(matches!(consumer.next_event().await, Some(FaceEvent::DataReceived(_))));
+        assert!(matches!(consumer.next_event().await, Some(FaceEvent::Closed)));
+    }
+
+    #[tokio::test]
+    async fn test_unread_events_do_not_stall_interests() {
+        let (consumer, producer) = link_face_pair("a", "b", &Arc::new(UdcnMetrics::new()));
+
+        tokio::spawn(async move {
+            while let Some(FaceEvent::InterestReceived(interest)) = producer.next_event().await {
+                producer.send_data(Data::new(interest.name().clone(), "x")).await.unwrap();
+            }
+        });
+
+        // More exchanges than the event channel holds, without reading it
+        for i in 0..250 {
+            let name = Name::from_string(&format!("/test/{}", i)).unwrap();
+            consumer.express_interest(Interest::new(name), 1000).await.unwrap();
+        }
+        timeout(Duration::from_secs(1), consumer.close()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_can_be_prefix_interest_answered() {
+        let (consumer, producer) = link_face_pair("a", "b", &Arc::new(UdcnMetrics::new()));
+
+        tokio::spawn(async move {
+            if let Some(FaceEvent::InterestReceived(interest)) = producer.next_event().await {
+                let name = Name::from_string("/a/1").unwrap();
+                assert!(interest.name().is_prefix_of(&name));
+                producer.send_data(Data::new(name, "x")).await.unwrap();
+            }
+        });
+
+        let interest = Interest::new(Name::from_string("/a").unwrap()).with_can_be_prefix(true);
+        let data = consumer.express_interest(interest, 1000).await.unwrap();
+        assert_eq!(data.name().to_string(), "/a/1");
+        assert_eq!(consumer.pending_interest_count().await, 0);
+    }
+}
@@ -0,0 +1,195 @@
+//! Transport-independent face interface.
+//!
+//! The forwarding core and [`NdnQuicTransport`](crate::NdnQuicTransport)
+//! manage faces through [`NdnFace`], so QUIC faces and faces over TCP, UDP,
+//! Unix sockets, Ethernet or WebSocket are handled the same way.
+
+use async_trait::async_trait;
+use rust_udcn_common::ndn::{Data, Interest, Nack, NackReason, Name};
+use std::fmt::Debug;
+
+use crate::{
+    error::FaceError,
+    face::{Face, FaceEvent, FaceStats},
+    forwarder::RIB_REGISTER_PREFIX,
+    identity::PeerIdentity,
+    link_face::LinkFace,
+    CLOSE_CODE_GOAWAY, DEFAULT_INTEREST_TIMEOUT_MS,
+};
+
+/// A face packets can be exchanged on, whatever transport it runs over
+#[async_trait]
+pub trait NdnFace: Debug + Send + Sync {
+    /// Unique identifier of the face
+    fn id(&self) -> &str;
+
+    /// Snapshot of the face's traffic statistics
+    fn stats(&self) -> FaceStats;
+
+    /// Whether the peer is on this host (a Unix socket or loopback
+    /// address), so it may register routes
+    fn is_local(&self) -> bool;
+
+    /// Identity the peer authenticated with. The routes of a peer outlive
+    /// each of its faces.
+    fn peer_identity(&self) -> Option<&PeerIdentity> {
+        None
+    }
+
+    /// Send an Interest without waiting for Data
+    async fn send_interest(&self, interest: Interest) -> Result<(), FaceError>;
+
+    /// Send a Data packet
+    async fn send_data(&self, data: Data) -> Result<(), FaceError>;
+
+    /// Send a Nack for an Interest
+    async fn send_nack(&self, interest: Interest, reason: NackReason) -> Result<(), FaceError>;
+
+    /// Send a Nack, as received from upstream or built by the caller
+    async fn send_nack_packet(&self, nack: Nack) -> Result<(), FaceError>;
+
+    /// Send an Interest and wait up to `timeout_ms` for Data
+    async fn express_interest(&self, interest: Interest, timeout_ms: u64) -> Result<Data, FaceError>;
+
+    /// Receive the next event, or None once the face is gone
+    async fn recv(&self) -> Option<FaceEvent>;
+
+    /// Close the face
+    async fn close(&self);
+
+    /// Close the face because the local node is shutting down
+    async fn close_going_away(&self) {
+        self.close().await;
+    }
+
+    /// Check if the face is closed
+    async fn is_closed(&self) -> bool;
+
+    /// Number of names with outstanding expressed Interests
+    async fn pending_interest_count(&self) -> usize;
+
+    /// Ask the forwarder at the other end to route Interests under `prefix`
+    /// to this face
+    async fn register_prefix(&self, prefix: &Name) -> Result<(), FaceError> {
+        let mut command = Name::from_string(RIB_REGISTER_PREFIX)?;
+        for component in prefix.components() {
+            command.push(component.clone());
+        }
+        self.express_interest(Interest::new(command), DEFAULT_INTEREST_TIMEOUT_MS).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NdnFace for Face {
+    fn id(&self) -> &str {
+        Face::id(self)
+    }
+
+    fn stats(&self) -> FaceStats {
+        Face::stats(self)
+    }
+
+    fn is_local(&self) -> bool {
+        Face::is_local(self)
+    }
+
+    fn peer_identity(&self) -> Option<&PeerIdentity> {
+        Face::peer_identity(self)
+    }
+
+    async fn send_interest(&self, interest: Interest) -> Result<(), FaceError> {
+        Face::send_interest(self, interest).await
+    }
+
+    async fn send_data(&self, data: Data) -> Result<(), FaceError> {
+        Face::send_data(self, data).await
+    }
+
+    async fn send_nack(&self, interest: Interest, reason: NackReason) -> Result<(), FaceError> {
+        Face::send_nack(self, interest, reason).await
+    }
+
+    async fn send_nack_packet(&self, nack: Nack) -> Result<(), FaceError> {
+        Face::send_nack_packet(self, nack).await
+    }
+
+    async fn express_interest(&self, interest: Interest, timeout_ms: u64) -> Result<Data, FaceError> {
+        Face::express_interest(self, interest, timeout_ms).await
+    }
+
+    async fn recv(&self) -> Option<FaceEvent> {
+        self.next_event().await
+    }
+
+    async fn close(&self) {
+        Face::close(self).await
+    }
+
+    async fn close_going_away(&self) {
+        self.close_with(CLOSE_CODE_GOAWAY, b"Shutting down").await
+    }
+
+    async fn is_closed(&self) -> bool {
+        Face::is_closed(self).await
+    }
+
+    async fn pending_interest_count(&self) -> usize {
+        Face::pending_interest_count(self).await
+    }
+
+    async fn register_prefix(&self, prefix: &Name) -> Result<(), FaceError> {
+        Face::register_prefix(self, prefix).await
+    }
+}
+
+#[async_trait]
+impl NdnFace for LinkFace {
+    fn id(&self) -> &str {
+        LinkFace::id(self)
+    }
+
+    fn stats(&self) -> FaceStats {
+        LinkFace::stats(self)
+    }
+
+    fn is_local(&self) -> bool {
+        LinkFace::is_local(self)
+    }
+
+    async fn send_interest(&self, interest: Interest) -> Result<(), FaceError> {
+        LinkFace::send_interest(self, interest).await
+    }
+
+    async fn send_data(&self, data: Data) -> Result<(), FaceError> {
+        LinkFace::send_data(self, data).await
+    }
+
+    async fn send_nack(&self, interest: Interest, reason: NackReason) -> Result<(), FaceError> {
+        LinkFace::send_nack(self, interest, reason).await
+    }
+
+    async fn send_nack_packet(&self, nack: Nack) -> Result<(), FaceError> {
+        LinkFace::send_nack_packet(self, nack).await
+    }
+
+    async fn express_interest(&self, interest: Interest, timeout_ms: u64) -> Result<Data, FaceError> {
+        LinkFace::express_interest(self, interest, timeout_ms).await
+    }
+
+    async fn recv(&self) -> Option<FaceEvent> {
+        self.next_event().await
+    }
+
+    async fn close(&self) {
+        LinkFace::close(self).await
+    }
+
+    async fn is_closed(&self) -> bool {
+        LinkFace::is_closed(self).await
+    }
+
+    async fn pending_interest_count(&self) -> usize {
+        LinkFace::pending_interest_count(self).await
+    }
+}
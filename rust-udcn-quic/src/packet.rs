@@ -3,14 +3,27 @@
 //! This module provides utilities for encoding and decoding NDN packets
 //! for transmission over QUIC.
 
-use anyhow::{anyhow, Result};
-use bytes::{Bytes, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
 use log::{debug, trace};
-use rust_udcn_common::ndn::{Data, Interest};
+use rust_udcn_common::{
+    ndn::{Data, Interest, Nack},
+    tlv::{decode_tlv_length, decode_tlv_type},
+    Error, Result,
+};
 
 /// Packet types
 const PACKET_TYPE_INTEREST: u8 = 0x05;
 const PACKET_TYPE_DATA: u8 = 0x06;
+const PACKET_TYPE_NACK: u8 = 0x03;
+
+/// First byte of a long-lived stream carrying length-prefixed packets
+pub const FRAMED_STREAM_MARKER: u8 = 0xFF;
+
+/// Maximum length of a single length-prefixed packet
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// Longest TLV header: one type byte and a five-byte length
+const MAX_TLV_HEADER_SIZE: usize = 6;
 
 /// An NDN packet that can be sent over QUIC
 #[derive(Debug, Clone)]
@@ -20,13 +33,16 @@ pub enum NdnPacket {
 
     /// A Data packet
     Data(Data),
+
+    /// A network Nack
+    Nack(Nack),
 }
 
 impl NdnPacket {
     /// Create a new packet from raw bytes
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
         if bytes.is_empty() {
-            return Err(anyhow!("Empty packet"));
+            return Err(Error::NdnPacket("Empty packet".to_string()));
         }
 
         // The first byte indicates the packet type in NDN-TLV
@@ -39,10 +55,44 @@ impl NdnPacket {
                 let data = Data::decode(bytes)?;
                 Ok(NdnPacket::Data(data))
             }
-            _ => Err(anyhow!("Unknown packet type: {}", bytes[0])),
+            PACKET_TYPE_NACK => {
+                let nack = Nack::decode(bytes)?;
+                Ok(NdnPacket::Nack(nack))
+            }
+            _ => Err(Error::NdnPacket(format!("Unknown packet type: {}", bytes[0]))),
         }
     }
 
+    /// Decode all packets from a buffer of back-to-back TLV-encoded packets
+    pub fn from_bytes_multi(bytes: &[u8]) -> Result<Vec<Self>> {
+        let mut packets = Vec::new();
+        let mut offset = 0;
+
+        while offset < bytes.len() {
+            let len = encoded_len(&bytes[offset..])?;
+            packets.push(Self::from_bytes(&bytes[offset..offset + len])?);
+            offset += len;
+        }
+
+        if packets.is_empty() {
+            return Err(Error::NdnPacket("Empty packet".to_string()));
+        }
+        Ok(packets)
+    }
+
+    /// Encode the packet with a 4-byte big-endian length prefix
+    pub fn to_frame(&self) -> Result<Bytes> {
+        let bytes = self.to_bytes()?;
+        if bytes.len() > MAX_FRAME_SIZE {
+            return Err(Error::NdnPacket(format!("Packet of {} bytes exceeds the maximum frame size", bytes.len())));
+        }
+
+        let mut frame = BytesMut::with_capacity(4 + bytes.len());
+        frame.put_u32(bytes.len() as u32);
+        frame.extend_from_slice(&bytes);
+        Ok(frame.freeze())
+    }
+
     /// Convert the packet to bytes for transmission
     pub fn to_bytes(&self) -> Result<Bytes> {
         match self {
@@ -56,6 +106,11 @@ impl NdnPacket {
                 data.encode(&mut buffer)?;
                 Ok(buffer.freeze())
             }
+            NdnPacket::Nack(nack) => {
+                let mut buffer = BytesMut::new();
+                nack.encode(&mut buffer)?;
+                Ok(buffer.freeze())
+            }
         }
     }
 
@@ -64,6 +119,7 @@ impl NdnPacket {
         match self {
             NdnPacket::Interest(interest) => interest.name().to_string(),
             NdnPacket::Data(data) => data.name().to_string(),
+            NdnPacket::Nack(nack) => nack.name().to_string(),
         }
     }
 
@@ -72,6 +128,7 @@ impl NdnPacket {
         match self {
             NdnPacket::Interest(_) => "Interest",
             NdnPacket::Data(_) => "Data",
+            NdnPacket::Nack(_) => "Nack",
         }
     }
 
@@ -80,3 +137,122 @@ impl NdnPacket {
         Ok(self.to_bytes()?.len())
     }
 }
+
+/// Split the first complete TLV packet off the front of a byte-stream buffer
+/// of back-to-back packets.
+///
+/// Returns None if the buffer does not hold a complete packet yet.
+pub fn split_stream_packet(buf: &mut BytesMut) -> Result<Option<Bytes>> {
+    let len = match encoded_len(buf) {
+        Ok(len) => len,
+        Err(_) if buf.len() < MAX_TLV_HEADER_SIZE => return Ok(None),
+        Err(e) => {
+            // Only a truncated value is recoverable by reading more
+            let mut header = &buf[..];
+            decode_tlv_type(&mut header)?;
+            let value_len = decode_tlv_length(&mut header)?;
+            if value_len > MAX_FRAME_SIZE {
+                return Err(Error::Tlv(format!("Packet of {} bytes exceeds the maximum size", value_len)));
+            }
+            trace!("Waiting for the rest of a packet: {}", e);
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(buf.split_to(len).freeze()))
+}
+
+/// Total encoded length (header and value) of the TLV packet at the start of `bytes`
+fn encoded_len(bytes: &[u8]) -> Result<usize> {
+    let mut buf = bytes;
+    decode_tlv_type(&mut buf)?;
+    let value_len = decode_tlv_length(&mut buf)?;
+    let total = bytes.len() - buf.len() + value_len;
+    if total > bytes.len() {
+        return Err(Error::Tlv(format!("Truncated packet: need {} bytes, have {}", total, bytes.len())));
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_udcn_common::ndn::{ChildSelector, Name};
+
+    #[test]
+    fn test_from_bytes_multi() {
+        let interest = NdnPacket::Interest(Interest::new(Name::from_string("/a/b").unwrap()));
+        let data = NdnPacket::Data(Data::new(Name::from_string("/a/b").unwrap(), vec![1u8; 300]));
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&interest.to_bytes().unwrap());
+        buf.extend_from_slice(&data.to_bytes().unwrap());
+
+        let packets = NdnPacket::from_bytes_multi(&buf).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].packet_type(), "Interest");
+        assert_eq!(packets[1].packet_type(), "Data");
+
+        assert!(NdnPacket::from_bytes_multi(&buf[..buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_split_stream_packet() {
+        let interest = NdnPacket::Interest(Interest::new(Name::from_string("/a/b").unwrap()));
+        let bytes = interest.to_bytes().unwrap();
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&bytes[..3]);
+        assert!(split_stream_packet(&mut buf).unwrap().is_none());
+
+        buf.extend_from_slice(&bytes[3..]);
+        buf.extend_from_slice(&bytes[..1]);
+        let packet = split_stream_packet(&mut buf).unwrap().unwrap();
+        assert_eq!(NdnPacket::from_bytes(&packet).unwrap().name(), "/a/b");
+        assert_eq!(buf.len(), 1);
+    }
+
+    #[test]
+    fn test_application_parameters() {
+        let interest = Interest::new(Name::from_string("/a/b").unwrap())
+            .with_can_be_prefix(true)
+            .with_child_selector(ChildSelector::Rightmost)
+            .with_application_parameters(vec![0xde, 0xad, 0xbe, 0xef]);
+        let bytes = NdnPacket::Interest(interest).to_bytes().unwrap();
+
+        match NdnPacket::from_bytes(&bytes).unwrap() {
+            NdnPacket::Interest(parsed) => {
+                assert!(parsed.can_be_prefix);
+                assert_eq!(parsed.child_selector, ChildSelector::Rightmost);
+                assert_eq!(parsed.application_parameters.as_deref(), Some(&[0xde, 0xad, 0xbe, 0xef][..]));
+            }
+            other => panic!("Unexpected packet {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_freshness_period() {
+        let data = Data::new(Name::from_string("/a/b").unwrap(), "x")
+            .with_freshness_period(1500)
+            .with_ttl(60_000);
+        let bytes = NdnPacket::Data(data).to_bytes().unwrap();
+
+        match NdnPacket::from_bytes(&bytes).unwrap() {
+            NdnPacket::Data(parsed) => {
+                assert_eq!(parsed.freshness_period_ms, 1500);
+                // Retention is local policy and not carried on the wire
+                assert_eq!(parsed.ttl_ms, 10_000);
+            }
+            other => panic!("Unexpected packet {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_frame_length_prefix() {
+        let packet = NdnPacket::Interest(Interest::new(Name::from_string("/a").unwrap()));
+        let frame = packet.to_frame().unwrap();
+        let len = u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize;
+        assert_eq!(len, frame.len() - 4);
+        assert!(NdnPacket::from_bytes(&frame[4..]).is_ok());
+    }
+}
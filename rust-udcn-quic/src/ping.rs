@@ -0,0 +1,332 @@
+//! Reachability tests, the way ndnping does them.
+//!
+//! A [`PingServer`] answers every Interest under `<prefix>/ping` with Data
+//! carrying the time it was produced. A [`Pinger`] expresses
+//! `<prefix>/ping/<seq>` Interests and times the Data coming back, and
+//! [`PingStats`] sums the outcomes up into loss, RTT and jitter.
+
+use bytes::{Buf, BufMut, BytesMut};
+use log::debug;
+use rust_udcn_common::ndn::{Data, Interest, Name, NameComponent};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{error::FaceError, face::FaceEvent, ndn_face::NdnFace};
+
+/// Name component between the pinged prefix and the sequence number
+pub const PING_COMPONENT: &str = "ping";
+
+/// `prefix` followed by the ping component
+fn ping_prefix(prefix: &Name) -> Name {
+    let mut name = prefix.clone();
+    name.push(NameComponent::new(PING_COMPONENT.as_bytes().to_vec()));
+    name
+}
+
+/// Microseconds since the Unix epoch
+fn unix_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_micros() as u64).unwrap_or(0)
+}
+
+/// Answers ping Interests under a prefix
+pub struct PingServer {
+    /// Face the Interests arrive on
+    face: Arc<dyn NdnFace>,
+
+    /// `<prefix>/ping`
+    prefix: Name,
+
+    /// Bytes of padding after the timestamp
+    payload_size: usize,
+}
+
+impl PingServer {
+    /// Answer pings of `prefix` arriving on `face`, padding each reply
+    /// with `payload_size` bytes
+    pub fn new(face: Arc<dyn NdnFace>, prefix: &Name, payload_size: usize) -> Self {
+        Self {
+            face,
+            prefix: ping_prefix(prefix),
+            payload_size,
+        }
+    }
+
+    /// Name prefix of the pings answered
+    pub fn prefix(&self) -> &Name {
+        &self.prefix
+    }
+
+    /// Ask the forwarder at the other end of the face to route the pings here
+    pub async fn register(&self) -> Result<(), FaceError> {
+        self.face.register_prefix(&self.prefix).await
+    }
+
+    /// Answer pings until the face closes, calling `on_ping` with the name
+    /// of each. Returns the number of pings answered.
+    pub async fn run(&self, mut on_ping: impl FnMut(&Name)) -> Result<u64, FaceError> {
+        let mut answered = 0;
+        while let Some(event) = self.face.recv().await {
+            match event {
+                FaceEvent::InterestReceived(interest) => {
+                    let name = interest.name();
+                    if name.len() <= self.prefix.len() || !self.prefix.is_prefix_of(name) {
+                        debug!("Ignoring Interest {} outside {}", name, self.prefix);
+                        continue;
+                    }
+                    self.face.send_data(self.reply(name.clone())).await?;
+                    on_ping(name);
+                    answered += 1;
+                }
+                FaceEvent::Closed => break,
+                _ => {}
+            }
+        }
+        Ok(answered)
+    }
+
+    /// Data answering the ping `name`
+    fn reply(&self, name: Name) -> Data {
+        let mut content = BytesMut::with_capacity(8 + self.payload_size);
+        content.put_u64(unix_micros(SystemTime::now()));
+        content.put_bytes(0, self.payload_size);
+
+        let mut data = Data::new(name, content.freeze());
+        // Neither kept nor fresh, so no cache answers in the server's place
+        data.ttl_ms = 0;
+        data.freshness_period_ms = 0;
+        data
+    }
+}
+
+/// What became of one ping
+#[derive(Debug, Clone, PartialEq)]
+pub enum PingOutcome {
+    /// Data came back
+    Reply {
+        /// Time from sending the Interest to receiving the Data
+        rtt: Duration,
+
+        /// When the server produced the Data, by its clock
+        server_time: Option<SystemTime>,
+    },
+
+    /// No Data came back: the Interest timed out, was Nacked or the face
+    /// failed, as described
+    Lost(String),
+}
+
+/// Sends sequenced ping Interests to a prefix
+pub struct Pinger {
+    /// Face the Interests are sent on
+    face: Arc<dyn NdnFace>,
+
+    /// `<prefix>/ping`
+    prefix: Name,
+
+    /// How long to wait for each reply
+    timeout: Duration,
+
+    /// Sequence number of the next ping
+    next_seq: AtomicU64,
+}
+
+impl Pinger {
+    /// Ping `prefix` on `face`, waiting up to `timeout` for each reply
+    ///
+    /// Sequence numbers start from a value picked from the clock, so runs
+    /// never ask for Data an earlier run left in a cache.
+    pub fn new(face: Arc<dyn NdnFace>, prefix: &Name, timeout: Duration) -> Self {
+        let start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self {
+            face,
+            prefix: ping_prefix(prefix),
+            timeout,
+            next_seq: AtomicU64::new(start % (1 << 32)),
+        }
+    }
+
+    /// Start the sequence numbers at `seq`
+    pub fn with_start_seq(self, seq: u64) -> Self {
+        self.next_seq.store(seq, Ordering::Relaxed);
+        self
+    }
+
+    /// Name of the ping with sequence number `seq`
+    pub fn name(&self, seq: u64) -> Name {
+        let mut name = self.prefix.clone();
+        name.push(NameComponent::new(seq.to_string().into_bytes()));
+        name
+    }
+
+    /// Send the next ping and wait for its reply. Returns its sequence
+    /// number and outcome.
+    pub async fn ping(&self) -> (u64, PingOutcome) {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        let interest = Interest::new(self.name(seq))
+            .with_must_be_fresh(true)
+            .with_lifetime(self.timeout.as_millis() as u32);
+
+        let start = Instant::now();
+        let outcome = match self.face.express_interest(interest, self.timeout.as_millis() as u64).await {
+            Ok(data) => PingOutcome::Reply {
+                rtt: start.elapsed(),
+                server_time: server_time(&data),
+            },
+            Err(e) => PingOutcome::Lost(e.to_string()),
+        };
+        (seq, outcome)
+    }
+}
+
+/// Production time a ping server put in its reply
+fn server_time(data: &Data) -> Option<SystemTime> {
+    let mut content = data.content().clone();
+    if content.len() < 8 {
+        return None;
+    }
+    UNIX_EPOCH.checked_add(Duration::from_micros(content.get_u64()))
+}
+
+/// Loss, RTT and jitter over a run of pings
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PingStats {
+    /// Pings sent
+    pub sent: u64,
+
+    /// Pings answered
+    pub received: u64,
+
+    /// Shortest RTT
+    pub min_rtt: Option<Duration>,
+
+    /// Longest RTT
+    pub max_rtt: Option<Duration>,
+
+    /// Sum of the RTTs
+    total_rtt: Duration,
+
+    /// RTT of the last reply
+    last_rtt: Option<Duration>,
+
+    /// Sum of the differences between consecutive RTTs
+    total_variation: Duration,
+}
+
+impl PingStats {
+    /// Count the outcome of a ping
+    pub fn record(&mut self, outcome: &PingOutcome) {
+        self.sent += 1;
+        let PingOutcome::Reply { rtt, .. } = outcome else {
+            return;
+        };
+
+        self.received += 1;
+        self.min_rtt = Some(self.min_rtt.map_or(*rtt, |min| min.min(*rtt)));
+        self.max_rtt = Some(self.max_rtt.map_or(*rtt, |max| max.max(*rtt)));
+        self.total_rtt += *rtt;
+        if let Some(last) = self.last_rtt {
+            self.total_variation += rtt.abs_diff(last);
+        }
+        self.last_rtt = Some(*rtt);
+    }
+
+    /// Share of the pings lost, between 0 and 1
+    pub fn loss(&self) -> f64 {
+        if self.sent == 0 {
+            0.0
+        } else {
+            (self.sent - self.received) as f64 / self.sent as f64
+        }
+    }
+
+    /// Average RTT, once a reply came back
+    pub fn avg_rtt(&self) -> Option<Duration> {
+        (self.received > 0).then(|| self.total_rtt / self.received as u32)
+    }
+
+    /// Average difference between the RTTs of consecutive replies, once two
+    /// came back
+    pub fn jitter(&self) -> Option<Duration> {
+        (self.received > 1).then(|| self.total_variation / (self.received - 1) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link_face::link_face_pair;
+    use rust_udcn_common::metrics::UdcnMetrics;
+
+    #[tokio::test]
+    async fn test_ping_server() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (client, server_face) = link_face_pair("client", "server", &metrics);
+        let client: Arc<dyn NdnFace> = Arc::new(client);
+        let server_face: Arc<dyn NdnFace> = Arc::new(server_face);
+
+        let prefix = Name::from_string("/node").unwrap();
+        let server = PingServer::new(Arc::clone(&server_face), &prefix, 16);
+        assert_eq!(server.prefix(), &Name::from_string("/node/ping").unwrap());
+        let served = tokio::spawn(async move { server.run(|_| {}).await });
+
+        let pinger = Pinger::new(Arc::clone(&client), &prefix, Duration::from_millis(500)).with_start_seq(7);
+        assert_eq!(pinger.name(7), Name::from_string("/node/ping/7").unwrap());
+
+        let mut stats = PingStats::default();
+        for expected_seq in 7..9 {
+            let (seq, outcome) = pinger.ping().await;
+            assert_eq!(seq, expected_seq);
+            match &outcome {
+                PingOutcome::Reply { server_time, .. } => assert!(server_time.is_some()),
+                other => panic!("Unexpected outcome {:?}", other),
+            }
+            stats.record(&outcome);
+        }
+
+        // Other prefixes are not answered
+        let stranger = Pinger::new(Arc::clone(&client), &Name::from_string("/other").unwrap(), Duration::from_millis(100));
+        let (_, outcome) = stranger.ping().await;
+        assert!(matches!(outcome, PingOutcome::Lost(_)));
+        stats.record(&outcome);
+
+        assert_eq!(stats.sent, 3);
+        assert_eq!(stats.received, 2);
+        assert!(stats.jitter().is_some());
+
+        server_face.close().await;
+        assert_eq!(served.await.unwrap().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_ping_stats() {
+        let reply = |ms| PingOutcome::Reply {
+            rtt: Duration::from_millis(ms),
+            server_time: None,
+        };
+
+        let mut stats = PingStats::default();
+        assert_eq!(stats.loss(), 0.0);
+        assert_eq!(stats.avg_rtt(), None);
+
+        stats.record(&reply(10));
+        assert_eq!(stats.jitter(), None);
+        stats.record(&reply(30));
+        stats.record(&PingOutcome::Lost("Interest timed out".to_string()));
+        stats.record(&reply(20));
+
+        assert_eq!(stats.loss(), 0.25);
+        assert_eq!(stats.min_rtt, Some(Duration::from_millis(10)));
+        assert_eq!(stats.max_rtt, Some(Duration::from_millis(30)));
+        assert_eq!(stats.avg_rtt(), Some(Duration::from_millis(20)));
+        // |30 - 10| and |20 - 30|, the lost ping in between is skipped
+        assert_eq!(stats.jitter(), Some(Duration::from_millis(15)));
+    }
+}
@@ -0,0 +1,299 @@
+//! Producer handler registration.
+//!
+//! This module lets applications serve content by registering async
+//! callbacks for name prefixes. Incoming Interests are dispatched to the
+//! handler with the longest matching prefix, within the [`HandlerLimits`]
+//! on the invocations running at once.
+
+use futures::future::BoxFuture;
+use rust_udcn_common::ndn::{Data, Interest, NackReason, Name};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore};
+
+/// Response produced by an Interest handler
+#[derive(Debug, Clone)]
+pub enum ProducerResponse {
+    /// Answer the Interest with Data
+    Data(Data),
+
+    /// Answer the Interest with a Nack
+    Nack(NackReason),
+
+    /// Do not answer the Interest (it will time out at the consumer)
+    Drop,
+}
+
+impl From<Data> for ProducerResponse {
+    fn from(data: Data) -> Self {
+        ProducerResponse::Data(data)
+    }
+}
+
+/// Type-erased Interest handler
+pub type InterestHandler =
+    Arc<dyn Fn(Interest) -> BoxFuture<'static, ProducerResponse> + Send + Sync>;
+
+/// Registered handler together with its prefix
+#[derive(Clone)]
+struct Registration {
+    prefix: Name,
+    handler: InterestHandler,
+}
+
+/// Table of Interest handlers keyed by name prefix
+#[derive(Clone, Default)]
+pub struct HandlerRegistry {
+    registrations: Arc<RwLock<Vec<Registration>>>,
+}
+
+impl std::fmt::Debug for HandlerRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HandlerRegistry").finish_non_exhaustive()
+    }
+}
+
+impl HandlerRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a handler for a prefix, replacing any previous handler
+    pub async fn insert<F, Fut>(&self, prefix: Name, handler: F)
+    where
+        F: Fn(Interest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ProducerResponse> + Send + 'static,
+    {
+        let handler: InterestHandler = Arc::new(move |interest| Box::pin(handler(interest)));
+        let mut registrations = self.registrations.write().await;
+        registrations.retain(|r| r.prefix != prefix);
+        registrations.push(Registration { prefix, handler });
+    }
+
+    /// Remove the handler for a prefix. Returns whether one was registered.
+    pub async fn remove(&self, prefix: &Name) -> bool {
+        let mut registrations = self.registrations.write().await;
+        let before = registrations.len();
+        registrations.retain(|r| &r.prefix != prefix);
+        registrations.len() != before
+    }
+
+    /// Find the handler with the longest prefix matching `name`
+    pub async fn lookup(&self, name: &Name) -> Option<(Name, InterestHandler)> {
+        let registrations = self.registrations.read().await;
+        registrations
+            .iter()
+            .filter(|r| r.prefix.len() <= name.len() && r.prefix.is_prefix_of(name))
+            .max_by_key(|r| r.prefix.len())
+            .map(|r| (r.prefix.clone(), Arc::clone(&r.handler)))
+    }
+
+    /// List the registered prefixes
+    pub async fn prefixes(&self) -> Vec<Name> {
+        let registrations = self.registrations.read().await;
+        registrations.iter().map(|r| r.prefix.clone()).collect()
+    }
+}
+
+/// What happens to an Interest whose handler is at its concurrency limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HandlerOverflow {
+    /// Wait for a running invocation to finish, in arrival order
+    Queue,
+
+    /// Answer with a Congestion Nack, so downstreams back off or retry
+    /// elsewhere
+    #[default]
+    Nack,
+
+    /// Do not answer the Interest (it will time out at the consumer)
+    Drop,
+}
+
+/// Limits on the handler invocations running at once
+///
+/// A per-prefix limit below the global one keeps a flooded prefix from
+/// taking every invocation, so the handlers of other prefixes still run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HandlerLimits {
+    /// Invocations of all handlers together (None for no limit)
+    pub global: Option<usize>,
+
+    /// Invocations of the handler of each prefix (None for no limit)
+    pub per_prefix: Option<usize>,
+
+    /// What happens to Interests over a limit
+    pub overflow: HandlerOverflow,
+}
+
+/// Permits of a running handler invocation, given back when dropped
+#[derive(Debug)]
+pub(crate) struct HandlerPermit {
+    _prefix: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+/// Enforces [`HandlerLimits`] on the dispatch of Interests to handlers
+#[derive(Debug)]
+pub(crate) struct HandlerLimiter {
+    limits: HandlerLimits,
+
+    /// Permits shared by all handlers
+    global: Option<Arc<Semaphore>>,
+
+    /// Permits of each registered prefix, created on first use
+    prefixes: Mutex<HashMap<Name, Arc<Semaphore>>>,
+}
+
+impl HandlerLimiter {
+    /// Limiter enforcing `limits`; a limit of 0 allows one invocation
+    pub fn new(limits: HandlerLimits) -> Self {
+        Self {
+            global: limits.global.map(|limit| Arc::new(Semaphore::new(limit.max(1)))),
+            limits,
+            prefixes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// What happens to Interests over a limit
+    pub fn overflow(&self) -> HandlerOverflow {
+        self.limits.overflow
+    }
+
+    /// Permits of the handler of `prefix`, if limited
+    fn prefix_semaphore(&self, prefix: &Name) -> Option<Arc<Semaphore>> {
+        let limit = self.limits.per_prefix?;
+        let mut prefixes = self.prefixes.lock().expect("handler limiter lock poisoned");
+        let semaphore = prefixes
+            .entry(prefix.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(limit.max(1))));
+        Some(Arc::clone(semaphore))
+    }
+
+    /// Permits to invoke the handler of `prefix` now, or None if it or all
+    /// handlers are at their limit
+    pub fn try_acquire(&self, prefix: &Name) -> Option<HandlerPermit> {
+        let prefix = match self.prefix_semaphore(prefix) {
+            Some(semaphore) => Some(semaphore.try_acquire_owned().ok()?),
+            None => None,
+        };
+        let global = match &self.global {
+            Some(semaphore) => Some(Arc::clone(semaphore).try_acquire_owned().ok()?),
+            None => None,
+        };
+        Some(HandlerPermit {
+            _prefix: prefix,
+            _global: global,
+        })
+    }
+
+    /// Wait for the permits to invoke the handler of `prefix`
+    ///
+    /// The permit of the prefix comes first, so Interests queued behind a
+    /// busy handler hold no global permit the other handlers could use.
+    pub async fn acquire(&self, prefix: &Name) -> HandlerPermit {
+        let prefix = match self.prefix_semaphore(prefix) {
+            Some(semaphore) => Some(semaphore.acquire_owned().await.expect("handler semaphore closed")),
+            None => None,
+        };
+        let global = match &self.global {
+            Some(semaphore) => Some(Arc::clone(semaphore).acquire_owned().await.expect("handler semaphore closed")),
+            None => None,
+        };
+        HandlerPermit {
+            _prefix: prefix,
+            _global: global,
+        }
+    }
+
+    /// Forget the permits of a prefix whose handler was removed
+    ///
+    /// Running invocations keep theirs until they finish.
+    pub fn remove(&self, prefix: &Name) {
+        self.prefixes.lock().expect("handler limiter lock poisoned").remove(prefix);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn name(s: &str) -> Name {
+        Name::from_string(s).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_registry_dispatch() {
+        let registry = HandlerRegistry::new();
+        registry
+            .insert(name("/app"), |_| async { ProducerResponse::Nack(NackReason::NoRoute) })
+            .await;
+        registry
+            .insert(name("/app/videos"), |interest: Interest| async move {
+                Data::new(interest.name().clone(), b"video".to_vec()).into()
+            })
+            .await;
+
+        // The longest matching prefix wins
+        let (prefix, handler) = registry.lookup(&name("/app/videos/1")).await.unwrap();
+        assert_eq!(prefix, name("/app/videos"));
+        assert!(matches!(handler(Interest::new(name("/app/videos/1"))).await, ProducerResponse::Data(_)));
+        let (prefix, _) = registry.lookup(&name("/app/images/1")).await.unwrap();
+        assert_eq!(prefix, name("/app"));
+        assert!(registry.lookup(&name("/other")).await.is_none());
+        // Components match whole, not as strings
+        let (prefix, _) = registry.lookup(&name("/app/videos2")).await.unwrap();
+        assert_eq!(prefix, name("/app"));
+
+        // A handler registered again replaces the previous one
+        registry.insert(name("/app"), |_| async { ProducerResponse::Drop }).await;
+        assert_eq!(registry.prefixes().await.len(), 2);
+        let (_, handler) = registry.lookup(&name("/app/x")).await.unwrap();
+        assert!(matches!(handler(Interest::new(name("/app/x"))).await, ProducerResponse::Drop));
+
+        // Removing the longer prefix falls back to the shorter one
+        assert!(registry.remove(&name("/app/videos")).await);
+        assert!(!registry.remove(&name("/app/videos")).await);
+        let (prefix, _) = registry.lookup(&name("/app/videos/1")).await.unwrap();
+        assert_eq!(prefix, name("/app"));
+        assert!(registry.remove(&name("/app")).await);
+        assert!(registry.lookup(&name("/app/videos/1")).await.is_none());
+        assert!(registry.prefixes().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handler_limits() {
+        let limiter = Arc::new(HandlerLimiter::new(HandlerLimits {
+            global: Some(3),
+            per_prefix: Some(2),
+            overflow: HandlerOverflow::Queue,
+        }));
+        let (a, b) = (name("/a"), name("/b"));
+
+        // A flooded prefix leaves room for the others
+        let first = limiter.try_acquire(&a).unwrap();
+        let _second = limiter.try_acquire(&a).unwrap();
+        assert!(limiter.try_acquire(&a).is_none());
+        let _third = limiter.try_acquire(&b).unwrap();
+        assert!(limiter.try_acquire(&b).is_none());
+
+        // Queued invocations start as running ones finish
+        let queued = tokio::spawn({
+            let limiter = Arc::clone(&limiter);
+            async move { limiter.acquire(&a).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!queued.is_finished());
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), queued).await.unwrap().unwrap();
+
+        let unlimited = HandlerLimiter::new(HandlerLimits::default());
+        let permits: Vec<_> = (0..100).map(|_| unlimited.try_acquire(&b).unwrap()).collect();
+        assert_eq!(permits.len(), 100);
+    }
+}
@@ -0,0 +1,384 @@
+//! Publish/subscribe on top of State Vector Sync.
+//!
+//! Every topic is a sync group. A publication takes the next sequence
+//! number of the publishing node in the topic's group and is served as the
+//! segmented object `<node-id>/<topic>/<seq>`. Subscribers learn the new
+//! sequence numbers of other nodes through sync, fetch the objects they are
+//! missing and receive each one as a single reassembled Data packet.
+
+use bytes::{Bytes, BytesMut};
+use futures::stream::{BoxStream, StreamExt};
+use log::{debug, info, warn};
+use rust_udcn_common::ndn::{Data, Interest, Name, NameComponent};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tokio::{
+    sync::{broadcast, Mutex, RwLock},
+    task::JoinHandle,
+};
+
+use crate::{
+    error::FetchError,
+    producer::ProducerResponse,
+    sync::{StateVectorSync, SvsOptions, SyncUpdate},
+    transport::NdnQuicTransport,
+    DEFAULT_INTEREST_TIMEOUT_MS,
+};
+
+/// Default size of publication segments, in bytes
+pub const DEFAULT_SEGMENT_SIZE: usize = 4096;
+
+/// Publications buffered for slow subscribers
+const SUBSCRIBER_BUFFER_SIZE: usize = 64;
+
+/// Options for publishing and subscribing
+#[derive(Debug, Clone)]
+pub struct PubSubOptions {
+    /// Largest segment content, in bytes
+    pub segment_size: usize,
+
+    /// Number of own publications per topic kept for subscribers to fetch
+    pub retained_publications: usize,
+
+    /// Timeout for each segment Interest, in milliseconds
+    pub fetch_timeout_ms: u64,
+
+    /// Sync options of the topics' groups
+    pub sync: SvsOptions,
+}
+
+impl Default for PubSubOptions {
+    fn default() -> Self {
+        Self {
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            retained_publications: 256,
+            fetch_timeout_ms: DEFAULT_INTEREST_TIMEOUT_MS,
+            sync: SvsOptions::default(),
+        }
+    }
+}
+
+/// Prefix of the publications of `node_id` on `topic`
+fn publication_prefix(node_id: &Name, topic: &Name) -> Name {
+    let mut prefix = node_id.clone();
+    for component in topic.components() {
+        prefix.push(component.clone());
+    }
+    prefix
+}
+
+/// Name of the publication `seq` of `node_id` on `topic`
+fn publication_name(node_id: &Name, topic: &Name, seq: u64) -> Name {
+    let mut name = publication_prefix(node_id, topic);
+    name.push(NameComponent::new(seq.to_string()));
+    name
+}
+
+/// Split `content` into the segments of the object `name`
+pub(crate) fn segment(name: &Name, content: &Bytes, segment_size: usize) -> Vec<Data> {
+    let chunks: Vec<Bytes> = if content.is_empty() {
+        vec![Bytes::new()]
+    } else {
+        (0..content.len())
+            .step_by(segment_size)
+            .map(|start| content.slice(start..content.len().min(start + segment_size)))
+            .collect()
+    };
+    let last = chunks.len() as u64 - 1;
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| Data::new(name.with_segment(i as u64), chunk).with_final_block_id(last))
+        .collect()
+}
+
+/// A topic this node has joined
+struct Topic {
+    /// Sync group of the topic
+    sync: StateVectorSync,
+
+    /// Segments of this node's retained publications, by sequence number
+    published: Arc<RwLock<BTreeMap<u64, Vec<Data>>>>,
+
+    /// Publications fetched from other nodes
+    received: broadcast::Sender<Data>,
+
+    /// Task fetching the publications announced through sync
+    fetch_task: JoinHandle<()>,
+}
+
+impl Drop for Topic {
+    fn drop(&mut self) {
+        self.fetch_task.abort();
+    }
+}
+
+/// Publisher and subscriber of topics on a transport
+pub struct PubSub {
+    /// Transport publications are exchanged on
+    transport: Arc<NdnQuicTransport>,
+
+    /// Name prefix of this node's publications
+    node_id: Name,
+
+    /// Options
+    options: PubSubOptions,
+
+    /// Joined topics
+    topics: Mutex<HashMap<Name, Arc<Topic>>>,
+}
+
+impl std::fmt::Debug for PubSub {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PubSub").field("node_id", &self.node_id).finish_non_exhaustive()
+    }
+}
+
+impl PubSub {
+    /// Publish and subscribe on `transport` as `node_id`
+    pub fn new(transport: Arc<NdnQuicTransport>, node_id: Name, options: PubSubOptions) -> Self {
+        Self {
+            transport,
+            node_id,
+            options,
+            topics: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the name prefix of this node's publications
+    pub fn node_id(&self) -> &Name {
+        &self.node_id
+    }
+
+    /// Publish `content` on `topic` and return its sequence number
+    pub async fn publish(&self, topic: &Name, content: impl Into<Bytes>) -> rust_udcn_common::Result<u64> {
+        let joined = self.join(topic).await;
+        let content = content.into();
+
+        let mut published = joined.published.write().await;
+        let seq = joined.sync.seq_no().await + 1;
+        let name = publication_name(&self.node_id, topic, seq);
+        published.insert(seq, segment(&name, &content, self.options.segment_size));
+        while published.len() > self.options.retained_publications {
+            published.pop_first();
+        }
+        drop(published);
+
+        // Only announce once the segments can be fetched
+        let announced = joined.sync.publish().await?;
+        debug!("Published {} ({} bytes)", name, content.len());
+        Ok(announced)
+    }
+
+    /// Subscribe to the publications of the other nodes on `topic`
+    pub async fn subscribe(&self, topic: &Name) -> BoxStream<'static, Data> {
+        let joined = self.join(topic).await;
+        let receiver = joined.received.subscribe();
+        futures::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(data) => return Some((data, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Subscriber fell behind and missed {} publications", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+
+    /// Stop publishing and receiving on `topic`
+    pub async fn leave(&self, topic: &Name) {
+        let Some(joined) = self.topics.lock().await.remove(topic) else {
+            return;
+        };
+        let prefix = publication_prefix(&self.node_id, topic);
+        self.transport.remove_interest_handler(&prefix).await;
+        // The sync group's handler, see StateVectorSync::leave
+        self.transport.remove_interest_handler(joined.sync.group()).await;
+    }
+
+    /// Leave all topics
+    pub async fn leave_all(&self) {
+        let topics: Vec<Name> = self.topics.lock().await.keys().cloned().collect();
+        for topic in topics {
+            self.leave(&topic).await;
+        }
+    }
+
+    /// Get a joined topic, joining it first if needed
+    async fn join(&self, topic: &Name) -> Arc<Topic> {
+        let mut topics = self.topics.lock().await;
+        if let Some(joined) = topics.get(topic) {
+            return Arc::clone(joined);
+        }
+
+        let sync = StateVectorSync::join(
+            Arc::clone(&self.transport),
+            topic.clone(),
+            self.node_id.clone(),
+            self.options.sync.clone(),
+        )
+        .await;
+
+        // Serve the segments of this node's publications
+        let published: Arc<RwLock<BTreeMap<u64, Vec<Data>>>> = Arc::new(RwLock::new(BTreeMap::new()));
+        let prefix = publication_prefix(&self.node_id, topic);
+        let prefix_len = prefix.len();
+        {
+            let published = Arc::clone(&published);
+            self.transport
+                .set_interest_handler(prefix, move |interest: Interest| {
+                    let published = Arc::clone(&published);
+                    async move {
+                        let name = interest.name();
+                        let seq = name.get(prefix_len).and_then(|c| c.to_string().parse::<u64>().ok());
+                        let segment = name.get(prefix_len + 1).and_then(|c| c.to_segment());
+                        let (Some(seq), Some(segment)) = (seq, segment) else {
+                            return ProducerResponse::Drop;
+                        };
+                        match published.read().await.get(&seq).and_then(|segments| segments.get(segment as usize)) {
+                            Some(data) => ProducerResponse::Data(data.clone()),
+                            None => ProducerResponse::Drop,
+                        }
+                    }
+                })
+                .await;
+        }
+
+        let (received, _) = broadcast::channel(SUBSCRIBER_BUFFER_SIZE);
+        let fetch_task = tokio::spawn(fetch_publications(
+            Arc::clone(&self.transport),
+            topic.clone(),
+            sync.subscribe(),
+            received.clone(),
+            self.options.fetch_timeout_ms,
+        ));
+
+        info!("Joined topic {} as {}", topic, self.node_id);
+        let joined = Arc::new(Topic {
+            sync,
+            published,
+            received,
+            fetch_task,
+        });
+        topics.insert(topic.clone(), Arc::clone(&joined));
+        joined
+    }
+}
+
+/// Fetch every publication announced on `updates` while anyone subscribes
+async fn fetch_publications(
+    transport: Arc<NdnQuicTransport>,
+    topic: Name,
+    mut updates: broadcast::Receiver<SyncUpdate>,
+    received: broadcast::Sender<Data>,
+    timeout_ms: u64,
+) {
+    loop {
+        let update = match updates.recv().await {
+            Ok(update) => update,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!("Missed {} sync updates on {}", missed, topic);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if received.receiver_count() == 0 {
+            continue;
+        }
+        for seq in update.low..=update.high {
+            let name = publication_name(&update.node_id, &topic, seq);
+            match fetch_object(&transport, &name, timeout_ms).await {
+                Ok(content) => {
+                    let _ = received.send(Data::new(name, content));
+                }
+                Err(e) => debug!("Failed to fetch publication {}: {}", name, e),
+            }
+        }
+    }
+}
+
+/// Fetch the segments of `name` in order and reassemble its content
+async fn fetch_object(transport: &NdnQuicTransport, name: &Name, timeout_ms: u64) -> Result<Bytes, FetchError> {
+    let mut content = BytesMut::new();
+    let mut segment = 0;
+    loop {
+        // Published segments never change, so stale cached copies do
+        let segment_name = name.with_segment(segment);
+        let interest = Interest::new(segment_name.clone()).with_must_be_fresh(false);
+        let data = transport
+            .express_interest(interest, None, Some(timeout_ms))
+            .await
+            .map_err(|e| FetchError::new(segment_name, e))?;
+        content.extend_from_slice(data.content());
+        match data.final_block_id {
+            Some(last) if segment >= last => return Ok(content.freeze()),
+            Some(_) => segment += 1,
+            None => return Err(FetchError::NoFinalBlock(name.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{link_face::link_face_pair, TransportConfig};
+    use rust_udcn_common::metrics::UdcnMetrics;
+    use std::time::Duration;
+
+    fn name(s: &str) -> Name {
+        Name::from_string(s).unwrap()
+    }
+
+    #[test]
+    fn test_segment_publication() {
+        let name = publication_name(&name("/alice"), &name("/chat"), 3);
+        assert_eq!(name, Name::from_string("/alice/chat/3").unwrap());
+
+        let segments = segment(&name, &Bytes::from(vec![7u8; 10]), 4);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[2].content().len(), 2);
+        assert!(segments.iter().all(|data| data.final_block_id == Some(2)));
+        assert_eq!(segment(&name, &Bytes::new(), 4).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_receives_publication() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (to_bob, to_alice) = link_face_pair("to-bob", "to-alice", &metrics);
+        let transport = |node_id: &str| {
+            NdnQuicTransport::new(TransportConfig {
+                node_id: Some(name(node_id)),
+                pubsub_options: PubSubOptions {
+                    segment_size: 1000,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        };
+        let alice = Arc::new(transport("/alice").await.unwrap());
+        let bob = Arc::new(transport("/bob").await.unwrap());
+        alice.add_face(Arc::new(to_bob)).await;
+        bob.add_face(Arc::new(to_alice)).await;
+
+        let topic = name("/chat");
+        let mut publications = bob.subscribe_topic(&topic).await;
+
+        let content = Bytes::from((0..2500u32).map(|i| i as u8).collect::<Vec<u8>>());
+        assert_eq!(alice.publish(&topic, content.clone()).await.unwrap(), 1);
+
+        let data = tokio::time::timeout(Duration::from_secs(5), publications.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.name(), &Name::from_string("/alice/chat/1").unwrap());
+        assert_eq!(data.content(), &content);
+
+        alice.close().await.unwrap();
+        bob.close().await.unwrap();
+    }
+}
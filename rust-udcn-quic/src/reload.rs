@@ -0,0 +1,60 @@
+//! Certificate hot-reload for long-running servers.
+//!
+//! The watcher polls the modification times of the configured certificate
+//! and key files and swaps the server's TLS configuration when they change.
+//! Existing connections keep the configuration they were established with.
+
+use log::{debug, info, warn};
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+use crate::NdnQuicServer;
+
+/// Modification times of the watched files (None if a file is missing)
+fn modification_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Spawn a task reloading the server's certificates whenever the files change
+pub(crate) fn spawn_certificate_watcher(server: NdnQuicServer, interval: Duration) {
+    let paths = server.certificate_paths();
+    let tasks = server.tasks.clone();
+    let token = tasks.token().clone();
+
+    tasks.spawn(async move {
+        debug!("Watching {:?} for certificate changes", paths);
+
+        let mut last = modification_times(&paths);
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = ticker.tick() => {}
+            }
+
+            let current = modification_times(&paths);
+            if current == last {
+                continue;
+            }
+            // Wait until both files exist again (e.g. during an atomic rename)
+            if current.iter().any(Option::is_none) {
+                debug!("Certificate files are incomplete, waiting");
+                continue;
+            }
+
+            info!("Certificate files changed, reloading");
+            match server.reload_certificates().await {
+                Ok(()) => last = current,
+                // Keep the old stamps so the reload is retried on the next tick
+                Err(e) => warn!("Failed to reload certificates: {:#}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,308 @@
+//! Log-structured on-disk storage for the userspace content store.
+//!
+//! Every insertion and removal is appended to a single log file. On open the
+//! log is replayed to rebuild the store, and `compact` rewrites it with only
+//! the live entries. A [`RepoWriter`] appends from a thread of its own, so
+//! the tasks filling the store never wait for the disk.
+
+use bytes::{Buf, BufMut, BytesMut};
+use log::{debug, info, warn};
+use rust_udcn_common::{
+    ndn::{Data, Name},
+    Error, Result,
+};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Record types in the log
+const RECORD_INSERT: u8 = 1;
+const RECORD_REMOVE: u8 = 2;
+
+/// Size of the fixed record header (type, timestamp, ttl, length)
+const RECORD_HEADER_SIZE: usize = 1 + 8 + 4 + 4;
+
+/// A Data packet as stored in the repository
+#[derive(Debug, Clone)]
+pub struct StoredData {
+    /// The Data packet
+    pub data: Data,
+
+    /// Wall-clock time the Data was inserted
+    pub inserted_at: SystemTime,
+}
+
+impl StoredData {
+    /// Whether the Data is still within its FreshnessPeriod
+    pub fn is_fresh(&self) -> bool {
+        let freshness = Duration::from_millis(self.data.freshness_period_ms as u64);
+        // A clock set back makes the Data younger, not stale
+        self.inserted_at.elapsed().map_or(true, |age| age < freshness)
+    }
+}
+
+/// Append-only log backing a content store
+#[derive(Debug)]
+pub struct RepoLog {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl RepoLog {
+    /// Open (or create) the log at `path` and replay its contents
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<StoredData>)> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).map_err(|e| file_error(e, "Failed to create repo directory", parent))?;
+            }
+        }
+
+        let entries = if path.exists() {
+            replay(&path)?
+        } else {
+            Vec::new()
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| file_error(e, "Failed to open repo log", &path))?;
+
+        info!("Opened repo {} with {} entries", path.display(), entries.len());
+
+        Ok((
+            Self {
+                path,
+                writer: BufWriter::new(file),
+            },
+            entries,
+        ))
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an insertion record, buffered until the next [`flush`](Self::flush)
+    pub fn append_insert(&mut self, data: &Data, inserted_at: SystemTime) -> Result<()> {
+        let mut payload = BytesMut::new();
+        data.encode(&mut payload)?;
+        write_record(&mut self.writer, RECORD_INSERT, inserted_at, data.ttl_ms, &payload)
+    }
+
+    /// Append a removal record, buffered until the next [`flush`](Self::flush)
+    pub fn append_remove(&mut self, name: &Name) -> Result<()> {
+        let mut payload = BytesMut::new();
+        name.to_tlv()?.encode(&mut payload);
+        write_record(&mut self.writer, RECORD_REMOVE, SystemTime::now(), 0, &payload)
+    }
+
+    /// Write the buffered records to the file
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Rewrite the log so it contains only `live` entries
+    pub fn compact<'a>(&mut self, live: impl Iterator<Item = &'a StoredData>) -> Result<()> {
+        let tmp_path = self.path.with_extension("compact");
+        {
+            let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+            for entry in live {
+                let mut payload = BytesMut::new();
+                entry.data.encode(&mut payload)?;
+                write_record(&mut tmp, RECORD_INSERT, entry.inserted_at, entry.data.ttl_ms, &payload)?;
+            }
+            tmp.flush()?;
+        }
+
+        fs::rename(&tmp_path, &self.path).map_err(|e| file_error(e, "Failed to replace repo log", &self.path))?;
+
+        let file = OpenOptions::new().append(true).open(&self.path)?;
+        self.writer = BufWriter::new(file);
+
+        debug!("Compacted repo log {}", self.path.display());
+        Ok(())
+    }
+}
+
+/// A change to apply to the log
+#[derive(Debug)]
+enum RepoOp {
+    Insert(Data, SystemTime),
+    Remove(Name),
+
+    /// Rewrite the log with these live entries
+    Compact(Vec<StoredData>),
+}
+
+/// Appends to a [`RepoLog`] from a thread of its own
+///
+/// Changes are applied in the order they are queued, and flushed once the
+/// queue is empty. Dropping the writer waits for the queued ones.
+#[derive(Debug)]
+pub struct RepoWriter {
+    path: PathBuf,
+    sender: Option<mpsc::Sender<RepoOp>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl RepoWriter {
+    /// Start writing to `log`
+    pub fn spawn(log: RepoLog) -> Result<Self> {
+        let path = log.path().to_path_buf();
+        let (sender, receiver) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("udcn-repo".to_string())
+            .spawn(move || write_ops(log, receiver))?;
+        Ok(Self {
+            path,
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Queue an insertion record
+    pub fn insert(&self, data: Data, inserted_at: SystemTime) {
+        self.send(RepoOp::Insert(data, inserted_at));
+    }
+
+    /// Queue a removal record
+    pub fn remove(&self, name: Name) {
+        self.send(RepoOp::Remove(name));
+    }
+
+    /// Queue a rewrite of the log with only the `live` entries
+    pub fn compact(&self, live: Vec<StoredData>) {
+        self.send(RepoOp::Compact(live));
+    }
+
+    fn send(&self, op: RepoOp) {
+        if let Some(sender) = &self.sender {
+            if sender.send(op).is_err() {
+                warn!("Writer of repo {} stopped, dropping a record", self.path.display());
+            }
+        }
+    }
+}
+
+impl Drop for RepoWriter {
+    fn drop(&mut self) {
+        // Closing the queue lets the thread write what is left and return
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Apply queued changes to `log` until the queue is closed
+fn write_ops(mut log: RepoLog, receiver: mpsc::Receiver<RepoOp>) {
+    while let Ok(op) = receiver.recv() {
+        let mut next = Some(op);
+        while let Some(op) = next {
+            let result = match op {
+                RepoOp::Insert(data, inserted_at) => log.append_insert(&data, inserted_at),
+                RepoOp::Remove(name) => log.append_remove(&name),
+                RepoOp::Compact(live) => log.compact(live.iter()),
+            };
+            if let Err(e) = result {
+                warn!("Failed to write to repo {}: {}", log.path().display(), e);
+            }
+            next = receiver.try_recv().ok();
+        }
+        if let Err(e) = log.flush() {
+            warn!("Failed to flush repo {}: {}", log.path().display(), e);
+        }
+    }
+}
+
+/// I/O error naming the file it happened on
+fn file_error(e: io::Error, what: &str, path: &Path) -> Error {
+    Error::Io(io::Error::new(e.kind(), format!("{}: {}: {}", what, path.display(), e)))
+}
+
+/// Write a single log record
+fn write_record<W: Write>(
+    writer: &mut W,
+    record_type: u8,
+    timestamp: SystemTime,
+    ttl_ms: u32,
+    payload: &[u8],
+) -> Result<()> {
+    let millis = timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_millis() as u64;
+
+    let mut header = BytesMut::with_capacity(RECORD_HEADER_SIZE);
+    header.put_u8(record_type);
+    header.put_u64(millis);
+    header.put_u32(ttl_ms);
+    header.put_u32(payload.len() as u32);
+
+    writer.write_all(&header)?;
+    writer.write_all(payload)?;
+    Ok(())
+}
+
+/// Replay a log file into the list of live entries
+fn replay(path: &Path) -> Result<Vec<StoredData>> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+
+    let mut live: HashMap<Name, StoredData> = HashMap::new();
+    let mut buf = &bytes[..];
+
+    while buf.has_remaining() {
+        if buf.remaining() < RECORD_HEADER_SIZE {
+            warn!("Truncated record header at end of repo log {}", path.display());
+            break;
+        }
+        let record_type = buf.get_u8();
+        let millis = buf.get_u64();
+        let ttl_ms = buf.get_u32();
+        let len = buf.get_u32() as usize;
+
+        if buf.remaining() < len {
+            warn!("Truncated record at end of repo log {}", path.display());
+            break;
+        }
+        let payload = &buf[..len];
+        buf.advance(len);
+
+        match record_type {
+            RECORD_INSERT => {
+                let data = Data::decode(payload)?.with_ttl(ttl_ms);
+                let stored = StoredData {
+                    data,
+                    inserted_at: UNIX_EPOCH + Duration::from_millis(millis),
+                };
+                live.insert(stored.data.name.clone(), stored);
+            }
+            RECORD_REMOVE => {
+                let mut name_buf = payload;
+                let element = rust_udcn_common::tlv::TlvElement::decode(&mut name_buf)?;
+                let name = Name::from_tlv(&element)?;
+                live.remove(&name);
+            }
+            other => return Err(Error::Other(format!("Unknown repo record type {}", other))),
+        }
+    }
+
+    Ok(live.into_values().collect())
+}
@@ -0,0 +1,455 @@
+//! Repo insertion, status check and deletion commands.
+//!
+//! A node with a content store can act as a repo under a command prefix,
+//! in the style of repo-ng. Command Interests are named
+//! `<repo-prefix>/<verb>/<parameters>/<timestamp>`, where the verb is
+//! `insert`, `insert-check` or `delete` and the parameters are a
+//! bincode-encoded [`RepoCommand`]. Every command is answered with a Data packet carrying a
+//! bincode-encoded [`RepoStatus`].
+//!
+//! An insert command makes the repo fetch the named Data, or a range of its
+//! segments, from the network and store it. Inserted Data is served from
+//! the content store afterwards, and persists across restarts when the
+//! store is backed by a repo log.
+
+use log::{debug, info, warn};
+use rust_udcn_common::ndn::{Data, Interest, Name, NameComponent};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    cs::ContentStore,
+    error::{FetchError, RepoError},
+    producer::ProducerResponse,
+    transport::NdnQuicTransport,
+};
+
+/// Command verb inserting Data into the repo
+pub const REPO_INSERT: &str = "insert";
+
+/// Command verb checking the progress of an insertion
+pub const REPO_INSERT_CHECK: &str = "insert-check";
+
+/// Command verb deleting Data from the repo
+pub const REPO_DELETE: &str = "delete";
+
+/// Status code of an insertion that was accepted and has started
+pub const STATUS_STARTED: u16 = 100;
+
+/// Status code of a completed command
+pub const STATUS_OK: u16 = 200;
+
+/// Status code of an insertion still fetching Data
+pub const STATUS_IN_PROGRESS: u16 = 300;
+
+/// Status code of a malformed command
+pub const STATUS_MALFORMED: u16 = 403;
+
+/// Status code of an unknown insertion
+pub const STATUS_NOT_FOUND: u16 = 404;
+
+/// Status code of an insertion that failed to fetch its Data
+pub const STATUS_FAILED: u16 = 405;
+
+/// Parameters of a repo command
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoCommand {
+    /// Name of the Data, or the prefix of its segments
+    pub name: Name,
+
+    /// First segment to insert or delete
+    pub start_block: Option<u64>,
+
+    /// Last segment to insert or delete. With only `start_block` set, an
+    /// insertion continues up to the FinalBlockId of the fetched segments.
+    pub end_block: Option<u64>,
+
+    /// Insertion to check, or the ID to give a new insertion
+    pub process_id: Option<u64>,
+}
+
+impl RepoCommand {
+    /// Command for the Data named `name`
+    pub fn new(name: Name) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    /// Restrict the command to the segments `start..=end` of `name`
+    pub fn with_segments(mut self, start: u64, end: Option<u64>) -> Self {
+        self.start_block = Some(start);
+        self.end_block = end;
+        self
+    }
+
+    /// Name of the command Interest for `verb` under `repo_prefix`. A
+    /// timestamp component keeps repeated commands from being answered by
+    /// an earlier reply.
+    pub fn to_interest_name(&self, repo_prefix: &Name, verb: &str) -> Result<Name, RepoError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        let mut name = repo_prefix.clone();
+        name.push(NameComponent::new(verb.as_bytes().to_vec()));
+        name.push(NameComponent::new(bincode::serialize(self)?));
+        name.push(NameComponent::new(timestamp.to_be_bytes().to_vec()));
+        Ok(name)
+    }
+}
+
+/// Reply to a repo command
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoStatus {
+    /// Status code, one of the `STATUS_*` constants
+    pub code: u16,
+
+    /// ID of the insertion the status refers to
+    pub process_id: u64,
+
+    /// Number of Data packets inserted so far
+    pub insert_count: u64,
+
+    /// Number of Data packets deleted
+    pub delete_count: u64,
+}
+
+impl RepoStatus {
+    fn code(code: u16) -> Self {
+        Self {
+            code,
+            ..Default::default()
+        }
+    }
+}
+
+/// Serves repo commands for a content store
+pub struct RepoServer {
+    /// Command prefix of the repo
+    prefix: Name,
+
+    /// Transport the commands arrive on
+    transport: Arc<NdnQuicTransport>,
+}
+
+/// State shared by the command handler and the insertions it starts
+struct RepoState {
+    transport: Arc<NdnQuicTransport>,
+    content_store: Arc<ContentStore>,
+    insertions: Mutex<HashMap<u64, RepoStatus>>,
+    next_process_id: AtomicU64,
+}
+
+impl RepoServer {
+    /// Serve repo commands under `prefix`, storing inserted Data in the
+    /// transport's content store
+    pub async fn start(transport: Arc<NdnQuicTransport>, prefix: Name) -> Result<Self, RepoError> {
+        let content_store = transport.content_store().ok_or(RepoError::NoContentStore)?;
+        if !content_store.is_persistent() {
+            warn!("Repo {} stores Data in memory only", prefix);
+        }
+
+        let state = Arc::new(RepoState {
+            transport: Arc::clone(&transport),
+            content_store,
+            insertions: Mutex::new(HashMap::new()),
+            next_process_id: AtomicU64::new(1),
+        });
+        let command_index = prefix.len();
+        transport
+            .set_interest_handler(prefix.clone(), move |interest: Interest| {
+                let state = Arc::clone(&state);
+                async move {
+                    let status = state.handle(interest.name(), command_index).await;
+                    let content = bincode::serialize(&status).unwrap_or_default();
+                    // Status replies are neither cached here nor fresh downstream
+                    let data = Data::new(interest.name().clone(), content).with_ttl(0).with_freshness_period(0);
+                    ProducerResponse::Data(data)
+                }
+            })
+            .await;
+
+        info!("Serving repo commands under {}", prefix);
+        Ok(Self { prefix, transport })
+    }
+
+    /// Get the command prefix of the repo
+    pub fn prefix(&self) -> &Name {
+        &self.prefix
+    }
+
+    /// Stop serving repo commands. Stored Data stays in the content store.
+    pub async fn stop(self) {
+        self.transport.remove_interest_handler(&self.prefix).await;
+    }
+}
+
+/// Sends commands to a repo
+pub struct RepoClient {
+    /// Transport the commands are sent on
+    transport: Arc<NdnQuicTransport>,
+
+    /// Command prefix of the repo
+    prefix: Name,
+}
+
+impl RepoClient {
+    /// Send commands to the repo serving under `prefix`
+    pub fn new(transport: Arc<NdnQuicTransport>, prefix: Name) -> Self {
+        Self { transport, prefix }
+    }
+
+    /// Ask the repo to fetch and store the Data of `command`
+    pub async fn insert(&self, command: &RepoCommand) -> Result<RepoStatus, RepoError> {
+        self.send(REPO_INSERT, command).await
+    }
+
+    /// Check the progress of the insertion `process_id`
+    pub async fn insert_check(&self, process_id: u64) -> Result<RepoStatus, RepoError> {
+        let command = RepoCommand {
+            process_id: Some(process_id),
+            ..Default::default()
+        };
+        self.send(REPO_INSERT_CHECK, &command).await
+    }
+
+    /// Delete the Data of `command` from the repo
+    pub async fn delete(&self, command: &RepoCommand) -> Result<RepoStatus, RepoError> {
+        self.send(REPO_DELETE, command).await
+    }
+
+    async fn send(&self, verb: &str, command: &RepoCommand) -> Result<RepoStatus, RepoError> {
+        let interest = Interest::new(command.to_interest_name(&self.prefix, verb)?).with_must_be_fresh(true);
+        let data = self.transport.express_interest(interest, None, None).await?;
+        let status: RepoStatus = bincode::deserialize(data.content())?;
+        if status.code == STATUS_MALFORMED {
+            return Err(RepoError::Rejected {
+                verb: verb.to_string(),
+                name: command.name.clone(),
+            });
+        }
+        Ok(status)
+    }
+}
+
+impl RepoState {
+    /// Run the command named `name`, whose verb is at `command_index`
+    async fn handle(self: &Arc<Self>, name: &Name, command_index: usize) -> RepoStatus {
+        let verb = name.get(command_index).map(|c| c.to_string());
+        let command = name
+            .get(command_index + 1)
+            .and_then(|c| bincode::deserialize::<RepoCommand>(c.as_bytes()).ok());
+        let (Some(verb), Some(command)) = (verb, command) else {
+            debug!("Malformed repo command {}", name);
+            return RepoStatus::code(STATUS_MALFORMED);
+        };
+
+        match verb.as_str() {
+            REPO_INSERT => self.insert(command).await,
+            REPO_INSERT_CHECK => self.insert_check(&command).await,
+            REPO_DELETE => self.delete(&command),
+            _ => RepoStatus::code(STATUS_MALFORMED),
+        }
+    }
+
+    /// Start fetching the Data of an insert command
+    async fn insert(self: &Arc<Self>, command: RepoCommand) -> RepoStatus {
+        if matches!((command.start_block, command.end_block), (Some(start), Some(end)) if start > end)
+            || (command.start_block.is_none() && command.end_block.is_some())
+        {
+            return RepoStatus::code(STATUS_MALFORMED);
+        }
+
+        let process_id = command
+            .process_id
+            .unwrap_or_else(|| self.next_process_id.fetch_add(1, Ordering::Relaxed));
+        let status = RepoStatus {
+            code: STATUS_STARTED,
+            process_id,
+            ..Default::default()
+        };
+        {
+            let mut insertions = self.insertions.lock().await;
+            if insertions.contains_key(&process_id) {
+                return RepoStatus::code(STATUS_MALFORMED);
+            }
+            insertions.insert(
+                process_id,
+                RepoStatus {
+                    code: STATUS_IN_PROGRESS,
+                    ..status.clone()
+                },
+            );
+        }
+
+        info!("Inserting {} (process {})", command.name, process_id);
+        let state = Arc::clone(self);
+        tokio::spawn(async move {
+            let code = match state.fetch(&command, process_id).await {
+                Ok(()) => STATUS_OK,
+                Err(e) => {
+                    warn!("Failed to insert {}: {}", command.name, e);
+                    STATUS_FAILED
+                }
+            };
+            if let Some(status) = state.insertions.lock().await.get_mut(&process_id) {
+                status.code = code;
+            }
+        });
+        status
+    }
+
+    /// Fetch and store the Data of an insert command
+    async fn fetch(&self, command: &RepoCommand, process_id: u64) -> Result<(), RepoError> {
+        let Some(start) = command.start_block else {
+            return self.fetch_one(command.name.clone(), process_id).await.map(|_| ());
+        };
+
+        let mut end = command.end_block;
+        let mut segment = start;
+        while end.is_none_or(|end| segment <= end) {
+            let data = self.fetch_one(command.name.with_segment(segment), process_id).await?;
+            if end.is_none() {
+                let last = data.final_block_id.ok_or_else(|| FetchError::NoFinalBlock(command.name.clone()))?;
+                end = Some(last);
+            }
+            segment += 1;
+        }
+        Ok(())
+    }
+
+    async fn fetch_one(&self, name: Name, process_id: u64) -> Result<Data, RepoError> {
+        let data = self
+            .transport
+            .express_interest(Interest::new(name.clone()), None, None)
+            .await
+            .map_err(|e| FetchError::new(name, e))?;
+        self.content_store.insert(data.clone())?;
+        if let Some(status) = self.insertions.lock().await.get_mut(&process_id) {
+            status.insert_count += 1;
+        }
+        Ok(data)
+    }
+
+    async fn insert_check(&self, command: &RepoCommand) -> RepoStatus {
+        let Some(process_id) = command.process_id else {
+            return RepoStatus::code(STATUS_MALFORMED);
+        };
+        self.insertions
+            .lock()
+            .await
+            .get(&process_id)
+            .cloned()
+            .unwrap_or_else(|| RepoStatus::code(STATUS_NOT_FOUND))
+    }
+
+    /// Delete the Data named by a delete command, or the segments in its
+    /// range. Without a range, everything under the name is deleted.
+    fn delete(&self, command: &RepoCommand) -> RepoStatus {
+        let names: Vec<Name> = match (command.start_block, command.end_block) {
+            (Some(start), Some(end)) => (start..=end).map(|segment| command.name.with_segment(segment)).collect(),
+            (Some(_), None) | (None, Some(_)) => return RepoStatus::code(STATUS_MALFORMED),
+            (None, None) => self
+                .content_store
+                .enumerate(&command.name)
+                .into_iter()
+                .map(|entry| entry.data.name().clone())
+                .collect(),
+        };
+
+        let mut delete_count = 0;
+        for name in names {
+            match self.content_store.remove(&name) {
+                Ok(true) => delete_count += 1,
+                Ok(false) => {}
+                Err(e) => warn!("Failed to delete {} from the repo: {}", name, e),
+            }
+        }
+        debug!("Deleted {} Data packets under {}", delete_count, command.name);
+        RepoStatus {
+            code: STATUS_OK,
+            delete_count,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cs::ContentStoreLimits, link_face::link_face_pair, TransportConfig};
+    use rust_udcn_common::metrics::UdcnMetrics;
+    use std::time::Duration;
+
+    #[test]
+    fn test_command_name() {
+        let command = RepoCommand::new(Name::from_string("/video/a").unwrap()).with_segments(0, Some(9));
+        let repo = Name::from_string("/repo").unwrap();
+        let name = command.to_interest_name(&repo, REPO_INSERT).unwrap();
+        assert_eq!(name.len(), 4);
+        assert_eq!(name.get(1).unwrap().to_string(), REPO_INSERT);
+        let decoded: RepoCommand = bincode::deserialize(name.get(2).unwrap().as_bytes()).unwrap();
+        assert_eq!(decoded, command);
+    }
+
+    #[tokio::test]
+    async fn test_insert_check_and_delete() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (to_producer, to_repo) = link_face_pair("to-producer", "to-repo", &metrics);
+        let repo = Arc::new(
+            NdnQuicTransport::new(TransportConfig {
+                content_store: Some(ContentStoreLimits::default()),
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+        );
+        let producer = Arc::new(NdnQuicTransport::new(TransportConfig::default()).await.unwrap());
+        repo.add_face(Arc::new(to_producer)).await;
+        producer.add_face(Arc::new(to_repo)).await;
+
+        let object = Name::from_string("/video/a").unwrap();
+        producer
+            .set_interest_handler(object.clone(), |interest: Interest| async move {
+                Data::new(interest.name().clone(), "segment").with_final_block_id(2).into()
+            })
+            .await;
+
+        let prefix = Name::from_string("/repo").unwrap();
+        let _server = RepoServer::start(Arc::clone(&repo), prefix.clone()).await.unwrap();
+        let client = RepoClient::new(producer, prefix);
+
+        let started = client.insert(&RepoCommand::new(object.clone()).with_segments(0, None)).await.unwrap();
+        assert_eq!(started.code, STATUS_STARTED);
+
+        let mut status = client.insert_check(started.process_id).await.unwrap();
+        for _ in 0..50 {
+            if status.code != STATUS_IN_PROGRESS {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            status = client.insert_check(started.process_id).await.unwrap();
+        }
+        assert_eq!(status.code, STATUS_OK);
+        assert_eq!(status.insert_count, 3);
+
+        // Only the inserted segments are stored, not the command replies
+        let cs = repo.content_store().unwrap();
+        assert_eq!(cs.len(), 3);
+        assert!(cs.get(&object.with_segment(2)).is_some());
+
+        let deleted = client.delete(&RepoCommand::new(object)).await.unwrap();
+        assert_eq!(deleted.delete_count, 3);
+        assert!(cs.is_empty());
+    }
+}
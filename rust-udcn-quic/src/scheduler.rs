@@ -0,0 +1,352 @@
+//! Fair scheduling of the packets the forwarder sends.
+//!
+//! Every face the forwarder sends on gets an output queue, and one task
+//! hands out turns by deficit round-robin: each round, a backlogged face
+//! may send up to a quantum of bytes plus what it left unused in the
+//! previous rounds. A face flooded with traffic then gets its share of the
+//! sending and no more, whatever the sizes of its packets, and the others
+//! are not starved behind it.
+//!
+//! The packets of a turn are sent on a task of their own, and a face gets
+//! no further turn until they are out, so a face that blocks only holds up
+//! its own queue.
+
+use log::{debug, warn};
+use rust_udcn_common::metrics::{Labels, UdcnMetrics};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use crate::{error::FaceError, ndn_face::NdnFace, packet::NdnPacket, send_queue::SendQueueFull};
+
+/// Default number of bytes a face may send per round
+pub const DEFAULT_QUANTUM_BYTES: usize = 9000;
+
+/// Default number of packets that may wait in the output queue of a face
+pub const DEFAULT_OUTPUT_QUEUE_CAPACITY: usize = 1024;
+
+/// Options of a [`FaceScheduler`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchedulerOptions {
+    /// Bytes a backlogged face may send per round
+    pub quantum_bytes: usize,
+
+    /// Packets that may wait for a face before further ones are dropped
+    pub queue_capacity: usize,
+}
+
+impl Default for SchedulerOptions {
+    fn default() -> Self {
+        Self {
+            quantum_bytes: DEFAULT_QUANTUM_BYTES,
+            queue_capacity: DEFAULT_OUTPUT_QUEUE_CAPACITY,
+        }
+    }
+}
+
+/// Packets waiting to be sent on a face
+struct OutputQueue {
+    face: Arc<dyn NdnFace>,
+
+    /// The packets, with their encoded sizes
+    packets: VecDeque<(NdnPacket, usize)>,
+
+    /// Bytes the face may still send before its turn ends
+    deficit: usize,
+
+    /// Whether the packets of its last turn are still being sent
+    sending: bool,
+}
+
+#[derive(Default)]
+struct State {
+    queues: HashMap<String, OutputQueue>,
+
+    /// Faces with packets waiting, in the order of their next turn
+    active: VecDeque<String>,
+}
+
+/// Output queues of the faces, drained by deficit round-robin
+pub struct FaceScheduler {
+    options: SchedulerOptions,
+    state: Mutex<State>,
+
+    /// Woken when packets are queued
+    ready: Notify,
+
+    metrics: Arc<UdcnMetrics>,
+}
+
+impl std::fmt::Debug for FaceScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FaceScheduler")
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FaceScheduler {
+    /// Create a scheduler reporting its queue depths into `metrics`
+    pub fn new(options: SchedulerOptions, metrics: Arc<UdcnMetrics>) -> Self {
+        Self {
+            options,
+            state: Mutex::new(State::default()),
+            ready: Notify::new(),
+            metrics,
+        }
+    }
+
+    /// Queue a packet to be sent on `face`
+    ///
+    /// Fails with [`FaceError::QueueFull`] once the queue of the face holds
+    /// as many packets as the options allow.
+    pub fn enqueue(&self, face: &Arc<dyn NdnFace>, packet: NdnPacket) -> Result<(), FaceError> {
+        let size = packet.size()?;
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        let queue = state.queues.entry(face.id().to_string()).or_insert_with(|| OutputQueue {
+            face: Arc::clone(face),
+            packets: VecDeque::new(),
+            deficit: 0,
+            sending: false,
+        });
+        if queue.packets.len() >= self.options.queue_capacity {
+            self.metrics.forwarder_queue_drops.add_labeled(&Labels::new().face(face.id()), 1);
+            return Err(SendQueueFull {
+                face_id: face.id().to_string(),
+                queued: queue.packets.len(),
+            }
+            .into());
+        }
+
+        // A face that is still sending rejoins the round when it is done
+        if queue.packets.is_empty() && !queue.sending {
+            state.active.push_back(face.id().to_string());
+        }
+        queue.packets.push_back((packet, size));
+        self.record_depth(state, face.id());
+        drop(guard);
+
+        self.ready.notify_one();
+        Ok(())
+    }
+
+    /// Number of packets waiting for a face
+    pub fn queue_depth(&self, face_id: &str) -> usize {
+        self.lock().queues.get(face_id).map_or(0, |queue| queue.packets.len())
+    }
+
+    /// Drop the queue of a closed face
+    pub fn remove_face(&self, face_id: &str) {
+        let mut state = self.lock();
+        if let Some(queue) = state.queues.remove(face_id) {
+            debug!("Dropping {} packets queued for closed face {}", queue.packets.len(), face_id);
+        }
+        state.active.retain(|id| id != face_id);
+        self.record_depth(&state, face_id);
+    }
+
+    /// Send the queued packets, until `token` is cancelled
+    ///
+    /// Packets that fail to go out are logged and dropped: their senders
+    /// were told of success when they were queued.
+    pub async fn run(self: Arc<Self>, token: CancellationToken) {
+        loop {
+            while let Some((face, packets)) = self.next_batch() {
+                let scheduler = Arc::clone(&self);
+                tokio::spawn(async move {
+                    for packet in packets {
+                        if let Err(e) = send_packet(&face, packet).await {
+                            warn!("Failed to send queued packet on face {}: {}", face.id(), e);
+                        }
+                    }
+                    scheduler.finish_batch(face.id());
+                });
+            }
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = self.ready.notified() => {}
+            }
+        }
+    }
+
+    /// Let a face whose packets went out take turns again
+    fn finish_batch(&self, face_id: &str) {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        let Some(queue) = state.queues.get_mut(face_id) else {
+            return;
+        };
+        queue.sending = false;
+        if !queue.packets.is_empty() {
+            state.active.push_back(face_id.to_string());
+            drop(guard);
+            self.ready.notify_one();
+        }
+    }
+
+    /// Take the packets of the next face whose turn lets it send some
+    ///
+    /// The face leaves the round until [`Self::finish_batch`] is called.
+    fn next_batch(&self) -> Option<(Arc<dyn NdnFace>, Vec<NdnPacket>)> {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        loop {
+            let face_id = state.active.pop_front()?;
+            let Some(queue) = state.queues.get_mut(&face_id) else {
+                continue;
+            };
+
+            queue.deficit += self.options.quantum_bytes;
+            let mut batch = Vec::new();
+            while let Some((_, size)) = queue.packets.front() {
+                if *size > queue.deficit {
+                    break;
+                }
+                queue.deficit -= size;
+                batch.extend(queue.packets.pop_front().map(|(packet, _)| packet));
+            }
+            let face = Arc::clone(&queue.face);
+            // An idle face saves no credit for later bursts
+            if queue.packets.is_empty() {
+                queue.deficit = 0;
+            }
+
+            if batch.is_empty() {
+                state.active.push_back(face_id);
+                continue;
+            }
+            queue.sending = true;
+            self.record_depth(state, &face_id);
+            return Some((face, batch));
+        }
+    }
+
+    /// Update the queue depth gauges after the queue of `face_id` changed
+    fn record_depth(&self, state: &State, face_id: &str) {
+        let depth = state.queues.get(face_id).map_or(0, |queue| queue.packets.len());
+        let total = state.queues.values().map(|queue| queue.packets.len()).sum::<usize>();
+        self.metrics.forwarder_queue_depth.with(&Labels::new().face(face_id)).set(depth as u64);
+        self.metrics.forwarder_queue_depth.set(total as u64);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("scheduler lock poisoned")
+    }
+}
+
+/// Send a packet on a face, by type
+pub(crate) async fn send_packet(face: &Arc<dyn NdnFace>, packet: NdnPacket) -> Result<(), FaceError> {
+    match packet {
+        NdnPacket::Interest(interest) => face.send_interest(interest).await,
+        NdnPacket::Data(data) => face.send_data(data).await,
+        NdnPacket::Nack(nack) => face.send_nack_packet(nack).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{face::FaceEvent, link::StreamLink, link_face::{link_face_pair, LinkFace}};
+    use rust_udcn_common::ndn::{Data, Interest, Name};
+    use std::time::Duration;
+
+    fn name(s: &str) -> Name {
+        Name::from_string(s).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_busy_face_does_not_starve_others() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (busy_app, busy) = link_face_pair("busy-app", "busy", &metrics);
+        let (quiet_app, quiet) = link_face_pair("quiet-app", "quiet", &metrics);
+        let busy: Arc<dyn NdnFace> = Arc::new(busy);
+        let quiet: Arc<dyn NdnFace> = Arc::new(quiet);
+
+        let options = SchedulerOptions {
+            quantum_bytes: 200,
+            queue_capacity: 8,
+        };
+        let scheduler = Arc::new(FaceScheduler::new(options, Arc::clone(&metrics)));
+        for i in 0..8 {
+            let data = Data::new(name(&format!("/busy/{}", i)), vec![0u8; 500]);
+            scheduler.enqueue(&busy, NdnPacket::Data(data)).unwrap();
+        }
+        assert!(matches!(
+            scheduler.enqueue(&busy, NdnPacket::Interest(Interest::new(name("/busy/more")))),
+            Err(FaceError::QueueFull(_))
+        ));
+        assert_eq!(metrics.forwarder_queue_drops.value(), 1);
+        for i in 0..2 {
+            scheduler
+                .enqueue(&quiet, NdnPacket::Interest(Interest::new(name(&format!("/quiet/{}", i)))))
+                .unwrap();
+        }
+        assert_eq!(metrics.forwarder_queue_depth.value(), 10);
+
+        // The large Data need several rounds of credit, the small Interests
+        // go out in the first one
+        let (face, packets) = scheduler.next_batch().unwrap();
+        assert_eq!((face.id(), packets.len()), ("quiet", 2));
+        let (face, packets) = scheduler.next_batch().unwrap();
+        assert_eq!((face.id(), packets.len()), ("busy", 1));
+        assert_eq!(scheduler.queue_depth("busy"), 7);
+        assert_eq!(metrics.forwarder_queue_depth.with(&Labels::new().face("busy")).value(), 7);
+        // A face takes no further turn until its batch is out
+        assert!(scheduler.next_batch().is_none());
+        scheduler.finish_batch("quiet");
+        scheduler.finish_batch("busy");
+
+        let token = CancellationToken::new();
+        let runner = tokio::spawn(Arc::clone(&scheduler).run(token.clone()));
+        scheduler
+            .enqueue(&quiet, NdnPacket::Interest(Interest::new(name("/quiet/2"))))
+            .unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(1), quiet_app.next_event()).await.unwrap();
+        assert!(matches!(received, Some(FaceEvent::InterestReceived(i)) if i.name() == &name("/quiet/2")));
+        for _ in 0..7 {
+            let received = tokio::time::timeout(Duration::from_secs(1), busy_app.next_event()).await.unwrap();
+            assert!(matches!(received, Some(FaceEvent::DataReceived(_))));
+        }
+        assert_eq!(metrics.forwarder_queue_depth.value(), 0);
+
+        token.cancel();
+        runner.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocked_face_does_not_stall_others() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        // Nobody reads the other end of the blocked face, which fills up
+        let (blocked_end, _unread) = tokio::io::duplex(64);
+        let blocked: Arc<dyn NdnFace> =
+            Arc::new(LinkFace::new("blocked".to_string(), Arc::new(StreamLink::new(blocked_end)), Arc::clone(&metrics)));
+        let (open_app, open) = link_face_pair("open-app", "open", &metrics);
+        let open: Arc<dyn NdnFace> = Arc::new(open);
+
+        let scheduler = Arc::new(FaceScheduler::new(SchedulerOptions::default(), Arc::clone(&metrics)));
+        let data = Data::new(name("/blocked/0"), vec![0u8; 4096]);
+        scheduler.enqueue(&blocked, NdnPacket::Data(data)).unwrap();
+        scheduler
+            .enqueue(&open, NdnPacket::Interest(Interest::new(name("/open/0"))))
+            .unwrap();
+
+        let token = CancellationToken::new();
+        let runner = tokio::spawn(Arc::clone(&scheduler).run(token.clone()));
+        let received = tokio::time::timeout(Duration::from_secs(1), open_app.next_event()).await.unwrap();
+        assert!(matches!(received, Some(FaceEvent::InterestReceived(i)) if i.name() == &name("/open/0")));
+
+        // The open face keeps getting turns while the blocked one waits
+        scheduler
+            .enqueue(&open, NdnPacket::Interest(Interest::new(name("/open/1"))))
+            .unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(1), open_app.next_event()).await.unwrap();
+        assert!(matches!(received, Some(FaceEvent::InterestReceived(i)) if i.name() == &name("/open/1")));
+
+        token.cancel();
+        runner.await.unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+//! Bounded, prioritized admission of outgoing streams.
+//!
+//! Every packet sent in stream mode opens a new QUIC stream. A face's send
+//! queue caps how many of those streams are in flight at once; further sends
+//! wait in a bounded queue, where Data is admitted before Interests and
+//! Interests before control traffic. Once the queue is full, sends fail
+//! immediately with [`SendQueueFull`] instead of piling up.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+/// Default number of streams a face may have open for sending at once
+pub const DEFAULT_MAX_INFLIGHT_STREAMS: usize = 64;
+
+/// Default number of sends that may wait for a stream
+pub const DEFAULT_SEND_QUEUE_CAPACITY: usize = 1024;
+
+/// Priority of an outgoing packet, highest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SendPriority {
+    /// Data packets, which complete exchanges already in progress
+    Data = 0,
+
+    /// Interests, which start new exchanges
+    Interest = 1,
+
+    /// Nacks and management commands
+    Control = 2,
+}
+
+const PRIORITY_LEVELS: usize = 3;
+
+/// Error returned when a face cannot accept more outgoing packets
+#[derive(Debug, Clone, Error)]
+#[error("Send queue of face {face_id} is full ({queued} packets waiting)")]
+pub struct SendQueueFull {
+    /// Face whose queue is full
+    pub face_id: String,
+
+    /// Number of sends waiting for a stream
+    pub queued: usize,
+}
+
+#[derive(Debug)]
+struct State {
+    inflight: usize,
+    max_inflight: usize,
+    capacity: usize,
+    waiting: [VecDeque<oneshot::Sender<()>>; PRIORITY_LEVELS],
+}
+
+impl State {
+    fn queued(&self) -> usize {
+        self.waiting.iter().map(VecDeque::len).sum()
+    }
+}
+
+/// Per-face limit on in-flight outgoing streams
+#[derive(Debug)]
+pub(crate) struct SendQueue {
+    face_id: String,
+    state: Mutex<State>,
+}
+
+impl SendQueue {
+    pub(crate) fn new(face_id: String, max_inflight: usize, capacity: usize) -> Self {
+        Self {
+            face_id,
+            state: Mutex::new(State {
+                inflight: 0,
+                max_inflight: max_inflight.max(1),
+                capacity,
+                waiting: Default::default(),
+            }),
+        }
+    }
+
+    /// Change the limits. Sends already admitted are not affected.
+    pub(crate) fn set_limits(&self, max_inflight: usize, capacity: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.max_inflight = max_inflight.max(1);
+        state.capacity = capacity;
+        drop(state);
+        // A higher limit may admit waiting sends right away
+        while self.grant_next() {}
+    }
+
+    /// Number of streams currently in flight
+    pub(crate) fn inflight(&self) -> usize {
+        self.state.lock().unwrap().inflight
+    }
+
+    /// Number of sends waiting for a stream
+    pub(crate) fn queued(&self) -> usize {
+        self.state.lock().unwrap().queued()
+    }
+
+    /// Wait for permission to open a stream
+    pub(crate) async fn acquire(self: &Arc<Self>, priority: SendPriority) -> Result<SendPermit, SendQueueFull> {
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+            if state.inflight < state.max_inflight && state.queued() == 0 {
+                state.inflight += 1;
+                return Ok(SendPermit { queue: Arc::clone(self) });
+            }
+
+            let queued = state.queued();
+            if queued >= state.capacity {
+                return Err(SendQueueFull {
+                    face_id: self.face_id.clone(),
+                    queued,
+                });
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            state.waiting[priority as usize].push_back(sender);
+            receiver
+        };
+
+        let mut waiter = Waiter {
+            queue: self,
+            receiver: Some(receiver),
+        };
+        let granted = waiter.receiver.as_mut().expect("receiver is set").await;
+        waiter.receiver = None;
+
+        match granted {
+            Ok(()) => Ok(SendPermit { queue: Arc::clone(self) }),
+            // The queue never drops a sender without granting it
+            Err(_) => unreachable!("send queue dropped a waiter"),
+        }
+    }
+
+    /// Hand a free slot to the highest-priority waiter. Returns false if
+    /// there was no free slot or no one was waiting.
+    fn grant_next(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.inflight >= state.max_inflight {
+            return false;
+        }
+        for level in 0..PRIORITY_LEVELS {
+            while let Some(sender) = state.waiting[level].pop_front() {
+                // Skip waiters that gave up
+                if sender.send(()).is_ok() {
+                    state.inflight += 1;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn release(&self) {
+        self.state.lock().unwrap().inflight -= 1;
+        self.grant_next();
+    }
+}
+
+/// Releases a granted slot if the waiting send is cancelled after the grant
+struct Waiter<'a> {
+    queue: &'a SendQueue,
+    receiver: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if let Some(mut receiver) = self.receiver.take() {
+            receiver.close();
+            if receiver.try_recv().is_ok() {
+                self.queue.release();
+            }
+        }
+    }
+}
+
+/// Permission to have one stream in flight, returned to the queue on drop
+#[derive(Debug)]
+pub(crate) struct SendPermit {
+    queue: Arc<SendQueue>,
+}
+
+impl Drop for SendPermit {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_priority_order_and_backpressure() {
+        let queue = Arc::new(SendQueue::new("test".to_string(), 1, 2));
+        let permit = queue.acquire(SendPriority::Interest).await.unwrap();
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        for priority in [SendPriority::Control, SendPriority::Data] {
+            let queue = Arc::clone(&queue);
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let _permit = queue.acquire(priority).await.unwrap();
+                order_tx.send(priority).unwrap();
+            });
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(queue.queued(), 2);
+
+        // The queue is full
+        let err = queue.acquire(SendPriority::Data).await.unwrap_err();
+        assert_eq!(err.queued, 2);
+
+        drop(permit);
+        assert_eq!(order_rx.recv().await, Some(SendPriority::Data));
+        assert_eq!(order_rx.recv().await, Some(SendPriority::Control));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_releases_slot() {
+        let queue = Arc::new(SendQueue::new("test".to_string(), 1, 4));
+        let permit = queue.acquire(SendPriority::Data).await.unwrap();
+
+        let waiting = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move { queue.acquire(SendPriority::Data).await.map(|_| ()) }
+        });
+        tokio::task::yield_now().await;
+        waiting.abort();
+        let _ = waiting.await;
+
+        drop(permit);
+        assert_eq!(queue.inflight(), 0);
+        assert!(queue.acquire(SendPriority::Data).await.is_ok());
+    }
+}
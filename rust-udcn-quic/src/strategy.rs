@@ -0,0 +1,503 @@
+//! Forwarding strategies.
+//!
+//! A strategy decides which of the next hops of a FIB entry an Interest is
+//! forwarded to. Strategies are chosen per name prefix, and are told about
+//! returning Data and upstream failures so they can adapt.
+
+use rust_udcn_common::ndn::{Interest, Name};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::forwarder::NextHop;
+
+/// Decides where Interests are forwarded
+pub trait Strategy: Debug + Send + Sync {
+    /// Name of the strategy
+    fn name(&self) -> &'static str;
+
+    /// Choose the faces to forward an Interest to.
+    ///
+    /// `next_hops` holds the usable next hops of the matching FIB entry,
+    /// sorted by increasing cost and excluding the incoming face.
+    fn select_next_hops(&self, prefix: &Name, interest: &Interest, next_hops: &[NextHop]) -> Vec<String>;
+
+    /// Called when Data returns from `face_id` after `rtt`
+    fn on_data(&self, _prefix: &Name, _face_id: &str, _rtt: Duration) {}
+
+    /// Called when `face_id` answered with a Nack or did not answer in time
+    fn on_failure(&self, _prefix: &Name, _face_id: &str) {}
+
+    /// Called when the route of `prefix` toward `face_id` is removed
+    fn on_next_hop_removed(&self, _prefix: &Name, _face_id: &str) {}
+
+    /// Called when `face_id` closed, to forget what was learned about it
+    fn on_face_removed(&self, _face_id: &str) {}
+
+    /// Whether Interests without a FIB match are flooded to every face
+    /// instead of being left to local applications
+    fn floods_unmatched(&self) -> bool {
+        false
+    }
+
+    /// Route to install toward `face_id` when Data named `name` returns
+    /// from it, as (prefix, lifetime)
+    fn learn_route(&self, _name: &Name, _face_id: &str) -> Option<(Name, Duration)> {
+        None
+    }
+}
+
+/// Name of the default strategy
+pub const DEFAULT_STRATEGY: &str = BestRouteStrategy::NAME;
+
+/// Create a strategy from its name
+pub fn strategy_by_name(name: &str) -> Option<Arc<dyn Strategy>> {
+    match name {
+        BestRouteStrategy::NAME => Some(Arc::new(BestRouteStrategy)),
+        MulticastStrategy::NAME => Some(Arc::new(MulticastStrategy)),
+        LoadBalanceStrategy::NAME => Some(Arc::new(LoadBalanceStrategy::default())),
+        AsfStrategy::NAME => Some(Arc::new(AsfStrategy::default())),
+        SelfLearningStrategy::NAME => Some(Arc::new(SelfLearningStrategy::default())),
+        _ => None,
+    }
+}
+
+/// Forward to the lowest-cost next hop
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BestRouteStrategy;
+
+impl BestRouteStrategy {
+    /// Name used to select this strategy
+    pub const NAME: &'static str = "best-route";
+}
+
+impl Strategy for BestRouteStrategy {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn select_next_hops(&self, _prefix: &Name, _interest: &Interest, next_hops: &[NextHop]) -> Vec<String> {
+        next_hops.iter().take(1).map(|hop| hop.face_id.clone()).collect()
+    }
+}
+
+/// Forward to every next hop
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MulticastStrategy;
+
+impl MulticastStrategy {
+    /// Name used to select this strategy
+    pub const NAME: &'static str = "multicast";
+}
+
+impl Strategy for MulticastStrategy {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn select_next_hops(&self, _prefix: &Name, _interest: &Interest, next_hops: &[NextHop]) -> Vec<String> {
+        next_hops.iter().map(|hop| hop.face_id.clone()).collect()
+    }
+}
+
+/// Spread Interests over all next hops in round-robin order
+#[derive(Debug, Default)]
+pub struct LoadBalanceStrategy {
+    next: AtomicUsize,
+}
+
+impl LoadBalanceStrategy {
+    /// Name used to select this strategy
+    pub const NAME: &'static str = "load-balance";
+}
+
+impl Strategy for LoadBalanceStrategy {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn select_next_hops(&self, _prefix: &Name, _interest: &Interest, next_hops: &[NextHop]) -> Vec<String> {
+        if next_hops.is_empty() {
+            return Vec::new();
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % next_hops.len();
+        vec![next_hops[index].face_id.clone()]
+    }
+}
+
+/// Default time after which ASF forgets the measurements of a face that
+/// saw no traffic under a prefix
+pub const DEFAULT_MEASUREMENT_LIFETIME: Duration = Duration::from_secs(300);
+
+/// Measurements of one face under one prefix
+#[derive(Debug, Clone)]
+struct FaceMeasurement {
+    srtt: Option<Duration>,
+    failures: u32,
+
+    /// When Data or a failure was last recorded
+    updated: Instant,
+}
+
+impl FaceMeasurement {
+    fn new(now: Instant) -> Self {
+        Self {
+            srtt: None,
+            failures: 0,
+            updated: now,
+        }
+    }
+}
+
+/// Measurements of an ASF strategy, by prefix and face
+#[derive(Debug)]
+struct Measurements {
+    entries: HashMap<(Name, String), FaceMeasurement>,
+
+    /// When measurements were last swept for age
+    swept: Instant,
+}
+
+/// Adaptive SRTT-based forwarding (ASF).
+///
+/// Interests go to the face with the best measured RTT. Every
+/// `probing_interval` Interests an extra copy probes another next hop so
+/// that measurements of alternative paths stay current.
+#[derive(Debug)]
+pub struct AsfStrategy {
+    probing_interval: u64,
+    max_failures: u32,
+    measurement_lifetime: Duration,
+    counter: AtomicU64,
+    measurements: Mutex<Measurements>,
+}
+
+impl Default for AsfStrategy {
+    fn default() -> Self {
+        Self::new(16, 3)
+    }
+}
+
+impl AsfStrategy {
+    /// Name used to select this strategy
+    pub const NAME: &'static str = "asf";
+
+    /// Create an ASF strategy that probes every `probing_interval` Interests
+    /// and stops preferring a face after `max_failures` consecutive failures
+    pub fn new(probing_interval: u64, max_failures: u32) -> Self {
+        Self {
+            probing_interval: probing_interval.max(1),
+            max_failures,
+            measurement_lifetime: DEFAULT_MEASUREMENT_LIFETIME,
+            counter: AtomicU64::new(0),
+            measurements: Mutex::new(Measurements {
+                entries: HashMap::new(),
+                swept: Instant::now(),
+            }),
+        }
+    }
+
+    /// Forget the measurements of a face once nothing was recorded for it
+    /// under a prefix for `lifetime`
+    pub fn with_measurement_lifetime(mut self, lifetime: Duration) -> Self {
+        self.measurement_lifetime = lifetime;
+        self
+    }
+
+    /// Smoothed RTT measured for a face under a prefix
+    pub fn srtt(&self, prefix: &Name, face_id: &str) -> Option<Duration> {
+        let measurements = self.measurements.lock().unwrap();
+        measurements
+            .entries
+            .get(&(prefix.clone(), face_id.to_string()))
+            .and_then(|m| m.srtt)
+    }
+
+    /// Number of (prefix, face) pairs with measurements
+    pub fn measurement_count(&self) -> usize {
+        self.measurements.lock().unwrap().entries.len()
+    }
+
+    /// Update the measurement of a face under a prefix, sweeping out the
+    /// stale ones at most once per lifetime
+    fn record(&self, prefix: &Name, face_id: &str, update: impl FnOnce(&mut FaceMeasurement)) {
+        let now = Instant::now();
+        let mut measurements = self.measurements.lock().unwrap();
+        if now.duration_since(measurements.swept) >= self.measurement_lifetime {
+            let lifetime = self.measurement_lifetime;
+            measurements
+                .entries
+                .retain(|_, m| now.duration_since(m.updated) < lifetime);
+            measurements.swept = now;
+        }
+        let m = measurements
+            .entries
+            .entry((prefix.clone(), face_id.to_string()))
+            .or_insert_with(|| FaceMeasurement::new(now));
+        update(m);
+        m.updated = now;
+    }
+}
+
+impl Strategy for AsfStrategy {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn select_next_hops(&self, prefix: &Name, _interest: &Interest, next_hops: &[NextHop]) -> Vec<String> {
+        if next_hops.is_empty() {
+            return Vec::new();
+        }
+
+        let measurements = self.measurements.lock().unwrap();
+        let measurement = |hop: &NextHop| {
+            let m = measurements.entries.get(&(prefix.clone(), hop.face_id.clone()));
+            let srtt = m.and_then(|m| m.srtt);
+            // Faces that keep failing rank last, unmeasured faces after measured ones
+            let failing = m.is_some_and(|m| m.failures >= self.max_failures);
+            (failing, srtt.is_none(), srtt.unwrap_or(Duration::MAX), hop.cost)
+        };
+
+        let mut ranked: Vec<&NextHop> = next_hops.iter().collect();
+        ranked.sort_by_key(|hop| measurement(hop));
+
+        let mut selected = vec![ranked[0].face_id.clone()];
+
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        if ranked.len() > 1 && count.is_multiple_of(self.probing_interval) {
+            // Probe the alternatives in turn
+            let probe = 1 + (count / self.probing_interval) as usize % (ranked.len() - 1);
+            selected.push(ranked[probe].face_id.clone());
+        }
+
+        selected
+    }
+
+    fn on_data(&self, prefix: &Name, face_id: &str, rtt: Duration) {
+        self.record(prefix, face_id, |m| {
+            m.srtt = Some(match m.srtt {
+                Some(srtt) => srtt.mul_f64(0.875) + rtt.mul_f64(0.125),
+                None => rtt,
+            });
+            m.failures = 0;
+        });
+    }
+
+    fn on_failure(&self, prefix: &Name, face_id: &str) {
+        self.record(prefix, face_id, |m| m.failures = m.failures.saturating_add(1));
+    }
+
+    fn on_next_hop_removed(&self, prefix: &Name, face_id: &str) {
+        let mut measurements = self.measurements.lock().unwrap();
+        measurements.entries.remove(&(prefix.clone(), face_id.to_string()));
+    }
+
+    fn on_face_removed(&self, face_id: &str) {
+        let mut measurements = self.measurements.lock().unwrap();
+        measurements.entries.retain(|(_, face), _| face != face_id);
+    }
+}
+
+/// Default lifetime of routes learned by [`SelfLearningStrategy`]
+pub const DEFAULT_LEARNED_ROUTE_LIFETIME: Duration = Duration::from_secs(300);
+
+/// Self-learning forwarding.
+///
+/// Interests without a route are flooded to every face. When Data comes
+/// back, a route for the Data name without its last component is learned
+/// toward the face that answered, and later Interests follow it until it
+/// has gone unused for the route lifetime.
+#[derive(Debug, Clone, Copy)]
+pub struct SelfLearningStrategy {
+    route_lifetime: Duration,
+}
+
+impl Default for SelfLearningStrategy {
+    fn default() -> Self {
+        Self::new(DEFAULT_LEARNED_ROUTE_LIFETIME)
+    }
+}
+
+impl SelfLearningStrategy {
+    /// Name used to select this strategy
+    pub const NAME: &'static str = "self-learning";
+
+    /// Create a strategy whose learned routes live for `route_lifetime`
+    /// after they were last used
+    pub fn new(route_lifetime: Duration) -> Self {
+        Self { route_lifetime }
+    }
+}
+
+impl Strategy for SelfLearningStrategy {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn select_next_hops(&self, _prefix: &Name, _interest: &Interest, next_hops: &[NextHop]) -> Vec<String> {
+        next_hops.iter().take(1).map(|hop| hop.face_id.clone()).collect()
+    }
+
+    fn floods_unmatched(&self) -> bool {
+        true
+    }
+
+    fn learn_route(&self, name: &Name, _face_id: &str) -> Option<(Name, Duration)> {
+        // Never learn a default route from a single-component name
+        let prefix = name.prefix(name.len().saturating_sub(1).max(1));
+        Some((prefix, self.route_lifetime))
+    }
+}
+
+/// Strategy choice table: which strategy governs which prefix
+#[derive(Debug)]
+pub(crate) struct StrategyChoice {
+    choices: HashMap<Name, Arc<dyn Strategy>>,
+}
+
+impl Default for StrategyChoice {
+    fn default() -> Self {
+        let mut choices: HashMap<Name, Arc<dyn Strategy>> = HashMap::new();
+        choices.insert(Name::new(), Arc::new(BestRouteStrategy));
+        Self { choices }
+    }
+}
+
+impl StrategyChoice {
+    pub(crate) fn set(&mut self, prefix: Name, strategy: Arc<dyn Strategy>) {
+        self.choices.insert(prefix, strategy);
+    }
+
+    /// Remove the choice for a prefix. The root choice cannot be removed.
+    pub(crate) fn unset(&mut self, prefix: &Name) -> bool {
+        !prefix.is_empty() && self.choices.remove(prefix).is_some()
+    }
+
+    /// Strategy governing `name` (longest prefix match)
+    pub(crate) fn find(&self, name: &Name) -> Arc<dyn Strategy> {
+        (0..=name.len())
+            .rev()
+            .find_map(|len| self.choices.get(&name.prefix(len)))
+            .map(Arc::clone)
+            .unwrap_or_else(|| Arc::new(BestRouteStrategy))
+    }
+
+    /// Every strategy in the table
+    pub(crate) fn strategies(&self) -> impl Iterator<Item = &Arc<dyn Strategy>> {
+        self.choices.values()
+    }
+
+    pub(crate) fn list(&self) -> Vec<(Name, &'static str)> {
+        self.choices
+            .iter()
+            .map(|(prefix, strategy)| (prefix.clone(), strategy.name()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hops(ids: &[&str]) -> Vec<NextHop> {
+        ids.iter()
+            .enumerate()
+            .map(|(cost, id)| NextHop {
+                face_id: id.to_string(),
+                cost: cost as u32,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_basic_strategies() {
+        let prefix = Name::from_string("/a").unwrap();
+        let interest = Interest::new(Name::from_string("/a/b").unwrap());
+        let next_hops = hops(&["f1", "f2", "f3"]);
+
+        assert_eq!(BestRouteStrategy.select_next_hops(&prefix, &interest, &next_hops), vec!["f1"]);
+        assert_eq!(MulticastStrategy.select_next_hops(&prefix, &interest, &next_hops).len(), 3);
+
+        let lb = LoadBalanceStrategy::default();
+        let picks: Vec<String> = (0..3)
+            .flat_map(|_| lb.select_next_hops(&prefix, &interest, &next_hops))
+            .collect();
+        assert_eq!(picks, vec!["f1", "f2", "f3"]);
+    }
+
+    #[test]
+    fn test_asf_prefers_fastest_face() {
+        let prefix = Name::from_string("/a").unwrap();
+        let interest = Interest::new(Name::from_string("/a/b").unwrap());
+        let next_hops = hops(&["f1", "f2"]);
+        let asf = AsfStrategy::new(4, 2);
+
+        asf.on_data(&prefix, "f1", Duration::from_millis(80));
+        asf.on_data(&prefix, "f2", Duration::from_millis(10));
+
+        // First Interest carries a probe, the rest go to the fastest face
+        assert_eq!(asf.select_next_hops(&prefix, &interest, &next_hops), vec!["f2", "f1"]);
+        assert_eq!(asf.select_next_hops(&prefix, &interest, &next_hops), vec!["f2"]);
+
+        asf.on_failure(&prefix, "f2");
+        asf.on_failure(&prefix, "f2");
+        assert_eq!(asf.select_next_hops(&prefix, &interest, &next_hops), vec!["f1"]);
+    }
+
+    #[test]
+    fn test_asf_forgets_measurements() {
+        let (a, b) = (Name::from_string("/a").unwrap(), Name::from_string("/b").unwrap());
+        let asf = AsfStrategy::default();
+        asf.on_data(&a, "f1", Duration::from_millis(10));
+        asf.on_data(&b, "f1", Duration::from_millis(10));
+        asf.on_failure(&a, "f2");
+        assert_eq!(asf.measurement_count(), 3);
+
+        asf.on_next_hop_removed(&a, "f2");
+        assert_eq!(asf.measurement_count(), 2);
+        asf.on_face_removed("f1");
+        assert_eq!(asf.measurement_count(), 0);
+
+        // Pairs that saw no traffic for a lifetime are swept out
+        let asf = AsfStrategy::default().with_measurement_lifetime(Duration::from_millis(20));
+        asf.on_data(&a, "f1", Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(30));
+        asf.on_data(&b, "f2", Duration::from_millis(10));
+        assert_eq!(asf.srtt(&a, "f1"), None);
+        assert_eq!(asf.measurement_count(), 1);
+    }
+
+    #[test]
+    fn test_self_learning_route_prefix() {
+        let strategy = SelfLearningStrategy::new(Duration::from_secs(10));
+        assert!(strategy.floods_unmatched());
+        assert_eq!(
+            strategy.learn_route(&Name::from_string("/video/clip/seg=3").unwrap(), "f1"),
+            Some((Name::from_string("/video/clip").unwrap(), Duration::from_secs(10)))
+        );
+        assert_eq!(
+            strategy.learn_route(&Name::from_string("/video").unwrap(), "f1"),
+            Some((Name::from_string("/video").unwrap(), Duration::from_secs(10)))
+        );
+        assert!(!BestRouteStrategy.floods_unmatched());
+    }
+
+    #[test]
+    fn test_strategy_choice_longest_prefix() {
+        let mut choice = StrategyChoice::default();
+        choice.set(Name::from_string("/video").unwrap(), Arc::new(MulticastStrategy));
+
+        assert_eq!(choice.find(&Name::from_string("/video/1").unwrap()).name(), "multicast");
+        assert_eq!(choice.find(&Name::from_string("/text").unwrap()).name(), "best-route");
+
+        assert!(!choice.unset(&Name::new()));
+        assert!(choice.unset(&Name::from_string("/video").unwrap()));
+        assert_eq!(choice.find(&Name::from_string("/video/1").unwrap()).name(), "best-route");
+    }
+}
@@ -0,0 +1,475 @@
+//! State Vector Sync (SVS) for sync groups.
+//!
+//! Every node of a sync group numbers its publications and the group
+//! shares a state vector holding the latest sequence number of each node.
+//! When its state changes, and periodically otherwise, a node sends its
+//! state vector in a sync Interest named `<group>/<state-vector>` on all
+//! faces. Receivers merge the vector into their own, report the sequence
+//! numbers they learned to subscribers, and send their own vector if the
+//! sender was missing anything. Sync Interests are never answered with
+//! Data, and fetching the publications themselves is up to the application.
+
+use bytes::{Bytes, BytesMut};
+use log::{debug, info, trace};
+use rust_udcn_common::{
+    ndn::{Interest, Name, NameComponent},
+    tlv::TlvElement,
+    Error, Result,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::{broadcast, Mutex, Notify},
+    task::JoinHandle,
+};
+
+use crate::{producer::ProducerResponse, transport::NdnQuicTransport};
+
+/// TLV type of a state vector
+const TLV_STATE_VECTOR: u8 = 0xC9;
+
+/// TLV type of one node's entry in a state vector
+const TLV_STATE_VECTOR_ENTRY: u8 = 0xCA;
+
+/// TLV type of a sequence number
+const TLV_SEQ_NO: u8 = 0xCC;
+
+/// Sync updates buffered for slow subscribers
+const UPDATE_BUFFER_SIZE: usize = 256;
+
+/// Latest sequence number of every node in a sync group
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateVector {
+    entries: HashMap<Name, u64>,
+}
+
+impl StateVector {
+    /// Create an empty state vector
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Latest sequence number of `node_id`, 0 if it never published
+    pub fn get(&self, node_id: &Name) -> u64 {
+        self.entries.get(node_id).copied().unwrap_or(0)
+    }
+
+    /// Set the latest sequence number of `node_id`
+    pub fn set(&mut self, node_id: Name, seq: u64) {
+        self.entries.insert(node_id, seq);
+    }
+
+    /// Iterate over (node ID, sequence number) entries
+    pub fn iter(&self) -> impl Iterator<Item = (&Name, u64)> {
+        self.entries.iter().map(|(node_id, seq)| (node_id, *seq))
+    }
+
+    /// Number of nodes in the vector
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the vector has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Encode the vector as a StateVector TLV. Entries are ordered by their
+    /// encoding, so equal vectors always encode the same.
+    pub fn encode(&self) -> Result<Bytes> {
+        let mut entries = Vec::with_capacity(self.entries.len());
+        for (node_id, seq) in &self.entries {
+            let mut entry = BytesMut::new();
+            node_id.to_tlv()?.encode(&mut entry);
+            TlvElement::new(TLV_SEQ_NO, encode_nonnegative(*seq)).encode(&mut entry);
+            entries.push(entry);
+        }
+        entries.sort();
+
+        let mut value = BytesMut::new();
+        for entry in entries {
+            TlvElement::new(TLV_STATE_VECTOR_ENTRY, entry).encode(&mut value);
+        }
+        let mut buf = BytesMut::new();
+        TlvElement::new(TLV_STATE_VECTOR, value).encode(&mut buf);
+        Ok(buf.freeze())
+    }
+
+    /// Decode a StateVector TLV
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut buf = bytes;
+        let vector = TlvElement::decode(&mut buf)?;
+        if vector.tlv_type != TLV_STATE_VECTOR {
+            return Err(Error::Tlv(format!("Expected state vector TLV, got type {}", vector.tlv_type)));
+        }
+
+        let mut entries = HashMap::new();
+        let mut value = vector.value;
+        while !value.is_empty() {
+            let entry = TlvElement::decode(&mut value)?;
+            if entry.tlv_type != TLV_STATE_VECTOR_ENTRY {
+                return Err(Error::Tlv(format!("Unexpected TLV type {} in state vector", entry.tlv_type)));
+            }
+            let mut fields = entry.value;
+            let node_id = Name::from_tlv(&TlvElement::decode(&mut fields)?)?;
+            let seq = TlvElement::decode(&mut fields)?;
+            if seq.tlv_type != TLV_SEQ_NO {
+                return Err(Error::Tlv(format!("Missing sequence number for {}", node_id)));
+            }
+            entries.insert(node_id, decode_nonnegative(&seq.value)?);
+        }
+        Ok(Self { entries })
+    }
+
+    /// Take in the newer entries of `other`. Returns the updates learned
+    /// from it, and whether `other` is missing anything this vector has.
+    fn merge(&mut self, other: &StateVector) -> (Vec<SyncUpdate>, bool) {
+        let mut updates = Vec::new();
+        for (node_id, seq) in other.iter() {
+            let local = self.get(node_id);
+            if seq > local {
+                updates.push(SyncUpdate {
+                    node_id: node_id.clone(),
+                    low: local + 1,
+                    high: seq,
+                });
+                self.entries.insert(node_id.clone(), seq);
+            }
+        }
+        let other_behind = self.iter().any(|(node_id, seq)| seq > other.get(node_id));
+        (updates, other_behind)
+    }
+}
+
+/// Encode a NonNegativeInteger in 1, 2, 4 or 8 bytes
+fn encode_nonnegative(value: u64) -> Vec<u8> {
+    if value <= u8::MAX as u64 {
+        vec![value as u8]
+    } else if value <= u16::MAX as u64 {
+        (value as u16).to_be_bytes().to_vec()
+    } else if value <= u32::MAX as u64 {
+        (value as u32).to_be_bytes().to_vec()
+    } else {
+        value.to_be_bytes().to_vec()
+    }
+}
+
+fn decode_nonnegative(bytes: &[u8]) -> Result<u64> {
+    match bytes.len() {
+        1 | 2 | 4 | 8 => Ok(bytes.iter().fold(0, |value, b| (value << 8) | *b as u64)),
+        len => Err(Error::Tlv(format!("Invalid NonNegativeInteger length {}", len))),
+    }
+}
+
+/// Publications of a node learned from the group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncUpdate {
+    /// Node that published
+    pub node_id: Name,
+
+    /// First new sequence number
+    pub low: u64,
+
+    /// Latest sequence number
+    pub high: u64,
+}
+
+/// Options for a sync group member
+#[derive(Debug, Clone)]
+pub struct SvsOptions {
+    /// How often the state vector is sent while nothing changes
+    pub sync_interval: Duration,
+
+    /// How long to wait before answering a sync Interest from a node that
+    /// is behind, so that one answer from the group suffices
+    pub suppression_delay: Duration,
+
+    /// Lifetime of sync Interests, in milliseconds
+    pub interest_lifetime_ms: u32,
+}
+
+impl Default for SvsOptions {
+    fn default() -> Self {
+        Self {
+            sync_interval: Duration::from_secs(30),
+            suppression_delay: Duration::from_millis(200),
+            interest_lifetime_ms: 1000,
+        }
+    }
+}
+
+/// `duration` shortened by up to 10%, so that a group's sync Interests
+/// spread out instead of firing together
+fn jittered(duration: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.subsec_nanos())
+        .unwrap_or(0);
+    duration.mul_f64(1.0 - (nanos % 1000) as f64 / 10_000.0)
+}
+
+/// Member of a sync group
+pub struct StateVectorSync {
+    /// Sync group prefix
+    group: Name,
+
+    /// ID of this node in the group
+    node_id: Name,
+
+    /// Transport the sync Interests are exchanged on
+    transport: Arc<NdnQuicTransport>,
+
+    /// State vector of the group as known to this node
+    state: Arc<Mutex<StateVector>>,
+
+    /// Updates learned from other nodes
+    updates: broadcast::Sender<SyncUpdate>,
+
+    /// Wakes the sync task to answer a node that is behind
+    behind: Arc<Notify>,
+
+    /// Options the member was created with
+    options: SvsOptions,
+
+    /// Task sending sync Interests
+    task: JoinHandle<()>,
+}
+
+impl StateVectorSync {
+    /// Join the sync group `group` as `node_id`
+    pub async fn join(
+        transport: Arc<NdnQuicTransport>,
+        group: Name,
+        node_id: Name,
+        options: SvsOptions,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(StateVector::new()));
+        let (updates, _) = broadcast::channel(UPDATE_BUFFER_SIZE);
+        let behind = Arc::new(Notify::new());
+
+        {
+            let group_len = group.len();
+            let node_id = node_id.clone();
+            let state = Arc::clone(&state);
+            let updates = updates.clone();
+            let behind = Arc::clone(&behind);
+            transport
+                .set_interest_handler(group.clone(), move |interest| {
+                    let node_id = node_id.clone();
+                    let state = Arc::clone(&state);
+                    let updates = updates.clone();
+                    let behind = Arc::clone(&behind);
+                    async move {
+                        let Some(component) = interest.name().get(group_len) else {
+                            return ProducerResponse::Drop;
+                        };
+                        let remote = match StateVector::decode(&component.0) {
+                            Ok(remote) => remote,
+                            Err(e) => {
+                                debug!("Ignoring malformed sync Interest {}: {}", interest.name(), e);
+                                return ProducerResponse::Drop;
+                            }
+                        };
+
+                        let (learned, remote_behind) = state.lock().await.merge(&remote);
+                        for update in learned.into_iter().filter(|update| update.node_id != node_id) {
+                            trace!("{} published up to {}", update.node_id, update.high);
+                            let _ = updates.send(update);
+                        }
+                        if remote_behind {
+                            behind.notify_one();
+                        }
+                        ProducerResponse::Drop
+                    }
+                })
+                .await;
+        }
+
+        let task = tokio::spawn(Self::run(
+            Arc::clone(&transport),
+            group.clone(),
+            Arc::clone(&state),
+            Arc::clone(&behind),
+            options.clone(),
+        ));
+        info!("Joined sync group {} as {}", group, node_id);
+
+        Self {
+            group,
+            node_id,
+            transport,
+            state,
+            updates,
+            behind,
+            options,
+            task,
+        }
+    }
+
+    /// Get the sync group prefix
+    pub fn group(&self) -> &Name {
+        &self.group
+    }
+
+    /// Get the ID of this node in the group
+    pub fn node_id(&self) -> &Name {
+        &self.node_id
+    }
+
+    /// Latest sequence number published by this node
+    pub async fn seq_no(&self) -> u64 {
+        self.state.lock().await.get(&self.node_id)
+    }
+
+    /// Snapshot of the group's state vector
+    pub async fn state_vector(&self) -> StateVector {
+        self.state.lock().await.clone()
+    }
+
+    /// Announce a new publication of this node to the group and return its
+    /// sequence number
+    pub async fn publish(&self) -> Result<u64> {
+        let (seq, state) = {
+            let mut state = self.state.lock().await;
+            let seq = state.get(&self.node_id) + 1;
+            state.set(self.node_id.clone(), seq);
+            (seq, state.clone())
+        };
+        debug!("{} published {} in {}", self.node_id, seq, self.group);
+        send_sync_interest(&self.transport, &self.group, &state, self.options.interest_lifetime_ms).await?;
+        Ok(seq)
+    }
+
+    /// Subscribe to the publications of the other nodes. Each update covers
+    /// the new sequence numbers of one node.
+    pub fn subscribe(&self) -> broadcast::Receiver<SyncUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Send the state vector now rather than at the next interval
+    pub fn sync_now(&self) {
+        self.behind.notify_one();
+    }
+
+    /// Leave the group, no longer handling its sync Interests
+    pub async fn leave(self) {
+        self.transport.remove_interest_handler(&self.group).await;
+    }
+
+    /// Send the state vector periodically, and shortly after hearing from a
+    /// node that is behind
+    async fn run(
+        transport: Arc<NdnQuicTransport>,
+        group: Name,
+        state: Arc<Mutex<StateVector>>,
+        behind: Arc<Notify>,
+        options: SvsOptions,
+    ) {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(jittered(options.sync_interval)) => {}
+                _ = behind.notified() => tokio::time::sleep(jittered(options.suppression_delay)).await,
+            }
+            let state = state.lock().await.clone();
+            if let Err(e) = send_sync_interest(&transport, &group, &state, options.interest_lifetime_ms).await {
+                debug!("Failed to send sync Interest for {}: {}", group, e);
+            }
+        }
+    }
+}
+
+impl Drop for StateVectorSync {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Send `state` to the group on every face
+async fn send_sync_interest(
+    transport: &NdnQuicTransport,
+    group: &Name,
+    state: &StateVector,
+    lifetime_ms: u32,
+) -> Result<()> {
+    let mut name = group.clone();
+    name.push(NameComponent::new(state.encode()?));
+    let interest = Interest::new(name).with_lifetime(lifetime_ms);
+
+    for face in transport.get_faces().await {
+        if let Err(e) = face.send_interest(interest.clone()).await {
+            trace!("Failed to send sync Interest on {}: {}", face.id(), e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{link_face::link_face_pair, TransportConfig};
+    use rust_udcn_common::metrics::UdcnMetrics;
+
+    fn name(s: &str) -> Name {
+        Name::from_string(s).unwrap()
+    }
+
+    #[test]
+    fn test_state_vector_encoding() {
+        let mut vector = StateVector::new();
+        vector.set(name("/node/a"), 7);
+        vector.set(name("/node/b"), 300);
+        vector.set(name("/node/c"), u32::MAX as u64 + 1);
+
+        let encoded = vector.encode().unwrap();
+        assert_eq!(StateVector::decode(&encoded).unwrap(), vector);
+        assert!(StateVector::decode(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_state_vector_merge() {
+        let mut local = StateVector::new();
+        local.set(name("/a"), 3);
+        local.set(name("/b"), 5);
+        let mut remote = StateVector::new();
+        remote.set(name("/a"), 6);
+        remote.set(name("/b"), 2);
+
+        let (updates, remote_behind) = local.merge(&remote);
+        assert_eq!(
+            updates,
+            vec![SyncUpdate {
+                node_id: name("/a"),
+                low: 4,
+                high: 6
+            }]
+        );
+        assert!(remote_behind);
+        assert_eq!(local.get(&name("/a")), 6);
+        assert_eq!(local.get(&name("/b")), 5);
+    }
+
+    #[tokio::test]
+    async fn test_publication_reaches_other_member() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (to_bob, to_alice) = link_face_pair("to-bob", "to-alice", &metrics);
+        let alice = Arc::new(NdnQuicTransport::new(TransportConfig::default()).await.unwrap());
+        let bob = Arc::new(NdnQuicTransport::new(TransportConfig::default()).await.unwrap());
+        alice.add_face(Arc::new(to_bob)).await;
+        bob.add_face(Arc::new(to_alice)).await;
+
+        let group = name("/chat");
+        let alice_sync = StateVectorSync::join(alice, group.clone(), name("/alice"), SvsOptions::default()).await;
+        let bob_sync = StateVectorSync::join(bob, group, name("/bob"), SvsOptions::default()).await;
+        let mut updates = bob_sync.subscribe();
+
+        alice_sync.publish().await.unwrap();
+        assert_eq!(alice_sync.publish().await.unwrap(), 2);
+
+        let update = tokio::time::timeout(Duration::from_secs(5), updates.recv()).await.unwrap().unwrap();
+        assert_eq!(update.node_id, name("/alice"));
+        assert_eq!(update.low, 1);
+    }
+}
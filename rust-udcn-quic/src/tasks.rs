@@ -0,0 +1,82 @@
+//! Background tasks stopped by a cancellation token.
+//!
+//! Servers and transports spawn tasks that would otherwise run as long as
+//! the runtime. They are spawned in a [`TaskSet`], whose token the tasks
+//! select on to return at their next await point; an application embedding
+//! the transport passes its own token, cancels it and awaits the handles.
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Tasks spawned until a token is cancelled
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TaskSet {
+    /// Cancelled to stop the tasks
+    token: CancellationToken,
+
+    /// The tasks not known to have finished
+    handles: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl TaskSet {
+    /// Set of tasks stopping once `token` is cancelled
+    pub fn new(token: CancellationToken) -> Self {
+        Self {
+            token,
+            handles: Arc::default(),
+        }
+    }
+
+    /// Token the tasks stop on
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Spawn a task, which is expected to return once the token is cancelled
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task);
+        let mut handles = self.handles.lock().expect("task set lock poisoned");
+        handles.retain(|handle| !handle.is_finished());
+        handles.push(handle);
+    }
+
+    /// Cancel the token and hand the tasks over, to await them
+    pub fn cancel(&self) -> Vec<JoinHandle<()>> {
+        self.token.cancel();
+        std::mem::take(&mut *self.handles.lock().expect("task set lock poisoned"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_stops_tasks() {
+        let parent = CancellationToken::new();
+        let tasks = TaskSet::new(parent.child_token());
+        for _ in 0..3 {
+            let token = tasks.token().clone();
+            tasks.spawn(async move { token.cancelled().await });
+        }
+
+        // Cancelling the parent reaches the tasks, and not the other way round
+        parent.cancel();
+        for handle in tasks.cancel() {
+            tokio::time::timeout(Duration::from_secs(1), handle).await.unwrap().unwrap();
+        }
+        assert!(tasks.cancel().is_empty());
+
+        let parent = CancellationToken::new();
+        TaskSet::new(parent.child_token()).cancel();
+        assert!(!parent.is_cancelled());
+    }
+}
@@ -0,0 +1,75 @@
+//! Plain TCP faces.
+//!
+//! Packets travel back to back on the connection, delimited by their TLV
+//! length, which is how NFD and the NDN testbed routers frame NDN over TCP.
+
+use log::{debug, info};
+use rust_udcn_common::metrics::UdcnMetrics;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::{error::TransportError, face::is_loopback, link::StreamLink, link_face::LinkFace};
+
+/// Port NFD listens on for TCP faces
+pub const NDN_TCP_PORT: u16 = 6363;
+
+/// Listener accepting NDN connections over TCP
+#[derive(Debug)]
+pub struct TcpFaceListener {
+    /// The listening socket
+    listener: TcpListener,
+    
+    /// Metrics shared by all accepted faces
+    metrics: Arc<UdcnMetrics>,
+}
+
+impl TcpFaceListener {
+    /// Listen on `addr`
+    pub async fn bind(addr: SocketAddr) -> Result<Self, TransportError> {
+        let listener = TcpListener::bind(addr).await.map_err(|source| TransportError::Bind {
+            addr: addr.to_string(),
+            source,
+        })?;
+        info!("Listening for TCP faces on {}", listener.local_addr()?);
+
+        Ok(Self {
+            listener,
+            metrics: Arc::new(UdcnMetrics::new()),
+        })
+    }
+
+    /// Get the local address the listener is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr, TransportError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Get the metrics shared by the accepted faces
+    pub fn metrics(&self) -> Arc<UdcnMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Wait for the next peer to connect
+    pub async fn accept(&self) -> Result<Arc<LinkFace>, TransportError> {
+        let (stream, peer) = self.listener.accept().await?;
+        stream.set_nodelay(true)?;
+        let face_id = format!("tcp://{}", peer);
+        debug!("[Face {}] Accepted TCP connection", face_id);
+
+        let link = Arc::new(StreamLink::new(stream));
+        let face = LinkFace::new(face_id, link, Arc::clone(&self.metrics)).with_local(is_loopback(peer.ip()));
+        Ok(Arc::new(face))
+    }
+}
+
+/// Open a TCP face to the router at `addr`
+pub async fn connect_tcp(addr: SocketAddr) -> Result<LinkFace, TransportError> {
+    let stream = TcpStream::connect(addr).await.map_err(|source| TransportError::Connect {
+        addr: addr.to_string(),
+        source,
+    })?;
+    stream.set_nodelay(true)?;
+    let face_id = format!("tcp://{}", addr);
+
+    let face = LinkFace::new(face_id, Arc::new(StreamLink::new(stream)), Arc::new(UdcnMetrics::new()));
+    Ok(face.with_local(is_loopback(addr.ip())))
+}
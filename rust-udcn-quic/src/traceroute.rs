@@ -0,0 +1,209 @@
+//! Finding the forwarders on the path toward a name.
+//!
+//! A [`Tracer`] expresses Interests for the name with a HopLimit of 0, 1,
+//! 2 and so on. Forwarders decrement the HopLimit before forwarding, and
+//! the one receiving it spent answers with a HopLimit Nack naming itself.
+//! The trace ends at the first hop answering with Data or with another
+//! Nack. Forwarders dropping spent Interests silently, like the XDP fast
+//! path, show up as timeouts.
+
+use log::trace;
+use rust_udcn_common::ndn::{Interest, NackReason, Name};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time::timeout_at;
+
+use crate::{error::FaceError, face::FaceEvent, ndn_face::NdnFace};
+
+/// Default number of hops a trace gives up after
+pub const DEFAULT_MAX_HOPS: u8 = 30;
+
+/// How a probe was answered
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceOutcome {
+    /// The HopLimit ran out at a forwarder, named if it said who it is
+    Forwarder(Option<Name>),
+
+    /// Data came back, from the producer or a cache
+    Reached,
+
+    /// A forwarder Nacked the Interest for another reason, which ends the
+    /// trace
+    Nacked(NackReason, Option<Name>),
+
+    /// Nothing came back in time
+    Timeout,
+}
+
+impl TraceOutcome {
+    /// Whether no hop lies beyond this one
+    pub fn is_final(&self) -> bool {
+        matches!(self, TraceOutcome::Reached | TraceOutcome::Nacked(..))
+    }
+}
+
+/// The answer to one probe
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceHop {
+    /// Distance of the hop, starting at 1 for the local forwarder
+    pub hop: u8,
+
+    /// Time from sending the probe to its answer, unless it timed out
+    pub rtt: Option<Duration>,
+
+    /// How the probe was answered
+    pub outcome: TraceOutcome,
+}
+
+/// Probes the path toward a name
+pub struct Tracer {
+    /// Face the probes are sent on, not shared with other consumers
+    face: Arc<dyn NdnFace>,
+
+    /// Name traced
+    name: Name,
+
+    /// How long to wait for each answer
+    timeout: Duration,
+
+    /// Nonce of the probe with HopLimit 0, the others following it
+    nonce: u32,
+}
+
+impl Tracer {
+    /// Trace the path toward `name` on `face`, waiting up to `timeout` for
+    /// the answer to each probe
+    ///
+    /// The tracer reads the face's events, so the face should not be used
+    /// by anything else meanwhile.
+    pub fn new(face: Arc<dyn NdnFace>, name: &Name, timeout: Duration) -> Self {
+        Self {
+            face,
+            name: name.clone(),
+            timeout,
+            nonce: Interest::new(name.clone()).nonce,
+        }
+    }
+
+    /// Name traced
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// Express the probe reaching `hop`, and wait for its answer. Hops are
+    /// numbered from 1, and probing hop 0 panics.
+    pub async fn probe(&self, hop: u8) -> Result<TraceHop, FaceError> {
+        assert!(hop > 0, "Hops are numbered from 1");
+        let nonce = self.nonce.wrapping_add(hop as u32);
+        let mut interest = Interest::new(self.name.clone())
+            .with_nonce(nonce)
+            .with_lifetime(self.timeout.as_millis() as u32);
+        interest.hop_limit = Some(hop - 1);
+
+        let sent_at = Instant::now();
+        let deadline = sent_at + self.timeout;
+        self.face.send_interest(interest).await?;
+
+        loop {
+            let event = match timeout_at(deadline.into(), self.face.recv()).await {
+                Ok(None) | Ok(Some(FaceEvent::Closed)) => return Err(FaceError::Closed),
+                Ok(Some(event)) => event,
+                Err(_) => {
+                    return Ok(TraceHop {
+                        hop,
+                        rtt: None,
+                        outcome: TraceOutcome::Timeout,
+                    })
+                }
+            };
+
+            let outcome = match event {
+                FaceEvent::DataReceived(data) if data.name() == &self.name => TraceOutcome::Reached,
+                FaceEvent::NackReceived(nack) if nack.interest.nonce == nonce => match nack.reason {
+                    NackReason::HopLimit => TraceOutcome::Forwarder(nack.reporter),
+                    reason => TraceOutcome::Nacked(reason, nack.reporter),
+                },
+                other => {
+                    trace!("Ignoring {:?} while tracing {}", other, self.name);
+                    continue;
+                }
+            };
+            return Ok(TraceHop {
+                hop,
+                rtt: Some(sent_at.elapsed()),
+                outcome,
+            });
+        }
+    }
+
+    /// Probe hop after hop, up to `max_hops` or until the trace ends,
+    /// calling `on_hop` with each answer as it comes
+    pub async fn run(&self, max_hops: u8, mut on_hop: impl FnMut(&TraceHop)) -> Result<Vec<TraceHop>, FaceError> {
+        let mut hops = Vec::new();
+        for hop in 1..=max_hops {
+            let answer = self.probe(hop).await?;
+            on_hop(&answer);
+            let done = answer.outcome.is_final();
+            hops.push(answer);
+            if done {
+                break;
+            }
+        }
+        Ok(hops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{forwarder::Forwarder, link_face::link_face_pair};
+    use rust_udcn_common::{metrics::UdcnMetrics, ndn::Data};
+
+    #[tokio::test]
+    async fn test_trace_through_forwarder() {
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (consumer, downstream) = link_face_pair("downstream-app", "downstream", &metrics);
+        let (producer, upstream) = link_face_pair("upstream-app", "upstream", &metrics);
+        let downstream: Arc<dyn NdnFace> = Arc::new(downstream);
+        let upstream: Arc<dyn NdnFace> = Arc::new(upstream);
+        let faces = vec![Arc::clone(&downstream), Arc::clone(&upstream)];
+
+        let forwarder = Arc::new(Forwarder::new(Arc::clone(&metrics)).with_node_id(Name::from_string("/router").unwrap()));
+        let name = Name::from_string("/p/x").unwrap();
+        forwarder.add_route(Name::from_string("/p").unwrap(), "upstream", 0).await;
+
+        // The producer answers whatever reaches it
+        tokio::spawn(async move {
+            while let Some(FaceEvent::InterestReceived(interest)) = producer.next_event().await {
+                assert_eq!(interest.hop_limit, Some(0));
+                producer.send_data(Data::new(interest.name().clone(), "x")).await.unwrap();
+            }
+        });
+        // The forwarder relays between the consumer and the producer
+        let relay = Arc::clone(&forwarder);
+        let relay_faces = faces.clone();
+        tokio::spawn(async move {
+            while let Some(event) = downstream.recv().await {
+                if let FaceEvent::InterestReceived(interest) = event {
+                    relay.process_interest(interest, &downstream, &relay_faces).await.unwrap();
+                }
+            }
+        });
+        tokio::spawn(async move {
+            while let Some(event) = upstream.recv().await {
+                if let FaceEvent::DataReceived(data) = event {
+                    forwarder.process_data(&data, &upstream, &faces).await;
+                }
+            }
+        });
+
+        let tracer = Tracer::new(Arc::new(consumer), &name, Duration::from_millis(500));
+        let hops = tracer.run(DEFAULT_MAX_HOPS, |_| {}).await.unwrap();
+        assert_eq!(hops.len(), 2);
+        assert_eq!(hops[0].outcome, TraceOutcome::Forwarder(Some(Name::from_string("/router").unwrap())));
+        assert_eq!((hops[1].hop, &hops[1].outcome), (2, &TraceOutcome::Reached));
+        assert!(hops[1].rtt.is_some());
+    }
+}
@@ -3,28 +3,60 @@
 //! This module provides a high-level API for NDN communications over QUIC,
 //! handling connection establishment, Interest/Data exchange, and event handling.
 
-use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
+use futures::stream::BoxStream;
 use log::{debug, error, info, trace, warn};
 use rust_udcn_common::{
-    ndn::{Data, Interest, Name},
+    config::Settings,
+    ndn::{Data, Interest, Nack, NackReason, Name},
     metrics::UdcnMetrics,
 };
 use std::{
-    net::ToSocketAddrs,
-    sync::Arc,
-    time::Duration,
+    collections::HashMap,
+    future::Future,
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tokio::{
-    sync::{mpsc, oneshot, RwLock},
+    sync::{broadcast, mpsc, oneshot, Mutex, RwLock},
+    task::JoinHandle,
     time::timeout,
 };
+use tokio_util::sync::CancellationToken;
+use tracing::instrument;
 
 use crate::{
-    face::{Face, FaceEvent},
+    cs::{ContentStore, ContentStoreLimits},
+    error::{FaceError, TransportError},
+    events::{EventFilter, EventSubscription},
+    face::{Face, FaceEvent, FaceStats, PacketMode, RetransmissionPolicy},
+    forwarder::Forwarder,
+    identity::PeerIdentity,
+    ndn_face::NdnFace,
+    producer::{HandlerLimiter, HandlerLimits, HandlerOverflow, HandlerRegistry, ProducerResponse},
+    pubsub::{PubSub, PubSubOptions},
+    scheduler::SchedulerOptions,
+    tasks::TaskSet,
     ClientOptions, NdnQuicClient, ServerOptions, NdnQuicServer,
     DEFAULT_INTEREST_TIMEOUT_MS,
 };
 
+/// How often a draining transport checks for outstanding exchanges
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long the routes of an authenticated peer with no face left wait
+/// for it to reconnect
+pub const PEER_ROUTE_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Face IDs holding the routes of authenticated peers that have no face
+/// left, by peer ID
+type OrphanedRoutes = Arc<Mutex<HashMap<String, String>>>;
+
 /// NDN QUIC transport modes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TransportMode {
@@ -53,6 +85,35 @@ pub struct TransportConfig {
     
     /// Interest timeout (in milliseconds)
     pub interest_timeout_ms: u64,
+    
+    /// How faces send packets (per-packet streams, datagrams or one long-lived
+    /// stream). `None` uses the default of each face's protocol version
+    pub packet_mode: Option<PacketMode>,
+    
+    /// Retransmission policy for Interests expressed on faces
+    pub retransmission: RetransmissionPolicy,
+    
+    /// Forward Interests between faces according to the forwarder's FIB
+    pub enable_forwarding: bool,
+    
+    /// Queue what the forwarder sends per face and send it in fair turns,
+    /// so a busy face cannot starve the others (None to send right away)
+    pub forwarder_scheduler: Option<SchedulerOptions>,
+    
+    /// Cache Data in a userspace content store (None to disable)
+    pub content_store: Option<ContentStoreLimits>,
+    
+    /// Persist the content store to this repo log file
+    pub content_store_path: Option<PathBuf>,
+    
+    /// Name prefix of this node's publications (generated when unset)
+    pub node_id: Option<Name>,
+    
+    /// Options for publishing and subscribing to topics
+    pub pubsub_options: PubSubOptions,
+    
+    /// Limits on the producer handler invocations running at once
+    pub handler_limits: HandlerLimits,
 }
 
 impl Default for TransportConfig {
@@ -63,6 +124,38 @@ impl Default for TransportConfig {
             client_options: ClientOptions::default(),
             event_buffer_size: 100,
             interest_timeout_ms: DEFAULT_INTEREST_TIMEOUT_MS,
+            packet_mode: None,
+            retransmission: RetransmissionPolicy::default(),
+            enable_forwarding: false,
+            forwarder_scheduler: Some(SchedulerOptions::default()),
+            content_store: None,
+            content_store_path: None,
+            node_id: None,
+            pubsub_options: PubSubOptions::default(),
+            handler_limits: HandlerLimits::default(),
+        }
+    }
+}
+
+impl TransportConfig {
+    /// Configuration of a forwarding node as `settings` describe it:
+    /// serving QUIC faces, opening others and caching the Data forwarded
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            mode: TransportMode::Dual,
+            server_options: Some(ServerOptions {
+                listen_addr: settings.transport.quic_listen.clone(),
+                cert_path: settings.security.cert.clone(),
+                key_path: settings.security.key.clone(),
+                ..Default::default()
+            }),
+            enable_forwarding: true,
+            content_store: Some(ContentStoreLimits {
+                max_entries: settings.cs.capacity,
+                ..Default::default()
+            }),
+            content_store_path: settings.cs.path.clone(),
+            ..Default::default()
         }
     }
 }
@@ -94,6 +187,15 @@ pub enum TransportEvent {
         face_id: String,
     },
     
+    /// A Nack was received
+    NackReceived {
+        /// The Nack
+        nack: Nack,
+        
+        /// ID of the face it was received on
+        face_id: String,
+    },
+    
     /// An error occurred
     Error(String),
 }
@@ -111,23 +213,69 @@ pub struct NdnQuicTransport {
     server: Option<Arc<NdnQuicServer>>,
     
     /// Connected faces
-    faces: Arc<RwLock<Vec<Arc<Face>>>>,
+    faces: Arc<RwLock<Vec<Arc<dyn NdnFace>>>>,
+    
+    /// Event sender, shared by all subscribers
+    event_sender: broadcast::Sender<TransportEvent>,
     
-    /// Event sender
-    event_sender: mpsc::Sender<TransportEvent>,
+    /// Subscription backing [`next_event`](Self::next_event)
+    event_receiver: Arc<RwLock<Option<EventSubscription>>>,
     
-    /// Event receiver
-    event_receiver: Arc<RwLock<Option<mpsc::Receiver<TransportEvent>>>>,
+    /// Events of all faces, fanned in for processing
+    face_events: mpsc::Sender<(Arc<dyn NdnFace>, FaceEvent)>,
     
     /// Metrics
     metrics: Arc<UdcnMetrics>,
+    
+    /// Registered producer handlers
+    handlers: HandlerRegistry,
+    
+    /// Forwarding engine (if forwarding is enabled)
+    forwarder: Option<Arc<Forwarder>>,
+    
+    /// Routes of authenticated peers waiting for them to reconnect
+    orphaned_routes: OrphanedRoutes,
+    
+    /// Content store (if caching is enabled)
+    content_store: Option<Arc<ContentStore>>,
+    
+    /// Set once a graceful shutdown has started
+    draining: Arc<AtomicBool>,
+    
+    /// Number of handler invocations still running
+    active_handlers: Arc<AtomicUsize>,
+    
+    /// Concurrency limits of the handler invocations
+    handler_limiter: Arc<HandlerLimiter>,
+    
+    /// Name prefix of this node's publications
+    node_id: Name,
+    
+    /// Topic publisher and subscriber, created on first use
+    pubsub: Mutex<Option<Arc<PubSub>>>,
+    
+    /// Background tasks, stopped by cancelling their token
+    tasks: TaskSet,
 }
 
 impl NdnQuicTransport {
     /// Create a new transport with the given configuration
-    pub async fn new(config: TransportConfig) -> Result<Self> {
+    pub async fn new(config: TransportConfig) -> Result<Self, TransportError> {
+        Self::with_cancellation(config, CancellationToken::new()).await
+    }
+    
+    /// Create a new transport whose background tasks, and those of its
+    /// server, stop once `token` is cancelled
+    ///
+    /// The tasks get a child token, so closing the transport leaves `token`
+    /// alone. Await the handles [`stop_tasks`](Self::stop_tasks) returns
+    /// to know they are done.
+    pub async fn with_cancellation(config: TransportConfig, token: CancellationToken) -> Result<Self, TransportError> {
+        let tasks = TaskSet::new(token.child_token());
+        
         // Create the event channel
-        let (event_sender, event_receiver) = mpsc::channel(config.event_buffer_size);
+        let (event_sender, event_receiver) = broadcast::channel(config.event_buffer_size);
+        let (face_event_sender, face_event_receiver) = mpsc::channel(config.event_buffer_size);
         
         // Create metrics
         let metrics = Arc::new(UdcnMetrics::new());
@@ -144,58 +292,108 @@ impl NdnQuicTransport {
         let server = match config.mode {
             TransportMode::Server | TransportMode::Dual => {
                 let server_options = config.server_options.clone()
-                    .ok_or_else(|| anyhow!("Server options are required in Server or Dual mode"))?;
+                    .ok_or_else(|| TransportError::Config("server options are required in Server or Dual mode".to_string()))?;
                 
-                let server = NdnQuicServer::new(server_options).await?;
+                let server = NdnQuicServer::new(server_options).await?
+                    .with_cancellation(tasks.token().child_token());
                 Some(Arc::new(server))
             }
             _ => None,
         };
         
+        
+        let content_store = match (&config.content_store, &config.content_store_path) {
+            (Some(limits), Some(path)) => Some(Arc::new(
+                ContentStore::open(path, limits.clone(), Arc::clone(&metrics))
+                    .map_err(TransportError::ContentStore)?,
+            )),
+            (Some(limits), None) => Some(Arc::new(ContentStore::new(limits.clone(), Arc::clone(&metrics)))),
+            (None, _) => None,
+        };
+        
+        let node_id = config.node_id.clone().unwrap_or_else(generate_node_id);
+        
+        let forwarder = if config.enable_forwarding {
+            let mut forwarder = Forwarder::new(Arc::clone(&metrics)).with_node_id(node_id.clone());
+            if let Some(options) = &config.forwarder_scheduler {
+                forwarder = forwarder.with_scheduler(options.clone());
+            }
+            Some(Arc::new(forwarder))
+        } else {
+            None
+        };
+        
+        let handler_limiter = Arc::new(HandlerLimiter::new(config.handler_limits.clone()));
+        
         let transport = Self {
             config,
             client,
             server,
             faces: Arc::new(RwLock::new(Vec::new())),
             event_sender,
-            event_receiver: Arc::new(RwLock::new(Some(event_receiver))),
+            event_receiver: Arc::new(RwLock::new(Some(EventSubscription::new(event_receiver, EventFilter::default())))),
+            face_events: face_event_sender,
             metrics,
+            handlers: HandlerRegistry::new(),
+            forwarder,
+            orphaned_routes: Arc::new(Mutex::new(HashMap::new())),
+            content_store,
+            draining: Arc::new(AtomicBool::new(false)),
+            active_handlers: Arc::new(AtomicUsize::new(0)),
+            handler_limiter,
+            node_id,
+            pubsub: Mutex::new(None),
+            tasks,
         };
         
         // Start the server if we have one
         if let Some(server) = &transport.server {
+            transport.track_server_faces(server);
             server.run().await?;
         }
         
-        // Start the event processor
-        transport.process_events();
+        // Start the event processor, and the sender of what it forwards
+        if let Some(scheduler) = transport.forwarder.as_ref().and_then(|forwarder| forwarder.scheduler()) {
+            transport.tasks.spawn(scheduler.run(transport.tasks.token().clone()));
+        }
+        transport.process_events(face_event_receiver);
         
         Ok(transport)
     }
 
     /// Connect to a remote NDN forwarder
-    pub async fn connect<T: ToSocketAddrs + std::fmt::Debug>(&self, addr: T) -> Result<Arc<Face>> {
+    pub async fn connect<T: ToSocketAddrs + std::fmt::Debug>(&self, addr: T) -> Result<Arc<Face>, TransportError> {
         let client = self.client.clone()
-            .ok_or_else(|| anyhow!("Client not initialized (transport is not in Client or Dual mode)"))?;
+            .ok_or_else(|| TransportError::Config("the transport is not in Client or Dual mode".to_string()))?;
         
         let face = client.connect(addr).await?;
+        configure_face(&face, &self.config).await;
+        self.add_face(Arc::clone(&face) as Arc<dyn NdnFace>).await;
         
-        // Add the face to our list
+        Ok(face)
+    }
+
+    /// Add a face over any transport, such as a TCP, UDP or Unix socket
+    /// face, and handle its packets like those of the QUIC faces
+    pub async fn add_face(&self, face: Arc<dyn NdnFace>) {
         self.faces.write().await.push(Arc::clone(&face));
+        pump_face_events(&self.tasks, Arc::clone(&face), self.face_events.clone());
         
-        // Emit an event
-        let _ = self.event_sender.send(TransportEvent::FaceCreated(face.id().to_string())).await;
-        
-        Ok(face)
+        let _ = self.event_sender.send(TransportEvent::FaceCreated(face.id().to_string()));
     }
 
     /// Express an Interest and wait for Data
+    #[instrument(skip_all, fields(name = %interest.name()))]
     pub async fn express_interest(
         &self,
         interest: Interest,
         face_id: Option<&str>,
         timeout_ms: Option<u64>,
-    ) -> Result<Data> {
+    ) -> Result<Data, FaceError> {
+        if self.is_draining() {
+            return Err(FaceError::Draining);
+        }
+        
         let faces = self.faces.read().await;
         
         // Find the face to use
@@ -204,23 +402,27 @@ impl NdnQuicTransport {
             Some(id) => {
                 faces.iter()
                     .find(|f| f.id() == id)
-                    .ok_or_else(|| anyhow!("Face not found: {}", id))?
+                    .ok_or_else(|| FaceError::UnknownFace(id.to_string()))?
                     .clone()
             }
             // Otherwise use the first available face
             None => {
                 if faces.is_empty() {
-                    return Err(anyhow!("No faces available"));
+                    return Err(FaceError::NoRoute);
                 }
                 Arc::clone(&faces[0])
             }
         };
         
         // Express the Interest
-        face.express_interest(
+        let data = face.express_interest(
             interest, 
             timeout_ms.unwrap_or(self.config.interest_timeout_ms)
-        ).await
+        ).await?;
+        
+        // Data answering an Interest of our own is worth caching
+        cache_data(self.content_store.as_deref(), &data);
+        Ok(data)
     }
 
     /// Send a Data packet
@@ -228,37 +430,223 @@ impl NdnQuicTransport {
         &self,
         data: Data,
         face_id: &str,
-    ) -> Result<()> {
+    ) -> Result<(), FaceError> {
         let faces = self.faces.read().await;
         
         // Find the face to use
         let face = faces.iter()
             .find(|f| f.id() == face_id)
-            .ok_or_else(|| anyhow!("Face not found: {}", face_id))?;
+            .ok_or_else(|| FaceError::UnknownFace(face_id.to_string()))?;
+        
+        cache_data(self.content_store.as_deref(), &data);
         
         // Send the Data
         face.send_data(data).await
     }
 
-    /// Get the next event from the transport
+    /// Register an async handler serving Interests under `prefix`.
+    ///
+    /// Interests matching a registered prefix are dispatched to the handler
+    /// with the longest matching prefix and its response is sent back on the
+    /// face the Interest arrived on. Such Interests are not reported through
+    /// [`next_event`](Self::next_event). Invocations beyond the
+    /// [`handler_limits`](TransportConfig::handler_limits) of the transport
+    /// are queued, Nacked or dropped as these say.
+    pub async fn set_interest_handler<F, Fut>(&self, prefix: Name, handler: F)
+    where
+        F: Fn(Interest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ProducerResponse> + Send + 'static,
+    {
+        info!("Registering Interest handler for {}", prefix);
+        self.handlers.insert(prefix, handler).await;
+    }
+
+    /// Remove the handler registered for `prefix`
+    pub async fn remove_interest_handler(&self, prefix: &Name) -> bool {
+        self.handler_limiter.remove(prefix);
+        self.handlers.remove(prefix).await
+    }
+
+    /// Get the name prefix of this node's publications
+    pub fn node_id(&self) -> &Name {
+        &self.node_id
+    }
+
+    /// Publish `content` on `topic` and return its sequence number.
+    ///
+    /// The topic's sync group is joined on first use, and the publication
+    /// is served to other nodes as `<node-id>/<topic>/<seq>`.
+    pub async fn publish(self: &Arc<Self>, topic: &Name, content: impl Into<Bytes>) -> rust_udcn_common::Result<u64> {
+        self.pubsub().await.publish(topic, content).await
+    }
+
+    /// Receive the publications of other nodes on `topic`, each reassembled
+    /// into one Data packet. Unlike [`subscribe`](Self::subscribe), this
+    /// subscribes to a topic rather than to transport events.
+    pub async fn subscribe_topic(self: &Arc<Self>, topic: &Name) -> BoxStream<'static, Data> {
+        self.pubsub().await.subscribe(topic).await
+    }
+
+    /// Stop publishing and receiving on `topic`
+    pub async fn leave_topic(&self, topic: &Name) {
+        if let Some(pubsub) = self.pubsub.lock().await.as_ref() {
+            pubsub.leave(topic).await;
+        }
+    }
+
+    /// Get the topic publisher and subscriber, creating it if needed
+    async fn pubsub(self: &Arc<Self>) -> Arc<PubSub> {
+        let mut pubsub = self.pubsub.lock().await;
+        let pubsub = pubsub.get_or_insert_with(|| {
+            Arc::new(PubSub::new(
+                Arc::clone(self),
+                self.node_id.clone(),
+                self.config.pubsub_options.clone(),
+            ))
+        });
+        Arc::clone(pubsub)
+    }
+
+    /// Subscribe to transport events matching `filter`.
+    ///
+    /// Each subscription receives its own copy of every matching event
+    /// emitted after it was created.
+    pub fn subscribe(&self, filter: EventFilter) -> EventSubscription {
+        EventSubscription::new(self.event_sender.subscribe(), filter)
+    }
+
+    /// Get the next event from the transport.
+    ///
+    /// All callers share one event stream; use [`subscribe`](Self::subscribe)
+    /// for independent streams.
     pub async fn next_event(&self) -> Option<TransportEvent> {
         let mut receiver_guard = self.event_receiver.write().await;
         let receiver = receiver_guard.as_mut()?;
         receiver.recv().await
     }
 
+    /// Get the forwarding engine, if forwarding is enabled
+    pub fn forwarder(&self) -> Option<Arc<Forwarder>> {
+        self.forwarder.clone()
+    }
+
+    /// Get the content store, if caching is enabled
+    pub fn content_store(&self) -> Option<Arc<ContentStore>> {
+        self.content_store.clone()
+    }
+
+    /// Get the addresses the QUIC server listens on, if there is a server
+    pub fn server_addresses(&self) -> Vec<SocketAddr> {
+        self.server
+            .as_ref()
+            .map(|server| server.addresses().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Re-read the server certificate and key, if there is a server.
+    ///
+    /// Existing connections are not affected.
+    pub async fn reload_certificates(&self) -> Result<(), TransportError> {
+        match &self.server {
+            Some(server) => server.reload_certificates().await,
+            None => Ok(()),
+        }
+    }
+
     /// Get metrics from the transport
     pub fn metrics(&self) -> Arc<UdcnMetrics> {
         Arc::clone(&self.metrics)
     }
 
     /// Get a list of all connected faces
-    pub async fn get_faces(&self) -> Vec<Arc<Face>> {
+    pub async fn get_faces(&self) -> Vec<Arc<dyn NdnFace>> {
         self.faces.read().await.clone()
     }
 
-    /// Close the transport and all connections
-    pub async fn close(&self) -> Result<()> {
+    /// Traffic statistics of every connected face
+    pub async fn face_stats(&self) -> Vec<FaceStats> {
+        self.faces.read().await.iter().map(|face| face.stats()).collect()
+    }
+
+    /// Whether a graceful shutdown is in progress
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Shut down gracefully.
+    ///
+    /// New connections and Interests are refused (incoming Interests are
+    /// Nacked), then the transport waits up to `drain_timeout` for expressed
+    /// Interests, forwarded Interests and running handlers to complete before
+    /// closing every face (QUIC faces with [`CLOSE_CODE_GOAWAY`](crate::CLOSE_CODE_GOAWAY)).
+    pub async fn shutdown(&self, drain_timeout: Duration) -> Result<(), TransportError> {
+        if self.draining.swap(true, Ordering::SeqCst) {
+            return Err(FaceError::Draining.into());
+        }
+        
+        info!("Draining transport (up to {:?})", drain_timeout);
+        
+        if let Some(server) = &self.server {
+            server.stop_accepting();
+        }
+        
+        let deadline = Instant::now() + drain_timeout;
+        loop {
+            let outstanding = self.outstanding_exchanges().await;
+            if outstanding == 0 {
+                info!("Transport drained");
+                break;
+            }
+            if Instant::now() >= deadline {
+                warn!("Drain timed out with {} exchange(s) outstanding", outstanding);
+                break;
+            }
+            trace!("Waiting for {} outstanding exchange(s)", outstanding);
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+        
+        let faces = self.faces.read().await.clone();
+        for face in faces {
+            face.close_going_away().await;
+        }
+        
+        self.close().await
+    }
+
+    /// Number of exchanges a draining transport still waits for
+    async fn outstanding_exchanges(&self) -> usize {
+        let mut outstanding = self.active_handlers.load(Ordering::SeqCst);
+        if let Some(forwarder) = &self.forwarder {
+            outstanding += forwarder.pit_size().await;
+        }
+        for face in self.faces.read().await.iter() {
+            outstanding += face.pending_interest_count().await;
+        }
+        outstanding
+    }
+
+    /// Cancel the background tasks of the transport and its server, and
+    /// hand back their handles, to await them
+    ///
+    /// Faces stay open; [`close`](Self::close) or
+    /// [`shutdown`](Self::shutdown) them first to stop the forwarder cleanly.
+    pub fn stop_tasks(&self) -> Vec<JoinHandle<()>> {
+        let mut handles = self.tasks.cancel();
+        if let Some(server) = &self.server {
+            handles.extend(server.stop_tasks());
+        }
+        handles
+    }
+
+    /// Close the transport and all connections immediately, then stop its
+    /// background tasks
+    pub async fn close(&self) -> Result<(), TransportError> {
+        // Leave all topics, which also releases the publisher's reference
+        // to the transport
+        if let Some(pubsub) = self.pubsub.lock().await.take() {
+            pubsub.leave_all().await;
+        }
+        
         // Close all faces
         let faces = self.faces.read().await.clone();
         for face in faces {
@@ -275,56 +663,326 @@ impl NdnQuicTransport {
             server.stop().await?;
         }
         
+        self.stop_tasks();
         Ok(())
     }
 
+    /// Add faces accepted by the server to the transport's face list
+    fn track_server_faces(&self, server: &NdnQuicServer) {
+        let mut accepted = server.subscribe_faces();
+        let faces = Arc::clone(&self.faces);
+        let forwarder = self.forwarder.clone();
+        let orphaned_routes = Arc::clone(&self.orphaned_routes);
+        let event_sender = self.event_sender.clone();
+        let face_events = self.face_events.clone();
+        let config = self.config.clone();
+        let tasks = self.tasks.clone();
+        
+        self.tasks.spawn(async move {
+            loop {
+                let accepted = tokio::select! {
+                    _ = tasks.token().cancelled() => break,
+                    accepted = accepted.recv() => accepted,
+                };
+                match accepted {
+                    Ok(face) => {
+                        configure_face(&face, &config).await;
+                        if let Some(forwarder) = &forwarder {
+                            adopt_routes(forwarder, face.as_ref(), &orphaned_routes).await;
+                        }
+                        let face_id = face.id().to_string();
+                        let face: Arc<dyn NdnFace> = face;
+                        faces.write().await.push(Arc::clone(&face));
+                        pump_face_events(&tasks, face, face_events.clone());
+                        let _ = event_sender.send(TransportEvent::FaceCreated(face_id));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Missed {} accepted faces", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
     /// Process events from all faces
-    fn process_events(&self) {
+    fn process_events(&self, mut face_events: mpsc::Receiver<(Arc<dyn NdnFace>, FaceEvent)>) {
         let faces = Arc::clone(&self.faces);
         let event_sender = self.event_sender.clone();
+        let handlers = self.handlers.clone();
+        let forwarder = self.forwarder.clone();
+        let orphaned_routes = Arc::clone(&self.orphaned_routes);
+        let content_store = self.content_store.clone();
+        let draining = Arc::clone(&self.draining);
+        let active_handlers = Arc::clone(&self.active_handlers);
+        let handler_limiter = Arc::clone(&self.handler_limiter);
+        let tasks = self.tasks.clone();
         
-        tokio::spawn(async move {
+        self.tasks.spawn(async move {
+            // Every face pumps its events into this channel as they arrive
             loop {
+                let (face, event) = tokio::select! {
+                    _ = tasks.token().cancelled() => break,
+                    received = face_events.recv() => match received {
+                        Some(received) => received,
+                        None => break,
+                    },
+                };
                 // Get a snapshot of the current faces
                 let current_faces = faces.read().await.clone();
+                let face = &face;
                 
-                // Wait for events from any face
-                for face in &current_faces {
-                    if let Some(event) = face.next_event().await {
-                        match event {
-                            FaceEvent::InterestReceived(interest) => {
-                                let _ = event_sender.send(TransportEvent::InterestReceived {
-                                    interest,
-                                    face_id: face.id().to_string(),
-                                }).await;
+                match event {
+                    FaceEvent::InterestReceived(interest) => {
+                        if draining.load(Ordering::SeqCst) {
+                            // Congestion is transient: downstreams retry elsewhere
+                            debug!("Refusing Interest {} while draining", interest.name());
+                            if let Err(e) = face.send_nack(interest, NackReason::Congestion).await {
+                                warn!("Failed to Nack Interest on face {}: {}", face.id(), e);
+                            }
+                            continue;
+                        }
+                        
+                        if let Some(data) = content_store.as_ref().and_then(|cs| cs.lookup(&interest)) {
+                            trace!("Answering Interest {} from the content store", interest.name());
+                            if let Err(e) = face.send_data(data).await {
+                                warn!("Failed to send cached Data on face {}: {}", face.id(), e);
                             }
-                            FaceEvent::DataReceived(data) => {
-                                let _ = event_sender.send(TransportEvent::DataReceived {
-                                    data,
-                                    face_id: face.id().to_string(),
-                                }).await;
+                            continue;
+                        }
+                        
+                        if let Some((prefix, handler)) = handlers.lookup(interest.name()).await {
+                            let permit = match handler_limiter.try_acquire(&prefix) {
+                                Some(permit) => Some(permit),
+                                None => match handler_limiter.overflow() {
+                                    HandlerOverflow::Queue => None,
+                                    HandlerOverflow::Nack => {
+                                        debug!("Handler for {} is busy, Nacking Interest {}", prefix, interest.name());
+                                        if let Err(e) = face.send_nack(interest, NackReason::Congestion).await {
+                                            warn!("Failed to Nack Interest on face {}: {}", face.id(), e);
+                                        }
+                                        continue;
+                                    }
+                                    HandlerOverflow::Drop => {
+                                        debug!("Handler for {} is busy, dropping Interest {}", prefix, interest.name());
+                                        continue;
+                                    }
+                                },
+                            };
+                            
+                            debug!("Dispatching Interest {} to handler for {}", interest.name(), prefix);
+                            let face = Arc::clone(face);
+                            let content_store = content_store.clone();
+                            let active_handlers = Arc::clone(&active_handlers);
+                            let handler_limiter = Arc::clone(&handler_limiter);
+                            active_handlers.fetch_add(1, Ordering::SeqCst);
+                            // Handlers run to completion, even once cancelled
+                            tasks.spawn(async move {
+                                // Queued Interests wait here, not in the event loop
+                                let _permit = match permit {
+                                    Some(permit) => permit,
+                                    None => handler_limiter.acquire(&prefix).await,
+                                };
+                                let response = handler(interest.clone()).await;
+                                let result = match response {
+                                    ProducerResponse::Data(data) => {
+                                        cache_data(content_store.as_deref(), &data);
+                                        face.send_data(data).await
+                                    }
+                                    ProducerResponse::Nack(reason) => face.send_nack(interest, reason).await,
+                                    ProducerResponse::Drop => Ok(()),
+                                };
+                                if let Err(e) = result {
+                                    warn!("Failed to send handler response on face {}: {}", face.id(), e);
+                                }
+                                active_handlers.fetch_sub(1, Ordering::SeqCst);
+                            });
+                            continue;
+                        }
+                        
+                        if let Some(forwarder) = &forwarder {
+                            match forwarder.process_interest(interest.clone(), face, &current_faces).await {
+                                Ok(true) => continue,
+                                Ok(false) => {}
+                                Err(e) => {
+                                    warn!("Failed to forward Interest {}: {}", interest.name(), e);
+                                    continue;
+                                }
                             }
-                            FaceEvent::Closed => {
-                                let _ = event_sender.send(TransportEvent::FaceClosed(
-                                    face.id().to_string(),
-                                )).await;
-                                
-                                // Remove the face from our list
-                                let mut faces_write = faces.write().await;
-                                faces_write.retain(|f| f.id() != face.id());
+                        }
+                        
+                        let _ = event_sender.send(TransportEvent::InterestReceived {
+                            interest,
+                            face_id: face.id().to_string(),
+                        });
+                    }
+                    FaceEvent::DataReceived(data) => {
+                        // Only Data some Interest asked for is cached, so peers
+                        // cannot fill the store with Data of their choosing
+                        if let Some(forwarder) = &forwarder {
+                            if forwarder.process_data(&data, face, &current_faces).await {
+                                cache_data(content_store.as_deref(), &data);
+                                continue;
                             }
-                            FaceEvent::Error(error) => {
-                                let _ = event_sender.send(TransportEvent::Error(
-                                    format!("Face {}: {}", face.id(), error),
-                                )).await;
+                        }
+                        
+                        let _ = event_sender.send(TransportEvent::DataReceived {
+                            data,
+                            face_id: face.id().to_string(),
+                        });
+                    }
+                    FaceEvent::NackReceived(nack) => {
+                        if let Some(forwarder) = &forwarder {
+                            if forwarder.process_nack(&nack, face, &current_faces).await {
+                                continue;
                             }
                         }
+                        
+                        let _ = event_sender.send(TransportEvent::NackReceived {
+                            nack,
+                            face_id: face.id().to_string(),
+                        });
+                    }
+                    FaceEvent::Closed => {
+                        let _ = event_sender.send(TransportEvent::FaceClosed(
+                            face.id().to_string(),
+                        ));
+                        
+                        // Remove the face from our list
+                        let mut faces_write = faces.write().await;
+                        faces_write.retain(|f| !Arc::ptr_eq(f, face));
+                        let remaining = faces_write.clone();
+                        drop(faces_write);
+                        
+                        if let Some(forwarder) = &forwarder {
+                            release_routes(forwarder, face, &remaining, &orphaned_routes, &tasks).await;
+                        }
+                    }
+                    FaceEvent::Error(error) => {
+                        let _ = event_sender.send(TransportEvent::Error(
+                            format!("Face {}: {}", face.id(), error),
+                        ));
                     }
                 }
-                
-                // Sleep a bit to avoid busy-waiting
-                tokio::time::sleep(Duration::from_millis(10)).await;
             }
         });
     }
 }
+
+/// Pass the routes of a closed face on. Those of an authenticated peer go
+/// to another of its faces, or wait [`PEER_ROUTE_GRACE_PERIOD`] for one to
+/// connect; the others are dropped.
+async fn release_routes(
+    forwarder: &Arc<Forwarder>,
+    face: &Arc<dyn NdnFace>,
+    remaining: &[Arc<dyn NdnFace>],
+    orphaned_routes: &OrphanedRoutes,
+    tasks: &TaskSet,
+) {
+    let Some(peer_id) = face.peer_identity().map(PeerIdentity::face_id) else {
+        forwarder.remove_face(face.id()).await;
+        return;
+    };
+
+    let successor = remaining
+        .iter()
+        .find(|other| other.peer_identity().map(PeerIdentity::face_id).as_ref() == Some(&peer_id));
+    if let Some(successor) = successor {
+        debug!("Routes of face {} go to face {}", face.id(), successor.id());
+        forwarder.move_face(face.id(), successor.id()).await;
+        return;
+    }
+
+    // A face of the peer that closed earlier may still hold routes too
+    let face_id = face.id().to_string();
+    let previous = orphaned_routes.lock().await.insert(peer_id.clone(), face_id.clone());
+    if let Some(previous) = previous {
+        forwarder.move_face(&previous, &face_id).await;
+    }
+    debug!("Keeping the routes of face {} for {:?}", face_id, PEER_ROUTE_GRACE_PERIOD);
+
+    let forwarder = Arc::clone(forwarder);
+    let orphaned_routes = Arc::clone(orphaned_routes);
+    let token = tasks.token().clone();
+    tasks.spawn(async move {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(PEER_ROUTE_GRACE_PERIOD) => {}
+        }
+        let mut orphaned = orphaned_routes.lock().await;
+        if orphaned.get(&peer_id) == Some(&face_id) {
+            orphaned.remove(&peer_id);
+            drop(orphaned);
+            debug!("Peer of face {} did not reconnect, dropping its routes", face_id);
+            forwarder.remove_face(&face_id).await;
+        }
+    });
+}
+
+/// Hand the routes an authenticated peer left behind to its new face
+async fn adopt_routes(forwarder: &Forwarder, face: &dyn NdnFace, orphaned_routes: &OrphanedRoutes) {
+    let Some(peer_id) = face.peer_identity().map(PeerIdentity::face_id) else {
+        return;
+    };
+    let previous = orphaned_routes.lock().await.remove(&peer_id);
+    if let Some(previous) = previous {
+        debug!("Face {} takes over the routes of face {}", face.id(), previous);
+        forwarder.move_face(&previous, face.id()).await;
+    }
+}
+
+/// Forward a face's events into the transport's shared event channel until
+/// the face closes or the tasks are cancelled
+fn pump_face_events(tasks: &TaskSet, face: Arc<dyn NdnFace>, face_events: mpsc::Sender<(Arc<dyn NdnFace>, FaceEvent)>) {
+    let token = tasks.token().clone();
+    tasks.spawn(async move {
+        loop {
+            let event = tokio::select! {
+                _ = token.cancelled() => break,
+                event = face.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+            };
+            let closed = matches!(event, FaceEvent::Closed);
+            if face_events.send((Arc::clone(&face), event)).await.is_err() || closed {
+                break;
+            }
+        }
+        trace!("Stopped pumping events of face {}", face.id());
+    });
+}
+
+/// Apply the transport's per-face settings to a new face
+async fn configure_face(face: &Face, config: &TransportConfig) {
+    if let Some(mode) = config.packet_mode {
+        if let Err(e) = face.set_packet_mode(mode).await {
+            warn!("Keeping the default packet mode on face {}: {}", face.id(), e);
+        }
+    }
+    face.set_retransmission_policy(config.retransmission.clone()).await;
+}
+
+/// Insert Data into the content store, if there is one. Data that is
+/// never fresh, such as a command reply, is not cached.
+fn cache_data(content_store: Option<&ContentStore>, data: &Data) {
+    if data.ttl_ms == 0 {
+        return;
+    }
+    if let Some(cs) = content_store {
+        if let Err(e) = cs.insert(data.clone()) {
+            warn!("Failed to cache Data {}: {}", data.name(), e);
+        }
+    }
+}
+
+/// Node ID for a transport configured without one, unique enough to tell
+/// the nodes of a deployment apart
+fn generate_node_id() -> Name {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_nanos() as u64)
+        .unwrap_or(0);
+    let id = nanos ^ ((std::process::id() as u64) << 32);
+    Name::from_string(&format!("/udcn/node/{:016x}", id)).expect("generated node IDs are valid names")
+}
@@ -0,0 +1,258 @@
+//! UDP unicast faces.
+//!
+//! Each datagram carries exactly one NDN packet, as on NFD's UDP faces. A
+//! listener shares one socket among all its peers and creates a face the
+//! first time a new peer sends a packet.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use log::{debug, info, trace, warn};
+use rust_udcn_common::metrics::UdcnMetrics;
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::{
+    net::UdpSocket,
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
+
+use crate::{
+    error::{FaceError, TransportError},
+    face::is_loopback,
+    link::Link,
+    link_face::LinkFace,
+    MAX_PACKET_SIZE,
+};
+
+/// Port NFD listens on for UDP faces
+pub const NDN_UDP_PORT: u16 = 6363;
+
+/// Datagrams queued for a peer whose face is not keeping up
+const PEER_QUEUE_SIZE: usize = 256;
+
+/// Receive buffer size, large enough for any UDP datagram
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+type Peers = Arc<Mutex<HashMap<SocketAddr, mpsc::Sender<Bytes>>>>;
+
+/// Link to a peer over a socket connected to it
+struct ConnectedUdpLink {
+    socket: UdpSocket,
+}
+
+#[async_trait]
+impl Link for ConnectedUdpLink {
+    async fn send(&self, packet: Bytes) -> Result<(), FaceError> {
+        check_size(&packet)?;
+        self.socket.send(&packet).await?;
+        Ok(())
+    }
+
+    async fn recv(&self) -> Result<Option<Bytes>, FaceError> {
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        let len = self.socket.recv(&mut buf).await?;
+        buf.truncate(len);
+        Ok(Some(Bytes::from(buf)))
+    }
+
+    async fn close(&self) {}
+}
+
+/// Link to one peer of a listener's shared socket
+struct PeerUdpLink {
+    socket: Arc<UdpSocket>,
+    peer: SocketAddr,
+    incoming: Mutex<mpsc::Receiver<Bytes>>,
+    peers: Peers,
+}
+
+#[async_trait]
+impl Link for PeerUdpLink {
+    async fn send(&self, packet: Bytes) -> Result<(), FaceError> {
+        check_size(&packet)?;
+        self.socket.send_to(&packet, self.peer).await?;
+        Ok(())
+    }
+
+    async fn recv(&self) -> Result<Option<Bytes>, FaceError> {
+        Ok(self.incoming.lock().await.recv().await)
+    }
+
+    async fn close(&self) {
+        // Datagrams from the peer will create a new face from now on
+        self.peers.lock().await.remove(&self.peer);
+    }
+}
+
+/// Packets are not fragmented, so each must fit into one datagram
+fn check_size(packet: &[u8]) -> Result<(), FaceError> {
+    if packet.len() > MAX_PACKET_SIZE {
+        return Err(FaceError::TooLarge {
+            size: packet.len(),
+            limit: MAX_PACKET_SIZE,
+        });
+    }
+    Ok(())
+}
+
+/// Listener creating a face for every peer that sends to its socket
+#[derive(Debug)]
+pub struct UdpFaceListener {
+    /// Address the shared socket is bound to
+    local_addr: SocketAddr,
+    
+    /// Faces for peers seen for the first time
+    new_faces: Mutex<mpsc::Receiver<Arc<LinkFace>>>,
+    
+    /// Task reading from the shared socket
+    receiver_task: JoinHandle<()>,
+    
+    /// Metrics shared by all faces
+    metrics: Arc<UdcnMetrics>,
+}
+
+impl UdpFaceListener {
+    /// Listen on `addr`
+    pub async fn bind(addr: SocketAddr) -> Result<Self, TransportError> {
+        let socket = UdpSocket::bind(addr).await.map_err(|source| TransportError::Bind {
+            addr: addr.to_string(),
+            source,
+        })?;
+        let local_addr = socket.local_addr()?;
+        info!("Listening for UDP faces on {}", local_addr);
+
+        let metrics = Arc::new(UdcnMetrics::new());
+        let (face_sender, new_faces) = mpsc::channel(16);
+        let receiver_task = tokio::spawn(Self::demultiplex(Arc::new(socket), face_sender, Arc::clone(&metrics)));
+
+        Ok(Self {
+            local_addr,
+            new_faces: Mutex::new(new_faces),
+            receiver_task,
+            metrics,
+        })
+    }
+
+    /// Get the local address the listener is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Get the metrics shared by the faces
+    pub fn metrics(&self) -> Arc<UdcnMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Wait for a packet from a new peer and return its face
+    pub async fn accept(&self) -> Result<Arc<LinkFace>, TransportError> {
+        self.new_faces
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| TransportError::Stopped(format!("UDP listener on {}", self.local_addr)))
+    }
+
+    /// Route datagrams from the shared socket to the face of their sender
+    async fn demultiplex(socket: Arc<UdpSocket>, new_faces: mpsc::Sender<Arc<LinkFace>>, metrics: Arc<UdcnMetrics>) {
+        let peers: Peers = Arc::new(Mutex::new(HashMap::new()));
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+
+        loop {
+            let (len, peer) = match socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    // ICMP errors for one peer must not stop the others
+                    trace!("UDP receive error: {}", e);
+                    continue;
+                }
+            };
+            let datagram = Bytes::copy_from_slice(&buf[..len]);
+
+            let mut table = peers.lock().await;
+            if let Some(sender) = table.get(&peer) {
+                if sender.try_send(datagram).is_err() {
+                    debug!("[Face udp://{}] Dropping datagram, face is not keeping up", peer);
+                }
+                continue;
+            }
+
+            let (sender, incoming) = mpsc::channel(PEER_QUEUE_SIZE);
+            let _ = sender.try_send(datagram);
+            table.insert(peer, sender);
+            drop(table);
+
+            let link = PeerUdpLink {
+                socket: Arc::clone(&socket),
+                peer,
+                incoming: Mutex::new(incoming),
+                peers: Arc::clone(&peers),
+            };
+            let face_id = format!("udp://{}", peer);
+            debug!("[Face {}] New UDP peer", face_id);
+            let face = LinkFace::new(face_id, Arc::new(link), Arc::clone(&metrics)).with_local(is_loopback(peer.ip()));
+            let face = Arc::new(face);
+
+            if new_faces.send(face).await.is_err() {
+                warn!("UDP listener dropped, no longer accepting peers");
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for UdpFaceListener {
+    fn drop(&mut self) {
+        self.receiver_task.abort();
+    }
+}
+
+/// Open a UDP face to the router at `addr`
+pub async fn connect_udp(addr: SocketAddr) -> Result<LinkFace, TransportError> {
+    let local: SocketAddr = if addr.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(addr).await.map_err(|source| TransportError::Connect {
+        addr: addr.to_string(),
+        source,
+    })?;
+    let face_id = format!("udp://{}", addr);
+
+    let face = LinkFace::new(face_id, Arc::new(ConnectedUdpLink { socket }), Arc::new(UdcnMetrics::new()));
+    Ok(face.with_local(is_loopback(addr.ip())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face::FaceEvent;
+    use rust_udcn_common::ndn::{Data, Interest, Name};
+
+    #[tokio::test]
+    async fn test_udp_face_exchange() {
+        let listener = UdpFaceListener::bind((Ipv4Addr::LOCALHOST, 0).into()).await.unwrap();
+        let client = connect_udp(listener.local_addr()).await.unwrap();
+
+        let name = Name::from_string("/udp/test").unwrap();
+        let fetch = tokio::spawn(async move { client.express_interest(Interest::new(name), 1000).await });
+
+        let server = listener.accept().await.unwrap();
+        match server.next_event().await {
+            Some(FaceEvent::InterestReceived(interest)) => {
+                let data = Data::new(interest.name().clone(), b"pong".to_vec());
+                server.send_data(data).await.unwrap();
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        let data = fetch.await.unwrap().unwrap();
+        assert_eq!(data.content().as_ref(), b"pong");
+        assert_eq!(listener.metrics().interests_received.value(), 1);
+    }
+}
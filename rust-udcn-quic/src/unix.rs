@@ -0,0 +1,150 @@
+//! NFD-compatible Unix domain socket faces.
+//!
+//! Local NDN applications such as ndnpeek and ndncatchunks connect to the
+//! forwarder over a Unix stream socket and exchange bare NDN-TLV packets.
+//! Listening on the same path NFD uses lets them reach μDCN without QUIC.
+
+use log::{debug, info};
+use rust_udcn_common::metrics::UdcnMetrics;
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::{error::TransportError, link::StreamLink, link_face::LinkFace};
+
+/// Socket path NDN client libraries connect to by default
+pub const DEFAULT_UNIX_SOCKET_PATH: &str = "/run/nfd/nfd.sock";
+
+/// Listener accepting local applications on a Unix socket
+#[derive(Debug)]
+pub struct UnixFaceListener {
+    /// Path the socket is bound to
+    path: PathBuf,
+    
+    /// The listening socket
+    listener: UnixListener,
+    
+    /// Sequence number for face IDs
+    next_face: AtomicU64,
+    
+    /// Metrics shared by all accepted faces
+    metrics: Arc<UdcnMetrics>,
+}
+
+impl UnixFaceListener {
+    /// Bind to `path`, replacing a stale socket left by a previous run
+    pub fn bind(path: impl AsRef<Path>) -> Result<Self, TransportError> {
+        let path = path.as_ref().to_path_buf();
+        let bind_error = |source| TransportError::Bind {
+            addr: path.display().to_string(),
+            source,
+        };
+
+        match fs::symlink_metadata(&path) {
+            Ok(meta) if meta.file_type().is_socket() => {
+                if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                    return Err(bind_error(io::Error::new(ErrorKind::AddrInUse, "socket is in use")));
+                }
+                debug!("Removing stale socket {}", path.display());
+                fs::remove_file(&path).map_err(bind_error)?;
+            }
+            Ok(_) => return Err(bind_error(io::Error::new(ErrorKind::AlreadyExists, "not a socket"))),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(bind_error(e)),
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(bind_error)?;
+        }
+
+        let listener = UnixListener::bind(&path).map_err(bind_error)?;
+        info!("Listening for local applications on {}", path.display());
+
+        Ok(Self {
+            path,
+            listener,
+            next_face: AtomicU64::new(0),
+            metrics: Arc::new(UdcnMetrics::new()),
+        })
+    }
+
+    /// Get the socket path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the metrics shared by the accepted faces
+    pub fn metrics(&self) -> Arc<UdcnMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Wait for the next application to connect
+    pub async fn accept(&self) -> Result<Arc<LinkFace>, TransportError> {
+        let (stream, _) = self.listener.accept().await?;
+        let n = self.next_face.fetch_add(1, Ordering::Relaxed);
+        let face_id = format!("unix://{}#{}", self.path.display(), n);
+        debug!("[Face {}] Accepted local application", face_id);
+
+        let link = Arc::new(StreamLink::new(stream));
+        Ok(Arc::new(LinkFace::new(face_id, link, Arc::clone(&self.metrics)).with_local(true)))
+    }
+}
+
+impl Drop for UnixFaceListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Connect to a forwarder listening on the Unix socket at `path`
+pub async fn connect_unix(path: impl AsRef<Path>) -> Result<LinkFace, TransportError> {
+    let path = path.as_ref();
+    let stream = UnixStream::connect(path).await.map_err(|source| TransportError::Connect {
+        addr: path.display().to_string(),
+        source,
+    })?;
+    let face_id = format!("unix://{}", path.display());
+
+    Ok(LinkFace::new(face_id, Arc::new(StreamLink::new(stream)), Arc::new(UdcnMetrics::new())).with_local(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face::FaceEvent;
+    use rust_udcn_common::ndn::{Interest, Name, NackReason};
+
+    #[tokio::test]
+    async fn test_unix_face_exchange() {
+        let path = std::env::temp_dir().join(format!("udcn-test-{}.sock", std::process::id()));
+        let listener = UnixFaceListener::bind(&path).unwrap();
+
+        let client = connect_unix(&path).await.unwrap();
+        let server = listener.accept().await.unwrap();
+
+        let name = Name::from_string("/local/app").unwrap();
+        client.send_interest(Interest::new(name.clone())).await.unwrap();
+        match server.next_event().await {
+            Some(FaceEvent::InterestReceived(interest)) => {
+                server.send_nack(interest, NackReason::NoRoute).await.unwrap();
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert!(matches!(client.next_event().await, Some(FaceEvent::NackReceived(_))));
+
+        // Binding again over a stale socket succeeds once the listener is gone
+        drop(listener);
+        assert!(!path.exists());
+        std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let listener = UnixFaceListener::bind(&path).unwrap();
+        drop(listener);
+    }
+}
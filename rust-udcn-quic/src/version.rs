@@ -0,0 +1,82 @@
+//! Wire protocol versions negotiated through ALPN.
+//!
+//! Each version is identified by its ALPN string and determines how packets
+//! may be framed on the connection, so the wire format can evolve while old
+//! peers keep talking the version they know.
+
+use crate::{face::PacketMode, NDN_QUIC_ALPN};
+
+/// ALPN string of the µDCN wire format with framed streams and fragments
+pub const UDCN2_ALPN: &[u8] = b"udcn2";
+
+/// A negotiated wire protocol version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProtocolVersion {
+    /// One NDN packet per stream or datagram
+    Ndn1,
+
+    /// Adds length-prefixed persistent streams and fragment headers
+    Udcn2,
+}
+
+impl ProtocolVersion {
+    /// All supported versions, most preferred first
+    pub const ALL: [ProtocolVersion; 2] = [ProtocolVersion::Udcn2, ProtocolVersion::Ndn1];
+
+    /// ALPN string identifying this version
+    pub fn alpn(self) -> &'static [u8] {
+        match self {
+            ProtocolVersion::Ndn1 => NDN_QUIC_ALPN,
+            ProtocolVersion::Udcn2 => UDCN2_ALPN,
+        }
+    }
+
+    /// Version identified by an ALPN string
+    pub fn from_alpn(alpn: &[u8]) -> Option<Self> {
+        Self::ALL.into_iter().find(|version| version.alpn() == alpn)
+    }
+
+    /// Packet mode used on new faces of this version
+    pub fn default_packet_mode(self) -> PacketMode {
+        match self {
+            ProtocolVersion::Ndn1 => PacketMode::Stream,
+            ProtocolVersion::Udcn2 => PacketMode::PersistentStream,
+        }
+    }
+
+    /// Whether peers of this version understand packets sent in `mode`
+    pub fn supports(self, mode: PacketMode) -> bool {
+        match self {
+            ProtocolVersion::Ndn1 => mode != PacketMode::PersistentStream,
+            ProtocolVersion::Udcn2 => true,
+        }
+    }
+}
+
+impl std::fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&String::from_utf8_lossy(self.alpn()))
+    }
+}
+
+/// ALPN strings of all supported versions, most preferred first
+pub fn default_alpn_protocols() -> Vec<Vec<u8>> {
+    ProtocolVersion::ALL.iter().map(|version| version.alpn().to_vec()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alpn_round_trip() {
+        for version in ProtocolVersion::ALL {
+            assert_eq!(ProtocolVersion::from_alpn(version.alpn()), Some(version));
+        }
+        assert_eq!(ProtocolVersion::from_alpn(b"h3"), None);
+        assert_eq!(ProtocolVersion::Udcn2.to_string(), "udcn2");
+
+        assert!(!ProtocolVersion::Ndn1.supports(PacketMode::PersistentStream));
+        assert!(ProtocolVersion::Ndn1.supports(ProtocolVersion::Ndn1.default_packet_mode()));
+    }
+}
@@ -0,0 +1,163 @@
+//! WebSocket gateway faces for browser applications.
+//!
+//! Browsers cannot open raw sockets, so NDN libraries running in them (such
+//! as NDNts) connect over WebSocket instead and send each packet as one
+//! binary message, the same convention NFD's WebSocket faces use.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, StreamExt,
+};
+use log::{debug, info, trace};
+use rust_udcn_common::metrics::UdcnMetrics;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::Mutex,
+};
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+
+use crate::{
+    error::{FaceError, TransportError},
+    face::is_loopback,
+    link::Link,
+    link_face::LinkFace,
+};
+
+/// Port NFD listens on for WebSocket faces
+pub const NDN_WS_PORT: u16 = 9696;
+
+/// Link carrying one NDN packet per binary WebSocket message
+pub struct WebSocketLink<S> {
+    /// Incoming messages
+    reader: Mutex<SplitStream<WebSocketStream<S>>>,
+    
+    /// Outgoing messages
+    writer: Mutex<SplitSink<WebSocketStream<S>, Message>>,
+}
+
+impl<S> WebSocketLink<S>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    /// Wrap an established WebSocket connection
+    pub fn new(socket: WebSocketStream<S>) -> Self {
+        let (writer, reader) = socket.split();
+        Self {
+            reader: Mutex::new(reader),
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> Link for WebSocketLink<S>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    async fn send(&self, packet: Bytes) -> Result<(), FaceError> {
+        self.writer.lock().await.send(Message::Binary(packet.to_vec())).await?;
+        Ok(())
+    }
+
+    async fn recv(&self) -> Result<Option<Bytes>, FaceError> {
+        let mut reader = self.reader.lock().await;
+        while let Some(message) = reader.next().await {
+            match message? {
+                Message::Binary(packet) => return Ok(Some(Bytes::from(packet))),
+                Message::Close(_) => break,
+                // Pings are answered by the WebSocket layer itself
+                other => trace!("Ignoring WebSocket message: {:?}", other),
+            }
+        }
+        Ok(None)
+    }
+
+    async fn close(&self) {
+        if let Err(e) = self.writer.lock().await.close().await {
+            trace!("Error closing WebSocket: {}", e);
+        }
+    }
+}
+
+/// Listener accepting browser applications over WebSocket
+#[derive(Debug)]
+pub struct WebSocketFaceListener {
+    /// The listening socket
+    listener: TcpListener,
+    
+    /// Metrics shared by all accepted faces
+    metrics: Arc<UdcnMetrics>,
+}
+
+impl WebSocketFaceListener {
+    /// Listen on `addr`
+    pub async fn bind(addr: SocketAddr) -> Result<Self, TransportError> {
+        let listener = TcpListener::bind(addr).await.map_err(|source| TransportError::Bind {
+            addr: addr.to_string(),
+            source,
+        })?;
+        info!("Listening for WebSocket faces on {}", listener.local_addr()?);
+
+        Ok(Self {
+            listener,
+            metrics: Arc::new(UdcnMetrics::new()),
+        })
+    }
+
+    /// Get the local address the listener is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr, TransportError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Get the metrics shared by the accepted faces
+    pub fn metrics(&self) -> Arc<UdcnMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Wait for the next application to connect and complete its handshake
+    pub async fn accept(&self) -> Result<Arc<LinkFace>, TransportError> {
+        let (stream, peer) = self.listener.accept().await?;
+        stream.set_nodelay(true)?;
+        let socket = tokio_tungstenite::accept_async(stream).await.map_err(FaceError::from)?;
+        let face_id = format!("ws://{}", peer);
+        debug!("[Face {}] Accepted WebSocket connection", face_id);
+
+        let link = Arc::new(WebSocketLink::new(socket));
+        let face = LinkFace::new(face_id, link, Arc::clone(&self.metrics)).with_local(is_loopback(peer.ip()));
+        Ok(Arc::new(face))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face::FaceEvent;
+    use rust_udcn_common::ndn::{Data, Interest, Name};
+    use tokio_tungstenite::tungstenite::protocol::Role;
+
+    #[tokio::test]
+    async fn test_websocket_face_exchange() {
+        let (a, b) = tokio::io::duplex(4096);
+        let (client, server) = tokio::join!(
+            WebSocketStream::from_raw_socket(a, Role::Client, None),
+            WebSocketStream::from_raw_socket(b, Role::Server, None),
+        );
+        let metrics = Arc::new(UdcnMetrics::new());
+        let client = LinkFace::new("client".to_string(), Arc::new(WebSocketLink::new(client)), Arc::clone(&metrics));
+        let server = LinkFace::new("server".to_string(), Arc::new(WebSocketLink::new(server)), metrics);
+
+        let name = Name::from_string("/browser/app").unwrap();
+        client.send_interest(Interest::new(name.clone())).await.unwrap();
+        assert!(matches!(server.next_event().await, Some(FaceEvent::InterestReceived(_))));
+
+        server.send_data(Data::new(name, b"hi".to_vec())).await.unwrap();
+        assert!(matches!(client.next_event().await, Some(FaceEvent::DataReceived(_))));
+
+        server.close().await;
+        assert!(matches!(client.next_event().await, Some(FaceEvent::Closed)));
+    }
+}
@@ -14,6 +14,15 @@ fn main() {
     if profile == "release" {
         cmd.arg("--release");
     }
+
+    // Forward the eBPF program features enabled on this crate
+    let features: Vec<&str> = ["tcp", "gtpu", "vxlan"]
+        .into_iter()
+        .filter(|feature| env::var_os(format!("CARGO_FEATURE_{}", feature.to_uppercase())).is_some())
+        .collect();
+    if !features.is_empty() {
+        cmd.arg("--features").arg(features.join(","));
+    }
     let status = cmd
         .current_dir(&ebpf_dir)
         .status()
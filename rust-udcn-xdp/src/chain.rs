@@ -0,0 +1,219 @@
+//! Sharing interfaces with other XDP programs.
+//!
+//! An interface runs one XDP program at a time. When another tool already
+//! attached one, udcn can take the interface over and chain that program
+//! behind its own: the entry program tail calls it, through the CHAIN
+//! program array, for every packet that is not NDN. The chained program is
+//! pinned next to the XDP links, so it is attached back once udcn detaches.
+//!
+//! The chained program must be a plain XDP program, attached through
+//! netlink; one attached through a BPF link belongs to the tool holding the
+//! link and cannot be taken over.
+
+use log::{info, warn};
+use std::{
+    ffi::CString,
+    fs, io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Result, XdpError},
+    netlink::{self, LinkXdp},
+    xsk::interface_index,
+    XdpMode,
+};
+
+const CHAIN_NAME: &str = "CHAIN";
+
+const BPF_MAP_UPDATE_ELEM: libc::c_long = 2;
+const BPF_MAP_DELETE_ELEM: libc::c_long = 3;
+const BPF_OBJ_PIN: libc::c_long = 6;
+const BPF_OBJ_GET: libc::c_long = 7;
+const BPF_PROG_GET_FD_BY_ID: libc::c_long = 13;
+
+/// Attributes of the map element commands
+#[repr(C)]
+#[derive(Default)]
+struct MapElemAttr {
+    map_fd: u32,
+    _pad: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+/// Attributes of the pinning commands
+#[repr(C)]
+#[derive(Default)]
+struct ObjAttr {
+    pathname: u64,
+    bpf_fd: u32,
+    file_flags: u32,
+}
+
+/// Attributes of BPF_PROG_GET_FD_BY_ID
+#[repr(C)]
+#[derive(Default)]
+struct GetIdAttr {
+    id: u32,
+    next_id: u32,
+    open_flags: u32,
+}
+
+/// The programs udcn took interfaces over from
+pub struct Chain {
+    /// The CHAIN program array, by interface index
+    programs: OwnedFd,
+
+    /// Where the chained programs are pinned
+    pin_path: PathBuf,
+}
+
+impl Chain {
+    /// Open the CHAIN map pinned under `pin_path`
+    pub fn open(pin_path: &Path) -> Result<Self> {
+        let map_path = pin_path.join(CHAIN_NAME);
+        let programs = obj_get(&map_path)
+            .map_err(|e| XdpError::syscall(format!("Failed to open map {}", map_path.display()), e))?;
+        Ok(Self {
+            programs,
+            pin_path: pin_path.to_path_buf(),
+        })
+    }
+
+    /// Take an interface over from the program attached to it, which then
+    /// runs after udcn for packets that are not NDN
+    ///
+    /// The interface runs no XDP program once this returns; the caller is
+    /// expected to attach udcn's right away.
+    pub fn take_over(&mut self, interface_name: &str, existing: LinkXdp) -> Result<()> {
+        if existing.mode == XdpMode::Multi || existing.prog_id == 0 {
+            return Err(XdpError::Unchainable(interface_name.to_string()));
+        }
+        let ifindex = interface_index(interface_name)?;
+
+        let program = prog_get_fd_by_id(existing.prog_id)
+            .map_err(|e| XdpError::syscall(format!("Failed to open XDP program {}", existing.prog_id), e))?;
+        let path = self.program_path(interface_name);
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| XdpError::syscall(format!("Failed to remove {}", path.display()), e))?;
+        }
+        obj_pin(&program, &path)
+            .map_err(|e| XdpError::syscall(format!("Failed to pin XDP program {}", existing.prog_id), e))?;
+
+        let result = map_update(&self.programs, ifindex, program.as_raw_fd())
+            .map_err(|e| XdpError::syscall("Failed to chain XDP program", e))
+            .and_then(|()| {
+                netlink::set_link_xdp(ifindex, -1, existing.mode.to_aya_flags().bits()).map_err(|e| {
+                    XdpError::syscall(
+                        format!(
+                            "Failed to detach XDP program {} from interface {}, is it attached through a BPF link?",
+                            existing.prog_id, interface_name
+                        ),
+                        e,
+                    )
+                })
+            });
+        if let Err(e) = result {
+            let _ = map_delete(&self.programs, ifindex);
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+
+        info!(
+            "Took interface {} over from XDP program {}, which now runs after udcn",
+            interface_name, existing.prog_id
+        );
+        Ok(())
+    }
+
+    /// Attach the program udcn took an interface over from back to it,
+    /// once udcn detached; returns whether there was one
+    pub fn restore(&mut self, interface_name: &str) -> Result<bool> {
+        let path = self.program_path(interface_name);
+        if !path.exists() {
+            return Ok(false);
+        }
+
+        let program = obj_get(&path).map_err(|e| XdpError::syscall(format!("Failed to open {}", path.display()), e))?;
+        // Gone if the interface was removed; the program is then just dropped
+        match interface_index(interface_name) {
+            Ok(ifindex) => {
+                let _ = map_delete(&self.programs, ifindex);
+                netlink::set_link_xdp(ifindex, program.as_raw_fd(), 0).map_err(|e| {
+                    XdpError::syscall(format!("Failed to attach chained XDP program back to {}", interface_name), e)
+                })?;
+                info!("Attached chained XDP program back to interface {}", interface_name);
+            }
+            Err(e) => warn!("Dropping chained XDP program of {}: {}", interface_name, e),
+        }
+        fs::remove_file(&path).map_err(|e| XdpError::syscall(format!("Failed to remove {}", path.display()), e))?;
+        Ok(true)
+    }
+
+    /// Path of the pinned program chained on an interface
+    fn program_path(&self, interface_name: &str) -> PathBuf {
+        self.pin_path.join(format!("chained_{}", interface_name))
+    }
+}
+
+/// Run a bpf(2) command
+fn bpf<T>(cmd: libc::c_long, attr: &mut T) -> io::Result<libc::c_long> {
+    let ret = unsafe { libc::syscall(libc::SYS_bpf, cmd, attr as *mut T, mem::size_of::<T>()) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ret)
+}
+
+fn map_update(map: &OwnedFd, key: u32, value: RawFd) -> io::Result<()> {
+    let value = value as u32;
+    let mut attr = MapElemAttr {
+        map_fd: map.as_raw_fd() as u32,
+        key: &key as *const u32 as u64,
+        value: &value as *const u32 as u64,
+        ..Default::default()
+    };
+    bpf(BPF_MAP_UPDATE_ELEM, &mut attr).map(|_| ())
+}
+
+fn map_delete(map: &OwnedFd, key: u32) -> io::Result<()> {
+    let mut attr = MapElemAttr {
+        map_fd: map.as_raw_fd() as u32,
+        key: &key as *const u32 as u64,
+        ..Default::default()
+    };
+    bpf(BPF_MAP_DELETE_ELEM, &mut attr).map(|_| ())
+}
+
+fn obj_pin(fd: &OwnedFd, path: &Path) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_encoded_bytes())?;
+    let mut attr = ObjAttr {
+        pathname: path.as_ptr() as u64,
+        bpf_fd: fd.as_raw_fd() as u32,
+        ..Default::default()
+    };
+    bpf(BPF_OBJ_PIN, &mut attr)?;
+    Ok(())
+}
+
+fn obj_get(path: &Path) -> io::Result<OwnedFd> {
+    let path = CString::new(path.as_os_str().as_encoded_bytes())?;
+    let mut attr = ObjAttr {
+        pathname: path.as_ptr() as u64,
+        ..Default::default()
+    };
+    let fd = bpf(BPF_OBJ_GET, &mut attr)?;
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+fn prog_get_fd_by_id(id: u32) -> io::Result<OwnedFd> {
+    let mut attr = GetIdAttr {
+        id,
+        ..Default::default()
+    };
+    let fd = bpf(BPF_PROG_GET_FD_BY_ID, &mut attr)?;
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
@@ -0,0 +1,78 @@
+//! Timestamps of the eBPF programs.
+//!
+//! The kernel stamps PIT and CS entries with `bpf_ktime_get_ns()` in
+//! milliseconds: CLOCK_MONOTONIC time since boot, which does not count
+//! suspend and means nothing outside this host. This module reads the same
+//! clock, so userspace writes timestamps the kernel can compare, and
+//! converts them to and from wall-clock time.
+
+use std::time::{Duration, SystemTime};
+
+/// Current time of the kernel clock, in milliseconds
+pub fn kernel_now_ms() -> u64 {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: ts is a valid timespec to write to
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000 + ts.tv_nsec as u64 / 1_000_000
+}
+
+/// Wall-clock time of a kernel timestamp
+pub fn to_system_time(timestamp_ms: u64) -> SystemTime {
+    convert_to_system(timestamp_ms, kernel_now_ms(), SystemTime::now())
+}
+
+/// Kernel timestamp of a wall-clock time, clamped to the boot time
+pub fn from_system_time(time: SystemTime) -> u64 {
+    convert_from_system(time, kernel_now_ms(), SystemTime::now())
+}
+
+/// Whether `lifetime_ms` has passed since `timestamp_ms`, the way the
+/// kernel decides it
+pub fn is_expired(timestamp_ms: u64, lifetime_ms: u64, now_ms: u64) -> bool {
+    now_ms.saturating_sub(timestamp_ms) > lifetime_ms
+}
+
+fn convert_to_system(timestamp_ms: u64, now_ms: u64, now: SystemTime) -> SystemTime {
+    if timestamp_ms <= now_ms {
+        now - Duration::from_millis(now_ms - timestamp_ms)
+    } else {
+        now + Duration::from_millis(timestamp_ms - now_ms)
+    }
+}
+
+fn convert_from_system(time: SystemTime, now_ms: u64, now: SystemTime) -> u64 {
+    match now.duration_since(time) {
+        Ok(ago) => now_ms.saturating_sub(ago.as_millis() as u64),
+        Err(e) => now_ms + e.duration().as_millis() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversion_round_trip() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let now_ms = 5_000_000;
+
+        let past = convert_to_system(now_ms - 1_500, now_ms, now);
+        assert_eq!(past, now - Duration::from_millis(1_500));
+        assert_eq!(convert_from_system(past, now_ms, now), now_ms - 1_500);
+
+        let future = convert_to_system(now_ms + 250, now_ms, now);
+        assert_eq!(convert_from_system(future, now_ms, now), now_ms + 250);
+
+        // Nothing happened before boot
+        let before_boot = now - Duration::from_secs(10_000);
+        assert_eq!(convert_from_system(before_boot, now_ms, now), 0);
+    }
+
+    #[test]
+    fn test_expiry() {
+        assert!(!is_expired(1_000, 4_000, 5_000));
+        assert!(is_expired(1_000, 4_000, 5_001));
+        // Written after the clock was read
+        assert!(!is_expired(6_000, 4_000, 5_000));
+    }
+}
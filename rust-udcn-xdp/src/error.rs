@@ -0,0 +1,135 @@
+//! Errors of the XDP manager and its tables.
+//!
+//! Loading, attaching and driving the XDP program fails with an
+//! [`XdpError`] telling which step failed, so callers can tell a missing
+//! interface from one another tool holds without parsing messages.
+
+use aya::{
+    maps::MapError,
+    pin::PinError,
+    programs::{links::LinkError, ProgramError},
+    BpfError,
+};
+use rust_udcn_common::ndn::Name;
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+use crate::loader::XdpMode;
+
+/// Result of the operations of this crate
+pub type Result<T> = std::result::Result<T, XdpError>;
+
+/// Why the XDP program or one of its maps could not be used
+#[derive(Debug, Error)]
+pub enum XdpError {
+    /// The eBPF object file could not be read
+    #[error("Failed to read eBPF object {}: {source}", path.display())]
+    ReadObject { path: PathBuf, source: io::Error },
+
+    /// The kernel refused the eBPF object
+    #[error("Failed to load eBPF object: {0}")]
+    Load(#[from] BpfError),
+
+    /// The eBPF object has no program of that name
+    #[error("Failed to find program '{0}'")]
+    ProgramNotFound(String),
+
+    /// The eBPF object has no map of that name
+    #[error("Failed to find map '{0}'")]
+    MapNotFound(&'static str),
+
+    /// A program could not be loaded or detached
+    #[error(transparent)]
+    Program(#[from] ProgramError),
+
+    /// The kernel verifier refused to load a program
+    #[error("Verifier rejected program '{program}':\n{log}")]
+    VerifierRejected { program: String, log: String },
+
+    /// The program could not be attached to an interface
+    #[error("Failed to attach to interface {interface}: {source}")]
+    Attach {
+        interface: String,
+        #[source]
+        source: ProgramError,
+    },
+
+    /// The interface runs an XDP program of another tool, and chaining
+    /// was not asked for
+    #[error("Interface {interface} already runs XDP program {prog_id} in {mode} mode; chain it to share the interface")]
+    InterfaceBusy { interface: String, prog_id: u32, mode: XdpMode },
+
+    /// The interface runs several XDP programs, which cannot be chained
+    #[error("Interface {0} runs several XDP programs, which cannot be chained")]
+    Unchainable(String),
+
+    /// The XDP program is not attached to the interface
+    #[error("XDP program is not attached to interface {0}")]
+    NotAttached(String),
+
+    /// No interface has this name
+    #[error("Unknown interface {0}")]
+    UnknownInterface(String),
+
+    /// A map could not be opened, read or updated
+    #[error(transparent)]
+    Map(#[from] MapError),
+
+    /// A pinned XDP link could not be opened or updated
+    #[error(transparent)]
+    Link(#[from] LinkError),
+
+    /// An XDP link could not be pinned
+    #[error(transparent)]
+    Pin(#[from] PinError),
+
+    /// The prefix is too long for the kernel FIB to match
+    #[error("Prefix {prefix} is longer than the {limit} {unit} the kernel FIB matches")]
+    PrefixTooLong { prefix: Name, limit: usize, unit: &'static str },
+
+    /// The FIB entry of a prefix could not be updated
+    #[error("Failed to update the FIB entry of {prefix}: {source}")]
+    FibUpdate { prefix: Name, source: Box<XdpError> },
+
+    /// Some updates of a batch were not applied to the FIB
+    #[error("{failed} of {total} FIB updates failed")]
+    PartialUpdate { failed: usize, total: usize },
+
+    /// A setting or size is out of range
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    /// The kernel gave no usable answer to a netlink request
+    #[error("Netlink error: {0}")]
+    Netlink(String),
+
+    /// A system call failed
+    #[error("{what}: {source}")]
+    Syscall { what: String, source: io::Error },
+
+    /// A file or socket failed
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl XdpError {
+    /// Error of a system call failing while doing `what`
+    pub(crate) fn syscall(what: impl Into<String>, source: io::Error) -> Self {
+        XdpError::Syscall {
+            what: what.into(),
+            source,
+        }
+    }
+
+    /// Error of loading `program` into the kernel, with the log of the
+    /// verifier when it was the one refusing
+    pub(crate) fn program_load(program: impl Into<String>, source: ProgramError) -> Self {
+        match source {
+            ProgramError::LoadError { verifier_log, .. } => XdpError::VerifierRejected {
+                program: program.into(),
+                log: verifier_log.to_string(),
+            },
+            source => XdpError::Program(source),
+        }
+    }
+}
@@ -0,0 +1,428 @@
+//! Network interface hotplug monitoring.
+//!
+//! Interfaces come and go: NICs reset, VMs get devices hot-added, USB
+//! adapters are unplugged. `LinkMonitor` listens to the rtnetlink link
+//! group for these changes, and `watch_interfaces` keeps the XDP program
+//! attached to the interfaces matching a set of name patterns.
+
+use log::{debug, warn};
+use std::{
+    collections::HashMap,
+    fs, io,
+    os::fd::{AsRawFd, OwnedFd},
+    sync::Arc,
+};
+use tokio::{
+    io::unix::AsyncFd,
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
+
+use crate::{
+    error::{Result, XdpError},
+    netlink::{self, IFINFOMSG_SIZE, IFLA_IFNAME, RECV_BUFFER_SIZE},
+    XdpManager,
+};
+
+/// Where the interfaces of the host are listed
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// What happened to an interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkEventKind {
+    /// The interface appeared
+    Added,
+    /// The interface was brought up
+    Up,
+    /// The interface was brought down
+    Down,
+    /// The interface disappeared
+    Removed,
+}
+
+/// A change of a network interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkEvent {
+    /// Interface index
+    pub ifindex: u32,
+    /// Interface name
+    pub name: String,
+    /// What happened
+    pub kind: LinkEventKind,
+}
+
+/// What the interface watcher did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotplugEvent {
+    /// An interface changed
+    Link(LinkEvent),
+    /// The XDP program was attached to an interface
+    Attached(String),
+    /// The XDP program could not be attached to an interface
+    AttachFailed {
+        /// Interface name
+        interface: String,
+        /// Why attaching failed
+        error: String,
+    },
+    /// The XDP program was detached from a removed interface
+    Detached(String),
+}
+
+/// A link message from the kernel
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LinkMessage {
+    ifindex: u32,
+    name: Option<String>,
+    up: bool,
+    removed: bool,
+}
+
+/// Listener for interface changes, over an rtnetlink socket
+pub struct LinkMonitor {
+    /// The netlink socket, subscribed to the link group
+    fd: AsyncFd<OwnedFd>,
+
+    /// Name and state of every interface seen, by index
+    links: HashMap<u32, (String, bool)>,
+
+    /// Events decoded but not yet returned
+    pending: Vec<LinkEvent>,
+}
+
+impl LinkMonitor {
+    /// Subscribe to interface changes; must be called within a Tokio runtime
+    pub fn open() -> Result<Self> {
+        let fd = netlink::open_socket(libc::RTMGRP_LINK as u32, libc::SOCK_NONBLOCK)
+            .map_err(|e| XdpError::syscall("Failed to subscribe to link changes", e))?;
+        Ok(Self {
+            fd: AsyncFd::new(fd)?,
+            links: HashMap::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Record an interface that exists already, so its changes are
+    /// reported against its current state
+    pub fn track(&mut self, ifindex: u32, name: &str, up: bool) {
+        self.links.insert(ifindex, (name.to_string(), up));
+    }
+
+    /// Wait for the next interface change
+    pub async fn next_event(&mut self) -> Result<LinkEvent> {
+        let mut buf = vec![0u8; RECV_BUFFER_SIZE];
+        loop {
+            if !self.pending.is_empty() {
+                return Ok(self.pending.remove(0));
+            }
+
+            let mut ready = self.fd.readable().await?;
+            let len = unsafe {
+                libc::recv(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    0,
+                )
+            };
+            if len < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::WouldBlock {
+                    ready.clear_ready();
+                    continue;
+                }
+                // Messages were lost when the socket buffer overflowed;
+                // carry on, later messages carry the full interface state
+                if e.raw_os_error() == Some(libc::ENOBUFS) {
+                    debug!("Netlink socket overflowed, some link changes were missed");
+                    continue;
+                }
+                return Err(XdpError::syscall("Failed to read link changes", e));
+            }
+
+            for message in parse_messages(&buf[..len as usize]) {
+                if let Some(event) = self.update(message) {
+                    self.pending.push(event);
+                }
+            }
+        }
+    }
+
+    /// Apply a link message to the known interfaces, returning the change
+    /// it makes, if any
+    fn update(&mut self, message: LinkMessage) -> Option<LinkEvent> {
+        if message.removed {
+            let (name, _) = self.links.remove(&message.ifindex)?;
+            return Some(LinkEvent {
+                ifindex: message.ifindex,
+                name: message.name.unwrap_or(name),
+                kind: LinkEventKind::Removed,
+            });
+        }
+
+        let name = message.name?;
+        let kind = match self.links.insert(message.ifindex, (name.clone(), message.up)) {
+            None => LinkEventKind::Added,
+            Some((_, was_up)) if was_up == message.up => return None,
+            Some(_) if message.up => LinkEventKind::Up,
+            Some(_) => LinkEventKind::Down,
+        };
+        Some(LinkEvent {
+            ifindex: message.ifindex,
+            name,
+            kind,
+        })
+    }
+}
+
+/// Keep the XDP program attached to the interfaces whose names match one
+/// of `patterns`, and the TC egress program too if `egress` is set
+///
+/// Matching interfaces are attached to now and whenever they are added or
+/// come up again, e.g. after a NIC reset or a VM hot-add; the links of
+/// removed ones are dropped. What happens is sent to `events`, if still
+/// listened to. The task stops with the other background tasks of the
+/// manager.
+pub fn watch_interfaces(
+    xdp: Arc<Mutex<XdpManager>>,
+    patterns: Vec<String>,
+    egress: bool,
+    events: mpsc::Sender<HotplugEvent>,
+) -> Result<JoinHandle<()>> {
+    // Subscribe first, so no change between listing and watching is missed
+    let mut monitor = LinkMonitor::open()?;
+    let existing = list_links()?;
+    for (ifindex, name, up) in &existing {
+        monitor.track(*ifindex, name, *up);
+    }
+
+    let wanted = move |name: &str| patterns.iter().any(|pattern| matches_pattern(pattern, name));
+    Ok(tokio::spawn(async move {
+        let cancellation = xdp.lock().await.cancellation_token();
+        for (_, name, _) in &existing {
+            if wanted(name) {
+                if let Some(event) = ensure_attached(&xdp, name, egress).await {
+                    let _ = events.send(event).await;
+                }
+            }
+        }
+
+        loop {
+            let next = tokio::select! {
+                _ = cancellation.cancelled() => return,
+                next = monitor.next_event() => next,
+            };
+            let link = match next {
+                Ok(link) => link,
+                Err(e) => {
+                    warn!("Stopped watching interfaces: {}", e);
+                    return;
+                }
+            };
+            debug!("Interface {} (index {}): {:?}", link.name, link.ifindex, link.kind);
+
+            let event = if wanted(&link.name) {
+                match link.kind {
+                    LinkEventKind::Added | LinkEventKind::Up => ensure_attached(&xdp, &link.name, egress).await,
+                    LinkEventKind::Removed => detach_removed(&xdp, &link.name).await,
+                    LinkEventKind::Down => None,
+                }
+            } else {
+                None
+            };
+            let _ = events.send(HotplugEvent::Link(link)).await;
+            if let Some(event) = event {
+                let _ = events.send(event).await;
+            }
+        }
+    }))
+}
+
+/// Attach the programs to an interface, unless they are already
+async fn ensure_attached(xdp: &Mutex<XdpManager>, interface: &str, egress: bool) -> Option<HotplugEvent> {
+    let mut xdp = xdp.lock().await;
+    if xdp.attached_interfaces().iter().any(|name| name == interface) {
+        return None;
+    }
+
+    let result = xdp
+        .attach(interface)
+        .and_then(|()| if egress { xdp.attach_egress(interface) } else { Ok(()) });
+    match result {
+        Ok(()) => Some(HotplugEvent::Attached(interface.to_string())),
+        Err(e) => Some(HotplugEvent::AttachFailed {
+            interface: interface.to_string(),
+            error: format!("{:#}", e),
+        }),
+    }
+}
+
+/// Drop the links of a removed interface, so a new interface of the same
+/// name does not reuse them
+async fn detach_removed(xdp: &Mutex<XdpManager>, interface: &str) -> Option<HotplugEvent> {
+    let mut xdp = xdp.lock().await;
+    if !xdp.attached_interfaces().iter().any(|name| name == interface) {
+        return None;
+    }
+    match xdp.detach(interface) {
+        Ok(()) => Some(HotplugEvent::Detached(interface.to_string())),
+        Err(e) => {
+            warn!("Failed to detach from removed interface {}: {:#}", interface, e);
+            None
+        }
+    }
+}
+
+/// Index, name and up state of the interfaces of the host
+fn list_links() -> Result<Vec<(u32, String, bool)>> {
+    let mut links = Vec::new();
+    let entries = fs::read_dir(SYS_CLASS_NET).map_err(|e| XdpError::syscall(format!("Failed to list {}", SYS_CLASS_NET), e))?;
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let read = |file: &str| fs::read_to_string(path.join(file)).ok();
+        let Some(ifindex) = read("ifindex").and_then(|index| index.trim().parse().ok()) else {
+            continue;
+        };
+        let flags = read("flags")
+            .and_then(|flags| u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).ok())
+            .unwrap_or(0);
+        links.push((ifindex, name.to_string(), flags & libc::IFF_UP as u32 != 0));
+    }
+    Ok(links)
+}
+
+/// Whether an interface name matches a pattern, where `*` stands for any
+/// run of characters and `?` for any one
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Where to resume after the last `*` when a match fails
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Decode the link messages in a netlink datagram
+fn parse_messages(buf: &[u8]) -> Vec<LinkMessage> {
+    netlink::messages(buf)
+        .filter(|(msg_type, _)| *msg_type == libc::RTM_NEWLINK || *msg_type == libc::RTM_DELLINK)
+        .filter_map(|(msg_type, payload)| {
+            let mut message = parse_link(payload)?;
+            message.removed = msg_type == libc::RTM_DELLINK;
+            Some(message)
+        })
+        .collect()
+}
+
+/// Decode an ifinfomsg and its attributes
+fn parse_link(payload: &[u8]) -> Option<LinkMessage> {
+    if payload.len() < IFINFOMSG_SIZE {
+        return None;
+    }
+    let ifindex = netlink::read_u32(payload, 4);
+    let flags = netlink::read_u32(payload, 8);
+
+    let name = netlink::rtattrs(&payload[IFINFOMSG_SIZE..])
+        .find(|(attr_type, _)| *attr_type == IFLA_IFNAME)
+        .map(|(_, value)| {
+            let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+            String::from_utf8_lossy(&value[..end]).into_owned()
+        });
+
+    Some(LinkMessage {
+        ifindex,
+        name,
+        up: flags & libc::IFF_UP as u32 != 0,
+        removed: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlink::{align, NLMSG_HDR_SIZE, RTATTR_HDR_SIZE};
+
+    /// An RTM_NEWLINK or RTM_DELLINK message for an interface
+    fn link_message(msg_type: u16, ifindex: u32, name: &str, up: bool) -> Vec<u8> {
+        let mut attr = Vec::new();
+        let attr_len = RTATTR_HDR_SIZE + name.len() + 1;
+        attr.extend_from_slice(&(attr_len as u16).to_ne_bytes());
+        attr.extend_from_slice(&IFLA_IFNAME.to_ne_bytes());
+        attr.extend_from_slice(name.as_bytes());
+        attr.push(0);
+        attr.resize(align(attr.len()), 0);
+
+        let mut info = vec![0u8; IFINFOMSG_SIZE];
+        info[4..8].copy_from_slice(&ifindex.to_ne_bytes());
+        let flags = if up { libc::IFF_UP as u32 } else { 0 };
+        info[8..12].copy_from_slice(&flags.to_ne_bytes());
+
+        let len = NLMSG_HDR_SIZE + info.len() + attr.len();
+        let mut message = Vec::new();
+        message.extend_from_slice(&(len as u32).to_ne_bytes());
+        message.extend_from_slice(&msg_type.to_ne_bytes());
+        message.extend_from_slice(&[0u8; 10]);
+        message.extend(info);
+        message.extend(attr);
+        message
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let mut buf = link_message(libc::RTM_NEWLINK, 3, "eth1", true);
+        buf.extend(link_message(libc::RTM_DELLINK, 4, "veth0", false));
+
+        assert_eq!(
+            parse_messages(&buf),
+            vec![
+                LinkMessage {
+                    ifindex: 3,
+                    name: Some("eth1".to_string()),
+                    up: true,
+                    removed: false,
+                },
+                LinkMessage {
+                    ifindex: 4,
+                    name: Some("veth0".to_string()),
+                    up: false,
+                    removed: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert!(matches_pattern("eth*", "eth0"));
+        assert!(matches_pattern("eth*", "eth"));
+        assert!(matches_pattern("*", "wlan0"));
+        assert!(matches_pattern("enp?s*", "enp3s0"));
+        assert!(matches_pattern("veth*a*b", "veth-a-xb"));
+        assert!(!matches_pattern("eth*", "veth0"));
+        assert!(!matches_pattern("eth?", "eth10"));
+    }
+}
@@ -3,25 +3,54 @@
 //! This crate provides userspace components for loading, managing,
 //! and interacting with the eBPF XDP programs in the µDCN architecture.
 
-use anyhow::{Context, Result};
 use aya::{
     include_bytes_aligned,
     maps::{HashMap, MapData, lru_hash_map::LruHashMap},
-    programs::{Xdp, XdpFlags, XdpLinkId},
-    Bpf,
+    programs::{
+        links::{FdLink, PinnedLink},
+        xdp::XdpLink,
+        tc::{self, SchedClassifierLinkId},
+        ProgramFd, SchedClassifier, TcAttachType, Xdp,
+    },
+    Bpf, BpfLoader,
 };
 use aya_log::BpfLogger;
 use log::{debug, info, warn};
-use rust_udcn_common::{metrics::UdcnMetrics, ndn::Name, types::*};
+use rust_udcn_common::{ndn::Name, types::*};
 use std::net::Ipv6Addr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
+mod chain;
+pub mod clock;
+mod error;
+mod hotplug;
 mod loader;
 mod maps;
+mod names;
+mod netlink;
+mod pipeline;
+mod rib;
+mod xsk;
 
-pub use maps::{ContentStore, Fib, PendingInterestTable};
+use chain::Chain;
+use error::Result;
+use maps::FIB_MODE_GLOBAL;
+
+pub use error::XdpError;
+pub use loader::{XdpAttachFlags, XdpMode};
+pub use maps::{
+    name_hash, prefix_hashes, CongestionMarks, Config, ContentStore, CsAdmission, CsPrefixRule, CsPrefixes, Egress, Fib, FibMode, InterfaceStats, KernelConfig,
+    KernelMetrics, MapSizes, PendingInterestTable, RateLimit,
+};
+pub use hotplug::{matches_pattern, watch_interfaces, HotplugEvent, LinkEvent, LinkEventKind, LinkMonitor};
+pub use names::{NameTable, DEFAULT_OBSERVED_NAMES};
+pub use netlink::{interface_xdp, LinkXdp};
+pub use pipeline::{Pipeline, PipelineStage};
+pub use rib::{FibUpdate, Rib, RibManager, Route, RouteFlags, RouteOrigin};
+pub use xsk::{decode_frame, XskConfig, XskContent, XskMode, XskPacket, XskSocket, XskSockets};
 
 /// NDN port as defined in RFC8609
 pub const NDN_PORT: u16 = 6363;
@@ -29,6 +58,39 @@ pub const NDN_PORT: u16 = 6363;
 /// Path to the default eBPF object file
 pub const DEFAULT_EBPF_PATH: &str = "rust_udcn_ebpf.o";
 
+/// Directory in the BPF filesystem the maps and XDP links are pinned in
+pub const DEFAULT_PIN_PATH: &str = "/sys/fs/bpf/udcn";
+
+/// How the XDP program is loaded
+#[derive(Debug, Clone)]
+pub struct XdpOptions {
+    /// How the kernel FIB matches Interest names
+    pub fib_mode: FibMode,
+    /// Number of entries of the kernel tables, when they are created
+    /// rather than found pinned
+    pub map_sizes: MapSizes,
+    /// Where the maps and XDP links are pinned; programs loaded with the
+    /// same path share their tables
+    pub pin_path: PathBuf,
+    /// How `XdpManager::attach` attaches the XDP program
+    pub attach_flags: XdpAttachFlags,
+    /// Stops the background tasks of the manager once cancelled, e.g. the
+    /// token of the application embedding it
+    pub cancellation: CancellationToken,
+}
+
+impl Default for XdpOptions {
+    fn default() -> Self {
+        Self {
+            fib_mode: FibMode::default(),
+            map_sizes: MapSizes::default(),
+            pin_path: PathBuf::from(DEFAULT_PIN_PATH),
+            attach_flags: XdpAttachFlags::default(),
+            cancellation: CancellationToken::new(),
+        }
+    }
+}
+
 /// XDP manager that handles loading and interaction with the XDP eBPF program
 pub struct XdpManager {
     /// The loaded BPF program
@@ -37,9 +99,6 @@ pub struct XdpManager {
     /// The XDP program instance
     program: Option<Xdp>,
     
-    /// Metrics for the XDP program
-    metrics: Arc<RwLock<UdcnMetrics>>,
-    
     /// The PIT (Pending Interest Table)
     pit: Arc<PendingInterestTable>,
     
@@ -49,126 +108,457 @@ pub struct XdpManager {
     /// The CS (Content Store)
     cs: Arc<ContentStore>,
     
-    /// List of attached network interfaces and their link IDs
-    attached_interfaces: Vec<(String, XdpLinkId)>,
+    /// The name prefixes allowed in or denied from the CS
+    cs_prefixes: Arc<CsPrefixes>,
+    
+    /// The interfaces the kernel redirects Interests to
+    egress: Arc<Egress>,
+    
+    /// The runtime settings of the XDP program
+    config: Arc<Config>,
+    
+    /// The stages the XDP program tail calls
+    pipeline: Pipeline,
+
+    /// Programs of other tools, run after the XDP program
+    chain: Chain,
+    
+    /// The interfaces the TC egress program marks congestion on
+    congestion: Arc<CongestionMarks>,
+    
+    /// The AF_XDP sockets the XDP program hands packets to
+    xsks: XskSockets,
+    
+    /// The RIB (Routing Information Base) feeding the FIB
+    rib: Arc<RibManager>,
+    
+    /// Names behind the hashes in the PIT and FIB
+    names: Arc<NameTable>,
+    
+    /// The task recording the Interest names the kernel reports
+    names_reader: Option<JoinHandle<()>>,
+    
+    /// Stops the background tasks, a child of the token of the options
+    cancellation: CancellationToken,
+    
+    /// How the program was loaded, reused to load its replacements
+    options: XdpOptions,
+    
+    /// List of attached network interfaces and their pinned links
+    attached_interfaces: Vec<(String, PinnedLink)>,
+    
+    /// The TC egress program instance
+    egress_program: Option<SchedClassifier>,
+    
+    /// Interfaces the TC egress program is attached to and their link IDs
+    egress_links: Vec<(String, SchedClassifierLinkId)>,
 }
 
 impl XdpManager {
     /// Load the XDP program from the given path or use the embedded program
     pub async fn load_from_embedded() -> Result<Self> {
+        Self::load_from_embedded_with_fib(FibMode::default()).await
+    }
+
+    /// Load the embedded XDP program, with the kernel FIB in `fib_mode`
+    pub async fn load_from_embedded_with_fib(fib_mode: FibMode) -> Result<Self> {
+        Self::load_from_embedded_with_options(XdpOptions {
+            fib_mode,
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Load the embedded XDP program with `options`
+    pub async fn load_from_embedded_with_options(options: XdpOptions) -> Result<Self> {
         // This will include the eBPF object file at compile time
         // The eBPF object compiled by the build script is placed in OUT_DIR
-        let mut bpf = Bpf::load(include_bytes_aligned!(concat!(
+        let bpf = Self::load_object(&options, include_bytes_aligned!(concat!(
             env!("OUT_DIR"), "/rust_udcn_ebpf.o"
         )))?;
 
-        // Initialize logging for the BPF program
-        if let Err(e) = BpfLogger::init(&mut bpf) {
-            warn!("Failed to initialize BPF logger: {}", e);
-        }
-        
-        // Initialize the metrics
-        let metrics = Arc::new(RwLock::new(UdcnMetrics::new()));
-        
-        // Initialize the tables
-        let pit = Arc::new(PendingInterestTable::new(&mut bpf)?);
-        let fib = Arc::new(Fib::new(&mut bpf)?);
-        let cs = Arc::new(ContentStore::new(&mut bpf)?);
-        
-        Ok(Self {
-            bpf,
-            program: None,
-            metrics,
-            pit,
-            fib,
-            cs,
-            attached_interfaces: Vec::new(),
-        })
+        Self::from_bpf(bpf, options)
     }
 
     /// Load the XDP program from a file
     pub async fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let mut bpf = Bpf::load_file(path.as_ref())?;
+        Self::load_from_file_with_fib(path, FibMode::default()).await
+    }
 
+    /// Load the XDP program from a file, with the kernel FIB in `fib_mode`
+    pub async fn load_from_file_with_fib<P: AsRef<Path>>(path: P, fib_mode: FibMode) -> Result<Self> {
+        Self::load_from_file_with_options(
+            path,
+            XdpOptions {
+                fib_mode,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Load the XDP program from a file with `options`
+    pub async fn load_from_file_with_options<P: AsRef<Path>>(path: P, options: XdpOptions) -> Result<Self> {
+        let object = read_object(path.as_ref())?;
+        let bpf = Self::load_object(&options, &object)?;
+
+        Self::from_bpf(bpf, options)
+    }
+
+    /// Load an eBPF object into the kernel, sharing the maps pinned under
+    /// the pin path or creating and pinning them
+    fn load_object(options: &XdpOptions, object: &[u8]) -> Result<Bpf> {
+        let fib_mode = options.fib_mode.global_value();
+        let mut loader = BpfLoader::new();
+        loader.set_global(FIB_MODE_GLOBAL, &fib_mode, true);
+        options.map_sizes.apply(&mut loader)?;
+        std::fs::create_dir_all(&options.pin_path)
+            .map_err(|e| XdpError::syscall(format!("Failed to create pin path {}", options.pin_path.display()), e))?;
+        loader.map_pin_path(&options.pin_path);
+
+        Ok(loader.load(object)?)
+    }
+
+    /// Set up logging and the map wrappers of a loaded BPF object
+    fn from_bpf(mut bpf: Bpf, options: XdpOptions) -> Result<Self> {
         // Initialize logging for the BPF program
         if let Err(e) = BpfLogger::init(&mut bpf) {
             warn!("Failed to initialize BPF logger: {}", e);
         }
         
-        // Initialize the metrics
-        let metrics = Arc::new(RwLock::new(UdcnMetrics::new()));
-        
         // Initialize the tables
+        let names = Arc::new(NameTable::default());
         let pit = Arc::new(PendingInterestTable::new(&mut bpf)?);
-        let fib = Arc::new(Fib::new(&mut bpf)?);
-        let cs = Arc::new(ContentStore::new(&mut bpf)?);
+        let fib = Arc::new(Fib::new(&mut bpf, options.fib_mode, Arc::clone(&names))?);
+        let cs = Arc::new(ContentStore::new(&mut bpf, options.map_sizes.cs_entries as usize, Arc::clone(&names))?);
+        let cs_prefixes = Arc::new(CsPrefixes::new(&mut bpf)?);
+        let egress = Arc::new(Egress::new(&mut bpf)?);
+        let config = Arc::new(Config::new(&mut bpf)?);
+        let pipeline = Pipeline::load(&mut bpf)?;
+        let chain = Chain::open(&options.pin_path)?;
+        let congestion = Arc::new(CongestionMarks::new(&mut bpf)?);
+        let xsks = XskSockets::new(&mut bpf)?;
+        let rib = Arc::new(RibManager::new(Arc::clone(&fib)));
+        let cancellation = options.cancellation.child_token();
+        let names_reader = match Arc::clone(&names).spawn_reader(&mut bpf, cancellation.clone()) {
+            Ok(reader) => Some(reader),
+            Err(e) => {
+                warn!("Failed to read kernel name events, PIT names will not be shown: {}", e);
+                None
+            }
+        };
         
         Ok(Self {
             bpf,
             program: None,
-            metrics,
             pit,
             fib,
             cs,
+            cs_prefixes,
+            egress,
+            config,
+            pipeline,
+            chain,
+            congestion,
+            xsks,
+            rib,
+            names,
+            names_reader,
+            cancellation,
             attached_interfaces: Vec::new(),
+            egress_program: None,
+            egress_links: Vec::new(),
+            options,
         })
     }
 
     /// Attach the XDP program to the specified network interface
+    ///
+    /// The link is pinned, so the program stays attached once this process
+    /// exits. If another process already attached it, its link is reused.
     pub fn attach(&mut self, interface_name: &str) -> Result<()> {
+        self.attach_with_flags(interface_name, self.options.attach_flags)
+            .map(|_| ())
+    }
+
+    /// Attach the XDP program to the specified network interface in the
+    /// mode `flags` ask for, returning the mode it runs in
+    ///
+    /// Without a mode asked for, native mode is tried first, then SKB mode
+    /// if the driver lacks native XDP. An XDP program another tool attached
+    /// is left alone, unless `flags.chain` has udcn take over and run it
+    /// for the packets that are not NDN.
+    pub fn attach_with_flags(&mut self, interface_name: &str, flags: XdpAttachFlags) -> Result<XdpMode> {
+        let link_path = self.link_path(interface_name);
+        if link_path.exists() {
+            let link = PinnedLink::from_pin(&link_path)?;
+            match interface_xdp(interface_name)? {
+                Some(xdp) => {
+                    self.attached_interfaces.push((interface_name.to_string(), link));
+                    info!("XDP program already attached to interface {} in {} mode", interface_name, xdp.mode);
+                    return Ok(xdp.mode);
+                }
+                // Left behind by an interface since removed
+                None => {
+                    warn!("Dropping stale XDP link of interface {}", interface_name);
+                    link.unpin()
+                        .map_err(|e| XdpError::syscall(format!("Failed to remove pinned link {}", link_path.display()), e))?;
+                }
+            }
+        }
+
+        if let Some(existing) = interface_xdp(interface_name)? {
+            if !flags.chain {
+                return Err(XdpError::InterfaceBusy {
+                    interface: interface_name.to_string(),
+                    prog_id: existing.prog_id,
+                    mode: existing.mode,
+                });
+            }
+            self.chain.take_over(interface_name, existing)?;
+        }
+
+        let result = self.attach_program(interface_name, flags, &link_path);
+        if result.is_err() {
+            // Give the interface back
+            if let Err(e) = self.chain.restore(interface_name) {
+                warn!("Failed to restore the XDP program of {}: {}", interface_name, e);
+            }
+        }
+        result
+    }
+
+    /// Attach the XDP program and pin its link at `link_path`
+    fn attach_program(&mut self, interface_name: &str, flags: XdpAttachFlags, link_path: &Path) -> Result<XdpMode> {
         // Get the XDP program from the BPF object
         let program: &mut Xdp = self.bpf.program_mut("ndn_xdp")
-            .context("Failed to find XDP program 'ndn_xdp'")?
+            .ok_or_else(|| XdpError::ProgramNotFound("ndn_xdp".to_string()))?
             .try_into()?;
 
-        // Load the program into the kernel
-        program.load()?;
+        // Load the program into the kernel, once for all interfaces
+        if self.program.is_none() {
+            program.load().map_err(|e| XdpError::program_load("ndn_xdp", e))?;
+        }
         
-        // Attach it to the interface and keep the link ID
-        let link_id = program
-            .attach(interface_name, XdpFlags::default())
-            .context(format!("Failed to attach to interface {}", interface_name))?;
+        // Attach it to the interface, falling back through the modes
+        let modes = flags.modes();
+        let mut attached = None;
+        for (i, &mode) in modes.iter().enumerate() {
+            match program.attach(interface_name, mode.to_aya_flags()) {
+                Ok(link_id) => {
+                    attached = Some((mode, link_id));
+                    break;
+                }
+                Err(e) if i + 1 < modes.len() => {
+                    warn!(
+                        "Cannot attach to interface {} in {} mode ({}), trying {} mode",
+                        interface_name, mode, e, modes[i + 1]
+                    );
+                }
+                Err(source) => {
+                    return Err(XdpError::Attach {
+                        interface: interface_name.to_string(),
+                        source,
+                    });
+                }
+            }
+        }
+        let (mode, link_id) = attached.ok_or_else(|| XdpError::Config("No XDP mode to attach in".to_string()))?;
+
+        // Pin the link
+        let link = FdLink::try_from(program.take_link(link_id)?)?.pin(link_path)?;
         
         // Store the program instance
         self.program = Some(program.clone());
         
-        // Add the interface to our list with its link ID
+        // Add the interface to our list with its link
         self
             .attached_interfaces
-            .push((interface_name.to_string(), link_id));
+            .push((interface_name.to_string(), link));
         
-        info!("XDP program attached to interface {}", interface_name);
+        info!("XDP program attached to interface {} in {} mode", interface_name, mode);
         
+        Ok(mode)
+    }
+
+    /// Attach the TC egress program to the specified network interface, to
+    /// see the NDN packets sent out of it
+    pub fn attach_egress(&mut self, interface_name: &str) -> Result<()> {
+        // The clsact qdisc may be there already, e.g. from another program
+        if let Err(e) = tc::qdisc_add_clsact(interface_name) {
+            debug!("Could not add clsact qdisc to {}: {}", interface_name, e);
+        }
+
+        let program: &mut SchedClassifier = self.bpf.program_mut("ndn_tc_egress")
+            .ok_or_else(|| XdpError::ProgramNotFound("ndn_tc_egress".to_string()))?
+            .try_into()?;
+
+        // Load the program into the kernel, once for all interfaces
+        if self.egress_program.is_none() {
+            program.load().map_err(|e| XdpError::program_load("ndn_tc_egress", e))?;
+        }
+
+        let link_id = program
+            .attach(interface_name, TcAttachType::Egress)
+            .map_err(|source| XdpError::Attach {
+                interface: interface_name.to_string(),
+                source,
+            })?;
+
+        self.egress_program = Some(program.clone());
+        self.egress_links.push((interface_name.to_string(), link_id));
+
+        info!("TC egress program attached to interface {}", interface_name);
+
+        Ok(())
+    }
+
+    /// Detach the XDP and TC egress programs from an interface, giving it
+    /// back to the XDP program udcn took it over from, if any
+    pub fn detach(&mut self, interface_name: &str) -> Result<()> {
+        let index = self
+            .attached_interfaces
+            .iter()
+            .position(|(name, _)| name == interface_name)
+            .ok_or_else(|| XdpError::NotAttached(interface_name.to_string()))?;
+        let (_, link) = self.attached_interfaces.remove(index);
+        link.unpin()
+            .map_err(|e| XdpError::syscall(format!("Failed to detach from interface {}", interface_name), e))?;
+        info!("Detached XDP program from interface {}", interface_name);
+        self.chain.restore(interface_name)?;
+
+        if let Some(prog) = self.egress_program.as_mut() {
+            // Gone with the interface if it was removed
+            let (links, others): (Vec<_>, Vec<_>) = self.egress_links.drain(..).partition(|(name, _)| name == interface_name);
+            self.egress_links = others;
+            for (_, link_id) in links {
+                if let Err(e) = prog.detach(link_id) {
+                    debug!("Failed to detach TC egress program from interface {}: {}", interface_name, e);
+                }
+            }
+        }
         Ok(())
     }
 
-    /// Detach the XDP program from all interfaces
+    /// Detach the XDP and TC egress programs from all interfaces
     pub fn detach_all(&mut self) -> Result<()> {
-        if let Some(prog) = self.program.as_mut() {
-            for (interface, link_id) in self.attached_interfaces.drain(..) {
+        // The program stays attached until the last reference to its link
+        // goes, here once the pin is removed
+        for (interface, link) in self.attached_interfaces.drain(..) {
+            if let Err(e) = link.unpin() {
+                warn!("Failed to detach from interface {}: {}", interface, e);
+                continue;
+            }
+            info!("Detached XDP program from interface {}", interface);
+            if let Err(e) = self.chain.restore(&interface) {
+                warn!("Failed to restore the XDP program of {}: {}", interface, e);
+            }
+        }
+        if let Some(prog) = self.egress_program.as_mut() {
+            for (interface, link_id) in self.egress_links.drain(..) {
                 if let Err(e) = prog.detach(link_id) {
-                    warn!("Failed to detach from interface {}: {}", interface, e);
+                    warn!("Failed to detach TC egress program from interface {}: {}", interface, e);
                 } else {
-                    info!("Detached XDP program from interface {}", interface);
+                    info!("Detached TC egress program from interface {}", interface);
                 }
             }
         }
         Ok(())
     }
 
-    /// Add a route to the FIB
+    /// Bind an AF_XDP socket to a receive queue of `interface_name`, which
+    /// gets the NDN packets of that queue the XDP program would pass up,
+    /// once `KernelConfig::af_xdp` is set
+    pub fn bind_xsk(&mut self, interface_name: &str, queue_id: u32, config: &XskConfig) -> Result<XskSocket> {
+        let socket = XskSocket::bind(interface_name, queue_id, config)?;
+        self.xsks.register(&socket)?;
+        Ok(socket)
+    }
+
+    /// Upgrade to the XDP program in the eBPF object at `path` without
+    /// detaching from any interface
+    ///
+    /// The new object shares the pinned maps, so PIT, FIB and CS state carry
+    /// over; it must keep their layouts. Its stages go into the pipeline, and
+    /// the link of each interface is switched to its entry program in one
+    /// update, so no packet goes unprocessed.
+    pub async fn replace<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let object = read_object(path.as_ref())?;
+        let mut bpf = Self::load_object(&self.options, &object)?;
+        if let Err(e) = BpfLogger::init(&mut bpf) {
+            warn!("Failed to initialize BPF logger: {}", e);
+        }
+
+        // Stages first, so the new entry program never tail calls old ones
+        let pipeline = Pipeline::load(&mut bpf)?;
+
+        let program: &mut Xdp = bpf.program_mut("ndn_xdp")
+            .ok_or_else(|| XdpError::ProgramNotFound("ndn_xdp".to_string()))?
+            .try_into()?;
+        program.load().map_err(|e| XdpError::program_load("ndn_xdp", e))?;
+
+        for (interface, _) in &self.attached_interfaces {
+            // A second reference to the link, so the pinned one stays
+            // attached even if the update fails
+            let link_path = self.link_path(interface);
+            let link = FdLink::from(PinnedLink::from_pin(&link_path)?);
+            program
+                .attach_to_link(XdpLink::try_from(link)?)
+                .map_err(|source| XdpError::Attach {
+                    interface: interface.clone(),
+                    source,
+                })?;
+            info!("Replaced XDP program on interface {}", interface);
+        }
+        let program = program.clone();
+
+        // The TC egress program only counts, so a short gap is acceptable
+        let egress_interfaces: Vec<String> = self.egress_links.iter().map(|(name, _)| name.clone()).collect();
+        if let Some(prog) = self.egress_program.as_mut() {
+            for (_, link_id) in self.egress_links.drain(..) {
+                let _ = prog.detach(link_id);
+            }
+        }
+        self.egress_program = None;
+
+        self.bpf = bpf;
+        self.program = Some(program);
+        self.pipeline = pipeline;
+        self.xsks = XskSockets::new(&mut self.bpf)?;
+
+        for interface in egress_interfaces {
+            self.attach_egress(&interface)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace a stage of the XDP pipeline with `program`, a loaded XDP
+    /// program, without detaching from any interface
+    pub fn replace_stage(&mut self, stage: PipelineStage, program: &ProgramFd) -> Result<()> {
+        self.pipeline.set_stage(stage, program)
+    }
+
+    /// Add a static route, which the RIB pushes into the FIB
     pub async fn add_route(&self, name_prefix: &Name, face_id: FaceId, cost: u8) -> Result<()> {
-        self.fib.add_route(name_prefix, face_id, cost).await
+        let route = Route::new(face_id, RouteOrigin::Static, cost as u32);
+        self.rib.register(name_prefix.clone(), route).await
     }
 
-    /// Remove a route from the FIB
+    /// Remove the static routes of a prefix
     pub async fn remove_route(&self, name_prefix: &Name) -> Result<()> {
-        self.fib.remove_route(name_prefix).await
+        self.rib.unregister_origin(name_prefix, RouteOrigin::Static).await?;
+        Ok(())
+    }
+
+    /// Get the current counters of the XDP program, summed over all CPUs
+    pub async fn get_metrics(&self) -> Result<KernelMetrics> {
+        KernelMetrics::read(&self.bpf)
     }
 
-    /// Get current metrics
-    pub async fn get_metrics(&self) -> UdcnMetrics {
-        self.metrics.read().await.clone()
+    /// Get the NDN traffic counters of each interface packets arrived on
+    pub async fn interface_stats(&self) -> Result<Vec<InterfaceStats>> {
+        InterfaceStats::read_all(&self.bpf)
     }
 
     /// Get the Pending Interest Table (PIT)
@@ -186,6 +576,57 @@ impl XdpManager {
         Arc::clone(&self.cs)
     }
     
+    /// Get the name prefixes allowed in or denied from the CS
+    pub fn cs_prefixes(&self) -> Arc<CsPrefixes> {
+        Arc::clone(&self.cs_prefixes)
+    }
+    
+    /// Get the egress interfaces of the kernel fast path
+    pub fn egress(&self) -> Arc<Egress> {
+        Arc::clone(&self.egress)
+    }
+    
+    /// Get the runtime settings of the XDP program
+    pub fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.config)
+    }
+    
+    /// Get the interfaces the TC egress program marks congestion on
+    pub fn congestion(&self) -> Arc<CongestionMarks> {
+        Arc::clone(&self.congestion)
+    }
+    
+    /// Get the Routing Information Base (RIB)
+    pub fn rib(&self) -> Arc<RibManager> {
+        Arc::clone(&self.rib)
+    }
+    
+    /// Get the names behind the hashes in the PIT and FIB
+    pub fn names(&self) -> Arc<NameTable> {
+        Arc::clone(&self.names)
+    }
+    
+    /// Token stopping the background tasks of the manager, for the tasks
+    /// spawned on its tables, such as `PendingInterestTable::spawn_gc`
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+    
+    /// Cancel the background tasks of the manager and of its tables, and
+    /// hand back the handles of its own, to await them
+    ///
+    /// The XDP program stays attached; detach it first to stop the fast
+    /// path as well.
+    pub fn stop_tasks(&mut self) -> Vec<JoinHandle<()>> {
+        self.cancellation.cancel();
+        self.names_reader.take().into_iter().collect()
+    }
+    
+    /// Path of the pinned XDP link of an interface
+    fn link_path(&self, interface_name: &str) -> PathBuf {
+        self.options.pin_path.join(format!("link_{}", interface_name))
+    }
+    
     /// Get a list of attached interface names
     pub fn attached_interfaces(&self) -> Vec<String> {
         self.attached_interfaces
@@ -199,3 +640,17 @@ impl XdpManager {
         !self.attached_interfaces.is_empty()
     }
 }
+
+impl Drop for XdpManager {
+    fn drop(&mut self) {
+        self.cancellation.cancel();
+    }
+}
+
+/// Read an eBPF object file
+fn read_object(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|source| XdpError::ReadObject {
+        path: path.to_path_buf(),
+        source,
+    })
+}
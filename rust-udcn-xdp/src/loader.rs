@@ -2,21 +2,25 @@
 //!
 //! This module provides functions for loading and managing the eBPF XDP program.
 
-use anyhow::{Context, Result};
 use aya::{
     programs::{Xdp, XdpFlags},
     Bpf,
 };
 use log::{debug, error, info, warn};
-use std::path::Path;
+use std::{fmt, path::Path};
+
+use crate::error::{Result, XdpError};
 
 /// Flags for attaching XDP programs
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct XdpAttachFlags {
     /// Use hardware offload if available
     pub offload: bool,
     /// Enable SKB mode (slower but more compatible)
     pub skb_mode: bool,
+    /// Take the interface over from an XDP program already attached to
+    /// it, which then runs after udcn for packets that are not NDN
+    pub chain: bool,
 }
 
 impl Default for XdpAttachFlags {
@@ -24,6 +28,7 @@ impl Default for XdpAttachFlags {
         Self {
             offload: false,
             skb_mode: false,
+            chain: false,
         }
     }
 }
@@ -44,6 +49,58 @@ impl XdpAttachFlags {
         
         flags
     }
+
+    /// Modes to try attaching in, in order
+    ///
+    /// Native mode falls back to SKB mode, for drivers without XDP support;
+    /// a mode asked for explicitly does not.
+    pub fn modes(&self) -> &'static [XdpMode] {
+        if self.offload {
+            &[XdpMode::Offload]
+        } else if self.skb_mode {
+            &[XdpMode::Skb]
+        } else {
+            &[XdpMode::Driver, XdpMode::Skb]
+        }
+    }
+}
+
+/// How an XDP program runs on an interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XdpMode {
+    /// In the driver, before any socket buffer is allocated
+    Driver,
+    /// Generic XDP on socket buffers, for drivers without native support
+    Skb,
+    /// On the NIC
+    Offload,
+    /// Several programs, in different modes
+    Multi,
+}
+
+impl fmt::Display for XdpMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XdpMode::Driver => write!(f, "driver"),
+            XdpMode::Skb => write!(f, "skb"),
+            XdpMode::Offload => write!(f, "offload"),
+            XdpMode::Multi => write!(f, "multi"),
+        }
+    }
+}
+
+
+impl XdpMode {
+    /// Flags to attach a program in this mode
+    pub fn to_aya_flags(self) -> XdpFlags {
+        match self {
+            XdpMode::Driver => XdpFlags::DRV_MODE,
+            XdpMode::Skb => XdpFlags::SKB_MODE,
+            XdpMode::Offload => XdpFlags::HW_MODE,
+            // Let the kernel pick
+            XdpMode::Multi => XdpFlags::default(),
+        }
+    }
 }
 
 /// Load an eBPF object file from the given path
@@ -52,8 +109,7 @@ pub fn load_bpf_object<P: AsRef<Path>>(path: P) -> Result<Bpf> {
     
     debug!("Loading BPF object file: {}", path.display());
     
-    let bpf = Bpf::load_file(path)
-        .context(format!("Failed to load BPF object file: {}", path.display()))?;
+    let bpf = Bpf::load_file(path)?;
     
     info!("Successfully loaded BPF object file: {}", path.display());
     
@@ -66,9 +122,8 @@ pub fn load_xdp_program(bpf: &mut Bpf, program_name: &str) -> Result<Xdp> {
     
     let program = bpf
         .program_mut(program_name)
-        .context(format!("Failed to find program '{}'", program_name))?
-        .try_into()
-        .context(format!("Failed to convert program '{}' to XDP", program_name))?;
+        .ok_or_else(|| XdpError::ProgramNotFound(program_name.to_string()))?
+        .try_into()?;
     
     info!("Successfully loaded XDP program: {}", program_name);
     
@@ -85,7 +140,10 @@ pub fn attach_xdp_to_interface(
     
     program
         .attach(interface, flags.to_aya_flags())
-        .context(format!("Failed to attach to interface: {}", interface))?;
+        .map_err(|source| XdpError::Attach {
+            interface: interface.to_string(),
+            source,
+        })?;
     
     info!("Successfully attached XDP program to interface: {}", interface);
     
@@ -96,9 +154,7 @@ pub fn attach_xdp_to_interface(
 pub fn detach_xdp_from_interface(program: &mut Xdp, interface: &str) -> Result<()> {
     debug!("Detaching XDP program from interface: {}", interface);
     
-    program
-        .detach(interface)
-        .context(format!("Failed to detach from interface: {}", interface))?;
+    program.detach(interface)?;
     
     info!("Successfully detached XDP program from interface: {}", interface);
     
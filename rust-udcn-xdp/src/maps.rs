@@ -3,21 +3,42 @@
 //! This module provides abstractions for interacting with eBPF maps
 //! from userspace, specifically for the PIT, FIB, and CS maps.
 
-use anyhow::{Context, Result};
+use bytes::BytesMut;
 use aya::{
-    maps::{HashMap, LruHashMap, MapData, MapRef, MapError},
-    Bpf,
+    maps::{
+        lpm_trie::{Key, LpmTrie},
+        Array, DevMapHash, HashMap, LruHashMap, MapData, MapRef, MapError, PerCpuArray, PerCpuHashMap,
+    },
+    Bpf, BpfLoader,
 };
 use log::{debug, info, warn};
-use rust_udcn_common::{ndn::Name, types::*};
-use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
+use rust_udcn_common::{metrics::UdcnMetrics, ndn::Name, types::*};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::{Mutex, RwLock},
+    task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::clock;
+use crate::error::{Result, XdpError};
+use crate::names::{self, NameTable};
+use crate::rib::FibUpdate;
 
 // Constants for map names matching those in the eBPF program
 const PIT_TABLE_NAME: &str = "PIT_TABLE";
+const DEAD_NONCES_NAME: &str = "DEAD_NONCES";
 const FIB_TABLE_NAME: &str = "FIB_TABLE";
+const FIB_TRIE_NAME: &str = "FIB_TRIE";
 const CS_TABLE_NAME: &str = "CS_TABLE";
+const CS_DATA_NAME: &str = "CS_DATA";
+const EGRESS_NAME: &str = "EGRESS";
+const EGRESS_FACES_NAME: &str = "EGRESS_FACES";
 const METRICS_MAP_NAME: &str = "METRICS";
+const CONFIG_MAP_NAME: &str = "CONFIG";
+const INTERFACE_COUNTERS_NAME: &str = "INTERFACE_COUNTERS";
+const CONGESTED_NAME: &str = "CONGESTED";
+const CS_PREFIXES_NAME: &str = "CS_PREFIXES";
 
 // Metric indices must match the eBPF program
 const METRIC_PACKETS_TOTAL: u32 = 0;
@@ -29,23 +50,372 @@ const METRIC_PIT_INSERTS: u32 = 5;
 const METRIC_FIB_HITS: u32 = 6;
 const METRIC_CS_INSERTS: u32 = 7;
 const METRIC_PIT_MATCHES: u32 = 8;
+const METRIC_CS_REPLIES: u32 = 9;
+const METRIC_INTERESTS_REDIRECTED: u32 = 10;
+const METRIC_DATA_UNSOLICITED: u32 = 11;
+const METRIC_ABORTED: u32 = 12;
+const METRIC_INTEREST_BYTES: u32 = 13;
+const METRIC_DATA_BYTES: u32 = 14;
+const METRIC_CS_REPLY_BYTES: u32 = 15;
+const METRIC_INTERESTS_LOOPED: u32 = 16;
+const METRIC_CS_EVICTIONS: u32 = 17;
+const METRIC_TCP_SEGMENTS: u32 = 18;
+const METRIC_TCP_BYTES: u32 = 19;
+const METRIC_INTERESTS_RATE_LIMITED: u32 = 20;
+const METRIC_INTERESTS_HOP_LIMIT: u32 = 21;
+const METRIC_PIPELINE_MISSES: u32 = 22;
+const METRIC_EGRESS_INTERESTS: u32 = 23;
+const METRIC_EGRESS_DATA: u32 = 24;
+const METRIC_EGRESS_BYTES: u32 = 25;
+const METRIC_CONGESTION_MARKS: u32 = 26;
+const METRIC_XSK_REDIRECTS: u32 = 27;
+const METRIC_COUNT: u32 = 28;
+
+// Setting indices and values must match the eBPF program
+const CONFIG_NDN_PORT: u32 = 0;
+const CONFIG_PIT_LIFETIME_MS: u32 = 1;
+const CONFIG_CS_TTL_MS: u32 = 2;
+const CONFIG_CS_ADMISSION: u32 = 3;
+const CONFIG_FLAGS: u32 = 4;
+const CONFIG_RATE_LIMIT_PPS: u32 = 5;
+const CONFIG_RATE_LIMIT_BURST: u32 = 6;
+const CONFIG_RATE_LIMIT_PREFIX_LEN: u32 = 7;
+const CONFIG_CS_MIN_SIZE: u32 = 8;
+const CONFIG_CS_MAX_SIZE: u32 = 9;
+const CONFIG_COUNT: u32 = 10;
+const CS_ADMIT_ALL: u32 = 0;
+const CS_ADMIT_NONE: u32 = 1;
+const CS_ADMIT_LISTED: u32 = 2;
+const CS_PREFIX_ALLOW: u8 = 1;
+const CS_PREFIX_DENY: u8 = 2;
+const FLAG_NO_CS_REPLY: u32 = 1 << 0;
+const FLAG_NO_REDIRECT: u32 = 1 << 1;
+const FLAG_RATE_LIMIT_PASS: u32 = 1 << 2;
+const FLAG_AF_XDP: u32 = 1 << 3;
+
+/// Encoded Data packets the kernel CS holds by default, as built into the
+/// eBPF program
+const DEFAULT_CS_DATA_ENTRIES: u32 = 1024;
+
+/// Longest prefix, in components, the kernel FIB can match
+const MAX_FIB_PREFIX_COMPONENTS: usize = 16;
+
+/// Longest encoded prefix, in bytes, the kernel LPM trie FIB holds
+const MAX_FIB_TRIE_NAME_LENGTH: usize = 64;
+
+/// Name of the eBPF global selecting the kernel FIB
+pub(crate) const FIB_MODE_GLOBAL: &str = "FIB_MODE";
+
+// FNV-1a parameters, matching the eBPF program
+const FNV_OFFSET_BASIS: u32 = 2166136261;
+const FNV_PRIME: u32 = 16777619;
+
+/// Deterministic hash of a name, as used in the keys of the kernel maps
+///
+/// This is FNV-1a over the encoded name components, the same hash the XDP
+/// program computes while parsing a Name TLV.
+pub fn name_hash(name: &Name) -> u32 {
+    prefix_hashes(name).last().copied().unwrap_or(FNV_OFFSET_BASIS)
+}
+
+/// Hashes of every prefix of a name, from its first component to the full
+/// name, as computed by the XDP program for longest prefix matching
+pub fn prefix_hashes(name: &Name) -> Vec<u32> {
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut buf = BytesMut::new();
+
+    name.components()
+        .map(|component| {
+            buf.clear();
+            component.to_tlv().encode(&mut buf);
+            for b in buf.iter() {
+                hash ^= *b as u32;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+            hash
+        })
+        .collect()
+}
+
+/// Name length in the keys of the kernel maps
+fn key_name_len(name: &Name) -> u8 {
+    name.len().min(u8::MAX as usize) as u8
+}
+
+/// Read every counter of the metrics map, summed over all CPUs
+fn read_metrics(bpf: &Bpf) -> Result<Vec<u64>> {
+    let metrics = bpf.map(METRICS_MAP_NAME)
+        .ok_or(XdpError::MapNotFound(METRICS_MAP_NAME))?;
+
+    let metrics: PerCpuArray<&MapData, u64> = PerCpuArray::try_from(metrics)?;
+
+    (0..METRIC_COUNT)
+        .map(|index| Ok(metrics.get(&index, 0)?.iter().sum()))
+        .collect()
+}
+
+/// Counters of the XDP program, summed over all CPUs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KernelMetrics {
+    /// NDN packets seen
+    pub packets_total: u64,
+    /// Interests received
+    pub interests_received: u64,
+    /// Data packets received
+    pub data_received: u64,
+    /// Interests matching the kernel CS
+    pub cs_hits: u64,
+    /// Interests answered from the kernel CS
+    pub cs_replies: u64,
+    /// Data recorded in the kernel CS
+    pub cs_inserts: u64,
+    /// Kernel CS entries evicted on lookup after their TTL ran out
+    pub cs_evictions: u64,
+    /// Interests recorded in the kernel PIT
+    pub pit_inserts: u64,
+    /// Data matching a kernel PIT entry
+    pub pit_matches: u64,
+    /// Interests matching a kernel FIB entry
+    pub fib_hits: u64,
+    /// Interests redirected to an egress interface
+    pub interests_redirected: u64,
+    /// Interests dropped as duplicates of a pending one
+    pub drops_duplicate: u64,
+    /// Interests dropped because their nonce was in the dead nonce list
+    pub drops_loop: u64,
+    /// Interests over the rate limit of their prefix
+    pub drops_rate_limited: u64,
+    /// Interests dropped because their HopLimit ran out
+    pub drops_hop_limit: u64,
+    /// Data dropped because no Interest was pending for it
+    pub drops_unsolicited: u64,
+    /// Packets aborted on a processing error
+    pub aborted: u64,
+    /// Packets passed up because a pipeline stage was not loaded
+    pub pipeline_misses: u64,
+    /// Bytes of Interests received
+    pub interest_bytes: u64,
+    /// Bytes of Data received
+    pub data_bytes: u64,
+    /// Bytes of Data sent from the kernel CS
+    pub cs_reply_bytes: u64,
+    /// NDN-over-TCP segments passed up, with the `tcp` feature
+    pub tcp_segments: u64,
+    /// Payload bytes of NDN-over-TCP segments
+    pub tcp_bytes: u64,
+    /// Interests sent, seen by the TC egress program
+    pub egress_interests: u64,
+    /// Data packets sent, seen by the TC egress program
+    pub egress_data: u64,
+    /// Bytes of NDN packets sent
+    pub egress_bytes: u64,
+    /// Packets sent with an ECN congestion mark
+    pub congestion_marks: u64,
+    /// NDN packets handed to AF_XDP sockets instead of the stack
+    pub xsk_redirects: u64,
+}
+
+impl KernelMetrics {
+    /// Read the counters of a loaded XDP program
+    pub fn read(bpf: &Bpf) -> Result<Self> {
+        let counters = read_metrics(bpf)?;
+        let counter = |index: u32| counters[index as usize];
+
+        Ok(Self {
+            packets_total: counter(METRIC_PACKETS_TOTAL),
+            interests_received: counter(METRIC_INTERESTS_RECEIVED),
+            data_received: counter(METRIC_DATA_RECEIVED),
+            cs_hits: counter(METRIC_CS_HITS),
+            cs_replies: counter(METRIC_CS_REPLIES),
+            cs_inserts: counter(METRIC_CS_INSERTS),
+            cs_evictions: counter(METRIC_CS_EVICTIONS),
+            pit_inserts: counter(METRIC_PIT_INSERTS),
+            pit_matches: counter(METRIC_PIT_MATCHES),
+            fib_hits: counter(METRIC_FIB_HITS),
+            interests_redirected: counter(METRIC_INTERESTS_REDIRECTED),
+            drops_duplicate: counter(METRIC_INTERESTS_DUPLICATE),
+            drops_loop: counter(METRIC_INTERESTS_LOOPED),
+            drops_rate_limited: counter(METRIC_INTERESTS_RATE_LIMITED),
+            drops_hop_limit: counter(METRIC_INTERESTS_HOP_LIMIT),
+            drops_unsolicited: counter(METRIC_DATA_UNSOLICITED),
+            aborted: counter(METRIC_ABORTED),
+            pipeline_misses: counter(METRIC_PIPELINE_MISSES),
+            interest_bytes: counter(METRIC_INTEREST_BYTES),
+            data_bytes: counter(METRIC_DATA_BYTES),
+            cs_reply_bytes: counter(METRIC_CS_REPLY_BYTES),
+            tcp_segments: counter(METRIC_TCP_SEGMENTS),
+            tcp_bytes: counter(METRIC_TCP_BYTES),
+            egress_interests: counter(METRIC_EGRESS_INTERESTS),
+            egress_data: counter(METRIC_EGRESS_DATA),
+            egress_bytes: counter(METRIC_EGRESS_BYTES),
+            congestion_marks: counter(METRIC_CONGESTION_MARKS),
+            xsk_redirects: counter(METRIC_XSK_REDIRECTS),
+        })
+    }
+
+    /// Mirror the counters into the kernel fast path metrics of `metrics`
+    pub fn record(&self, metrics: &UdcnMetrics) {
+        metrics.kernel_packets.set(self.packets_total);
+        metrics.kernel_interests_received.set(self.interests_received);
+        metrics.kernel_data_received.set(self.data_received);
+        metrics.kernel_cs_hits.set(self.cs_replies);
+        metrics.kernel_cs_inserts.set(self.cs_inserts);
+        metrics.kernel_fib_hits.set(self.fib_hits);
+        metrics.kernel_interests_forwarded.set(self.interests_redirected);
+        metrics.kernel_drops.set(self.drops());
+        metrics.kernel_bytes_received.set(self.interest_bytes + self.data_bytes);
+        metrics.kernel_bytes_sent.set(self.cs_reply_bytes);
+        metrics.kernel_interest_rate.update(self.interests_received);
+        metrics.kernel_byte_rate.update(self.interest_bytes + self.data_bytes);
+    }
+
+    /// Packets dropped for any reason
+    pub fn drops(&self) -> u64 {
+        self.drops_duplicate
+            + self.drops_loop
+            + self.drops_rate_limited
+            + self.drops_hop_limit
+            + self.drops_unsolicited
+            + self.aborted
+    }
+}
+
+/// NDN traffic the XDP program saw arrive on one interface, summed over
+/// all CPUs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceStats {
+    /// Index of the interface
+    pub ifindex: u32,
+    /// Name of the interface, if it still exists
+    pub name: Option<String>,
+    /// Interests received
+    pub interests: u64,
+    /// Data packets received
+    pub data: u64,
+    /// Interests and Data dropped
+    pub drops: u64,
+    /// Bytes of the NDN packets received
+    pub bytes: u64,
+}
+
+impl InterfaceStats {
+    /// Read the counters of every interface the loaded XDP program has seen
+    /// traffic on, ordered by interface index
+    pub fn read_all(bpf: &Bpf) -> Result<Vec<Self>> {
+        let counters = bpf.map(INTERFACE_COUNTERS_NAME)
+            .ok_or(XdpError::MapNotFound(INTERFACE_COUNTERS_NAME))?;
+
+        let counters: PerCpuHashMap<&MapData, u32, InterfaceCounters> = PerCpuHashMap::try_from(counters)?;
+
+        let mut stats = counters
+            .iter()
+            .map(|entry| {
+                let (ifindex, per_cpu) = entry?;
+                let mut total = Self {
+                    ifindex,
+                    name: interface_name(ifindex),
+                    interests: 0,
+                    data: 0,
+                    drops: 0,
+                    bytes: 0,
+                };
+                for counters in per_cpu.iter() {
+                    total.interests += counters.interests;
+                    total.data += counters.data;
+                    total.drops += counters.drops;
+                    total.bytes += counters.bytes;
+                }
+                Ok(total)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        stats.sort_by_key(|stats| stats.ifindex);
+
+        Ok(stats)
+    }
+}
+
+/// Name of the interface with index `ifindex`
+fn interface_name(ifindex: u32) -> Option<String> {
+    let mut buf = [0 as libc::c_char; libc::IF_NAMESIZE];
+    // SAFETY: buf holds IF_NAMESIZE bytes, as if_indextoname requires
+    let name = unsafe { libc::if_indextoname(ifindex, buf.as_mut_ptr()) };
+    if name.is_null() {
+        return None;
+    }
+    // SAFETY: on success buf holds a NUL-terminated name
+    let name = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Number of entries of the kernel tables, fixed when the program is loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapSizes {
+    /// Pending Interests in the kernel PIT
+    pub pit_entries: u32,
+    /// Prefixes in the kernel FIB, in either mode
+    pub fib_entries: u32,
+    /// Names the kernel CS knows to be cached
+    pub cs_entries: u32,
+    /// Encoded Data packets the kernel CS can answer Interests with
+    pub cs_data_entries: u32,
+}
+
+impl Default for MapSizes {
+    fn default() -> Self {
+        Self {
+            pit_entries: MAX_PIT_ENTRIES as u32,
+            fib_entries: MAX_FIB_ENTRIES as u32,
+            cs_entries: MAX_CS_ENTRIES as u32,
+            cs_data_entries: DEFAULT_CS_DATA_ENTRIES,
+        }
+    }
+}
+
+impl MapSizes {
+    /// Resize the maps of a program about to be loaded, overriding the
+    /// sizes built into the eBPF object
+    pub(crate) fn apply(&self, loader: &mut BpfLoader) -> Result<()> {
+        let sizes = [
+            (PIT_TABLE_NAME, self.pit_entries),
+            (FIB_TABLE_NAME, self.fib_entries),
+            (FIB_TRIE_NAME, self.fib_entries),
+            (CS_TABLE_NAME, self.cs_entries),
+            (CS_DATA_NAME, self.cs_data_entries),
+        ];
+        for (name, size) in sizes {
+            if size == 0 {
+                return Err(XdpError::Config(format!("Map '{}' must have at least one entry", name)));
+            }
+            loader.set_max_entries(name, size);
+        }
+        Ok(())
+    }
+}
 
 /// Wrapper for accessing the PIT (Pending Interest Table) from userspace
 pub struct PendingInterestTable {
     /// The underlying eBPF LRU hash map
     map: Arc<RwLock<LruHashMap<MapData, PitKey, PitValue>>>,
+    /// When the nonces of Interests no longer pending were retired
+    dead_nonces: Arc<RwLock<LruHashMap<MapData, DeadNonceKey, u64>>>,
 }
 
 impl PendingInterestTable {
     /// Create a new PIT wrapper from a BPF object
     pub fn new(bpf: &mut Bpf) -> Result<Self> {
         let map = bpf.map_mut(PIT_TABLE_NAME)
-            .context(format!("Failed to find map '{}'", PIT_TABLE_NAME))?;
+            .ok_or(XdpError::MapNotFound(PIT_TABLE_NAME))?;
         
         let map = map.try_into()?;
+
+        let dead_nonces = bpf.map_mut(DEAD_NONCES_NAME)
+            .ok_or(XdpError::MapNotFound(DEAD_NONCES_NAME))?;
+
+        let dead_nonces = dead_nonces.try_into()?;
         
         Ok(Self {
             map: Arc::new(RwLock::new(map)),
+            dead_nonces: Arc::new(RwLock::new(dead_nonces)),
         })
     }
 
@@ -73,80 +443,226 @@ impl PendingInterestTable {
         Ok(())
     }
 
+    /// Remove the entry for `name`. Returns the number of entries removed.
+    pub async fn remove_name(&self, name: &Name) -> Result<usize> {
+        let key = PitKey {
+            name_hash: name_hash(name),
+            name_len: key_name_len(name),
+        };
+        let mut map = self.map.write().await;
+        match map.remove(&key) {
+            Ok(()) => Ok(1),
+            Err(MapError::KeyNotFound) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     /// Get all entries in the PIT
     /// 
     /// Note: This is not an atomic operation and may not reflect the exact state
     /// of the PIT if concurrent modifications are happening.
     pub async fn get_all_entries(&self) -> Result<Vec<(PitKey, PitValue)>> {
-        // This is a simplified approach as we can't directly iterate eBPF maps from userspace
-        // In a real implementation, we'd have a more sophisticated approach
         let map = self.map.read().await;
-        let mut entries = Vec::new();
+        let entries = map.iter().collect::<std::result::Result<Vec<_>, MapError>>()?;
         
-        // Try to fetch a reasonable number of entries by probing keys
-        // This is not a complete solution, just a demonstration
-        for name_hash in 0..1000 {
-            for name_len in 1..=5 {
-                for nonce in 0..5 {
-                    let key = PitKey {
-                        name_hash: name_hash as u32,
-                        name_len,
-                        nonce: nonce as u32,
-                    };
-                    
-                    match map.get(&key, 0) {
-                        Ok(v) => entries.push((key, v)),
-                        Err(MapError::KeyNotFound) => (),
-                        Err(e) => return Err(e.into()),
-                    }
-                }
+        Ok(entries)
+    }
+
+    /// Remove the entries with these keys. Returns the number of entries
+    /// removed, skipping keys that are already gone.
+    pub async fn remove_keys(&self, keys: &[PitKey]) -> Result<usize> {
+        let mut map = self.map.write().await;
+        let mut removed = 0;
+        for key in keys {
+            match map.remove(key) {
+                Ok(()) => removed += 1,
+                Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
             }
         }
         
-        Ok(entries)
+        Ok(removed)
     }
 
-    /// Count entries in the PIT (approximate)
+    /// Count entries in the PIT
     pub async fn count_entries(&self) -> Result<usize> {
         let entries = self.get_all_entries().await?;
         Ok(entries.len())
     }
+
+    /// Remove the entries whose lifetime has passed, adding the nonces of
+    /// their Interests to the dead nonce list so the kernel still catches
+    /// them if they loop. Returns the number of entries removed.
+    pub async fn remove_expired(&self) -> Result<usize> {
+        let now_ms = clock::kernel_now_ms();
+        let mut map = self.map.write().await;
+        let expired: Vec<(PitKey, PitValue)> = map
+            .iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|(_, value)| clock::is_expired(value.timestamp, value.lifetime_ms as u64, now_ms))
+            .collect();
+
+        let mut dead_nonces = self.dead_nonces.write().await;
+        let mut removed = 0;
+        for (key, value) in &expired {
+            match map.remove(key) {
+                Ok(()) => removed += 1,
+                Err(MapError::KeyNotFound) => continue,
+                Err(e) => return Err(e.into()),
+            }
+            for dead in value.dead_nonces(key) {
+                dead_nonces.insert(dead, now_ms, 0)?;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Periodically remove expired entries, counting them in
+    /// `metrics.pit_expirations`, instead of leaving them to LRU eviction,
+    /// until `cancellation` is cancelled
+    pub fn spawn_gc(
+        self: Arc<Self>,
+        interval: Duration,
+        metrics: Arc<UdcnMetrics>,
+        cancellation: CancellationToken,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancellation.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                match self.remove_expired().await {
+                    Ok(0) => {}
+                    Ok(removed) => {
+                        debug!("Removed {} expired kernel PIT entries", removed);
+                        metrics.pit_expirations.add(removed as u64);
+                    }
+                    Err(e) => warn!("Failed to remove expired kernel PIT entries: {}", e),
+                }
+            }
+        })
+    }
+}
+
+/// How the kernel FIB matches Interest names against prefixes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FibMode {
+    /// Hash each prefix of the name and probe a hash map, longest first
+    #[default]
+    Hash,
+    /// Look up the encoded name in an LPM trie, which matches whole
+    /// components and has no hash collisions
+    LpmTrie,
+}
+
+impl FibMode {
+    /// Value of the eBPF global selecting this mode
+    pub(crate) fn global_value(self) -> u8 {
+        match self {
+            FibMode::Hash => 0,
+            FibMode::LpmTrie => 1,
+        }
+    }
+}
+
+/// Key of `name_prefix` in the LPM trie FIB: its encoded components, with
+/// the prefix length in bits
+fn trie_key(name_prefix: &Name) -> Result<Key<[u8; MAX_FIB_TRIE_NAME_LENGTH]>> {
+    let mut buf = BytesMut::new();
+    for component in name_prefix.components() {
+        component.to_tlv().encode(&mut buf);
+    }
+    if buf.len() > MAX_FIB_TRIE_NAME_LENGTH {
+        return Err(XdpError::PrefixTooLong {
+            prefix: name_prefix.clone(),
+            limit: MAX_FIB_TRIE_NAME_LENGTH,
+            unit: "bytes",
+        });
+    }
+
+    let mut data = [0u8; MAX_FIB_TRIE_NAME_LENGTH];
+    data[..buf.len()].copy_from_slice(&buf);
+    Ok(Key::new((buf.len() * 8) as u32, data))
+}
+
+/// Key of `name_prefix` in the hash FIB
+fn hash_key(name_prefix: &Name) -> Result<FibKey> {
+    // The kernel only probes this many prefixes of a name
+    if name_prefix.len() > MAX_FIB_PREFIX_COMPONENTS {
+        return Err(XdpError::PrefixTooLong {
+            prefix: name_prefix.clone(),
+            limit: MAX_FIB_PREFIX_COMPONENTS,
+            unit: "components",
+        });
+    }
+
+    Ok(FibKey {
+        prefix_hash: name_hash(name_prefix),
+        prefix_len: name_prefix.len() as u8,
+    })
 }
 
 /// Wrapper for accessing the FIB (Forwarding Information Base) from userspace
 pub struct Fib {
     /// The underlying eBPF hash map
     map: Arc<RwLock<HashMap<MapData, FibKey, FibValue>>>,
+
+    /// The LPM trie used instead in `FibMode::LpmTrie`
+    trie: Arc<RwLock<LpmTrie<MapData, [u8; MAX_FIB_TRIE_NAME_LENGTH], FibValue>>>,
+
+    /// Which of the two the kernel matches against
+    mode: FibMode,
+
+    /// Names of the prefixes hashed into `map`
+    names: Arc<NameTable>,
 }
 
 impl Fib {
-    /// Create a new FIB wrapper from a BPF object
-    pub fn new(bpf: &mut Bpf) -> Result<Self> {
+    /// Create a new FIB wrapper from a BPF object loaded with `mode`,
+    /// recording the prefixes of its routes in `names`
+    pub fn new(bpf: &mut Bpf, mode: FibMode, names: Arc<NameTable>) -> Result<Self> {
         let map = bpf.map_mut(FIB_TABLE_NAME)
-            .context(format!("Failed to find map '{}'", FIB_TABLE_NAME))?;
+            .ok_or(XdpError::MapNotFound(FIB_TABLE_NAME))?;
         
         let map = map.try_into()?;
+
+        let trie = bpf.map_mut(FIB_TRIE_NAME)
+            .ok_or(XdpError::MapNotFound(FIB_TRIE_NAME))?;
+
+        let trie = trie.try_into()?;
         
         Ok(Self {
             map: Arc::new(RwLock::new(map)),
+            trie: Arc::new(RwLock::new(trie)),
+            mode,
+            names,
         })
     }
 
+    /// How the kernel matches names against this FIB
+    pub fn mode(&self) -> FibMode {
+        self.mode
+    }
+
     /// Add a route to the FIB
     pub async fn add_route(&self, name_prefix: &Name, face_id: FaceId, cost: u8) -> Result<()> {
-        // Compute a deterministic hash for the name prefix
-        let prefix_hash = self.compute_prefix_hash(name_prefix);
-        let prefix_len = name_prefix.len() as u8;
-        
-        let key = FibKey {
-            prefix_hash,
-            prefix_len,
-        };
-        
+        if self.mode == FibMode::LpmTrie {
+            let key = trie_key(name_prefix)?;
+            self.trie.write().await.insert(&key, FibValue { face_id, cost }, 0)?;
+            self.names.add_prefix(name_prefix).await;
+            info!("Added route for prefix {} to face {}", name_prefix, face_id.0);
+            return Ok(());
+        }
+
+        let key = hash_key(name_prefix)?;
         let value = FibValue { face_id, cost };
         
         let mut map = self.map.write().await;
         map.insert(&key, &value, 0)?;
+        self.names.add_prefix(name_prefix).await;
         
         info!("Added route for prefix {} to face {}", name_prefix, face_id.0);
         
@@ -155,6 +671,14 @@ impl Fib {
 
     /// Remove a route from the FIB
     pub async fn remove_route(&self, name_prefix: &Name) -> Result<()> {
+        if self.mode == FibMode::LpmTrie {
+            let key = trie_key(name_prefix)?;
+            self.trie.write().await.remove(&key)?;
+            self.names.remove_prefix(name_prefix).await;
+            info!("Removed route for prefix {}", name_prefix);
+            return Ok(());
+        }
+
         // Compute the same deterministic hash
         let prefix_hash = self.compute_prefix_hash(name_prefix);
         let prefix_len = name_prefix.len() as u8;
@@ -166,14 +690,25 @@ impl Fib {
         
         let mut map = self.map.write().await;
         map.remove(&key)?;
+        self.names.remove_prefix(name_prefix).await;
         
         info!("Removed route for prefix {}", name_prefix);
         
         Ok(())
     }
 
-    /// Get a route from the FIB
+    /// Get a route from the FIB. In `FibMode::LpmTrie` this is the route
+    /// of the longest prefix of `name_prefix`, as the kernel would match it.
     pub async fn get_route(&self, name_prefix: &Name) -> Result<Option<FibValue>> {
+        if self.mode == FibMode::LpmTrie {
+            let key = trie_key(name_prefix)?;
+            return match self.trie.read().await.get(&key, 0) {
+                Ok(value) => Ok(Some(value)),
+                Err(MapError::KeyNotFound) => Ok(None),
+                Err(e) => Err(e.into()),
+            };
+        }
+
         // Compute the same deterministic hash
         let prefix_hash = self.compute_prefix_hash(name_prefix);
         let prefix_len = name_prefix.len() as u8;
@@ -187,29 +722,313 @@ impl Fib {
         Ok(map.get(&key, 0)?)
     }
 
+    /// Apply many route changes under a single lock of the map
+    ///
+    /// If `atomic`, either all changes are applied or, on the first
+    /// failure, those already applied are undone and its error returned.
+    /// Otherwise every change that can be applied is, and the error counts
+    /// those that could not. Removing a prefix without a route is not a
+    /// failure.
+    pub async fn apply_batch(&self, updates: Vec<FibUpdate>, atomic: bool) -> Result<()> {
+        let applied = if self.mode == FibMode::LpmTrie {
+            let mut trie = self.trie.write().await;
+            apply_updates(&mut *trie, &updates, atomic)?
+        } else {
+            let mut map = self.map.write().await;
+            apply_updates(&mut *map, &updates, atomic)?
+        };
+
+        let mut failed = 0;
+        for (update, applied) in updates.iter().zip(applied) {
+            if !applied {
+                failed += 1;
+                continue;
+            }
+            match update {
+                FibUpdate::Set { prefix, .. } => self.names.add_prefix(prefix).await,
+                FibUpdate::Remove { prefix } => self.names.remove_prefix(prefix).await,
+            }
+        }
+        info!("Applied {} of {} FIB updates", updates.len() - failed, updates.len());
+
+        if failed > 0 {
+            return Err(XdpError::PartialUpdate {
+                failed,
+                total: updates.len(),
+            });
+        }
+        Ok(())
+    }
+
     /// Get all entries in the FIB
+    ///
+    /// The hash FIB only holds prefix hashes, so its prefixes are those
+    /// recorded when their routes were added; routes added by another
+    /// process are left out.
     pub async fn get_all_entries(&self) -> Result<Vec<(Name, FaceId, u8)>> {
-        // This is a simplified placeholder
-        // In a real implementation, we'd have a more sophisticated approach
-        // to retrieve all entries, possibly with help from the kernel
-        unimplemented!("Not yet implemented")
+        let mut entries = Vec::new();
+
+        if self.mode == FibMode::LpmTrie {
+            let trie = self.trie.read().await;
+            for entry in trie.iter() {
+                let (key, value) = entry?;
+                let len = key.prefix_len() as usize / 8;
+                match names::decode_components(&key.data()[..len]) {
+                    Some(prefix) => entries.push((prefix, value.face_id, value.cost)),
+                    None => warn!("Undecodable prefix in the kernel FIB"),
+                }
+            }
+            return Ok(entries);
+        }
+
+        let map = self.map.read().await;
+        for entry in map.iter() {
+            let (key, value) = entry?;
+            match self.names.get(key.prefix_hash, key.prefix_len).await {
+                Some(prefix) => entries.push((prefix, value.face_id, value.cost)),
+                None => debug!("No name for kernel FIB prefix {:#010x}", key.prefix_hash),
+            }
+        }
+
+        Ok(entries)
     }
 
     /// Compute a deterministic hash for a name prefix
     fn compute_prefix_hash(&self, name: &Name) -> u32 {
-        // Simple FNV-1a hash
-        let mut hash: u32 = 2166136261;
-        let prime: u32 = 16777619;
+        name_hash(name)
+    }
+}
 
-        // Convert name to string for hashing
-        let name_str = name.to_string();
-        
-        for b in name_str.bytes() {
-            hash ^= b as u32;
-            hash = hash.wrapping_mul(prime);
+/// A kernel map the FIB is kept in, so batches of updates are applied the
+/// same way to either
+trait FibEntries {
+    type Key;
+
+    /// Key of `prefix` in the map
+    fn key(prefix: &Name) -> Result<Self::Key>;
+
+    /// Current entry of each key
+    fn entries(&self, keys: &[Self::Key]) -> Result<Vec<Option<FibValue>>>;
+
+    /// Set the entry of a key, or remove it if `value` is none
+    fn set(&mut self, key: &Self::Key, value: Option<FibValue>) -> Result<()>;
+}
+
+impl FibEntries for HashMap<MapData, FibKey, FibValue> {
+    type Key = FibKey;
+
+    fn key(prefix: &Name) -> Result<FibKey> {
+        hash_key(prefix)
+    }
+
+    fn entries(&self, keys: &[FibKey]) -> Result<Vec<Option<FibValue>>> {
+        keys.iter()
+            .map(|key| match self.get(key, 0) {
+                Ok(value) => Ok(Some(value)),
+                Err(MapError::KeyNotFound) => Ok(None),
+                Err(e) => Err(e.into()),
+            })
+            .collect()
+    }
+
+    fn set(&mut self, key: &FibKey, value: Option<FibValue>) -> Result<()> {
+        match value {
+            Some(value) => self.insert(key, value, 0)?,
+            None => match self.remove(key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+        Ok(())
+    }
+}
+
+impl FibEntries for LpmTrie<MapData, [u8; MAX_FIB_TRIE_NAME_LENGTH], FibValue> {
+    type Key = Key<[u8; MAX_FIB_TRIE_NAME_LENGTH]>;
+
+    fn key(prefix: &Name) -> Result<Self::Key> {
+        trie_key(prefix)
+    }
+
+    fn entries(&self, keys: &[Self::Key]) -> Result<Vec<Option<FibValue>>> {
+        // Lookups return the longest matching prefix, not the exact one,
+        // so read the whole trie instead
+        let mut all = std::collections::HashMap::new();
+        for entry in self.iter() {
+            let (key, value) = entry?;
+            all.insert((key.prefix_len(), key.data()), value);
+        }
+        Ok(keys
+            .iter()
+            .map(|key| all.get(&(key.prefix_len(), key.data())).copied())
+            .collect())
+    }
+
+    fn set(&mut self, key: &Self::Key, value: Option<FibValue>) -> Result<()> {
+        match value {
+            Some(value) => self.insert(key, value, 0)?,
+            None => match self.remove(key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Apply `updates` to `map`, returning which were applied
+///
+/// If `atomic`, the first failure undoes the updates already applied and
+/// is returned instead.
+fn apply_updates<M: FibEntries>(map: &mut M, updates: &[FibUpdate], atomic: bool) -> Result<Vec<bool>> {
+    if !atomic {
+        return Ok(updates
+            .iter()
+            .map(|update| {
+                match M::key(update.prefix()).and_then(|key| map.set(&key, update.value())) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        warn!("Failed to update the FIB entry of {}: {}", update.prefix(), e);
+                        false
+                    }
+                }
+            })
+            .collect());
+    }
+
+    let keys = updates
+        .iter()
+        .map(|update| M::key(update.prefix()))
+        .collect::<Result<Vec<_>>>()?;
+    let previous = map.entries(&keys)?;
+
+    for (i, (update, key)) in updates.iter().zip(&keys).enumerate() {
+        if let Err(e) = map.set(key, update.value()) {
+            // Undo in reverse, so a prefix updated twice gets its first value
+            let applied = updates[..i].iter().zip(&keys).zip(&previous);
+            for ((applied, key), value) in applied.rev() {
+                if let Err(undo) = map.set(key, *value) {
+                    warn!("Failed to restore the FIB entry of {}: {}", applied.prefix(), undo);
+                }
+            }
+            return Err(XdpError::FibUpdate {
+                prefix: update.prefix().clone(),
+                source: Box::new(e),
+            });
+        }
+    }
+
+    Ok(vec![true; updates.len()])
+}
+
+/// Wrapper for the interfaces the kernel redirects Interests to, keyed by
+/// the face IDs used in the FIB
+pub struct Egress {
+    /// Egress interface of each face, used by XDP_REDIRECT
+    devmap: Arc<RwLock<DevMapHash<MapData>>>,
+
+    /// Headers to rewrite when sending out of each face
+    faces: Arc<RwLock<HashMap<MapData, u32, EgressFace>>>,
+}
+
+impl Egress {
+    /// Create a new egress wrapper from a BPF object
+    pub fn new(bpf: &mut Bpf) -> Result<Self> {
+        let devmap = bpf.map_mut(EGRESS_NAME)
+            .ok_or(XdpError::MapNotFound(EGRESS_NAME))?;
+
+        let devmap = devmap.try_into()?;
+
+        let faces = bpf.map_mut(EGRESS_FACES_NAME)
+            .ok_or(XdpError::MapNotFound(EGRESS_FACES_NAME))?;
+
+        let faces = faces.try_into()?;
+
+        Ok(Self {
+            devmap: Arc::new(RwLock::new(devmap)),
+            faces: Arc::new(RwLock::new(faces)),
+        })
+    }
+
+    /// Let the kernel forward Interests routed to `face_id` out of
+    /// `face.ifindex`, rewriting their headers to reach the next hop
+    pub async fn add_face(&self, face_id: FaceId, face: &EgressFace) -> Result<()> {
+        let key = face_id.0 as u32;
+        // Headers first, so the kernel never redirects without them
+        self.faces.write().await.insert(key, face, 0)?;
+        self.devmap.write().await.insert(key, face.ifindex, None, 0)?;
+
+        info!("Kernel forwards face {} out of interface {}", face_id.0, face.ifindex);
+
+        Ok(())
+    }
+
+    /// Stop forwarding Interests routed to `face_id` in the kernel
+    pub async fn remove_face(&self, face_id: FaceId) -> Result<()> {
+        let key = face_id.0 as u32;
+        match self.devmap.write().await.remove(key) {
+            Ok(()) | Err(MapError::KeyNotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+        match self.faces.write().await.remove(&key) {
+            Ok(()) | Err(MapError::KeyNotFound) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Get how the kernel sends packets out of `face_id`
+    pub async fn get_face(&self, face_id: FaceId) -> Result<Option<EgressFace>> {
+        let faces = self.faces.read().await;
+        match faces.get(&(face_id.0 as u32), 0) {
+            Ok(face) => Ok(Some(face)),
+            Err(MapError::KeyNotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Wrapper for the interfaces whose outgoing NDN packets the TC egress
+/// program marks as Congestion Experienced
+pub struct CongestionMarks {
+    /// The underlying eBPF map, keyed by interface index
+    map: Arc<RwLock<HashMap<MapData, u32, u8>>>,
+}
+
+impl CongestionMarks {
+    /// Create a new congestion mark wrapper from a BPF object
+    pub fn new(bpf: &mut Bpf) -> Result<Self> {
+        let map = bpf.map_mut(CONGESTED_NAME)
+            .ok_or(XdpError::MapNotFound(CONGESTED_NAME))?;
+
+        let map = map.try_into()?;
+
+        Ok(Self {
+            map: Arc::new(RwLock::new(map)),
+        })
+    }
+
+    /// Start or stop marking ECN-capable NDN packets sent out of `ifindex`
+    pub async fn set(&self, ifindex: u32, congested: bool) -> Result<()> {
+        let mut map = self.map.write().await;
+        if congested {
+            map.insert(ifindex, 1, 0)?;
+            debug!("Marking NDN packets sent out of interface {}", ifindex);
+        } else {
+            match map.remove(&ifindex) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether packets sent out of `ifindex` are marked
+    pub async fn is_congested(&self, ifindex: u32) -> Result<bool> {
+        match self.map.read().await.get(&ifindex, 0) {
+            Ok(_) => Ok(true),
+            Err(MapError::KeyNotFound) => Ok(false),
+            Err(e) => Err(e.into()),
         }
-        
-        hash
     }
 }
 
@@ -217,18 +1036,37 @@ impl Fib {
 pub struct ContentStore {
     /// The underlying eBPF LRU hash map
     map: Arc<RwLock<LruHashMap<MapData, CsKey, CsValue>>>,
+
+    /// Encoded Data the kernel answers matching Interests with
+    data: Arc<RwLock<LruHashMap<MapData, CsKey, CsData>>>,
+
+    /// Number of entries the map was created with
+    capacity: usize,
+
+    /// Names of the entries userspace inserts, next to those of the
+    /// Interests the kernel reports
+    names: Arc<NameTable>,
 }
 
 impl ContentStore {
-    /// Create a new CS wrapper from a BPF object
-    pub fn new(bpf: &mut Bpf) -> Result<Self> {
+    /// Create a new CS wrapper from a BPF object, whose CS map holds
+    /// `capacity` entries, recording the names of its entries in `names`
+    pub fn new(bpf: &mut Bpf, capacity: usize, names: Arc<NameTable>) -> Result<Self> {
         let map = bpf.map_mut(CS_TABLE_NAME)
-            .context(format!("Failed to find map '{}'", CS_TABLE_NAME))?;
+            .ok_or(XdpError::MapNotFound(CS_TABLE_NAME))?;
         
         let map = map.try_into()?;
+
+        let data = bpf.map_mut(CS_DATA_NAME)
+            .ok_or(XdpError::MapNotFound(CS_DATA_NAME))?;
+
+        let data = data.try_into()?;
         
         Ok(Self {
             map: Arc::new(RwLock::new(map)),
+            data: Arc::new(RwLock::new(data)),
+            capacity,
+            names,
         })
     }
 
@@ -256,6 +1094,160 @@ impl ContentStore {
         Ok(())
     }
 
+    /// Record that Data for `name` is cached in userspace, so the kernel
+    /// reports matching Interests as CS hits, MustBeFresh ones only within
+    /// `freshness_ms`
+    pub async fn insert_name(&self, name: &Name, content_size: u32, ttl_ms: u32, freshness_ms: u32) -> Result<()> {
+        let key = CsKey {
+            name_hash: name_hash(name),
+            name_len: key_name_len(name),
+        };
+        let value = CsValue {
+            content_hash: 0,
+            // The kernel checks the TTL against its own clock
+            timestamp: clock::kernel_now_ms(),
+            content_size,
+            ttl_ms,
+            freshness_ms,
+            _pad: 0,
+        };
+        self.insert(&key, &value).await?;
+        self.names.observe(name.clone()).await;
+        Ok(())
+    }
+
+    /// Store the encoded Data for `name` in the kernel, so matching
+    /// Interests are answered without leaving the kernel. Returns false if
+    /// the Data is too large for the kernel to hold.
+    pub async fn insert_data(&self, name: &Name, wire: &[u8]) -> Result<bool> {
+        let Some(value) = CsData::new(wire) else {
+            return Ok(false);
+        };
+        let key = CsKey {
+            name_hash: name_hash(name),
+            name_len: key_name_len(name),
+        };
+        let mut data = self.data.write().await;
+        data.insert(&key, &value, 0)?;
+        self.names.observe(name.clone()).await;
+        Ok(true)
+    }
+
+    /// Forget the cached Data for `name`
+    pub async fn remove_name(&self, name: &Name) -> Result<()> {
+        let key = CsKey {
+            name_hash: name_hash(name),
+            name_len: key_name_len(name),
+        };
+        match self.data.write().await.remove(&key) {
+            Ok(()) | Err(MapError::KeyNotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+        match self.remove(&key).await {
+            Err(XdpError::Map(MapError::KeyNotFound)) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Remove every entry under `prefix`, so stale Data is no longer
+    /// reported or answered from the kernel. Returns the number of names
+    /// removed.
+    ///
+    /// The CS is keyed by name hash, so entries are matched through their
+    /// names in the shadow name table; entries whose name is not known
+    /// there are left to expire.
+    pub async fn purge_prefix(&self, prefix: &Name) -> Result<usize> {
+        let mut map = self.map.write().await;
+        let mut data = self.data.write().await;
+
+        let mut keys: Vec<CsKey> = map.keys().filter_map(|key| key.ok()).collect();
+        keys.extend(data.keys().filter_map(|key| key.ok()));
+        keys.sort_by_key(|key| (key.name_hash, key.name_len));
+        keys.dedup_by_key(|key| (key.name_hash, key.name_len));
+
+        let mut purged = 0;
+        for key in &keys {
+            let under_prefix = match self.names.get(key.name_hash, key.name_len).await {
+                Some(name) => prefix.len() <= name.len() && prefix.is_prefix_of(&name),
+                None => false,
+            };
+            if !under_prefix {
+                continue;
+            }
+            for result in [map.remove(key), data.remove(key)] {
+                match result {
+                    Ok(()) | Err(MapError::KeyNotFound) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            purged += 1;
+        }
+
+        info!("Purged {} kernel CS entries under {}", purged, prefix);
+        Ok(purged)
+    }
+
+    /// Evict entries until the Data they record adds up to at most
+    /// `max_bytes`, expired entries first, then the oldest. Returns the
+    /// number of entries evicted.
+    pub async fn evict_to_budget(&self, max_bytes: u64) -> Result<usize> {
+        let now_ms = clock::kernel_now_ms();
+        let mut map = self.map.write().await;
+        let mut entries: Vec<(CsKey, CsValue)> = map.iter().filter_map(|entry| entry.ok()).collect();
+        let mut total: u64 = entries.iter().map(|(_, value)| value.content_size as u64).sum();
+        if total <= max_bytes {
+            return Ok(0);
+        }
+
+        entries.sort_by_key(|(_, value)| {
+            let fresh = !clock::is_expired(value.timestamp, value.ttl_ms as u64, now_ms);
+            (fresh, value.timestamp)
+        });
+
+        let mut data = self.data.write().await;
+        let mut evicted = 0;
+        for (key, value) in &entries {
+            if total <= max_bytes {
+                break;
+            }
+            for result in [map.remove(key), data.remove(key)] {
+                match result {
+                    Ok(()) | Err(MapError::KeyNotFound) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            total -= value.content_size as u64;
+            evicted += 1;
+        }
+
+        Ok(evicted)
+    }
+
+    /// Periodically evict entries so the kernel CS stays within
+    /// `max_bytes` of Data, a budget LRU eviction by entry count cannot
+    /// keep when Data sizes vary, until `cancellation` is cancelled
+    pub fn spawn_eviction(
+        self: Arc<Self>,
+        interval: Duration,
+        max_bytes: u64,
+        cancellation: CancellationToken,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancellation.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                match self.evict_to_budget(max_bytes).await {
+                    Ok(0) => {}
+                    Ok(evicted) => debug!("Evicted {} kernel CS entries over the byte budget", evicted),
+                    Err(e) => warn!("Failed to evict kernel CS entries: {}", e),
+                }
+            }
+        })
+    }
+
     /// Clear the entire content store
     pub async fn clear(&self) -> Result<()> {
         // Since we can't directly clear an eBPF map, we would need
@@ -267,14 +1259,11 @@ impl ContentStore {
 
     /// Get the CS hit rate
     pub async fn hit_rate(&self, bpf: &Bpf) -> Result<f64> {
-        // Get the metrics map to read the hit/miss counters
-        let metrics = bpf.map(METRICS_MAP_NAME)
-            .context(format!("Failed to find map '{}'", METRICS_MAP_NAME))?;
-            
-        let metrics: HashMap<MapData, u32, u64> = metrics.try_into()?;
+        // Read the hit/miss counters from the metrics map
+        let metrics = KernelMetrics::read(bpf)?;
         
-        let hits = metrics.get(&METRIC_CS_HITS, 0).unwrap_or(0);
-        let misses = metrics.get(&METRIC_INTERESTS_RECEIVED, 0).unwrap_or(0).saturating_sub(hits);
+        let hits = metrics.cs_hits;
+        let misses = metrics.interests_received.saturating_sub(hits);
         
         if hits + misses == 0 {
             return Ok(0.0);
@@ -285,16 +1274,13 @@ impl ContentStore {
 
     /// Get statistics about the CS
     pub async fn get_stats(&self, bpf: &Bpf) -> Result<ContentStoreStats> {
-        let metrics = bpf.map(METRICS_MAP_NAME)
-            .context(format!("Failed to find map '{}'", METRICS_MAP_NAME))?;
-            
-        let metrics: HashMap<MapData, u32, u64> = metrics.try_into()?;
+        let metrics = KernelMetrics::read(bpf)?;
         
-        let hits = metrics.get(&METRIC_CS_HITS, 0).unwrap_or(0);
-        let inserts = metrics.get(&METRIC_CS_INSERTS, 0).unwrap_or(0);
+        let hits = metrics.cs_hits;
+        let inserts = metrics.cs_inserts;
         
-        // Map capacity is fixed at creation time in the eBPF program
-        let capacity = MAX_CS_ENTRIES;
+        // Map capacity is fixed when the program is loaded
+        let capacity = self.capacity;
         
         // Size could be measured more accurately but this is a simplification
         let size = inserts.min(capacity as u64);
@@ -320,3 +1306,379 @@ pub struct ContentStoreStats {
     /// Current size (approximate)
     pub size: u64,
 }
+
+/// Which Data the kernel CS records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CsAdmission {
+    /// Every Data matching a kernel PIT entry
+    #[default]
+    All,
+    /// None, leaving caching to userspace
+    None,
+    /// Only Data under prefixes allowed in `CsPrefixes`
+    Listed,
+}
+
+/// Whether Data under a name prefix enters the kernel CS, whatever the
+/// admission setting. The rule of the longest listed prefix of a name wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsPrefixRule {
+    /// Cache Data under the prefix
+    Allow,
+    /// Never cache Data under the prefix
+    Deny,
+}
+
+impl CsPrefixRule {
+    fn to_value(self) -> u8 {
+        match self {
+            CsPrefixRule::Allow => CS_PREFIX_ALLOW,
+            CsPrefixRule::Deny => CS_PREFIX_DENY,
+        }
+    }
+
+    fn from_value(value: u8) -> Option<Self> {
+        match value {
+            CS_PREFIX_ALLOW => Some(CsPrefixRule::Allow),
+            CS_PREFIX_DENY => Some(CsPrefixRule::Deny),
+            _ => None,
+        }
+    }
+}
+
+/// Wrapper for the name prefixes allowed in or denied from the kernel CS
+pub struct CsPrefixes {
+    /// Rule of each prefix, keyed like the hash FIB
+    map: Arc<RwLock<HashMap<MapData, FibKey, u8>>>,
+}
+
+impl CsPrefixes {
+    /// Create a new wrapper from a BPF object
+    pub fn new(bpf: &mut Bpf) -> Result<Self> {
+        let map = bpf.map_mut(CS_PREFIXES_NAME)
+            .ok_or(XdpError::MapNotFound(CS_PREFIXES_NAME))?;
+
+        let map = map.try_into()?;
+
+        Ok(Self {
+            map: Arc::new(RwLock::new(map)),
+        })
+    }
+
+    /// Set the rule of a prefix
+    pub async fn set(&self, prefix: &Name, rule: CsPrefixRule) -> Result<()> {
+        let key = hash_key(prefix)?;
+        self.map.write().await.insert(key, rule.to_value(), 0)?;
+        info!("Kernel CS rule for {} set to {:?}", prefix, rule);
+        Ok(())
+    }
+
+    /// Get the rule of a prefix, if it has one
+    pub async fn get(&self, prefix: &Name) -> Result<Option<CsPrefixRule>> {
+        let key = hash_key(prefix)?;
+        match self.map.read().await.get(&key, 0) {
+            Ok(value) => Ok(CsPrefixRule::from_value(value)),
+            Err(MapError::KeyNotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Remove the rule of a prefix, leaving it to shorter prefixes and the
+    /// admission setting
+    pub async fn remove(&self, prefix: &Name) -> Result<()> {
+        let key = hash_key(prefix)?;
+        match self.map.write().await.remove(&key) {
+            Ok(()) | Err(MapError::KeyNotFound) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Limit on the Interests the kernel admits for each name prefix arriving
+/// on each interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Interests admitted per second
+    pub interests_per_sec: u32,
+    /// Interests admitted at once after a quiet period
+    pub burst: u32,
+    /// Components of the prefixes limited separately, at least 1
+    pub prefix_len: u8,
+    /// Whether Interests over the limit are dropped, rather than passed up
+    /// to userspace without entering the kernel PIT
+    pub drop: bool,
+}
+
+impl RateLimit {
+    /// Limit each first-component prefix to `interests_per_sec`, with bursts
+    /// of up to one second's worth
+    pub fn new(interests_per_sec: u32) -> Self {
+        Self {
+            interests_per_sec,
+            burst: interests_per_sec,
+            prefix_len: 1,
+            drop: true,
+        }
+    }
+}
+
+/// Settings of the XDP program that can change while it runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KernelConfig {
+    /// UDP port NDN packets are sent to
+    pub ndn_port: u16,
+    /// Lifetime of kernel PIT entries, in milliseconds
+    pub pit_lifetime_ms: u32,
+    /// Freshness of Data cached in the kernel CS, in milliseconds
+    pub cs_ttl_ms: u32,
+    /// Which Data the kernel CS records
+    pub cs_admission: CsAdmission,
+    /// Smallest Data, in bytes, the kernel CS records
+    pub cs_min_size: u32,
+    /// Largest Data, in bytes, the kernel CS records, unlimited if None
+    pub cs_max_size: Option<u32>,
+    /// Whether the kernel answers Interests from its CS
+    pub cs_replies: bool,
+    /// Whether the kernel redirects Interests out of egress faces
+    pub redirects: bool,
+    /// Limit on the Interests entering the kernel PIT, unlimited if None
+    pub rate_limit: Option<RateLimit>,
+    /// Whether the packets the kernel passes up go to the AF_XDP socket of
+    /// their receive queue, where one is bound
+    pub af_xdp: bool,
+}
+
+impl Default for KernelConfig {
+    fn default() -> Self {
+        Self {
+            ndn_port: crate::NDN_PORT,
+            pit_lifetime_ms: 4000,
+            cs_ttl_ms: 10000,
+            cs_admission: CsAdmission::All,
+            cs_min_size: 0,
+            cs_max_size: None,
+            cs_replies: true,
+            redirects: true,
+            rate_limit: None,
+            af_xdp: false,
+        }
+    }
+}
+
+impl KernelConfig {
+    /// Encode as the values of the config map
+    fn to_values(self) -> [u32; CONFIG_COUNT as usize] {
+        let mut flags = 0;
+        if !self.cs_replies {
+            flags |= FLAG_NO_CS_REPLY;
+        }
+        if !self.redirects {
+            flags |= FLAG_NO_REDIRECT;
+        }
+        if self.af_xdp {
+            flags |= FLAG_AF_XDP;
+        }
+
+        let mut values = [0; CONFIG_COUNT as usize];
+        values[CONFIG_NDN_PORT as usize] = self.ndn_port as u32;
+        values[CONFIG_PIT_LIFETIME_MS as usize] = self.pit_lifetime_ms;
+        values[CONFIG_CS_TTL_MS as usize] = self.cs_ttl_ms;
+        values[CONFIG_CS_ADMISSION as usize] = match self.cs_admission {
+            CsAdmission::All => CS_ADMIT_ALL,
+            CsAdmission::None => CS_ADMIT_NONE,
+            CsAdmission::Listed => CS_ADMIT_LISTED,
+        };
+        values[CONFIG_CS_MIN_SIZE as usize] = self.cs_min_size;
+        values[CONFIG_CS_MAX_SIZE as usize] = self.cs_max_size.unwrap_or(0);
+        if let Some(limit) = self.rate_limit {
+            values[CONFIG_RATE_LIMIT_PPS as usize] = limit.interests_per_sec;
+            values[CONFIG_RATE_LIMIT_BURST as usize] = limit.burst;
+            values[CONFIG_RATE_LIMIT_PREFIX_LEN as usize] = limit.prefix_len as u32;
+            if !limit.drop {
+                flags |= FLAG_RATE_LIMIT_PASS;
+            }
+        }
+        values[CONFIG_FLAGS as usize] = flags;
+        values
+    }
+
+    /// Decode the values of the config map, where 0 stands for the default
+    fn from_values(values: &[u32; CONFIG_COUNT as usize]) -> Self {
+        let defaults = Self::default();
+        let or_default = |index: u32, default: u32| match values[index as usize] {
+            0 => default,
+            value => value,
+        };
+        let flags = values[CONFIG_FLAGS as usize];
+
+        Self {
+            ndn_port: or_default(CONFIG_NDN_PORT, defaults.ndn_port as u32) as u16,
+            pit_lifetime_ms: or_default(CONFIG_PIT_LIFETIME_MS, defaults.pit_lifetime_ms),
+            cs_ttl_ms: or_default(CONFIG_CS_TTL_MS, defaults.cs_ttl_ms),
+            cs_admission: match values[CONFIG_CS_ADMISSION as usize] {
+                CS_ADMIT_NONE => CsAdmission::None,
+                CS_ADMIT_LISTED => CsAdmission::Listed,
+                _ => CsAdmission::All,
+            },
+            cs_min_size: values[CONFIG_CS_MIN_SIZE as usize],
+            cs_max_size: match values[CONFIG_CS_MAX_SIZE as usize] {
+                0 => None,
+                max => Some(max),
+            },
+            cs_replies: flags & FLAG_NO_CS_REPLY == 0,
+            redirects: flags & FLAG_NO_REDIRECT == 0,
+            rate_limit: match values[CONFIG_RATE_LIMIT_PPS as usize] {
+                0 => None,
+                interests_per_sec => Some(RateLimit {
+                    interests_per_sec,
+                    burst: or_default(CONFIG_RATE_LIMIT_BURST, interests_per_sec),
+                    prefix_len: or_default(CONFIG_RATE_LIMIT_PREFIX_LEN, 1) as u8,
+                    drop: flags & FLAG_RATE_LIMIT_PASS == 0,
+                }),
+            },
+            af_xdp: flags & FLAG_AF_XDP != 0,
+        }
+    }
+}
+
+/// Wrapper for the runtime settings of the XDP program
+pub struct Config {
+    /// The underlying eBPF array, one value per setting
+    map: Arc<RwLock<Array<MapData, u32>>>,
+}
+
+impl Config {
+    /// Create a new config wrapper from a BPF object
+    pub fn new(bpf: &mut Bpf) -> Result<Self> {
+        let map = bpf.map_mut(CONFIG_MAP_NAME)
+            .ok_or(XdpError::MapNotFound(CONFIG_MAP_NAME))?;
+
+        let map = map.try_into()?;
+
+        Ok(Self {
+            map: Arc::new(RwLock::new(map)),
+        })
+    }
+
+    /// Get the settings the XDP program runs with
+    pub async fn get(&self) -> Result<KernelConfig> {
+        let map = self.map.read().await;
+        let mut values = [0; CONFIG_COUNT as usize];
+        for (index, value) in values.iter_mut().enumerate() {
+            *value = map.get(&(index as u32), 0)?;
+        }
+        Ok(KernelConfig::from_values(&values))
+    }
+
+    /// Change the settings of the running XDP program
+    pub async fn set(&self, config: &KernelConfig) -> Result<()> {
+        if config.ndn_port == 0 {
+            return Err(XdpError::Config("NDN port must not be 0".to_string()));
+        }
+        if let Some(limit) = config.rate_limit {
+            if limit.interests_per_sec == 0 || limit.prefix_len == 0 {
+                return Err(XdpError::Config("Rate limit and its prefix length must not be 0".to_string()));
+            }
+        }
+
+        let mut map = self.map.write().await;
+        for (index, value) in config.to_values().into_iter().enumerate() {
+            map.set(index as u32, value, 0)?;
+        }
+
+        info!("Kernel config updated: {:?}", config);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FIB entries in memory, where setting `broken` fails
+    #[derive(Default)]
+    struct MemoryFib {
+        entries: std::collections::HashMap<Name, FibValue>,
+        broken: Option<Name>,
+    }
+
+    impl FibEntries for MemoryFib {
+        type Key = Name;
+
+        fn key(prefix: &Name) -> Result<Name> {
+            Ok(prefix.clone())
+        }
+
+        fn entries(&self, keys: &[Name]) -> Result<Vec<Option<FibValue>>> {
+            Ok(keys.iter().map(|key| self.entries.get(key).copied()).collect())
+        }
+
+        fn set(&mut self, key: &Name, value: Option<FibValue>) -> Result<()> {
+            if self.broken.as_ref() == Some(key) {
+                return Err(XdpError::Config("broken".to_string()));
+            }
+            match value {
+                Some(value) => self.entries.insert(key.clone(), value),
+                None => self.entries.remove(key),
+            };
+            Ok(())
+        }
+    }
+
+    fn name(s: &str) -> Name {
+        Name::from_string(s).unwrap()
+    }
+
+    fn set(prefix: &str, face: u16) -> FibUpdate {
+        FibUpdate::Set {
+            prefix: name(prefix),
+            face_id: FaceId(face),
+            cost: 10,
+        }
+    }
+
+    fn face(fib: &MemoryFib, prefix: &str) -> Option<FaceId> {
+        fib.entries.get(&name(prefix)).map(|value| value.face_id)
+    }
+
+    #[test]
+    fn test_batch_rollback() {
+        let mut fib = MemoryFib::default();
+        apply_updates(&mut fib, &[set("/a", 1), set("/b", 1)], true).unwrap();
+
+        fib.broken = Some(name("/c"));
+        let updates = [
+            set("/a", 2),
+            set("/a", 3),
+            FibUpdate::Remove { prefix: name("/b") },
+            set("/c", 2),
+        ];
+        assert!(apply_updates(&mut fib, &updates, true).is_err());
+        assert_eq!(face(&fib, "/a"), Some(FaceId(1)));
+        assert_eq!(face(&fib, "/b"), Some(FaceId(1)));
+        assert_eq!(face(&fib, "/c"), None);
+
+        // Without rollback, everything but the broken prefix goes in
+        let applied = apply_updates(&mut fib, &updates, false).unwrap();
+        assert_eq!(applied, vec![true, true, true, false]);
+        assert_eq!(face(&fib, "/a"), Some(FaceId(3)));
+        assert_eq!(face(&fib, "/b"), None);
+    }
+
+    #[test]
+    fn test_config_values() {
+        let config = KernelConfig {
+            cs_admission: CsAdmission::Listed,
+            cs_min_size: 64,
+            cs_max_size: Some(900),
+            rate_limit: Some(RateLimit::new(100)),
+            ..Default::default()
+        };
+        assert_eq!(KernelConfig::from_values(&config.to_values()), config);
+
+        // Zero leaves every setting at its default
+        let unset = [0; CONFIG_COUNT as usize];
+        assert_eq!(KernelConfig::from_values(&unset), KernelConfig::default());
+    }
+}
@@ -0,0 +1,214 @@
+//! Names behind the hashes in the kernel maps.
+//!
+//! The PIT and FIB maps are keyed by name hashes, so userspace cannot tell
+//! which names they hold. `NameTable` shadows them: it records every
+//! prefix routed into the FIB, and every Interest name the XDP program
+//! reports through the NAME_EVENTS ring buffer when it adds a PIT entry.
+
+use aya::{
+    maps::{MapData, RingBuf},
+    Bpf,
+};
+use bytes::Bytes;
+use log::{debug, warn};
+use rust_udcn_common::{ndn::Name, tlv::{self, TlvElement}, types::NameEvent};
+use std::{collections::HashMap, sync::Arc};
+use tokio::{io::unix::AsyncFd, sync::RwLock, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    error::{Result, XdpError},
+    maps::name_hash,
+};
+
+const NAME_EVENTS_NAME: &str = "NAME_EVENTS";
+
+/// Interest names kept by default before the oldest are forgotten
+pub const DEFAULT_OBSERVED_NAMES: usize = 65536;
+
+/// Key of a name in the kernel maps: its hash and number of components
+type NameKey = (u32, u8);
+
+fn key_of(name: &Name) -> NameKey {
+    (name_hash(name), name.len().min(u8::MAX as usize) as u8)
+}
+
+/// Shadow table mapping the name hashes of the kernel maps to names
+pub struct NameTable {
+    /// Prefixes of the FIB routes, kept until the route is removed
+    prefixes: RwLock<HashMap<NameKey, Name>>,
+
+    /// Names of the Interests seen by the kernel
+    observed: RwLock<HashMap<NameKey, Name>>,
+
+    /// Most Interest names kept at once
+    capacity: usize,
+}
+
+impl Default for NameTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_OBSERVED_NAMES)
+    }
+}
+
+impl NameTable {
+    /// Create an empty table keeping up to `capacity` Interest names
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            prefixes: RwLock::new(HashMap::new()),
+            observed: RwLock::new(HashMap::new()),
+            capacity,
+        }
+    }
+
+    /// Record the prefix of a route
+    pub async fn add_prefix(&self, prefix: &Name) {
+        self.prefixes.write().await.insert(key_of(prefix), prefix.clone());
+    }
+
+    /// Forget the prefix of a removed route
+    pub async fn remove_prefix(&self, prefix: &Name) {
+        self.prefixes.write().await.remove(&key_of(prefix));
+    }
+
+    /// Record the name of an Interest
+    ///
+    /// Interest names come and go with the PIT, so once `capacity` are
+    /// kept they are all forgotten rather than tracking which are stale.
+    pub async fn observe(&self, name: Name) {
+        let mut observed = self.observed.write().await;
+        if observed.len() >= self.capacity {
+            debug!("Forgetting {} observed Interest names", observed.len());
+            observed.clear();
+        }
+        observed.insert(key_of(&name), name);
+    }
+
+    /// The name with `name_hash` and `name_len` components, if known
+    pub async fn get(&self, name_hash: u32, name_len: u8) -> Option<Name> {
+        let key = (name_hash, name_len);
+        if let Some(name) = self.prefixes.read().await.get(&key) {
+            return Some(name.clone());
+        }
+        self.observed.read().await.get(&key).cloned()
+    }
+
+    /// Record the Interest names the XDP program reports, until the ring
+    /// buffer fails or `cancellation` is cancelled
+    pub fn spawn_reader(self: Arc<Self>, bpf: &mut Bpf, cancellation: CancellationToken) -> Result<JoinHandle<()>> {
+        let map = bpf
+            .take_map(NAME_EVENTS_NAME)
+            .ok_or(XdpError::MapNotFound(NAME_EVENTS_NAME))?;
+        let ring: RingBuf<MapData> = RingBuf::try_from(map)?;
+        let mut fd = AsyncFd::new(ring)?;
+
+        Ok(tokio::spawn(async move {
+            loop {
+                let readable = tokio::select! {
+                    _ = cancellation.cancelled() => return,
+                    readable = fd.readable_mut() => readable,
+                };
+                let mut ready = match readable {
+                    Ok(ready) => ready,
+                    Err(e) => {
+                        warn!("Failed to read kernel name events: {}", e);
+                        return;
+                    }
+                };
+
+                // Collect the batch first, the ring is borrowed while read
+                let mut names = Vec::new();
+                let ring = ready.get_inner_mut();
+                while let Some(record) = ring.next() {
+                    if let Some(name) = NameEvent::from_bytes(&record).and_then(|event| decode_event(&event)) {
+                        names.push(name);
+                    }
+                }
+                ready.clear_ready();
+
+                for name in names {
+                    self.observe(name).await;
+                }
+            }
+        }))
+    }
+}
+
+/// Decode the name reported in an event
+///
+/// Names too long for the event arrive truncated, which their hash no
+/// longer matches; those are dropped.
+pub(crate) fn decode_event(event: &NameEvent) -> Option<Name> {
+    let name = decode_components(event.components())?;
+    if key_of(&name) != (event.name_hash, event.name_len) {
+        return None;
+    }
+    Some(name)
+}
+
+/// Decode the encoded components of a name, as found in the kernel maps
+pub(crate) fn decode_components(components: &[u8]) -> Option<Name> {
+    let element = TlvElement::new(tlv::TLV_NAME, Bytes::copy_from_slice(components));
+    Name::from_tlv(&element).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use rust_udcn_common::types::MAX_NAME_EVENT_LENGTH;
+
+    fn event(name: &Name) -> NameEvent {
+        let mut encoded = BytesMut::new();
+        for component in name.components() {
+            component.to_tlv().encode(&mut encoded);
+        }
+        let len = encoded.len().min(MAX_NAME_EVENT_LENGTH);
+        let mut components = [0u8; MAX_NAME_EVENT_LENGTH];
+        components[..len].copy_from_slice(&encoded[..len]);
+
+        NameEvent {
+            name_hash: name_hash(name),
+            name_len: name.len() as u8,
+            _pad: 0,
+            components_len: len as u16,
+            components,
+        }
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let name = Name::from_string("/udcn/video/seg=3").unwrap();
+        assert_eq!(decode_event(&event(&name)), Some(name));
+
+        // Truncated by the kernel
+        let long = Name::from_string(&format!("/udcn/{}", "x".repeat(200))).unwrap();
+        assert_eq!(decode_event(&event(&long)), None);
+    }
+
+    #[tokio::test]
+    async fn test_lookup() {
+        let table = NameTable::new(2);
+        let prefix = Name::from_string("/udcn").unwrap();
+        let a = Name::from_string("/udcn/a").unwrap();
+        let b = Name::from_string("/udcn/b").unwrap();
+        let c = Name::from_string("/udcn/c").unwrap();
+
+        table.add_prefix(&prefix).await;
+        table.observe(a.clone()).await;
+        table.observe(b.clone()).await;
+        let (hash, len) = key_of(&a);
+        assert_eq!(table.get(hash, len).await, Some(a.clone()));
+
+        // Full, the older names are forgotten but not the prefixes
+        table.observe(c.clone()).await;
+        assert_eq!(table.get(hash, len).await, None);
+        let (hash, len) = key_of(&c);
+        assert_eq!(table.get(hash, len).await, Some(c));
+        let (hash, len) = key_of(&prefix);
+        assert_eq!(table.get(hash, len).await, Some(prefix.clone()));
+
+        table.remove_prefix(&prefix).await;
+        assert_eq!(table.get(hash, len).await, None);
+    }
+}
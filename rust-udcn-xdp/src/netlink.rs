@@ -0,0 +1,269 @@
+//! Minimal rtnetlink support.
+//!
+//! Just enough of the protocol to follow interface changes, to ask the
+//! kernel which XDP program runs on an interface, in which mode, and to
+//! attach or detach programs the way tools without BPF links do.
+
+use std::{
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use crate::{
+    error::{Result, XdpError},
+    loader::XdpMode,
+    xsk::interface_index,
+};
+
+/// Netlink messages and attributes are aligned to 4 bytes
+const NLMSG_ALIGNTO: usize = 4;
+pub(crate) const NLMSG_HDR_SIZE: usize = 16;
+pub(crate) const IFINFOMSG_SIZE: usize = 16;
+pub(crate) const RTATTR_HDR_SIZE: usize = 4;
+
+/// Size of the buffer netlink messages are read into
+pub(crate) const RECV_BUFFER_SIZE: usize = 32 * 1024;
+
+pub(crate) const IFLA_IFNAME: u16 = 3;
+const IFLA_XDP: u16 = 43;
+const IFLA_XDP_FD: u16 = 1;
+const IFLA_XDP_ATTACHED: u16 = 2;
+const IFLA_XDP_FLAGS: u16 = 3;
+const IFLA_XDP_PROG_ID: u16 = 4;
+const NLA_F_NESTED: u16 = 0x8000;
+const NLA_TYPE_MASK: u16 = 0x3fff;
+
+const XDP_ATTACHED_DRV: u8 = 1;
+const XDP_ATTACHED_SKB: u8 = 2;
+const XDP_ATTACHED_HW: u8 = 3;
+const XDP_ATTACHED_MULTI: u8 = 4;
+
+/// The XDP program attached to an interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkXdp {
+    /// How it runs
+    pub mode: XdpMode,
+    /// Kernel ID of the program, 0 if several are attached
+    pub prog_id: u32,
+}
+
+/// Open an rtnetlink socket, subscribed to `groups`
+pub(crate) fn open_socket(groups: u32, flags: libc::c_int) -> io::Result<OwnedFd> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC | flags,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as u16;
+    addr.nl_groups = groups;
+    let ret = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const _ as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(fd)
+}
+
+/// The XDP program attached to an interface, if any
+pub fn interface_xdp(interface_name: &str) -> Result<Option<LinkXdp>> {
+    link_xdp(interface_index(interface_name)?)
+}
+
+/// The XDP program attached to the interface with index `ifindex`, if any
+fn link_xdp(ifindex: u32) -> Result<Option<LinkXdp>> {
+    let reply = request(libc::RTM_GETLINK, 0, ifindex, &[])
+        .map_err(|e| XdpError::syscall(format!("Failed to query interface {}", ifindex), e))?;
+    for (msg_type, payload) in messages(&reply) {
+        if msg_type == libc::RTM_NEWLINK && payload.len() >= IFINFOMSG_SIZE {
+            return Ok(parse_link_xdp(&payload[IFINFOMSG_SIZE..]));
+        }
+    }
+    Err(XdpError::Netlink(format!("No answer about interface {}", ifindex)))
+}
+
+/// Attach the XDP program `fd` to the interface with index `ifindex`, or
+/// detach the one attached in the mode of `flags` if `fd` is -1
+pub(crate) fn set_link_xdp(ifindex: u32, fd: RawFd, flags: u32) -> io::Result<()> {
+    let mut xdp = rtattr(IFLA_XDP_FD, &fd.to_ne_bytes());
+    if flags != 0 {
+        xdp.extend(rtattr(IFLA_XDP_FLAGS, &flags.to_ne_bytes()));
+    }
+    let attributes = rtattr(IFLA_XDP | NLA_F_NESTED, &xdp);
+
+    request(libc::RTM_SETLINK, libc::NLM_F_ACK as u16, ifindex, &attributes)?;
+    Ok(())
+}
+
+/// Send a link request about the interface with index `ifindex`, and read
+/// the reply
+fn request(msg_type: u16, flags: u16, ifindex: u32, attributes: &[u8]) -> io::Result<Vec<u8>> {
+    let fd = open_socket(0, 0)?;
+
+    let len = NLMSG_HDR_SIZE + IFINFOMSG_SIZE + attributes.len();
+    let mut request = vec![0u8; NLMSG_HDR_SIZE + IFINFOMSG_SIZE];
+    request[0..4].copy_from_slice(&(len as u32).to_ne_bytes());
+    request[4..6].copy_from_slice(&msg_type.to_ne_bytes());
+    request[6..8].copy_from_slice(&(libc::NLM_F_REQUEST as u16 | flags).to_ne_bytes());
+    request[8..12].copy_from_slice(&1u32.to_ne_bytes());
+    request[NLMSG_HDR_SIZE + 4..NLMSG_HDR_SIZE + 8].copy_from_slice(&ifindex.to_ne_bytes());
+    request.extend_from_slice(attributes);
+
+    let ret = unsafe {
+        libc::send(
+            fd.as_raw_fd(),
+            request.as_ptr() as *const libc::c_void,
+            request.len(),
+            0,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut buf = vec![0u8; RECV_BUFFER_SIZE];
+    let len = unsafe {
+        libc::recv(
+            fd.as_raw_fd(),
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+            0,
+        )
+    };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    buf.truncate(len as usize);
+
+    // Errors come back as an error code, acknowledgements as error 0
+    for (msg_type, payload) in messages(&buf) {
+        if msg_type == libc::NLMSG_ERROR as u16 && payload.len() >= 4 {
+            let errno = -(read_u32(payload, 0) as i32);
+            if errno != 0 {
+                return Err(io::Error::from_raw_os_error(errno));
+            }
+        }
+    }
+    Ok(buf)
+}
+
+/// Find the XDP program in the attributes of a link message
+fn parse_link_xdp(attributes: &[u8]) -> Option<LinkXdp> {
+    let (_, xdp) = rtattrs(attributes).find(|(attr_type, _)| *attr_type == IFLA_XDP)?;
+
+    let mut mode = None;
+    let mut prog_id = 0;
+    for (attr_type, value) in rtattrs(xdp) {
+        match attr_type {
+            IFLA_XDP_ATTACHED if !value.is_empty() => {
+                mode = match value[0] {
+                    XDP_ATTACHED_DRV => Some(XdpMode::Driver),
+                    XDP_ATTACHED_SKB => Some(XdpMode::Skb),
+                    XDP_ATTACHED_HW => Some(XdpMode::Offload),
+                    XDP_ATTACHED_MULTI => Some(XdpMode::Multi),
+                    _ => None,
+                }
+            }
+            IFLA_XDP_PROG_ID if value.len() >= 4 => prog_id = read_u32(value, 0),
+            _ => {}
+        }
+    }
+
+    Some(LinkXdp { mode: mode?, prog_id })
+}
+
+/// The type and payload of each message in a netlink datagram
+pub(crate) fn messages(buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        if offset + NLMSG_HDR_SIZE > buf.len() {
+            return None;
+        }
+        let len = read_u32(buf, offset) as usize;
+        let msg_type = read_u16(buf, offset + 4);
+        if len < NLMSG_HDR_SIZE || offset + len > buf.len() {
+            return None;
+        }
+        let payload = &buf[offset + NLMSG_HDR_SIZE..offset + len];
+        offset += align(len);
+        Some((msg_type, payload))
+    })
+}
+
+/// The type and value of each attribute in a message
+pub(crate) fn rtattrs(buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        if offset + RTATTR_HDR_SIZE > buf.len() {
+            return None;
+        }
+        let len = read_u16(buf, offset) as usize;
+        let attr_type = read_u16(buf, offset + 2) & NLA_TYPE_MASK;
+        if len < RTATTR_HDR_SIZE || offset + len > buf.len() {
+            return None;
+        }
+        let value = &buf[offset + RTATTR_HDR_SIZE..offset + len];
+        offset += align(len);
+        Some((attr_type, value))
+    })
+}
+
+/// Encode an attribute
+fn rtattr(attr_type: u16, value: &[u8]) -> Vec<u8> {
+    let mut attr = Vec::new();
+    attr.extend_from_slice(&((RTATTR_HDR_SIZE + value.len()) as u16).to_ne_bytes());
+    attr.extend_from_slice(&attr_type.to_ne_bytes());
+    attr.extend_from_slice(value);
+    attr.resize(align(attr.len()), 0);
+    attr
+}
+
+pub(crate) fn align(len: usize) -> usize {
+    (len + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
+}
+
+pub(crate) fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_ne_bytes([buf[offset], buf[offset + 1]])
+}
+
+pub(crate) fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_link_xdp() {
+        let mut xdp = rtattr(IFLA_XDP_ATTACHED, &[XDP_ATTACHED_SKB]);
+        xdp.extend(rtattr(IFLA_XDP_PROG_ID, &42u32.to_ne_bytes()));
+        let mut attributes = rtattr(IFLA_IFNAME, b"eth0\0");
+        // Nested attributes may carry NLA_F_NESTED
+        attributes.extend(rtattr(IFLA_XDP | NLA_F_NESTED, &xdp));
+
+        assert_eq!(
+            parse_link_xdp(&attributes),
+            Some(LinkXdp {
+                mode: XdpMode::Skb,
+                prog_id: 42,
+            })
+        );
+
+        // Nothing attached
+        let attributes = rtattr(IFLA_XDP, &rtattr(IFLA_XDP_ATTACHED, &[0]));
+        assert_eq!(parse_link_xdp(&attributes), None);
+    }
+}
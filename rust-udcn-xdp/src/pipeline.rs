@@ -0,0 +1,85 @@
+//! Tail call pipeline of the XDP program.
+//!
+//! The entry program `ndn_xdp` hands each packet to a stage program through
+//! the PIPELINE program array. This module loads the stages and lets them
+//! be replaced while the entry program stays attached.
+
+use aya::{
+    maps::{MapData, ProgramArray},
+    programs::{ProgramFd, Xdp},
+    Bpf,
+};
+use log::info;
+
+use crate::error::{Result, XdpError};
+
+const PIPELINE_NAME: &str = "PIPELINE";
+
+/// A stage of the XDP pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineStage {
+    /// Interest processing: PIT, CS and FIB lookups
+    Interest,
+    /// Data processing: PIT matching and CS insertion
+    Data,
+    /// Sending Interests out of egress faces
+    Forward,
+}
+
+impl PipelineStage {
+    /// Every stage, in program array order
+    pub const ALL: [PipelineStage; 3] = [Self::Interest, Self::Data, Self::Forward];
+
+    /// Index of the stage in the program array, matching the eBPF program
+    fn index(self) -> u32 {
+        match self {
+            Self::Interest => 0,
+            Self::Data => 1,
+            Self::Forward => 2,
+        }
+    }
+
+    /// Name of the program built for the stage
+    pub fn program_name(self) -> &'static str {
+        match self {
+            Self::Interest => "ndn_interest",
+            Self::Data => "ndn_data",
+            Self::Forward => "ndn_forward",
+        }
+    }
+}
+
+/// The program array the entry program tail calls stages through
+pub struct Pipeline {
+    /// The stages; the kernel empties the array once this is dropped
+    stages: ProgramArray<MapData>,
+}
+
+impl Pipeline {
+    /// Load the stage programs of a BPF object and install them
+    pub fn load(bpf: &mut Bpf) -> Result<Self> {
+        let stages = bpf.take_map(PIPELINE_NAME)
+            .ok_or(XdpError::MapNotFound(PIPELINE_NAME))?;
+
+        let mut pipeline = Self {
+            stages: ProgramArray::try_from(stages)?,
+        };
+
+        for stage in PipelineStage::ALL {
+            let program: &mut Xdp = bpf.program_mut(stage.program_name())
+                .ok_or_else(|| XdpError::ProgramNotFound(stage.program_name().to_string()))?
+                .try_into()?;
+            program.load().map_err(|e| XdpError::program_load(stage.program_name(), e))?;
+            pipeline.set_stage(stage, program.fd()?)?;
+        }
+
+        Ok(pipeline)
+    }
+
+    /// Run `program` for `stage` from the next packet on
+    pub fn set_stage(&mut self, stage: PipelineStage, program: &ProgramFd) -> Result<()> {
+        self.stages.set(stage.index(), program, 0)?;
+        info!("XDP pipeline stage {:?} installed", stage);
+        Ok(())
+    }
+}
@@ -0,0 +1,438 @@
+//! Routing Information Base (RIB) above the kernel FIB
+//!
+//! Routes are kept per origin (static configuration, client registration,
+//! routing protocol), so one origin withdrawing a route leaves the routes of
+//! the others in place. Inheritance is resolved like NFD's RIB: a route with
+//! CHILD_INHERIT also applies to longer prefixes, and a route with CAPTURE
+//! stops routes of shorter prefixes from being inherited. The kernel FIB
+//! holds a single next hop per prefix, so the cheapest effective next hop of
+//! every prefix is what gets pushed into the eBPF map.
+
+use log::debug;
+use rust_udcn_common::{
+    ndn::Name,
+    types::{FaceId, FibValue},
+};
+use std::{
+    collections::HashMap,
+    ops::BitOr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+use crate::{error::Result, maps::Fib};
+
+/// Who installed a route
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteOrigin {
+    /// A local producer application
+    App,
+    /// A prefix registration by a client
+    Client,
+    /// A routing protocol
+    RoutingProtocol,
+    /// Static configuration
+    Static,
+}
+
+impl RouteOrigin {
+    /// Numeric origin as used by NFD management
+    pub fn code(self) -> u16 {
+        match self {
+            RouteOrigin::App => 0,
+            RouteOrigin::Client => 65,
+            RouteOrigin::RoutingProtocol => 128,
+            RouteOrigin::Static => 255,
+        }
+    }
+}
+
+/// Route inheritance flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RouteFlags(u8);
+
+impl RouteFlags {
+    /// No flags
+    pub const NONE: Self = Self(0);
+    /// The route also applies to longer prefixes
+    pub const CHILD_INHERIT: Self = Self(1);
+    /// Routes of shorter prefixes are not inherited past this prefix
+    pub const CAPTURE: Self = Self(2);
+
+    /// Check if all flags of `other` are set
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for RouteFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A route registered for a prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    /// Next-hop face
+    pub face_id: FaceId,
+    /// Who installed the route
+    pub origin: RouteOrigin,
+    /// Routing cost (lower is preferred)
+    pub cost: u32,
+    /// Inheritance flags
+    pub flags: RouteFlags,
+    /// When the route goes away, if ever
+    pub expires_at: Option<Instant>,
+}
+
+impl Route {
+    /// A permanent route inherited by longer prefixes
+    pub fn new(face_id: FaceId, origin: RouteOrigin, cost: u32) -> Self {
+        Self {
+            face_id,
+            origin,
+            cost,
+            flags: RouteFlags::CHILD_INHERIT,
+            expires_at: None,
+        }
+    }
+
+    /// Set the inheritance flags
+    pub fn with_flags(mut self, flags: RouteFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Expire the route after `lifetime`
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.expires_at = Some(Instant::now() + lifetime);
+        self
+    }
+}
+
+/// A change to push into the kernel FIB
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FibUpdate {
+    /// Set the next hop of a prefix
+    Set {
+        prefix: Name,
+        face_id: FaceId,
+        cost: u8,
+    },
+    /// Remove the entry of a prefix
+    Remove { prefix: Name },
+}
+
+impl FibUpdate {
+    /// The prefix whose entry changes
+    pub fn prefix(&self) -> &Name {
+        match self {
+            FibUpdate::Set { prefix, .. } | FibUpdate::Remove { prefix } => prefix,
+        }
+    }
+
+    /// The entry of the prefix once applied, none if removed
+    pub(crate) fn value(&self) -> Option<FibValue> {
+        match self {
+            FibUpdate::Set { face_id, cost, .. } => Some(FibValue {
+                face_id: *face_id,
+                cost: *cost,
+            }),
+            FibUpdate::Remove { .. } => None,
+        }
+    }
+}
+
+/// Routes by prefix, and the FIB entries derived from them
+#[derive(Debug, Default)]
+pub struct Rib {
+    /// Registered routes
+    entries: HashMap<Name, Vec<Route>>,
+    /// Next hops last pushed to the FIB
+    installed: HashMap<Name, (FaceId, u8)>,
+}
+
+impl Rib {
+    /// Create an empty RIB
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a route, replacing the route of the same face and origin
+    pub fn register(&mut self, prefix: Name, route: Route) {
+        let routes = self.entries.entry(prefix).or_default();
+        routes.retain(|r| !(r.face_id == route.face_id && r.origin == route.origin));
+        routes.push(route);
+    }
+
+    /// Unregister the route of a face and origin. Returns whether it existed.
+    pub fn unregister(&mut self, prefix: &Name, face_id: FaceId, origin: RouteOrigin) -> bool {
+        self.retain_routes(|p, r| !(p == prefix && r.face_id == face_id && r.origin == origin)) > 0
+    }
+
+    /// Unregister all routes of an origin for a prefix. Returns how many
+    /// were removed.
+    pub fn unregister_origin(&mut self, prefix: &Name, origin: RouteOrigin) -> usize {
+        self.retain_routes(|p, r| !(p == prefix && r.origin == origin))
+    }
+
+    /// Drop all routes through a face. Returns how many were removed.
+    pub fn remove_face(&mut self, face_id: FaceId) -> usize {
+        self.retain_routes(|_, r| r.face_id != face_id)
+    }
+
+    /// Drop the routes that expired before `now`. Returns how many were
+    /// removed.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        self.retain_routes(|_, r| r.expires_at.is_none_or(|at| at > now))
+    }
+
+    fn retain_routes(&mut self, mut keep: impl FnMut(&Name, &Route) -> bool) -> usize {
+        let mut removed = 0;
+        self.entries.retain(|prefix, routes| {
+            let before = routes.len();
+            routes.retain(|r| keep(prefix, r));
+            removed += before - routes.len();
+            !routes.is_empty()
+        });
+        removed
+    }
+
+    /// List all registered routes
+    pub fn routes(&self) -> Vec<(Name, Route)> {
+        self.entries
+            .iter()
+            .flat_map(|(prefix, routes)| routes.iter().map(move |r| (prefix.clone(), *r)))
+            .collect()
+    }
+
+    /// Effective next hops of a registered prefix as (face, cost), including
+    /// inherited routes, sorted by increasing cost
+    pub fn next_hops(&self, prefix: &Name) -> Vec<(FaceId, u32)> {
+        let Some(own) = self.entries.get(prefix) else {
+            return Vec::new();
+        };
+
+        let mut hops: Vec<(FaceId, u32)> = Vec::new();
+        let mut add = |hops: &mut Vec<(FaceId, u32)>, route: &Route| {
+            match hops.iter_mut().find(|(face, _)| *face == route.face_id) {
+                Some(hop) => hop.1 = hop.1.min(route.cost),
+                None => hops.push((route.face_id, route.cost)),
+            }
+        };
+        for route in own {
+            add(&mut hops, route);
+        }
+
+        let captured = |routes: &[Route]| routes.iter().any(|r| r.flags.contains(RouteFlags::CAPTURE));
+        if !captured(own) {
+            for len in (0..prefix.len()).rev() {
+                let Some(routes) = self.entries.get(&prefix.prefix(len)) else {
+                    continue;
+                };
+                let own_faces: Vec<FaceId> = hops.iter().map(|(face, _)| *face).collect();
+                // Routes of the prefix itself take precedence over inherited ones
+                for route in routes.iter().filter(|r| r.flags.contains(RouteFlags::CHILD_INHERIT)) {
+                    if !own_faces.contains(&route.face_id) {
+                        add(&mut hops, route);
+                    }
+                }
+                if captured(routes) {
+                    break;
+                }
+            }
+        }
+
+        hops.sort_by_key(|(face, cost)| (*cost, face.0));
+        hops
+    }
+
+    /// Compute the FIB changes since the last call and record them as
+    /// installed
+    pub fn fib_updates(&mut self) -> Vec<FibUpdate> {
+        let desired: HashMap<Name, (FaceId, u8)> = self
+            .entries
+            .keys()
+            .filter_map(|prefix| {
+                let (face_id, cost) = *self.next_hops(prefix).first()?;
+                Some((prefix.clone(), (face_id, cost.min(u8::MAX as u32) as u8)))
+            })
+            .collect();
+
+        let mut updates = Vec::new();
+        for prefix in self.installed.keys() {
+            if !desired.contains_key(prefix) {
+                updates.push(FibUpdate::Remove { prefix: prefix.clone() });
+            }
+        }
+        for (prefix, hop) in &desired {
+            if self.installed.get(prefix) != Some(hop) {
+                updates.push(FibUpdate::Set {
+                    prefix: prefix.clone(),
+                    face_id: hop.0,
+                    cost: hop.1,
+                });
+            }
+        }
+
+        self.installed = desired;
+        updates
+    }
+}
+
+/// RIB whose effective routes are kept installed in the kernel FIB
+pub struct RibManager {
+    /// The routes
+    rib: Mutex<Rib>,
+    /// The kernel FIB
+    fib: Arc<Fib>,
+}
+
+impl RibManager {
+    /// Manage the routes of `fib`
+    pub fn new(fib: Arc<Fib>) -> Self {
+        Self {
+            rib: Mutex::new(Rib::new()),
+            fib,
+        }
+    }
+
+    /// Register a route and update the FIB
+    pub async fn register(&self, prefix: Name, route: Route) -> Result<()> {
+        let mut rib = self.rib.lock().await;
+        debug!("Registering {} via face {} ({:?})", prefix, route.face_id.0, route.origin);
+        rib.register(prefix, route);
+        self.push(&mut rib).await
+    }
+
+    /// Unregister the route of a face and origin and update the FIB.
+    /// Returns whether the route existed.
+    pub async fn unregister(&self, prefix: &Name, face_id: FaceId, origin: RouteOrigin) -> Result<bool> {
+        let mut rib = self.rib.lock().await;
+        let removed = rib.unregister(prefix, face_id, origin);
+        self.push(&mut rib).await?;
+        Ok(removed)
+    }
+
+    /// Unregister all routes of an origin for a prefix and update the FIB
+    pub async fn unregister_origin(&self, prefix: &Name, origin: RouteOrigin) -> Result<usize> {
+        let mut rib = self.rib.lock().await;
+        let removed = rib.unregister_origin(prefix, origin);
+        self.push(&mut rib).await?;
+        Ok(removed)
+    }
+
+    /// Drop all routes through a face and update the FIB
+    pub async fn remove_face(&self, face_id: FaceId) -> Result<usize> {
+        let mut rib = self.rib.lock().await;
+        let removed = rib.remove_face(face_id);
+        self.push(&mut rib).await?;
+        Ok(removed)
+    }
+
+    /// Drop expired routes and update the FIB
+    pub async fn expire(&self) -> Result<usize> {
+        let mut rib = self.rib.lock().await;
+        let removed = rib.expire(Instant::now());
+        self.push(&mut rib).await?;
+        Ok(removed)
+    }
+
+    /// List all registered routes
+    pub async fn routes(&self) -> Vec<(Name, Route)> {
+        self.rib.lock().await.routes()
+    }
+
+    /// Effective next hops of a registered prefix
+    pub async fn next_hops(&self, prefix: &Name) -> Vec<(FaceId, u32)> {
+        self.rib.lock().await.next_hops(prefix)
+    }
+
+    async fn push(&self, rib: &mut Rib) -> Result<()> {
+        let updates = rib.fib_updates();
+        if updates.is_empty() {
+            return Ok(());
+        }
+        self.fib.apply_batch(updates, false).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(s: &str) -> Name {
+        Name::from_string(s).unwrap()
+    }
+
+    #[test]
+    fn test_origins_are_independent() {
+        let mut rib = Rib::new();
+        rib.register(name("/a"), Route::new(FaceId(1), RouteOrigin::Static, 10));
+        rib.register(name("/a"), Route::new(FaceId(1), RouteOrigin::RoutingProtocol, 5));
+        assert_eq!(rib.next_hops(&name("/a")), vec![(FaceId(1), 5)]);
+
+        assert!(rib.unregister(&name("/a"), FaceId(1), RouteOrigin::RoutingProtocol));
+        assert_eq!(rib.next_hops(&name("/a")), vec![(FaceId(1), 10)]);
+    }
+
+    #[test]
+    fn test_inheritance_and_capture() {
+        let mut rib = Rib::new();
+        rib.register(name("/"), Route::new(FaceId(1), RouteOrigin::Static, 50));
+        rib.register(
+            name("/a"),
+            Route::new(FaceId(2), RouteOrigin::Client, 10).with_flags(RouteFlags::NONE),
+        );
+        rib.register(name("/a/b"), Route::new(FaceId(3), RouteOrigin::Client, 20));
+        rib.register(
+            name("/c"),
+            Route::new(FaceId(4), RouteOrigin::Client, 10).with_flags(RouteFlags::CAPTURE),
+        );
+
+        // The route of /a is not inherited, the default route is
+        assert_eq!(rib.next_hops(&name("/a/b")), vec![(FaceId(3), 20), (FaceId(1), 50)]);
+        assert_eq!(rib.next_hops(&name("/a")), vec![(FaceId(2), 10), (FaceId(1), 50)]);
+        // CAPTURE keeps the default route out
+        assert_eq!(rib.next_hops(&name("/c")), vec![(FaceId(4), 10)]);
+        assert!(rib.next_hops(&name("/unregistered")).is_empty());
+    }
+
+    #[test]
+    fn test_fib_updates() {
+        let mut rib = Rib::new();
+        rib.register(name("/a"), Route::new(FaceId(1), RouteOrigin::Static, 10));
+        assert_eq!(
+            rib.fib_updates(),
+            vec![FibUpdate::Set {
+                prefix: name("/a"),
+                face_id: FaceId(1),
+                cost: 10,
+            }]
+        );
+        assert!(rib.fib_updates().is_empty());
+
+        rib.register(
+            name("/a"),
+            Route::new(FaceId(2), RouteOrigin::Client, 300).with_lifetime(Duration::from_secs(0)),
+        );
+        assert!(rib.fib_updates().is_empty());
+
+        rib.remove_face(FaceId(1));
+        assert_eq!(
+            rib.fib_updates(),
+            vec![FibUpdate::Set {
+                prefix: name("/a"),
+                face_id: FaceId(2),
+                cost: u8::MAX,
+            }]
+        );
+
+        assert_eq!(rib.expire(Instant::now() + Duration::from_millis(1)), 1);
+        assert_eq!(rib.fib_updates(), vec![FibUpdate::Remove { prefix: name("/a") }]);
+    }
+}
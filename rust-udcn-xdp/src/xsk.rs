@@ -0,0 +1,521 @@
+//! AF_XDP sockets for the userspace data plane.
+//!
+//! With `KernelConfig::af_xdp` set, the XDP program hands the NDN packets it
+//! cannot finish itself to the AF_XDP socket bound to their receive queue,
+//! instead of passing them up the network stack. The packets land in a UMEM
+//! area shared with the kernel, without a copy when the driver supports
+//! zero-copy, and a poller decodes the NDN TLV straight out of it.
+
+use aya::{
+    maps::{MapData, XskMap},
+    Bpf,
+};
+use bytes::Buf;
+use log::{debug, info, warn};
+use rust_udcn_common::{
+    ndn::{Data, Interest},
+    tlv,
+};
+use std::{
+    ffi::CString,
+    io, mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    ptr, slice,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use tokio::{io::unix::AsyncFd, sync::mpsc, task::JoinHandle};
+
+use crate::error::{Result, XdpError};
+
+const XSKS_NAME: &str = "XSKS";
+
+/// Descriptors in the completion ring, unused as nothing is transmitted
+const COMPLETION_RING_SIZE: u32 = 64;
+
+const ETH_HDR_SIZE: usize = 14;
+const VLAN_HDR_SIZE: usize = 4;
+const UDP_HDR_SIZE: usize = 8;
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86DD;
+const ETH_P_8021Q: u16 = 0x8100;
+const ETH_P_8021AD: u16 = 0x88A8;
+const IPPROTO_UDP: u8 = 17;
+
+/// How an AF_XDP socket gets packets from the driver
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum XskMode {
+    /// Zero-copy if the driver supports it, copy otherwise
+    #[default]
+    Auto,
+    /// Always copy packets into the UMEM
+    Copy,
+    /// Fail to bind unless the driver supports zero-copy
+    ZeroCopy,
+}
+
+impl XskMode {
+    /// Flags to bind the socket with
+    fn bind_flags(self) -> u16 {
+        match self {
+            Self::Auto => 0,
+            Self::Copy => libc::XDP_COPY,
+            Self::ZeroCopy => libc::XDP_ZEROCOPY,
+        }
+    }
+}
+
+/// Sizes of the UMEM and rings of an AF_XDP socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XskConfig {
+    /// Bytes of each UMEM frame, which holds one packet: 2048 or 4096
+    pub frame_size: u32,
+    /// Frames in the UMEM, a power of two
+    pub frame_count: u32,
+    /// Descriptors in the receive ring, a power of two
+    pub rx_size: u32,
+    /// Whether the driver may, or must, use zero-copy
+    pub mode: XskMode,
+    /// Packets received before yielding to other tasks
+    pub batch_size: usize,
+}
+
+impl Default for XskConfig {
+    fn default() -> Self {
+        Self {
+            frame_size: 2048,
+            frame_count: 4096,
+            rx_size: 2048,
+            mode: XskMode::Auto,
+            batch_size: 64,
+        }
+    }
+}
+
+/// An NDN packet, decoded from an AF_XDP socket
+#[derive(Debug, Clone)]
+pub enum XskContent {
+    Interest(Interest),
+    Data(Data),
+}
+
+/// An NDN packet the XDP program handed to userspace
+#[derive(Debug, Clone)]
+pub struct XskPacket {
+    /// Receive queue the packet arrived on
+    pub queue_id: u32,
+    /// Address and UDP port the packet was sent from
+    pub source: SocketAddr,
+    /// The decoded packet
+    pub content: XskContent,
+}
+
+/// A ring shared with the kernel, mapped from the socket
+struct Ring<T> {
+    map: *mut libc::c_void,
+    map_len: usize,
+    producer: *const AtomicU32,
+    consumer: *const AtomicU32,
+    descs: *mut T,
+    mask: u32,
+}
+
+impl<T> Ring<T> {
+    /// Map the ring of `size` descriptors at page offset `pgoff`
+    fn map(fd: RawFd, offsets: &libc::xdp_ring_offset, size: u32, pgoff: libc::off_t) -> Result<Self> {
+        let map_len = offsets.desc as usize + size as usize * mem::size_of::<T>();
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                pgoff,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(XdpError::syscall("Failed to map AF_XDP ring", io::Error::last_os_error()));
+        }
+
+        let base = map as *mut u8;
+        unsafe {
+            Ok(Self {
+                map,
+                map_len,
+                producer: base.add(offsets.producer as usize) as *const AtomicU32,
+                consumer: base.add(offsets.consumer as usize) as *const AtomicU32,
+                descs: base.add(offsets.desc as usize) as *mut T,
+                mask: size - 1,
+            })
+        }
+    }
+
+    fn producer(&self) -> &AtomicU32 {
+        unsafe { &*self.producer }
+    }
+
+    fn consumer(&self) -> &AtomicU32 {
+        unsafe { &*self.consumer }
+    }
+
+    /// Pointer to the descriptor at ring position `index`
+    fn desc(&self, index: u32) -> *mut T {
+        unsafe { self.descs.add((index & self.mask) as usize) }
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.map, self.map_len);
+        }
+    }
+}
+
+/// Memory the kernel writes received packets into, split in frames
+struct Umem {
+    area: *mut libc::c_void,
+    len: usize,
+}
+
+impl Umem {
+    fn new(len: usize) -> Result<Self> {
+        let area = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if area == libc::MAP_FAILED {
+            return Err(XdpError::syscall("Failed to allocate UMEM", io::Error::last_os_error()));
+        }
+        Ok(Self { area, len })
+    }
+}
+
+impl Drop for Umem {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.area, self.len);
+        }
+    }
+}
+
+/// An AF_XDP socket receiving the packets of one queue of an interface
+pub struct XskSocket {
+    rx: Ring<libc::xdp_desc>,
+    fill: Ring<u64>,
+    _completion: Ring<u64>,
+    fd: AsyncFd<OwnedFd>,
+    umem: Umem,
+    queue_id: u32,
+    config: XskConfig,
+}
+
+// The rings and UMEM are only touched through `&mut self`, or by the
+// kernel, so the socket can move between threads
+unsafe impl Send for XskSocket {}
+
+impl XskSocket {
+    /// Create a socket with its own UMEM and bind it to `queue_id` of
+    /// `interface`; must be called within a Tokio runtime
+    pub fn bind(interface: &str, queue_id: u32, config: &XskConfig) -> Result<Self> {
+        if !config.frame_count.is_power_of_two() || !config.rx_size.is_power_of_two() {
+            return Err(XdpError::Config(
+                "AF_XDP frame count and ring size must be powers of two".to_string(),
+            ));
+        }
+        let ifindex = interface_index(interface)?;
+
+        let fd = unsafe { libc::socket(libc::AF_XDP, libc::SOCK_RAW | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(XdpError::syscall("Failed to create AF_XDP socket", io::Error::last_os_error()));
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let raw = fd.as_raw_fd();
+
+        let umem = Umem::new(config.frame_size as usize * config.frame_count as usize)?;
+        let mut reg: libc::xdp_umem_reg = unsafe { mem::zeroed() };
+        reg.addr = umem.area as u64;
+        reg.len = umem.len as u64;
+        reg.chunk_size = config.frame_size;
+        set_option(raw, libc::XDP_UMEM_REG, &reg).map_err(|e| XdpError::syscall("Failed to register UMEM", e))?;
+
+        // The fill ring has a slot for every frame, so returning frames
+        // never has to wait for the kernel
+        set_option(raw, libc::XDP_UMEM_FILL_RING, &config.frame_count)
+            .map_err(|e| XdpError::syscall("Failed to size fill ring", e))?;
+        set_option(raw, libc::XDP_UMEM_COMPLETION_RING, &COMPLETION_RING_SIZE)
+            .map_err(|e| XdpError::syscall("Failed to size completion ring", e))?;
+        set_option(raw, libc::XDP_RX_RING, &config.rx_size)
+            .map_err(|e| XdpError::syscall("Failed to size receive ring", e))?;
+
+        let mut offsets: libc::xdp_mmap_offsets = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::xdp_mmap_offsets>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                raw,
+                libc::SOL_XDP,
+                libc::XDP_MMAP_OFFSETS,
+                &mut offsets as *mut _ as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret < 0 {
+            return Err(XdpError::syscall("Failed to get AF_XDP ring offsets", io::Error::last_os_error()));
+        }
+
+        let rx = Ring::map(raw, &offsets.rx, config.rx_size, libc::XDP_PGOFF_RX_RING)?;
+        let fill = Ring::map(raw, &offsets.fr, config.frame_count, libc::XDP_UMEM_PGOFF_FILL_RING as libc::off_t)?;
+        let completion = Ring::map(
+            raw,
+            &offsets.cr,
+            COMPLETION_RING_SIZE,
+            libc::XDP_UMEM_PGOFF_COMPLETION_RING as libc::off_t,
+        )?;
+
+        // Hand every frame to the kernel to receive into
+        for frame in 0..config.frame_count {
+            unsafe { *fill.desc(frame) = frame as u64 * config.frame_size as u64 };
+        }
+        fill.producer().store(config.frame_count, Ordering::Release);
+
+        let mut addr: libc::sockaddr_xdp = unsafe { mem::zeroed() };
+        addr.sxdp_family = libc::AF_XDP as u16;
+        addr.sxdp_flags = config.mode.bind_flags();
+        addr.sxdp_ifindex = ifindex;
+        addr.sxdp_queue_id = queue_id;
+        let ret = unsafe {
+            libc::bind(
+                raw,
+                &addr as *const _ as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_xdp>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(XdpError::syscall(
+                format!("Failed to bind AF_XDP socket to {} queue {}", interface, queue_id),
+                io::Error::last_os_error(),
+            ));
+        }
+
+        debug!("AF_XDP socket bound to {} queue {}", interface, queue_id);
+
+        Ok(Self {
+            rx,
+            fill,
+            _completion: completion,
+            fd: AsyncFd::new(fd)?,
+            umem,
+            queue_id,
+            config: *config,
+        })
+    }
+
+    /// Receive queue the socket is bound to
+    pub fn queue_id(&self) -> u32 {
+        self.queue_id
+    }
+
+    /// Hand up to `max` received frames to `handle`, then give the frames
+    /// back to the kernel; returns the number of frames received
+    pub fn receive(&mut self, max: usize, mut handle: impl FnMut(&[u8])) -> usize {
+        let producer = self.rx.producer().load(Ordering::Acquire);
+        let consumer = self.rx.consumer().load(Ordering::Relaxed);
+        let available = producer.wrapping_sub(consumer).min(max as u32);
+        if available == 0 {
+            return 0;
+        }
+
+        let fill_producer = self.fill.producer().load(Ordering::Relaxed);
+        let frame_size = self.config.frame_size as u64;
+        for i in 0..available {
+            let desc = unsafe { *self.rx.desc(consumer.wrapping_add(i)) };
+            let frame = unsafe {
+                slice::from_raw_parts((self.umem.area as *const u8).add(desc.addr as usize), desc.len as usize)
+            };
+            handle(frame);
+            unsafe { *self.fill.desc(fill_producer.wrapping_add(i)) = desc.addr - desc.addr % frame_size };
+        }
+
+        self.rx.consumer().store(consumer.wrapping_add(available), Ordering::Release);
+        self.fill.producer().store(fill_producer.wrapping_add(available), Ordering::Release);
+
+        available as usize
+    }
+
+    /// Decode the packets arriving on the socket and send them to `packets`,
+    /// until the receiver is dropped
+    pub fn spawn_poller(mut self, packets: mpsc::Sender<XskPacket>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let batch_size = self.config.batch_size.max(1);
+            let mut batch = Vec::with_capacity(batch_size);
+            loop {
+                match self.fd.readable().await {
+                    Ok(mut ready) => ready.clear_ready(),
+                    Err(e) => {
+                        warn!("AF_XDP socket on queue {} failed: {}", self.queue_id, e);
+                        return;
+                    }
+                }
+
+                // Drain the ring, as readiness is only signalled on new packets
+                loop {
+                    let queue_id = self.queue_id;
+                    let received = self.receive(batch_size, |frame| {
+                        if let Some(packet) = decode_frame(queue_id, frame) {
+                            batch.push(packet);
+                        }
+                    });
+                    for packet in batch.drain(..) {
+                        if packets.send(packet).await.is_err() {
+                            return;
+                        }
+                    }
+                    if received < batch_size {
+                        break;
+                    }
+                    tokio::task::yield_now().await;
+                }
+            }
+        })
+    }
+}
+
+impl AsRawFd for XskSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// The XSKS map the XDP program redirects packets to AF_XDP sockets through
+pub struct XskSockets {
+    /// The sockets, indexed by receive queue
+    map: XskMap<MapData>,
+}
+
+impl XskSockets {
+    /// Create a new socket map wrapper from a BPF object
+    pub fn new(bpf: &mut Bpf) -> Result<Self> {
+        let map = bpf.take_map(XSKS_NAME)
+            .ok_or(XdpError::MapNotFound(XSKS_NAME))?;
+
+        Ok(Self {
+            map: XskMap::try_from(map)?,
+        })
+    }
+
+    /// Redirect packets from the queue `socket` is bound to into it
+    ///
+    /// The map is indexed by queue alone, so the same queue of two
+    /// interfaces cannot have sockets at once.
+    pub fn register(&mut self, socket: &XskSocket) -> Result<()> {
+        self.map.set(socket.queue_id(), socket.as_raw_fd(), 0)?;
+        info!("AF_XDP socket registered for queue {}", socket.queue_id());
+        Ok(())
+    }
+}
+
+/// Decode the NDN packet in a received Ethernet frame
+pub fn decode_frame(queue_id: u32, frame: &[u8]) -> Option<XskPacket> {
+    let (source, payload) = udp_payload(frame)?;
+
+    // Read the outer TLV in place to find where the packet ends
+    let mut header = payload;
+    let tlv_type = tlv::decode_tlv_type(&mut header).ok()?;
+    let length = tlv::decode_tlv_length(&mut header).ok()?;
+    let end = (payload.len() - header.remaining()).checked_add(length)?;
+    let packet = payload.get(..end)?;
+
+    let content = match tlv_type {
+        tlv::TLV_INTEREST => XskContent::Interest(Interest::decode(packet).ok()?),
+        tlv::TLV_DATA => XskContent::Data(Data::decode(packet).ok()?),
+        _ => return None,
+    };
+
+    Some(XskPacket {
+        queue_id,
+        source,
+        content,
+    })
+}
+
+/// Find the UDP payload of an Ethernet frame and who sent it
+fn udp_payload(frame: &[u8]) -> Option<(SocketAddr, &[u8])> {
+    let mut offset = ETH_HDR_SIZE;
+    let mut eth_type = read_u16(frame, offset - 2)?;
+
+    // The XDP program accepts up to two VLAN tags
+    for _ in 0..2 {
+        if eth_type != ETH_P_8021Q && eth_type != ETH_P_8021AD {
+            break;
+        }
+        eth_type = read_u16(frame, offset + 2)?;
+        offset += VLAN_HDR_SIZE;
+    }
+
+    let (ip, udp_offset) = match eth_type {
+        ETH_P_IPV6 => {
+            let header = frame.get(offset..offset + 40)?;
+            if header[6] != IPPROTO_UDP {
+                return None;
+            }
+            let src: [u8; 16] = header[8..24].try_into().ok()?;
+            (IpAddr::V6(Ipv6Addr::from(src)), offset + 40)
+        }
+        ETH_P_IP => {
+            let header = frame.get(offset..offset + 20)?;
+            let header_len = (header[0] & 0x0f) as usize * 4;
+            if header[9] != IPPROTO_UDP || header_len < 20 {
+                return None;
+            }
+            let src: [u8; 4] = header[12..16].try_into().ok()?;
+            (IpAddr::V4(Ipv4Addr::from(src)), offset + header_len)
+        }
+        _ => return None,
+    };
+
+    let src_port = read_u16(frame, udp_offset)?;
+    let udp_len = read_u16(frame, udp_offset + 4)? as usize;
+    let payload = frame.get(udp_offset + UDP_HDR_SIZE..udp_offset + udp_len.max(UDP_HDR_SIZE))?;
+
+    Some((SocketAddr::new(ip, src_port), payload))
+}
+
+/// Read a big endian u16 at `offset`
+fn read_u16(frame: &[u8], offset: usize) -> Option<u16> {
+    let bytes = frame.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Index of a network interface
+pub(crate) fn interface_index(interface: &str) -> Result<u32> {
+    let name = CString::new(interface).map_err(|_| XdpError::UnknownInterface(interface.to_string()))?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(XdpError::UnknownInterface(interface.to_string())),
+        ifindex => Ok(ifindex),
+    }
+}
+
+/// Set an SOL_XDP socket option
+fn set_option<T>(fd: RawFd, name: libc::c_int, value: &T) -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_XDP,
+            name,
+            value as *const T as *const libc::c_void,
+            mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}